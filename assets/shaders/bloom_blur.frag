@@ -0,0 +1,24 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+
+layout(location = 0) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform BlurUniform {
+    vec2 direction;
+} u_blur;
+layout(set = 0, binding = 1) uniform texture2D u_source;
+layout(set = 0, binding = 3) uniform sampler u_sampler;
+
+// Weights of a gaussian blur over 9 texels
+const float WEIGHTS[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec2 step = u_blur.direction / vec2(textureSize(sampler2D(u_source, u_sampler), 0));
+    vec3 color = texture(sampler2D(u_source, u_sampler), i_uv).rgb * WEIGHTS[0];
+    for (int i = 1; i < 5; i++) {
+        color += texture(sampler2D(u_source, u_sampler), i_uv + step * i).rgb * WEIGHTS[i];
+        color += texture(sampler2D(u_source, u_sampler), i_uv - step * i).rgb * WEIGHTS[i];
+    }
+    o_color = vec4(color, 1.0);
+}
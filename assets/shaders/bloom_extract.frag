@@ -0,0 +1,25 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+
+layout(location = 0) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform PostUniform {
+    float exposure;
+    float bloom_strength;
+    float vignette;
+    float underwater;
+    float time;
+    float bloom_threshold;
+    float gamma;
+} u_post;
+layout(set = 0, binding = 1) uniform texture2D u_source;
+layout(set = 0, binding = 3) uniform sampler u_sampler;
+
+void main() {
+    // Keep the part of the colors above the threshold
+    vec3 color = texture(sampler2D(u_source, u_sampler), i_uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float excess = max(brightness - u_post.bloom_threshold, 0.0) / max(brightness, 0.0001);
+    o_color = vec4(color * excess, 1.0);
+}
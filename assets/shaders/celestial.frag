@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec2 v_Uv;
+layout(location = 1) flat in vec4 v_Color;
+
+layout(location = 0) out vec4 ColorBuffer;
+
+// Radius of the disc, as a fraction of the size of the quad. The rest of the quad is the glow around it.
+const float DISC_RADIUS = 0.4;
+const float EDGE_WIDTH = 0.03;
+const float GLOW = 0.35;
+
+void main() {
+    float r = length(v_Uv);
+    float disc = 1.0 - smoothstep(DISC_RADIUS - EDGE_WIDTH, DISC_RADIUS, r);
+    float glow = GLOW * pow(clamp(1.0 - r, 0.0, 1.0), 3.0);
+    // The alpha of the color fades the disc out below the horizon
+    ColorBuffer = vec4(v_Color.rgb, clamp(disc + glow, 0.0, 1.0) * v_Color.a);
+}
@@ -0,0 +1,17 @@
+#version 450
+
+// The corner of the quad, from -1 to 1 on x and y
+layout(location = 0) in vec3 a_Pos;
+
+layout(set = 0, binding = 0) uniform Temp1 { mat4 u_ViewProj; };
+// The model matrix places the quad in the sky, facing the camera
+layout(set = 0, binding = 1) uniform Temp2 { mat4 u_Model; vec4 u_Color; };
+
+layout(location = 0) out vec2 v_Uv;
+layout(location = 1) flat out vec4 v_Color;
+
+void main() {
+    v_Uv = a_Pos.xy;
+    v_Color = u_Color;
+    gl_Position = u_ViewProj * u_Model * vec4(a_Pos, 1.0);
+}
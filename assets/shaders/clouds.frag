@@ -0,0 +1,63 @@
+#version 450
+
+layout(location = 0) in vec3 v_WorldPos;
+layout(location = 1) flat in float v_Density;
+
+layout(location = 0) out vec4 ColorBuffer;
+
+layout(set = 0, binding = 2) uniform Sky {
+    // The direction of the sun, and the intensity of the sky light in w
+    vec4 u_Sky;
+    vec4 u_SkyColor;
+    // The color of the fog, and the distance at which it hides everything in w
+    vec4 u_Fog;
+    vec4 u_WaterColor;
+    vec4 u_CameraPos;
+    // The animation time in seconds in x
+    vec4 u_Time;
+};
+
+// Size of the cells of the noise, in blocks
+const float CELL_SIZE = 48.0;
+// Speed of the clouds along x, in blocks per second
+const float WIND_SPEED = 2.0;
+// The noise repeats after this many cells, which is the distance that the clouds move during the period of the
+// animation time. This way the clouds don't jump when the animation time wraps around.
+const float NOISE_PERIOD = 150.0;
+// Distance from the camera at which the clouds fade out, in blocks. It is the size of the quad.
+const float CLOUD_RADIUS = 1000.0;
+const float MAX_ALPHA = 0.85;
+
+float hash(vec2 cell, float period) {
+    cell = mod(cell, period);
+    return fract(sin(dot(cell, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+float noise(vec2 p, float period) {
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(hash(i, period), hash(i + vec2(1.0, 0.0), period), u.x),
+        mix(hash(i + vec2(0.0, 1.0), period), hash(i + vec2(1.0, 1.0), period), u.x),
+        u.y
+    );
+}
+
+// Between 0 and 1, with 3 octaves of noise
+float cloud_noise(vec2 p) {
+    return (4.0 * noise(p, NOISE_PERIOD) + 2.0 * noise(2.0 * p, 2.0 * NOISE_PERIOD) + noise(4.0 * p, 4.0 * NOISE_PERIOD)) / 7.0;
+}
+
+void main() {
+    vec2 p = (v_WorldPos.xz + vec2(u_Time.x * WIND_SPEED, 0.0)) / CELL_SIZE;
+    float n = cloud_noise(p);
+    // The denser the clouds, the lower the noise that still makes a cloud
+    float coverage = smoothstep(1.0 - v_Density, 1.0 - v_Density + 0.15, n);
+
+    float dist = distance(v_WorldPos.xz, u_CameraPos.xz);
+    float fade = 1.0 - smoothstep(0.6 * CLOUD_RADIUS, CLOUD_RADIUS, dist);
+
+    vec3 color = mix(vec3(1.0), u_Fog.rgb, 0.2) * u_Sky.w;
+    ColorBuffer = vec4(color, coverage * fade * MAX_ALPHA);
+}
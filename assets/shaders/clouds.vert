@@ -0,0 +1,18 @@
+#version 450
+
+// The corner of the quad, from -1 to 1 on x and y
+layout(location = 0) in vec3 a_Pos;
+
+layout(set = 0, binding = 0) uniform Temp1 { mat4 u_ViewProj; };
+// The model matrix lays the quad flat above the camera, and the density of the clouds is in x
+layout(set = 0, binding = 1) uniform Temp2 { mat4 u_Model; vec4 u_Clouds; };
+
+layout(location = 0) out vec3 v_WorldPos;
+layout(location = 1) flat out float v_Density;
+
+void main() {
+    vec4 world_pos = u_Model * vec4(a_Pos, 1.0);
+    v_WorldPos = world_pos.xyz;
+    v_Density = u_Clouds.x;
+    gl_Position = u_ViewProj * world_pos;
+}
@@ -0,0 +1,60 @@
+#version 450
+
+layout(location = 0) in vec3 v_Pos;
+layout(location = 1) flat in float v_Progress;
+
+layout(location = 0) out vec4 ColorBuffer;
+
+// The cracks are drawn on a grid of pixels, like the textures of the blocks
+const float PIXELS = 16.0;
+// Number of pieces that the cracks split the faces into, along each side
+const float PIECES = 3.0;
+const float CRACK_WIDTH = 0.05;
+const float CRACK_ALPHA = 0.6;
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+void main() {
+    // The largest coordinate gives the face of the cube, and the two others the position on the face
+    vec3 p = v_Pos / max(abs(v_Pos.x), max(abs(v_Pos.y), abs(v_Pos.z)));
+    vec3 a = abs(p);
+    vec2 uv;
+    float face;
+    if (a.x >= a.y && a.x >= a.z) {
+        uv = p.zy;
+        face = 1.0 + sign(p.x);
+    } else if (a.y >= a.z) {
+        uv = p.xz;
+        face = 4.0 + sign(p.y);
+    } else {
+        uv = p.xy;
+        face = 7.0 + sign(p.z);
+    }
+    uv = (floor((uv * 0.5 + 0.5) * PIXELS) + 0.5) / PIXELS;
+
+    // The cracks are the borders between the pieces of a Voronoi pattern, which is different on every face
+    vec2 piece = floor(uv * PIECES);
+    float closest = 8.0;
+    float second = 8.0;
+    for (int i = -1; i <= 1; i++) {
+        for (int j = -1; j <= 1; j++) {
+            vec2 neighbor = piece + vec2(i, j);
+            vec2 seed = neighbor + 13.0 * face;
+            vec2 center = (neighbor + vec2(hash(seed), hash(seed + 0.5))) / PIECES;
+            float d = distance(uv, center);
+            if (d < closest) {
+                second = closest;
+                closest = d;
+            } else if (d < second) {
+                second = d;
+            }
+        }
+    }
+    // The cracks spread from the center of the faces
+    if (second - closest > CRACK_WIDTH || distance(uv, vec2(0.5)) > v_Progress * 0.75) {
+        discard;
+    }
+    ColorBuffer = vec4(0.0, 0.0, 0.0, CRACK_ALPHA);
+}
@@ -0,0 +1,17 @@
+#version 450
+
+// The corner of the skybox cube, which is scaled down to the broken block
+layout(location = 0) in vec3 a_Pos;
+
+layout(set = 0, binding = 0) uniform Temp1 { mat4 u_ViewProj; };
+// The model matrix places the cube around the block, and the progress of the cracks goes from 0 to 1
+layout(set = 0, binding = 1) uniform Temp2 { mat4 u_Model; vec4 u_Progress; };
+
+layout(location = 0) out vec3 v_Pos;
+layout(location = 1) flat out float v_Progress;
+
+void main() {
+    v_Pos = a_Pos;
+    v_Progress = u_Progress.x;
+    gl_Position = u_ViewProj * u_Model * vec4(a_Pos, 1.0);
+}
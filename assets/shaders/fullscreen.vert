@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) out vec2 o_uv;
+
+void main() {
+    // A triangle that covers the whole screen
+    vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    o_uv = uv;
+    gl_Position = vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec2 i_texture_uv;
+layout(location = 1) flat in uint i_texture_page;
+
+layout(set = 0, binding = 1) uniform sampler u_sampler;
+layout(set = 0, binding = 2) uniform texture2DArray u_texture_atlas;
+
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    // The full size texture is sampled, since the mipmaps blend the small textures with their neighbours
+    vec4 color = textureLod(sampler2DArray(u_texture_atlas, u_sampler), vec3(i_texture_uv, i_texture_page), 0.0);
+    // The transparent pixels are discarded so that they don't hide what is drawn behind them later
+    if (color.a < 0.5) {
+        discard;
+    }
+    o_color = color;
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform Transform {
+    mat4 u_transform;
+};
+
+layout(location = 0) in vec3 i_position;
+layout(location = 1) in vec2 i_texture_uv;
+layout(location = 2) in uint i_texture_page;
+
+layout(location = 0) out vec2 o_texture_uv;
+layout(location = 1) flat out uint o_texture_page;
+
+void main() {
+    gl_Position = u_transform * vec4(i_position, 1.0);
+
+    o_texture_uv = i_texture_uv;
+    o_texture_page = i_texture_page;
+}
@@ -4,7 +4,8 @@ layout(location = 0) in vec3 a_Pos;
 layout(location = 1) in uint a_Info;
 
 layout(set = 0, binding = 0) uniform Temp1 { mat4 u_ViewProj; };
-layout(set = 0, binding = 1) uniform Temp2 { mat4 u_Model; };
+// The brightness of the model is sampled from the light around it
+layout(set = 0, binding = 1) uniform Temp2 { mat4 u_Model; float u_Brightness; };
 
 layout(location = 0) out vec3 v_Norm;
 layout(location = 1) out float occl;
@@ -47,6 +48,8 @@ void main() {
         occl = 0.5;
     }
 
+    occl *= u_Brightness;
+
     v_Norm = get_normal(normal);
 
     float rr = float(r)/255.0;
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec3 i_position;
+
+layout(set = 0, binding = 0) uniform Transform {
+    // The view-projection matrix of the shadow cascade, from the sun
+    mat4 u_cascade_view_proj;
+};
+
+void main() {
+    gl_Position = u_cascade_view_proj * vec4(i_position, 1.0);
+}
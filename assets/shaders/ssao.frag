@@ -0,0 +1,83 @@
+#version 450
+
+layout(location = 0) out vec4 o_occlusion;
+
+layout(set = 0, binding = 0) uniform Ssao {
+    // The projection matrix of the camera, and its inverse
+    mat4 u_proj;
+    mat4 u_inv_proj;
+    // The number of samples, their radius in blocks and the strength of the occlusion
+    vec4 u_params;
+};
+#ifdef MULTISAMPLED
+layout(set = 0, binding = 1) uniform texture2DMS u_depth;
+// The depth of the first sample of a pixel
+#define DEPTH(pixel) texelFetch(sampler2DMS(u_depth, u_sampler), pixel, 0).r
+#define DEPTH_SIZE textureSize(sampler2DMS(u_depth, u_sampler))
+#else
+layout(set = 0, binding = 1) uniform texture2D u_depth;
+#define DEPTH(pixel) texelFetch(sampler2D(u_depth, u_sampler), pixel, 0).r
+#define DEPTH_SIZE textureSize(sampler2D(u_depth, u_sampler), 0)
+#endif
+layout(set = 0, binding = 2) uniform sampler u_sampler;
+
+// How far a surface must be in front of a sample to hide it, in blocks
+const float BIAS = 0.03;
+const float GOLDEN_ANGLE = 2.39996323;
+const float PI = 3.14159265;
+
+// The position of what a pixel shows, relative to the camera
+vec3 view_position(ivec2 pixel) {
+    float depth = DEPTH(pixel);
+    vec2 uv = (vec2(pixel) + 0.5) / vec2(DEPTH_SIZE);
+    vec4 position = u_inv_proj * vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+void main() {
+    ivec2 size = DEPTH_SIZE;
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    // Nothing occludes the sky
+    if (DEPTH(pixel) >= 1.0) {
+        o_occlusion = vec4(1.0);
+        return;
+    }
+    vec3 position = view_position(pixel);
+
+    // The normal from the closest neighbours, which are the most likely to be on the same face
+    vec3 right = view_position(min(pixel + ivec2(1, 0), size - 1)) - position;
+    vec3 left = position - view_position(max(pixel - ivec2(1, 0), ivec2(0)));
+    vec3 down = view_position(min(pixel + ivec2(0, 1), size - 1)) - position;
+    vec3 up = position - view_position(max(pixel - ivec2(0, 1), ivec2(0)));
+    vec3 dx = abs(right.z) < abs(left.z) ? right : left;
+    vec3 dy = abs(down.z) < abs(up.z) ? down : up;
+    vec3 normal = normalize(cross(dy, dx));
+    vec3 tangent = normalize(cross(normal, abs(normal.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0)));
+    vec3 bitangent = cross(normal, tangent);
+
+    // The samples turn with a pattern that repeats every 4 pixels, which the composition blurs away
+    float noise = float((pixel.x % 4) * 4 + pixel.y % 4) / 16.0;
+    int samples = int(u_params.x);
+    float radius = u_params.y;
+    float occlusion = 0.0;
+    for (int i = 0; i < samples; i++) {
+        // A spiral over the hemisphere around the normal, with more samples close to the center
+        float t = (float(i) + 0.5) / float(samples);
+        float angle = float(i) * GOLDEN_ANGLE + noise * 2.0 * PI;
+        vec3 direction = vec3(cos(angle) * sqrt(t), sin(angle) * sqrt(t), sqrt(1.0 - t));
+        float scale = mix(0.1, 1.0, t * t);
+        vec3 sample_position = position
+            + (tangent * direction.x + bitangent * direction.y + normal * direction.z) * radius * scale;
+
+        vec4 clip = u_proj * vec4(sample_position, 1.0);
+        vec2 uv = vec2(clip.x / clip.w * 0.5 + 0.5, 0.5 - clip.y / clip.w * 0.5);
+        if (any(lessThan(uv, vec2(0.0))) || any(greaterThanEqual(uv, vec2(1.0)))) {
+            continue;
+        }
+        vec3 surface = view_position(ivec2(uv * vec2(size)));
+        // The surfaces far in front of the sample don't occlude it
+        float range = smoothstep(0.0, 1.0, radius / abs(position.z - surface.z));
+        occlusion += (surface.z >= sample_position.z + BIAS ? 1.0 : 0.0) * range;
+    }
+    o_occlusion = vec4(1.0 - u_params.z * occlusion / float(samples));
+}
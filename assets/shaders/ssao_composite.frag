@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform texture2D u_occlusion;
+layout(set = 0, binding = 1) uniform sampler u_sampler;
+
+void main() {
+    // Average the 4x4 pixels over which the samples of the occlusion turn
+    ivec2 size = textureSize(sampler2D(u_occlusion, u_sampler), 0);
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    float occlusion = 0.0;
+    for (int x = -2; x < 2; x++) {
+        for (int y = -2; y < 2; y++) {
+            ivec2 neighbour = clamp(pixel + ivec2(x, y), ivec2(0), size - 1);
+            occlusion += texelFetch(sampler2D(u_occlusion, u_sampler), neighbour, 0).r;
+        }
+    }
+    // The color of the world is multiplied by the output
+    o_color = vec4(vec3(occlusion / 16.0), 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in float v_Height;
+layout(location = 1) flat in vec4 v_Color;
+
+layout(location = 0) out vec4 ColorBuffer;
+
+void main() {
+    // The stars fade out close to the horizon, where the air is thicker
+    float horizon = smoothstep(-0.05, 0.15, v_Height);
+    ColorBuffer = vec4(v_Color.rgb, v_Color.a * horizon);
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec3 a_Pos;
+
+layout(set = 0, binding = 0) uniform Temp1 { mat4 u_ViewProj; };
+// The model matrix turns the stars with the sun around the camera
+layout(set = 0, binding = 1) uniform Temp2 { mat4 u_Model; vec4 u_Color; };
+
+// Height of the star in the sky, from -1 below the camera to 1 above it
+layout(location = 0) out float v_Height;
+layout(location = 1) flat out vec4 v_Color;
+
+void main() {
+    v_Height = normalize(mat3(u_Model) * a_Pos).y;
+    v_Color = u_Color;
+    gl_Position = u_ViewProj * u_Model * vec4(a_Pos, 1.0);
+}
@@ -0,0 +1,46 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+
+layout(location = 0) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform PostUniform {
+    float exposure;
+    float bloom_strength;
+    float vignette;
+    float underwater;
+    float time;
+    float bloom_threshold;
+    float gamma;
+} u_post;
+layout(set = 0, binding = 1) uniform texture2D u_world;
+layout(set = 0, binding = 2) uniform texture2D u_bloom;
+layout(set = 0, binding = 3) uniform sampler u_sampler;
+
+// The filmic curve of ACES, fitted by Krzysztof Narkowicz
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec2 uv = i_uv;
+    // The view waves when the camera is in the water
+    if (u_post.underwater > 0.5) {
+        uv += vec2(sin(uv.y * 40.0 + u_post.time * 2.0), cos(uv.x * 30.0 + u_post.time * 3.0)) * 0.003;
+        uv = clamp(uv, 0.0, 1.0);
+    }
+    vec3 color = texture(sampler2D(u_world, u_sampler), uv).rgb;
+    color += texture(sampler2D(u_bloom, u_sampler), uv).rgb * u_post.bloom_strength;
+    color *= u_post.exposure;
+    // The water absorbs the red first
+    if (u_post.underwater > 0.5) {
+        color *= vec3(0.55, 0.8, 1.0);
+    }
+    color = aces(color);
+    // Brighten or darken the dark colors, according to the settings
+    color = pow(color, vec3(1.0 / u_post.gamma));
+    // Darken the corners
+    float distance_to_center = length(i_uv - 0.5) * sqrt(2.0);
+    color *= 1.0 - u_post.vignette * smoothstep(0.4, 1.0, distance_to_center);
+    o_color = vec4(color, 1.0);
+}
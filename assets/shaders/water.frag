@@ -0,0 +1,66 @@
+#version 450
+
+layout(location = 0) flat in vec3 i_norm;
+layout(location = 1) in float i_occl;
+layout(location = 2) flat in vec2 i_texture_top_left;
+layout(location = 3) flat in vec2 i_texture_size;
+layout(location = 4) flat in vec2 i_texture_max_uv;
+layout(location = 5) in vec2 i_texture_uv;
+layout(location = 6) in float i_light_level;
+layout(location = 7) flat in uint i_texture_page;
+layout(location = 8) in vec3 i_world_pos;
+
+layout(location = 0) out vec4 o_color;
+
+layout(set = 0, binding = 1) uniform sampler u_sampler;
+layout(set = 0, binding = 2) uniform texture2DArray u_texture_atlas;
+layout(set = 0, binding = 3) uniform Sky {
+    // The direction of the sun, and the intensity of the sky light in w
+    vec4 u_sky;
+    vec4 u_sky_color;
+    // The color of the fog, and the distance at which it hides everything in w
+    vec4 u_fog;
+    // Multiplies the texture of the water
+    vec4 u_water_color;
+    vec4 u_camera_pos;
+    // The animation time in seconds in x
+    vec4 u_time;
+};
+
+const vec3 SUN_DIRECTION = normalize(vec3(0, 1, 0.5));
+const float SUN_FRACTION = 0.1;
+const vec2 EPSILON = vec2(1e-7, 1e-7);
+// Fraction of the fog distance where the fog starts
+const float FOG_START = 0.6;
+// Number of seconds for the texture to scroll by its whole size along each axis.
+// They must divide the period of the animation time.
+const vec2 SCROLL_PERIOD = vec2(16.0, 24.0);
+const float WATER_ALPHA = 0.75;
+
+void main() {
+    /* TEXTURE ACCESS */
+    // avoid going out of bounds when multisampling is enabled
+    vec2 corrected_uv = clamp(i_texture_uv, EPSILON, i_texture_max_uv - EPSILON);
+    // compute the texture gradients before texture wrapping
+    vec2 x_derivative = dFdx(corrected_uv);
+    vec2 y_derivative = dFdy(corrected_uv);
+    // scroll and wrap texture
+    vec2 scroll = i_texture_size * fract(u_time.x / SCROLL_PERIOD);
+    vec2 actual_uv = i_texture_top_left + mod(corrected_uv + scroll, i_texture_size);
+    // get texture value
+    vec4 tex_color = textureGrad(sampler2DArray(u_texture_atlas, u_sampler), vec3(actual_uv, float(i_texture_page)), x_derivative, y_derivative);
+    tex_color.rgb *= u_water_color.rgb;
+
+    /* VARIOUS BRIGHTNESS FACTORS */
+    float light_factor = pow(0.8, 15.0 - i_light_level) * u_sky.w;
+    float normal_factor = 1.0 - SUN_FRACTION + SUN_FRACTION * dot(i_norm, SUN_DIRECTION);
+    float total_factor = light_factor * i_occl * normal_factor;
+
+    /* FOG */
+    float fog_factor = smoothstep(FOG_START * u_fog.w, u_fog.w, distance(i_world_pos, u_camera_pos.xyz));
+    vec3 fog_color = u_fog.rgb * u_sky.w;
+
+    /* OUTPUT */
+    o_color = vec4(total_factor, total_factor, total_factor, WATER_ALPHA) * tex_color;
+    o_color.rgb = mix(o_color.rgb, fog_color, fog_factor);
+}
@@ -6,16 +6,72 @@ layout(location = 2) flat in vec2 i_texture_top_left;
 layout(location = 3) flat in vec2 i_texture_size;
 layout(location = 4) flat in vec2 i_texture_max_uv;
 layout(location = 5) in vec2 i_texture_uv;
-layout(location = 6) flat in float i_light_level;
+layout(location = 6) in float i_light_level;
+layout(location = 7) flat in uint i_texture_page;
+layout(location = 8) in vec3 i_world_pos;
 
 layout(location = 0) out vec4 o_color;
 
 layout(set = 0, binding = 1) uniform sampler u_sampler;
-layout(set = 0, binding = 2) uniform texture2D u_texture_atlas;
+layout(set = 0, binding = 2) uniform texture2DArray u_texture_atlas;
+layout(set = 0, binding = 3) uniform Sky {
+    // The direction of the sun, and the intensity of the sky light in w
+    vec4 u_sky;
+    vec4 u_sky_color;
+    // The color of the fog, and the distance at which it hides everything in w
+    vec4 u_fog;
+    // Multiplies the texture of the water
+    vec4 u_water_color;
+    vec4 u_camera_pos;
+    // The animation time in seconds in x
+    vec4 u_time;
+};
+layout(set = 0, binding = 4) uniform Shadows {
+    // The view-projection matrices of the shadow cascades, from the sun
+    mat4 u_shadow_view_proj[3];
+    // The radii of the cascades around the camera, and how much light the shadows remove in w, 0 if they are disabled
+    vec4 u_shadow_cascades;
+};
+layout(set = 0, binding = 5) uniform texture2DArray u_shadow_maps;
+layout(set = 0, binding = 6) uniform samplerShadow u_shadow_sampler;
 
 const vec3 SUN_DIRECTION = normalize(vec3(0, 1, 0.5));
 const float SUN_FRACTION = 0.1;
 const vec2 EPSILON = vec2(1e-7, 1e-7);
+// Fraction of the fog distance where the fog starts
+const float FOG_START = 0.6;
+// How far the fragments are moved out of their face before looking up the shadow maps, in texels of the cascade
+const float SHADOW_NORMAL_OFFSET = 1.5;
+
+// The fraction of the light of the sun that reaches the fragment
+float sun_visibility() {
+    float distance_to_camera = distance(i_world_pos, u_camera_pos.xyz);
+    int cascade = 0;
+    while (cascade < 3 && distance_to_camera > u_shadow_cascades[cascade]) {
+        cascade++;
+    }
+    if (cascade == 3) {
+        return 1.0;
+    }
+    // The faces turned away from the sun are in their own shadow
+    if (dot(i_norm, u_sky.xyz) <= 0.0) {
+        return 0.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(sampler2DArrayShadow(u_shadow_maps, u_shadow_sampler), 0).xy);
+    float offset = SHADOW_NORMAL_OFFSET * 2.0 * u_shadow_cascades[cascade] * texel.x;
+    vec4 shadow_pos = u_shadow_view_proj[cascade] * vec4(i_world_pos + i_norm * offset, 1.0);
+    vec3 shadow_ndc = shadow_pos.xyz / shadow_pos.w;
+    vec3 shadow_uv = vec3(shadow_ndc.x * 0.5 + 0.5, 0.5 - shadow_ndc.y * 0.5, shadow_ndc.z);
+    // Average the neighbouring texels to soften the edges of the shadows
+    float visibility = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec2 uv = shadow_uv.xy + vec2(x, y) * texel;
+            visibility += texture(sampler2DArrayShadow(u_shadow_maps, u_shadow_sampler), vec4(uv, float(cascade), shadow_uv.z));
+        }
+    }
+    return visibility / 9.0;
+}
 
 void main() {
     /* TEXTURE ACCESS */
@@ -27,13 +83,27 @@ void main() {
     // wrap texture
     vec2 actual_uv = i_texture_top_left + mod(corrected_uv, i_texture_size);
     // get texture value
-    vec4 tex_color = textureGrad(sampler2D(u_texture_atlas, u_sampler), actual_uv, x_derivative, y_derivative);
+    vec4 tex_color = textureGrad(sampler2DArray(u_texture_atlas, u_sampler), vec3(actual_uv, float(i_texture_page)), x_derivative, y_derivative);
+    // the blocks with a model can have transparent parts
+    if (tex_color.a < 0.5) {
+        discard;
+    }
 
     /* VARIOUS BRIGHTNESS FACTORS */
-    float light_factor = pow(0.8, 15.0 - i_light_level);
+    float light_factor = pow(0.8, 15.0 - i_light_level) * u_sky.w;
     float normal_factor = 1.0 - SUN_FRACTION + SUN_FRACTION * dot(i_norm, SUN_DIRECTION);
     float total_factor = light_factor * i_occl * normal_factor;
+    if (u_shadow_cascades.w > 0.0) {
+        // Only the blocks reached by the sky light are shadowed, and only while the sun is up
+        float shadow_strength = u_shadow_cascades.w * smoothstep(10.0, 15.0, i_light_level) * smoothstep(0.0, 0.15, u_sky.y);
+        total_factor *= 1.0 - shadow_strength * (1.0 - sun_visibility());
+    }
+
+    /* FOG */
+    float fog_factor = smoothstep(FOG_START * u_fog.w, u_fog.w, distance(i_world_pos, u_camera_pos.xyz));
+    vec3 fog_color = u_fog.rgb * u_sky.w;
 
     /* OUTPUT */
     o_color = vec4(total_factor, total_factor, total_factor, 1.0) * tex_color;
+    o_color.rgb = mix(o_color.rgb, fog_color, fog_factor);
 }
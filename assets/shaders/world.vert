@@ -7,6 +7,7 @@ layout(location = 3) in vec2 i_texture_max_uv;
 layout(location = 4) in vec2 i_texture_uv;
 // occl at end, then face then light
 layout(location = 5) in uint i_occl_and_face;
+// atlas page: the remaining bits
 // light: 4 bits
 // occl: 2 bits
 // face: 3 bits
@@ -21,7 +22,9 @@ layout(location = 2) flat out vec2 o_texture_top_left;
 layout(location = 3) flat out vec2 o_texture_size;
 layout(location = 4) flat out vec2 o_texture_max_uv;
 layout(location = 5) out vec2 o_texture_uv;
-layout(location = 6) flat out float o_light_level;
+layout(location = 6) out float o_light_level;
+layout(location = 7) flat out uint o_texture_page;
+layout(location = 8) out vec3 o_world_pos;
 
 vec3 get_normal(uint id) {
     if(id == 0u) {
@@ -56,6 +59,7 @@ void main() {
     uint light_level = (i_occl_and_face & 0x000001E0u) >> 5;
     uint occl_code = (i_occl_and_face & 0x00000018u) >> 3;
     uint face_index = (i_occl_and_face & 0x00000007u) >> 0;
+    o_texture_page = i_occl_and_face >> 9;
 
     o_norm = get_normal(face_index);
     o_occl = get_occl(occl_code);
@@ -64,6 +68,7 @@ void main() {
     o_texture_max_uv = i_texture_max_uv;
     o_texture_uv = i_texture_uv;
     o_light_level = float(light_level);
+    o_world_pos = i_position;
 
     gl_Position = u_view_proj * vec4(i_position, 1.0);
 }
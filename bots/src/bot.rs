@@ -0,0 +1,214 @@
+//! A simulated player, that follows a script instead of reading the inputs of a human.
+use crate::config::{BotAction, BotScript};
+use history_survival_common::{
+    network::{
+        messages::{InteractionAction, InteractionTarget, ToClient, ToServer},
+        Client, ClientEvent,
+    },
+    physics::player::{PhysicsPlayer, YawPitch},
+    player::{GameMode, PlayerId, PlayerInput, RenderDistance},
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// How long the jump key is held
+const JUMP_DURATION: Duration = Duration::from_millis(250);
+/// The pitch of the bots that break or place blocks, looking at the ground in front of them
+const BUILD_PITCH: f64 = -70.0;
+/// The top face of a block, that the bots break or build on
+const TOP_FACE: usize = 2;
+
+/// What the bots received from the server since the last report
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Traffic {
+    pub messages: u64,
+    pub chunks: u64,
+    pub physics_updates: u64,
+    pub chat_messages: u64,
+}
+
+impl std::ops::AddAssign for Traffic {
+    fn add_assign(&mut self, other: Self) {
+        self.messages += other.messages;
+        self.chunks += other.chunks;
+        self.physics_updates += other.physics_updates;
+        self.chat_messages += other.chat_messages;
+    }
+}
+
+/// A bot connected to the server
+pub struct Bot {
+    client: Box<dyn Client>,
+    script: BotScript,
+    render_distance: RenderDistance,
+    /// The id of the bot, once the server sent it
+    id: Option<PlayerId>,
+    /// The position of the bot in the last physics update of the server
+    position: Option<Vector3<f64>>,
+    game_mode: GameMode,
+    input: PlayerInput,
+    /// The index of the current action in the script, and when it started
+    action: Option<(usize, Instant)>,
+    traffic: Traffic,
+}
+
+impl Bot {
+    pub fn new(client: Box<dyn Client>, script: BotScript, render_distance: RenderDistance) -> Self {
+        Self {
+            client,
+            script,
+            render_distance,
+            id: None,
+            position: None,
+            game_mode: GameMode::default(),
+            input: PlayerInput {
+                flying: false,
+                ..Default::default()
+            },
+            action: None,
+            traffic: Traffic::default(),
+        }
+    }
+
+    /// The traffic received since the last call
+    pub fn take_traffic(&mut self) -> Traffic {
+        std::mem::take(&mut self.traffic)
+    }
+
+    /// Whether the bot was spawned by the server
+    pub fn is_spawned(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Handle the messages of the server, and move on with the script
+    pub fn tick(&mut self, now: Instant) {
+        self.handle_server_messages();
+        let position = match self.position {
+            Some(position) => position,
+            None => return,
+        };
+        if self.script.actions.is_empty() {
+            return;
+        }
+
+        let (index, start) = match self.action {
+            Some(action) => action,
+            None => {
+                self.start_action(0, now, position);
+                (0, now)
+            }
+        };
+        let action = &self.script.actions[index];
+        let elapsed = now - start;
+        let done = match action {
+            BotAction::Walk { secs, .. } | BotAction::Idle { secs } | BotAction::Break { secs } => elapsed.as_secs_f64() >= *secs,
+            BotAction::Jump => elapsed >= JUMP_DURATION,
+            BotAction::Place | BotAction::Chat(_) => true,
+        };
+        if done {
+            if let BotAction::Break { .. } = action {
+                self.interact(position, InteractionAction::Attack);
+            }
+            self.start_action((index + 1) % self.script.actions.len(), now, position);
+        }
+
+        let mut input = self.input;
+        self.game_mode.adapt_input(&mut input);
+        self.client.send(ToServer::UpdateInput(input));
+    }
+
+    fn start_action(&mut self, index: usize, now: Instant, position: Vector3<f64>) {
+        self.action = Some((index, now));
+        self.input.key_move_forward = false;
+        self.input.key_move_up = false;
+        self.input.sprinting = false;
+        match self.script.actions[index].clone() {
+            BotAction::Walk { sprint, .. } => {
+                self.input.yaw_pitch = YawPitch {
+                    yaw: rand::thread_rng().gen_range(0.0..360.0),
+                    pitch: 0.0,
+                };
+                self.input.key_move_forward = true;
+                self.input.sprinting = sprint;
+            }
+            BotAction::Jump => self.input.key_move_up = true,
+            BotAction::Idle { .. } => {}
+            BotAction::Break { .. } => {
+                self.input.yaw_pitch.pitch = BUILD_PITCH;
+                let block = self.ground_in_front(position);
+                self.client.send(ToServer::StartBreaking(position, block));
+            }
+            BotAction::Place => {
+                self.input.yaw_pitch.pitch = BUILD_PITCH;
+                self.interact(position, InteractionAction::Use);
+            }
+            BotAction::Chat(message) => self.client.send(ToServer::ChatMessage(message)),
+        }
+    }
+
+    /// The block of the ground in front of the bot, that it looks at while it builds.
+    /// The bots don't keep the chunks, so they assume that they stand on flat ground.
+    fn ground_in_front(&self, position: Vector3<f64>) -> BlockPos {
+        let mut player = PhysicsPlayer::default();
+        player.aabb.pos = position;
+        let camera_pos = player.get_camera_position();
+        let YawPitch { yaw, pitch } = self.input.yaw_pitch;
+        let (y, p) = (yaw.to_radians(), pitch.to_radians());
+        // The view reaches the ground this far in front of the camera
+        let distance = (camera_pos.y - position.y) / (-p).tan();
+        let ground = camera_pos + Vector3::new(-y.sin(), 0.0, -y.cos()) * distance;
+        BlockPos::from(Vector3::new(ground.x, position.y - 0.5, ground.z))
+    }
+
+    /// Interact with the ground in front of the bot, with the first slot of its hotbar
+    fn interact(&mut self, position: Vector3<f64>, action: InteractionAction) {
+        let YawPitch { yaw, pitch } = self.input.yaw_pitch;
+        let target = InteractionTarget::Block(self.ground_in_front(position), TOP_FACE);
+        self.client.send(ToServer::Interact {
+            player_pos: position,
+            yaw,
+            pitch,
+            target,
+            action,
+            hand: 0,
+        });
+    }
+
+    fn handle_server_messages(&mut self) {
+        let mut received_chunks = Vec::new();
+        loop {
+            let message = match self.client.receive_event() {
+                ClientEvent::NoEvent => break,
+                ClientEvent::Connected | ClientEvent::Disconnected => continue,
+                ClientEvent::ServerMessage(message) => message,
+            };
+            self.traffic.messages += 1;
+            match message {
+                ToClient::CurrentId(id) => {
+                    self.id = Some(id);
+                    self.client.send(ToServer::SetRenderDistance(self.render_distance));
+                }
+                ToClient::Chunk(chunk, _) => {
+                    self.traffic.chunks += 1;
+                    received_chunks.push(chunk.pos);
+                }
+                ToClient::UpdatePhysics(server_state) => {
+                    self.traffic.physics_updates += 1;
+                    if let Some(player) = self.id.and_then(|id| server_state.physics_state.players.get(&id)) {
+                        self.position = Some(player.aabb.pos);
+                    }
+                }
+                ToClient::ChatMessage(_) => self.traffic.chat_messages += 1,
+                ToClient::GameMode(game_mode) => self.game_mode = game_mode,
+                ToClient::Respawn(_) => self.action = None,
+                _ => {}
+            }
+        }
+        // The server slows down the chunk delivery of the clients that don't acknowledge their chunks
+        if !received_chunks.is_empty() {
+            self.client.send(ToServer::ChunksReceived(received_chunks));
+        }
+    }
+}
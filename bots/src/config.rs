@@ -0,0 +1,106 @@
+//! The configuration of the bots: how many connect, and the scripts they follow.
+use log::{info, warn};
+use serde::Deserialize;
+use std::path::Path;
+
+/// The file the configuration is read from if no other file is given on the command line
+pub const DEFAULT_CONFIG_FILE: &str = "config/bots.ron";
+
+/// The configuration of a crowd of bots
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BotsConfig {
+    /// How many bots connect to the server
+    pub bot_count: usize,
+    /// How long the test runs, in seconds, `None` to run until the process is killed
+    pub duration_secs: Option<u64>,
+    /// Horizontal and vertical render distance of the bots, in chunks
+    pub render_distance: (u64, u64),
+    /// Time between two reports of the traffic received by the bots, in seconds
+    pub report_interval_secs: u64,
+    /// The scripts of the bots, given to the bots in turn
+    pub scripts: Vec<BotScript>,
+}
+
+impl Default for BotsConfig {
+    fn default() -> Self {
+        Self {
+            bot_count: 10,
+            duration_secs: None,
+            render_distance: (6, 4),
+            report_interval_secs: 10,
+            scripts: vec![
+                BotScript {
+                    name: "wanderer".to_owned(),
+                    actions: vec![
+                        BotAction::Walk { secs: 4.0, sprint: false },
+                        BotAction::Jump,
+                        BotAction::Walk { secs: 2.0, sprint: true },
+                        BotAction::Idle { secs: 1.0 },
+                    ],
+                },
+                BotScript {
+                    name: "builder".to_owned(),
+                    actions: vec![
+                        BotAction::Walk { secs: 2.0, sprint: false },
+                        BotAction::Break { secs: 1.5 },
+                        BotAction::Place,
+                        BotAction::Place,
+                        BotAction::Idle { secs: 0.5 },
+                    ],
+                },
+                BotScript {
+                    name: "chatter".to_owned(),
+                    actions: vec![
+                        BotAction::Walk { secs: 3.0, sprint: false },
+                        BotAction::Chat("Hello from a bot!".to_owned()),
+                        BotAction::Idle { secs: 2.0 },
+                    ],
+                },
+            ],
+        }
+    }
+}
+
+/// A list of actions that a bot repeats forever
+#[derive(Debug, Clone, Deserialize)]
+pub struct BotScript {
+    pub name: String,
+    pub actions: Vec<BotAction>,
+}
+
+/// Something that a bot does
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum BotAction {
+    /// Walk forward in a random direction
+    Walk { secs: f64, sprint: bool },
+    /// Jump once
+    Jump,
+    /// Do nothing
+    Idle { secs: f64 },
+    /// Break the block below, holding the button for some time
+    Break { secs: f64 },
+    /// Place the held block below
+    Place,
+    /// Send a chat message
+    Chat(String),
+}
+
+/// Load the configuration of the bots, or use the default configuration if there is none or it is invalid
+pub fn load_config(file: &str) -> BotsConfig {
+    let path = Path::new(file);
+    if !path.is_file() {
+        info!("No bots configuration at {}, using the default configuration", file);
+        return BotsConfig::default();
+    }
+    let parsed = std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|string| ron::de::from_str(&string).map_err(anyhow::Error::from));
+    match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Failed to load the bots configuration {}, using the default configuration: {:?}", file, e);
+            BotsConfig::default()
+        }
+    }
+}
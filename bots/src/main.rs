@@ -0,0 +1,114 @@
+//! A crowd of bots connected to a server, to load-test it without humans.
+//!
+//! Usage: `history_survival_bots [bot count] [configuration file]`.
+//! The bots walk around, break and place blocks and chat following the scripts of the configuration.
+use anyhow::{Context, Result};
+use history_survival_common::{network::dummy, player::RenderDistance};
+use history_survival_server::launch_server;
+use log::{debug, error, info};
+use std::time::{Duration, Instant};
+
+mod bot;
+mod config;
+
+use bot::{Bot, Traffic};
+
+/// Time between two ticks of the bots
+const TICK_INTERVAL: Duration = Duration::from_millis(50);
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let bot_count = args.next().map(|count| count.parse::<usize>().context("Invalid bot count")).transpose()?;
+    let config_file = args.next().unwrap_or_else(|| config::DEFAULT_CONFIG_FILE.to_owned());
+    let mut config = config::load_config(&config_file);
+    if let Some(bot_count) = bot_count {
+        config.bot_count = bot_count;
+    }
+    anyhow::ensure!(!config.scripts.is_empty(), "The bots configuration has no scripts");
+    info!("Starting {} bots", config.bot_count);
+
+    let (clients, server) = dummy::new_many(config.bot_count);
+    std::thread::spawn(move || {
+        if let Err(e) = launch_server(Box::new(server)) {
+            error!(
+                "Error happened in the server code: {}\nPrinting chain:\n{}",
+                e,
+                e.chain()
+                    .enumerate()
+                    .map(|(i, e)| format!("{}: {}", i, e))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+    });
+
+    let (horizontal, vertical) = config.render_distance;
+    let render_distance = RenderDistance {
+        x_max: horizontal,
+        x_min: horizontal,
+        y_max: vertical,
+        y_min: vertical,
+        z_max: horizontal,
+        z_min: horizontal,
+    };
+    let mut bots: Vec<Bot> = clients
+        .into_iter()
+        .enumerate()
+        .map(|(i, client)| {
+            let script = config.scripts[i % config.scripts.len()].clone();
+            debug!("Bot {} follows the {} script", i, script.name);
+            Bot::new(Box::new(client), script, render_distance)
+        })
+        .collect();
+
+    let start = Instant::now();
+    let report_interval = Duration::from_secs(config.report_interval_secs.max(1));
+    let mut last_report = start;
+    let mut next_tick = start;
+    loop {
+        let now = Instant::now();
+        if let Some(duration) = config.duration_secs {
+            if now - start >= Duration::from_secs(duration) {
+                info!("The test is over after {} seconds", duration);
+                return Ok(());
+            }
+        }
+
+        for bot in bots.iter_mut() {
+            bot.tick(now);
+        }
+
+        if now - last_report >= report_interval {
+            report(&mut bots, now - last_report);
+            last_report = now;
+        }
+
+        // Keep a steady tick rate, without trying to catch up if the bots are late
+        next_tick += TICK_INTERVAL;
+        match next_tick.checked_duration_since(Instant::now()) {
+            Some(sleep) => std::thread::sleep(sleep),
+            None => next_tick = Instant::now(),
+        }
+    }
+}
+
+/// Log what the bots received during the last `elapsed`
+fn report(bots: &mut [Bot], elapsed: Duration) {
+    let spawned = bots.iter().filter(|bot| bot.is_spawned()).count();
+    let mut traffic = Traffic::default();
+    for bot in bots.iter_mut() {
+        traffic += bot.take_traffic();
+    }
+    let secs = elapsed.as_secs_f64();
+    info!(
+        "{}/{} bots spawned, received per second: {:.0} messages, {:.1} chunks, {:.1} physics updates, {:.1} chat messages",
+        spawned,
+        bots.len(),
+        traffic.messages as f64 / secs,
+        traffic.chunks as f64 / secs,
+        traffic.physics_updates as f64 / secs,
+        traffic.chat_messages as f64 / secs,
+    );
+}
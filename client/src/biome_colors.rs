@@ -0,0 +1,111 @@
+//! The colors of the water, the sky and the fog in each biome.
+//! The colors of the biomes around the camera are blended, so that they change smoothly when the player walks
+//! from a biome to another.
+use crate::world::World;
+use history_survival_common::{biome::Biome, world::BlockPos};
+
+/// Horizontal distance from the camera of the columns whose biome is blended
+const BLEND_RADIUS: i64 = 24;
+/// Distance between two sampled columns
+const BLEND_STEP: i64 = 4;
+
+/// The colors of a biome, as linear RGB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeColors {
+    /// Multiplies the texture of the water blocks
+    pub water: [f32; 3],
+    /// The color of the sky above the horizon
+    pub sky: [f32; 3],
+    /// The color of the fog that hides the far chunks
+    pub fog: [f32; 3],
+}
+
+/// The colors of every biome, by biome id
+const BIOME_COLORS: [BiomeColors; Biome::ALL.len()] = [
+    // Plains
+    BiomeColors {
+        water: [1.0, 1.0, 1.0],
+        sky: [0.2, 0.4, 0.8],
+        fog: [0.7, 0.8, 0.95],
+    },
+    // Ocean
+    BiomeColors {
+        water: [0.6, 0.75, 1.0],
+        sky: [0.15, 0.4, 0.85],
+        fog: [0.6, 0.75, 0.95],
+    },
+    // Swamp
+    BiomeColors {
+        water: [0.55, 0.7, 0.45],
+        sky: [0.3, 0.4, 0.45],
+        fog: [0.45, 0.5, 0.4],
+    },
+    // Snowy
+    BiomeColors {
+        water: [0.8, 0.9, 1.0],
+        sky: [0.55, 0.65, 0.8],
+        fog: [0.85, 0.88, 0.92],
+    },
+];
+
+impl BiomeColors {
+    /// The colors of some biome
+    pub fn of(biome: Biome) -> Self {
+        BIOME_COLORS[biome.id() as usize]
+    }
+
+    /// The weighted average of the colors of some biomes. Without any biome, the colors of the plains.
+    pub fn blend(biomes: impl IntoIterator<Item = (Biome, f32)>) -> Self {
+        let mut sum = [[0.0; 3]; 3];
+        let mut total_weight = 0.0;
+        for (biome, weight) in biomes {
+            let colors = Self::of(biome);
+            for (sum, color) in sum.iter_mut().zip([colors.water, colors.sky, colors.fog].iter()) {
+                for i in 0..3 {
+                    sum[i] += color[i] * weight;
+                }
+            }
+            total_weight += weight;
+        }
+        if total_weight <= 0.0 {
+            return Self::of(Biome::Plains);
+        }
+        let average = |color: [f32; 3]| [color[0] / total_weight, color[1] / total_weight, color[2] / total_weight];
+        Self {
+            water: average(sum[0]),
+            sky: average(sum[1]),
+            fog: average(sum[2]),
+        }
+    }
+
+    /// Blend the colors of the biomes of the loaded columns around the camera, the closest ones weighing more
+    pub fn around(world: &World, camera: BlockPos) -> Self {
+        let samples = (-BLEND_RADIUS..=BLEND_RADIUS).step_by(BLEND_STEP as usize).flat_map(move |dx| {
+            (-BLEND_RADIUS..=BLEND_RADIUS).step_by(BLEND_STEP as usize).filter_map(move |dz| {
+                let distance = ((dx * dx + dz * dz) as f32).sqrt();
+                let weight = 1.0 - distance / (BLEND_RADIUS + BLEND_STEP) as f32;
+                if weight <= 0.0 {
+                    return None;
+                }
+                world.get_biome(camera.offset(dx, 0, dz)).map(|biome| (biome, weight))
+            })
+        });
+        Self::blend(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_weighs_the_biomes() {
+        assert_eq!(BiomeColors::blend(Vec::new()), BiomeColors::of(Biome::Plains));
+        assert_eq!(BiomeColors::blend(vec![(Biome::Swamp, 0.5)]), BiomeColors::of(Biome::Swamp));
+        let blended = BiomeColors::blend(vec![(Biome::Plains, 3.0), (Biome::Ocean, 1.0)]);
+        let (plains, ocean) = (BiomeColors::of(Biome::Plains), BiomeColors::of(Biome::Ocean));
+        for i in 0..3 {
+            assert!((blended.water[i] - (plains.water[i] * 0.75 + ocean.water[i] * 0.25)).abs() < 1e-6);
+        }
+    }
+}
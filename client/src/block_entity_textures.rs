@@ -0,0 +1,305 @@
+//! The textures of the block entities, like the banners, the paintings and the framed maps,
+//! composed from their data into the dynamic regions of the texture atlas.
+use crate::render::WorldRenderer;
+use history_survival_common::{
+    block::entity::BlockEntity,
+    data::{TextureRect, DYNAMIC_TEXTURE_SIZE},
+    debug::send_debug_info,
+    item::map::{MapCanvas, MapId},
+    world::{BlockPos, Chunk, ChunkPos},
+};
+use image::{imageops, RgbaImage};
+use std::collections::{HashMap, HashSet};
+
+/// A texture that replaces the usual texture of a block: (position in the chunk, face or `None` for all faces, texture)
+pub type TextureOverride = ((u32, u32, u32), Option<usize>, TextureRect);
+
+/// The map displayed by a block entity, if any
+fn get_framed_map(block_entity: &BlockEntity, map_items: &[String]) -> Option<MapId> {
+    match block_entity {
+        BlockEntity::ItemFrame(frame) => {
+            let framed_item = frame.item.as_ref()?;
+            if map_items.contains(&framed_item.item) && framed_item.data != 0 {
+                Some(framed_item.data)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Compose the texture of a block entity, with the face it is drawn on or `None` for all the faces.
+/// Return `None` if the block keeps its usual texture.
+fn compose_texture(
+    block_entity: &BlockEntity,
+    map_items: &[String],
+    maps: &HashMap<MapId, MapCanvas>,
+) -> Option<(RgbaImage, Option<usize>)> {
+    match block_entity {
+        BlockEntity::Banner(pattern) => Some((pattern.compose(DYNAMIC_TEXTURE_SIZE), None)),
+        BlockEntity::Painting(painting) => {
+            let image = painting.image.as_ref()?;
+            Some((image.compose(DYNAMIC_TEXTURE_SIZE), Some(painting.facing)))
+        }
+        BlockEntity::ItemFrame(frame) => {
+            let canvas = maps.get(&get_framed_map(block_entity, map_items)?)?;
+            let rotation = frame.item.as_ref().map(|item| item.rotation).unwrap_or(0);
+            Some((rotate_texture(canvas.compose(DYNAMIC_TEXTURE_SIZE), rotation), Some(frame.facing)))
+        }
+        BlockEntity::Bed(_) | BlockEntity::Chest(_) | BlockEntity::Furnace(_) => None,
+    }
+}
+
+/// Turn a texture clockwise by some quarter turns
+fn rotate_texture(texture: RgbaImage, quarter_turns: u8) -> RgbaImage {
+    match quarter_turns % 4 {
+        1 => imageops::rotate90(&texture),
+        2 => imageops::rotate180(&texture),
+        3 => imageops::rotate270(&texture),
+        _ => texture,
+    }
+}
+
+/// Assigns a region of the texture atlas to every textured block entity of the loaded chunks
+pub struct BlockEntityTextures {
+    /// The names of the map items, whose canvas is drawn on the item frames
+    map_items: Vec<String>,
+    /// The regions of the texture atlas that no block entity uses
+    free_slots: Vec<TextureRect>,
+    /// The block entities that have a texture, with the face of the texture and its region
+    textured: HashMap<BlockPos, (BlockEntity, Option<usize>, TextureRect)>,
+    /// The composed textures that are not in the texture atlas yet
+    pending_uploads: Vec<(TextureRect, RgbaImage)>,
+    /// Number of block entities that didn't get a texture because all the regions are used
+    num_missing: usize,
+    /// The item frames that display a map whose canvas was not received yet
+    waiting_for_maps: HashMap<BlockPos, (BlockEntity, MapId)>,
+    /// The maps that item frames display and whose canvas was never requested
+    missing_maps: HashSet<MapId>,
+}
+
+impl BlockEntityTextures {
+    pub fn new(dynamic_textures: Vec<TextureRect>, map_items: Vec<String>) -> Self {
+        Self {
+            map_items,
+            free_slots: dynamic_textures,
+            textured: HashMap::new(),
+            pending_uploads: Vec::new(),
+            num_missing: 0,
+            waiting_for_maps: HashMap::new(),
+            missing_maps: HashSet::new(),
+        }
+    }
+
+    /// Give a texture to a block entity, or compose its texture again. Return `false` if it keeps its usual texture.
+    fn update_block_entity(&mut self, pos: BlockPos, block_entity: &BlockEntity, maps: &HashMap<MapId, MapCanvas>) -> bool {
+        self.waiting_for_maps.remove(&pos);
+        let (texture, face) = match compose_texture(block_entity, &self.map_items, maps) {
+            Some(texture) => texture,
+            None => {
+                // The block entity doesn't need its texture anymore
+                if let Some((_, _, slot)) = self.textured.remove(&pos) {
+                    self.free_slots.push(slot);
+                }
+                // The frame gets its texture once the canvas of its map is received
+                if let Some(map_id) = get_framed_map(block_entity, &self.map_items) {
+                    self.waiting_for_maps.insert(pos, (block_entity.clone(), map_id));
+                    self.missing_maps.insert(map_id);
+                }
+                return false;
+            }
+        };
+        let slot = match self.textured.get(&pos) {
+            Some((_, _, slot)) => *slot,
+            None => match self.free_slots.pop() {
+                Some(slot) => slot,
+                None => {
+                    self.num_missing += 1;
+                    return false;
+                }
+            },
+        };
+        self.pending_uploads.push((slot, texture));
+        self.textured.insert(pos, (block_entity.clone(), face, slot));
+        true
+    }
+
+    /// Update the block entities of a chunk that was received
+    pub fn update_chunk(&mut self, chunk: &Chunk, maps: &HashMap<MapId, MapCanvas>) {
+        let chunk_pos = chunk.pos;
+        self.waiting_for_maps.retain(|pos, _| pos.containing_chunk_pos() != chunk_pos);
+        // Free the textures of the block entities that were removed
+        let removed = self
+            .textured
+            .keys()
+            .filter(|pos| pos.containing_chunk_pos() == chunk_pos)
+            .filter(|pos| chunk.get_block_entity(pos.pos_in_containing_chunk()).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        for pos in removed {
+            let (_, _, slot) = self.textured.remove(&pos).unwrap();
+            self.free_slots.push(slot);
+        }
+        // Compose the textures of the new and changed block entities
+        for (pos_in_chunk, block_entity) in chunk.block_entities.iter() {
+            let pos = chunk_pos.block_pos(*pos_in_chunk);
+            if let Some((current, _, _)) = self.textured.get(&pos) {
+                if current == block_entity {
+                    continue;
+                }
+            }
+            self.update_block_entity(pos, block_entity, maps);
+        }
+    }
+
+    /// Compose again the textures of the item frames that display a map whose canvas changed.
+    /// Return the chunks of the frames that got a texture, that must be meshed again.
+    pub fn update_map(&mut self, map_id: MapId, maps: &HashMap<MapId, MapCanvas>) -> Vec<ChunkPos> {
+        let framed = self
+            .textured
+            .iter()
+            .filter(|(_, (block_entity, _, _))| get_framed_map(block_entity, &self.map_items) == Some(map_id))
+            .map(|(pos, (block_entity, _, _))| (*pos, block_entity.clone()))
+            .collect::<Vec<_>>();
+        for (pos, block_entity) in framed {
+            self.update_block_entity(pos, &block_entity, maps);
+        }
+        let waiting = self
+            .waiting_for_maps
+            .iter()
+            .filter(|(_, (_, id))| *id == map_id)
+            .map(|(pos, (block_entity, _))| (*pos, block_entity.clone()))
+            .collect::<Vec<_>>();
+        waiting
+            .into_iter()
+            .filter(|(pos, block_entity)| self.update_block_entity(*pos, block_entity, maps))
+            .map(|(pos, _)| pos.containing_chunk_pos())
+            .collect()
+    }
+
+    /// The maps displayed by item frames whose canvas must be requested from the server
+    pub fn take_missing_maps(&mut self) -> HashSet<MapId> {
+        std::mem::take(&mut self.missing_maps)
+    }
+
+    /// Free the textures of the block entities of a chunk that was unloaded
+    pub fn remove_chunk(&mut self, chunk_pos: ChunkPos) {
+        self.waiting_for_maps.retain(|pos, _| pos.containing_chunk_pos() != chunk_pos);
+        let free_slots = &mut self.free_slots;
+        self.textured.retain(|pos, (_, _, slot)| {
+            if pos.containing_chunk_pos() == chunk_pos {
+                free_slots.push(*slot);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// The textures of the block entities of a chunk, for the meshing
+    pub fn get_texture_overrides(&self, chunk_pos: ChunkPos) -> Vec<TextureOverride> {
+        self.textured
+            .iter()
+            .filter(|(pos, _)| pos.containing_chunk_pos() == chunk_pos)
+            .map(|(pos, (_, face, slot))| (pos.pos_in_containing_chunk(), *face, *slot))
+            .collect()
+    }
+
+    /// Copy the new textures to the texture atlas
+    pub fn upload(&mut self, renderer: &mut WorldRenderer, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        for (slot, texture) in self.pending_uploads.drain(..) {
+            renderer.update_texture_atlas_region(device, encoder, slot, &texture);
+        }
+        send_debug_info(
+            "Chunks",
+            "blockentitytextures",
+            format!(
+                "Block entity textures = {}, free = {}, block entities without texture = {}",
+                self.textured.len(),
+                self.free_slots.len(),
+                self.num_missing
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use history_survival_common::block::{
+        banner::{BannerColor, BannerPattern},
+        item_frame::{FramedItem, ItemFrame},
+        painting::Painting,
+    };
+    use history_survival_common::world::BlockPos;
+
+    #[test]
+    fn slots_are_reused() {
+        let slot = |i| TextureRect {
+            x: i as f32,
+            ..Default::default()
+        };
+        let mut textures = BlockEntityTextures::new(vec![slot(0), slot(1)], Vec::new());
+        let maps = HashMap::new();
+        let pos = ChunkPos::from([0, 0, 0]);
+        let mut chunk = Chunk::new(pos);
+        for x in 0..3 {
+            chunk.set_block_entity((x, 0, 0), Some(BlockEntity::Banner(BannerPattern::default())));
+        }
+        // Blank paintings keep their usual texture
+        let blank_painting = Painting {
+            facing: 0,
+            image: None,
+            author: None,
+        };
+        chunk.set_block_entity((0, 1, 0), Some(BlockEntity::Painting(blank_painting)));
+        textures.update_chunk(&chunk, &maps);
+        // Only two of the three banners get a texture
+        assert_eq!(textures.get_texture_overrides(pos).len(), 2);
+        assert_eq!(textures.pending_uploads.len(), 2);
+
+        // Changing a banner composes its texture again, in the same region
+        let textured = textures.get_texture_overrides(pos)[0];
+        let pattern = BannerPattern {
+            base: BannerColor::Red,
+            layers: Vec::new(),
+        };
+        chunk.set_block_entity(textured.0, Some(BlockEntity::Banner(pattern)));
+        textures.update_chunk(&chunk, &maps);
+        assert_eq!(textures.pending_uploads.last().unwrap().0, textured.2);
+        assert_eq!(textures.free_slots.len(), 0);
+
+        textures.remove_chunk(pos);
+        assert_eq!(textures.free_slots.len(), 2);
+        assert!(textures.get_texture_overrides(pos).is_empty());
+    }
+
+    #[test]
+    fn framed_maps_wait_for_their_canvas() {
+        let mut textures = BlockEntityTextures::new(vec![TextureRect::default()], vec!["map".to_owned()]);
+        let mut maps = HashMap::new();
+        let pos = ChunkPos::from([0, 0, 0]);
+        let mut chunk = Chunk::new(pos);
+        let frame = ItemFrame {
+            facing: 4,
+            item: Some(FramedItem {
+                item: "map".to_owned(),
+                data: 7,
+                rotation: 0,
+            }),
+        };
+        chunk.set_block_entity((1, 2, 3), Some(BlockEntity::ItemFrame(frame)));
+        textures.update_chunk(&chunk, &maps);
+        assert!(textures.get_texture_overrides(pos).is_empty());
+        assert_eq!(textures.take_missing_maps().into_iter().collect::<Vec<_>>(), vec![7]);
+
+        // The frame gets a texture on its front face once the canvas is received, so its chunk must be meshed again
+        maps.insert(7, MapCanvas::new(BlockPos { px: 0, py: 0, pz: 0 }));
+        assert_eq!(textures.update_map(7, &maps), vec![pos]);
+        assert_eq!(textures.get_texture_overrides(pos)[0].1, Some(4));
+        // Later changes of the canvas only compose the texture again
+        assert!(textures.update_map(7, &maps).is_empty());
+        assert_eq!(textures.pending_uploads.len(), 2);
+    }
+}
@@ -0,0 +1,106 @@
+//! The box models of the mobs and of the other players, whose legs and arms swing while they walk.
+use crate::render::Model;
+use history_survival_common::{
+    data::vox::VoxelModel,
+    entity::model::{box_model_part_name, BoxModel, ModelPart, BOX_MODEL_VOXELS_PER_BLOCK},
+    mob::{Mob, MobId},
+    registry::Registry,
+};
+use nalgebra::{UnitQuaternion, Vector3};
+
+/// Number of steps of the walk animation per second
+const WALK_CYCLES_PER_SECOND: f32 = 1.5;
+/// Horizontal speed at which the parts swing by their full angle, in blocks per second
+const FULL_SWING_SPEED: f64 = 3.0;
+
+/// The parts of a box model, with their mesh ids
+type MeshedBoxModel = Vec<(u32, ModelPart)>;
+
+pub struct BoxModels {
+    player: MeshedBoxModel,
+    /// By mob id
+    mobs: Vec<MeshedBoxModel>,
+}
+
+impl BoxModels {
+    pub fn new(models: &Registry<VoxelModel>, player_model: &BoxModel, mobs: &Registry<Mob>) -> Self {
+        let mesh = |model_name: &str, model: &BoxModel| -> MeshedBoxModel {
+            model
+                .parts
+                .iter()
+                .enumerate()
+                .filter_map(|(index, part)| {
+                    let mesh_id = models.get_id_by_name(&box_model_part_name(model_name, index))?;
+                    Some((mesh_id, part.clone()))
+                })
+                .collect()
+        };
+        Self {
+            player: mesh("player", player_model),
+            mobs: (0..mobs.get_number_of_ids())
+                .map(|mob_id| {
+                    let mob = mobs.get_value_by_id(mob_id).unwrap();
+                    mesh(&format!("mob:{}", mob.name), &mob.ty.model)
+                })
+                .collect(),
+        }
+    }
+
+    /// The models of the parts of a player whose aabb is at `aabb_pos`
+    pub fn player_models(&self, aabb_pos: Vector3<f64>, size: (f64, f64, f64), yaw: f64, velocity: Vector3<f64>, time: f32) -> Vec<Model> {
+        part_models(&self.player, aabb_pos, size, yaw, velocity, time)
+    }
+
+    /// The models of the parts of a mob whose aabb is at `aabb_pos`
+    pub fn mob_models(&self, mob_id: MobId, aabb_pos: Vector3<f64>, size: (f64, f64, f64), yaw: f64, velocity: Vector3<f64>, time: f32) -> Vec<Model> {
+        match self.mobs.get(mob_id as usize) {
+            Some(parts) => part_models(parts, aabb_pos, size, yaw, velocity, time),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Place the parts of a box model on the bottom center of an aabb, facing `yaw` in degrees.
+/// The parts swing with a phase that comes from `time`, by an angle that grows with the horizontal speed.
+fn part_models(
+    parts: &[(u32, ModelPart)],
+    aabb_pos: Vector3<f64>,
+    (size_x, _, size_z): (f64, f64, f64),
+    yaw: f64,
+    velocity: Vector3<f64>,
+    time: f32,
+) -> Vec<Model> {
+    let scale = 1.0 / BOX_MODEL_VOXELS_PER_BLOCK;
+    let bottom_center = Vector3::new(
+        (aabb_pos.x + size_x / 2.0) as f32,
+        aabb_pos.y as f32,
+        (aabb_pos.z + size_z / 2.0) as f32,
+    );
+    // The models face +x, and an entity with a yaw of 0 faces -z
+    let rot_y = yaw.to_radians() as f32 + std::f32::consts::FRAC_PI_2;
+    let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), rot_y);
+    let walk_amount = (velocity.x.hypot(velocity.z) / FULL_SWING_SPEED).min(1.0) as f32;
+    let walk_phase = (time * WALK_CYCLES_PER_SECOND * std::f32::consts::TAU).sin();
+    parts
+        .iter()
+        .map(|(mesh_id, part)| {
+            let (offset_x, offset_y, offset_z) = part.offset;
+            let (size_x, size_y, size_z) = part.size;
+            // The parts swing around the center of their top
+            let pivot = Vector3::new(size_x as f32 / 2.0, size_y as f32, size_z as f32 / 2.0) * scale;
+            let offset = Vector3::new(offset_x as f32, offset_y as f32, offset_z as f32) * scale;
+            // The part turns with the whole model around the bottom center, but the model turns around the pivot
+            let pos = bottom_center + rotation * (offset + pivot) - pivot;
+            Model {
+                mesh_id: *mesh_id,
+                pos_x: pos.x,
+                pos_y: pos.y,
+                pos_z: pos.z,
+                scale,
+                rot_offset: [pivot.x, pivot.y, pivot.z],
+                rot_y,
+                roll: part.swing.to_radians() * walk_amount * walk_phase,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,188 @@
+//! The camera modes, which place the view at the eyes of the player, behind the player or in front of the player,
+//! the smoothing of the camera and the bobbing of the view while walking.
+use history_survival_common::physics::{player::YawPitch, raycast::raycast, BlockContainer};
+use nalgebra::Vector3;
+
+/// Distance between the eyes of the player and the camera in the third-person modes, in blocks
+const THIRD_PERSON_DISTANCE: f64 = 4.0;
+/// The camera stays this far from the blocks between it and the player, so that the view doesn't clip into them
+const CAMERA_MARGIN: f64 = 0.25;
+
+/// How fast the smoothed camera follows the eyes of the player: the difference shrinks by a factor e every
+/// 1 / SMOOTHING_SPEED seconds
+const SMOOTHING_SPEED: f64 = 10.0;
+/// The smoothed camera jumps to the eyes of the player when they are farther than this, for example after a teleport
+const SMOOTHING_MAX_DISTANCE: f64 = 8.0;
+/// Horizontal speed at which the view bobs the most, in blocks per second
+const BOBBING_FULL_SPEED: f64 = 7.0;
+/// Number of steps of the player per block that it walks. The view goes down once per step.
+const BOBBING_STEPS_PER_BLOCK: f64 = 0.6;
+/// How far the view moves sideways and down during a step, in blocks
+const BOBBING_AMPLITUDE: (f64, f64) = (0.04, 0.05);
+/// How much the view rolls during a step, in degrees
+const BOBBING_ROLL: f64 = 0.6;
+/// How fast the bobbing fades in and out when the player starts and stops walking
+const BOBBING_FADE_SPEED: f64 = 8.0;
+
+/// Where the view is placed relative to the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    /// Behind the player, looking in the same direction
+    ThirdPersonBack,
+    /// In front of the player, looking at the player
+    ThirdPersonFront,
+}
+
+impl CameraMode {
+    /// The next mode of the cycle
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::FirstPerson => CameraMode::ThirdPersonBack,
+            CameraMode::ThirdPersonBack => CameraMode::ThirdPersonFront,
+            CameraMode::ThirdPersonFront => CameraMode::FirstPerson,
+        }
+    }
+
+    /// Whether the model of the player is drawn, instead of the held item
+    pub fn shows_player(self) -> bool {
+        self != CameraMode::FirstPerson
+    }
+
+    /// The position and the orientation of the camera, for the eyes of the player at `eye` looking towards `yaw_pitch`.
+    /// The camera is brought closer to the player when blocks are in the way.
+    pub fn place<BC: BlockContainer>(self, eye: Vector3<f64>, yaw_pitch: YawPitch, world: &BC) -> (Vector3<f64>, YawPitch) {
+        let (yaw, pitch) = (yaw_pitch.yaw.to_radians(), yaw_pitch.pitch.to_radians());
+        let look = Vector3::new(-yaw.sin() * pitch.cos(), pitch.sin(), -yaw.cos() * pitch.cos());
+        let (direction, yaw_pitch) = match self {
+            CameraMode::FirstPerson => return (eye, yaw_pitch),
+            CameraMode::ThirdPersonBack => (-look, yaw_pitch),
+            CameraMode::ThirdPersonFront => (look, YawPitch { yaw: yaw_pitch.yaw + 180.0, pitch: -yaw_pitch.pitch }),
+        };
+        let distance = match raycast(eye, direction, THIRD_PERSON_DISTANCE + CAMERA_MARGIN, world) {
+            Some(hit) => ((hit.pos - eye).norm() - CAMERA_MARGIN).max(0.0),
+            None => THIRD_PERSON_DISTANCE,
+        };
+        (eye + direction * distance, yaw_pitch)
+    }
+}
+
+/// The camera that follows the eyes of the player, with some damping when the smoothing is enabled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmoothCamera {
+    current: Option<(Vector3<f64>, YawPitch)>,
+}
+
+impl SmoothCamera {
+    /// Move the camera towards the eyes of the player, after `seconds_delta` seconds since the last update
+    pub fn update(&mut self, eye: Vector3<f64>, yaw_pitch: YawPitch, seconds_delta: f64, smoothing: bool) {
+        self.current = match self.current {
+            Some((position, current)) if smoothing && (eye - position).norm() < SMOOTHING_MAX_DISTANCE => {
+                let factor = 1.0 - (-SMOOTHING_SPEED * seconds_delta).exp();
+                // The yaw turns the shortest way
+                let yaw_difference = (yaw_pitch.yaw - current.yaw + 180.0).rem_euclid(360.0) - 180.0;
+                let yaw_pitch = YawPitch {
+                    yaw: current.yaw + yaw_difference * factor,
+                    pitch: current.pitch + (yaw_pitch.pitch - current.pitch) * factor,
+                };
+                Some((position + (eye - position) * factor, yaw_pitch))
+            }
+            _ => Some((eye, yaw_pitch)),
+        };
+    }
+
+    /// The position and the orientation of the camera, or the ones of the eyes before the first update
+    pub fn get(&self, eye: Vector3<f64>, yaw_pitch: YawPitch) -> (Vector3<f64>, YawPitch) {
+        self.current.unwrap_or((eye, yaw_pitch))
+    }
+}
+
+/// The bobbing of the view while the player walks on the ground
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ViewBobbing {
+    /// How far the player has walked, in steps
+    phase: f64,
+    /// How much the view bobs, from 0 when the player stands still or is in the air to 1 at full speed
+    amount: f64,
+}
+
+impl ViewBobbing {
+    /// Advance the bobbing with the velocity of the player, after `seconds_delta` seconds since the last update
+    pub fn update(&mut self, velocity: Vector3<f64>, on_the_ground: bool, seconds_delta: f64) {
+        let horizontal_speed = (velocity.x * velocity.x + velocity.z * velocity.z).sqrt();
+        let target = if on_the_ground { (horizontal_speed / BOBBING_FULL_SPEED).min(1.0) } else { 0.0 };
+        self.amount += (target - self.amount) * (1.0 - (-BOBBING_FADE_SPEED * seconds_delta).exp());
+        self.phase = (self.phase + horizontal_speed * BOBBING_STEPS_PER_BLOCK * seconds_delta) % 2.0;
+    }
+
+    /// The offset of the view in the space of the camera and its roll in degrees
+    pub fn offset(&self) -> (Vector3<f64>, f64) {
+        // The view sways from one side to the other every two steps, and goes down at every step
+        let (sin, cos) = (self.phase * std::f64::consts::PI).sin_cos();
+        let offset = Vector3::new(
+            sin * BOBBING_AMPLITUDE.0 * self.amount,
+            -(1.0 - cos.abs()) * BOBBING_AMPLITUDE.1 * self.amount,
+            0.0,
+        );
+        (offset, sin * BOBBING_ROLL * self.amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use history_survival_common::world::BlockPos;
+
+    /// A wall of full blocks at z = 2
+    struct Wall;
+
+    impl BlockContainer for Wall {
+        fn is_block_full(&self, pos: BlockPos) -> bool {
+            pos.pz == 2
+        }
+
+        fn is_block_climbable(&self, _pos: BlockPos) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn camera_stops_in_front_of_the_blocks() {
+        let eye = Vector3::new(0.5, 1.5, 0.5);
+        // Yaw 0 looks towards -z, so the camera behind the player goes towards the wall
+        let yaw_pitch = YawPitch { yaw: 0.0, pitch: 0.0 };
+        assert_eq!(CameraMode::FirstPerson.place(eye, yaw_pitch, &Wall).0, eye);
+        let (back, _) = CameraMode::ThirdPersonBack.place(eye, yaw_pitch, &Wall);
+        assert!((back.z - (2.0 - CAMERA_MARGIN)).abs() < 1e-4);
+        let (front, front_yaw_pitch) = CameraMode::ThirdPersonFront.place(eye, yaw_pitch, &Wall);
+        assert!((front.z - (0.5 - THIRD_PERSON_DISTANCE)).abs() < 1e-6);
+        assert_eq!(front_yaw_pitch.yaw, 180.0);
+        assert_eq!(CameraMode::ThirdPersonFront.next(), CameraMode::FirstPerson);
+    }
+
+    #[test]
+    fn view_bobs_only_while_walking_on_the_ground() {
+        let mut bobbing = ViewBobbing::default();
+        let walking = Vector3::new(BOBBING_FULL_SPEED, 0.0, 0.0);
+        bobbing.update(walking, true, 0.3);
+        let (offset, roll) = bobbing.offset();
+        assert!(offset.y < 0.0 && roll != 0.0);
+        for _ in 0..100 {
+            bobbing.update(walking, false, 0.1);
+        }
+        assert!(bobbing.offset().0.norm() < 1e-6);
+    }
+
+    #[test]
+    fn smooth_camera_turns_the_shortest_way() {
+        let mut camera = SmoothCamera::default();
+        let eye = Vector3::zeros();
+        camera.update(eye, YawPitch { yaw: 350.0, pitch: 0.0 }, 0.1, true);
+        camera.update(eye, YawPitch { yaw: 10.0, pitch: 0.0 }, 0.1, true);
+        let yaw = camera.get(eye, YawPitch::default()).1.yaw;
+        assert!(yaw > 350.0 && yaw < 370.0);
+        camera.update(eye, YawPitch { yaw: 10.0, pitch: 0.0 }, 0.1, false);
+        assert_eq!(camera.get(eye, YawPitch::default()).1.yaw, 10.0);
+    }
+}
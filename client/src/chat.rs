@@ -0,0 +1,128 @@
+//! The messages of the chat, shown over the game for a while after they arrive and kept in a history
+//! that can be scrolled while the chat is open.
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of messages kept in the history, the oldest ones are forgotten
+const HISTORY_LENGTH: usize = 100;
+/// How long the messages stay over the game while the chat is closed, including their fading
+const MESSAGE_DURATION: Duration = Duration::from_secs(10);
+/// How long the messages take to fade out at the end of their duration
+const FADE_DURATION: Duration = Duration::from_secs(2);
+
+/// A message of the chat, with when it was received
+#[derive(Debug, Clone)]
+struct ReceivedMessage {
+    text: String,
+    received_at: Instant,
+}
+
+/// The history of the chat and the message being typed
+#[derive(Debug, Default)]
+pub struct Chat {
+    messages: VecDeque<ReceivedMessage>,
+    /// The message being typed, sent when Enter is pressed
+    pub input: String,
+    /// By how many messages the history is scrolled up from the latest message
+    scroll: usize,
+}
+
+impl Chat {
+    /// Add a message to the history
+    pub fn receive(&mut self, text: String, now: Instant) {
+        self.messages.push_back(ReceivedMessage { text, received_at: now });
+        if self.messages.len() > HISTORY_LENGTH {
+            self.messages.pop_front();
+        }
+        // The history doesn't move under the player who is reading it
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.messages.len().saturating_sub(1));
+        }
+    }
+
+    /// Scroll the history up by some messages, or down if `messages` is negative
+    pub fn scroll(&mut self, messages: isize) {
+        let max_scroll = self.messages.len().saturating_sub(1) as isize;
+        self.scroll = (self.scroll as isize + messages).clamp(0, max_scroll) as usize;
+    }
+
+    /// Take the message that was typed, if it isn't empty, and go back to the latest messages
+    pub fn take_input(&mut self) -> Option<String> {
+        self.scroll = 0;
+        let input = std::mem::take(&mut self.input);
+        Some(input.trim().to_owned()).filter(|input| !input.is_empty())
+    }
+
+    /// Forget the message being typed and go back to the latest messages
+    pub fn cancel_input(&mut self) {
+        self.input.clear();
+        self.scroll = 0;
+    }
+
+    /// The last `lines` messages of the history before the scroll, oldest first
+    pub fn history(&self, lines: usize) -> impl Iterator<Item = &str> + '_ {
+        let end = self.messages.len() - self.scroll;
+        let start = end.saturating_sub(lines);
+        self.messages.range(start..end).map(|message| message.text.as_str())
+    }
+
+    /// The last `lines` messages that are still shown over the game, oldest first, with their opacity from 0 to 1
+    pub fn recent_messages(&self, lines: usize, now: Instant) -> Vec<(&str, f32)> {
+        let mut recent = self
+            .messages
+            .iter()
+            .rev()
+            .take(lines)
+            .map(|message| (message.text.as_str(), opacity(now.saturating_duration_since(message.received_at))))
+            .take_while(|(_, opacity)| *opacity > 0.0)
+            .collect::<Vec<_>>();
+        recent.reverse();
+        recent
+    }
+}
+
+/// The opacity of a message that was received some time ago
+fn opacity(age: Duration) -> f32 {
+    let remaining = MESSAGE_DURATION.checked_sub(age).unwrap_or_default();
+    (remaining.as_secs_f32() / FADE_DURATION.as_secs_f32()).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_fade_out_and_stay_in_the_history() {
+        let start = Instant::now();
+        let mut chat = Chat::default();
+        chat.receive("first".to_owned(), start);
+        chat.receive("second".to_owned(), start + Duration::from_secs(5));
+        assert_eq!(chat.recent_messages(10, start), vec![("first", 1.0), ("second", 1.0)]);
+        assert_eq!(chat.recent_messages(1, start), vec![("second", 1.0)]);
+        // The first message fades out during the last seconds of its duration
+        assert_eq!(chat.recent_messages(10, start + Duration::from_secs(9)), vec![("first", 0.5), ("second", 1.0)]);
+        assert_eq!(chat.recent_messages(10, start + Duration::from_secs(12)), vec![("second", 1.0)]);
+        assert_eq!(chat.history(10).collect::<Vec<_>>(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn the_history_scrolls_until_the_oldest_message() {
+        let now = Instant::now();
+        let mut chat = Chat::default();
+        for i in 0..5 {
+            chat.receive(i.to_string(), now);
+        }
+        chat.scroll(2);
+        assert_eq!(chat.history(2).collect::<Vec<_>>(), vec!["1", "2"]);
+        // A new message doesn't move the scrolled history
+        chat.receive("5".to_owned(), now);
+        assert_eq!(chat.history(2).collect::<Vec<_>>(), vec!["1", "2"]);
+        chat.scroll(10);
+        assert_eq!(chat.history(2).collect::<Vec<_>>(), vec!["0"]);
+        chat.scroll(-10);
+        assert_eq!(chat.history(2).collect::<Vec<_>>(), vec!["4", "5"]);
+        chat.input = "  hello ".to_owned();
+        assert_eq!(chat.take_input(), Some("hello".to_owned()));
+        assert_eq!(chat.take_input(), None);
+    }
+}
@@ -0,0 +1,103 @@
+//! Periodic consistency check of the client world, to repair the holes left by lost chunks or meshes,
+//! and the chunks that are out of sync with the server.
+use crate::world::World;
+use history_survival_common::{
+    debug::send_debug_info,
+    player::RenderDistance,
+    world::ChunkPos,
+};
+use log::warn;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Time between two checks
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a chunk in render distance can be missing before it is requested again
+const MISSING_CHUNK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a chunk can stay in the meshing queue before it is meshed again
+const MESHING_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum number of chunks requested again in a single check
+const MAX_REQUESTED_CHUNKS: usize = 64;
+
+/// Finds the chunks that were never received and the chunks that were never meshed
+pub struct ConsistencyChecker {
+    last_check: Instant,
+    /// The chunks in render distance that are missing, with the time they were first found missing
+    missing_since: HashMap<ChunkPos, Instant>,
+    /// Total number of chunks requested again
+    total_requested: usize,
+    /// Total number of chunks queued for meshing again
+    total_requeued: usize,
+    /// Total number of chunks whose hash didn't match the hash sent by the server
+    total_out_of_sync: usize,
+}
+
+impl ConsistencyChecker {
+    pub fn new() -> Self {
+        Self {
+            last_check: Instant::now(),
+            missing_since: HashMap::new(),
+            total_requested: 0,
+            total_requeued: 0,
+            total_out_of_sync: 0,
+        }
+    }
+
+    /// Compare the chunks of the world with the hashes sent by the server, and return the chunks that are out of sync
+    /// and must be requested again. The chunks that are not in the world are left to the periodic check.
+    pub fn verify_hashes(&mut self, world: &World, hashes: &[(ChunkPos, u64)]) -> Vec<ChunkPos> {
+        let out_of_sync = hashes
+            .iter()
+            .filter(|(pos, hash)| world.get_chunk_hash(*pos).map(|client_hash| client_hash != *hash).unwrap_or(false))
+            .map(|(pos, _)| *pos)
+            .collect::<Vec<_>>();
+        for pos in out_of_sync.iter() {
+            warn!("Chunk {:?} is out of sync with the server, requesting it again", pos);
+        }
+        self.total_out_of_sync += out_of_sync.len();
+        out_of_sync
+    }
+
+    /// Periodically check the chunks around the player. Queue the unmeshed chunks for meshing again,
+    /// and return the chunks that must be requested from the server again.
+    pub fn update(&mut self, world: &mut World, player_chunk: ChunkPos, render_distance: &RenderDistance, now: Instant) -> Vec<ChunkPos> {
+        if now - self.last_check < CHECK_INTERVAL {
+            return Vec::new();
+        }
+        self.last_check = now;
+
+        // Forget the chunks that arrived or that are out of range
+        let missing_since = &mut self.missing_since;
+        missing_since.retain(|pos, _| render_distance.is_chunk_visible(player_chunk, *pos) && !world.has_chunk(*pos));
+        let mut requested_chunks = Vec::new();
+        for pos in render_distance.iterate_around_player(player_chunk) {
+            if world.has_chunk(pos) {
+                continue;
+            }
+            let since = *missing_since.entry(pos).or_insert(now);
+            if now - since >= MISSING_CHUNK_TIMEOUT && requested_chunks.len() < MAX_REQUESTED_CHUNKS {
+                requested_chunks.push(pos);
+                // Give the server time to send it again
+                missing_since.insert(pos, now);
+            }
+        }
+        let requeued = world.requeue_unmeshed_chunks(now, MESHING_TIMEOUT);
+
+        self.total_requested += requested_chunks.len();
+        self.total_requeued += requeued;
+        send_debug_info(
+            "Chunks",
+            "consistency",
+            format!(
+                "Missing chunks = {}, requested again = {} (total {}), meshed again = {} (total {}), out of sync = {}",
+                self.missing_since.len(),
+                requested_chunks.len(),
+                self.total_requested,
+                requeued,
+                self.total_requeued,
+                self.total_out_of_sync
+            ),
+        );
+        requested_chunks
+    }
+}
@@ -0,0 +1,44 @@
+//! Client-side smoothing of the entity positions.
+use history_survival_common::{entity::EntityId, physics::simulation::ClientPhysicsSimulation};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// How fast the rendered positions catch up with the simulated positions, in 1/s
+const CATCH_UP_RATE: f64 = 15.0;
+/// Rendered positions that are further than this from the simulated positions are snapped
+const SNAP_DISTANCE: f64 = 4.0;
+
+/// The rendered positions of the entities. They follow the interpolated simulated positions smoothly,
+/// hiding the corrections that happen when a new server state is received.
+pub struct InterpolatedEntities {
+    positions: HashMap<EntityId, Vector3<f64>>,
+}
+
+impl InterpolatedEntities {
+    pub fn new() -> Self {
+        Self {
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Move the rendered positions towards the simulated positions
+    pub fn update(&mut self, physics_simulation: &ClientPhysicsSimulation, seconds_delta: f64) {
+        let entities = physics_simulation.get_entities();
+        self.positions.retain(|id, _| entities.contains_key(id));
+        let factor = 1.0 - (-CATCH_UP_RATE * seconds_delta).exp();
+        for id in entities.keys() {
+            let target = physics_simulation.get_interpolated_entity_position(*id).unwrap();
+            let position = self.positions.entry(*id).or_insert(target);
+            if (target - *position).norm() > SNAP_DISTANCE {
+                *position = target;
+            } else {
+                *position += (target - *position) * factor;
+            }
+        }
+    }
+
+    /// Get the rendered position of the aabb of an entity
+    pub fn get_position(&self, id: EntityId) -> Option<Vector3<f64>> {
+        self.positions.get(&id).cloned()
+    }
+}
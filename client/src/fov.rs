@@ -0,0 +1,60 @@
+//! The field of view of the camera, which widens while the player sprints and narrows while the zoom key is held.
+//! The field of view moves smoothly towards its target every frame.
+use crate::settings::Settings;
+
+/// The field of view is kept between these angles, in degrees, so that the projection stays valid
+const FOV_RANGE: (f64, f64) = (10.0, 170.0);
+/// How fast the field of view reaches its target: the difference shrinks by a factor e every 1 / FOV_SPEED seconds
+const FOV_SPEED: f64 = 12.0;
+
+/// The field of view that the camera moves towards, in degrees
+pub fn target_fov(settings: &Settings, sprinting: bool, zooming: bool) -> f64 {
+    let fov = if zooming {
+        settings.zoom_fov
+    } else if sprinting {
+        settings.fov + settings.sprint_fov_increase
+    } else {
+        settings.fov
+    };
+    fov.max(FOV_RANGE.0).min(FOV_RANGE.1)
+}
+
+/// The current field of view of the camera
+#[derive(Debug, Clone, Copy)]
+pub struct FieldOfView {
+    current: f64,
+}
+
+impl FieldOfView {
+    pub fn new(fov: f64) -> Self {
+        Self { current: fov }
+    }
+
+    /// Move the field of view towards the target, after `seconds_delta` seconds since the last update
+    pub fn update(&mut self, target: f64, seconds_delta: f64) {
+        self.current += (target - self.current) * (1.0 - (-FOV_SPEED * seconds_delta).exp());
+    }
+
+    /// The field of view in degrees
+    pub fn degrees(&self) -> f64 {
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fov_moves_smoothly_to_the_target() {
+        let settings = Settings::default();
+        let mut fov = FieldOfView::new(target_fov(&settings, false, false));
+        let sprint = target_fov(&settings, true, false);
+        assert_eq!(sprint, settings.fov + settings.sprint_fov_increase);
+        assert_eq!(target_fov(&settings, true, true), settings.zoom_fov);
+        fov.update(sprint, 0.05);
+        assert!(fov.degrees() > settings.fov && fov.degrees() < sprint);
+        fov.update(sprint, 2.0);
+        assert!((fov.degrees() - sprint).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,7 @@
-use std::{collections::VecDeque, time::Instant};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
 const SECONDS_DIFFERENCE: u64 = 2;
 
@@ -28,4 +31,51 @@ impl FpsCounter {
     pub fn fps(&self) -> usize {
         self.frames.len() / SECONDS_DIFFERENCE as usize
     }
+
+    /// The time between the last `count` frames and the frames before them, oldest first
+    pub fn recent_frame_times(&self, count: usize) -> Vec<Duration> {
+        let intervals = self.frames.len().saturating_sub(1);
+        let skipped = intervals.saturating_sub(count);
+        self.frames.iter().zip(self.frames.iter().skip(1)).skip(skipped).map(|(previous, frame)| *frame - *previous).collect()
+    }
+}
+
+/// Spaces the frames to cap the frame rate
+#[derive(Debug, Default)]
+pub struct FrameLimiter {
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// How long to wait before starting a frame, so that there are at most `max_fps` frames per second.
+    /// The frames that are late start right away, without making the next ones catch up.
+    pub fn wait_time(&mut self, now: Instant, max_fps: Option<u32>) -> Duration {
+        let max_fps = match max_fps.filter(|max_fps| *max_fps > 0) {
+            Some(max_fps) => max_fps,
+            None => {
+                self.next_frame = None;
+                return Duration::from_secs(0);
+            }
+        };
+        let start = self.next_frame.filter(|next_frame| *next_frame > now).unwrap_or(now);
+        self.next_frame = Some(start + Duration::from_secs(1) / max_fps);
+        start - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_spaced_without_catching_up() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::default();
+        assert_eq!(limiter.wait_time(start, Some(10)), Duration::from_secs(0));
+        assert_eq!(limiter.wait_time(start + Duration::from_millis(30), Some(10)), Duration::from_millis(70));
+        // A slow frame doesn't shorten the next ones
+        assert_eq!(limiter.wait_time(start + Duration::from_millis(500), Some(10)), Duration::from_secs(0));
+        assert_eq!(limiter.wait_time(start + Duration::from_millis(550), Some(10)), Duration::from_millis(50));
+        assert_eq!(limiter.wait_time(start + Duration::from_millis(560), None), Duration::from_secs(0));
+    }
 }
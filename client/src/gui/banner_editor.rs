@@ -0,0 +1,133 @@
+use history_survival_common::{
+    block::banner::{BannerColor, BannerPattern, BannerPatternKind, MAX_BANNER_LAYERS},
+    world::BlockPos,
+};
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const EDITOR_WIDTH: i32 = 400;
+const COLOR_BUTTON_WIDTH: i32 = 46;
+/// Number of cells on each side of the preview
+const PREVIEW_CELLS: i32 = 16;
+/// Side of a cell of the preview, in pixels
+const PREVIEW_CELL_SIZE: i32 = 8;
+/// Offset of the ids of the editor buttons, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 2 << 20;
+
+/// What the player chose to do with the edited banner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BannerEditorResult {
+    /// Put the edited pattern on the banner
+    Apply,
+    /// Close the editor without changing the banner
+    Cancel,
+}
+
+/// The state of the banner editor
+pub struct BannerEditor {
+    /// The position of the edited banner
+    pub pos: BlockPos,
+    /// The edited pattern
+    pub pattern: BannerPattern,
+    /// The color of the next layer or of the base
+    color: BannerColor,
+}
+
+impl BannerEditor {
+    pub fn new(pos: BlockPos, pattern: BannerPattern) -> Self {
+        Self {
+            pos,
+            pattern,
+            color: BannerColor::Black,
+        }
+    }
+}
+
+fn to_gui_color(color: BannerColor) -> [f32; 4] {
+    let [r, g, b] = color.rgb();
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0]
+}
+
+/// Draw the banner editor in the middle of the screen, returning what the player chose to do once they are done
+pub fn render_banner_editor(gui: &mut super::Gui, window_width: i32, editor: &mut BannerEditor) -> Option<BannerEditorResult> {
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let black = [0.0, 0.0, 0.0, 1.0];
+    let x = (window_width - EDITOR_WIDTH) / 2;
+    let mut y = 4 + ELEMENT_OFFSET;
+    let mut id = BUTTON_ID_OFFSET;
+    let mut next_id = || {
+        id += 1;
+        id
+    };
+    gui.text(x, y, ELEMENT_HEIGHT, "BANNER".to_owned(), white, 0.02);
+    y += ELEMENT_OFFSET;
+
+    // Preview of the pattern
+    for i in 0..PREVIEW_CELLS {
+        for j in 0..PREVIEW_CELLS {
+            let u = (i as f64 + 0.5) / PREVIEW_CELLS as f64;
+            let v = (j as f64 + 0.5) / PREVIEW_CELLS as f64;
+            let color = to_gui_color(editor.pattern.color_at(u, v));
+            gui.primitives.draw_rect(
+                x + i * PREVIEW_CELL_SIZE,
+                y + j * PREVIEW_CELL_SIZE,
+                PREVIEW_CELL_SIZE,
+                PREVIEW_CELL_SIZE,
+                color,
+                0.01,
+            );
+        }
+    }
+    y += PREVIEW_CELLS * PREVIEW_CELL_SIZE + 8;
+    gui.text(
+        x,
+        y,
+        ELEMENT_HEIGHT,
+        format!("Layers: {}/{}", editor.pattern.layers.len(), MAX_BANNER_LAYERS),
+        white,
+        0.02,
+    );
+    y += ELEMENT_OFFSET;
+
+    // Palette
+    for (i, &color) in BannerColor::ALL.iter().enumerate() {
+        let name = format!("{:?}", color);
+        let text = if color == editor.color { format!("[{}]", name) } else { name };
+        let button_x = x + i as i32 * (COLOR_BUTTON_WIDTH + 4);
+        if gui.button(next_id(), button_x, y, COLOR_BUTTON_WIDTH, ELEMENT_HEIGHT).text(text, to_gui_color(color)).build() {
+            editor.color = color;
+        }
+    }
+    y += ELEMENT_OFFSET;
+    if gui.button(next_id(), x, y, EDITOR_WIDTH, ELEMENT_HEIGHT).text("Set base color".to_owned(), black).build() {
+        editor.pattern.base = editor.color;
+    }
+    y += ELEMENT_OFFSET;
+
+    // Patterns, in two columns. Clicking a pattern adds a layer with the selected color.
+    let can_add_layer = editor.pattern.layers.len() < MAX_BANNER_LAYERS;
+    let column_width = (EDITOR_WIDTH - 4) / 2;
+    let rows = (BannerPatternKind::ALL.len() as i32 + 1) / 2;
+    for (i, &kind) in BannerPatternKind::ALL.iter().enumerate() {
+        let (column, row) = (i as i32 / rows, i as i32 % rows);
+        let text_color = if can_add_layer { black } else { [0.4, 0.4, 0.4, 1.0] };
+        let button = gui.button(next_id(), x + column * (column_width + 4), y + row * ELEMENT_OFFSET, column_width, ELEMENT_HEIGHT);
+        if button.text(format!("Add {:?}", kind), text_color).build() && can_add_layer {
+            editor.pattern.layers.push((kind, editor.color));
+        }
+    }
+    y += rows * ELEMENT_OFFSET;
+    if gui.button(next_id(), x, y, EDITOR_WIDTH, ELEMENT_HEIGHT).text("Remove last layer".to_owned(), black).build() {
+        editor.pattern.layers.pop();
+    }
+    y += ELEMENT_OFFSET;
+
+    let mut result = None;
+    if gui.button(next_id(), x, y, column_width, ELEMENT_HEIGHT).text("Done".to_owned(), black).build() {
+        result = Some(BannerEditorResult::Apply);
+    }
+    if gui.button(next_id(), x + column_width + 4, y, column_width, ELEMENT_HEIGHT).text("Cancel".to_owned(), black).build() {
+        result = Some(BannerEditorResult::Cancel);
+    }
+    result
+}
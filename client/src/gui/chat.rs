@@ -0,0 +1,37 @@
+//! The chat in the bottom-left corner of the screen
+use crate::chat::Chat;
+use std::time::Instant;
+
+const ELEMENT_HEIGHT: i32 = 20;
+const CHAT_WIDTH: i32 = 500;
+/// Number of messages shown above the input line
+pub const CHAT_LINES: usize = 10;
+/// The messages that can be typed are as long as the ones that the server accepts
+const MAX_INPUT_LENGTH: usize = 256;
+/// Id of the input line, so that it doesn't collide with the other text fields
+const INPUT_ID: u32 = 6 << 20;
+
+/// Draw the chat: the history and the input line when it is open, and the recent messages fading out when it is closed.
+/// Return whether Enter was pressed in the input line.
+pub fn render_chat(gui: &mut super::Gui, window_height: i32, chat: &mut Chat, open: bool, now: Instant) -> bool {
+    let x = 4;
+    let input_y = window_height - 4 - ELEMENT_HEIGHT;
+    let messages = if open {
+        chat.history(CHAT_LINES).map(|message| (message, 1.0)).collect::<Vec<_>>()
+    } else {
+        chat.recent_messages(CHAT_LINES, now)
+    };
+    // The latest message is right above the input line
+    let top = input_y - messages.len() as i32 * ELEMENT_HEIGHT;
+    for (i, (message, opacity)) in messages.into_iter().enumerate() {
+        let y = top + i as i32 * ELEMENT_HEIGHT;
+        gui.primitives.draw_rect(x, y, CHAT_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5 * opacity], 0.02);
+        gui.text(x + 6, y, ELEMENT_HEIGHT, message.to_owned(), [1.0, 1.0, 1.0, opacity], 0.01);
+    }
+    if !open {
+        return false;
+    }
+    // The input line keeps the keyboard until the chat is closed
+    gui.focus_text_field(INPUT_ID);
+    gui.text_field(INPUT_ID, x, input_y, CHAT_WIDTH, ELEMENT_HEIGHT).max_length(MAX_INPUT_LENGTH).build(&mut chat.input)
+}
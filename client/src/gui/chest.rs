@@ -0,0 +1,151 @@
+use history_survival_common::{
+    block::chest::ChestSlot,
+    inventory::{Inventory, ItemStack, HOTBAR_SIZE},
+    item::Item,
+    registry::Registry,
+    world::BlockPos,
+};
+
+pub(super) const ELEMENT_HEIGHT: i32 = 20;
+pub(super) const ELEMENT_OFFSET: i32 = 25;
+/// Number of slots in a row of the chest and of the player inventory
+pub(super) const SLOTS_PER_ROW: usize = 9;
+pub(super) const SLOT_WIDTH: i32 = 96;
+pub(super) const SLOT_OFFSET: i32 = 100;
+/// Offset of the ids of the chest buttons, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 4 << 20;
+
+/// What the player did in the chest screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChestScreenResult {
+    /// Move the stack of a slot to another slot
+    Move(ChestSlot, ChestSlot),
+    /// Close the chest
+    Close,
+}
+
+/// The state of the screen of an open chest
+pub struct ChestScreen {
+    /// The position of the chest
+    pub pos: BlockPos,
+    /// The contents of the chest, as sent by the server
+    pub inventory: Inventory,
+    /// The slot whose stack is moved by the next click on a slot
+    selected: Option<ChestSlot>,
+}
+
+impl ChestScreen {
+    pub fn new(pos: BlockPos, inventory: Inventory) -> Self {
+        Self {
+            pos,
+            inventory,
+            selected: None,
+        }
+    }
+
+    /// Update a slot of the chest, when the player or another player moved items
+    pub fn set_slot(&mut self, slot: usize, stack: Option<ItemStack>) {
+        self.inventory.set(slot, stack);
+    }
+}
+
+/// Draw the slots of an inventory in rows, returning the slot that was clicked
+pub(super) fn render_slots(
+    gui: &mut super::Gui,
+    x: i32,
+    y: &mut i32,
+    first_id: u32,
+    inventory: &Inventory,
+    items: &Registry<Item>,
+    selected: Option<usize>,
+) -> Option<usize> {
+    let mut clicked_slot = None;
+    for (slot, stack) in inventory.slots().enumerate() {
+        let column = (slot % SLOTS_PER_ROW) as i32;
+        let row = (slot / SLOTS_PER_ROW) as i32;
+        let text = match stack {
+            Some(stack) => {
+                let name = items.get_value_by_id(stack.item_id).map(|item| item.name.as_str()).unwrap_or("?");
+                format!("{} {}", stack.count, name)
+            }
+            None => String::new(),
+        };
+        let text = if selected == Some(slot) { format!("[{}]", text) } else { text };
+        let button = gui.button(first_id + slot as u32, x + column * SLOT_OFFSET, *y + row * ELEMENT_OFFSET, SLOT_WIDTH, ELEMENT_HEIGHT);
+        if button.text(text, [0.0, 0.0, 0.0, 1.0]).build() {
+            clicked_slot = Some(slot);
+        }
+    }
+    let rows = inventory.len().div_ceil(SLOTS_PER_ROW);
+    *y += rows as i32 * ELEMENT_OFFSET;
+    clicked_slot
+}
+
+/// Draw the chest above the inventory of the player in the middle of the screen.
+/// Clicking a stack selects it, and clicking another slot moves the selected stack there.
+pub fn render_chest_screen(
+    gui: &mut super::Gui,
+    window_width: i32,
+    screen: &mut ChestScreen,
+    player_inventory: &Inventory,
+    items: &Registry<Item>,
+) -> Option<ChestScreenResult> {
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let width = SLOTS_PER_ROW as i32 * SLOT_OFFSET;
+    let x = (window_width - width) / 2;
+    let mut y = 4 + ELEMENT_OFFSET;
+    gui.text(x, y, ELEMENT_HEIGHT, "CHEST".to_owned(), white, 0.02);
+    y += ELEMENT_OFFSET;
+    let selected_chest_slot = match screen.selected {
+        Some(ChestSlot::Chest(slot)) => Some(slot),
+        _ => None,
+    };
+    let clicked_chest_slot = render_slots(gui, x, &mut y, BUTTON_ID_OFFSET, &screen.inventory, items, selected_chest_slot);
+    y += ELEMENT_OFFSET / 2;
+    gui.text(x, y, ELEMENT_HEIGHT, "INVENTORY".to_owned(), white, 0.02);
+    y += ELEMENT_OFFSET;
+    let selected_player_slot = match screen.selected {
+        Some(ChestSlot::Player(slot)) => Some(slot),
+        _ => None,
+    };
+    let first_player_id = BUTTON_ID_OFFSET + screen.inventory.len() as u32;
+    let clicked_player_slot = render_slots(gui, x, &mut y, first_player_id, player_inventory, items, selected_player_slot);
+    gui.text(x, y, ELEMENT_HEIGHT, format!("The first {} slots are the hotbar", HOTBAR_SIZE), white, 0.02);
+    y += ELEMENT_OFFSET;
+
+    let clicked = clicked_chest_slot
+        .map(ChestSlot::Chest)
+        .or_else(|| clicked_player_slot.map(ChestSlot::Player));
+    let mut result = click_slot(&mut screen.selected, clicked, &screen.inventory, player_inventory)
+        .map(|(from, to)| ChestScreenResult::Move(from, to));
+    let close_id = first_player_id + player_inventory.len() as u32;
+    if gui.button(close_id, x, y, width - (SLOT_OFFSET - SLOT_WIDTH), ELEMENT_HEIGHT).text("Close".to_owned(), [0.0, 0.0, 0.0, 1.0]).build() {
+        result = Some(ChestScreenResult::Close);
+    }
+    result
+}
+
+/// Select the clicked slot if it has a stack, or move the stack of the selected slot to the clicked slot.
+/// Return the move, if any.
+pub(super) fn click_slot(
+    selected: &mut Option<ChestSlot>,
+    clicked: Option<ChestSlot>,
+    container: &Inventory,
+    player_inventory: &Inventory,
+) -> Option<(ChestSlot, ChestSlot)> {
+    let clicked = clicked?;
+    let clicked_stack = match clicked {
+        ChestSlot::Chest(slot) => container.get(slot),
+        ChestSlot::Player(slot) => player_inventory.get(slot),
+    };
+    match selected.take() {
+        Some(selected) if selected != clicked => Some((selected, clicked)),
+        Some(_) => None,
+        None => {
+            if clicked_stack.is_some() {
+                *selected = Some(clicked);
+            }
+            None
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use history_survival_common::{
+    crafting::{can_craft, Recipe, RecipeId},
+    inventory::Inventory,
+    item::Item,
+    registry::Registry,
+};
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const CRAFTING_WIDTH: i32 = 320;
+/// Offset of the ids of the recipe buttons, so that they don't collide with the debug info buttons
+const BUTTON_ID_OFFSET: u32 = 1 << 20;
+
+/// Draw the recipes on the right of the screen, returning the recipe that was clicked
+pub fn render_crafting(
+    gui: &mut super::Gui,
+    window_width: i32,
+    recipes: &Registry<Recipe>,
+    items: &Registry<Item>,
+    inventory: &Inventory,
+) -> Option<RecipeId> {
+    let item_name = |item_id| items.get_value_by_id(item_id).map(|item| item.name.as_str()).unwrap_or("?");
+    let x = window_width - CRAFTING_WIDTH - 4;
+    let mut y = 4 + ELEMENT_OFFSET;
+    gui.text(x, y, ELEMENT_HEIGHT, "CRAFTING".to_owned(), [1.0, 1.0, 1.0, 1.0], 0.02);
+    y += ELEMENT_OFFSET;
+    let mut clicked_recipe = None;
+    for recipe_id in 0..recipes.get_number_of_ids() {
+        let recipe = recipes.get_value_by_id(recipe_id).unwrap();
+        let ingredients = recipe
+            .ingredients
+            .iter()
+            .map(|&(item_id, count)| format!("{} {}", count, item_name(item_id)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let text = format!("{} {} <- {}", recipe.result.count, item_name(recipe.result.item_id), ingredients);
+        // Recipes that can't be crafted are grayed out
+        let color = if can_craft(inventory, recipe) {
+            [1.0, 1.0, 1.0, 1.0]
+        } else {
+            [0.4, 0.4, 0.4, 1.0]
+        };
+        if gui.button(BUTTON_ID_OFFSET + recipe_id, x, y, CRAFTING_WIDTH, ELEMENT_HEIGHT).text(text, color).build() {
+            clicked_recipe = Some(recipe_id);
+        }
+        y += ELEMENT_OFFSET;
+    }
+    clicked_recipe
+}
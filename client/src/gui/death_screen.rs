@@ -0,0 +1,43 @@
+//! The screen shown over the game while the player is dead
+use crate::lang::tr;
+use history_survival_common::player::DeathCause;
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const BUTTON_WIDTH: i32 = 320;
+/// Offset of the ids of the buttons, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 8 << 20;
+
+/// A button of the death screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathScreenButton {
+    Respawn,
+    /// Leave the world, which is saved, and go back to the main menu
+    TitleScreen,
+}
+
+/// Darken the whole window in red and draw the cause of the death with the buttons, returning the button that was clicked
+pub fn render_death_screen(gui: &mut super::Gui, window_width: i32, window_height: i32, cause: DeathCause) -> Option<DeathScreenButton> {
+    gui.primitives.draw_rect(0, 0, window_width, window_height, [0.5, 0.0, 0.0, 0.5], 0.04);
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let buttons = [("death.respawn", DeathScreenButton::Respawn), ("death.title_screen", DeathScreenButton::TitleScreen)];
+    let x = (window_width - BUTTON_WIDTH) / 2;
+    let mut y = (window_height - (buttons.len() as i32 + 3) * ELEMENT_OFFSET) / 2;
+    gui.primitives.draw_rect(x, y, BUTTON_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr("death.title"), white, 0.01);
+    y += ELEMENT_OFFSET;
+    let message = match cause {
+        DeathCause::Starvation => "death.starvation",
+        DeathCause::Projectile => "death.projectile",
+    };
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr(message), white, 0.01);
+    y += 2 * ELEMENT_OFFSET;
+    let mut clicked = None;
+    for (i, &(key, button)) in buttons.iter().enumerate() {
+        if gui.button(BUTTON_ID_OFFSET + i as u32, x, y, BUTTON_WIDTH, ELEMENT_HEIGHT).text(tr(key), white).build() {
+            clicked = Some(button);
+        }
+        y += ELEMENT_OFFSET;
+    }
+    clicked
+}
@@ -1,14 +1,21 @@
-use history_survival_common::debug::{DebugInfo, DebugInfoPart};
+use crate::lang::{tr_args, translate};
+use history_survival_common::debug::{DebugInfo, DebugInfoPart, TimeGraph, TIME_GRAPH_SAMPLES};
 
 const ELEMENT_HEIGHT: i32 = 20;
 const ELEMENT_OFFSET: i32 = 25;
+/// Width of the bars of the time graphs
+const GRAPH_BAR_WIDTH: i32 = 4;
+/// Height of the time graphs, which is twice the hitch threshold
+const GRAPH_HEIGHT: i32 = 60;
 
 pub fn render_debug_info(gui: &mut super::Gui, debug_info: &mut DebugInfo) {
     let debug_info = debug_info.get_debug_info();
     let x = 4;
     let mut y = 4;
     for (section, (displayed, id, messages)) in debug_info {
-        let section_text = format!("{} debug info", section.to_uppercase());
+        let key = format!("debug.section.{}", section.to_lowercase().replace(' ', "_"));
+        let name = translate(&key).unwrap_or_else(|| section.clone());
+        let section_text = tr_args("debug.section", &[&name.to_uppercase()]);
         if gui.button(*id, x, y, 400, ELEMENT_HEIGHT).text(section_text, [1.0, 1.0, 1.0, 1.0]).build() {
             *displayed = !*displayed;
         }
@@ -43,8 +50,40 @@ pub fn render_debug_info(gui: &mut super::Gui, debug_info: &mut DebugInfo) {
                             y += ELEMENT_HEIGHT;
                         }
                     },
+                    DebugInfoPart::TimeGraph(graph) => {
+                        y = render_time_graph(gui, x + 10, y, graph);
+                    },
                 }
             }
         }
     }
+}
+
+/// Draw a strip with a bar for every duration of a time graph, and return the position below it.
+/// The bars are green below the hitch threshold, yellow below twice the threshold, and red above.
+fn render_time_graph(gui: &mut super::Gui, x: i32, mut y: i32, graph: &TimeGraph) -> i32 {
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let average = graph.millis.iter().sum::<f32>() / graph.millis.len().max(1) as f32;
+    let max = graph.millis.iter().copied().fold(0.0, f32::max);
+    let text = format!("{}: {:5.1} ms average, {:5.1} ms max", graph.name, average, max);
+    gui.text(x, y, ELEMENT_HEIGHT, text, white, 0.02);
+    y += ELEMENT_HEIGHT;
+    let width = TIME_GRAPH_SAMPLES as i32 * GRAPH_BAR_WIDTH;
+    gui.primitives.draw_rect(x, y, width, GRAPH_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.03);
+    // The line of the hitch threshold is in the middle of the graph
+    gui.primitives.draw_rect(x, y + GRAPH_HEIGHT / 2, width, 1, [1.0, 1.0, 1.0, 0.5], 0.01);
+    // The latest durations are on the right
+    let first_x = x + width - graph.millis.len() as i32 * GRAPH_BAR_WIDTH;
+    for (i, &millis) in graph.millis.iter().enumerate() {
+        let ratio = millis / graph.hitch_threshold_millis.max(f32::EPSILON);
+        let color = match ratio {
+            r if r <= 1.0 => [0.2, 0.8, 0.2, 0.8],
+            r if r <= 2.0 => [0.9, 0.8, 0.2, 0.8],
+            _ => [0.9, 0.2, 0.2, 0.8],
+        };
+        let height = ((ratio / 2.0).min(1.0) * GRAPH_HEIGHT as f32).round().max(1.0) as i32;
+        let bar_x = first_x + i as i32 * GRAPH_BAR_WIDTH;
+        gui.primitives.draw_rect(bar_x, y + GRAPH_HEIGHT - height, GRAPH_BAR_WIDTH - 1, height, color, 0.02);
+    }
+    y + GRAPH_HEIGHT + 5
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+use super::chest::{click_slot, render_slots, ELEMENT_HEIGHT, ELEMENT_OFFSET, SLOTS_PER_ROW, SLOT_OFFSET, SLOT_WIDTH};
+use history_survival_common::{
+    block::{chest::ChestSlot, furnace::FurnaceProgress},
+    inventory::{Inventory, ItemStack},
+    item::Item,
+    registry::Registry,
+    world::BlockPos,
+};
+
+/// Offset of the ids of the furnace buttons, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 5 << 20;
+
+/// What the player did in the furnace screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FurnaceScreenResult {
+    /// Move the stack of a slot to another slot
+    Move(ChestSlot, ChestSlot),
+    /// Close the furnace
+    Close,
+}
+
+/// The state of the screen of an open furnace
+pub struct FurnaceScreen {
+    /// The position of the furnace
+    pub pos: BlockPos,
+    /// The contents of the furnace, as sent by the server
+    pub inventory: Inventory,
+    pub progress: FurnaceProgress,
+    /// The slot whose stack is moved by the next click on a slot
+    selected: Option<ChestSlot>,
+}
+
+impl FurnaceScreen {
+    pub fn new(pos: BlockPos, inventory: Inventory, progress: FurnaceProgress) -> Self {
+        Self {
+            pos,
+            inventory,
+            progress,
+            selected: None,
+        }
+    }
+
+    /// Update a slot of the furnace, when the smelting or a player moved items
+    pub fn set_slot(&mut self, slot: usize, stack: Option<ItemStack>) {
+        self.inventory.set(slot, stack);
+    }
+}
+
+/// A fraction as a percentage, 0 if the total is 0
+fn percent(part: u32, total: u32) -> u32 {
+    (part * 100).checked_div(total).unwrap_or(0)
+}
+
+/// Draw the fuel, input and output slots of the furnace with its progress, above the inventory of the player.
+/// Moving items works like in the chest screen.
+pub fn render_furnace_screen(
+    gui: &mut super::Gui,
+    window_width: i32,
+    screen: &mut FurnaceScreen,
+    player_inventory: &Inventory,
+    items: &Registry<Item>,
+) -> Option<FurnaceScreenResult> {
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let width = SLOTS_PER_ROW as i32 * SLOT_OFFSET;
+    let x = (window_width - width) / 2;
+    let mut y = 4 + ELEMENT_OFFSET;
+    gui.text(x, y, ELEMENT_HEIGHT, "FURNACE".to_owned(), white, 0.02);
+    y += ELEMENT_OFFSET;
+    for (i, label) in ["Fuel", "Input", "Output"].iter().enumerate() {
+        gui.text(x + i as i32 * SLOT_OFFSET, y, ELEMENT_HEIGHT, label.to_string(), white, 0.02);
+    }
+    y += ELEMENT_OFFSET;
+    let selected_furnace_slot = match screen.selected {
+        Some(ChestSlot::Chest(slot)) => Some(slot),
+        _ => None,
+    };
+    let clicked_furnace_slot = render_slots(gui, x, &mut y, BUTTON_ID_OFFSET, &screen.inventory, items, selected_furnace_slot);
+    let progress = screen.progress;
+    let status = format!(
+        "Burning {}%, smelting {}%",
+        percent(progress.burn_left, progress.burn_time),
+        percent(progress.smelted, progress.smelt_time)
+    );
+    gui.text(x, y, ELEMENT_HEIGHT, status, white, 0.02);
+    y += ELEMENT_OFFSET + ELEMENT_OFFSET / 2;
+    gui.text(x, y, ELEMENT_HEIGHT, "INVENTORY".to_owned(), white, 0.02);
+    y += ELEMENT_OFFSET;
+    let selected_player_slot = match screen.selected {
+        Some(ChestSlot::Player(slot)) => Some(slot),
+        _ => None,
+    };
+    let first_player_id = BUTTON_ID_OFFSET + screen.inventory.len() as u32;
+    let clicked_player_slot = render_slots(gui, x, &mut y, first_player_id, player_inventory, items, selected_player_slot);
+
+    let clicked = clicked_furnace_slot
+        .map(ChestSlot::Chest)
+        .or_else(|| clicked_player_slot.map(ChestSlot::Player));
+    let mut result = click_slot(&mut screen.selected, clicked, &screen.inventory, player_inventory)
+        .map(|(from, to)| FurnaceScreenResult::Move(from, to));
+    let close_id = first_player_id + player_inventory.len() as u32;
+    if gui.button(close_id, x, y, width - (SLOT_OFFSET - SLOT_WIDTH), ELEMENT_HEIGHT).text("Close".to_owned(), [0.0, 0.0, 0.0, 1.0]).build() {
+        result = Some(FurnaceScreenResult::Close);
+    }
+    result
+}
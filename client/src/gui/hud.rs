@@ -0,0 +1,232 @@
+use crate::lang::{tr, tr_args};
+use history_survival_common::advancement::AdvancementType;
+use history_survival_common::data::TextureRect;
+use history_survival_common::inventory::HOTBAR_SIZE;
+use history_survival_common::item::map::{MapCanvas, MAP_SIZE};
+use history_survival_common::player::{GameMode, PlayerStats, MAX_HEALTH, MAX_HUNGER};
+use history_survival_common::time::{DayPhase, WorldTime};
+use std::time::Duration;
+
+const ELEMENT_HEIGHT: i32 = 20;
+const CLOCK_WIDTH: i32 = 220;
+const ELEMENT_OFFSET: i32 = 25;
+
+/// Draw the world time in the top-right corner of the screen
+pub fn render_clock(gui: &mut super::Gui, window_width: i32, world_time: WorldTime) {
+    let phase = match world_time.phase() {
+        DayPhase::Sunrise => "clock.sunrise",
+        DayPhase::Day => "clock.day",
+        DayPhase::Sunset => "clock.sunset",
+        DayPhase::Night => "clock.night",
+    };
+    let (hours, minutes) = world_time.hours_minutes();
+    let time = format!("{:02}:{:02}", hours, minutes);
+    let text = tr_args("clock.text", &[&(world_time.day() + 1), &tr(phase), &time]);
+    let x = window_width - CLOCK_WIDTH - 4;
+    gui.primitives.draw_rect(x, 4, CLOCK_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, 4, ELEMENT_HEIGHT, text, [1.0, 1.0, 1.0, 1.0], 0.01);
+}
+
+/// Draw the game mode below the clock, instead of the stats of the players that don't get hungry
+pub fn render_game_mode(gui: &mut super::Gui, window_width: i32, game_mode: GameMode) {
+    let text = match game_mode {
+        GameMode::Survival => "game_mode.survival",
+        GameMode::Creative => "game_mode.creative",
+        GameMode::Spectator => "game_mode.spectator",
+    };
+    let x = window_width - CLOCK_WIDTH - 4;
+    let y = 4 + ELEMENT_OFFSET;
+    gui.primitives.draw_rect(x, y, CLOCK_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr(text), [1.0, 1.0, 1.0, 1.0], 0.01);
+}
+
+const TOAST_WIDTH: i32 = 400;
+
+/// Draw a short debug message at the top of the screen, like the hitches of the main thread
+pub fn render_toast(gui: &mut super::Gui, window_width: i32, text: String) {
+    let x = (window_width - TOAST_WIDTH) / 2;
+    gui.primitives.draw_rect(x, 4, TOAST_WIDTH, ELEMENT_HEIGHT, [0.4, 0.1, 0.1, 0.7], 0.02);
+    gui.text(x + 6, 4, ELEMENT_HEIGHT, text, [1.0, 1.0, 1.0, 1.0], 0.01);
+}
+
+/// Draw an advancement that the player just earned at the top of the screen, below the debug messages
+pub fn render_advancement_toast(gui: &mut super::Gui, window_width: i32, advancement: &AdvancementType) {
+    let x = (window_width - TOAST_WIDTH) / 2;
+    let y = 4 + ELEMENT_OFFSET;
+    gui.primitives.draw_rect(x, y, TOAST_WIDTH, 2 * ELEMENT_HEIGHT, [0.5, 0.4, 0.1, 0.8], 0.02);
+    let title = tr_args("advancement.toast", &[&advancement.title]);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, title, [1.0, 0.9, 0.4, 1.0], 0.01);
+    gui.text(x + 6, y + ELEMENT_HEIGHT, ELEMENT_HEIGHT, advancement.description.clone(), [1.0, 1.0, 1.0, 1.0], 0.01);
+}
+
+/// Side of the slots of the hotbar and of the inventory
+pub(super) const SLOT_SIZE: i32 = 40;
+/// Space between the border of a slot and the icon of its item
+const SLOT_PADDING: i32 = 4;
+/// Width of the border around the selected slot
+const SELECTED_BORDER: i32 = 2;
+
+/// Draw the hotbar centered at the bottom of the screen, with the selected slot highlighted.
+/// `slots` holds the icon of the item of every slot with the number of items of its stack.
+pub fn render_hotbar(gui: &mut super::Gui, window_width: i32, window_height: i32, slots: &[Option<(TextureRect, u32)>], selected: usize) {
+    let x = (window_width - slots.len() as i32 * SLOT_SIZE) / 2;
+    let y = window_height - SLOT_SIZE - 4;
+    for (slot, stack) in slots.iter().enumerate() {
+        let slot_x = x + slot as i32 * SLOT_SIZE;
+        if slot == selected {
+            let side = SLOT_SIZE + 2 * SELECTED_BORDER;
+            gui.primitives.draw_rect(slot_x - SELECTED_BORDER, y - SELECTED_BORDER, side, side, [1.0, 1.0, 1.0, 0.9], 0.03);
+        }
+        gui.primitives.draw_rect(slot_x, y, SLOT_SIZE, SLOT_SIZE, [0.0, 0.0, 0.0, 0.5], 0.02);
+        if let Some((texture, count)) = *stack {
+            render_stack(gui, slot_x, y, texture, count, 0.01);
+        }
+    }
+}
+
+/// Draw the icon of a stack in the slot whose top-left corner is at (x, y), with the number of items if there are several
+pub(super) fn render_stack(gui: &mut super::Gui, x: i32, y: i32, texture: TextureRect, count: u32, z: f32) {
+    let icon_size = SLOT_SIZE - 2 * SLOT_PADDING;
+    gui.primitives.draw_textured_rect(x + SLOT_PADDING, y + SLOT_PADDING, icon_size, icon_size, texture, z);
+    if count > 1 {
+        let text_y = y + SLOT_SIZE - ELEMENT_HEIGHT;
+        gui.text(x + SLOT_PADDING, text_y, ELEMENT_HEIGHT, count.to_string(), [1.0, 1.0, 1.0, 1.0], z / 2.0);
+    }
+}
+
+/// The pixels of the icons of the status bars, 7 wide and 6 high
+const HEART_ICON: [&str; 6] = [".XX.XX.", "XXXXXXX", "XXXXXXX", ".XXXXX.", "..XXX..", "...X..."];
+const FOOD_ICON: [&str; 6] = ["..XXX..", ".XXXXX.", ".XXXXX.", "..XXX..", "...X...", "..X.X.."];
+/// Side of the pixels of the icons, in pixels of the screen
+const ICON_PIXEL_SIZE: i32 = 2;
+/// Distance between the left sides of two icons
+const ICON_OFFSET: i32 = 18;
+/// Each icon shows two points of health or hunger
+const POINTS_PER_ICON: u32 = 2;
+/// The hearts pulse when the health is at most this low
+const LOW_HEALTH: u32 = 4;
+/// How long the hearts flash after the player is hurt
+const DAMAGE_FLASH_DURATION: Duration = Duration::from_millis(500);
+const HEART_COLOR: [f32; 4] = [0.85, 0.1, 0.1, 1.0];
+const FOOD_COLOR: [f32; 4] = [0.7, 0.45, 0.2, 1.0];
+const EMPTY_ICON_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 0.7];
+
+/// How bright the flash of the hearts is some time after the player was hurt, from 1 to 0
+pub fn damage_flash(since_damage: Duration) -> f32 {
+    let remaining = DAMAGE_FLASH_DURATION.checked_sub(since_damage).unwrap_or_default();
+    remaining.as_secs_f32() / DAMAGE_FLASH_DURATION.as_secs_f32()
+}
+
+/// How much of an icon is filled for some number of points: 1 for a full icon, 0.5 for a half icon and 0 for an empty one
+fn icon_fill(points: u32, icon: u32) -> f32 {
+    let icon_points = points.saturating_sub(icon * POINTS_PER_ICON).min(POINTS_PER_ICON);
+    icon_points as f32 / POINTS_PER_ICON as f32
+}
+
+/// Draw an icon of the status bars, with its left part filled with the color and the rest empty
+fn draw_icon(gui: &mut super::Gui, icon: &[&str; 6], x: i32, y: i32, fill: f32, color: [f32; 4]) {
+    let width = icon[0].len();
+    for (row, pixels) in icon.iter().enumerate() {
+        for (column, pixel) in pixels.chars().enumerate() {
+            if pixel == 'X' {
+                let filled = (column as f32 + 0.5) < fill * width as f32;
+                let color = if filled { color } else { EMPTY_ICON_COLOR };
+                let (px, py) = (x + column as i32 * ICON_PIXEL_SIZE, y + row as i32 * ICON_PIXEL_SIZE);
+                gui.primitives.draw_rect(px, py, ICON_PIXEL_SIZE, ICON_PIXEL_SIZE, color, 0.01);
+            }
+        }
+    }
+}
+
+/// Draw the hearts and the food of the player above the hotbar, the hearts on the left and the food on the right.
+/// The hearts flash white with `damage_flash` after the player is hurt, and pulse when the health is low.
+/// `time` is the number of seconds since the start of the game, which drives the pulse.
+pub fn render_status_bars(gui: &mut super::Gui, window_width: i32, window_height: i32, stats: PlayerStats, damage_flash: f32, time: f32) {
+    let hotbar_width = HOTBAR_SIZE as i32 * SLOT_SIZE;
+    let left = (window_width - hotbar_width) / 2;
+    let y = window_height - SLOT_SIZE - 4 - SELECTED_BORDER - 6 * ICON_PIXEL_SIZE - 6;
+    let heart_color = [0, 1, 2, 3].map(|i| HEART_COLOR[i] + (1.0 - HEART_COLOR[i]) * damage_flash);
+    for heart in 0..MAX_HEALTH / POINTS_PER_ICON {
+        // The hearts bob one after the other when the player is about to die
+        let pulse = if stats.health <= LOW_HEALTH {
+            ((time * 10.0 + heart as f32).sin() * ICON_PIXEL_SIZE as f32).round() as i32
+        } else {
+            0
+        };
+        let x = left + heart as i32 * ICON_OFFSET;
+        draw_icon(gui, &HEART_ICON, x, y + pulse, icon_fill(stats.health, heart), heart_color);
+    }
+    let right = left + hotbar_width;
+    for food in 0..MAX_HUNGER / POINTS_PER_ICON {
+        // The food fills from the right, so that it mirrors the hearts
+        let x = right - (food as i32 + 1) * ICON_OFFSET + (ICON_OFFSET - 7 * ICON_PIXEL_SIZE);
+        draw_icon(gui, &FOOD_ICON, x, y, icon_fill(stats.hunger, food), FOOD_COLOR);
+    }
+}
+
+/// Side of the pixels of the held map, in pixels of the screen
+const MAP_PIXEL_SIZE: i32 = 2;
+/// Width of the border around the held map
+const MAP_BORDER: i32 = 4;
+
+/// Draw the map held by the player in the bottom-right corner of the screen, with a marker at the position of the player
+pub fn render_held_map(gui: &mut super::Gui, window_width: i32, window_height: i32, canvas: &MapCanvas, player_pixel: Option<(u32, u32)>) {
+    let side = MAP_SIZE as i32 * MAP_PIXEL_SIZE;
+    let x = window_width - side - MAP_BORDER - 4;
+    let y = window_height - side - MAP_BORDER - 4;
+    gui.primitives.draw_rect(
+        x - MAP_BORDER,
+        y - MAP_BORDER,
+        side + 2 * MAP_BORDER,
+        side + 2 * MAP_BORDER,
+        [0.45, 0.32, 0.18, 1.0],
+        0.03,
+    );
+    let texture = canvas.compose(MAP_SIZE);
+    for row in 0..MAP_SIZE {
+        // Draw the runs of pixels of the same color at once
+        let mut start = 0;
+        while start < MAP_SIZE {
+            let color = texture.get_pixel(start, row).0;
+            let mut end = start + 1;
+            while end < MAP_SIZE && texture.get_pixel(end, row).0 == color {
+                end += 1;
+            }
+            let [r, g, b, _] = color.map(|c| c as f32 / 255.0);
+            gui.primitives.draw_rect(
+                x + start as i32 * MAP_PIXEL_SIZE,
+                y + row as i32 * MAP_PIXEL_SIZE,
+                (end - start) as i32 * MAP_PIXEL_SIZE,
+                MAP_PIXEL_SIZE,
+                [r, g, b, 1.0],
+                0.02,
+            );
+            start = end;
+        }
+    }
+    if let Some((u, v)) = player_pixel {
+        let marker = 3 * MAP_PIXEL_SIZE;
+        gui.primitives.draw_rect(
+            x + u as i32 * MAP_PIXEL_SIZE - MAP_PIXEL_SIZE,
+            y + v as i32 * MAP_PIXEL_SIZE - MAP_PIXEL_SIZE,
+            marker,
+            marker,
+            [0.9, 0.1, 0.1, 1.0],
+            0.01,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icons_show_two_points_each() {
+        assert_eq!((0..4).map(|icon| icon_fill(5, icon)).collect::<Vec<_>>(), vec![1.0, 1.0, 0.5, 0.0]);
+        assert_eq!(icon_fill(MAX_HEALTH, MAX_HEALTH / POINTS_PER_ICON - 1), 1.0);
+        assert_eq!(damage_flash(Duration::from_secs(0)), 1.0);
+        assert_eq!(damage_flash(DAMAGE_FLASH_DURATION / 2), 0.5);
+        assert_eq!(damage_flash(Duration::from_secs(10)), 0.0);
+    }
+}
@@ -0,0 +1,219 @@
+//! The inventory of the player, whose stacks are moved with the mouse
+use super::hud::{render_stack, SLOT_SIZE};
+use history_survival_common::{data::TextureRect, inventory::HOTBAR_SIZE};
+
+const ELEMENT_HEIGHT: i32 = 20;
+/// Number of slots in a row of the inventory, like the hotbar
+const SLOTS_PER_ROW: usize = HOTBAR_SIZE;
+/// Space around the slots, and between the hotbar and the other slots
+const MARGIN: i32 = 8;
+
+/// What the player did in the inventory screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryScreenResult {
+    /// Move the stack of a slot to another slot (from, to)
+    Move(usize, usize),
+    /// Move some items of a slot to another slot (from, to, count)
+    MoveSome(usize, usize, u32),
+    /// Move the stack of a slot between the hotbar and the other slots
+    QuickMove(usize),
+}
+
+/// Some items picked up from a slot, that follow the mouse until they are placed in another slot.
+/// They stay in their slot until the server moves them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HeldItems {
+    slot: usize,
+    count: u32,
+}
+
+/// What the mouse did over the slots since the last frame
+#[derive(Debug, Clone, Copy, Default)]
+struct SlotInput {
+    /// The slot under the mouse
+    hovered: Option<usize>,
+    pressed: bool,
+    released: bool,
+    right_clicked: bool,
+    /// Whether the stacks are moved between the hotbar and the other slots when they are clicked
+    quick_move: bool,
+}
+
+/// The state of the inventory screen
+#[derive(Debug, Default)]
+pub struct InventoryScreen {
+    held: Option<HeldItems>,
+    /// Whether the left button was down during the last frame, to find when it is pressed and released
+    mouse_was_down: bool,
+}
+
+impl InventoryScreen {
+    /// Pick up and place the items with the mouse. `counts` gives the number of items of every slot.
+    fn handle_input(&mut self, input: SlotInput, counts: &[u32]) -> Option<InventoryScreenResult> {
+        let count = |slot: usize| counts.get(slot).copied().unwrap_or(0);
+        // The server may have moved the items that are held
+        if let Some(held) = &mut self.held {
+            held.count = held.count.min(count(held.slot));
+            if held.count == 0 {
+                self.held = None;
+            }
+        }
+        let slot = input.hovered?;
+        match self.held {
+            _ if input.pressed && input.quick_move => {
+                self.held = None;
+                Some(InventoryScreenResult::QuickMove(slot)).filter(|_| count(slot) > 0)
+            }
+            None if input.pressed && count(slot) > 0 => {
+                self.held = Some(HeldItems { slot, count: count(slot) });
+                None
+            }
+            None if input.right_clicked && count(slot) > 0 => {
+                self.held = Some(HeldItems { slot, count: count(slot).div_ceil(2) });
+                None
+            }
+            // Clicking the slot of the held items puts them back
+            Some(held) if held.slot == slot => {
+                if input.pressed {
+                    self.held = None;
+                }
+                None
+            }
+            // The items are placed by a click, or by releasing the button after dragging them
+            Some(held) if input.pressed || input.released => {
+                self.held = None;
+                if held.count == count(held.slot) {
+                    Some(InventoryScreenResult::Move(held.slot, slot))
+                } else {
+                    Some(InventoryScreenResult::MoveSome(held.slot, slot, held.count))
+                }
+            }
+            Some(held) if input.right_clicked => {
+                self.held = Some(HeldItems { count: held.count - 1, ..held }).filter(|held| held.count > 0);
+                Some(InventoryScreenResult::MoveSome(held.slot, slot, 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Draw the inventory of the player in the middle of the screen, with the hotbar below the other slots.
+/// `slots` holds the icon of the item of every slot with the number of items of its stack, and `names` the name of
+/// the item of every slot, shown in a tooltip over the slot while no items are held.
+/// Clicking a stack picks it up and clicking another slot places it, or the stack can be dragged to the other slot.
+/// Right-clicking a stack picks up half of it, and right-clicking another slot places one of the held items.
+/// Clicking a stack while `quick_move` is set moves it between the hotbar and the other slots.
+pub fn render_inventory_screen(
+    gui: &mut super::Gui,
+    window_width: i32,
+    window_height: i32,
+    screen: &mut InventoryScreen,
+    slots: &[Option<(TextureRect, u32)>],
+    names: &[Option<&str>],
+    quick_move: bool,
+) -> Option<InventoryScreenResult> {
+    let rows = slots.len().saturating_sub(HOTBAR_SIZE).div_ceil(SLOTS_PER_ROW) as i32;
+    let width = SLOTS_PER_ROW as i32 * SLOT_SIZE + 2 * MARGIN;
+    let height = ELEMENT_HEIGHT + (rows + 1) * SLOT_SIZE + 3 * MARGIN;
+    let (x, y) = ((window_width - width) / 2, (window_height - height) / 2);
+    gui.primitives.draw_rect(x, y, width, height, [0.0, 0.0, 0.0, 0.6], 0.03);
+    gui.text(x + MARGIN, y + MARGIN / 2, ELEMENT_HEIGHT, "INVENTORY".to_owned(), [1.0, 1.0, 1.0, 1.0], 0.02);
+    let grid_y = y + MARGIN + ELEMENT_HEIGHT;
+    let hotbar_y = grid_y + rows * SLOT_SIZE + MARGIN;
+    let slot_position = |slot: usize| {
+        if slot < HOTBAR_SIZE {
+            (x + MARGIN + slot as i32 * SLOT_SIZE, hotbar_y)
+        } else {
+            let (column, row) = ((slot - HOTBAR_SIZE) % SLOTS_PER_ROW, (slot - HOTBAR_SIZE) / SLOTS_PER_ROW);
+            (x + MARGIN + column as i32 * SLOT_SIZE, grid_y + row as i32 * SLOT_SIZE)
+        }
+    };
+
+    let input = SlotInput {
+        hovered: (0..slots.len()).find(|&slot| {
+            let (slot_x, slot_y) = slot_position(slot);
+            gui.is_mouse_inside(slot_x, slot_y, SLOT_SIZE, SLOT_SIZE)
+        }),
+        pressed: gui.mouse_down && !screen.mouse_was_down,
+        released: !gui.mouse_down && screen.mouse_was_down,
+        right_clicked: gui.right_clicked,
+        quick_move,
+    };
+    screen.mouse_was_down = gui.mouse_down;
+    let counts = slots.iter().map(|stack| stack.map(|(_, count)| count).unwrap_or(0)).collect::<Vec<_>>();
+    let result = screen.handle_input(input, &counts);
+
+    for (slot, stack) in slots.iter().enumerate() {
+        let (slot_x, slot_y) = slot_position(slot);
+        let color = if input.hovered == Some(slot) { [0.5, 0.5, 0.5, 0.8] } else { [0.25, 0.25, 0.25, 0.8] };
+        gui.primitives.draw_rect(slot_x + 1, slot_y + 1, SLOT_SIZE - 2, SLOT_SIZE - 2, color, 0.02);
+        if let Some((texture, count)) = *stack {
+            // The held items are drawn under the mouse instead
+            let held = screen.held.filter(|held| held.slot == slot).map(|held| held.count).unwrap_or(0);
+            if count > held {
+                render_stack(gui, slot_x, slot_y, texture, count - held, 0.01);
+            }
+        }
+    }
+    if let Some(held) = screen.held {
+        if let Some((texture, _)) = slots[held.slot] {
+            let (mouse_x, mouse_y) = (gui.mouse_x - SLOT_SIZE / 2, gui.mouse_y - SLOT_SIZE / 2);
+            render_stack(gui, mouse_x, mouse_y, texture, held.count, 0.001);
+        }
+    } else if let Some(slot) = input.hovered {
+        if let Some(name) = names.get(slot).copied().flatten() {
+            let (slot_x, slot_y) = slot_position(slot);
+            gui.tooltip(slot_x, slot_y, SLOT_SIZE, SLOT_SIZE, name);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click(slot: usize) -> SlotInput {
+        SlotInput {
+            hovered: Some(slot),
+            pressed: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stacks_are_picked_up_and_placed() {
+        let mut screen = InventoryScreen::default();
+        let counts = [10, 0, 5];
+        // Clicking an empty slot does nothing, and clicking a stack picks it up until another slot is clicked
+        assert_eq!(screen.handle_input(click(1), &counts), None);
+        assert_eq!(screen.held, None);
+        assert_eq!(screen.handle_input(click(0), &counts), None);
+        assert_eq!(screen.handle_input(click(1), &counts), Some(InventoryScreenResult::Move(0, 1)));
+        // The stacks can be dragged to another slot
+        assert_eq!(screen.handle_input(click(2), &counts), None);
+        let release = SlotInput {
+            hovered: Some(1),
+            released: true,
+            ..Default::default()
+        };
+        assert_eq!(screen.handle_input(release, &counts), Some(InventoryScreenResult::Move(2, 1)));
+        // Right-clicking splits a stack, and places one item at a time
+        let right_click = |slot| SlotInput {
+            hovered: Some(slot),
+            right_clicked: true,
+            ..Default::default()
+        };
+        assert_eq!(screen.handle_input(right_click(2), &counts), None);
+        assert_eq!(screen.held, Some(HeldItems { slot: 2, count: 3 }));
+        assert_eq!(screen.handle_input(right_click(1), &counts), Some(InventoryScreenResult::MoveSome(2, 1, 1)));
+        assert_eq!(screen.handle_input(click(1), &counts), Some(InventoryScreenResult::MoveSome(2, 1, 2)));
+        assert_eq!(screen.held, None);
+        // Shift-clicking moves the stack at once
+        let quick_move = SlotInput {
+            quick_move: true,
+            ..click(0)
+        };
+        assert_eq!(screen.handle_input(quick_move, &counts), Some(InventoryScreenResult::QuickMove(0)));
+    }
+}
@@ -0,0 +1,30 @@
+//! The screen shown while the terrain around a joining player is loading
+use super::main_menu::render_background;
+use crate::lang::tr;
+use crate::loading::SpawnAreaLoading;
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const BAR_WIDTH: i32 = 320;
+const BAR_HEIGHT: i32 = 6;
+
+/// Draw the landscape of the main menu with a progress bar for every step of the loading
+pub fn render_loading_screen(gui: &mut super::Gui, window_width: i32, window_height: i32, loading: &SpawnAreaLoading) {
+    render_background(gui, window_width, window_height);
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let steps = loading.steps();
+    let x = (window_width - BAR_WIDTH) / 2;
+    let mut y = (window_height - (2 * steps.len() as i32 + 2) * ELEMENT_OFFSET) / 2;
+    gui.primitives.draw_rect(x, y, BAR_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr("loading.title"), white, 0.01);
+    y += 2 * ELEMENT_OFFSET;
+    for &(key, done, total) in steps.iter() {
+        let status = if total == 0 { "...".to_owned() } else { format!("{}/{}", done, total) };
+        gui.text(x, y, ELEMENT_HEIGHT, format!("{} {}", tr(key), status), white, 0.02);
+        y += ELEMENT_OFFSET;
+        let filled = if total == 0 { 0 } else { BAR_WIDTH * done.min(total) as i32 / total as i32 };
+        gui.primitives.draw_rect(x, y, BAR_WIDTH, BAR_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+        gui.primitives.draw_rect(x, y, filled, BAR_HEIGHT, [0.4, 0.8, 0.3, 1.0], 0.01);
+        y += ELEMENT_OFFSET;
+    }
+}
@@ -0,0 +1,133 @@
+//! The screens of the main menu, drawn over a static landscape
+use crate::lang::tr;
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const BUTTON_WIDTH: i32 = 320;
+const MULTIPLAYER_WIDTH: i32 = 480;
+/// Width of the buttons next to the saved servers
+const SMALL_BUTTON_WIDTH: i32 = 70;
+/// Offset of the ids of the buttons, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 5 << 20;
+/// Color of the top of the sky of the background, which fades to the color of the horizon
+const SKY_TOP_COLOR: [f32; 3] = [0.25, 0.45, 0.85];
+const SKY_HORIZON_COLOR: [f32; 3] = [0.7, 0.82, 0.95];
+const GRASS_COLOR: [f32; 3] = [0.3, 0.55, 0.2];
+const DIRT_COLOR: [f32; 3] = [0.45, 0.3, 0.18];
+/// Number of bands of the fading sky
+const SKY_BANDS: i32 = 16;
+
+/// A button of the main menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainMenuButton {
+    Singleplayer,
+    Multiplayer,
+    Settings,
+    Quit,
+    /// Go back from the settings or the multiplayer screen to the main menu
+    Back,
+}
+
+/// What the player did in the multiplayer screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiplayerAction {
+    /// Save the address of the text field
+    Save,
+    /// Forget the saved server with this index
+    Remove(usize),
+}
+
+/// Draw a landscape that fills the window: a sky above the grass and the dirt
+pub fn render_background(gui: &mut super::Gui, window_width: i32, window_height: i32) {
+    let horizon = window_height * 2 / 3;
+    for band in 0..SKY_BANDS {
+        let t = band as f32 / (SKY_BANDS - 1) as f32;
+        let color = [0, 1, 2].map(|i| SKY_TOP_COLOR[i] + (SKY_HORIZON_COLOR[i] - SKY_TOP_COLOR[i]) * t);
+        let (top, bottom) = (horizon * band / SKY_BANDS, horizon * (band + 1) / SKY_BANDS);
+        gui.primitives.draw_rect(0, top, window_width, bottom - top, [color[0], color[1], color[2], 1.0], 0.1);
+    }
+    let grass_height = (window_height - horizon) / 4;
+    let [r, g, b] = GRASS_COLOR;
+    gui.primitives.draw_rect(0, horizon, window_width, grass_height, [r, g, b, 1.0], 0.1);
+    let [r, g, b] = DIRT_COLOR;
+    gui.primitives.draw_rect(0, horizon + grass_height, window_width, window_height - horizon - grass_height, [r, g, b, 1.0], 0.1);
+}
+
+/// Draw the title and the buttons of the main menu in the middle of the window, returning the button that was clicked
+pub fn render_main_menu(gui: &mut super::Gui, window_width: i32, window_height: i32) -> Option<MainMenuButton> {
+    let buttons = [
+        ("main_menu.singleplayer", MainMenuButton::Singleplayer),
+        ("main_menu.multiplayer", MainMenuButton::Multiplayer),
+        ("main_menu.settings", MainMenuButton::Settings),
+        ("main_menu.quit", MainMenuButton::Quit),
+    ];
+    let x = (window_width - BUTTON_WIDTH) / 2;
+    let mut y = (window_height - (buttons.len() as i32 + 2) * ELEMENT_OFFSET) / 2;
+    gui.primitives.draw_rect(x, y, BUTTON_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr("main_menu.title"), [1.0, 1.0, 1.0, 1.0], 0.01);
+    y += 2 * ELEMENT_OFFSET;
+    let mut clicked = None;
+    for (i, &(key, button)) in buttons.iter().enumerate() {
+        if gui.button(BUTTON_ID_OFFSET + i as u32, x, y, BUTTON_WIDTH, ELEMENT_HEIGHT).text(tr(key), [1.0, 1.0, 1.0, 1.0]).build() {
+            clicked = Some(button);
+        }
+        y += ELEMENT_OFFSET;
+    }
+    clicked
+}
+
+/// Draw the multiplayer screen, returning what the player did.
+/// `servers` holds the address of every saved server, and `scroll` is how far their list is scrolled.
+pub fn render_multiplayer(
+    gui: &mut super::Gui,
+    window_width: i32,
+    window_height: i32,
+    address: &mut String,
+    servers: &[String],
+    scroll: &mut i32,
+) -> Option<MultiplayerAction> {
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let x = (window_width - MULTIPLAYER_WIDTH) / 2;
+    let mut y = 4 + 3 * ELEMENT_OFFSET;
+    let mut action = None;
+    gui.primitives.draw_rect(x, y, MULTIPLAYER_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr("multiplayer.title"), white, 0.01);
+    y += ELEMENT_OFFSET;
+    // The text field and the saved servers leave room for a button on their right
+    let row_width = MULTIPLAYER_WIDTH - SMALL_BUTTON_WIDTH - 5;
+    let button_x = x + row_width + 5;
+    if gui.text_field(BUTTON_ID_OFFSET + 32, x, y, row_width, ELEMENT_HEIGHT).build(address) {
+        action = Some(MultiplayerAction::Save);
+    }
+    if gui.button(BUTTON_ID_OFFSET + 33, button_x, y, SMALL_BUTTON_WIDTH, ELEMENT_HEIGHT).text(tr("multiplayer.save"), white).build() {
+        action = Some(MultiplayerAction::Save);
+    }
+    y += ELEMENT_OFFSET;
+    gui.primitives.draw_rect(x, y, MULTIPLAYER_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr("multiplayer.saved_servers"), white, 0.01);
+    y += ELEMENT_OFFSET;
+    // The servers scroll between the text field and the back button
+    let list_height = (window_height - 2 * ELEMENT_OFFSET - y).max(ELEMENT_OFFSET);
+    let content_height = servers.len() as i32 * ELEMENT_OFFSET;
+    let list_width = MULTIPLAYER_WIDTH + 5 + super::SCROLL_BAR_WIDTH;
+    let top = gui.scroll_area(BUTTON_ID_OFFSET + 36, x, y, list_width, list_height).begin(content_height, scroll);
+    for (i, address) in servers.iter().enumerate() {
+        let y = top + i as i32 * ELEMENT_OFFSET;
+        let id = BUTTON_ID_OFFSET + 64 + i as u32;
+        gui.primitives.draw_rect(x, y, row_width, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+        gui.text(x + 6, y, ELEMENT_HEIGHT, address.clone(), white, 0.01);
+        if gui.button(id, button_x, y, SMALL_BUTTON_WIDTH, ELEMENT_HEIGHT).text(tr("multiplayer.remove"), white).build() {
+            action = Some(MultiplayerAction::Remove(i));
+        }
+    }
+    gui.end_scroll_area();
+    action
+}
+
+/// Draw the button that goes back to the main menu at the bottom of the screen, returning whether it was clicked
+pub fn render_back_button(gui: &mut super::Gui, window_width: i32, window_height: i32) -> bool {
+    let x = (window_width - BUTTON_WIDTH) / 2;
+    let y = window_height - 2 * ELEMENT_OFFSET;
+    let id = BUTTON_ID_OFFSET + 16;
+    gui.button(id, x, y, BUTTON_WIDTH, ELEMENT_HEIGHT).text(tr("gui.back"), [1.0, 1.0, 1.0, 1.0]).build()
+}
@@ -1,20 +1,52 @@
-use crate::ui::PrimitiveBuffer;
+use crate::ui::{ClipRect, PrimitiveBuffer};
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+use winit::event::MouseScrollDelta;
 
+pub mod banner_editor;
+pub mod chat;
+pub mod chest;
+pub mod crafting;
+pub mod death_screen;
 pub mod experiments;
+pub mod furnace;
+pub mod hud;
+pub mod inventory;
+pub mod loading;
+pub mod main_menu;
+pub mod options;
+pub mod painting_picker;
+pub mod pause_menu;
 
 /// Immediate-mode GUI
 pub struct Gui {
     pub(self) mouse_x: i32,
     pub(self) mouse_y: i32,
     pub(self) mouse_down: bool,
+    /// Whether the right button was pressed since the last frame
+    pub(self) right_clicked: bool,
 
     pub(self) hot_item: u32,
     /// Active item. Ids 0 and 1 are reserved.
     /// 0 means "no item but can be assigned"
     /// 1 means "no item and cannot be assigned"
     pub(self) active_item: u32,
+    /// The text field that receives the typed characters, 0 if there is none
+    pub(self) focused_item: u32,
+    /// Characters typed since the last frame
+    pub(self) typed_characters: Vec<char>,
+    /// Scroll of the mouse wheel since the last frame, in pixels, positive towards the bottom
+    pub(self) wheel_delta: f32,
+    /// The areas that clip what is drawn, from the outermost to the innermost
+    pub(self) clips: Vec<ClipRect>,
 
     pub(self) primitives: PrimitiveBuffer,
+    /// What is drawn over everything else, including the text of the primitives
+    pub(self) overlay: PrimitiveBuffer,
+    /// The text of the tooltip under the mouse during this frame
+    pub(self) tooltip: Option<String>,
+    /// The text of the tooltip under the mouse during the last frame, and since when it is under the mouse
+    pub(self) hovered_tooltip: Option<(String, Instant)>,
 }
 
 impl Gui {
@@ -24,9 +56,17 @@ impl Gui {
             mouse_x: 0,
             mouse_y: 0,
             mouse_down: false,
+            right_clicked: false,
             hot_item: 0,
             active_item: 0,
+            focused_item: 0,
+            typed_characters: Vec::new(),
+            wheel_delta: 0.0,
+            clips: Vec::new(),
             primitives: Default::default(),
+            overlay: Default::default(),
+            tooltip: None,
+            hovered_tooltip: None,
         }
     }
 
@@ -41,11 +81,49 @@ impl Gui {
         self.mouse_down = is_down;
     }
 
+    /// Update the state of the right mouse button, which can only be clicked
+    pub fn update_right_mouse_button(&mut self, is_down: bool) {
+        self.right_clicked |= is_down;
+    }
+
+    /// Add characters typed by the player, received by the focused text field during the next frame
+    pub fn update_typed_characters(&mut self, characters: impl IntoIterator<Item = char>) {
+        self.typed_characters.extend(characters);
+    }
+
+    /// Scroll with the mouse wheel, which scrolls the area under the mouse during the next frame
+    pub fn update_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        self.wheel_delta -= match delta {
+            MouseScrollDelta::LineDelta(_, y) => y * SCROLL_LINE_HEIGHT,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32,
+        };
+    }
+
+    /// Whether a text field receives the typed characters
+    pub fn has_focus(&self) -> bool {
+        self.focused_item != 0
+    }
+
+    /// Make the text field with this id receive the typed characters, as if it was clicked
+    pub fn focus_text_field(&mut self, id: u32) {
+        self.focused_item = id + 2;
+    }
+
+    /// Make no text field receive the typed characters
+    pub fn clear_focus(&mut self) {
+        self.focused_item = 0;
+    }
+
     /// Drain stores primitives
     pub fn drain_primitives(&mut self) -> PrimitiveBuffer {
         std::mem::replace(&mut self.primitives, PrimitiveBuffer::default())
     }
 
+    /// Drain the primitives drawn over everything else
+    pub fn drain_overlay_primitives(&mut self) -> PrimitiveBuffer {
+        std::mem::take(&mut self.overlay)
+    }
+
     /// Prepare for frame drawing
     pub fn prepare(&mut self) {
         self.hot_item = 0;
@@ -53,6 +131,11 @@ impl Gui {
 
     /// Finish the frame
     pub fn finish(&mut self) {
+        self.show_tooltip(Instant::now());
+        // The characters typed while no text field is focused are lost, and so is the scroll outside of the scroll areas
+        self.typed_characters.clear();
+        self.wheel_delta = 0.0;
+        self.right_clicked = false;
         if !self.mouse_down {
             // If the mouse button is not down, then we allow an item to become active
             // when the mouse button will be pressed.
@@ -66,9 +149,47 @@ impl Gui {
         }
     }
 
-    /// Is the mouse inside the rectangle
+    /// Show the tooltip of this frame once the mouse stayed over it long enough, next to the mouse
+    fn show_tooltip(&mut self, now: Instant) {
+        let tooltip = match self.tooltip.take() {
+            Some(tooltip) => tooltip,
+            None => {
+                self.hovered_tooltip = None;
+                return;
+            }
+        };
+        let since = match &self.hovered_tooltip {
+            Some((hovered, since)) if *hovered == tooltip => *since,
+            _ => now,
+        };
+        if now.duration_since(since) >= TOOLTIP_DELAY {
+            let lines = tooltip.lines().collect::<Vec<_>>();
+            let max_chars = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as i32;
+            let w = max_chars * TOOLTIP_CHAR_WIDTH + 2 * TOOLTIP_PADDING;
+            let h = lines.len() as i32 * TOOLTIP_LINE_HEIGHT + 2 * TOOLTIP_PADDING;
+            let (x, y) = (self.mouse_x + TOOLTIP_MOUSE_OFFSET, self.mouse_y + TOOLTIP_MOUSE_OFFSET);
+            self.overlay.draw_rect(x, y, w, h, [0.5, 0.5, 0.5, 1.0], 0.0);
+            self.overlay.draw_rect(x + 1, y + 1, w - 2, h - 2, [0.1, 0.1, 0.1, 0.95], 0.0);
+            for (i, line) in lines.into_iter().enumerate() {
+                let line_y = y + TOOLTIP_PADDING + i as i32 * TOOLTIP_LINE_HEIGHT;
+                let color = [1.0, 1.0, 1.0, 1.0];
+                self.overlay.draw_text_simple(x + TOOLTIP_PADDING, line_y, TOOLTIP_LINE_HEIGHT, line.to_owned(), color, 0.0);
+            }
+        }
+        self.hovered_tooltip = Some((tooltip, since));
+    }
+
+    /// Show a tooltip with some text, which may have several lines, when the mouse stays over the rectangle
+    pub fn tooltip(&mut self, x: i32, y: i32, w: i32, h: i32, text: &str) {
+        if self.is_mouse_inside(x, y, w, h) {
+            self.tooltip = Some(text.to_owned());
+        }
+    }
+
+    /// Is the mouse inside the rectangle, and inside the scroll area that clips it if any
     pub fn is_mouse_inside(&self, x: i32, y: i32, w: i32, h: i32) -> bool {
-        x <= self.mouse_x && self.mouse_x < x + w && y <= self.mouse_y && self.mouse_y < y + h
+        let inside_clip = self.clips.last().map(|clip| clip.contains(self.mouse_x, self.mouse_y)).unwrap_or(true);
+        inside_clip && x <= self.mouse_x && self.mouse_x < x + w && y <= self.mouse_y && self.mouse_y < y + h
     }
 
     /// Draw a button, returning whether the button was pressed
@@ -84,6 +205,65 @@ impl Gui {
         }
     }
 
+    /// Draw a checkbox for an option that is on or off
+    pub fn checkbox(&mut self, id: u32, x: i32, y: i32, w: i32, h: i32) -> CheckboxBuilder<'_> {
+        CheckboxBuilder {
+            gui: self,
+            id: id + 2,
+            x,
+            y,
+            w,
+            h,
+            label: String::new(),
+        }
+    }
+
+    /// Draw a slider for a value in some range
+    pub fn slider(&mut self, id: u32, x: i32, y: i32, w: i32, h: i32, range: RangeInclusive<u64>) -> SliderBuilder<'_> {
+        SliderBuilder {
+            gui: self,
+            id: id + 2,
+            x,
+            y,
+            w,
+            h,
+            range,
+            label: None,
+            unit: String::new(),
+        }
+    }
+
+    /// Draw a text field that edits a string once it is clicked
+    pub fn text_field(&mut self, id: u32, x: i32, y: i32, w: i32, h: i32) -> TextFieldBuilder<'_> {
+        TextFieldBuilder {
+            gui: self,
+            id: id + 2,
+            x,
+            y,
+            w,
+            h,
+            max_length: TEXT_FIELD_MAX_LENGTH,
+        }
+    }
+
+    /// Draw an area whose content scrolls vertically with the mouse wheel or its scroll bar
+    pub fn scroll_area(&mut self, id: u32, x: i32, y: i32, w: i32, h: i32) -> ScrollAreaBuilder<'_> {
+        ScrollAreaBuilder {
+            gui: self,
+            id: id + 2,
+            x,
+            y,
+            w,
+            h,
+        }
+    }
+
+    /// Stop clipping what is drawn to the last scroll area
+    pub fn end_scroll_area(&mut self) {
+        self.clips.pop();
+        self.primitives.set_clip(self.clips.last().copied());
+    }
+
     /// Draw text, aligned to the left but centered vertically
     pub fn text(&mut self, x: i32, y: i32, h: i32, text: String, color: [f32; 4], z: f32) {
         self.primitives.draw_text_simple(x, y, h, text, color, z);
@@ -92,6 +272,30 @@ impl Gui {
 
 // TODO: fix depth
 
+/// Margin between the border of the checkboxes and their box, and between their box and their label
+const CHECKBOX_MARGIN: i32 = 4;
+/// Width of the handle of the sliders
+const SLIDER_HANDLE_WIDTH: i32 = 8;
+/// Width of the scroll bars, on the right of the scroll areas
+pub const SCROLL_BAR_WIDTH: i32 = 6;
+/// Minimum height of the handle of the scroll bars
+const SCROLL_HANDLE_MIN_HEIGHT: i32 = 16;
+/// Distance scrolled by a line of the mouse wheel, in pixels
+const SCROLL_LINE_HEIGHT: f32 = 25.0;
+/// Default maximum number of characters of the text fields
+const TEXT_FIELD_MAX_LENGTH: usize = 64;
+/// How long the mouse stays over something before its tooltip is shown
+const TOOLTIP_DELAY: Duration = Duration::from_millis(500);
+/// Distance between the mouse and the top left corner of the tooltips
+const TOOLTIP_MOUSE_OFFSET: i32 = 12;
+/// Space between the border of the tooltips and their text
+const TOOLTIP_PADDING: i32 = 4;
+/// Height of the lines of the tooltips, and width of their characters in the monospace font
+const TOOLTIP_LINE_HEIGHT: i32 = 20;
+const TOOLTIP_CHAR_WIDTH: i32 = 12;
+/// The character that winit sends for the backspace key
+const BACKSPACE: char = '\u{8}';
+
 /// Builder for a button
 #[must_use]
 pub struct ButtonBuilder<'a> {
@@ -153,4 +357,297 @@ impl<'a> ButtonBuilder<'a> {
         self.text = Some((text, color));
         self
     }
-}
\ No newline at end of file
+}
+
+/// Builder for a checkbox
+#[must_use]
+pub struct CheckboxBuilder<'a> {
+    gui: &'a mut Gui,
+    id: u32,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    label: String,
+}
+
+impl<'a> CheckboxBuilder<'a> {
+    /// Build the checkbox showing whether it is checked, returning the new state when it is clicked
+    pub fn build(self, checked: bool) -> Option<bool> {
+        let Self { gui, id, x, y, w, h, label } = self;
+        if gui.is_mouse_inside(x, y, w, h) {
+            gui.hot_item = id;
+            if gui.active_item == 0 && gui.mouse_down {
+                gui.active_item = id;
+            }
+        }
+        // Like the buttons, the checkbox is clicked when the mouse is released over it
+        let clicked = !gui.mouse_down && gui.active_item == id && gui.hot_item == id;
+        let checked = checked != clicked;
+        // Draw the shadow, the background, the box with its check and the label
+        gui.primitives.draw_rect(x + 3, y + 3, w, h, [0.0, 0.0, 0.0, 1.0], 0.02);
+        let color = if gui.hot_item == id { [0.7, 0.7, 0.7, 1.0] } else { [0.8, 0.8, 0.8, 1.0] };
+        gui.primitives.draw_rect(x, y, w, h, color, 0.01);
+        let box_size = h - 2 * CHECKBOX_MARGIN;
+        let (box_x, box_y) = (x + CHECKBOX_MARGIN, y + CHECKBOX_MARGIN);
+        gui.primitives.draw_rect(box_x, box_y, box_size, box_size, [0.2, 0.2, 0.2, 1.0], 0.008);
+        if checked {
+            let check_offset = CHECKBOX_MARGIN / 2;
+            let check_size = box_size - 2 * check_offset;
+            let check_color = [0.3, 0.8, 0.3, 1.0];
+            gui.primitives.draw_rect(box_x + check_offset, box_y + check_offset, check_size, check_size, check_color, 0.007);
+        }
+        gui.text(box_x + box_size + CHECKBOX_MARGIN, y, h, label, [1.0, 1.0, 1.0, 1.0], 0.005);
+        Some(checked).filter(|_| clicked)
+    }
+
+    /// Add a label after the box
+    pub fn label(mut self, label: String) -> Self {
+        self.label = label;
+        self
+    }
+}
+
+/// Builder for a slider
+#[must_use]
+pub struct SliderBuilder<'a> {
+    gui: &'a mut Gui,
+    id: u32,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    range: RangeInclusive<u64>,
+    label: Option<String>,
+    unit: String,
+}
+
+impl<'a> SliderBuilder<'a> {
+    /// Build the slider showing some value, returning the new value when the slider is dragged to another value
+    pub fn build(self, value: u64) -> Option<u64> {
+        let Self { gui, id, x, y, w, h, range, label, unit } = self;
+        let (min, max) = (*range.start(), *range.end());
+        if gui.is_mouse_inside(x, y, w, h) {
+            gui.hot_item = id;
+            if gui.active_item == 0 && gui.mouse_down {
+                gui.active_item = id;
+            }
+        }
+        // While the slider is dragged, the value follows the mouse even outside of it
+        let new_value = if gui.active_item == id && gui.mouse_down {
+            let fraction = ((gui.mouse_x - x) as f64 / (w - 1).max(1) as f64).clamp(0.0, 1.0);
+            min + (fraction * (max - min) as f64).round() as u64
+        } else {
+            value.clamp(min, max)
+        };
+        // Draw the shadow, the track and the handle
+        gui.primitives.draw_rect(x + 3, y + 3, w, h, [0.0, 0.0, 0.0, 1.0], 0.02);
+        let track_color = if gui.hot_item == id { [0.7, 0.7, 0.7, 1.0] } else { [0.8, 0.8, 0.8, 1.0] };
+        gui.primitives.draw_rect(x, y, w, h, track_color, 0.01);
+        let fraction = (new_value - min) as f64 / (max - min).max(1) as f64;
+        let handle_x = x + (fraction * (w - SLIDER_HANDLE_WIDTH) as f64) as i32;
+        gui.primitives.draw_rect(handle_x, y, SLIDER_HANDLE_WIDTH, h, [0.4, 0.4, 0.4, 1.0], 0.008);
+        let text = match label {
+            Some(label) => format!("{}: {}{}", label, new_value, unit),
+            None => format!("{}{}", new_value, unit),
+        };
+        gui.text(x, y, h, text, [1.0, 1.0, 1.0, 1.0], 0.005);
+        Some(new_value).filter(|new_value| *new_value != value)
+    }
+
+    /// Add a label before the value
+    pub fn label(mut self, label: String) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Add a unit after the value
+    pub fn unit(mut self, unit: String) -> Self {
+        self.unit = unit;
+        self
+    }
+}
+
+/// Builder for a text field
+#[must_use]
+pub struct TextFieldBuilder<'a> {
+    gui: &'a mut Gui,
+    id: u32,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    /// Maximum number of characters of the text
+    max_length: usize,
+}
+
+impl<'a> TextFieldBuilder<'a> {
+    /// Build the text field editing some text, returning whether enter was pressed while it was focused
+    pub fn build(self, text: &mut String) -> bool {
+        let Self { gui, id, x, y, w, h, max_length } = self;
+        // Clicking the field focuses it, and clicking anywhere else unfocuses it
+        if gui.mouse_down {
+            if gui.is_mouse_inside(x, y, w, h) {
+                gui.focused_item = id;
+            } else if gui.focused_item == id {
+                gui.focused_item = 0;
+            }
+        }
+        let focused = gui.focused_item == id;
+        let mut submitted = false;
+        if focused {
+            for c in gui.typed_characters.drain(..) {
+                match c {
+                    BACKSPACE => {
+                        text.pop();
+                    }
+                    '\r' | '\n' => submitted = true,
+                    c if !c.is_control() && text.chars().count() < max_length => text.push(c),
+                    _ => {}
+                }
+            }
+        }
+        let border_color = if focused { [1.0, 1.0, 1.0, 1.0] } else { [0.5, 0.5, 0.5, 1.0] };
+        gui.primitives.draw_rect(x, y, w, h, border_color, 0.02);
+        gui.primitives.draw_rect(x + 1, y + 1, w - 2, h - 2, [0.0, 0.0, 0.0, 1.0], 0.01);
+        let shown = if focused { format!("{}_", text) } else { text.clone() };
+        gui.text(x + 4, y, h, shown, [1.0, 1.0, 1.0, 1.0], 0.005);
+        submitted
+    }
+
+    /// Change the maximum number of characters of the text
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+}
+
+/// Builder for a scroll area
+#[must_use]
+pub struct ScrollAreaBuilder<'a> {
+    gui: &'a mut Gui,
+    id: u32,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+impl<'a> ScrollAreaBuilder<'a> {
+    /// Start drawing content of some height, scrolled down by `scroll` pixels, which the mouse wheel and the scroll bar
+    /// change. Return the position of the top of the content. What is drawn until `Gui::end_scroll_area` is clipped
+    /// to the area, whose right side is covered by the scroll bar when the content doesn't fit.
+    pub fn begin(self, content_height: i32, scroll: &mut i32) -> i32 {
+        let Self { gui, id, x, y, w, h } = self;
+        let max_scroll = (content_height - h).max(0);
+        if gui.wheel_delta != 0.0 && gui.is_mouse_inside(x, y, w, h) {
+            *scroll += gui.wheel_delta.round() as i32;
+            gui.wheel_delta = 0.0;
+        }
+        if max_scroll > 0 {
+            let bar_x = x + w - SCROLL_BAR_WIDTH;
+            let handle_height = (h * h / content_height).max(SCROLL_HANDLE_MIN_HEIGHT).min(h);
+            if gui.is_mouse_inside(bar_x, y, SCROLL_BAR_WIDTH, h) {
+                gui.hot_item = id;
+                if gui.active_item == 0 && gui.mouse_down {
+                    gui.active_item = id;
+                }
+            }
+            // While the bar is dragged, the middle of the handle follows the mouse
+            if gui.active_item == id && gui.mouse_down {
+                let fraction = (gui.mouse_y - y - handle_height / 2) as f64 / (h - handle_height).max(1) as f64;
+                *scroll = (fraction.clamp(0.0, 1.0) * max_scroll as f64).round() as i32;
+            }
+            *scroll = (*scroll).clamp(0, max_scroll);
+            let handle_y = y + (*scroll as i64 * (h - handle_height) as i64 / max_scroll as i64) as i32;
+            let handle_color = if gui.hot_item == id { [0.7, 0.7, 0.7, 1.0] } else { [0.8, 0.8, 0.8, 1.0] };
+            gui.primitives.draw_rect(bar_x, y, SCROLL_BAR_WIDTH, h, [0.0, 0.0, 0.0, 0.5], 0.01);
+            gui.primitives.draw_rect(bar_x, handle_y, SCROLL_BAR_WIDTH, handle_height, handle_color, 0.008);
+        } else {
+            *scroll = 0;
+        }
+        let area = ClipRect { x, y, w, h };
+        let clip = gui.clips.last().map(|clip| clip.intersection(area)).unwrap_or(area);
+        gui.clips.push(clip);
+        gui.primitives.set_clip(Some(clip));
+        y - *scroll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draw a frame with a slider from 0 to 100 that is 101 pixels wide, returning its new value
+    fn slider_frame(gui: &mut Gui, value: u64) -> Option<u64> {
+        gui.prepare();
+        let new_value = gui.slider(10, 0, 0, 101, 20, 0..=100).build(value);
+        gui.finish();
+        new_value
+    }
+
+    #[test]
+    fn checkboxes_are_toggled_when_the_mouse_is_released_over_them() {
+        let mut gui = Gui::new();
+        let frame = |gui: &mut Gui, checked| {
+            gui.prepare();
+            let new_state = gui.checkbox(10, 0, 0, 100, 20).build(checked);
+            gui.finish();
+            new_state
+        };
+        gui.update_mouse_position(50, 10);
+        gui.update_mouse_button(true);
+        assert_eq!(frame(&mut gui, false), None);
+        gui.update_mouse_button(false);
+        assert_eq!(frame(&mut gui, false), Some(true));
+        assert_eq!(frame(&mut gui, true), None);
+        // Releasing the mouse outside of the checkbox doesn't toggle it
+        gui.update_mouse_button(true);
+        assert_eq!(frame(&mut gui, true), None);
+        gui.update_mouse_position(150, 10);
+        gui.update_mouse_button(false);
+        assert_eq!(frame(&mut gui, true), None);
+    }
+
+    #[test]
+    fn tooltips_are_shown_after_a_delay() {
+        let mut gui = Gui::new();
+        let start = Instant::now();
+        let frame = |gui: &mut Gui, text: &str, elapsed: u64| {
+            gui.tooltip(0, 0, 100, 20, text);
+            gui.show_tooltip(start + Duration::from_millis(elapsed));
+            gui.drain_overlay_primitives()
+        };
+        gui.update_mouse_position(50, 10);
+        assert!(frame(&mut gui, "Name", 0).text.is_empty());
+        assert!(frame(&mut gui, "Name", 400).text.is_empty());
+        let overlay = frame(&mut gui, "Name\nSecond line", 600);
+        // Another text restarts the delay
+        assert!(overlay.text.is_empty());
+        let overlay = frame(&mut gui, "Name\nSecond line", 1100);
+        assert_eq!(overlay.text.len(), 2);
+        assert_eq!(overlay.rectangle.len(), 2);
+        // Leaving the rectangle hides the tooltip and restarts the delay
+        gui.update_mouse_position(150, 10);
+        assert!(frame(&mut gui, "Name\nSecond line", 1200).text.is_empty());
+        gui.update_mouse_position(50, 10);
+        assert!(frame(&mut gui, "Name\nSecond line", 1300).text.is_empty());
+    }
+
+    #[test]
+    fn sliders_follow_the_mouse_while_they_are_dragged() {
+        let mut gui = Gui::new();
+        gui.update_mouse_position(30, 10);
+        assert_eq!(slider_frame(&mut gui, 50), None);
+        gui.update_mouse_button(true);
+        assert_eq!(slider_frame(&mut gui, 50), Some(30));
+        // Moving without changing the value doesn't report a change
+        assert_eq!(slider_frame(&mut gui, 30), None);
+        // The slider is still dragged outside of it
+        gui.update_mouse_position(500, 200);
+        assert_eq!(slider_frame(&mut gui, 30), Some(100));
+        gui.update_mouse_button(false);
+        gui.update_mouse_position(-20, 10);
+        assert_eq!(slider_frame(&mut gui, 100), None);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::keybindings::{Action, KeyBindings, KeyCapture, KeyChord};
+use crate::lang::{language_names, next_language, tr, tr_args, translate};
+use crate::render_distance::HORIZONTAL_RENDER_DISTANCES;
+use crate::settings::{Lighting, Settings, Vsync};
+use std::ops::RangeInclusive;
+use winit::event::{ElementState, MouseButton};
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const OPTIONS_WIDTH: i32 = 320;
+/// Offset of the ids of the options, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 4 << 20;
+/// The vertical fields of view that can be chosen, in degrees
+const FOV_RANGE: RangeInclusive<u64> = 30..=120;
+/// The sensitivities of the mouse that can be chosen, in percent
+const MOUSE_SENSITIVITY_RANGE: RangeInclusive<u64> = 10..=300;
+/// The frame rate caps that the button cycles through, `None` for no cap
+const MAX_FPS_CHOICES: [Option<u32>; 6] = [None, Some(30), Some(60), Some(120), Some(144), Some(240)];
+/// The scales of the UI that the button cycles through, `None` to follow the scale factor of the screen
+const UI_SCALE_CHOICES: [Option<f64>; 6] = [None, Some(1.0), Some(1.25), Some(1.5), Some(2.0), Some(3.0)];
+/// Pressing Escape while an action is rebound cancels the rebinding
+const ESCAPE: KeyChord = KeyChord::key(1);
+
+/// A page of the settings screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsTab {
+    Graphics,
+    Controls,
+    Audio,
+}
+
+/// A change made in the settings screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingsChange {
+    RenderDistance(u64),
+    AutoRenderDistance(bool),
+    Fov(f64),
+    Vsync(Vsync),
+    MaxFps(Option<u32>),
+    UiScale(Option<f64>),
+    /// Use the language after the current one
+    NextLanguage,
+    Fullscreen(bool),
+    OcclusionCulling(bool),
+    ViewBobbing(bool),
+    CameraSmoothing(bool),
+    /// Whether the light is smooth across the faces of the blocks instead of flat
+    SmoothLighting(bool),
+    MouseSensitivity(f64),
+    MasterVolume(f32),
+    MusicVolume(f32),
+    EffectsVolume(f32),
+    /// Bind a key to an action instead of its previous keys
+    Rebind(Action, KeyChord),
+    ResetKeyBindings,
+}
+
+/// The state of the settings screen that lasts between the frames
+#[derive(Debug)]
+pub struct OptionsMenu {
+    tab: SettingsTab,
+    /// The action that the next key rebinds, with the keys pressed since it was clicked
+    rebinding: Option<(Action, KeyCapture)>,
+    /// The new key of the action that was rebound, turned into a change during the next frame
+    captured: Option<(Action, KeyChord)>,
+    /// What happened to the last key that was rebound
+    message: Option<String>,
+    /// How far the list of the actions is scrolled, in pixels
+    bindings_scroll: i32,
+}
+
+impl Default for OptionsMenu {
+    fn default() -> Self {
+        Self {
+            tab: SettingsTab::Graphics,
+            rebinding: None,
+            captured: None,
+            message: None,
+            bindings_scroll: 0,
+        }
+    }
+}
+
+impl OptionsMenu {
+    /// Whether the keys and the mouse buttons rebind an action instead of doing their usual job
+    pub fn is_rebinding(&self) -> bool {
+        self.rebinding.is_some()
+    }
+
+    pub fn handle_key(&mut self, scancode: u32, state: ElementState) {
+        if let Some((action, capture)) = self.rebinding.as_mut() {
+            if let Some(chord) = capture.key(scancode, state) {
+                let action = *action;
+                self.capture(action, chord);
+            }
+        }
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if let (Some((action, capture)), ElementState::Pressed) = (self.rebinding.as_mut(), state) {
+            let (action, chord) = (*action, capture.mouse_button(button));
+            self.capture(action, chord);
+        }
+    }
+
+    fn capture(&mut self, action: Action, chord: KeyChord) {
+        self.rebinding = None;
+        if chord == ESCAPE {
+            self.message = Some(tr_args("settings.keys.kept", &[&action_name(action)]));
+        } else {
+            self.captured = Some((action, chord));
+        }
+    }
+}
+
+impl SettingsChange {
+    /// Whether the change affects the chunks that are loaded
+    pub fn changes_render_distance(self) -> bool {
+        matches!(self, SettingsChange::RenderDistance(_) | SettingsChange::AutoRenderDistance(_))
+    }
+
+    pub fn apply(self, settings: &mut Settings) {
+        match self {
+            SettingsChange::RenderDistance(distance) => settings.set_horizontal_render_distance(distance),
+            SettingsChange::AutoRenderDistance(automatic) => settings.auto_render_distance = automatic,
+            SettingsChange::Fov(fov) => settings.fov = fov,
+            SettingsChange::Vsync(vsync) => settings.vsync = vsync,
+            SettingsChange::MaxFps(max_fps) => settings.max_fps = max_fps,
+            SettingsChange::UiScale(scale) => settings.ui_scale = scale,
+            SettingsChange::NextLanguage => settings.language = next_language(&settings.language),
+            SettingsChange::Fullscreen(fullscreen) => settings.fullscreen = fullscreen,
+            SettingsChange::OcclusionCulling(culling) => settings.occlusion_culling = culling,
+            SettingsChange::ViewBobbing(bobbing) => settings.view_bobbing = bobbing,
+            SettingsChange::CameraSmoothing(smoothing) => settings.camera_smoothing = smoothing,
+            SettingsChange::SmoothLighting(smooth) => {
+                settings.lighting = if smooth { Lighting::Smooth } else { Lighting::Flat };
+            }
+            SettingsChange::MouseSensitivity(sensitivity) => settings.mouse_sensitivity = sensitivity,
+            SettingsChange::MasterVolume(volume) => settings.audio.master_volume = volume,
+            SettingsChange::MusicVolume(volume) => settings.audio.music_volume = volume,
+            SettingsChange::EffectsVolume(volume) => settings.audio.effects_volume = volume,
+            SettingsChange::Rebind(action, chord) => settings.key_bindings.rebind(action, chord),
+            SettingsChange::ResetKeyBindings => settings.key_bindings = KeyBindings::default(),
+        }
+    }
+}
+
+/// Draw the settings at the top of the screen, below the toasts, returning the change made by the player if any.
+/// The long pages scroll down to the bottom of the window, above the room for a button.
+/// `auto_reduction` is by how many chunks the automatic render distance currently reduces the render distance.
+pub fn render_options(
+    gui: &mut super::Gui,
+    (window_width, window_height): (i32, i32),
+    settings: &Settings,
+    menu: &mut OptionsMenu,
+    auto_reduction: u64,
+) -> Option<SettingsChange> {
+    let x = (window_width - OPTIONS_WIDTH) / 2;
+    let mut y = 4 + 3 * ELEMENT_OFFSET;
+    gui.text(x, y, ELEMENT_HEIGHT, tr("settings.title"), [1.0, 1.0, 1.0, 1.0], 0.02);
+    y += ELEMENT_OFFSET;
+    let tabs = [
+        ("settings.tab.graphics", SettingsTab::Graphics),
+        ("settings.tab.controls", SettingsTab::Controls),
+        ("settings.tab.audio", SettingsTab::Audio),
+    ];
+    let tab_width = (OPTIONS_WIDTH - 10) / 3;
+    for (i, &(key, new_tab)) in tabs.iter().enumerate() {
+        let text = if menu.tab == new_tab { format!("> {} <", tr(key)) } else { tr(key) };
+        let tab_x = x + i as i32 * (tab_width + 5);
+        if gui.button(BUTTON_ID_OFFSET + i as u32, tab_x, y, tab_width, ELEMENT_HEIGHT).text(text, [1.0, 1.0, 1.0, 1.0]).build() {
+            menu.tab = new_tab;
+            menu.rebinding = None;
+        }
+    }
+    y += ELEMENT_OFFSET;
+    let mut rows = OptionRows { gui, x, y, id: BUTTON_ID_OFFSET + 16, change: None };
+    match menu.tab {
+        SettingsTab::Graphics => {
+            let distance = rows.slider(
+                "settings.render_distance",
+                HORIZONTAL_RENDER_DISTANCES,
+                settings.render_distance.0,
+                tr("settings.unit.chunks"),
+            );
+            if let Some(distance) = distance {
+                rows.change = Some(SettingsChange::RenderDistance(distance));
+            }
+            if let Some(automatic) = rows.toggle("settings.auto_render_distance", settings.auto_render_distance) {
+                rows.change = Some(SettingsChange::AutoRenderDistance(automatic));
+            }
+            rows.explain("settings.auto_render_distance.explanation");
+            if let Some(fov) = rows.slider("settings.fov", FOV_RANGE, settings.fov.round() as u64, "°".to_owned()) {
+                rows.change = Some(SettingsChange::Fov(fov as f64));
+            }
+            let vsync = match settings.vsync {
+                Vsync::On => "settings.vsync.on",
+                Vsync::Off => "settings.vsync.off",
+                Vsync::Mailbox => "settings.vsync.mailbox",
+            };
+            if rows.button(tr_args("settings.vsync", &[&tr(vsync)])) {
+                rows.change = Some(SettingsChange::Vsync(match settings.vsync {
+                    Vsync::On => Vsync::Off,
+                    Vsync::Off => Vsync::Mailbox,
+                    Vsync::Mailbox => Vsync::On,
+                }));
+            }
+            rows.explain("settings.vsync.explanation");
+            let max_fps = settings.max_fps.map(|fps| fps.to_string()).unwrap_or_else(|| tr("settings.max_fps.unlimited"));
+            if rows.button(tr_args("settings.max_fps", &[&max_fps])) {
+                rows.change = Some(SettingsChange::MaxFps(next_max_fps(settings.max_fps)));
+            }
+            let ui_scale = settings.ui_scale.map(|scale| format!("{}x", scale)).unwrap_or_else(|| tr("settings.ui_scale.auto"));
+            if rows.button(tr_args("settings.ui_scale", &[&ui_scale])) {
+                rows.change = Some(SettingsChange::UiScale(next_ui_scale(settings.ui_scale)));
+            }
+            rows.explain("settings.ui_scale.explanation");
+            let language = language_names()
+                .into_iter()
+                .find(|(code, _)| *code == settings.language)
+                .map(|(_, name)| name)
+                .unwrap_or_else(|| settings.language.clone());
+            if rows.button(tr_args("settings.language", &[&language])) {
+                rows.change = Some(SettingsChange::NextLanguage);
+            }
+            if let Some(fullscreen) = rows.toggle("settings.fullscreen", settings.fullscreen) {
+                rows.change = Some(SettingsChange::Fullscreen(fullscreen));
+            }
+            if let Some(culling) = rows.toggle("settings.occlusion_culling", settings.occlusion_culling) {
+                rows.change = Some(SettingsChange::OcclusionCulling(culling));
+            }
+            rows.explain("settings.occlusion_culling.explanation");
+            if let Some(smooth) = rows.toggle("settings.smooth_lighting", settings.lighting == Lighting::Smooth) {
+                rows.change = Some(SettingsChange::SmoothLighting(smooth));
+            }
+            rows.explain("settings.smooth_lighting.explanation");
+            if settings.auto_render_distance && auto_reduction > 0 {
+                rows.note(tr_args("settings.render_distance.reduced", &[&auto_reduction]));
+            }
+        }
+        SettingsTab::Controls => {
+            if let Some(bobbing) = rows.toggle("settings.view_bobbing", settings.view_bobbing) {
+                rows.change = Some(SettingsChange::ViewBobbing(bobbing));
+            }
+            rows.explain("settings.view_bobbing.explanation");
+            if let Some(smoothing) = rows.toggle("settings.camera_smoothing", settings.camera_smoothing) {
+                rows.change = Some(SettingsChange::CameraSmoothing(smoothing));
+            }
+            rows.explain("settings.camera_smoothing.explanation");
+            let sensitivity = (settings.mouse_sensitivity * 100.0).round() as u64;
+            if let Some(sensitivity) = rows.slider("settings.mouse_sensitivity", MOUSE_SENSITIVITY_RANGE, sensitivity, "%".to_owned()) {
+                rows.change = Some(SettingsChange::MouseSensitivity(sensitivity as f64 / 100.0));
+            }
+            if rows.button(tr("settings.keys.reset")) {
+                menu.rebinding = None;
+                menu.message = Some(tr("settings.keys.restored"));
+                rows.change = Some(SettingsChange::ResetKeyBindings);
+            }
+            if let Some((action, chord)) = menu.captured.take() {
+                let conflicts = settings.key_bindings.conflicting_actions(action, chord);
+                menu.message = Some(if conflicts.is_empty() {
+                    tr_args("settings.keys.bound", &[&action_name(action), &chord.describe()])
+                } else {
+                    let conflicts = conflicts.into_iter().map(action_name).collect::<Vec<_>>();
+                    tr_args("settings.keys.unbound", &[&chord.describe(), &conflicts.join(", ")])
+                });
+                rows.change = Some(SettingsChange::Rebind(action, chord));
+            }
+            let message = match &menu.rebinding {
+                Some((action, _)) => tr_args("settings.keys.press", &[&action_name(*action)]),
+                None => menu.message.clone().unwrap_or_else(|| tr("settings.keys.click")),
+            };
+            rows.note(message);
+            let rebinding = menu.rebinding.as_ref().map(|(action, _)| *action);
+            let rows_y = (rows.y, window_height - 2 * ELEMENT_OFFSET);
+            let bindings = &settings.key_bindings;
+            if let Some(action) = render_bindings(rows.gui, window_width, rows_y, bindings, rebinding, &mut menu.bindings_scroll) {
+                menu.rebinding = Some((action, KeyCapture::default()));
+            }
+        }
+        SettingsTab::Audio => {
+            let audio = settings.audio;
+            if let Some(volume) = rows.volume_slider("settings.volume.master", audio.master_volume) {
+                rows.change = Some(SettingsChange::MasterVolume(volume));
+            }
+            if let Some(volume) = rows.volume_slider("settings.volume.music", audio.music_volume) {
+                rows.change = Some(SettingsChange::MusicVolume(volume));
+            }
+            if let Some(volume) = rows.volume_slider("settings.volume.effects", audio.effects_volume) {
+                rows.change = Some(SettingsChange::EffectsVolume(volume));
+            }
+            // TODO: remove this note once the sounds are played
+            rows.note(tr("settings.no_sounds"));
+        }
+    }
+    rows.change
+}
+
+/// Draw the keys of every action in two columns that scroll down to `bottom`,
+/// returning the action that was clicked to rebind it
+fn render_bindings(
+    gui: &mut super::Gui,
+    window_width: i32,
+    (y, bottom): (i32, i32),
+    key_bindings: &KeyBindings,
+    rebinding: Option<Action>,
+    scroll: &mut i32,
+) -> Option<Action> {
+    let actions = Action::all().collect::<Vec<_>>();
+    let rows_per_column = actions.len().div_ceil(2);
+    let left_x = (window_width - 2 * OPTIONS_WIDTH - 5) / 2;
+    // The area leaves room for the shadows of the buttons and the scroll bar
+    let area_width = 2 * OPTIONS_WIDTH + 10 + super::SCROLL_BAR_WIDTH;
+    let content_height = rows_per_column as i32 * ELEMENT_OFFSET;
+    let area_height = (bottom - y).max(ELEMENT_OFFSET);
+    let top = gui.scroll_area(BUTTON_ID_OFFSET + 63, left_x, y, area_width, area_height).begin(content_height, scroll);
+    let mut clicked = None;
+    for (i, &action) in actions.iter().enumerate() {
+        let x = left_x + (i / rows_per_column) as i32 * (OPTIONS_WIDTH + 5);
+        let y = top + (i % rows_per_column) as i32 * ELEMENT_OFFSET;
+        let keys = match rebinding {
+            Some(rebound) if rebound == action => "...".to_owned(),
+            _ => {
+                let keys = key_bindings.keys_of(action).iter().map(KeyChord::describe).collect::<Vec<_>>();
+                if keys.is_empty() {
+                    tr("settings.keys.none")
+                } else {
+                    keys.join(", ")
+                }
+            }
+        };
+        let text = format!("{}: {}", action_name(action), keys);
+        let id = BUTTON_ID_OFFSET + 64 + i as u32;
+        if gui.button(id, x, y, OPTIONS_WIDTH, ELEMENT_HEIGHT).text(text, [1.0, 1.0, 1.0, 1.0]).build() {
+            clicked = Some(action);
+        }
+    }
+    gui.end_scroll_area();
+    clicked
+}
+
+/// The name of an action in the current language, or its English label if the language doesn't have it
+fn action_name(action: Action) -> String {
+    if let Action::HotbarSlot(slot) = action {
+        return tr_args("action.hotbar_slot", &[&(slot + 1)]);
+    }
+    let label = action.label();
+    translate(&format!("action.{}", label.to_lowercase().replace(' ', "_"))).unwrap_or(label)
+}
+
+/// The frame rate cap after `max_fps` in the choices of the button
+fn next_max_fps(max_fps: Option<u32>) -> Option<u32> {
+    let index = MAX_FPS_CHOICES.iter().position(|choice| *choice == max_fps).map(|i| i + 1).unwrap_or(0);
+    MAX_FPS_CHOICES[index % MAX_FPS_CHOICES.len()]
+}
+
+/// The scale of the UI after `scale` in the choices of the button
+fn next_ui_scale(scale: Option<f64>) -> Option<f64> {
+    let index = UI_SCALE_CHOICES.iter().position(|choice| *choice == scale).map(|i| i + 1).unwrap_or(0);
+    UI_SCALE_CHOICES[index % UI_SCALE_CHOICES.len()]
+}
+
+/// The rows of a page of the settings, one below the other
+struct OptionRows<'a> {
+    gui: &'a mut super::Gui,
+    x: i32,
+    y: i32,
+    /// Id of the next row
+    id: u32,
+    change: Option<SettingsChange>,
+}
+
+impl<'a> OptionRows<'a> {
+    /// The position and the id of the next row
+    fn next_row(&mut self) -> (i32, u32) {
+        let row = (self.y, self.id);
+        self.y += ELEMENT_OFFSET;
+        self.id += 1;
+        row
+    }
+
+    fn button(&mut self, text: String) -> bool {
+        let (y, id) = self.next_row();
+        self.gui.button(id, self.x, y, OPTIONS_WIDTH, ELEMENT_HEIGHT).text(text, [1.0, 1.0, 1.0, 1.0]).build()
+    }
+
+    /// A checkbox showing whether an option is on, returning the new state once it is clicked.
+    /// The label is the key of its text.
+    fn toggle(&mut self, label: &str, value: bool) -> Option<bool> {
+        let (y, id) = self.next_row();
+        self.gui.checkbox(id, self.x, y, OPTIONS_WIDTH, ELEMENT_HEIGHT).label(tr(label)).build(value)
+    }
+
+    /// A slider showing a value with its unit, returning the new value once it is moved.
+    /// The label is the key of its text.
+    fn slider(&mut self, label: &str, range: RangeInclusive<u64>, value: u64, unit: String) -> Option<u64> {
+        let (y, id) = self.next_row();
+        let slider = self.gui.slider(id, self.x, y, OPTIONS_WIDTH, ELEMENT_HEIGHT, range);
+        slider.label(tr(label)).unit(unit).build(value)
+    }
+
+    /// A slider of a volume from 0 to 1 shown in percent, returning the volume once it is moved
+    fn volume_slider(&mut self, label: &str, volume: f32) -> Option<f32> {
+        let percent = (volume.clamp(0.0, 1.0) * 100.0).round() as u64;
+        self.slider(label, 0..=100, percent, "%".to_owned()).map(|percent| percent as f32 / 100.0)
+    }
+
+    /// Show a tooltip explaining the last row when the mouse stays over it, from the key of the explanation
+    fn explain(&mut self, key: &str) {
+        self.gui.tooltip(self.x, self.y - ELEMENT_OFFSET, OPTIONS_WIDTH, ELEMENT_HEIGHT, &tr(key));
+    }
+
+    fn note(&mut self, text: String) {
+        let (y, _) = self.next_row();
+        self.gui.text(self.x, y, ELEMENT_HEIGHT, text, [1.0, 1.0, 1.0, 1.0], 0.02);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_applied_to_the_settings() {
+        let mut settings = Settings::default();
+        SettingsChange::RenderDistance(4).apply(&mut settings);
+        SettingsChange::MusicVolume(0.25).apply(&mut settings);
+        SettingsChange::MouseSensitivity(1.5).apply(&mut settings);
+        SettingsChange::SmoothLighting(false).apply(&mut settings);
+        assert_eq!((settings.render_distance.0, settings.render_distance.4), (4, 4));
+        assert_eq!(settings.audio.music_volume, 0.25);
+        assert_eq!(settings.mouse_sensitivity, 1.5);
+        assert_eq!(settings.lighting, Lighting::Flat);
+        assert!(SettingsChange::AutoRenderDistance(true).changes_render_distance());
+        assert!(!SettingsChange::Fov(70.0).changes_render_distance());
+        // The frame rate caps wrap around, and an unknown cap goes back to the first choice
+        assert_eq!(next_max_fps(Some(240)), None);
+        assert_eq!(next_max_fps(None), Some(30));
+        assert_eq!(next_max_fps(Some(75)), None);
+        // The scale of the UI follows the screen unless it is set, and stays in a usable range
+        assert_eq!(settings.ui_scale(2.0), 2.0);
+        SettingsChange::UiScale(next_ui_scale(None)).apply(&mut settings);
+        assert_eq!(settings.ui_scale(2.0), 1.0);
+        assert_eq!(next_ui_scale(Some(3.0)), None);
+        settings.ui_scale = Some(0.0);
+        assert_eq!(settings.ui_scale(1.0), 0.5);
+    }
+}
@@ -0,0 +1,111 @@
+use history_survival_common::{
+    block::painting::{PaintingImage, PaintingLimits},
+    world::BlockPos,
+};
+use std::path::{Path, PathBuf};
+
+/// The directory where the players put the images they want to display on the paintings
+const PAINTINGS_DIRECTORY: &str = "paintings";
+/// The extensions of the images that can be imported
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+/// Maximum number of images that are listed
+const MAX_LISTED_IMAGES: usize = 16;
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const PICKER_WIDTH: i32 = 400;
+/// Offset of the ids of the picker buttons, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 3 << 20;
+
+/// What the player chose to do with the painting
+#[derive(Debug, Clone)]
+pub enum PaintingPickerResult {
+    /// Display the imported image on the painting
+    Import(PaintingImage),
+    /// Close the picker without changing the painting
+    Cancel,
+}
+
+/// The state of the picker of the image of a painting
+pub struct PaintingPicker {
+    /// The position of the painting
+    pub pos: BlockPos,
+    /// The images that can be imported
+    images: Vec<PathBuf>,
+    /// The error of the last import, if it failed
+    error: Option<String>,
+}
+
+impl PaintingPicker {
+    pub fn new(pos: BlockPos) -> Self {
+        let (images, error) = match list_images(Path::new(PAINTINGS_DIRECTORY)) {
+            Ok(images) if images.is_empty() => (images, Some(format!("Put images in the {} directory", PAINTINGS_DIRECTORY))),
+            Ok(images) => (images, None),
+            Err(e) => (Vec::new(), Some(format!("Can't read the {} directory: {}", PAINTINGS_DIRECTORY, e))),
+        };
+        Self { pos, images, error }
+    }
+}
+
+/// The images of a directory that can be imported, sorted by name
+fn list_images(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut images = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    images.sort();
+    images.truncate(MAX_LISTED_IMAGES);
+    Ok(images)
+}
+
+/// Draw the painting picker in the middle of the screen, returning what the player chose to do once they are done
+pub fn render_painting_picker(
+    gui: &mut super::Gui,
+    window_width: i32,
+    picker: &mut PaintingPicker,
+    limits: PaintingLimits,
+) -> Option<PaintingPickerResult> {
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let black = [0.0, 0.0, 0.0, 1.0];
+    let x = (window_width - PICKER_WIDTH) / 2;
+    let mut y = 4 + ELEMENT_OFFSET;
+    gui.text(x, y, ELEMENT_HEIGHT, "PAINTING".to_owned(), white, 0.02);
+    y += ELEMENT_OFFSET;
+    gui.text(
+        x,
+        y,
+        ELEMENT_HEIGHT,
+        format!("Images are reduced to {} pixels and {} colors", limits.max_size, limits.max_colors),
+        white,
+        0.02,
+    );
+    y += ELEMENT_OFFSET;
+
+    let mut result = None;
+    for (i, path) in picker.images.iter().enumerate() {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        if gui.button(BUTTON_ID_OFFSET + i as u32, x, y, PICKER_WIDTH, ELEMENT_HEIGHT).text(name, black).build() {
+            match image::open(path) {
+                Ok(image) => result = Some(PaintingPickerResult::Import(PaintingImage::import(&image.to_rgba8(), limits))),
+                Err(e) => picker.error = Some(format!("Can't import {}: {}", path.display(), e)),
+            }
+        }
+        y += ELEMENT_OFFSET;
+    }
+    if let Some(error) = &picker.error {
+        gui.text(x, y, ELEMENT_HEIGHT, error.clone(), [1.0, 0.3, 0.3, 1.0], 0.02);
+        y += ELEMENT_OFFSET;
+    }
+    if gui.button(BUTTON_ID_OFFSET + MAX_LISTED_IMAGES as u32, x, y, PICKER_WIDTH, ELEMENT_HEIGHT).text("Cancel".to_owned(), black).build() {
+        result = Some(PaintingPickerResult::Cancel);
+    }
+    result
+}
@@ -0,0 +1,47 @@
+//! The menu opened with Escape during the game
+use crate::lang::tr;
+
+const ELEMENT_HEIGHT: i32 = 20;
+const ELEMENT_OFFSET: i32 = 25;
+const BUTTON_WIDTH: i32 = 320;
+/// Offset of the ids of the buttons, so that they don't collide with the other buttons
+const BUTTON_ID_OFFSET: u32 = 7 << 20;
+
+/// A button of the pause menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMenuButton {
+    Resume,
+    Options,
+    /// Leave the world, which is saved, and go back to the main menu
+    SaveAndQuit,
+}
+
+/// Draw the buttons of the pause menu in the middle of the window, returning the button that was clicked
+pub fn render_pause_menu(gui: &mut super::Gui, window_width: i32, window_height: i32) -> Option<PauseMenuButton> {
+    let buttons = [
+        ("pause.resume", PauseMenuButton::Resume),
+        ("pause.options", PauseMenuButton::Options),
+        ("pause.save_and_quit", PauseMenuButton::SaveAndQuit),
+    ];
+    let x = (window_width - BUTTON_WIDTH) / 2;
+    let mut y = (window_height - (buttons.len() as i32 + 2) * ELEMENT_OFFSET) / 2;
+    gui.primitives.draw_rect(x, y, BUTTON_WIDTH, ELEMENT_HEIGHT, [0.0, 0.0, 0.0, 0.5], 0.02);
+    gui.text(x + 6, y, ELEMENT_HEIGHT, tr("pause.title"), [1.0, 1.0, 1.0, 1.0], 0.01);
+    y += 2 * ELEMENT_OFFSET;
+    let mut clicked = None;
+    for (i, &(key, button)) in buttons.iter().enumerate() {
+        if gui.button(BUTTON_ID_OFFSET + i as u32, x, y, BUTTON_WIDTH, ELEMENT_HEIGHT).text(tr(key), [1.0, 1.0, 1.0, 1.0]).build() {
+            clicked = Some(button);
+        }
+        y += ELEMENT_OFFSET;
+    }
+    clicked
+}
+
+/// Draw the button at the bottom of the options that goes back to the pause menu, returning whether it was clicked
+pub fn render_options_back_button(gui: &mut super::Gui, window_width: i32, window_height: i32) -> bool {
+    let x = (window_width - BUTTON_WIDTH) / 2;
+    let y = window_height - 2 * ELEMENT_OFFSET;
+    let id = BUTTON_ID_OFFSET + 16;
+    gui.button(id, x, y, BUTTON_WIDTH, ELEMENT_HEIGHT).text(tr("gui.back"), [1.0, 1.0, 1.0, 1.0]).build()
+}
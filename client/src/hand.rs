@@ -0,0 +1,81 @@
+//! The item of the selected hotbar slot, held in the bottom right corner of the view.
+//! The held item is placed relative to the camera, which looks towards -z with +y up.
+use crate::render::Model;
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+/// How long a swing of the hand lasts
+pub const HAND_SWING_DURATION: Duration = Duration::from_millis(250);
+/// Vertical field of view of the held item, in degrees, with which the position of the held item was chosen
+pub const HELD_ITEM_FOV: f64 = 90.0;
+/// Size of the held item, in blocks
+const HELD_ITEM_MODEL_SIZE: f32 = 0.5;
+/// The center of the held item when it is not swinging
+const HELD_ITEM_CENTER: [f32; 3] = [0.55, -0.45, -0.9];
+/// The held item is turned towards the center of the view
+const HELD_ITEM_ROTATION: f32 = 0.4;
+/// The angle by which the swing tilts the held item forward, in radians
+const SWING_ANGLE: f32 = 0.9;
+/// How far the swing brings the held item forward and towards the center of the view
+const SWING_REACH: [f32; 3] = [-0.25, 0.1, -0.3];
+
+/// The swing of the hand when the player clicks
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HandSwing {
+    start: Option<Instant>,
+}
+
+impl HandSwing {
+    /// Start a swing, unless the previous one is still going on
+    pub fn swing(&mut self, now: Instant) {
+        if self.progress(now).is_none() {
+            self.start = Some(now);
+        }
+    }
+
+    /// How far the swing has gone, from 0 to 1, or `None` if the hand is not swinging
+    pub fn progress(&self, now: Instant) -> Option<f32> {
+        let elapsed = now.saturating_duration_since(self.start?);
+        if elapsed < HAND_SWING_DURATION {
+            Some(elapsed.as_secs_f32() / HAND_SWING_DURATION.as_secs_f32())
+        } else {
+            None
+        }
+    }
+}
+
+/// The model of a held item whose mesh has some scale and center, with the swing going out and back
+pub fn held_item_model(mesh_id: u32, item_scale: f32, mesh_center: (f32, f32, f32), swing: Option<f32>) -> Model {
+    let scale = item_scale * HELD_ITEM_MODEL_SIZE;
+    let half = [mesh_center.0 * scale, mesh_center.1 * scale, mesh_center.2 * scale];
+    let swing = swing.map(|progress| (progress * PI).sin()).unwrap_or(0.0);
+    let center = [0, 1, 2].map(|i| HELD_ITEM_CENTER[i] + SWING_REACH[i] * swing);
+    Model {
+        mesh_id,
+        pos_x: center[0] - half[0],
+        pos_y: center[1] - half[1],
+        pos_z: center[2] - half[2],
+        scale,
+        rot_offset: half,
+        rot_y: HELD_ITEM_ROTATION,
+        roll: -SWING_ANGLE * swing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swings_are_not_restarted() {
+        let start = Instant::now();
+        let mut hand = HandSwing::default();
+        assert_eq!(hand.progress(start), None);
+        hand.swing(start);
+        hand.swing(start + HAND_SWING_DURATION / 2);
+        assert_eq!(hand.progress(start + HAND_SWING_DURATION / 2), Some(0.5));
+        assert_eq!(hand.progress(start + HAND_SWING_DURATION), None);
+        hand.swing(start + HAND_SWING_DURATION);
+        assert_eq!(hand.progress(start + HAND_SWING_DURATION), Some(0.0));
+    }
+}
@@ -1,34 +1,107 @@
+use crate::keybindings::{Action, InputContext, Key, KeyBindings};
+use log::warn;
 use std::collections::HashMap;
 use history_survival_common::debug::send_debug_info;
+use history_survival_common::inventory::HOTBAR_SIZE;
 use history_survival_common::player::PlayerInput;
 use history_survival_common::physics::player::YawPitch;
-use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton};
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta};
 
 /// The state of the keyboard and mouse buttons.
 pub struct InputState {
     keys: HashMap<u32, ElementState>,
     mouse_buttons: HashMap<MouseButton, ElementState>,
     modifiers_state: ModifiersState,
-    flying: bool,             // TODO: reset this on game start
-    pub enable_culling: bool, // TODO: don't put this here
+    flying: bool, // TODO: reset this on game start
+    /// The selected slot of the hotbar
+    hotbar_slot: usize,
+    key_bindings: KeyBindings,
+    /// The context in which the keys are resolved, chosen by the current window state
+    context: InputContext,
+    /// The actions triggered by key presses since the last call to `take_triggered_actions`
+    triggered_actions: Vec<Action>,
 }
 
 impl InputState {
-    pub fn new() -> InputState {
-        Self {
+    pub fn new(key_bindings: KeyBindings) -> InputState {
+        let mut input_state = Self {
             keys: HashMap::new(),
             mouse_buttons: HashMap::new(),
             modifiers_state: ModifiersState::default(),
             flying: true,
-            enable_culling: true,
+            hotbar_slot: 0,
+            key_bindings: KeyBindings::default(),
+            context: InputContext::Gameplay,
+            triggered_actions: Vec::new(),
+        };
+        input_state.set_key_bindings(key_bindings);
+        input_state
+    }
+
+    /// The key bindings in use, without the conflicting keys
+    pub fn key_bindings(&self) -> &KeyBindings {
+        &self.key_bindings
+    }
+
+    /// Use other key bindings, for example after the player rebinds a key
+    pub fn set_key_bindings(&mut self, mut key_bindings: KeyBindings) {
+        for (context, chord, action) in key_bindings.remove_conflicts() {
+            warn!("Key {} is bound more than once in context {:?}, ignoring its binding to {:?}", chord, context, action);
         }
+        self.key_bindings = key_bindings;
+    }
+
+    /// Set the context in which the keys are resolved
+    pub fn set_context(&mut self, context: InputContext) {
+        self.context = context;
     }
 
     /// Process a keyboard input, returning whether the state of the key changed or not
     pub fn process_keyboard_input(&mut self, input: KeyboardInput) -> bool {
         let previous_state = self.keys.get(&input.scancode).cloned();
         self.keys.insert(input.scancode, input.state);
-        previous_state != Some(input.state)
+        let changed = previous_state != Some(input.state);
+        if changed && input.state == ElementState::Pressed {
+            self.trigger_actions(Key::Scancode(input.scancode));
+        }
+        changed
+    }
+
+    /// Trigger the actions bound to a key that was just pressed
+    fn trigger_actions(&mut self, key: Key) {
+        for action in self.key_bindings.resolve(key, self.modifiers_state, self.context) {
+            match action {
+                Action::HotbarSlot(slot) if slot < HOTBAR_SIZE => self.hotbar_slot = slot,
+                Action::ToggleFlying => self.flying = !self.flying,
+                _ => {}
+            }
+            self.triggered_actions.push(action);
+        }
+    }
+
+    /// Get the actions triggered by key presses since the last call
+    pub fn take_triggered_actions(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.triggered_actions)
+    }
+
+    /// Process a mouse wheel movement, scrolling down selects the next hotbar slot
+    pub fn process_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        if self.context != InputContext::Gameplay {
+            return;
+        }
+        let scroll = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y as f64,
+            MouseScrollDelta::PixelDelta(position) => position.y,
+        };
+        if scroll < 0.0 {
+            self.hotbar_slot = (self.hotbar_slot + 1) % HOTBAR_SIZE;
+        } else if scroll > 0.0 {
+            self.hotbar_slot = (self.hotbar_slot + HOTBAR_SIZE - 1) % HOTBAR_SIZE;
+        }
+    }
+
+    pub fn get_hotbar_slot(&self) -> usize {
+        self.hotbar_slot
     }
 
     /// Process a mouse input, returning whether the state of the button changed or not
@@ -39,7 +112,15 @@ impl InputState {
     ) -> bool {
         let previous_state = self.mouse_buttons.get(&button).cloned();
         self.mouse_buttons.insert(button, state);
-        previous_state != Some(state)
+        let changed = previous_state != Some(state);
+        if changed && state == ElementState::Pressed {
+            self.trigger_actions(Key::Mouse(button));
+        }
+        changed
+    }
+
+    pub fn is_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.get(&button) == Some(&ElementState::Pressed)
     }
 
     /// Update the modifiers
@@ -47,52 +128,40 @@ impl InputState {
         self.modifiers_state = modifiers_state;
     }
 
-    pub fn _get_modifiers_state(&self) -> ModifiersState {
+    pub fn get_modifiers_state(&self) -> ModifiersState {
         self.modifiers_state
     }
 
-    pub fn get_key_state(&self, scancode: u32) -> ElementState {
-        self.keys
-            .get(&scancode)
-            .cloned()
-            .unwrap_or(ElementState::Released)
-    }
-
     pub fn clear(&mut self) {
         self.keys.clear();
         self.mouse_buttons.clear();
         self.modifiers_state = ModifiersState::default();
     }
 
-    fn is_key_pressed(&self, scancode: u32) -> bool {
-        match self.get_key_state(scancode) {
-            ElementState::Pressed => true,
-            ElementState::Released => false,
-        }
+    /// Whether a held key or mouse button triggers the action in the current context
+    pub fn is_action_active(&self, action: Action) -> bool {
+        let keys = self.keys.iter().map(|(&scancode, &state)| (Key::Scancode(scancode), state));
+        let mouse_buttons = self.mouse_buttons.iter().map(|(&button, &state)| (Key::Mouse(button), state));
+        keys.chain(mouse_buttons).any(|(key, state)| {
+            state == ElementState::Pressed && self.key_bindings.resolve(key, self.modifiers_state, self.context).contains(&action)
+        })
     }
 
-    // TODO: add configuration for this
-    pub fn get_physics_input(&self, yaw_pitch: YawPitch, allow_movement: bool) -> PlayerInput {
+    pub fn get_physics_input(&self, yaw_pitch: YawPitch) -> PlayerInput {
         PlayerInput {
-            key_move_forward: allow_movement && self.is_key_pressed(MOVE_FORWARD),
-            key_move_left: allow_movement && self.is_key_pressed(MOVE_LEFT),
-            key_move_backward: allow_movement && self.is_key_pressed(MOVE_BACKWARD),
-            key_move_right: allow_movement && self.is_key_pressed(MOVE_RIGHT),
-            key_move_up: allow_movement && self.is_key_pressed(MOVE_UP),
-            key_move_down: allow_movement && self.is_key_pressed(MOVE_DOWN),
-            key_rotate_left: allow_movement && self.is_key_pressed(ROTATE_LEFT),
-            key_rotate_right: allow_movement && self.is_key_pressed(ROTATE_RIGHT),
+            key_move_forward: self.is_action_active(Action::MoveForward),
+            key_move_left: self.is_action_active(Action::MoveLeft),
+            key_move_backward: self.is_action_active(Action::MoveBackward),
+            key_move_right: self.is_action_active(Action::MoveRight),
+            key_move_up: self.is_action_active(Action::MoveUp),
+            key_move_down: self.is_action_active(Action::MoveDown),
+            key_rotate_left: self.is_action_active(Action::RotateLeft),
+            key_rotate_right: self.is_action_active(Action::RotateRight),
             yaw_pitch: yaw_pitch,
             flying: self.flying,
+            sprinting: self.is_action_active(Action::Sprint),
+            // Set from the game mode
+            no_clip: false,
         }
     }
 }
-
-pub const MOVE_FORWARD: u32 = 17;
-pub const MOVE_LEFT: u32 = 30;
-pub const MOVE_BACKWARD: u32 = 31;
-pub const MOVE_RIGHT: u32 = 32;
-pub const MOVE_UP: u32 = 57;
-pub const MOVE_DOWN: u32 = 42;
-pub const ROTATE_LEFT: u32 = 16;
-pub const ROTATE_RIGHT: u32 = 18;
@@ -0,0 +1,68 @@
+//! Selection of the icons of dynamic items, such as the compass and the clock.
+use history_survival_common::{
+    data::TextureRect,
+    item::{DynamicIcon, ItemMesh},
+    time::WorldTime,
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+
+/// The client state that dynamic item icons depend on
+#[derive(Debug, Clone, Copy)]
+pub struct IconContext {
+    /// Position of the player's camera
+    pub player_pos: Vector3<f64>,
+    /// Yaw of the player, in degrees
+    pub yaw: f64,
+    /// The position the compass points to, if the server sent it
+    pub compass_target: Option<BlockPos>,
+    /// The current world time
+    pub world_time: WorldTime,
+}
+
+impl IconContext {
+    /// Angle of the compass target relative to where the player is looking, in degrees, clockwise.
+    /// `None` if there is no target or the player is right on top of it.
+    pub fn compass_angle(&self) -> Option<f64> {
+        let target = self.compass_target?;
+        let dx = target.px as f64 + 0.5 - self.player_pos.x;
+        let dz = target.pz as f64 + 0.5 - self.player_pos.z;
+        if dx * dx + dz * dz < 1e-6 {
+            return None;
+        }
+        // Same convention as the player's yaw: 0 is looking towards -z, and the yaw increases when turning left
+        let target_yaw = (-dx).atan2(-dz).to_degrees();
+        Some((self.yaw - target_yaw).rem_euclid(360.0))
+    }
+}
+
+/// Index of the icon to use among `icon_count` icons
+fn get_icon_index(icon: DynamicIcon, icon_count: usize, context: &IconContext) -> usize {
+    match icon {
+        DynamicIcon::Compass => match context.compass_angle() {
+            Some(angle) => {
+                let step = 360.0 / icon_count as f64;
+                (angle / step).round() as usize % icon_count
+            }
+            // Show the needle straight ahead
+            None => 0,
+        },
+        DynamicIcon::Clock => (context.world_time.day_fraction() * icon_count as f64) as usize % icon_count,
+    }
+}
+
+/// Get the id of the mesh to draw for an item in the current context
+pub fn get_item_mesh_id(mesh: &ItemMesh, context: &IconContext) -> u32 {
+    match mesh {
+        ItemMesh::SimpleMesh { mesh_id, .. } => *mesh_id,
+        ItemMesh::DynamicMesh { mesh_ids, icon, .. } => mesh_ids[get_icon_index(*icon, mesh_ids.len(), context)],
+    }
+}
+
+/// Get the texture to draw as the icon of an item in the current context
+pub fn get_item_texture(mesh: &ItemMesh, context: &IconContext) -> TextureRect {
+    match mesh {
+        ItemMesh::SimpleMesh { texture, .. } => *texture,
+        ItemMesh::DynamicMesh { textures, icon, .. } => textures[get_icon_index(*icon, textures.len(), context)],
+    }
+}
@@ -0,0 +1,678 @@
+//! Configurable key bindings.
+//!
+//! Every action can be bound to multiple keys or mouse buttons, optionally chorded with modifiers (for example Ctrl+K).
+//! The bindings are grouped by input context. A key is looked up in the maps of the active context in its
+//! resolution order, and the first map with a matching binding wins. Within a map, the binding that requires
+//! the most modifiers wins, so that Ctrl+K shadows K.
+use history_survival_common::inventory::HOTBAR_SIZE;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use winit::event::{ElementState, ModifiersState, MouseButton};
+
+/// Something that the player can do with the keyboard.
+/// It is written as the name of the variant, followed by the slot for the hotbar slots, for example "HotbarSlot3".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Action {
+    MoveForward,
+    MoveLeft,
+    MoveBackward,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    RotateLeft,
+    RotateRight,
+    UseItem,
+    Sprint,
+    /// Narrow the field of view while the key is held
+    Zoom,
+    /// Switch between the first-person view and the views from behind and in front of the player
+    CycleCamera,
+    /// Switch between the wireframe of the chunks, the borders of the chunks, the hitboxes and the normal view
+    CycleDebugRenderMode,
+    /// Select a slot of the hotbar
+    HotbarSlot(usize),
+    ToggleFlying,
+    ToggleCulling,
+    ToggleMenu,
+    /// Open the chat to type a message
+    OpenChat,
+    /// Open the inventory, or close it if it is open
+    ToggleInventory,
+    /// Switch between the window and the borderless fullscreen
+    ToggleFullscreen,
+}
+
+impl Action {
+    /// The actions without a parameter
+    const SIMPLE_ACTIONS: [Action; 19] = [
+        Action::MoveForward,
+        Action::MoveLeft,
+        Action::MoveBackward,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::UseItem,
+        Action::Sprint,
+        Action::Zoom,
+        Action::CycleCamera,
+        Action::CycleDebugRenderMode,
+        Action::ToggleFlying,
+        Action::ToggleCulling,
+        Action::ToggleMenu,
+        Action::OpenChat,
+        Action::ToggleInventory,
+        Action::ToggleFullscreen,
+    ];
+
+    /// Every action that can be bound, in the order in which the controls list them
+    pub fn all() -> impl Iterator<Item = Action> {
+        Self::SIMPLE_ACTIONS.iter().copied().chain((0..HOTBAR_SIZE).map(Action::HotbarSlot))
+    }
+
+    /// The name of the action shown to the player, like "Move forward" or "Hotbar slot 1"
+    pub fn label(self) -> String {
+        if let Action::HotbarSlot(slot) = self {
+            return format!("Hotbar slot {}", slot + 1);
+        }
+        let mut label = String::new();
+        for (i, c) in format!("{:?}", self).chars().enumerate() {
+            if i > 0 && c.is_uppercase() {
+                label.push(' ');
+                label.extend(c.to_lowercase());
+            } else {
+                label.push(c);
+            }
+        }
+        label
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::HotbarSlot(slot) => write!(f, "HotbarSlot{}", slot),
+            action => write!(f, "{:?}", action),
+        }
+    }
+}
+
+impl TryFrom<String> for Action {
+    type Error = String;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        if let Some(slot) = string.strip_prefix("HotbarSlot") {
+            return slot
+                .parse()
+                .map(Action::HotbarSlot)
+                .map_err(|_| format!("invalid hotbar slot in action {}", string));
+        }
+        Self::SIMPLE_ACTIONS
+            .iter()
+            .find(|action| action.to_string() == string)
+            .cloned()
+            .ok_or_else(|| format!("unknown action {}", string))
+    }
+}
+
+impl From<Action> for String {
+    fn from(action: Action) -> Self {
+        action.to_string()
+    }
+}
+
+/// The situation in which the keys are pressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputContext {
+    /// Bindings that apply in every context, except when typing in the chat
+    Global,
+    /// Playing the game
+    Gameplay,
+    /// Navigating a menu
+    Ui,
+    /// Typing in the chat
+    Chat,
+}
+
+impl InputContext {
+    /// The maps in which the keys are looked up when this context is active, in order
+    pub fn resolution_order(self) -> &'static [InputContext] {
+        match self {
+            InputContext::Global => &[InputContext::Global],
+            InputContext::Gameplay => &[InputContext::Gameplay, InputContext::Global],
+            InputContext::Ui => &[InputContext::Ui, InputContext::Global],
+            InputContext::Chat => &[InputContext::Chat],
+        }
+    }
+
+    /// Whether the bindings of the two contexts can be looked up for the same key press, so that they compete for the keys
+    pub fn overlaps(self, other: InputContext) -> bool {
+        [InputContext::Global, InputContext::Gameplay, InputContext::Ui, InputContext::Chat]
+            .iter()
+            .any(|context| context.resolution_order().contains(&self) && context.resolution_order().contains(&other))
+    }
+}
+
+/// A key of the keyboard or a button of the mouse.
+/// It is written as the scancode of the key, or as the name of the mouse button, for example "MouseMiddle" or "Mouse4".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Scancode(u32),
+    Mouse(MouseButton),
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Scancode(scancode) => write!(f, "{}", scancode),
+            Key::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Key::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Key::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Key::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{}", button),
+        }
+    }
+}
+
+impl std::str::FromStr for Key {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let button = match string.strip_prefix("Mouse") {
+            Some("Left") => MouseButton::Left,
+            Some("Right") => MouseButton::Right,
+            Some("Middle") => MouseButton::Middle,
+            Some(button) => MouseButton::Other(button.parse().map_err(|_| format!("invalid mouse button {}", string))?),
+            None => return string.parse().map(Key::Scancode).map_err(|_| format!("invalid scancode {}", string)),
+        };
+        Ok(Key::Mouse(button))
+    }
+}
+
+/// A key or a mouse button, with the modifiers that must be held at the same time.
+/// It is written as the modifiers followed by the key, for example "Ctrl+Shift+37".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyChord {
+    pub key: Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl KeyChord {
+    /// A key without modifiers
+    pub const fn key(scancode: u32) -> Self {
+        Self {
+            key: Key::Scancode(scancode),
+            ctrl: false,
+            shift: false,
+            alt: false,
+            logo: false,
+        }
+    }
+
+    /// A key with Ctrl
+    pub const fn ctrl(scancode: u32) -> Self {
+        Self {
+            ctrl: true,
+            ..Self::key(scancode)
+        }
+    }
+
+    fn num_modifiers(&self) -> usize {
+        [self.ctrl, self.shift, self.alt, self.logo].iter().filter(|&&held| held).count()
+    }
+
+    /// Whether the chord is the given key, and all its modifiers are held. Extra modifiers are allowed.
+    pub fn matches(&self, key: Key, modifiers: ModifiersState) -> bool {
+        self.key == key
+            && (!self.ctrl || modifiers.ctrl())
+            && (!self.shift || modifiers.shift())
+            && (!self.alt || modifiers.alt())
+            && (!self.logo || modifiers.logo())
+    }
+
+    /// The chord with the names of the keys shown to the player, like "Ctrl+K"
+    pub fn describe(&self) -> String {
+        let modifiers = [(self.ctrl, "Ctrl+"), (self.shift, "Shift+"), (self.alt, "Alt+"), (self.logo, "Logo+")];
+        let mut description = modifiers.iter().filter(|(held, _)| *held).map(|(_, name)| *name).collect::<String>();
+        match self.key {
+            Key::Scancode(scancode) => match scancode_name(scancode) {
+                Some(name) => description.push_str(name),
+                None => description.push_str(&format!("Key {}", scancode)),
+            },
+            Key::Mouse(MouseButton::Left) => description.push_str("Left click"),
+            Key::Mouse(MouseButton::Right) => description.push_str("Right click"),
+            Key::Mouse(MouseButton::Middle) => description.push_str("Middle click"),
+            Key::Mouse(MouseButton::Other(button)) => description.push_str(&format!("Mouse button {}", button)),
+        }
+        description
+    }
+}
+
+/// The names of the keys of a QWERTY keyboard, by scancode
+const SCANCODE_NAMES: [(u32, &str); 80] = [
+    (1, "Escape"),
+    (2, "1"),
+    (3, "2"),
+    (4, "3"),
+    (5, "4"),
+    (6, "5"),
+    (7, "6"),
+    (8, "7"),
+    (9, "8"),
+    (10, "9"),
+    (11, "0"),
+    (12, "-"),
+    (13, "="),
+    (14, "Backspace"),
+    (15, "Tab"),
+    (16, "Q"),
+    (17, "W"),
+    (18, "E"),
+    (19, "R"),
+    (20, "T"),
+    (21, "Y"),
+    (22, "U"),
+    (23, "I"),
+    (24, "O"),
+    (25, "P"),
+    (26, "["),
+    (27, "]"),
+    (28, "Enter"),
+    (29, "Left Ctrl"),
+    (30, "A"),
+    (31, "S"),
+    (32, "D"),
+    (33, "F"),
+    (34, "G"),
+    (35, "H"),
+    (36, "J"),
+    (37, "K"),
+    (38, "L"),
+    (39, ";"),
+    (40, "'"),
+    (41, "`"),
+    (42, "Left Shift"),
+    (43, "\\"),
+    (44, "Z"),
+    (45, "X"),
+    (46, "C"),
+    (47, "V"),
+    (48, "B"),
+    (49, "N"),
+    (50, "M"),
+    (51, ","),
+    (52, "."),
+    (53, "/"),
+    (54, "Right Shift"),
+    (56, "Left Alt"),
+    (57, "Space"),
+    (58, "Caps Lock"),
+    (59, "F1"),
+    (60, "F2"),
+    (61, "F3"),
+    (62, "F4"),
+    (63, "F5"),
+    (64, "F6"),
+    (65, "F7"),
+    (66, "F8"),
+    (67, "F9"),
+    (68, "F10"),
+    (87, "F11"),
+    (88, "F12"),
+    (97, "Right Ctrl"),
+    (100, "Right Alt"),
+    (103, "Up"),
+    (104, "Page Up"),
+    (105, "Left"),
+    (106, "Right"),
+    (108, "Down"),
+    (109, "Page Down"),
+    (111, "Delete"),
+    (125, "Left Logo"),
+    (126, "Right Logo"),
+];
+
+fn scancode_name(scancode: u32) -> Option<&'static str> {
+    SCANCODE_NAMES.iter().find(|(code, _)| *code == scancode).map(|(_, name)| *name)
+}
+
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl"), (self.shift, "Shift"), (self.alt, "Alt"), (self.logo, "Logo")].iter() {
+            if *held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl TryFrom<String> for KeyChord {
+    type Error = String;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        let mut parts = string.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or("");
+        let key = key.parse().map_err(|error| format!("{} in key chord {}", error, string))?;
+        let mut chord = KeyChord { key, ..KeyChord::key(0) };
+        for modifier in parts {
+            match modifier {
+                "Ctrl" => chord.ctrl = true,
+                "Shift" => chord.shift = true,
+                "Alt" => chord.alt = true,
+                "Logo" => chord.logo = true,
+                _ => return Err(format!("unknown modifier {} in key chord {}", modifier, string)),
+            }
+        }
+        Ok(chord)
+    }
+}
+
+impl From<KeyChord> for String {
+    fn from(chord: KeyChord) -> Self {
+        chord.to_string()
+    }
+}
+
+/// The keys of an action in some context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub context: InputContext,
+    pub keys: Vec<KeyChord>,
+    pub action: Action,
+}
+
+/// All the key bindings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KeyBindings {
+    bindings: Vec<Binding>,
+}
+
+impl KeyBindings {
+    /// Find the actions triggered by a key in the given context
+    pub fn resolve(&self, key: Key, modifiers: ModifiersState, context: InputContext) -> Vec<Action> {
+        for &map in context.resolution_order() {
+            let matching = self
+                .bindings
+                .iter()
+                .filter(|binding| binding.context == map)
+                .filter_map(|binding| {
+                    binding
+                        .keys
+                        .iter()
+                        .filter(|chord| chord.matches(key, modifiers))
+                        .map(KeyChord::num_modifiers)
+                        .max()
+                        .map(|num_modifiers| (binding.action, num_modifiers))
+                })
+                .collect::<Vec<_>>();
+            if let Some(most_modifiers) = matching.iter().map(|(_, num_modifiers)| *num_modifiers).max() {
+                return matching
+                    .into_iter()
+                    .filter(|(_, num_modifiers)| *num_modifiers == most_modifiers)
+                    .map(|(action, _)| action)
+                    .collect();
+            }
+        }
+        Vec::new()
+    }
+
+    /// Make sure that a chord is bound to at most one action in every context.
+    /// A chord that is bound to multiple actions is kept for the first one, and removed from the others.
+    /// Return the bindings that were removed.
+    pub fn remove_conflicts(&mut self) -> Vec<(InputContext, KeyChord, Action)> {
+        let mut removed = Vec::new();
+        let mut seen: Vec<(InputContext, KeyChord)> = Vec::new();
+        for binding in self.bindings.iter_mut() {
+            let (context, action) = (binding.context, binding.action);
+            binding.keys.retain(|&chord| {
+                if seen.contains(&(context, chord)) {
+                    removed.push((context, chord, action));
+                    false
+                } else {
+                    seen.push((context, chord));
+                    true
+                }
+            });
+        }
+        removed
+    }
+
+    /// The keys bound to an action, in every context
+    pub fn keys_of(&self, action: Action) -> Vec<KeyChord> {
+        self.bindings.iter().filter(|binding| binding.action == action).flat_map(|binding| binding.keys.iter().copied()).collect()
+    }
+
+    /// The context of the bindings of an action, or of its default bindings if it is not bound
+    fn context_of(&self, action: Action) -> InputContext {
+        let find = |bindings: &[Binding]| bindings.iter().find(|binding| binding.action == action).map(|binding| binding.context);
+        find(&self.bindings).or_else(|| find(&KeyBindings::default().bindings)).unwrap_or(InputContext::Gameplay)
+    }
+
+    /// The other actions that lose a chord if it is bound to an action, because it would trigger them in the same context
+    pub fn conflicting_actions(&self, action: Action, chord: KeyChord) -> Vec<Action> {
+        let context = self.context_of(action);
+        self.bindings
+            .iter()
+            .filter(|binding| binding.action != action && binding.context.overlaps(context) && binding.keys.contains(&chord))
+            .map(|binding| binding.action)
+            .collect()
+    }
+
+    /// Bind a chord to an action instead of its previous keys, removing it from the conflicting actions
+    pub fn rebind(&mut self, action: Action, chord: KeyChord) {
+        let context = self.context_of(action);
+        for binding in self.bindings.iter_mut().filter(|binding| binding.action != action && binding.context.overlaps(context)) {
+            binding.keys.retain(|&key| key != chord);
+        }
+        match self.bindings.iter_mut().find(|binding| binding.action == action) {
+            Some(binding) => binding.keys = vec![chord],
+            None => self.bindings.push(Binding {
+                context,
+                keys: vec![chord],
+                action,
+            }),
+        }
+    }
+}
+
+/// The scancodes of the modifier keys, with the modifier that they hold
+const MODIFIER_SCANCODES: [(u32, Modifier); 8] = [
+    (29, Modifier::Ctrl),
+    (97, Modifier::Ctrl),
+    (42, Modifier::Shift),
+    (54, Modifier::Shift),
+    (56, Modifier::Alt),
+    (100, Modifier::Alt),
+    (125, Modifier::Logo),
+    (126, Modifier::Logo),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Logo,
+}
+
+/// Turns the keys pressed while an action is rebound into a chord.
+/// The modifier keys are chorded with the next key, or bound alone if they are released first.
+#[derive(Debug, Default)]
+pub struct KeyCapture {
+    /// The modifier keys held since the capture started
+    held_modifiers: Vec<(u32, Modifier)>,
+    /// The last modifier key pressed, while no other key was pressed after it
+    lone_modifier: Option<u32>,
+}
+
+impl KeyCapture {
+    /// Record a key press or release, returning the chord once it is complete
+    pub fn key(&mut self, scancode: u32, state: ElementState) -> Option<KeyChord> {
+        let modifier = MODIFIER_SCANCODES.iter().find(|(code, _)| *code == scancode).map(|(_, modifier)| *modifier);
+        match (state, modifier) {
+            (ElementState::Pressed, Some(modifier)) => {
+                self.held_modifiers.push((scancode, modifier));
+                self.lone_modifier = Some(scancode);
+                None
+            }
+            (ElementState::Pressed, None) => Some(self.chord(Key::Scancode(scancode))),
+            (ElementState::Released, Some(_)) => {
+                self.held_modifiers.retain(|(code, _)| *code != scancode);
+                Some(KeyChord::key(scancode)).filter(|_| self.lone_modifier.take() == Some(scancode))
+            }
+            (ElementState::Released, None) => None,
+        }
+    }
+
+    /// Record a mouse button press, returning the chord
+    pub fn mouse_button(&mut self, button: MouseButton) -> KeyChord {
+        self.chord(Key::Mouse(button))
+    }
+
+    /// The key chorded with the modifiers that are held
+    fn chord(&mut self, key: Key) -> KeyChord {
+        self.lone_modifier = None;
+        let held = |modifier| self.held_modifiers.iter().any(|(_, held)| *held == modifier);
+        KeyChord {
+            key,
+            ctrl: held(Modifier::Ctrl),
+            shift: held(Modifier::Shift),
+            alt: held(Modifier::Alt),
+            logo: held(Modifier::Logo),
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use Action::*;
+        let gameplay = |action, keys: &[KeyChord]| Binding {
+            context: InputContext::Gameplay,
+            keys: keys.to_vec(),
+            action,
+        };
+        let mut bindings = vec![
+            gameplay(MoveForward, &[KeyChord::key(17)]),
+            gameplay(MoveLeft, &[KeyChord::key(30)]),
+            gameplay(MoveBackward, &[KeyChord::key(31)]),
+            gameplay(MoveRight, &[KeyChord::key(32)]),
+            gameplay(MoveUp, &[KeyChord::key(57)]),
+            gameplay(MoveDown, &[KeyChord::key(42)]),
+            gameplay(RotateLeft, &[KeyChord::key(105)]),
+            gameplay(RotateRight, &[KeyChord::key(106)]),
+            gameplay(UseItem, &[KeyChord::key(33)]),
+            gameplay(Sprint, &[KeyChord::key(29)]),
+            gameplay(Zoom, &[KeyChord::key(46)]),
+            gameplay(CycleCamera, &[KeyChord::key(63)]),
+            gameplay(CycleDebugRenderMode, &[KeyChord::key(62)]),
+            gameplay(ToggleFlying, &[KeyChord::ctrl(33)]),
+            gameplay(ToggleCulling, &[KeyChord::ctrl(37)]),
+            gameplay(OpenChat, &[KeyChord::key(20)]),
+            Binding {
+                context: InputContext::Global,
+                keys: vec![KeyChord::key(1)],
+                action: ToggleMenu,
+            },
+            // E also closes the inventory, while the menu context is active
+            Binding {
+                context: InputContext::Global,
+                keys: vec![KeyChord::key(18)],
+                action: ToggleInventory,
+            },
+            Binding {
+                context: InputContext::Global,
+                keys: vec![KeyChord::key(87)],
+                action: ToggleFullscreen,
+            },
+        ];
+        // The number keys select the slots of the hotbar
+        bindings.extend((0..HOTBAR_SIZE).map(|slot| gameplay(HotbarSlot(slot), &[KeyChord::key(2 + slot as u32)])));
+        Self { bindings }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chords_shadow_plain_keys() {
+        let bindings = KeyBindings::default();
+        let none = ModifiersState::empty();
+        assert_eq!(bindings.resolve(Key::Scancode(33), none, InputContext::Gameplay), vec![Action::UseItem]);
+        assert_eq!(bindings.resolve(Key::Scancode(33), ModifiersState::CTRL, InputContext::Gameplay), vec![Action::ToggleFlying]);
+        // Extra modifiers don't prevent a binding from matching
+        assert_eq!(bindings.resolve(Key::Scancode(17), ModifiersState::SHIFT, InputContext::Gameplay), vec![Action::MoveForward]);
+        // Gameplay bindings don't apply in menus, but global bindings do
+        assert_eq!(bindings.resolve(Key::Scancode(17), none, InputContext::Ui), vec![]);
+        assert_eq!(bindings.resolve(Key::Scancode(1), none, InputContext::Ui), vec![Action::ToggleMenu]);
+        assert_eq!(bindings.resolve(Key::Scancode(1), none, InputContext::Chat), vec![]);
+        assert_eq!(bindings.resolve(Key::Scancode(87), none, InputContext::Gameplay), vec![Action::ToggleFullscreen]);
+    }
+
+    #[test]
+    fn conflicts_are_removed() {
+        let mut bindings = KeyBindings::default();
+        bindings.bindings.push(Binding {
+            context: InputContext::Gameplay,
+            keys: vec![KeyChord::key(17), KeyChord::key(45)],
+            action: Action::UseItem,
+        });
+        let removed = bindings.remove_conflicts();
+        assert_eq!(removed, vec![(InputContext::Gameplay, KeyChord::key(17), Action::UseItem)]);
+        assert_eq!(bindings.resolve(Key::Scancode(17), ModifiersState::empty(), InputContext::Gameplay), vec![Action::MoveForward]);
+        assert_eq!(bindings.resolve(Key::Scancode(45), ModifiersState::empty(), InputContext::Gameplay), vec![Action::UseItem]);
+    }
+
+    #[test]
+    fn bindings_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            key_bindings: KeyBindings,
+        }
+        let string = toml::ser::to_string(&Wrapper {
+            key_bindings: KeyBindings::default(),
+        })
+        .unwrap();
+        let parsed: Wrapper = toml::de::from_str(&string).unwrap();
+        assert_eq!(parsed.key_bindings.bindings.len(), KeyBindings::default().bindings.len());
+        assert_eq!(KeyChord::try_from("Ctrl+Shift+37".to_owned()).unwrap().to_string(), "Ctrl+Shift+37");
+        assert_eq!(Action::try_from("HotbarSlot3".to_owned()), Ok(Action::HotbarSlot(3)));
+        assert_eq!(KeyChord::try_from("Shift+MouseMiddle".to_owned()).unwrap().to_string(), "Shift+MouseMiddle");
+        assert_eq!(KeyChord::try_from("Mouse4".to_owned()).unwrap().key, Key::Mouse(MouseButton::Other(4)));
+        assert!(KeyChord::try_from("MouseUp".to_owned()).is_err());
+    }
+
+    #[test]
+    fn rebinding_removes_the_conflicting_keys() {
+        let mut bindings = KeyBindings::default();
+        // F is bound to UseItem in the same context
+        assert_eq!(bindings.conflicting_actions(Action::Zoom, KeyChord::key(33)), vec![Action::UseItem]);
+        bindings.rebind(Action::Zoom, KeyChord::key(33));
+        assert_eq!(bindings.keys_of(Action::Zoom), vec![KeyChord::key(33)]);
+        assert!(bindings.keys_of(Action::UseItem).is_empty());
+        // The global bindings compete with the gameplay bindings
+        assert_eq!(bindings.conflicting_actions(Action::MoveUp, KeyChord::key(87)), vec![Action::ToggleFullscreen]);
+        assert_eq!(Action::CycleDebugRenderMode.label(), "Cycle debug render mode");
+        assert_eq!(KeyChord::ctrl(37).describe(), "Ctrl+K");
+    }
+
+    #[test]
+    fn modifiers_are_chorded_or_bound_alone() {
+        let mut capture = KeyCapture::default();
+        assert_eq!(capture.key(29, ElementState::Pressed), None);
+        assert_eq!(capture.key(37, ElementState::Pressed), Some(KeyChord::ctrl(37)));
+        // A modifier released after another key doesn't make a chord
+        assert_eq!(capture.key(29, ElementState::Released), None);
+        assert_eq!(capture.key(42, ElementState::Pressed), None);
+        assert_eq!(capture.key(42, ElementState::Released), Some(KeyChord::key(42)));
+        assert_eq!(capture.mouse_button(MouseButton::Middle).key, Key::Mouse(MouseButton::Middle));
+    }
+}
@@ -0,0 +1,163 @@
+//! The translations of the texts shown to the player, read from the `<code>.ron` files of `data/lang`.
+//! A text is looked up by its key in the current language, then in the default language, and is the key itself
+//! if no language has it.
+use history_survival_common::data::load_files_from_folder;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+/// The folder of the language files
+pub const LANG_FOLDER: &str = "data/lang";
+/// The language of the texts that the other languages don't translate
+pub const DEFAULT_LANGUAGE: &str = "en_us";
+
+lazy_static! {
+    static ref LANGUAGES: RwLock<Languages> = RwLock::new(Languages::default());
+}
+
+/// A language file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Language {
+    /// The name of the language, written in the language itself
+    pub name: String,
+    /// The font of the texts, among the fonts of `assets/fonts/list.toml`,
+    /// for the languages whose characters are missing from the default font
+    #[serde(default)]
+    pub font: Option<String>,
+    /// The texts by their key. `{}` is replaced by the arguments of the text, in order.
+    pub texts: HashMap<String, String>,
+}
+
+/// The loaded languages by their code, and the code of the current language
+#[derive(Debug, Default)]
+struct Languages {
+    languages: BTreeMap<String, Language>,
+    current: String,
+}
+
+impl Languages {
+    fn get(&self, key: &str) -> Option<&str> {
+        [self.current.as_str(), DEFAULT_LANGUAGE]
+            .iter()
+            .filter_map(|code| self.languages.get(*code))
+            .find_map(|language| language.texts.get(key))
+            .map(String::as_str)
+    }
+}
+
+/// Load the languages of `LANG_FOLDER`, and use the language with some code
+pub fn load_languages(code: &str) {
+    let languages = load_files_from_folder(PathBuf::from(LANG_FOLDER)).into_iter().collect();
+    LANGUAGES.write().unwrap().languages = languages;
+    set_language(code);
+}
+
+/// Use the language with some code. The texts of an unknown language are the texts of the default language.
+pub fn set_language(code: &str) {
+    let mut languages = LANGUAGES.write().unwrap();
+    if languages.languages.contains_key(code) {
+        info!("Using the language {}", code);
+    } else {
+        warn!("Unknown language {}, using the texts of {}", code, DEFAULT_LANGUAGE);
+    }
+    languages.current = code.to_owned();
+}
+
+/// The code of the current language
+pub fn current_language() -> String {
+    LANGUAGES.read().unwrap().current.clone()
+}
+
+/// The codes of the languages with their name, sorted by code
+pub fn language_names() -> Vec<(String, String)> {
+    let languages = LANGUAGES.read().unwrap();
+    languages.languages.iter().map(|(code, language)| (code.clone(), language.name.clone())).collect()
+}
+
+/// The language after the language with some code, to cycle through the languages
+pub fn next_language(code: &str) -> String {
+    let codes = language_names().into_iter().map(|(code, _)| code).collect::<Vec<_>>();
+    let index = codes.iter().position(|other| other == code).map(|i| i + 1).unwrap_or(0);
+    codes.get(index % codes.len().max(1)).cloned().unwrap_or_else(|| DEFAULT_LANGUAGE.to_owned())
+}
+
+/// The font of the current language, if it doesn't use the default font
+pub fn font() -> Option<String> {
+    let languages = LANGUAGES.read().unwrap();
+    languages.languages.get(&languages.current).and_then(|language| language.font.clone())
+}
+
+/// The text with some key, if a language has it
+pub fn translate(key: &str) -> Option<String> {
+    LANGUAGES.read().unwrap().get(key).map(str::to_owned)
+}
+
+/// The text with some key, or the key if no language has it
+pub fn tr(key: &str) -> String {
+    translate(key).unwrap_or_else(|| key.to_owned())
+}
+
+/// The text with some key, whose `{}` are replaced by the arguments
+pub fn tr_args(key: &str, args: &[&dyn Display]) -> String {
+    fill(&tr(key), args)
+}
+
+/// Replace the `{}` of a text by the arguments, in order. The `{}` without argument are removed.
+fn fill(text: &str, args: &[&dyn Display]) -> String {
+    let mut parts = text.split("{}");
+    let mut filled = parts.next().unwrap_or("").to_owned();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            filled.push_str(&arg.to_string());
+        }
+        filled.push_str(part);
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_language_files() -> BTreeMap<String, Language> {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/../data/lang");
+        let mut languages = BTreeMap::new();
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            let language: Result<Language, _> = ron::de::from_str(&std::fs::read_to_string(&path).unwrap());
+            let language = language.unwrap_or_else(|e| panic!("{} is invalid: {}", path.display(), e));
+            languages.insert(path.file_stem().unwrap().to_str().unwrap().to_owned(), language);
+        }
+        languages
+    }
+
+    #[test]
+    fn language_files_only_translate_the_default_texts() {
+        let languages = read_language_files();
+        let default = &languages[DEFAULT_LANGUAGE];
+        for (code, language) in languages.iter() {
+            for key in language.texts.keys() {
+                assert!(default.texts.contains_key(key), "{} translates the unknown key {}", code, key);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_texts_fall_back_to_the_default_language() {
+        let languages = Languages {
+            languages: read_language_files(),
+            current: "fr_fr".to_owned(),
+        };
+        assert_eq!(languages.get("pause.title"), Some("JEU EN PAUSE"));
+        let mut unknown = Languages { current: "xx_xx".to_owned(), ..languages };
+        assert_eq!(unknown.get("pause.title"), Some("GAME PAUSED"));
+        unknown.languages.get_mut(DEFAULT_LANGUAGE).unwrap().texts.remove("pause.title");
+        assert_eq!(unknown.get("pause.title"), None);
+        assert_eq!(fill("{} debug info", &[&"RENDER"]), "RENDER debug info");
+        assert_eq!(fill("{}/{} and {}", &[&1, &2]), "1/2 and ");
+    }
+}
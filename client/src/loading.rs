@@ -0,0 +1,68 @@
+//! The loading of the terrain around a player who joins a world. The player waits on a loading screen until
+//! the server generated the spawn area, and until its chunks were received and meshed.
+
+/// The progress of the loading of the spawn area
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpawnAreaLoading {
+    /// How many chunks of the spawn area the server generated, out of how many, as last sent by the server
+    generated: Option<(u32, u32)>,
+    /// How many chunks of the spawn area were received and meshed, out of how many
+    received: u32,
+    meshed: u32,
+    total: u32,
+}
+
+impl SpawnAreaLoading {
+    /// Record the progress of the generation sent by the server
+    pub fn set_generation_progress(&mut self, generated: u32, total: u32) {
+        self.generated = Some((generated, total));
+    }
+
+    /// Record how many chunks of the spawn area were received and meshed, out of how many
+    pub fn set_local_progress(&mut self, received: u32, meshed: u32, total: u32) {
+        self.received = received;
+        self.meshed = meshed;
+        self.total = total;
+    }
+
+    /// Whether the server generated the whole spawn area, and every chunk of it is meshed
+    pub fn is_done(&self) -> bool {
+        let is_generated = matches!(self.generated, Some((generated, total)) if generated >= total);
+        is_generated && self.total > 0 && self.meshed >= self.total
+    }
+
+    /// The steps of the loading, with the key of their name and how many chunks they finished, out of how many.
+    /// A step whose progress isn't known yet is empty.
+    pub fn steps(&self) -> [(&'static str, u32, u32); 3] {
+        let (generated, generation_total) = self.generated.unwrap_or((0, 0));
+        [
+            ("loading.generating", generated, generation_total),
+            ("loading.receiving", self.received, self.total),
+            ("loading.building", self.meshed, self.total),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading_waits_for_the_server_and_the_meshes() {
+        let mut loading = SpawnAreaLoading::default();
+        // Nothing is known before the first chunks arrive
+        assert!(!loading.is_done());
+        assert_eq!(loading.steps()[0], ("loading.generating", 0, 0));
+        // The local progress alone doesn't finish the loading, because the player may not be placed yet
+        loading.set_local_progress(27, 27, 27);
+        assert!(!loading.is_done());
+        loading.set_generation_progress(20, 27);
+        assert!(!loading.is_done());
+        loading.set_generation_progress(27, 27);
+        loading.set_local_progress(27, 20, 27);
+        assert!(!loading.is_done());
+        assert_eq!(loading.steps()[2], ("loading.building", 20, 27));
+        loading.set_local_progress(27, 27, 27);
+        assert!(loading.is_done());
+    }
+}
@@ -1,18 +1,32 @@
 use anyhow::Result;
-use log::{error, info};
+use log::info;
 use std::path::Path;
-use history_survival_common::network::dummy;
-use history_survival_server::launch_server;
 
+mod biome_colors;
+mod block_entity_textures;
+mod box_models;
+mod camera;
+mod chat;
+mod consistency;
+mod entities;
+mod fov;
 mod fps;
 mod gui;
+mod hand;
 mod input;
-//mod mainmenu; TODO: fix this
+mod item_icons;
+mod keybindings;
+mod lang;
+mod loading;
+mod mainmenu;
 mod render;
+mod render_distance;
+mod servers;
 mod settings;
 mod singleplayer;
 mod texture;
 mod ui;
+mod weather;
 mod window;
 mod world;
 
@@ -21,29 +35,10 @@ fn main() -> Result<()> {
 
     info!("Starting up...");
     let config_folder = Path::new("config");
-    let config_file = Path::new("config/settings.toml");
+    let config_file = Path::new(settings::SETTINGS_FILE);
     let settings = settings::load_settings(&config_folder, &config_file)?;
     info!("Current settings: {:?}", settings);
+    lang::load_languages(&settings.language);
 
-    let (client, server) = dummy::new();
-
-    std::thread::spawn(move || {
-        if let Err(e) = launch_server(Box::new(server)) {
-            // TODO: rewrite this error reporting
-            error!(
-                "Error happened in the server code: {}\nPrinting chain:\n{}",
-                e,
-                e.chain()
-                    .enumerate()
-                    .map(|(i, e)| format!("{}: {}", i, e))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            );
-        }
-    });
-
-    window::open_window(
-        settings,
-        Box::new(singleplayer::SinglePlayer::new_factory(Box::new(client))),
-    )
+    window::open_window(settings, mainmenu::MainMenu::new_factory())
 }
@@ -1,182 +1,249 @@
 use anyhow::Result;
-use log::info;
+use log::{error, info};
+use std::path::Path;
+use std::thread::JoinHandle;
 
 use crate::{
-    fps::FpsCounter,
+    gui::{
+        main_menu::{render_back_button, render_background, render_main_menu, render_multiplayer, MainMenuButton, MultiplayerAction},
+        options::{render_options, OptionsMenu, SettingsChange},
+        Gui,
+    },
     input::InputState,
-    settings::Settings,
+    keybindings::{Action, InputContext},
+    render::UiRenderer,
+    servers::ServerList,
+    settings::{save_settings, Settings},
     singleplayer::SinglePlayer,
-    ui::{
-        renderer::{self, UiRenderer},
-        widgets,
-    },
-    window::{Gfx, State, StateTransition, WindowData, WindowFlags},
+    ui::PrimitiveBuffer,
+    window::{State, StateTransition, WindowBuffers, WindowData, WindowFlags},
 };
+use history_survival_common::network::dummy;
+use history_survival_server::launch_server;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta};
+
+/// The file where the servers of the multiplayer screen are saved
+const SERVER_LIST_FILE: &str = "config/servers.toml";
+
+/// The screen of the main menu that is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuScreen {
+    Main,
+    Settings,
+    Multiplayer,
+}
 
 /// State of the main menu
 pub struct MainMenu {
-    fps_counter: FpsCounter,
-    ui: self::Ui,
     ui_renderer: UiRenderer,
+    gui: Gui,
+    /// The retained ui that the renderer expects. The main menu is drawn with the immediate-mode gui only.
+    ui: quint::Ui<PrimitiveBuffer, ()>,
+    screen: MenuScreen,
+    /// The button clicked in the last frame, handled during the next update
+    clicked: Option<MainMenuButton>,
+    /// The change of the settings made in the last frame, applied to the settings during the next update
+    settings_change: Option<SettingsChange>,
+    options_menu: OptionsMenu,
+    /// What the player did in the multiplayer screen in the last frame, handled during the next update
+    multiplayer_action: Option<MultiplayerAction>,
+    /// The address typed in the multiplayer screen
+    address: String,
+    /// How far the saved servers are scrolled, in pixels
+    server_list_scroll: i32,
+    server_list: ServerList,
 }
 
 impl MainMenu {
-    pub fn new(_settings: &mut Settings, gfx: &mut Gfx) -> Result<Box<dyn State>> {
-        info!("Creating main menu");
-
-        Ok(Box::new(Self {
-            fps_counter: FpsCounter::new(),
-            ui: self::Ui::new(),
-            ui_renderer: UiRenderer::new(gfx)?,
-        }))
+    pub fn new_factory() -> crate::window::StateFactory {
+        Box::new(|settings, device| {
+            info!("Creating main menu");
+            let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            let main_menu = Self {
+                ui_renderer: UiRenderer::new(device, settings.antialiasing.sample_count()),
+                gui: Gui::new(),
+                ui: quint::Ui::new(),
+                screen: MenuScreen::Main,
+                clicked: None,
+                settings_change: None,
+                options_menu: OptionsMenu::default(),
+                multiplayer_action: None,
+                address: String::new(),
+                server_list_scroll: 0,
+                server_list: ServerList::load(Path::new(SERVER_LIST_FILE)),
+            };
+            Ok((Box::new(main_menu) as Box<dyn State>, encoder.finish()))
+        })
     }
-}
-
-impl State for MainMenu {
-    fn update(&mut self, _: &mut Settings, _: &InputState, _: &WindowData, flags: &mut WindowFlags, _: f64, _: &mut Gfx) -> Result<StateTransition> {
-        flags.hide_and_center_cursor = false;
 
-        if self.ui.should_exit {
-            Ok(StateTransition::CloseWindow)
-        } else if self.ui.should_start_single_player {
-            Ok(StateTransition::ReplaceCurrent(Box::new(SinglePlayer::new)))
-        } else {
-            Ok(StateTransition::KeepCurrent)
+    fn save_server_list(&self) {
+        if let Err(error) = self.server_list.save(Path::new(SERVER_LIST_FILE)) {
+            error!("{:?}", error);
         }
     }
 
-    fn render(&mut self, _: &Settings, gfx: &mut Gfx, data: &WindowData) -> Result<StateTransition> {
-        use gfx::traits::Device;
-
-        self.fps_counter.add_frame();
+    fn handle_multiplayer_action(&mut self, action: MultiplayerAction) {
+        match action {
+            MultiplayerAction::Save => {
+                if self.server_list.add(&self.address) {
+                    self.address.clear();
+                    self.save_server_list();
+                }
+            }
+            MultiplayerAction::Remove(index) => {
+                if index < self.server_list.servers.len() {
+                    self.server_list.servers.remove(index);
+                    self.save_server_list();
+                }
+            }
+        }
+    }
+}
 
-        // Clear buffers
-        gfx.encoder
-            .clear(&gfx.color_buffer, crate::window::CLEAR_COLOR);
-        gfx.encoder
-            .clear_depth(&gfx.depth_buffer, crate::window::CLEAR_DEPTH);
-        // Rebuild ui
-        self.ui.rebuild(self.fps_counter.fps(), data);
-        self.ui_renderer.render(gfx, data, &self.ui.ui)?;
-        // Flush and swap buffers
-        gfx.encoder.flush(&mut gfx.device);
-        gfx.context.swap_buffers()?;
-        gfx.device.cleanup();
+/// Start the server of a local world in another thread, and return the client connected to it with the thread
+fn launch_local_server() -> (dummy::DummyClient, JoinHandle<()>) {
+    let (client, server) = dummy::new();
+    let server_thread = std::thread::spawn(move || {
+        if let Err(e) = launch_server(Box::new(server)) {
+            // TODO: rewrite this error reporting
+            error!(
+                "Error happened in the server code: {}\nPrinting chain:\n{}",
+                e,
+                e.chain()
+                    .enumerate()
+                    .map(|(i, e)| format!("{}: {}", i, e))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+    });
+    (client, server_thread)
+}
 
+impl State for MainMenu {
+    fn update(
+        &mut self,
+        settings: &mut Settings,
+        _input_state: &InputState,
+        _data: &WindowData,
+        flags: &mut WindowFlags,
+        _seconds_delta: f64,
+        _device: &mut wgpu::Device,
+    ) -> Result<StateTransition> {
+        flags.grab_cursor = false;
+        if let Some(change) = self.settings_change.take() {
+            change.apply(settings);
+            save_settings(settings);
+        }
+        match self.clicked.take() {
+            Some(MainMenuButton::Singleplayer) => {
+                info!("Starting a local world");
+                let (client, server_thread) = launch_local_server();
+                let factory = SinglePlayer::new_factory(Box::new(client), Some(server_thread));
+                return Ok(StateTransition::ReplaceCurrent(factory));
+            }
+            Some(MainMenuButton::Multiplayer) => self.screen = MenuScreen::Multiplayer,
+            Some(MainMenuButton::Settings) => self.screen = MenuScreen::Settings,
+            Some(MainMenuButton::Back) => self.screen = MenuScreen::Main,
+            Some(MainMenuButton::Quit) => return Ok(StateTransition::CloseWindow),
+            None => {}
+        }
+        if let Some(action) = self.multiplayer_action.take() {
+            self.handle_multiplayer_action(action);
+        }
         Ok(StateTransition::KeepCurrent)
     }
 
-    fn handle_mouse_motion(&mut self, _: &Settings, _: (f64, f64)) {}
-
-    fn handle_cursor_movement(&mut self, logical_position: glutin::dpi::LogicalPosition) {
-        self.ui.cursor_moved(logical_position);
-    }
+    fn render<'a>(
+        &mut self,
+        settings: &Settings,
+        buffers: WindowBuffers<'a>,
+        device: &mut wgpu::Device,
+        data: &WindowData,
+        _input_state: &InputState,
+    ) -> Result<(StateTransition, wgpu::CommandBuffer)> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        crate::render::clear_frame(&mut encoder, buffers);
+
+        let (window_width, window_height) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+        self.gui.prepare();
+        render_background(&mut self.gui, window_width, window_height);
+        match self.screen {
+            MenuScreen::Main => {
+                if let Some(button) = render_main_menu(&mut self.gui, window_width, window_height) {
+                    self.clicked = Some(button);
+                }
+            }
+            MenuScreen::Settings => {
+                if let Some(change) = render_options(&mut self.gui, (window_width, window_height), settings, &mut self.options_menu, 0) {
+                    self.settings_change = Some(change);
+                }
+                if render_back_button(&mut self.gui, window_width, window_height) {
+                    self.clicked = Some(MainMenuButton::Back);
+                }
+            }
+            MenuScreen::Multiplayer => {
+                let servers = self.server_list.servers.iter().map(|server| server.address.clone()).collect::<Vec<_>>();
+                let scroll = &mut self.server_list_scroll;
+                let action = render_multiplayer(&mut self.gui, window_width, window_height, &mut self.address, &servers, scroll);
+                if let Some(action) = action {
+                    self.multiplayer_action = Some(action);
+                }
+                if render_back_button(&mut self.gui, window_width, window_height) {
+                    self.clicked = Some(MainMenuButton::Back);
+                }
+            }
+        }
+        self.gui.finish();
+        self.ui_renderer.render(buffers, device, &mut encoder, data, &self.ui, &mut self.gui, false);
 
-    fn handle_mouse_state_changes(&mut self, changes: Vec<(glutin::MouseButton, glutin::ElementState)>) {
-        self.ui.handle_mouse_state_changes(changes);
+        Ok((StateTransition::KeepCurrent, encoder.finish()))
     }
 
-    fn handle_key_state_changes(&mut self, _: Vec<(u32, glutin::ElementState)>) {}
-}
+    fn handle_mouse_motion(&mut self, _settings: &Settings, _delta: (f64, f64)) {}
 
-#[derive(Debug, Clone, Copy)]
-enum UiMessage {
-    StartSinglePlayer,
-    ExitGame,
-}
+    fn handle_cursor_movement(&mut self, logical_position: winit::dpi::LogicalPosition<f64>) {
+        let (x, y) = logical_position.into();
+        self.gui.update_mouse_position(x, y);
+    }
 
-struct Ui {
-    pub(self) ui: quint::Ui<renderer::PrimitiveBuffer, UiMessage>,
-    messages: Vec<UiMessage>,
-    pub(self) should_exit: bool,
-    pub(self) should_start_single_player: bool,
-}
+    fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        self.gui.update_mouse_wheel(delta);
+    }
 
-impl Ui {
-    pub fn new() -> Self {
-        Self {
-            ui: quint::Ui::new(),
-            messages: Vec::new(),
-            should_exit: false,
-            should_start_single_player: false,
+    fn handle_mouse_state_changes(&mut self, changes: Vec<(MouseButton, ElementState)>) {
+        for (button, state) in changes {
+            if self.options_menu.is_rebinding() {
+                self.options_menu.handle_mouse_button(button, state);
+            } else if button == MouseButton::Left {
+                self.gui.update_mouse_button(state == ElementState::Pressed);
+            }
         }
     }
 
-    pub fn cursor_moved(&mut self, p: glutin::dpi::LogicalPosition) {
-        self.ui.set_cursor_position(quint::Position {
-            x: p.x as f32,
-            y: p.y as f32,
-        });
+    fn handle_key_state_changes(&mut self, changes: Vec<(u32, ElementState)>) {
+        for (scancode, state) in changes {
+            self.options_menu.handle_key(scancode, state);
+        }
     }
 
-    pub fn handle_mouse_state_changes(
-        &mut self,
-        changes: Vec<(glutin::MouseButton, glutin::ElementState)>,
-    ) {
-        let changes = changes
-            .into_iter()
-            .map(|(button, state)| quint::Event::MouseInput {
-                button: crate::ui::quint_mouse_button(button),
-                state: crate::ui::quint_element_state(state),
-            })
-            .collect();
-        self.messages.extend(self.ui.update(changes));
+    fn handle_received_characters(&mut self, characters: Vec<char>) {
+        self.gui.update_typed_characters(characters);
     }
 
-    pub fn rebuild(&mut self, _fps: usize, data: &WindowData) {
-        use quint::WidgetTree;
-
-        self.update();
-
-        let mut menu_button_count = 0;
-        let mut menu_button = |text: &'static str, message| {
-
-            menu_button_count += 1;
-            quint::wt! {
-                widgets::Button {
-                    text: text.to_owned(),
-                    font_size: gfx_glyph::Scale::uniform(40.0),
-                    message,
-                    style: quint::Style::default().absolute_size(400.0, 100.0),
-                },
-            }
-        };
-
-        let buttons = vec![
-            menu_button("Start Singleplayer Game", UiMessage::StartSinglePlayer),
-            menu_button("Exit Game", UiMessage::ExitGame),
-        ];
-
-        let menu_layer = WidgetTree::new(
-            Box::new(widgets::WithStyle {
-                style: quint::Style::default()
-                    .percent_size(1.0, 1.0)
-                    .center_cross()
-                    .center_main()
-                    .vertical(),
-            }),
-            buttons,
-        );
-
-        let (win_w, win_h) = (
-            data.logical_window_size.width,
-            data.logical_window_size.height,
-        );
-        self.ui.rebuild(
-            vec![menu_layer],
-            quint::Size {
-                width: win_w as f32,
-                height: win_h as f32,
-            },
-        );
+    fn handle_actions(&mut self, actions: Vec<Action>) {
+        // Escape goes back from the settings and the multiplayer screen
+        if actions.contains(&Action::ToggleMenu) && self.screen != MenuScreen::Main {
+            self.clicked = Some(MainMenuButton::Back);
+        }
     }
 
-    pub fn update(&mut self) {
-        for message in self.messages.drain(..) {
-            match message {
-                UiMessage::StartSinglePlayer => self.should_start_single_player = true,
-                UiMessage::ExitGame => self.should_exit = true,
-            }
+    fn input_context(&self) -> InputContext {
+        // The keys only type in the text field while it is focused, and only rebind an action while it is rebound
+        if self.gui.has_focus() || self.options_menu.is_rebinding() {
+            InputContext::Chat
+        } else {
+            InputContext::Ui
         }
     }
-}
\ No newline at end of file
+}
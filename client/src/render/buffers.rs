@@ -85,6 +85,10 @@ impl<T: Copy + 'static> DynamicBuffer<T> {
     }
 }
 
+/// An object didn't fit in a `MultiBuffer`, because the buffer would have grown beyond its maximum length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferFull;
+
 /// A buffer that can contain multiple objects. Every object is of type `T` and can be accessed by a key of type `K`.
 pub struct MultiBuffer<K: Hash + Eq + Clone, T: Copy + 'static> {
     buffer: wgpu::Buffer,
@@ -92,6 +96,8 @@ pub struct MultiBuffer<K: Hash + Eq + Clone, T: Copy + 'static> {
     objects: HashMap<K, usize>,
     segments: Vec<MultiBufferSegment>,
     len: usize,
+    /// The number of elements above which the buffer doesn't grow
+    max_len: usize,
     phantom: std::marker::PhantomData<T>,
 }
 
@@ -126,10 +132,16 @@ impl<K: Hash + Eq + Clone + std::fmt::Debug, T: Copy + std::fmt::Debug + 'static
             objects: HashMap::new(),
             segments,
             len: initial_capacity,
+            max_len: usize::MAX,
             phantom: std::marker::PhantomData,
         }
     }
 
+    /// Limit the number of elements of the buffer, so that it doesn't take too much GPU memory
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+
     /// Remove object `object` from the buffer
     pub fn remove(&mut self, object: &K) {
         if let Some(start_position) = self.objects.remove(object) {
@@ -158,18 +170,18 @@ impl<K: Hash + Eq + Clone + std::fmt::Debug, T: Copy + std::fmt::Debug + 'static
         }
     }
 
-    /// Update the data for object `object` in the buffer
+    /// Update the data for object `object` in the buffer.
+    /// If the buffer would have to grow beyond its maximum length, the object is removed from the buffer instead.
     ///
     /// # Panics
     /// Will panic if `data` is empty.
-    // TODO: handle memory fragmentation
     pub fn update(
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
         object: K,
         data: &[T],
-    ) {
+    ) -> Result<(), BufferFull> {
         assert!(data.len() > 0, "cannot add an empty slice to a MultiBuffer");
         // Remove the object if it's already in the buffer
         self.remove(&object);
@@ -178,11 +190,18 @@ impl<K: Hash + Eq + Clone + std::fmt::Debug, T: Copy + std::fmt::Debug + 'static
             .segments
             .iter_mut()
             .position(|seg| seg.len >= data.len() && seg.free);
-        let insert_position = insert_position.unwrap_or_else(|| {
-            // Reallocate at least twice the size
-            self.reallocate(device, encoder, (self.len + data.len()).max(2 * self.len));
-            self.segments.len() - 1
-        });
+        let insert_position = match insert_position {
+            Some(insert_position) => insert_position,
+            None => {
+                // Reallocate at least twice the size, without going over the maximum length
+                let new_len = (self.len + data.len()).max(2 * self.len).min(self.max_len);
+                if new_len < self.len + data.len() {
+                    return Err(BufferFull);
+                }
+                self.reallocate(device, encoder, new_len);
+                self.segments.len() - 1
+            }
+        };
         // Copy data into the buffer
         let src_buffer = buffer_from_slice(
             device,
@@ -220,6 +239,54 @@ impl<K: Hash + Eq + Clone + std::fmt::Debug, T: Copy + std::fmt::Debug + 'static
         // Update the map
         self.objects
             .insert(object.clone(), self.segments[insert_position].pos);
+        Ok(())
+    }
+
+    /// Move the objects that come after holes down into them, so that the free space ends up at the end of the buffer.
+    /// Objects are moved until `max_len` elements were copied, so that the compaction can be spread over several frames,
+    /// but at least one object is moved. Returns the number of elements that were copied.
+    pub fn compact(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, max_len: usize) -> usize {
+        let mut moved = 0;
+        // The last segment is the only free segment that is not followed by an object
+        while let Some(hole) = self.segments[..self.segments.len() - 1].iter().position(|seg| seg.free) {
+            let object = self.segments[hole + 1];
+            if moved > 0 && moved + object.len > max_len {
+                break;
+            }
+            let new_pos = self.segments[hole].pos;
+            // The old and the new range of the object may overlap, so it goes through a temporary buffer
+            let size = (object.len * std::mem::size_of::<T>()) as u64;
+            let temporary_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                mapped_at_creation: false,
+                size,
+                usage: wgpu::BufferUsage::COPY_SRC | wgpu::BufferUsage::COPY_DST,
+            });
+            encoder.copy_buffer_to_buffer(&self.buffer, (object.pos * std::mem::size_of::<T>()) as u64, &temporary_buffer, 0, size);
+            encoder.copy_buffer_to_buffer(&temporary_buffer, 0, &self.buffer, (new_pos * std::mem::size_of::<T>()) as u64, size);
+            *self.objects.values_mut().find(|pos| **pos == object.pos).expect("logic error!") = new_pos;
+            // Swap the object and the hole, then merge the hole with the free segment after it
+            let hole_len = self.segments[hole].len;
+            self.segments[hole] = MultiBufferSegment { free: false, pos: new_pos, len: object.len };
+            self.segments[hole + 1] = MultiBufferSegment { free: true, pos: new_pos + object.len, len: hole_len };
+            if hole + 2 < self.segments.len() && self.segments[hole + 2].free {
+                self.segments[hole + 1].len += self.segments[hole + 2].len;
+                self.segments.remove(hole + 2);
+            }
+            moved += object.len;
+        }
+        moved
+    }
+
+    /// How much of the buffer is used by the objects, and how much of it is lost in holes between them
+    pub fn usage(&self) -> MultiBufferUsage {
+        let holes = self.segments[..self.segments.len() - 1].iter().filter(|seg| seg.free);
+        MultiBufferUsage {
+            capacity: self.len,
+            used: self.segments.iter().filter(|seg| !seg.free).map(|seg| seg.len).sum(),
+            holes: holes.clone().map(|seg| seg.len).sum(),
+            hole_count: holes.count(),
+        }
     }
 
     fn reallocate(
@@ -324,6 +391,35 @@ impl<K: Hash + Eq + Clone + std::fmt::Debug, T: Copy + std::fmt::Debug + 'static
     }
 }
 
+/// How the elements of a `MultiBuffer` are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiBufferUsage {
+    /// The number of elements that fit in the buffer
+    pub capacity: usize,
+    /// The number of elements of the objects
+    pub used: usize,
+    /// The number of free elements between the objects. Only the objects that fit in a hole can use it.
+    pub holes: usize,
+    pub hole_count: usize,
+}
+
+impl MultiBufferUsage {
+    /// The fraction of the buffer that is used by the objects
+    pub fn occupancy(&self) -> f64 {
+        self.used as f64 / self.capacity as f64
+    }
+
+    /// The fraction of the free space that is in holes between the objects instead of at the end of the buffer
+    pub fn fragmentation(&self) -> f64 {
+        let free = self.capacity - self.used;
+        if free == 0 {
+            0.0
+        } else {
+            self.holes as f64 / free as f64
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct MultiBufferSegment {
     pub free: bool,
@@ -362,28 +458,47 @@ mod tests {
         let seg3 = [9u16];
 
         // Single insert
-        multi_buffer.update(&device, &mut encoder, 0u16, &seg1);
+        multi_buffer.update(&device, &mut encoder, 0u16, &seg1).unwrap();
         multi_buffer.remove(&0u16);
         assert_eq!(multi_buffer.get_pos_len(&0), None);
 
         // Double insert
-        multi_buffer.update(&device, &mut encoder, 1u16, &seg2);
+        multi_buffer.update(&device, &mut encoder, 1u16, &seg2).unwrap();
         assert_eq!(multi_buffer.get_pos_len(&1), Some((0, 4)));
-        multi_buffer.update(&device, &mut encoder, 2u16, &seg2);
+        multi_buffer.update(&device, &mut encoder, 2u16, &seg2).unwrap();
         assert_eq!(multi_buffer.get_pos_len(&2), Some((4, 4)));
         multi_buffer.remove(&1u16);
         assert_eq!(multi_buffer.get_pos_len(&1), None);
         assert_eq!(multi_buffer.get_pos_len(&2), Some((4, 4)));
 
         // Triple insert
-        multi_buffer.update(&device, &mut encoder, 0u16, &seg1);
+        multi_buffer.update(&device, &mut encoder, 0u16, &seg1).unwrap();
         assert_eq!(multi_buffer.get_pos_len(&0), Some((0, 3)));
-        multi_buffer.update(&device, &mut encoder, 1u16, &seg3);
+        multi_buffer.update(&device, &mut encoder, 1u16, &seg3).unwrap();
         assert_eq!(multi_buffer.get_pos_len(&1), Some((3, 1)));
         // Now we have 8 items
 
         // Reallocate
-        multi_buffer.update(&device, &mut encoder, 3u16, &seg2);
+        multi_buffer.update(&device, &mut encoder, 3u16, &seg2).unwrap();
         assert_eq!(multi_buffer.get_pos_len(&3), Some((8, 4)));
+
+        // The buffer doesn't grow beyond its maximum length
+        multi_buffer.set_max_len(20);
+        assert_eq!(multi_buffer.update(&device, &mut encoder, 4u16, &[0u16; 10]), Err(BufferFull));
+        assert_eq!(multi_buffer.get_pos_len(&4), None);
+
+        // Compact the holes left by removed objects
+        multi_buffer.remove(&0u16);
+        multi_buffer.remove(&2u16);
+        assert_eq!(
+            multi_buffer.usage(),
+            MultiBufferUsage { capacity: 20, used: 5, holes: 7, hole_count: 2 }
+        );
+        assert_eq!(multi_buffer.compact(&device, &mut encoder, 1), 1);
+        assert_eq!(multi_buffer.get_pos_len(&1), Some((0, 1)));
+        assert_eq!(multi_buffer.compact(&device, &mut encoder, 100), 4);
+        assert_eq!(multi_buffer.get_pos_len(&3), Some((1, 4)));
+        assert_eq!(multi_buffer.usage().holes, 0);
+        multi_buffer._assert_invariants();
     }
 }
@@ -17,8 +17,6 @@ impl Plane {
     }
 }
 
-const FOV: f64 = 90.0f64 * 2.0 * std::f64::consts::PI / 360.0;
-
 /// The player's frustum
 #[derive(Debug, Clone, Copy)]
 pub struct Frustum {
@@ -28,33 +26,57 @@ pub struct Frustum {
     pub yaw: f64,
     /// Yaw in degrees
     pub pitch: f64,
+    /// Vertical field of view in degrees
+    pub fov: f64,
+    /// Offset of the view in the space of the camera, for the bobbing of the view
+    pub view_offset: Vector3<f64>,
+    /// Roll of the view in degrees
+    pub roll: f64,
 }
 
 impl Frustum {
     /// Create a new frustum. This function should be called each frame.
-    pub fn new(position: Vector3<f64>, yaw_pitch: YawPitch) -> Frustum {
+    pub fn new(position: Vector3<f64>, yaw_pitch: YawPitch, fov: f64) -> Frustum {
         Self {
             position,
             yaw: yaw_pitch.yaw,
             pitch: yaw_pitch.pitch,
+            fov,
+            view_offset: Vector3::zeros(),
+            roll: 0.0,
         }
     }
 
+    /// Move the view by an offset in the space of the camera and roll it, without moving the camera itself
+    pub fn with_view_offset(self, view_offset: Vector3<f64>, roll: f64) -> Frustum {
+        Self { view_offset, roll, ..self }
+    }
+
     /// Get the view/projection matrix associated with this frustum
     pub fn get_view_projection(&self, aspect_ratio: f64) -> Matrix4<f64> {
-        let proj = Perspective3::new(aspect_ratio, FOV, 0.1, 3000.0);
-        proj.as_matrix() * self.get_view_matrix()
+        self.get_projection(aspect_ratio) * self.get_view_matrix()
+    }
+
+    /// Get the projection matrix alone
+    pub fn get_projection(&self, aspect_ratio: f64) -> Matrix4<f64> {
+        Self::get_projection_with_fov(aspect_ratio, self.fov)
+    }
+
+    /// Get a projection matrix with another vertical field of view in degrees, for what is drawn relative to the camera
+    pub fn get_projection_with_fov(aspect_ratio: f64, fov: f64) -> Matrix4<f64> {
+        *Perspective3::new(aspect_ratio, fov.to_radians(), 0.1, 3000.0).as_matrix()
     }
 
     pub fn get_view_matrix(&self) -> Matrix4<f64> {
-        let rotation = Matrix4::from_euler_angles(-self.pitch.to_radians(), 0.0, 0.0)
+        let rotation = Matrix4::from_euler_angles(0.0, 0.0, -self.roll.to_radians())
+            * Matrix4::from_euler_angles(-self.pitch.to_radians(), 0.0, 0.0)
             * Matrix4::from_euler_angles(0.0, -self.yaw.to_radians(), 0.0);
         let translation = Matrix4::new_translation(&-self.position);
-        rotation * translation
+        Matrix4::new_translation(&-self.view_offset) * rotation * translation
     }
 
     pub fn get_planes(&self, aspect_ratio: f64) -> [[Plane; 2]; 3] {
-        let (fovy, znear, zfar) = (FOV, 0.1, 3000.0);
+        let (fovy, znear, zfar) = (self.fov.to_radians(), 0.1, 3000.0);
         let t = (fovy / 2.0).tan();
         let h_near = t * 2.0 * znear;
         let w_near = h_near * aspect_ratio;
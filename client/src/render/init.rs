@@ -1,27 +1,82 @@
 //! Helpers for pipeline creation and initialization
+use std::hash::Hasher;
 use std::path::Path;
+use twox_hash::XxHash64;
+
+/// The compiled shaders are cached in this folder, named after the hash of their source
+const SHADER_CACHE_FOLDER: &str = "cache/shaders";
 
 /// Shader stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShaderStage {
     Vertex,
     Fragment,
 }
 
+impl ShaderStage {
+    /// The stage of a shader file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "vert" => Some(ShaderStage::Vertex),
+            "frag" => Some(ShaderStage::Fragment),
+            _ => None,
+        }
+    }
+}
+
 /// Load a GLSL shader from a file and compile it to SPIR-V
 pub fn load_glsl_shader<'a, P: AsRef<Path>>(stage: ShaderStage, path: P) -> Vec<u8> {
+    load_glsl_shader_with_macros(stage, path, &[])
+}
+
+/// Load a GLSL shader from a file and compile it to SPIR-V, with some macros defined
+pub fn load_glsl_shader_with_macros<P: AsRef<Path>>(stage: ShaderStage, path: P, macros: &[&str]) -> Vec<u8> {
+    try_load_glsl_shader(stage, path, macros).unwrap_or_else(|error| panic!("Couldn't compile shader: {}", error))
+}
+
+/// Load a GLSL shader from a file and compile it to SPIR-V, with some macros defined, unless it doesn't compile.
+/// The compiled shaders are cached on the disk, so that they are only compiled again when their source changes.
+pub fn try_load_glsl_shader<P: AsRef<Path>>(stage: ShaderStage, path: P, macros: &[&str]) -> Result<Vec<u8>, String> {
     let ty = match stage {
         ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
         ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
     };
     let path_display = path.as_ref().display().to_string();
     log::info!("Loading GLSL shader from {}", path_display);
-    let glsl_source = std::fs::read_to_string(path).expect("Couldn't read shader from file");
+    let glsl_source = std::fs::read_to_string(path).map_err(|error| format!("Couldn't read {}: {}", path_display, error))?;
+
+    let cache_path = Path::new(SHADER_CACHE_FOLDER).join(format!("{:016x}.spv", shader_hash(stage, &glsl_source, macros)));
+    if let Ok(spirv) = std::fs::read(&cache_path) {
+        return Ok(spirv);
+    }
 
     let mut compiler = shaderc::Compiler::new().unwrap();
-    compiler.compile_into_spirv(&glsl_source, ty, &path_display, "main", None)
-        .expect("Couldn't compile shader.")
+    let mut options = shaderc::CompileOptions::new().unwrap();
+    for name in macros {
+        options.add_macro_definition(name, None);
+    }
+    let spirv = compiler
+        .compile_into_spirv(&glsl_source, ty, &path_display, "main", Some(&options))
+        .map_err(|error| error.to_string())?
         .as_binary_u8()
-        .to_vec()
+        .to_vec();
+    // The cache only saves time, so failing to write it is not an error
+    if let Err(error) = std::fs::create_dir_all(SHADER_CACHE_FOLDER).and_then(|()| std::fs::write(&cache_path, &spirv)) {
+        log::warn!("Couldn't cache the compiled shader {}: {}", path_display, error);
+    }
+    Ok(spirv)
+}
+
+/// The hash of everything the compiled SPIR-V of a shader depends on
+fn shader_hash(stage: ShaderStage, glsl_source: &str, macros: &[&str]) -> u64 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write_u8(stage as u8);
+    for name in macros {
+        hasher.write(name.as_bytes());
+        hasher.write_u8(0);
+    }
+    hasher.write(glsl_source.as_bytes());
+    hasher.finish()
 }
 
 /// Default `RasterizationStateDescriptor` with no backface culling
@@ -73,7 +128,16 @@ pub const DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR: wgpu::DepthStencilStateDescrip
         }
     };
 
-/// Create a default pipeline
+/// `DepthStencilStateDescriptor` of the transparent surfaces, which are hidden by the opaque ones
+/// but don't hide what is drawn after them
+pub const TRANSPARENT_DEPTH_STENCIL_STATE_DESCRIPTOR: wgpu::DepthStencilStateDescriptor =
+    wgpu::DepthStencilStateDescriptor {
+        depth_write_enabled: false,
+        ..DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR
+    };
+
+/// Create a default pipeline that draws into buffers of some color format and number of samples
+#[allow(clippy::too_many_arguments)]
 pub fn create_default_pipeline(
     device: &wgpu::Device,
     uniform_layout: &wgpu::BindGroupLayout,
@@ -82,6 +146,85 @@ pub fn create_default_pipeline(
     primitive_topology: wgpu::PrimitiveTopology,
     vertex_buffer_descriptor: wgpu::VertexBufferDescriptor,
     cull_back_faces: bool,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    create_pipeline(
+        device,
+        uniform_layout,
+        vertex_shader,
+        fragment_shader,
+        primitive_topology,
+        vertex_buffer_descriptor,
+        cull_back_faces,
+        color_format,
+        sample_count,
+        Some(DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR),
+    )
+}
+
+/// Create a pipeline for transparent surfaces, which must be drawn after the opaque ones
+#[allow(clippy::too_many_arguments)]
+pub fn create_transparent_pipeline(
+    device: &wgpu::Device,
+    uniform_layout: &wgpu::BindGroupLayout,
+    vertex_shader: wgpu::ShaderModuleSource,
+    fragment_shader: wgpu::ShaderModuleSource,
+    primitive_topology: wgpu::PrimitiveTopology,
+    vertex_buffer_descriptor: wgpu::VertexBufferDescriptor,
+    cull_back_faces: bool,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    create_pipeline(
+        device,
+        uniform_layout,
+        vertex_shader,
+        fragment_shader,
+        primitive_topology,
+        vertex_buffer_descriptor,
+        cull_back_faces,
+        color_format,
+        sample_count,
+        Some(TRANSPARENT_DEPTH_STENCIL_STATE_DESCRIPTOR),
+    )
+}
+
+/// Create a pipeline that draws over a frame that was already resolved, so without depth and without multisampling
+pub fn create_overlay_pipeline(
+    device: &wgpu::Device,
+    uniform_layout: &wgpu::BindGroupLayout,
+    vertex_shader: wgpu::ShaderModuleSource,
+    fragment_shader: wgpu::ShaderModuleSource,
+    vertex_buffer_descriptor: wgpu::VertexBufferDescriptor,
+    color_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    create_pipeline(
+        device,
+        uniform_layout,
+        vertex_shader,
+        fragment_shader,
+        wgpu::PrimitiveTopology::TriangleList,
+        vertex_buffer_descriptor,
+        false,
+        color_format,
+        1,
+        None,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    device: &wgpu::Device,
+    uniform_layout: &wgpu::BindGroupLayout,
+    vertex_shader: wgpu::ShaderModuleSource,
+    fragment_shader: wgpu::ShaderModuleSource,
+    primitive_topology: wgpu::PrimitiveTopology,
+    vertex_buffer_descriptor: wgpu::VertexBufferDescriptor,
+    cull_back_faces: bool,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_stencil_state: Option<wgpu::DepthStencilStateDescriptor>,
 ) -> wgpu::RenderPipeline {
     // Shaders
     let vertex_shader_module = device.create_shader_module(vertex_shader);
@@ -117,10 +260,71 @@ pub fn create_default_pipeline(
             RASTERIZER_NO_CULLING
         }),
         primitive_topology,
-        color_states: &DEFAULT_COLOR_STATE_DESCRIPTOR,
-        depth_stencil_state: Some(DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR),
-        sample_count: crate::window::SAMPLE_COUNT,
+        color_states: &[wgpu::ColorStateDescriptor {
+            format: color_format,
+            ..DEFAULT_COLOR_STATE_DESCRIPTOR[0].clone()
+        }],
+        depth_stencil_state,
+        sample_count,
         sample_mask: 0xFFFFFFFF,
         alpha_to_coverage_enabled: false,
     })
 }
+
+/// Create a pipeline that draws a triangle over the whole screen with some fragment shader
+pub fn create_fullscreen_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    fragment_shader_path: &str,
+    fragment_shader_macros: &[&str],
+    color_state: wgpu::ColorStateDescriptor,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/fullscreen.vert");
+    let vertex_shader_module = device.create_shader_module(wgpu::util::make_spirv(&vertex_shader_bytes));
+    let fragment_shader_bytes = load_glsl_shader_with_macros(ShaderStage::Fragment, fragment_shader_path, fragment_shader_macros);
+    let fragment_shader_module = device.create_shader_module(wgpu::util::make_spirv(&fragment_shader_bytes));
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vertex_shader_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fragment_shader_module,
+            entry_point: "main",
+        }),
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint32,
+            vertex_buffers: &[],
+        },
+        rasterization_state: Some(RASTERIZER_NO_CULLING),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[color_state],
+        depth_stencil_state: None,
+        sample_count,
+        sample_mask: 0xFFFFFFFF,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shader_hashes_depend_on_the_stage_and_the_macros() {
+        let source = "void main() {}";
+        let hash = shader_hash(ShaderStage::Vertex, source, &[]);
+        assert_eq!(hash, shader_hash(ShaderStage::Vertex, source, &[]));
+        assert_ne!(hash, shader_hash(ShaderStage::Fragment, source, &[]));
+        assert_ne!(hash, shader_hash(ShaderStage::Vertex, source, &["MULTISAMPLED"]));
+        assert_ne!(shader_hash(ShaderStage::Vertex, source, &["A", "B"]), shader_hash(ShaderStage::Vertex, source, &["AB"]));
+    }
+}
@@ -5,14 +5,17 @@ mod buffers;
 mod init;
 mod render;
 pub use self::buffers::MultiBuffer;
-pub use self::render::{clear_color_and_depth, clear_depth, encode_resolve_render_pass, to_u8_slice, buffer_from_slice};
+pub use self::render::{clear_color_and_depth, clear_depth, clear_frame, encode_resolve_render_pass, to_u8_slice, buffer_from_slice};
 
 /* OTHER HELPER MODULES */
 mod frustum;
+mod shader_watcher;
 pub use self::frustum::Frustum;
 
 /* RENDERING-RESPONSIBLE MODULES */
+mod post;
 mod ui;
 pub mod world;
+pub use self::post::PostProcessing;
 pub use self::ui::UiRenderer;
-pub use self::world::{Model, WorldRenderer, ChunkVertex};
+pub use self::world::{DebugRenderMode, Model, WorldRenderer, ChunkVertex};
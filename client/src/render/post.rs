@@ -0,0 +1,270 @@
+//! The post-processing of the world. The world is drawn into an HDR buffer, which is resolved, then the effects
+//! of the screen are applied to it while it is tonemapped into the multisampled frame buffer, below the interface.
+use super::init::create_fullscreen_pipeline;
+use super::render::encode_resolve_world_render_pass;
+use super::{buffer_from_slice, to_u8_slice};
+use crate::settings::PostProcessingSettings;
+use crate::window::{WindowBuffers, COLOR_FORMAT, HDR_FORMAT};
+use std::time::Instant;
+
+/// Size of the uniforms: the exposure, the bloom strength, the vignette, whether the camera is underwater,
+/// the time, the bloom threshold and the gamma, padded to 32 bytes
+const POST_UNIFORM_SIZE: u64 = 32;
+/// Brightness of the colors above which they bleed into their surroundings
+const BLOOM_THRESHOLD: f32 = 0.8;
+/// How much of the blurred bright colors is added to the picture
+const BLOOM_STRENGTH: f32 = 0.3;
+/// The gamma is kept above this, since the picture becomes black when it goes to 0
+const MIN_GAMMA: f32 = 0.1;
+/// Period of the time of the animated effects, in seconds. The waves of the underwater distortion repeat with it.
+const TIME_PERIOD: f32 = 2.0 * std::f32::consts::PI;
+
+/// The two textures with half the size of the window between which the bright colors are blurred
+struct BloomTargets {
+    size: (u32, u32),
+    views: [wgpu::TextureView; 2],
+}
+
+/// The resources of the post-processing chain
+pub struct PostProcessing {
+    settings: PostProcessingSettings,
+    start_time: Instant,
+    uniform: wgpu::Buffer,
+    /// The directions of the horizontal and of the vertical blur
+    blur_uniforms: [wgpu::Buffer; 2],
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    bloom_extract_pipeline: wgpu::RenderPipeline,
+    bloom_blur_pipeline: wgpu::RenderPipeline,
+    tonemap_pipeline: wgpu::RenderPipeline,
+    bloom: Option<BloomTargets>,
+}
+
+impl PostProcessing {
+    pub fn new(device: &wgpu::Device, settings: PostProcessingSettings, sample_count: u32) -> Self {
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: POST_UNIFORM_SIZE,
+            usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
+        });
+        let blur_uniforms = [[1.0f32, 0.0], [0.0, 1.0]].map(|direction| {
+            let mut blur_uniform = [0.0f32; POST_UNIFORM_SIZE as usize / 4];
+            blur_uniform[0..2].copy_from_slice(&direction);
+            buffer_from_slice(device, wgpu::BufferUsage::UNIFORM, to_u8_slice(&blur_uniform))
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 0.0,
+            compare: None,
+            anisotropy_clamp: None,
+        });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::SampledTexture {
+                component_type: wgpu::TextureComponentType::Float,
+                multisampled: false,
+                dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        // Every pass reads a uniform, a source texture and the bloom texture
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let bloom_extract_pipeline =
+            create_fullscreen_pipeline(device, &layout, "assets/shaders/bloom_extract.frag", &[], replace(HDR_FORMAT), 1);
+        let bloom_blur_pipeline =
+            create_fullscreen_pipeline(device, &layout, "assets/shaders/bloom_blur.frag", &[], replace(HDR_FORMAT), 1);
+        let tonemap_pipeline = create_tonemap_pipeline(device, &layout, sample_count);
+        Self {
+            settings,
+            start_time: Instant::now(),
+            uniform,
+            blur_uniforms,
+            sampler,
+            layout,
+            bloom_extract_pipeline,
+            bloom_blur_pipeline,
+            tonemap_pipeline,
+            bloom: None,
+        }
+    }
+
+    /// Recreate the pipeline that draws into the frame buffer, for window buffers with another number of samples
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.tonemap_pipeline = create_tonemap_pipeline(device, &self.layout, sample_count);
+    }
+
+    /// Resolve the HDR buffer of the world and draw it with the effects into the multisampled frame buffer.
+    /// `underwater` is whether the camera is in the water.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: WindowBuffers,
+        window_size: (u32, u32),
+        underwater: bool,
+    ) {
+        encode_resolve_world_render_pass(encoder, buffers);
+
+        // Update the uniform
+        let time = self.start_time.elapsed().as_secs_f32() % TIME_PERIOD;
+        let bloom_strength = if self.settings.bloom { BLOOM_STRENGTH } else { 0.0 };
+        let underwater = if underwater { 1.0 } else { 0.0 };
+        let uniform: [f32; POST_UNIFORM_SIZE as usize / 4] = [
+            self.settings.exposure, bloom_strength, self.settings.vignette, underwater,
+            time, BLOOM_THRESHOLD, self.settings.gamma.max(MIN_GAMMA), 0.0,
+        ];
+        let src_buffer = buffer_from_slice(device, wgpu::BufferUsage::COPY_SRC, to_u8_slice(&uniform));
+        encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform, 0, POST_UNIFORM_SIZE);
+
+        // Blur the bright colors of the world at half the size of the window
+        if self.settings.bloom {
+            let bloom_size = ((window_size.0 / 2).max(1), (window_size.1 / 2).max(1));
+            if self.bloom.as_ref().map(|bloom| bloom.size) != Some(bloom_size) {
+                let create_view = || {
+                    device
+                        .create_texture(&wgpu::TextureDescriptor {
+                            label: None,
+                            size: wgpu::Extent3d {
+                                width: bloom_size.0,
+                                height: bloom_size.1,
+                                depth: 1,
+                            },
+                            mip_level_count: 1,
+                            sample_count: 1,
+                            dimension: wgpu::TextureDimension::D2,
+                            format: HDR_FORMAT,
+                            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+                        })
+                        .create_view(&wgpu::TextureViewDescriptor::default())
+                };
+                self.bloom = Some(BloomTargets {
+                    size: bloom_size,
+                    views: [create_view(), create_view()],
+                });
+            }
+            let [first, second] = &self.bloom.as_ref().unwrap().views;
+            self.draw_pass(device, encoder, &self.bloom_extract_pipeline, &self.uniform, buffers.hdr_texture_buffer, first);
+            self.draw_pass(device, encoder, &self.bloom_blur_pipeline, &self.blur_uniforms[0], first, second);
+            self.draw_pass(device, encoder, &self.bloom_blur_pipeline, &self.blur_uniforms[1], second, first);
+        }
+
+        // Tonemap the world with the effects. Without bloom, the world is bound in place of the bloom texture.
+        let bloom_view = self.bloom.as_ref().map(|bloom| &bloom.views[0]).unwrap_or(buffers.hdr_texture_buffer);
+        let bind_group = self.create_bind_group(device, &self.uniform, buffers.hdr_texture_buffer, bloom_view);
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: buffers.multisampled_texture_buffer,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(crate::window::CLEAR_COLOR),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&self.tonemap_pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    /// Draw a fullscreen pass that reads `source` into `target`
+    fn draw_pass(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        uniform: &wgpu::Buffer,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+    ) {
+        let bind_group = self.create_bind_group(device, uniform, source, source);
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    /// The buffers of the window are replaced when it is resized, so the bind groups are created every frame
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        uniform: &wgpu::Buffer,
+        source: &wgpu::TextureView,
+        bloom: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(uniform.slice(..)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(bloom),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
+
+/// A color state that replaces the color of the target
+fn replace(format: wgpu::TextureFormat) -> wgpu::ColorStateDescriptor {
+    wgpu::ColorStateDescriptor {
+        format,
+        color_blend: wgpu::BlendDescriptor::REPLACE,
+        alpha_blend: wgpu::BlendDescriptor::REPLACE,
+        write_mask: wgpu::ColorWrite::ALL,
+    }
+}
+
+/// Every sample of the frame buffer gets the color of its pixel, and the interface is drawn over it
+fn create_tonemap_pipeline(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, sample_count: u32) -> wgpu::RenderPipeline {
+    create_fullscreen_pipeline(device, layout, "assets/shaders/tonemap.frag", &[], replace(COLOR_FORMAT), sample_count)
+}
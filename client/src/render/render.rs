@@ -40,8 +40,51 @@ pub fn create_default_render_pass<'a>(
     })
 }
 
+/// Create a render pass that renders to the multisampled HDR buffer of the world without resolving and without clearing.
+pub fn create_world_render_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    buffers: WindowBuffers<'a>,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: buffers.hdr_multisampled_texture_buffer,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: true
+            },
+        }],
+        depth_stencil_attachment: Some(create_default_depth_stencil_attachment(
+            buffers.depth_buffer,
+        )),
+    })
+}
+
+/// Encode a render pass to resolve the multisampled HDR buffer of the world to the buffer that the post-processing reads
+pub fn encode_resolve_world_render_pass(encoder: &mut wgpu::CommandEncoder, buffers: WindowBuffers) {
+    // Without multisampling, the world is drawn into the resolved buffer directly
+    if buffers.sample_count == 1 {
+        return;
+    }
+    let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: buffers.hdr_multisampled_texture_buffer,
+            resolve_target: Some(buffers.hdr_texture_buffer),
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: true
+            },
+        }],
+        depth_stencil_attachment: None,
+    });
+}
+
 /// Encode a render pass to resolve the multisampled frame buffer to the window frame buffer
 pub fn encode_resolve_render_pass<'a>(encoder: &mut wgpu::CommandEncoder, buffers: WindowBuffers) {
+    // Without multisampling, the frame is drawn into the window frame buffer directly
+    if buffers.sample_count == 1 {
+        return;
+    }
     let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
             attachment: buffers.multisampled_texture_buffer,
@@ -59,7 +102,7 @@ fn create_clear_color_attachment(
     buffers: WindowBuffers,
 ) -> [wgpu::RenderPassColorAttachmentDescriptor; 1] {
     [wgpu::RenderPassColorAttachmentDescriptor {
-        attachment: buffers.multisampled_texture_buffer,
+        attachment: buffers.hdr_multisampled_texture_buffer,
         resolve_target: None,
         ops: wgpu::Operations {
             load: wgpu::LoadOp::Clear(crate::window::CLEAR_COLOR),
@@ -84,7 +127,7 @@ fn create_clear_depth_attachment(
     }
 }
 
-/// Clear the multisampled color buffer and the depth buffer
+/// Clear the multisampled HDR buffer of the world and the depth buffer
 pub fn clear_color_and_depth(encoder: &mut wgpu::CommandEncoder, buffers: WindowBuffers) {
     let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         color_attachments: &create_clear_color_attachment(buffers),
@@ -92,6 +135,21 @@ pub fn clear_color_and_depth(encoder: &mut wgpu::CommandEncoder, buffers: Window
     });
 }
 
+/// Clear the multisampled frame buffer and the depth buffer, for the states that draw the interface alone
+pub fn clear_frame(encoder: &mut wgpu::CommandEncoder, buffers: WindowBuffers) {
+    let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: buffers.multisampled_texture_buffer,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(crate::window::CLEAR_COLOR),
+                store: true
+            },
+        }],
+        depth_stencil_attachment: Some(create_clear_depth_attachment(buffers)),
+    });
+}
+
 /// Clear the depth buffer
 pub fn clear_depth(encoder: &mut wgpu::CommandEncoder, buffers: WindowBuffers) {
     let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -0,0 +1,81 @@
+//! Reloading of the shaders while the game runs, to develop them without restarting the game.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The folder of the shaders is checked at most this often, since reading it takes a few system calls per file
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches the modification times of the files of a folder
+pub struct ShaderWatcher {
+    folder: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_check: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    pub fn new(folder: impl Into<PathBuf>) -> Self {
+        let folder = folder.into();
+        Self {
+            modified: modification_times(&folder),
+            folder,
+            last_check: None,
+        }
+    }
+
+    /// The files that were modified or added since the last check, unless the last check was too recent
+    pub fn changed_files(&mut self, now: Instant) -> Vec<PathBuf> {
+        if self.last_check.map(|last| now.saturating_duration_since(last) < CHECK_INTERVAL).unwrap_or(false) {
+            return Vec::new();
+        }
+        self.last_check = Some(now);
+        let modified = modification_times(&self.folder);
+        let mut changed = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        changed.sort();
+        self.modified = modified;
+        changed
+    }
+}
+
+/// The modification time of every file of a folder
+fn modification_times(folder: &Path) -> HashMap<PathBuf, SystemTime> {
+    let entries = match std::fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(error) => {
+            log::warn!("Couldn't read the shader folder {}: {}", folder.display(), error);
+            return HashMap::new();
+        }
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.path(), modified))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_files_are_reported_once() {
+        let folder = std::env::temp_dir().join(format!("shader_watcher_test_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("a.frag"), "void main() {}").unwrap();
+        let mut watcher = ShaderWatcher::new(&folder);
+        let start = Instant::now();
+        assert!(watcher.changed_files(start).is_empty());
+        std::fs::write(folder.join("b.vert"), "void main() {}").unwrap();
+        // The folder is not checked again right away
+        assert!(watcher.changed_files(start + CHECK_INTERVAL / 2).is_empty());
+        assert_eq!(watcher.changed_files(start + CHECK_INTERVAL), vec![folder.join("b.vert")]);
+        assert!(watcher.changed_files(start + 2 * CHECK_INTERVAL).is_empty());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -3,10 +3,11 @@
 use super::{ buffer_from_slice, to_u8_slice };
 use super::buffers::DynamicBuffer;
 use super::init::{load_glsl_shader, ShaderStage};
-use crate::ui::PrimitiveBuffer;
+use crate::ui::{ClipRect, PrimitiveBuffer, RectanglePrimitive, TextPrimitive, TexturedPrimitive};
 use crate::window::{WindowBuffers, WindowData};
 use std::collections::{BTreeMap, HashMap};
-use wgpu_glyph::{FontId, ab_glyph::FontVec};
+use std::ops::Range;
+use wgpu_glyph::{FontId, Region, ab_glyph::FontVec};
 
 pub struct UiRenderer {
     // Glyph rendering
@@ -15,13 +16,28 @@ pub struct UiRenderer {
     // Rectangle rendering
     transform_buffer: wgpu::Buffer,
     uniforms_bind_group: wgpu::BindGroup,
+    uniform_layout: wgpu::BindGroupLayout,
     pipeline: wgpu::RenderPipeline,
+    // Number of samples of the window buffers that the pipeline draws into
+    sample_count: u32,
     vertex_buffer: DynamicBuffer<UiVertex>,
     index_buffer: DynamicBuffer<u32>,
+    // Textured rectangle rendering, once the texture atlas is set
+    atlas_layout: wgpu::BindGroupLayout,
+    atlas_bind_group: Option<wgpu::BindGroup>,
+    textured_pipeline: wgpu::RenderPipeline,
+    textured_vertex_buffer: DynamicBuffer<TexturedVertex>,
+    textured_index_buffer: DynamicBuffer<u32>,
+    // Rectangles of the overlay, drawn over the text like the tooltips
+    overlay_pipeline: wgpu::RenderPipeline,
+    overlay_vertex_buffer: DynamicBuffer<UiVertex>,
+    overlay_index_buffer: DynamicBuffer<u32>,
+    /// The font of the texts that don't choose one, which depends on the language
+    default_font: FontId,
 }
 
 impl<'a> UiRenderer {
-    pub fn new(device: &mut wgpu::Device) -> Self {
+    pub fn new(device: &mut wgpu::Device, sample_count: u32) -> Self {
         // Load fonts
         let default_font = FontVec::try_from_vec(
             include_bytes!("../../../assets/fonts/IBMPlexMono-Regular.ttf").to_vec()
@@ -79,41 +95,69 @@ impl<'a> UiRenderer {
             }],
         });
 
-        // Create shader modules
-        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/gui-rect.vert");
-        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
-        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/gui-rect.frag");
-        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
-
-        log::trace!("Creating pipeline.");
-
-        let pipeline = super::init::create_default_pipeline(
-            device,
-            &uniform_layout,
-            vertex_shader,
-            fragment_shader,
-            wgpu::PrimitiveTopology::TriangleList,
-            wgpu::VertexBufferDescriptor {
-                stride: std::mem::size_of::<UiVertex>() as u64,
-                step_mode: wgpu::InputStepMode::Vertex,
-                attributes: &UI_VERTEX_ATTRIBUTES,
-            },
-            false,
-        );
-
-        log::trace!("Created pipeline.");
+        let pipeline = create_rect_pipeline(device, &uniform_layout, sample_count);
+        let atlas_layout = device.create_bind_group_layout(&ATLAS_BIND_GROUP_LAYOUT);
+        let textured_pipeline = create_textured_pipeline(device, &atlas_layout, sample_count);
+        let overlay_pipeline = create_overlay_pipeline(device, &uniform_layout);
 
         Self {
             glyph_brush,
             fonts,
             transform_buffer,
             uniforms_bind_group,
+            uniform_layout,
             pipeline,
+            sample_count,
             vertex_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsage::VERTEX),
             index_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsage::INDEX),
+            atlas_layout,
+            atlas_bind_group: None,
+            textured_pipeline,
+            textured_vertex_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsage::VERTEX),
+            textured_index_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsage::INDEX),
+            overlay_pipeline,
+            overlay_vertex_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsage::VERTEX),
+            overlay_index_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsage::INDEX),
+            default_font: FontId::default(),
         }
     }
 
+    /// Draw the textured rectangles with the textures of an atlas, like the one of the world
+    pub fn set_texture_atlas(&mut self, device: &wgpu::Device, texture_atlas_view: &wgpu::TextureView) {
+        // The icons are small pixel art, that stays sharp when it is scaled up
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 0.0,
+            compare: None,
+            anisotropy_clamp: None,
+        });
+        self.atlas_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.atlas_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(self.transform_buffer.slice(0..64)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(texture_atlas_view),
+                },
+            ],
+        }));
+    }
+
     pub fn render<Message>(
         &mut self,
         buffers: WindowBuffers<'a>,
@@ -124,52 +168,38 @@ impl<'a> UiRenderer {
         gui: &mut crate::gui::Gui,
         draw_crosshair: bool,
     ) {
+        // The pipeline must match the window buffers, whose number of samples follows the settings
+        if buffers.sample_count != self.sample_count {
+            self.sample_count = buffers.sample_count;
+            self.pipeline = create_rect_pipeline(device, &self.uniform_layout, self.sample_count);
+            self.textured_pipeline = create_textured_pipeline(device, &self.atlas_layout, self.sample_count);
+        }
+
         // Render test dropdown
         let mut primitive_buffer = gui.drain_primitives();
+        let overlay = gui.drain_overlay_primitives();
 
         //ui.render(&mut primitive_buffer);
 
         // Render primitives
         let mut rect_vertices: Vec<UiVertex> = Vec::new();
         let mut rect_indices: Vec<u32> = Vec::new();
+        // The ranges of indices that share a clipping rectangle, in drawing order
+        let mut batches: Vec<(Option<ClipRect>, Range<u32>)> = Vec::new();
 
-        use crate::ui::{RectanglePrimitive, TextPrimitive, TrianglesPrimitive};
+        use crate::ui::TrianglesPrimitive;
 
         // Rectangles
-        for RectanglePrimitive {
-            layout: l,
-            color,
-            z,
-        } in primitive_buffer.rectangle.into_iter()
-        {
-            let a = UiVertex {
-                position: [l.x, l.y, z],
-                color: color.clone(),
-            };
-            let b = UiVertex {
-                position: [l.x + l.width, l.y, z],
-                color: color.clone(),
-            };
-            let c = UiVertex {
-                position: [l.x, l.y + l.height, z],
-                color: color.clone(),
-            };
-            let d = UiVertex {
-                position: [l.x + l.width, l.y + l.height, z],
-                color: color.clone(),
-            };
-            let a_index = rect_vertices.len() as u32;
-            let b_index = a_index + 1;
-            let c_index = b_index + 1;
-            let d_index = c_index + 1;
-            rect_vertices.extend([a, b, c, d].iter());
-            rect_indices.extend([b_index, a_index, c_index, b_index, c_index, d_index].iter());
+        for rectangle in primitive_buffer.rectangle.iter() {
+            push_rectangle(&mut rect_vertices, &mut rect_indices, rectangle);
+            push_batch(&mut batches, rectangle.clip, rect_indices.len() as u32);
         }
         // Triangles
         for TrianglesPrimitive {
             vertices,
             indices,
             color,
+            clip,
         } in primitive_buffer.triangles.into_iter()
         {
             let index_offset = rect_vertices.len() as u32;
@@ -179,78 +209,42 @@ impl<'a> UiRenderer {
                     .map(|v| UiVertex { position: v, color }),
             );
             rect_indices.extend(indices.into_iter().map(|id| id + index_offset));
+            push_batch(&mut batches, clip, rect_indices.len() as u32);
         }
-        // Text
-        for TextPrimitive {
-            x, y, w, h,
-            mut parts,
-            z,
-            center_horizontally, center_vertically,
-        } in primitive_buffer.text.into_iter()
-        {
-            let dpi = data.hidpi_factor as f32;
-
-            // Apply DPI to font size
-            for p in parts.iter_mut() {
-                p.font_size.x *= dpi;
-                p.font_size.y *= dpi;
-            }
-            // Get font IDs
-            let Self { ref fonts, .. } = &self;
-            let parts: Vec<wgpu_glyph::Text> = parts
-                .iter()
-                .map(|part| wgpu_glyph::Text::new(&part.text)
-                    .with_scale(part.font_size)
-                    .with_color(part.color)
-                    .with_font_id(part
-                        .font
-                        .clone()
-                        .and_then(|f| fonts.get(&f).cloned())
-                        .unwrap_or_default())
-                )
-                .collect();
-            // Calculate positions
-            let mut x = x as f32;
-            let mut y = y as f32;
-            let mut w = match w {
-                Some(w) => w as f32,
-                None => std::f32::INFINITY,
-            };
-            let mut h = match h {
-                Some(h) => h as f32,
-                None => std::f32::INFINITY,
-            };
-            if center_horizontally {
-                x += w/2.0;
-            }
-            if center_vertically {
-                y += h/2.0;
+        // Textured rectangles, which are only drawn once there is a texture atlas
+        let mut textured_vertices: Vec<TexturedVertex> = Vec::new();
+        let mut textured_indices: Vec<u32> = Vec::new();
+        let mut textured_batches: Vec<(Option<ClipRect>, Range<u32>)> = Vec::new();
+        if self.atlas_bind_group.is_some() {
+            for TexturedPrimitive {
+                layout: l,
+                texture: t,
+                z,
+                clip,
+            } in primitive_buffer.textured.into_iter()
+            {
+                let vertex = |x, y, u, v| TexturedVertex {
+                    position: [x, y, z],
+                    texture_uv: [u, v],
+                    texture_page: t.page,
+                };
+                let index_offset = textured_vertices.len() as u32;
+                textured_vertices.extend_from_slice(&[
+                    vertex(l.x, l.y, t.x, t.y),
+                    vertex(l.x + l.width, l.y, t.x + t.width, t.y),
+                    vertex(l.x, l.y + l.height, t.x, t.y + t.height),
+                    vertex(l.x + l.width, l.y + l.height, t.x + t.width, t.y + t.height),
+                ]);
+                textured_indices.extend([1, 0, 2, 1, 2, 3].iter().map(|id| id + index_offset));
+                push_batch(&mut textured_batches, clip, textured_indices.len() as u32);
             }
-            // Apply DPI to positions
-            x *= dpi;
-            y *= dpi;
-            w *= dpi;
-            h *= dpi;
-            let v_align = if center_vertically {
-                wgpu_glyph::VerticalAlign::Center
-            } else {
-                wgpu_glyph::VerticalAlign::Top
-            };
-            let h_align = if center_horizontally {
-                wgpu_glyph::HorizontalAlign::Center
-            } else {
-                wgpu_glyph::HorizontalAlign::Left
-            };
-            let section = wgpu_glyph::Section::default()
-                .with_screen_position((x, y))
-                .with_bounds((w, h))
-                .with_layout(wgpu_glyph::Layout::Wrap {
-                    line_breaker: Default::default(),
-                    v_align,
-                    h_align,
-                })
-                .with_text(parts);
-            self.glyph_brush.queue(section);
+        }
+        // Text. The clipped text is queued later, since the glyphs are clipped when they are drawn.
+        let (clipped_text, text): (Vec<_>, Vec<_>) = primitive_buffer.text.into_iter().partition(|text| text.clip.is_some());
+        let dpi = data.ui_scale as f32;
+        self.default_font = crate::lang::font().and_then(|font| self.fonts.get(&font).copied()).unwrap_or_default();
+        for text in text.iter() {
+            self.queue_text(text, dpi);
         }
         // Crosshair
         if draw_crosshair {
@@ -300,13 +294,14 @@ impl<'a> UiRenderer {
                     .iter()
                     .map(|id| id + voffset),
             );
+            push_batch(&mut batches, None, rect_indices.len() as u32);
         }
 
         // Draw rectangles
         {
             let (win_w, win_h) = (
-                data.logical_window_size.width,
-                data.logical_window_size.height,
+                data.logical_window_size.width.max(1.0),
+                data.logical_window_size.height.max(1.0),
             );
             // Update the uniform buffer to map (w, h) coordinates to [-1, 1]
             let transformation_matrix = [
@@ -337,6 +332,10 @@ impl<'a> UiRenderer {
             self.vertex_buffer.upload(device, encoder, &rect_vertices);
             // Update index buffer
             self.index_buffer.upload(device, encoder, &rect_indices);
+            debug_assert_eq!(batches.last().map(|(_, indices)| indices.end as usize).unwrap_or(0), self.index_buffer.len());
+            // Update the buffers of the textured rectangles
+            self.textured_vertex_buffer.upload(device, encoder, &textured_vertices);
+            self.textured_index_buffer.upload(device, encoder, &textured_indices);
             // Draw
             {
                 let mut rpass = super::render::create_default_render_pass(encoder, buffers);
@@ -344,7 +343,15 @@ impl<'a> UiRenderer {
                 rpass.set_bind_group(0, &self.uniforms_bind_group, &[]);
                 rpass.set_vertex_buffer(0, self.vertex_buffer.get_buffer().slice(..));
                 rpass.set_index_buffer(self.index_buffer.get_buffer().slice(..));
-                rpass.draw_indexed(0..(self.index_buffer.len() as u32), 0, 0..1);
+                let window_size = (data.physical_window_size.width, data.physical_window_size.height);
+                draw_batches(&mut rpass, batches, dpi, window_size);
+                if let Some(atlas_bind_group) = self.atlas_bind_group.as_ref().filter(|_| !textured_batches.is_empty()) {
+                    rpass.set_pipeline(&self.textured_pipeline);
+                    rpass.set_bind_group(0, atlas_bind_group, &[]);
+                    rpass.set_vertex_buffer(0, self.textured_vertex_buffer.get_buffer().slice(..));
+                    rpass.set_index_buffer(self.textured_index_buffer.get_buffer().slice(..));
+                    draw_batches(&mut rpass, textured_batches, dpi, window_size);
+                }
             }
         }
 
@@ -365,9 +372,290 @@ impl<'a> UiRenderer {
                 data.physical_window_size.height,
             )
             .expect("couldn't draw queued glyphs");
+        // Draw the clipped text, one clipping rectangle at a time
+        let mut clips: Vec<ClipRect> = Vec::new();
+        for clip in clipped_text.iter().filter_map(|text| text.clip) {
+            if !clips.contains(&clip) {
+                clips.push(clip);
+            }
+        }
+        let (width, height) = (data.physical_window_size.width, data.physical_window_size.height);
+        for clip in clips {
+            for text in clipped_text.iter().filter(|text| text.clip == Some(clip)) {
+                self.queue_text(text, dpi);
+            }
+            let region = physical_region(clip, dpi, width, height);
+            if region.width > 0 && region.height > 0 {
+                self.glyph_brush
+                    .draw_queued_with_transform_and_scissoring(
+                        device,
+                        &mut staging_belt,
+                        encoder,
+                        buffers.texture_buffer,
+                        wgpu_glyph::orthographic_projection(width, height),
+                        region,
+                    )
+                    .expect("couldn't draw queued glyphs");
+            }
+        }
+
+        // Draw the overlay over the text, in the order of its primitives
+        if !overlay.rectangle.is_empty() || !overlay.text.is_empty() {
+            let mut overlay_vertices: Vec<UiVertex> = Vec::new();
+            let mut overlay_indices: Vec<u32> = Vec::new();
+            for rectangle in overlay.rectangle.iter() {
+                push_rectangle(&mut overlay_vertices, &mut overlay_indices, rectangle);
+            }
+            self.overlay_vertex_buffer.upload(device, encoder, &overlay_vertices);
+            self.overlay_index_buffer.upload(device, encoder, &overlay_indices);
+            {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: buffers.texture_buffer,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&self.overlay_pipeline);
+                rpass.set_bind_group(0, &self.uniforms_bind_group, &[]);
+                rpass.set_vertex_buffer(0, self.overlay_vertex_buffer.get_buffer().slice(..));
+                rpass.set_index_buffer(self.overlay_index_buffer.get_buffer().slice(..));
+                rpass.draw_indexed(0..overlay_indices.len() as u32, 0, 0..1);
+            }
+            for text in overlay.text.iter() {
+                self.queue_text(text, dpi);
+            }
+            self.glyph_brush
+                .draw_queued(device, &mut staging_belt, encoder, buffers.texture_buffer, width, height)
+                .expect("couldn't draw queued glyphs");
+        }
+    }
+
+    /// Queue a text for the next draw of the glyphs
+    fn queue_text(&mut self, text: &TextPrimitive, dpi: f32) {
+        let TextPrimitive {
+            x, y, w, h,
+            parts,
+            center_horizontally, center_vertically,
+            ..
+        } = text;
+        let (center_horizontally, center_vertically) = (*center_horizontally, *center_vertically);
+        // Get font IDs, and apply DPI to font size
+        let Self { ref fonts, default_font, .. } = *self;
+        let parts: Vec<wgpu_glyph::Text> = parts
+            .iter()
+            .map(|part| wgpu_glyph::Text::new(&part.text)
+                .with_scale(wgpu_glyph::ab_glyph::PxScale { x: part.font_size.x * dpi, y: part.font_size.y * dpi })
+                .with_color(part.color)
+                .with_font_id(part
+                    .font
+                    .clone()
+                    .and_then(|f| fonts.get(&f).cloned())
+                    .unwrap_or(default_font))
+            )
+            .collect();
+        // Calculate positions
+        let mut x = *x as f32;
+        let mut y = *y as f32;
+        let mut w = match w {
+            Some(w) => *w as f32,
+            None => f32::INFINITY,
+        };
+        let mut h = match h {
+            Some(h) => *h as f32,
+            None => f32::INFINITY,
+        };
+        if center_horizontally {
+            x += w/2.0;
+        }
+        if center_vertically {
+            y += h/2.0;
+        }
+        // Apply DPI to positions
+        x *= dpi;
+        y *= dpi;
+        w *= dpi;
+        h *= dpi;
+        let v_align = if center_vertically {
+            wgpu_glyph::VerticalAlign::Center
+        } else {
+            wgpu_glyph::VerticalAlign::Top
+        };
+        let h_align = if center_horizontally {
+            wgpu_glyph::HorizontalAlign::Center
+        } else {
+            wgpu_glyph::HorizontalAlign::Left
+        };
+        let section = wgpu_glyph::Section::default()
+            .with_screen_position((x, y))
+            .with_bounds((w, h))
+            .with_layout(wgpu_glyph::Layout::Wrap {
+                line_breaker: Default::default(),
+                v_align,
+                h_align,
+            })
+            .with_text(parts);
+        self.glyph_brush.queue(section);
     }
 }
 
+/// Extend the last batch of indices up to `end` if it has the same clipping rectangle, or start a new batch
+fn push_batch(batches: &mut Vec<(Option<ClipRect>, Range<u32>)>, clip: Option<ClipRect>, end: u32) {
+    match batches.last_mut() {
+        Some((last_clip, indices)) if *last_clip == clip => indices.end = end,
+        last => {
+            let start = last.map(|(_, indices)| indices.end).unwrap_or(0);
+            batches.push((clip, start..end));
+        }
+    }
+}
+
+/// Draw the batches of indices, each one clipped to its rectangle
+fn draw_batches(rpass: &mut wgpu::RenderPass, batches: Vec<(Option<ClipRect>, Range<u32>)>, dpi: f32, (width, height): (u32, u32)) {
+    for (clip, indices) in batches {
+        let region = match clip {
+            Some(clip) => physical_region(clip, dpi, width, height),
+            None => Region { x: 0, y: 0, width, height },
+        };
+        if region.width > 0 && region.height > 0 {
+            rpass.set_scissor_rect(region.x, region.y, region.width, region.height);
+            rpass.draw_indexed(indices, 0, 0..1);
+        }
+    }
+}
+
+/// The part of the window inside a clipping rectangle, in physical pixels
+fn physical_region(clip: ClipRect, dpi: f32, width: u32, height: u32) -> Region {
+    let to_physical = |logical: i32, max: u32| ((logical as f32 * dpi).round().max(0.0) as u32).min(max);
+    let (x, y) = (to_physical(clip.x, width), to_physical(clip.y, height));
+    let (right, bottom) = (to_physical(clip.x + clip.w, width), to_physical(clip.y + clip.h, height));
+    Region {
+        x,
+        y,
+        width: right.saturating_sub(x),
+        height: bottom.saturating_sub(y),
+    }
+}
+
+/// Add the vertices and the indices of a rectangle
+fn push_rectangle(vertices: &mut Vec<UiVertex>, indices: &mut Vec<u32>, rectangle: &RectanglePrimitive) {
+    let RectanglePrimitive { layout: l, color, z, .. } = *rectangle;
+    let vertex = |x, y| UiVertex { position: [x, y, z], color };
+    let a_index = vertices.len() as u32;
+    vertices.extend_from_slice(&[
+        vertex(l.x, l.y),
+        vertex(l.x + l.width, l.y),
+        vertex(l.x, l.y + l.height),
+        vertex(l.x + l.width, l.y + l.height),
+    ]);
+    indices.extend([1, 0, 2, 1, 2, 3].iter().map(|id| id + a_index));
+}
+
+/// Create the pipeline of the rectangles, for window buffers with some number of samples
+fn create_rect_pipeline(device: &wgpu::Device, uniform_layout: &wgpu::BindGroupLayout, sample_count: u32) -> wgpu::RenderPipeline {
+    // Create shader modules
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/gui-rect.vert");
+    let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+    let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/gui-rect.frag");
+    let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+    log::trace!("Creating pipeline.");
+
+    super::init::create_default_pipeline(
+        device,
+        uniform_layout,
+        vertex_shader,
+        fragment_shader,
+        wgpu::PrimitiveTopology::TriangleList,
+        wgpu::VertexBufferDescriptor {
+            stride: std::mem::size_of::<UiVertex>() as u64,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &UI_VERTEX_ATTRIBUTES,
+        },
+        false,
+        crate::window::COLOR_FORMAT,
+        sample_count,
+    )
+}
+
+/// Create the pipeline of the rectangles of the overlay, which draws over the resolved frame
+fn create_overlay_pipeline(device: &wgpu::Device, uniform_layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/gui-rect.vert");
+    let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+    let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/gui-rect.frag");
+    let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+    super::init::create_overlay_pipeline(
+        device,
+        uniform_layout,
+        vertex_shader,
+        fragment_shader,
+        wgpu::VertexBufferDescriptor {
+            stride: std::mem::size_of::<UiVertex>() as u64,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &UI_VERTEX_ATTRIBUTES,
+        },
+        crate::window::COLOR_FORMAT,
+    )
+}
+
+/// Create the pipeline of the textured rectangles, for window buffers with some number of samples
+fn create_textured_pipeline(device: &wgpu::Device, atlas_layout: &wgpu::BindGroupLayout, sample_count: u32) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/gui-textured.vert");
+    let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+    let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/gui-textured.frag");
+    let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+    super::init::create_default_pipeline(
+        device,
+        atlas_layout,
+        vertex_shader,
+        fragment_shader,
+        wgpu::PrimitiveTopology::TriangleList,
+        wgpu::VertexBufferDescriptor {
+            stride: std::mem::size_of::<TexturedVertex>() as u64,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &TEXTURED_VERTEX_ATTRIBUTES,
+        },
+        false,
+        crate::window::COLOR_FORMAT,
+        sample_count,
+    )
+}
+
+/// The transformation of the window coordinates, then the texture atlas with its sampler
+const ATLAS_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+    label: None,
+    entries: &[
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::VERTEX,
+            ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Sampler { comparison: false },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::SampledTexture {
+                component_type: wgpu::TextureComponentType::Float,
+                multisampled: false,
+                dimension: wgpu::TextureViewDimension::D2Array,
+            },
+            count: None,
+        },
+    ],
+};
+
 #[derive(Debug, Clone, Copy)]
 struct UiVertex {
     position: [f32; 3],
@@ -386,3 +674,49 @@ const UI_VERTEX_ATTRIBUTES: [wgpu::VertexAttributeDescriptor; 2] = [
         offset: 12,
     },
 ];
+
+#[derive(Debug, Clone, Copy)]
+struct TexturedVertex {
+    position: [f32; 3],
+    texture_uv: [f32; 2],
+    texture_page: u32,
+}
+
+const TEXTURED_VERTEX_ATTRIBUTES: [wgpu::VertexAttributeDescriptor; 3] = [
+    wgpu::VertexAttributeDescriptor {
+        shader_location: 0,
+        format: wgpu::VertexFormat::Float3,
+        offset: 0,
+    },
+    wgpu::VertexAttributeDescriptor {
+        shader_location: 1,
+        format: wgpu::VertexFormat::Float2,
+        offset: 12,
+    },
+    wgpu::VertexAttributeDescriptor {
+        shader_location: 2,
+        format: wgpu::VertexFormat::Uint,
+        offset: 20,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_are_batched_by_clipping_rectangle() {
+        let clip = ClipRect { x: 10, y: 20, w: 100, h: 50 };
+        let mut batches = Vec::new();
+        push_batch(&mut batches, None, 6);
+        push_batch(&mut batches, None, 12);
+        push_batch(&mut batches, Some(clip), 18);
+        push_batch(&mut batches, None, 24);
+        assert_eq!(batches, vec![(None, 0..12), (Some(clip), 12..18), (None, 18..24)]);
+        // The region is scaled to the physical pixels, and stays inside the window
+        let region = physical_region(clip, 2.0, 150, 1000);
+        assert_eq!((region.x, region.y, region.width, region.height), (20, 40, 130, 100));
+        let outer = ClipRect { x: 0, y: 0, w: 50, h: 30 };
+        assert_eq!(clip.intersection(outer), ClipRect { x: 10, y: 20, w: 40, h: 10 });
+    }
+}
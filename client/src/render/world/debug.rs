@@ -0,0 +1,125 @@
+//! The debug views of the world: the wireframe of the chunk meshes, the borders of the chunks around the camera
+//! and the hitboxes of the players and of the entities.
+use history_survival_common::physics::aabb::AABB;
+use history_survival_common::world::{ChunkPos, CHUNK_SIZE};
+use nalgebra::Vector3;
+
+/// Color of the borders of the chunk of the camera
+const CAMERA_CHUNK_COLOR: [f32; 3] = [1.0, 0.85, 0.2];
+/// Color of the borders of the chunks around it
+const NEIGHBOR_CHUNK_COLOR: [f32; 3] = [0.2, 0.6, 1.0];
+const HITBOX_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+/// The borders of the chunks are drawn up to this many chunks around the chunk of the camera
+const CHUNK_BORDER_RADIUS: i64 = 1;
+
+/// What the world renderer draws to debug the meshing and the physics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugRenderMode {
+    #[default]
+    Off,
+    /// The edges of the quads of the chunk meshes, instead of their faces
+    Wireframe,
+    /// The borders of the chunks around the camera
+    ChunkBorders,
+    /// The boxes with which the players and the entities collide
+    Hitboxes,
+}
+
+impl DebugRenderMode {
+    /// The next mode of the cycle
+    pub fn next(self) -> Self {
+        match self {
+            DebugRenderMode::Off => DebugRenderMode::Wireframe,
+            DebugRenderMode::Wireframe => DebugRenderMode::ChunkBorders,
+            DebugRenderMode::ChunkBorders => DebugRenderMode::Hitboxes,
+            DebugRenderMode::Hitboxes => DebugRenderMode::Off,
+        }
+    }
+}
+
+/// A vertex of the colored lines of the debug views
+#[derive(Debug, Clone, Copy)]
+pub struct DebugLineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+pub const DEBUG_LINE_VERTEX_ATTRIBUTES: [wgpu::VertexAttributeDescriptor; 2] = [
+    wgpu::VertexAttributeDescriptor {
+        shader_location: 0,
+        format: wgpu::VertexFormat::Float3,
+        offset: 0,
+    },
+    wgpu::VertexAttributeDescriptor {
+        shader_location: 1,
+        format: wgpu::VertexFormat::Float3,
+        offset: 4 * 3,
+    },
+];
+
+/// The line list indices of the edges of `quads` quads of 4 consecutive vertices. The chunk meshes are made of such
+/// quads, so the same indices draw the wireframe of every chunk. Both diagonals are drawn, since the corners of the
+/// greedy quads and of the block model quads are not in the same order.
+pub fn quad_outline_indices(quads: usize) -> Vec<u32> {
+    const EDGES: [u32; 12] = [0, 1, 1, 2, 2, 3, 3, 0, 0, 2, 1, 3];
+    (0..quads as u32).flat_map(|quad| EDGES.iter().map(move |i| 4 * quad + i)).collect()
+}
+
+/// The 12 edges of a box, as a line list
+fn box_lines(min: Vector3<f64>, max: Vector3<f64>, color: [f32; 3]) -> impl Iterator<Item = DebugLineVertex> {
+    let corner = move |i: usize| DebugLineVertex {
+        position: [
+            if i & 1 == 0 { min.x } else { max.x } as f32,
+            if i & 2 == 0 { min.y } else { max.y } as f32,
+            if i & 4 == 0 { min.z } else { max.z } as f32,
+        ],
+        color,
+    };
+    // Every corner is linked to the corners that differ by one coordinate
+    (0..8)
+        .flat_map(|i| [1, 2, 4].iter().filter(move |bit| i & **bit == 0).map(move |bit| (i, i | bit)))
+        .flat_map(move |(a, b)| [corner(a), corner(b)])
+}
+
+/// The borders of the chunks around the chunk of the camera
+pub fn chunk_border_lines(camera_chunk: ChunkPos) -> Vec<DebugLineVertex> {
+    let r = CHUNK_BORDER_RADIUS;
+    let mut lines = Vec::new();
+    for dx in -r..=r {
+        for dy in -r..=r {
+            for dz in -r..=r {
+                let chunk = camera_chunk.offset(dx, dy, dz);
+                let min = Vector3::new(chunk.px as f64, chunk.py as f64, chunk.pz as f64) * CHUNK_SIZE as f64;
+                let max = min + Vector3::repeat(CHUNK_SIZE as f64);
+                let color = if chunk == camera_chunk { CAMERA_CHUNK_COLOR } else { NEIGHBOR_CHUNK_COLOR };
+                lines.extend(box_lines(min, max, color));
+            }
+        }
+    }
+    lines
+}
+
+/// The edges of the hitboxes
+pub fn hitbox_lines(hitboxes: &[AABB]) -> Vec<DebugLineVertex> {
+    hitboxes
+        .iter()
+        .flat_map(|aabb| box_lines(aabb.pos, aabb.pos + Vector3::new(aabb.size_x, aabb.size_y, aabb.size_z), HITBOX_COLOR))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxes_have_twelve_edges() {
+        let lines: Vec<_> = box_lines(Vector3::zeros(), Vector3::repeat(1.0), HITBOX_COLOR).collect();
+        assert_eq!(lines.len(), 24);
+        // Every edge is axis-aligned with length 1
+        for edge in lines.chunks(2) {
+            let length: f32 = (0..3).map(|i| (edge[1].position[i] - edge[0].position[i]).abs()).sum();
+            assert_eq!(length, 1.0);
+        }
+        assert_eq!(quad_outline_indices(2)[12..16], [4, 5, 5, 6]);
+    }
+}
@@ -0,0 +1,118 @@
+//! Light and ambient occlusion at arbitrary positions, for the vertices that are not on the block grid
+//! and for the models drawn in the world.
+//!
+//! The values are interpolated trilinearly between the centers of the 8 blocks around the position.
+
+/// Something that knows the light level and the opacity of the blocks
+pub trait LightSource {
+    /// Whether the block at some position is opaque
+    fn is_opaque(&self, x: i64, y: i64, z: i64) -> bool;
+    /// The light level of the block at some position, between 0 and 15
+    fn get_light(&self, x: i64, y: i64, z: i64) -> u8;
+}
+
+/// The light at some position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledLight {
+    /// Light level between 0 and 15, interpolated between the non-opaque blocks
+    pub light: f32,
+    /// Fraction of the surrounding blocks that are not opaque, between 0 and 1
+    pub openness: f32,
+}
+
+impl SampledLight {
+    /// Get the ambient occlusion code used by the meshing, between 0 (fully occluded) and 3 (not occluded).
+    /// A position on a flat surface always has half of its surrounding blocks opaque, so it is not considered occluded.
+    pub fn occlusion_code(&self) -> u32 {
+        ((self.openness * 2.0).min(1.0) * 3.0).round() as u32
+    }
+
+    /// Get the rounded light level
+    pub fn light_level(&self) -> u32 {
+        self.light.round().clamp(0.0, 15.0) as u32
+    }
+
+    /// Pack the light, the ambient occlusion and a face into the `occl_and_face` format of the chunk vertices
+    pub fn pack(&self, face: u32) -> u32 {
+        face + (self.occlusion_code() << 3) + (self.light_level() << 5)
+    }
+
+    /// Get the brightness factor, computed like in the world shaders
+    pub fn brightness(&self) -> f32 {
+        let light_factor = 0.8f32.powf(15.0 - self.light);
+        let occlusion_factor = match self.occlusion_code() {
+            3 => 1.0,
+            2 => 0.8,
+            1 => 0.6,
+            _ => 0.4,
+        };
+        light_factor * occlusion_factor
+    }
+}
+
+/// Sample the light and the ambient occlusion at some position
+pub fn sample_light<LS: LightSource>(source: &LS, pos: [f64; 3]) -> SampledLight {
+    // The 8 block centers around the position
+    let base = [
+        (pos[0] - 0.5).floor(),
+        (pos[1] - 0.5).floor(),
+        (pos[2] - 0.5).floor(),
+    ];
+    let frac = [pos[0] - 0.5 - base[0], pos[1] - 0.5 - base[1], pos[2] - 0.5 - base[2]];
+    let mut open_weight = 0.0;
+    let mut light_sum = 0.0;
+    for dx in 0..2 {
+        for dy in 0..2 {
+            for dz in 0..2 {
+                let weight = (if dx == 0 { 1.0 - frac[0] } else { frac[0] })
+                    * (if dy == 0 { 1.0 - frac[1] } else { frac[1] })
+                    * (if dz == 0 { 1.0 - frac[2] } else { frac[2] });
+                let (x, y, z) = (base[0] as i64 + dx, base[1] as i64 + dy, base[2] as i64 + dz);
+                if !source.is_opaque(x, y, z) {
+                    open_weight += weight;
+                    light_sum += weight * source.get_light(x, y, z) as f64;
+                }
+            }
+        }
+    }
+    SampledLight {
+        light: if open_weight > 1e-9 { (light_sum / open_weight) as f32 } else { 0.0 },
+        openness: open_weight as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opaque below y = 0, with the light increasing with x
+    struct Ground;
+
+    impl LightSource for Ground {
+        fn is_opaque(&self, _x: i64, y: i64, _z: i64) -> bool {
+            y < 0
+        }
+
+        fn get_light(&self, x: i64, _y: i64, _z: i64) -> u8 {
+            x.clamp(0, 15) as u8
+        }
+    }
+
+    #[test]
+    fn light_is_interpolated() {
+        let sampled = sample_light(&Ground, [4.5, 3.5, 0.5]);
+        assert!((sampled.light - 4.0).abs() < 1e-6);
+        let sampled = sample_light(&Ground, [5.0, 3.5, 0.5]);
+        assert!((sampled.light - 4.5).abs() < 1e-6);
+        assert_eq!(sampled.occlusion_code(), 3);
+    }
+
+    #[test]
+    fn flat_surfaces_are_not_occluded() {
+        let sampled = sample_light(&Ground, [2.0, 0.0, 2.0]);
+        assert!((sampled.openness - 0.5).abs() < 1e-6);
+        assert_eq!(sampled.occlusion_code(), 3);
+        let sampled = sample_light(&Ground, [2.0, -0.5, 2.0]);
+        assert_eq!(sampled.occlusion_code(), 0);
+    }
+}
@@ -1,9 +1,13 @@
 //! Meshing code
 use super::ChunkVertex;
+use crate::block_entity_textures::TextureOverride;
+use super::light_sampler::{sample_light, LightSource};
+use crate::settings::Lighting;
+use std::collections::HashMap;
 use std::sync::Arc;
 use history_survival_common::world::LightChunk;
 use history_survival_common::{
-    block::BlockMesh,
+    block::{BlockId, BlockMesh},
     collections::zero_initialized_vec,
     world::{Chunk, CHUNK_SIZE},
 };
@@ -19,6 +23,8 @@ pub struct Quad {
     v4: u32,
     // i = 1 j = 1 => (y, z) = (1, 1)
     block_id: u16,
+    /// 1 + the index of the texture override of the block, or 0 if the block uses its usual texture
+    texture_override: u16,
 }
 
 impl Quad {
@@ -27,6 +33,9 @@ impl Quad {
     }
 }
 
+/// The page of the atlas with the texture is stored after the face, the occlusion and the light of the vertices
+const TEXTURE_PAGE_SHIFT: u32 = 9;
+
 const D: [[i32; 3]; 6] = [
     [1, 0, 0],
     [-1, 0, 0],
@@ -49,6 +58,30 @@ fn ambiant_occl(corners: u32, edge: u32) -> u32 {
     }
 }
 
+/// The light of a corner of a face with smooth lighting: the average light of the transparent blocks among the 4 blocks
+/// in front of the face that touch the corner. The block in the diagonal is hidden if both blocks on the edges are opaque.
+///
+/// `lights` and `opaque` are the 3x3 blocks in front of the face, and `(di, dj)` is the block in the diagonal.
+fn smooth_light(lights: &[[u8; 3]; 3], opaque: &[[bool; 3]; 3], di: usize, dj: usize) -> u32 {
+    // The block right in front of the face is always transparent since the face is visible
+    let mut sum = lights[1][1] as u32;
+    let mut count = 1;
+    if !opaque[di][1] {
+        sum += lights[di][1] as u32;
+        count += 1;
+    }
+    if !opaque[1][dj] {
+        sum += lights[1][dj] as u32;
+        count += 1;
+    }
+    let hidden = opaque[di][dj] || (opaque[di][1] && opaque[1][dj]);
+    if !hidden {
+        sum += lights[di][dj] as u32;
+        count += 1;
+    }
+    (sum + count / 2) / count
+}
+
 /// The chunk-specific data that is needed to mesh it.
 pub struct ChunkMeshData {
     /// The chunk to mesh
@@ -59,29 +92,69 @@ pub struct ChunkMeshData {
     pub light_chunk: Arc<LightChunk>,
     /// The light chunks that are adjacent to the current light chunk
     pub all_light_chunks: [Option<Arc<LightChunk>>; 27],
+    /// The textures that replace the texture of some blocks, for example the banners on all their faces
+    /// and the paintings on their front face
+    pub texture_overrides: Vec<TextureOverride>,
+}
+
+/// The opacity and the light levels of a chunk and of the blocks around it, as gathered for the meshing.
+/// Positions are relative to the chunk, and go from -1 to `CHUNK_SIZE` included.
+struct MeshingLightSource<'a> {
+    chunk_mask: &'a [bool],
+    light_levels: &'a [u8],
+}
+
+impl<'a> MeshingLightSource<'a> {
+    fn index(x: i64, y: i64, z: i64) -> Option<usize> {
+        const N_SIZE: i64 = CHUNK_SIZE as i64 + 2;
+        let (x, y, z) = (x + 1, y + 1, z + 1);
+        if x < 0 || y < 0 || z < 0 || x >= N_SIZE || y >= N_SIZE || z >= N_SIZE {
+            None
+        } else {
+            Some((x * N_SIZE * N_SIZE + y * N_SIZE + z) as usize)
+        }
+    }
+}
+
+impl<'a> LightSource for MeshingLightSource<'a> {
+    fn is_opaque(&self, x: i64, y: i64, z: i64) -> bool {
+        Self::index(x, y, z).map(|i| self.chunk_mask[i]).unwrap_or(false)
+    }
+
+    fn get_light(&self, x: i64, y: i64, z: i64) -> u8 {
+        Self::index(x, y, z).map(|i| self.light_levels[i]).unwrap_or(15)
+    }
 }
 
 /// Greedy meshing : compressed adjacent quads, return the number of uncompressed and compressed quads
 ///
+/// The faces of the water block are returned separately from the other faces, so that they can be drawn with
+/// the water pipeline: the result is the vertices and indices of the other faces, then those of the water faces.
+///
 /// `quads`: Buffer that is reused every time.
+/// `greedy`: Whether the adjacent quads are merged, otherwise every visible face gets its own quad.
+/// `lighting`: Whether the light of the faces is the same at their 4 corners or smoothed across the faces.
 pub fn greedy_meshing(
     chunk_data: ChunkMeshData,
     meshes: &Vec<BlockMesh>,
+    water_block: Option<BlockId>,
     quads: &mut Vec<Quad>,
-) -> (Vec<ChunkVertex>, Vec<u32>, u32, u32) {
+    greedy: bool,
+    lighting: Lighting,
+) -> (Vec<ChunkVertex>, Vec<u32>, Vec<ChunkVertex>, Vec<u32>, u32, u32) {
     let chunk_pos = chunk_data.chunk.pos;
     let offset_x = chunk_pos.px as f32 * CHUNK_SIZE as f32;
     let offset_y = chunk_pos.py as f32 * CHUNK_SIZE as f32;
     let offset_z = chunk_pos.pz as f32 * CHUNK_SIZE as f32;
 
     let mut res_vertex: Vec<ChunkVertex> = Vec::new();
-    let mut res_index: Vec<usize> = Vec::new();
+    let mut res_index: Vec<u32> = Vec::new();
+    let mut res_water_vertex: Vec<ChunkVertex> = Vec::new();
+    let mut res_water_index: Vec<u32> = Vec::new();
 
     let mut tot_quad = 0;
     let mut act_quad = 0;
 
-    let mut n_of_different_vertex = 0;
-
     const N_SIZE: usize = (CHUNK_SIZE + 2) as usize;
     let mut chunk_mask = [false; N_SIZE * N_SIZE * N_SIZE];
     let mut light_levels = [15; N_SIZE * N_SIZE * N_SIZE];
@@ -214,6 +287,19 @@ pub fn greedy_meshing(
 
     let mut to_mesh_faces = [0, 0, 0, 0, 0, 0];
 
+    let texture_override_indices = chunk_data
+        .texture_overrides
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (pos, face, _))| {
+            let faces = match face {
+                Some(face) => *face..*face + 1,
+                None => 0..6,
+            };
+            faces.map(move |face| ((*pos, face), index as u16 + 1))
+        })
+        .collect::<HashMap<_, _>>();
+
     for s in 0..6 {
         let mut opaque_blocks_count_pass = opaque_blocks_count;
         // each direction
@@ -228,6 +314,8 @@ pub fn greedy_meshing(
                             if !*chunk_mask.get_unchecked(ind(i + 1 + D[s][0], j + 1 + D[s][1], k + 1 + D[s][2])) {
                                 let mut coins = [0; 4];
                                 let mut edge = [0; 4];
+                                let mut lights = [[0; 3]; 3];
+                                let mut opaque = [[false; 3]; 3];
 
                                 for i2 in -1..=1 {
                                     for j2 in -1..=1 {
@@ -238,7 +326,10 @@ pub fn greedy_meshing(
                                         let dz =
                                             1 + D[s][2] + D_DELTA1[s][2] * i2 + D_DELTA2[s][2] * j2;
 
-                                        if *chunk_mask.get_unchecked(ind(i + dx, j + dy, k + dz)) {
+                                        let (li, lj) = ((i2 + 1) as usize, (j2 + 1) as usize);
+                                        lights[li][lj] = *light_levels.get_unchecked(ind(i + dx, j + dy, k + dz));
+                                        opaque[li][lj] = *chunk_mask.get_unchecked(ind(i + dx, j + dy, k + dz));
+                                        if opaque[li][lj] {
                                             match (i2, j2) {
                                                 (-1, -1) => {
                                                     coins[0] += 1;
@@ -274,24 +365,39 @@ pub fn greedy_meshing(
                                     }
                                 }
 
-                                let light_level = *light_levels
-                                    .get_unchecked(ind(i + 1 + D[s][0], j + 1 + D[s][1], k + 1 + D[s][2]));
+                                let corner_lights = match lighting {
+                                    Lighting::Flat => [lights[1][1] as u32; 4],
+                                    Lighting::Smooth => [
+                                        smooth_light(&lights, &opaque, 0, 0),
+                                        smooth_light(&lights, &opaque, 0, 2),
+                                        smooth_light(&lights, &opaque, 2, 0),
+                                        smooth_light(&lights, &opaque, 2, 2),
+                                    ],
+                                };
                                 let quad = Quad {
                                     v1: (s as u32)
                                         + (ambiant_occl(coins[0], edge[0]) << 3)
-                                        + ((light_level as u32) << 5),
+                                        + (corner_lights[0] << 5),
                                     v2: (s as u32)
                                         + (ambiant_occl(coins[1], edge[1]) << 3)
-                                        + ((light_level as u32) << 5),
+                                        + (corner_lights[1] << 5),
                                     v3: (s as u32)
                                         + (ambiant_occl(coins[2], edge[2]) << 3)
-                                        + ((light_level as u32) << 5),
+                                        + (corner_lights[2] << 5),
                                     v4: (s as u32)
                                         + (ambiant_occl(coins[3], edge[3]) << 3)
-                                        + ((light_level as u32) << 5),
+                                        + (corner_lights[3] << 5),
                                     block_id: chunk_data
                                         .chunk
                                         .get_block_at((i as u32, j as u32, k as u32)),
+                                    texture_override: if texture_override_indices.is_empty() {
+                                        0
+                                    } else {
+                                        texture_override_indices
+                                            .get(&((i as u32, j as u32, k as u32), s))
+                                            .cloned()
+                                            .unwrap_or(0)
+                                    },
                                 };
                                 *quads.get_unchecked_mut(ind_mesh(s, i, j, k)) = quad;
                                 *to_mesh.get_unchecked_mut(ind_mesh(s, i, j, k)) = true;
@@ -381,7 +487,9 @@ pub fn greedy_meshing(
                             let mut j_end = j + 1; // + y + x + x
                             let mut k_end = k + 1; // +z + z + x
 
-                            if current_quad.v1 == current_quad.v3 && current_quad.v2 == current_quad.v4
+                            if !greedy {
+                                // one quad per face
+                            } else if current_quad.v1 == current_quad.v3 && current_quad.v2 == current_quad.v4
                             {
                                 // meshing along j
                                 let mut j2 = j + 1;
@@ -396,6 +504,7 @@ pub fn greedy_meshing(
                                             && next_quad.v1 == next_quad.v3
                                             && next_quad.v2 == next_quad.v4
                                             && current_quad.block_id == next_quad.block_id
+                                            && current_quad.texture_override == next_quad.texture_override
                                         {
                                             *to_mesh.get_unchecked_mut(ind_mesh(s, pos.0, pos.1, pos.2)) = false;
                                             j2 += 1;
@@ -416,7 +525,8 @@ pub fn greedy_meshing(
                                             if !(*to_mesh.get_unchecked(ind_mesh(s, pos.0, pos.1, pos.2))
                                                 && next_quad.is_same()
                                                 && next_quad.v1 == current_quad.v1
-                                                && next_quad.block_id == current_quad.block_id)
+                                                && next_quad.block_id == current_quad.block_id
+                                                && next_quad.texture_override == current_quad.texture_override)
                                             {
                                                 break 'wloop;
                                             }
@@ -445,6 +555,7 @@ pub fn greedy_meshing(
                                             && next_quad.v1 == next_quad.v2
                                             && next_quad.v3 == next_quad.v4
                                             && next_quad.block_id == current_quad.block_id
+                                            && next_quad.texture_override == current_quad.texture_override
                                         {
                                             *to_mesh.get_unchecked_mut(ind_mesh(s, pos.0, pos.1, pos.2)) = false;
                                             k2 += 1;
@@ -492,7 +603,10 @@ pub fn greedy_meshing(
                             }
 
                             let uv = match meshes[current_quad.block_id as usize] {
-                                BlockMesh::Empty => continue,
+                                BlockMesh::Empty | BlockMesh::Model { .. } => continue,
+                                BlockMesh::FullCube { .. } if current_quad.texture_override > 0 => {
+                                    chunk_data.texture_overrides[current_quad.texture_override as usize - 1].2
+                                }
                                 BlockMesh::FullCube { textures } => textures[s],
                             };
 
@@ -522,15 +636,21 @@ pub fn greedy_meshing(
                                 ],
                             ];
                             let texture_max_uv = [uv.width * uv_factors[0], uv.height * uv_factors[1]];
+                            let (vertices, indices) = if Some(current_quad.block_id) == water_block {
+                                (&mut res_water_vertex, &mut res_water_index)
+                            } else {
+                                (&mut res_vertex, &mut res_index)
+                            };
+                            let n_of_different_vertex = vertices.len() as u32;
 
                             for kk in 0..4 {
-                                res_vertex.push(ChunkVertex {
+                                vertices.push(ChunkVertex {
                                     pos: [px_[kk] + offset_x, py_[kk] + offset_y, pz_[kk] + offset_z],
                                     texture_top_left,
                                     texture_uv: uvs[kk],
                                     texture_max_uv,
                                     texture_size,
-                                    occl_and_face: v[kk],
+                                    occl_and_face: v[kk] + (uv.page << TEXTURE_PAGE_SHIFT),
                                 });
                             }
 
@@ -541,12 +661,11 @@ pub fn greedy_meshing(
 
                             for kk in 0..6 {
                                 if a00 + a11 < a01 + a10 {
-                                    res_index.push(n_of_different_vertex + order1[s][kk]);
+                                    indices.push(n_of_different_vertex + order1[s][kk] as u32);
                                 } else {
-                                    res_index.push(n_of_different_vertex + order2[s][kk]);
+                                    indices.push(n_of_different_vertex + order2[s][kk] as u32);
                                 }
                             }
-                            n_of_different_vertex += 4;
                             act_quad += 1;
                         } else if *to_mesh_faces.get_unchecked(s) == 0 {
                             break 'quads;
@@ -557,6 +676,60 @@ pub fn greedy_meshing(
         }
     }
 
-    let res_index: Vec<u32> = res_index.iter().map(|x| *x as u32).collect();
-    (res_vertex, res_index, tot_quad, act_quad)
+    // The blocks with a model get their faces one by one, with the light sampled at their corners
+    for i in 0..(CHUNK_SIZE as i32) {
+        for j in 0..(CHUNK_SIZE as i32) {
+            for k in 0..(CHUNK_SIZE as i32) {
+                let block_id = chunk_data.chunk.get_block_at((i as u32, j as u32, k as u32));
+                let quads = match &meshes[block_id as usize] {
+                    BlockMesh::Model { quads } => quads,
+                    _ => continue,
+                };
+                // The server lights the blocks with a model like the opaque blocks, so they take the light around them
+                let around_light = D
+                    .iter()
+                    .filter(|d| !chunk_mask[ind(i + 1 + d[0], j + 1 + d[1], k + 1 + d[2])])
+                    .map(|d| light_levels[ind(i + 1 + d[0], j + 1 + d[1], k + 1 + d[2])])
+                    .max()
+                    .unwrap_or(0);
+                let block_light = &mut light_levels[ind(i + 1, j + 1, k + 1)];
+                *block_light = (*block_light).max(around_light);
+                let light_source = MeshingLightSource {
+                    chunk_mask: &chunk_mask,
+                    light_levels: &light_levels,
+                };
+                let center = [i as f64 + 0.5, j as f64 + 0.5, k as f64 + 0.5];
+                for quad in quads {
+                    if let Some(cull_face) = quad.cull_face {
+                        let d = D[cull_face];
+                        if chunk_mask[ind(i + 1 + d[0], j + 1 + d[1], k + 1 + d[2])] {
+                            continue;
+                        }
+                    }
+                    tot_quad += 1;
+                    act_quad += 1;
+                    let uv = quad.texture;
+                    let first_vertex = res_vertex.len() as u32;
+                    for (corner, corner_uv) in quad.corners.iter().zip(quad.uvs.iter()) {
+                        let pos = [i as f32 + corner[0], j as f32 + corner[1], k as f32 + corner[2]];
+                        let light = match lighting {
+                            Lighting::Flat => sample_light(&light_source, center),
+                            Lighting::Smooth => sample_light(&light_source, [pos[0] as f64, pos[1] as f64, pos[2] as f64]),
+                        };
+                        res_vertex.push(ChunkVertex {
+                            pos: [pos[0] + offset_x, pos[1] + offset_y, pos[2] + offset_z],
+                            texture_top_left: [uv.x, uv.y],
+                            texture_uv: [corner_uv[0] * uv.width, corner_uv[1] * uv.height],
+                            texture_max_uv: [uv.width, uv.height],
+                            texture_size: [uv.width, uv.height],
+                            occl_and_face: light.pack(quad.face as u32) + (uv.page << TEXTURE_PAGE_SHIFT),
+                        });
+                    }
+                    res_index.extend([0, 1, 2, 0, 2, 3].iter().map(|index| first_vertex + index));
+                }
+            }
+        }
+    }
+
+    (res_vertex, res_index, res_water_vertex, res_water_index, tot_quad, act_quad)
 }
@@ -1,31 +1,97 @@
 //! Meshing worker, allowing meshing to be performed in a separate thread
 use super::meshing::{greedy_meshing, ChunkMeshData};
+use super::visibility::ChunkVisibility;
 use crate::render::world::ChunkVertex;
-use history_survival_common::block::BlockMesh;
+use crate::settings::Lighting;
+use history_survival_common::block::{BlockId, BlockMesh};
+use history_survival_common::debug::send_debug_info;
 use history_survival_common::world::ChunkPos;
 use history_survival_common::worker::{WorkerState, Worker};
+use nalgebra::Vector3;
+use std::sync::{Arc, Mutex};
 
-pub type ChunkMesh = (ChunkPos, Vec<ChunkVertex>, Vec<u32>);
+/// The chunks behind the camera are meshed as late as the chunks in front of it that are this many times farther
+const BEHIND_CAMERA_FACTOR: u64 = 2;
+
+/// The vertices and indices of the faces of a chunk, then those of its water faces, and the faces of the chunk
+/// that can be seen from each other
+pub type ChunkMesh = (ChunkPos, Vec<ChunkVertex>, Vec<u32>, Vec<ChunkVertex>, Vec<u32>, ChunkVisibility);
 pub type MeshingWorker = Worker<ChunkMeshData, ChunkMesh, MeshingState>;
+/// The focus of the meshing worker, shared with the client that updates it
+pub type SharedMeshingFocus = Arc<Mutex<Option<MeshingFocus>>>;
+
+/// Where the camera is and where it looks, so that the chunks that the player sees are meshed first
+#[derive(Debug, Clone, Copy)]
+pub struct MeshingFocus {
+    pub camera_chunk: ChunkPos,
+    pub look_direction: Vector3<f64>,
+}
+
+impl MeshingFocus {
+    /// The priority of the meshing of a chunk, lowest first: the closest chunks first, and the chunks in front
+    /// of the camera before the ones behind it. The chunks around the camera come first in every direction.
+    pub fn priority(&self, pos: ChunkPos) -> u64 {
+        let (dx, dy, dz) = (pos.px - self.camera_chunk.px, pos.py - self.camera_chunk.py, pos.pz - self.camera_chunk.pz);
+        let squared_distance = (dx * dx + dy * dy + dz * dz) as u64;
+        let facing = Vector3::new(dx as f64, dy as f64, dz as f64).dot(&self.look_direction);
+        if squared_distance > 3 && facing < 0.0 {
+            squared_distance * BEHIND_CAMERA_FACTOR * BEHIND_CAMERA_FACTOR
+        } else {
+            squared_distance
+        }
+    }
+}
 
-pub fn start_meshing_worker(block_meshes: Vec<BlockMesh>) -> MeshingWorker {
-    MeshingWorker::new(
-        MeshingState::new(block_meshes),
+/// Start the meshing worker, returning it with the focus that orders its chunks and the lighting of the next meshes
+pub fn start_meshing_worker(
+    block_meshes: Vec<BlockMesh>,
+    water_block: Option<BlockId>,
+    greedy_meshing: bool,
+    lighting: Lighting,
+) -> (MeshingWorker, SharedMeshingFocus, Arc<Mutex<Lighting>>) {
+    let focus = Arc::new(Mutex::new(None));
+    let lighting = Arc::new(Mutex::new(lighting));
+    let worker = MeshingWorker::new(
+        MeshingState::new(block_meshes, water_block, greedy_meshing, lighting.clone(), focus.clone()),
         WORKER_CHANNEL_SIZE,
         "Meshing".to_owned(),
-    )
+    );
+    (worker, focus, lighting)
 }
 
 pub struct MeshingState {
     block_meshes: Vec<BlockMesh>,
+    water_block: Option<BlockId>,
     quads_reuse: Vec<super::meshing::Quad>,
+    /// Whether the adjacent faces are merged into larger quads
+    greedy_meshing: bool,
+    /// Updated by the client when the lighting setting changes
+    lighting: Arc<Mutex<Lighting>>,
+    /// Total number of visible faces that were meshed
+    total_faces: u64,
+    /// Total number of quads that the faces were meshed into
+    total_quads: u64,
+    /// Updated by the client as the camera moves
+    focus: Arc<Mutex<Option<MeshingFocus>>>,
 }
 
 impl MeshingState {
-    pub(self) fn new(block_meshes: Vec<BlockMesh>) -> Self {
+    pub(self) fn new(
+        block_meshes: Vec<BlockMesh>,
+        water_block: Option<BlockId>,
+        greedy_meshing: bool,
+        lighting: Arc<Mutex<Lighting>>,
+        focus: Arc<Mutex<Option<MeshingFocus>>>,
+    ) -> Self {
         Self {
             block_meshes,
+            water_block,
             quads_reuse: Vec::new(),
+            greedy_meshing,
+            lighting,
+            total_faces: 0,
+            total_quads: 0,
+            focus,
         }
     }
 }
@@ -33,9 +99,53 @@ impl MeshingState {
 impl WorkerState<ChunkMeshData, ChunkMesh> for MeshingState {
     fn compute(&mut self, input: ChunkMeshData) -> ChunkMesh {
         let pos = input.chunk.pos;
-        let (vertices, indices, _, _) = greedy_meshing(input, &self.block_meshes, &mut self.quads_reuse);
-        (pos, vertices, indices)
+        let visibility =
+            ChunkVisibility::compute(|x, y, z| self.block_meshes[input.chunk.get_block_at((x, y, z)) as usize].is_opaque());
+        let lighting = *self.lighting.lock().unwrap();
+        let (vertices, indices, water_vertices, water_indices, faces, quads) =
+            greedy_meshing(input, &self.block_meshes, self.water_block, &mut self.quads_reuse, self.greedy_meshing, lighting);
+        self.total_faces += faces as u64;
+        self.total_quads += quads as u64;
+        // Every quad has 4 vertices
+        send_debug_info(
+            "Chunks",
+            "greedymeshing",
+            format!(
+                "Greedy meshing {}: {} vertices instead of {} ({:.1}%)",
+                if self.greedy_meshing { "on" } else { "off" },
+                4 * self.total_quads,
+                4 * self.total_faces,
+                100.0 * self.total_quads as f64 / self.total_faces.max(1) as f64,
+            ),
+        );
+        (pos, vertices, indices, water_vertices, water_indices, visibility)
+    }
+
+    fn priority(&self, input: &ChunkMeshData) -> u64 {
+        match *self.focus.lock().unwrap() {
+            Some(focus) => focus.priority(input.chunk.pos),
+            None => 0,
+        }
     }
 }
 
 static WORKER_CHANNEL_SIZE: usize = 20; // TODO: better size?
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_in_front_of_the_camera_come_first() {
+        let focus = MeshingFocus {
+            camera_chunk: ChunkPos { px: 0, py: 0, pz: 0 },
+            look_direction: Vector3::new(0.0, 0.0, -1.0),
+        };
+        let chunk = |px, pz| ChunkPos { px, py: 0, pz };
+        assert!(focus.priority(chunk(0, -3)) < focus.priority(chunk(0, 3)));
+        assert!(focus.priority(chunk(0, 3)) < focus.priority(chunk(0, -7)));
+        // The chunks next to the camera are meshed first in every direction
+        assert_eq!(focus.priority(chunk(0, 1)), focus.priority(chunk(0, -1)));
+        assert!(focus.priority(chunk(1, 1)) < focus.priority(chunk(0, -2)));
+    }
+}
@@ -1,62 +1,156 @@
 //! World rendering
 
-use super::buffers::MultiBuffer;
+use super::buffers::{BufferFull, MultiBuffer};
 use super::frustum::Frustum;
-use super::init::{create_default_pipeline, load_glsl_shader, ShaderStage};
-use super::{ to_u8_slice, buffer_from_slice };
-use crate::texture::load_image;
+use super::init::{create_default_pipeline, create_transparent_pipeline, load_glsl_shader, try_load_glsl_shader, ShaderStage};
+use super::shader_watcher::ShaderWatcher;
+use super::{ to_u8_slice, buffer_from_slice, PostProcessing };
+use crate::biome_colors::BiomeColors;
+use crate::settings::{AmbientOcclusion, CloudSettings, PostProcessingSettings, TextureFilter, TextureSettings};
+use crate::texture::{load_images, update_image_region, MIPMAP_LEVELS};
 use crate::window::WindowBuffers;
 use image::{ImageBuffer, Rgba};
 use nalgebra::{Matrix4, Similarity3, Translation3, UnitQuaternion, Vector3};
-use history_survival_common::data::vox::VoxelModel;
+use history_survival_common::block::breaking::BREAK_STAGES;
+use history_survival_common::data::{pad_texture, vox::VoxelModel, TextureRect, MAX_TEXTURE_SIZE, TEXTURE_PADDING};
 use history_survival_common::debug::send_debug_info;
+use history_survival_common::physics::aabb::AABB;
 use history_survival_common::registry::Registry;
+use history_survival_common::time::WorldTime;
 use history_survival_common::world::{BlockPos, ChunkPos};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Instant;
 
+mod debug;
+mod light_sampler;
 mod meshing;
 mod meshing_worker;
 mod model;
+mod shadows;
 mod skybox;
+mod ssao;
+mod visibility;
+
+/// Size of the sky uniform: the sun direction and the sky light intensity, the sky color, the fog color and distance,
+/// the water color, the camera position and the animation time, as 6 vec4
+const SKY_UNIFORM_SIZE: u64 = 96;
+/// The animation time wraps around after this many seconds, so that it stays precise as a `f32`.
+/// The periods of the water animation divide it, so that the wrapping can't be seen.
+const ANIMATION_TIME_PERIOD: f32 = 3600.0;
+/// Distance from the camera to the sun and the moon, closer than the skybox
+const CELESTIAL_DISTANCE: f64 = 800.0;
+/// Half of the size of the quads of the sun and of the moon
+const SUN_SIZE: f64 = 60.0;
+const MOON_SIZE: f64 = 45.0;
+const SUN_COLOR: [f32; 3] = [1.0, 0.9, 0.6];
+const MOON_COLOR: [f32; 3] = [0.85, 0.85, 0.95];
+const STAR_COLOR: [f32; 3] = [0.9, 0.92, 1.0];
+/// Half of the size of the quad of the clouds, which is centered on the camera
+const CLOUD_RADIUS: f64 = 1000.0;
+/// How far the cracks are drawn outside of the faces of the broken blocks, so that they don't flicker
+const CRACK_OFFSET: f32 = 0.002;
+/// The buffers of the chunk meshes are compacted while more than this fraction of them is lost in holes
+const COMPACTION_THRESHOLD: f64 = 0.25;
+/// The folder of the shaders, watched when they are hot reloaded
+const SHADER_FOLDER: &str = "assets/shaders";
+/// How many bytes of chunk meshes every buffer moves per frame while it is compacted
+const COMPACTION_BYTES_PER_FRAME: usize = 1 << 20;
+
+pub use self::debug::DebugRenderMode;
+pub use self::light_sampler::{sample_light, LightSource};
 pub use self::model::Model;
 pub use self::meshing::ChunkMeshData;
-pub use self::meshing_worker::{ChunkMesh, MeshingWorker, start_meshing_worker};
+pub use self::meshing_worker::{ChunkMesh, MeshingFocus, MeshingWorker, start_meshing_worker};
+use self::debug::{chunk_border_lines, hitbox_lines, quad_outline_indices, DebugLineVertex, DEBUG_LINE_VERTEX_ATTRIBUTES};
+use self::shadows::{shadow_uniform, ShadowCascade, ShadowMaps, SHADOW_CASCADE_RADII, SHADOW_UNIFORM_SIZE};
+use self::ssao::Ssao;
+use self::visibility::{visible_chunks, ChunkVisibility};
 
 /// All the state necessary to render the world.
 pub struct WorldRenderer {
+    // Texture atlas, kept to update the dynamic textures
+    texture_atlas: wgpu::Texture,
     // View-projection matrix
     uniform_view_proj: wgpu::Buffer,
     // Model matrix
     uniform_model: wgpu::Buffer,
+    // Sun direction and sky light intensity, and the colors of the biome around the camera
+    uniform_sky: wgpu::Buffer,
     // Chunk rendering
     chunk_index_buffers: MultiBuffer<ChunkPos, u32>,
     chunk_vertex_buffers: MultiBuffer<ChunkPos, ChunkVertex>,
-    chunk_pipeline: wgpu::RenderPipeline,
     chunk_bind_group: wgpu::BindGroup,
+    // Water rendering, with the chunk bind group
+    water_index_buffers: MultiBuffer<ChunkPos, u32>,
+    water_vertex_buffers: MultiBuffer<ChunkPos, ChunkVertex>,
+    // The faces of the meshed chunks that can be seen from each other, for the occlusion culling
+    chunk_visibility: HashMap<ChunkPos, ChunkVisibility>,
+    // The start of the animations
+    start_time: Instant,
     // Skybox rendering
     skybox_index_buffer: wgpu::Buffer,
     skybox_vertex_buffer: wgpu::Buffer,
-    skybox_pipeline: wgpu::RenderPipeline,
+    // Sun and moon rendering
+    celestial_vertex_buffer: wgpu::Buffer,
+    // Star rendering, with the number of vertices of the stars
+    star_vertex_buffer: wgpu::Buffer,
+    star_vertex_count: u32,
+    // Cloud rendering, on the quad of the sun and of the moon
+    clouds: CloudSettings,
     // View-proj and model bind group
     vpm_bind_group: wgpu::BindGroup,
     // Targeted block rendering
     target_vertex_buffer: wgpu::Buffer,
-    target_pipeline: wgpu::RenderPipeline,
+    // Crack rendering, on the cube of the skybox
     // Model rendering
     model_index_buffers: MultiBuffer<u32, u32>,
     model_vertex_buffers: MultiBuffer<u32, RgbVertex>,
-    model_pipeline: wgpu::RenderPipeline,
+    // Held item rendering, with the projection matrix alone so that the item follows the camera
+    uniform_hand_proj: wgpu::Buffer,
+    hand_bind_group: wgpu::BindGroup,
+    // Sun shadows, drawn from the chunk meshes before the chunks when they are enabled
+    shadow_maps: ShadowMaps,
+    // Screen-space ambient occlusion, drawn over the chunks unless it is disabled
+    ssao: Option<Ssao>,
+    // The effects of the screen, which draw the world into the frame buffer
+    post_processing: PostProcessing,
+    // The line indices of the wireframe of the chunks, with the number of quads that they outline
+    wireframe_indices: Option<(usize, wgpu::Buffer)>,
+    // Whether the chunks are drawn with a single indirect call per pass instead of one call per chunk
+    multi_draw_indirect: bool,
+    // Watches the shaders to rebuild the pipelines when they change, if hot reloading is enabled
+    shader_watcher: Option<ShaderWatcher>,
+    // The pipelines that draw into the window buffers, with the number of samples of the buffers
+    pipelines: WorldPipelines,
+    sample_count: u32,
+    chunk_bind_group_layout: wgpu::BindGroupLayout,
+    vpm_bind_group_layout: wgpu::BindGroupLayout,
+    ambient_occlusion: AmbientOcclusion,
 }
 
 impl WorldRenderer {
+    /// `chunk_buffer_budget` is the maximum size of each of the buffers of the chunk meshes, in bytes
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
-        texture_atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
+        texture_atlas: &[ImageBuffer<Rgba<u8>, Vec<u8>>],
         models: &Registry<VoxelModel>,
+        chunk_buffer_budget: u64,
+        clouds: CloudSettings,
+        textures: TextureSettings,
+        shadows: bool,
+        ambient_occlusion: AmbientOcclusion,
+        post_processing: PostProcessingSettings,
+        sample_count: u32,
     ) -> Self {
         // Load texture atlas
-        let texture_atlas = load_image(device, encoder, texture_atlas);
-        let texture_atlas_view = texture_atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture_atlas = load_images(device, encoder, texture_atlas);
+        let texture_atlas_view = texture_atlas.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
 
         // Create uniform buffers
         let uniform_view_proj = device.create_buffer(&wgpu::BufferDescriptor {
@@ -65,12 +159,23 @@ impl WorldRenderer {
             size: 64,
             usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
         });
+        // The model matrix, followed by the brightness of the model
         let uniform_model = device.create_buffer(&wgpu::BufferDescriptor {
             mapped_at_creation: false,
             label: None,
-            size: 64,
+            size: 80,
             usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
         });
+        // The direction of the sun, followed by the intensity of the sky light, and the colors of the biome
+        let uniform_sky = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: SKY_UNIFORM_SIZE,
+            usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
+        });
+
+        // Create the shadow maps
+        let shadow_maps = ShadowMaps::new(device, shadows);
 
         // Create uniform bind group
         let chunk_bind_group_layout = device.create_bind_group_layout(&CHUNK_BIND_GROUP_LAYOUT);
@@ -79,29 +184,16 @@ impl WorldRenderer {
             &chunk_bind_group_layout,
             &texture_atlas_view,
             &uniform_view_proj,
+            &uniform_sky,
+            textures,
+            &shadow_maps,
         );
-
-        // Create chunk pipeline
-        let chunk_pipeline = {
-            let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/world.vert");
-            let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
-            let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/world.frag");
-            let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
-
-            create_default_pipeline(
-                device,
-                &chunk_bind_group_layout,
-                vertex_shader,
-                fragment_shader,
-                wgpu::PrimitiveTopology::TriangleList,
-                wgpu::VertexBufferDescriptor {
-                    stride: std::mem::size_of::<ChunkVertex>() as u64,
-                    step_mode: wgpu::InputStepMode::Vertex,
-                    attributes: &CHUNK_VERTEX_ATTRIBUTES,
-                },
-                true,
-            )
-        };
+        let uniform_hand_proj = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: 64,
+            usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
+        });
 
         // Create skybox vertex and index buffers
         let (skybox_vertex_buffer, skybox_index_buffer) = self::skybox::create_skybox(device);
@@ -113,79 +205,27 @@ impl WorldRenderer {
             &vpm_bind_group_layout,
             &uniform_view_proj,
             &uniform_model,
+            &uniform_sky,
+        );
+        let hand_bind_group = create_vpm_bind_group(
+            device,
+            &vpm_bind_group_layout,
+            &uniform_hand_proj,
+            &uniform_model,
+            &uniform_sky,
         );
 
-        // Create skybox pipeline
-        let skybox_pipeline = {
-            let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/skybox.vert");
-            let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
-            let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/skybox.frag");
-            let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
-
-            create_default_pipeline(
-                device,
-                &vpm_bind_group_layout,
-                vertex_shader,
-                fragment_shader,
-                wgpu::PrimitiveTopology::TriangleList,
-                wgpu::VertexBufferDescriptor {
-                    stride: std::mem::size_of::<SkyboxVertex>() as u64,
-                    step_mode: wgpu::InputStepMode::Vertex,
-                    attributes: &SKYBOX_VERTEX_ATTRIBUTES,
-                },
-                false,
-            )
-        };
+        // Create sun and moon buffer
+        let celestial_vertex_buffer = self::skybox::create_celestial_quad(device);
+        let (star_vertex_buffer, star_vertex_count) = self::skybox::create_star_field(device);
 
-        // Create target buffer and pipeline
+        // Create target buffer
         let target_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             mapped_at_creation: false,
             label: None,
             size: 8 * std::mem::size_of::<SkyboxVertex>() as u64,
             usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
         });
-        let target_pipeline = {
-            let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/target.vert");
-            let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
-            let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/target.frag");
-            let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
-
-            create_default_pipeline(
-                device,
-                &vpm_bind_group_layout,
-                vertex_shader,
-                fragment_shader,
-                wgpu::PrimitiveTopology::LineList,
-                wgpu::VertexBufferDescriptor {
-                    stride: std::mem::size_of::<SkyboxVertex>() as u64,
-                    step_mode: wgpu::InputStepMode::Vertex,
-                    attributes: &SKYBOX_VERTEX_ATTRIBUTES,
-                },
-                false,
-            )
-        };
-
-        // Create model pipeline
-        let model_pipeline = {
-            let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/model.vert");
-            let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
-            let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/model.frag");
-            let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
-
-            create_default_pipeline(
-                device,
-                &vpm_bind_group_layout,
-                vertex_shader,
-                fragment_shader,
-                wgpu::PrimitiveTopology::TriangleList,
-                wgpu::VertexBufferDescriptor {
-                    stride: std::mem::size_of::<RgbVertex>() as u64,
-                    step_mode: wgpu::InputStepMode::Vertex,
-                    attributes: &RGB_VERTEX_ATTRIBUTES,
-                },
-                true,
-            )
-        };
 
         // Mesh models
         let mut model_index_buffers =
@@ -195,33 +235,61 @@ impl WorldRenderer {
         for mesh_id in 0..models.get_number_of_ids() {
             let (vertices, indices) =
                 self::model::mesh_model(models.get_value_by_id(mesh_id).unwrap());
-            model_index_buffers.update(device, encoder, mesh_id, &indices);
-            model_vertex_buffers.update(device, encoder, mesh_id, &vertices);
+            model_index_buffers.update(device, encoder, mesh_id, &indices).expect("the model buffers have no maximum length");
+            model_vertex_buffers.update(device, encoder, mesh_id, &vertices).expect("the model buffers have no maximum length");
         }
+        let mut chunk_index_buffers = MultiBuffer::with_capacity(device, 1000, wgpu::BufferUsage::INDEX);
+        chunk_index_buffers.set_max_len(chunk_buffer_budget as usize / std::mem::size_of::<u32>());
+        let mut chunk_vertex_buffers = MultiBuffer::with_capacity(device, 1000, wgpu::BufferUsage::VERTEX);
+        chunk_vertex_buffers.set_max_len(chunk_buffer_budget as usize / std::mem::size_of::<ChunkVertex>());
+        let mut water_index_buffers = MultiBuffer::with_capacity(device, 1000, wgpu::BufferUsage::INDEX);
+        water_index_buffers.set_max_len(chunk_buffer_budget as usize / std::mem::size_of::<u32>());
+        let mut water_vertex_buffers = MultiBuffer::with_capacity(device, 1000, wgpu::BufferUsage::VERTEX);
+        water_vertex_buffers.set_max_len(chunk_buffer_budget as usize / std::mem::size_of::<ChunkVertex>());
 
         Self {
+            texture_atlas,
             uniform_view_proj,
             uniform_model,
-            chunk_index_buffers: MultiBuffer::with_capacity(device, 1000, wgpu::BufferUsage::INDEX),
-            chunk_vertex_buffers: MultiBuffer::with_capacity(
-                device,
-                1000,
-                wgpu::BufferUsage::VERTEX,
-            ),
-            chunk_pipeline,
+            uniform_sky,
+            chunk_index_buffers,
+            chunk_vertex_buffers,
             chunk_bind_group,
+            water_index_buffers,
+            water_vertex_buffers,
+            chunk_visibility: HashMap::new(),
+            start_time: Instant::now(),
             skybox_vertex_buffer,
             skybox_index_buffer,
-            skybox_pipeline,
+            celestial_vertex_buffer,
+            star_vertex_buffer,
+            star_vertex_count,
+            clouds,
             vpm_bind_group,
             target_vertex_buffer,
-            target_pipeline,
-            model_pipeline,
             model_index_buffers,
             model_vertex_buffers,
+            uniform_hand_proj,
+            hand_bind_group,
+            shadow_maps,
+            ssao: Ssao::new(device, ambient_occlusion, sample_count),
+            post_processing: PostProcessing::new(device, post_processing, sample_count),
+            wireframe_indices: None,
+            multi_draw_indirect: device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+            shader_watcher: None,
+            pipelines: create_world_pipelines(device, &chunk_bind_group_layout, &vpm_bind_group_layout, sample_count),
+            sample_count,
+            chunk_bind_group_layout,
+            vpm_bind_group_layout,
+            ambient_occlusion,
         }
     }
 
+    /// Draw the world, then draw it into the frame buffer with the post-processing.
+    /// `held_model` is the held item with its brightness, placed relative to the camera.
+    /// `underwater` is whether the camera is in the water.
+    /// `hitboxes` are drawn when `debug_mode` shows them.
+    #[allow(clippy::too_many_arguments)]
     pub fn render(
         &mut self,
         device: &wgpu::Device,
@@ -231,16 +299,39 @@ impl WorldRenderer {
         frustum: &Frustum,
         enable_culling: bool,
         pointed_block: Option<(BlockPos, usize)>,
+        break_stages: &[(BlockPos, u8)],
         models: &[model::Model],
+        model_brightness: &[f32],
+        world_time: WorldTime,
+        biome_colors: BiomeColors,
+        fog_distance: f32,
+        held_model: Option<(&model::Model, f32)>,
+        underwater: bool,
+        debug_mode: DebugRenderMode,
+        hitboxes: &[AABB],
     ) {
+        // The pipelines must match the window buffers, whose number of samples follows the settings,
+        // and the shaders when they are reloaded
+        if buffers.sample_count != self.sample_count || self.shaders_changed() {
+            self.sample_count = buffers.sample_count;
+            self.pipelines = create_world_pipelines(
+                device,
+                &self.chunk_bind_group_layout,
+                &self.vpm_bind_group_layout,
+                self.sample_count,
+            );
+            self.ssao = Ssao::new(device, self.ambient_occlusion, self.sample_count);
+            self.post_processing.set_sample_count(device, self.sample_count);
+        }
+
         //============= RENDER =============//
-        // TODO: what if win_h is 0 ?
+        // The height is 0 while the window is minimized
         let aspect_ratio = {
             let winit::dpi::PhysicalSize {
                 width: win_w,
                 height: win_h,
             } = data.physical_window_size;
-            win_w as f64 / win_h as f64
+            win_w as f64 / win_h.max(1) as f64
         };
 
         let view_mat = frustum.get_view_matrix();
@@ -266,35 +357,139 @@ impl WorldRenderer {
         );
         encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_view_proj, 0, 64);
 
+        // Update the sky
+        let [sun_x, sun_y, sun_z] = world_time.sun_direction();
+        let BiomeColors { water, sky: sky_color, fog } = biome_colors;
+        let camera = frustum.position;
+        let time = self.start_time.elapsed().as_secs_f32() % ANIMATION_TIME_PERIOD;
+        let sky: [f32; 24] = [
+            sun_x as f32, sun_y as f32, sun_z as f32, world_time.sky_light_factor() as f32,
+            sky_color[0], sky_color[1], sky_color[2], 1.0,
+            fog[0], fog[1], fog[2], fog_distance,
+            water[0], water[1], water[2], 1.0,
+            camera.x as f32, camera.y as f32, camera.z as f32, 1.0,
+            time, 0.0, 0.0, 0.0,
+        ];
+        let src_buffer = buffer_from_slice(
+            device,
+            wgpu::BufferUsage::COPY_SRC,
+            to_u8_slice(&sky)
+        );
+        encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_sky, 0, SKY_UNIFORM_SIZE);
+
+        // Draw the depth of the chunks seen from the sun into the shadow maps, while the sun is up
+        let mut cascade_view_projs = Vec::new();
+        if self.shadow_maps.enabled && sun_y > 0.0 {
+            let sun_direction = Vector3::new(sun_x, sun_y, sun_z);
+            let mut count = 0;
+            for (radius, cascade_view) in SHADOW_CASCADE_RADII.iter().zip(self.shadow_maps.cascade_views.iter()) {
+                let cascade = ShadowCascade::new(camera, sun_direction, *radius);
+                let chunks = self.chunk_index_buffers.keys().filter(|chunk_pos| cascade.contains_chunk(*chunk_pos)).collect::<Vec<_>>();
+                count += chunks.len();
+                let draws = ChunkDraws::new(device, &self.chunk_index_buffers, &self.chunk_vertex_buffers, &chunks, false, self.multi_draw_indirect);
+                let view_proj: Matrix4<f32> =
+                    nalgebra::convert(opengl_to_wgpu * cascade.get_view_projection());
+                cascade_view_projs.push(view_proj);
+                let src_buffer = buffer_from_slice(
+                    device,
+                    wgpu::BufferUsage::COPY_SRC,
+                    to_u8_slice(view_proj.as_slice())
+                );
+                encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.shadow_maps.uniform_cascade, 0, 64);
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                        attachment: cascade_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: true,
+                        }),
+                        stencil_ops: None,
+                    }),
+                });
+                rpass.set_pipeline(&self.shadow_maps.pipeline);
+                rpass.set_bind_group(0, &self.shadow_maps.bind_group, &[]);
+                draws.draw(&mut rpass, &self.chunk_index_buffers, &self.chunk_vertex_buffers, None);
+            }
+            send_debug_info("Render", "shadowchunks", format!("{} chunks were drawn into the shadow maps", count));
+        }
+        let src_buffer = buffer_from_slice(
+            device,
+            wgpu::BufferUsage::COPY_SRC,
+            to_u8_slice(&shadow_uniform(&cascade_view_projs))
+        );
+        encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.shadow_maps.uniform, 0, SHADOW_UNIFORM_SIZE);
+
+        // Only the chunks in the frustum that can be seen through the chunks between them and the camera are drawn
+        let camera_chunk = BlockPos::from(frustum.position).containing_chunk_pos();
+        let visible = if enable_culling {
+            let radius = self
+                .chunk_visibility
+                .keys()
+                .map(|p| (p.px - camera_chunk.px).abs().max((p.py - camera_chunk.py).abs()).max((p.pz - camera_chunk.pz).abs()))
+                .max()
+                .unwrap_or(0);
+            let visible = visible_chunks(camera_chunk, &self.chunk_visibility, radius, |chunk_pos| {
+                Frustum::contains_chunk(&planes, &view_mat, chunk_pos)
+            });
+            let in_frustum = self
+                .chunk_index_buffers
+                .keys()
+                .filter(|chunk_pos| Frustum::contains_chunk(&planes, &view_mat, *chunk_pos))
+                .count();
+            let drawn = self.chunk_index_buffers.keys().filter(|chunk_pos| visible.contains(chunk_pos)).count();
+            send_debug_info("Render", "occludedchunks", format!("{} chunks were hidden by other chunks", in_frustum - drawn));
+            Some(visible)
+        } else {
+            None
+        };
+        let is_visible = |chunk_pos: &ChunkPos| visible.as_ref().map(|visible| visible.contains(chunk_pos)).unwrap_or(true);
+
+        // The wireframe of the chunks outlines their quads with the same indices, which are extended for the largest mesh
+        let wireframe = debug_mode == DebugRenderMode::Wireframe;
+        if wireframe {
+            let max_quads = self
+                .chunk_vertex_buffers
+                .keys()
+                .map(|chunk_pos| self.chunk_vertex_buffers.get_pos_len(&chunk_pos).unwrap().1)
+                .chain(self.water_vertex_buffers.keys().map(|chunk_pos| self.water_vertex_buffers.get_pos_len(&chunk_pos).unwrap().1))
+                .max()
+                .unwrap_or(0)
+                / 4;
+            if self.wireframe_indices.as_ref().map(|(quads, _)| *quads < max_quads).unwrap_or(true) {
+                let quads = max_quads.next_power_of_two();
+                let indices = quad_outline_indices(quads);
+                self.wireframe_indices = Some((quads, buffer_from_slice(device, wgpu::BufferUsage::INDEX, to_u8_slice(&indices))));
+            }
+        }
+        let wireframe_indices = self.wireframe_indices.as_ref().map(|(_, buffer)| buffer).filter(|_| wireframe);
+
         // Draw all the chunks
         {
-            let mut rpass = super::render::create_default_render_pass(encoder, buffers);
-            rpass.set_pipeline(&self.chunk_pipeline);
+            let chunks = self
+                .chunk_index_buffers
+                .keys()
+                .filter(is_visible)
+                .collect::<Vec<_>>();
+            let draws = ChunkDraws::new(device, &self.chunk_index_buffers, &self.chunk_vertex_buffers, &chunks, wireframe, self.multi_draw_indirect);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(if wireframe { &self.pipelines.wireframe } else { &self.pipelines.chunk });
             rpass.set_bind_group(0, &self.chunk_bind_group, &[]);
-            rpass.set_vertex_buffer(0, self.chunk_vertex_buffers.get_buffer().slice(..));
-            rpass.set_index_buffer(self.chunk_index_buffers.get_buffer().slice(..));
-            let mut count = 0;
-            for chunk_pos in self.chunk_index_buffers.keys() {
-                if !enable_culling || Frustum::contains_chunk(&planes, &view_mat, chunk_pos) {
-                    count += 1;
-                    let (index_pos, index_len) =
-                        self.chunk_index_buffers.get_pos_len(&chunk_pos).unwrap();
-                    let (vertex_pos, _) =
-                        self.chunk_vertex_buffers.get_pos_len(&chunk_pos).unwrap();
-                    rpass.draw_indexed(
-                        (index_pos as u32)..((index_pos + index_len) as u32),
-                        vertex_pos as i32,
-                        0..1,
-                    );
-                }
-            }
+            draws.draw(&mut rpass, &self.chunk_index_buffers, &self.chunk_vertex_buffers, wireframe_indices);
             send_debug_info(
                 "Render",
                 "renderedchunks",
-                format!("{} chunks were rendered", count),
+                format!("{} chunks were rendered in {} draw calls", chunks.len(), draws.draw_calls()),
             );
         }
 
+        // Darken the chunks with the ambient occlusion, before anything else fills the depth buffer
+        if let Some(ssao) = self.ssao.as_mut().filter(|_| !wireframe) {
+            let projection = nalgebra::convert(opengl_to_wgpu * frustum.get_projection(aspect_ratio));
+            let window_size = (data.physical_window_size.width, data.physical_window_size.height);
+            ssao.render(device, encoder, buffers, window_size, projection);
+        }
+
         // Draw the skybox
         {
             // Update model buffer
@@ -321,14 +516,81 @@ impl WorldRenderer {
                 ])
             );
             encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 64);
-            let mut rpass = super::render::create_default_render_pass(encoder, buffers);
-            rpass.set_pipeline(&self.skybox_pipeline);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(&self.pipelines.skybox);
             rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
             rpass.set_vertex_buffer(0, self.skybox_vertex_buffer.slice(..));
             rpass.set_index_buffer(self.skybox_index_buffer.slice(..));
             rpass.draw_indexed(0..36, 0, 0..1);
         }
 
+        // Draw the stars, the sun and the moon, with the sun and the moon on opposite sides of the sky
+        {
+            let sun_direction = Vector3::new(sun_x, sun_y, sun_z);
+            // The sun sets a bit below the horizon, and the moon and the stars only show up once the sky is dark
+            let sun_alpha = smoothstep(-0.15, 0.0, sun_y) as f32;
+            let moon_alpha = 1.0 - smoothstep(-0.1, 0.2, sun_y) as f32;
+            if moon_alpha > 0.0 {
+                let model_uniform = star_model_uniform(frustum.position, sun_direction, moon_alpha);
+                let src_buffer = buffer_from_slice(
+                    device,
+                    wgpu::BufferUsage::COPY_SRC,
+                    to_u8_slice(&model_uniform)
+                );
+                encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 80);
+                let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+                rpass.set_pipeline(&self.pipelines.stars);
+                rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
+                rpass.set_vertex_buffer(0, self.star_vertex_buffer.slice(..));
+                rpass.draw(0..self.star_vertex_count, 0..1);
+            }
+            for &(direction, size, color, alpha) in [
+                (sun_direction, SUN_SIZE, SUN_COLOR, sun_alpha),
+                (-sun_direction, MOON_SIZE, MOON_COLOR, moon_alpha),
+            ]
+            .iter()
+            {
+                if alpha <= 0.0 {
+                    continue;
+                }
+                let model_uniform = celestial_model_uniform(frustum.position, direction, size, color, alpha);
+                let src_buffer = buffer_from_slice(
+                    device,
+                    wgpu::BufferUsage::COPY_SRC,
+                    to_u8_slice(&model_uniform)
+                );
+                encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 80);
+                let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+                rpass.set_pipeline(&self.pipelines.celestial);
+                rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
+                rpass.set_vertex_buffer(0, self.celestial_vertex_buffer.slice(..));
+                rpass.draw(0..6, 0..1);
+            }
+        }
+
+        // Draw the clouds
+        if self.clouds.density > 0.0 {
+            let center = Vector3::new(frustum.position.x, self.clouds.height as f64, frustum.position.z);
+            let model_uniform: [f32; 20] = [
+                CLOUD_RADIUS as f32, 0.0, 0.0, 0.0,
+                0.0, 0.0, CLOUD_RADIUS as f32, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                center.x as f32, center.y as f32, center.z as f32, 1.0,
+                self.clouds.density.min(1.0), 0.0, 0.0, 0.0,
+            ];
+            let src_buffer = buffer_from_slice(
+                device,
+                wgpu::BufferUsage::COPY_SRC,
+                to_u8_slice(&model_uniform)
+            );
+            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 80);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(&self.pipelines.cloud);
+            rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
+            rpass.set_vertex_buffer(0, self.celestial_vertex_buffer.slice(..));
+            rpass.draw(0..6, 0..1);
+        }
+
         // Draw the target if necessary
         if let Some((target_pos, target_face)) = pointed_block {
             // Generate the vertices
@@ -369,80 +631,339 @@ impl WorldRenderer {
                 ])
             );
             encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 64);
-            let mut rpass = super::render::create_default_render_pass(encoder, buffers);
-            rpass.set_pipeline(&self.target_pipeline);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(&self.pipelines.target);
             rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
             rpass.set_vertex_buffer(0, self.target_vertex_buffer.slice(..));
             rpass.draw(0..8, 0..1);
         }
 
+        // Draw the cracks of the blocks that are being broken
+        for &(block, stage) in break_stages {
+            let scale = (0.5 + CRACK_OFFSET) / self::skybox::FAR;
+            let model_uniform: [f32; 20] = [
+                scale, 0.0, 0.0, 0.0,
+                0.0, scale, 0.0, 0.0,
+                0.0, 0.0, scale, 0.0,
+                block.px as f32 + 0.5, block.py as f32 + 0.5, block.pz as f32 + 0.5, 1.0,
+                (stage + 1) as f32 / BREAK_STAGES as f32, 0.0, 0.0, 0.0,
+            ];
+            let src_buffer = buffer_from_slice(
+                device,
+                wgpu::BufferUsage::COPY_SRC,
+                to_u8_slice(&model_uniform)
+            );
+            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 80);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(&self.pipelines.crack);
+            rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
+            rpass.set_vertex_buffer(0, self.skybox_vertex_buffer.slice(..));
+            rpass.set_index_buffer(self.skybox_index_buffer.slice(..));
+            rpass.draw_indexed(0..36, 0, 0..1);
+        }
+
         // Draw the models
-        for model in models {
-            // Compute model matrix
-            let mut transform = Similarity3::identity();
-            transform.append_scaling_mut(model.scale);
-            let offset_translation = Translation3::from(-Vector3::from(model.rot_offset));
-            transform.append_translation_mut(&offset_translation);
-            transform.append_rotation_mut(&UnitQuaternion::from_axis_angle(
-                &Vector3::y_axis(),
-                model.rot_y,
-            ));
-            transform.append_translation_mut(&Translation3::from(
-                Vector3::new(model.pos_x, model.pos_y, model.pos_z)
-                    + &Vector3::from(model.rot_offset),
-            ));
-            let transformation_matrix: Matrix4<f32> = nalgebra::convert(transform);
+        for (model, brightness) in models.iter().zip(model_brightness) {
             // Update model buffer
             let src_buffer = buffer_from_slice(
                 device,
                 wgpu::BufferUsage::COPY_SRC,
-                to_u8_slice(transformation_matrix.as_ref())
+                to_u8_slice(&model_uniform(model, *brightness))
             );
-            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 64);
+            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 80);
             // Draw model
-            let mut rpass = super::render::create_default_render_pass(encoder, buffers);
-            rpass.set_pipeline(&self.model_pipeline);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(&self.pipelines.model);
+            rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
+            self.draw_model_mesh(&mut rpass, model.mesh_id);
+        }
+
+        // Draw the lines of the debug views
+        let debug_lines = match debug_mode {
+            DebugRenderMode::ChunkBorders => chunk_border_lines(BlockPos::from(frustum.position).containing_chunk_pos()),
+            DebugRenderMode::Hitboxes => hitbox_lines(hitboxes),
+            DebugRenderMode::Off | DebugRenderMode::Wireframe => Vec::new(),
+        };
+        if !debug_lines.is_empty() {
+            let vertex_buffer = buffer_from_slice(device, wgpu::BufferUsage::VERTEX, to_u8_slice(&debug_lines));
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(&self.pipelines.debug_lines);
             rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
-            rpass.set_vertex_buffer(0, self.model_vertex_buffers.get_buffer().slice(..));
-            rpass.set_index_buffer(self.model_index_buffers.get_buffer().slice(..));
-            let (index_pos, index_len) = self
-                .model_index_buffers
-                .get_pos_len(&model.mesh_id)
-                .unwrap();
-            let (vertex_pos, _) = self
-                .model_vertex_buffers
-                .get_pos_len(&model.mesh_id)
-                .unwrap();
-            rpass.draw_indexed(
-                (index_pos as u32)..((index_pos + index_len) as u32),
-                vertex_pos as i32,
-                0..1,
+            rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            rpass.draw(0..debug_lines.len() as u32, 0..1);
+        }
+
+        // Draw the water last, from the farthest chunks to the closest ones, so that the transparent surfaces blend
+        // with everything behind them
+        {
+            let mut water_chunks = self
+                .water_index_buffers
+                .keys()
+                .filter(is_visible)
+                .collect::<Vec<_>>();
+            water_chunks.sort_by_key(|chunk_pos| std::cmp::Reverse(chunk_pos.squared_euclidian_distance(camera_chunk)));
+            // The indirect draws keep their order, so the water still blends from back to front
+            let draws = ChunkDraws::new(device, &self.water_index_buffers, &self.water_vertex_buffers, &water_chunks, wireframe, self.multi_draw_indirect);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(if wireframe { &self.pipelines.wireframe } else { &self.pipelines.water });
+            rpass.set_bind_group(0, &self.chunk_bind_group, &[]);
+            draws.draw(&mut rpass, &self.water_index_buffers, &self.water_vertex_buffers, wireframe_indices);
+        }
+
+        // Draw the held item on top of everything, with the projection alone so that the view doesn't move it.
+        // Its field of view is fixed, so that it doesn't stretch when the player sprints or zooms.
+        if let Some((model, brightness)) = held_model {
+            let projection: [[f32; 4]; 4] = nalgebra::convert::<
+                nalgebra::Matrix4<f64>,
+                nalgebra::Matrix4<f32>,
+            >(opengl_to_wgpu * Frustum::get_projection_with_fov(aspect_ratio, crate::hand::HELD_ITEM_FOV))
+            .into();
+            let src_buffer = buffer_from_slice(
+                device,
+                wgpu::BufferUsage::COPY_SRC,
+                to_u8_slice(&projection)
             );
+            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_hand_proj, 0, 64);
+            let src_buffer = buffer_from_slice(
+                device,
+                wgpu::BufferUsage::COPY_SRC,
+                to_u8_slice(&model_uniform(model, brightness))
+            );
+            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 80);
+            // The item must not sink into the blocks in front of the camera
+            super::render::clear_depth(encoder, buffers);
+            let mut rpass = super::render::create_world_render_pass(encoder, buffers);
+            rpass.set_pipeline(&self.pipelines.hand);
+            rpass.set_bind_group(0, &self.hand_bind_group, &[]);
+            self.draw_model_mesh(&mut rpass, model.mesh_id);
         }
+
+        // Apply the effects of the screen
+        let window_size = (data.physical_window_size.width, data.physical_window_size.height);
+        self.post_processing.render(device, encoder, buffers, window_size, underwater);
+    }
+
+    /// Draw a mesh of the model registry with the pipeline and the bind group of the render pass
+    fn draw_model_mesh<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, mesh_id: u32) {
+        rpass.set_vertex_buffer(0, self.model_vertex_buffers.get_buffer().slice(..));
+        rpass.set_index_buffer(self.model_index_buffers.get_buffer().slice(..));
+        let (index_pos, index_len) = self.model_index_buffers.get_pos_len(&mesh_id).unwrap();
+        let (vertex_pos, _) = self.model_vertex_buffers.get_pos_len(&mesh_id).unwrap();
+        rpass.draw_indexed(
+            (index_pos as u32)..((index_pos + index_len) as u32),
+            vertex_pos as i32,
+            0..1,
+        );
     }
 
+    /// Upload the mesh of a chunk. If it doesn't fit in the memory budget, the chunk is left without a mesh.
     pub fn update_chunk_mesh(
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
         chunk_mesh: ChunkMesh,
-    ) {
-        let (pos, vertices, indices) = chunk_mesh;
+    ) -> Result<(), BufferFull> {
+        let (pos, vertices, indices, water_vertices, water_indices, visibility) = chunk_mesh;
+        self.chunk_visibility.insert(pos, visibility);
         if vertices.len() > 0 && indices.len() > 0 {
-            self.chunk_vertex_buffers
-                .update(device, encoder, pos, &vertices[..]);
-            self.chunk_index_buffers
-                .update(device, encoder, pos, &indices[..]);
+            let result = self
+                .chunk_vertex_buffers
+                .update(device, encoder, pos, &vertices[..])
+                .and_then(|()| self.chunk_index_buffers.update(device, encoder, pos, &indices[..]));
+            if result.is_err() {
+                self.remove_chunk_mesh(pos);
+                return result;
+            }
+        }
+        if !water_vertices.is_empty() && !water_indices.is_empty() {
+            let result = self
+                .water_vertex_buffers
+                .update(device, encoder, pos, &water_vertices[..])
+                .and_then(|()| self.water_index_buffers.update(device, encoder, pos, &water_indices[..]));
+            if result.is_err() {
+                self.remove_chunk_mesh(pos);
+            }
+            return result;
+        }
+        // The water of the previous mesh may have been removed
+        self.water_vertex_buffers.remove(&pos);
+        self.water_index_buffers.remove(&pos);
+        Ok(())
+    }
+
+    /// Rebuild the pipelines of the world when the shaders in `assets/shaders` change
+    pub fn enable_shader_hot_reload(&mut self) {
+        self.shader_watcher = Some(ShaderWatcher::new(SHADER_FOLDER));
+    }
+
+    /// Whether some shaders changed since the last check, and they all compile.
+    /// A shader that doesn't compile is reported instead of crashing the game, and the pipelines are kept.
+    fn shaders_changed(&mut self) -> bool {
+        let changed = match self.shader_watcher.as_mut() {
+            Some(watcher) => watcher.changed_files(Instant::now()),
+            None => return false,
+        };
+        let mut shaders_changed = false;
+        for path in changed {
+            if let Some(stage) = ShaderStage::from_path(&path) {
+                if let Err(error) = try_load_glsl_shader(stage, &path, &[]) {
+                    log::error!("Couldn't reload {}: {}", path.display(), error);
+                    return false;
+                }
+                shaders_changed = true;
+            }
         }
+        if shaders_changed {
+            log::info!("Rebuilding the pipelines of the world with the new shaders");
+        }
+        shaders_changed
+    }
+
+    /// Move the chunk meshes into the holes left by the removed chunks, a few at a time, and report the usage of the buffers
+    pub fn compact_chunk_buffers(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        compact_chunk_buffer(device, encoder, &mut self.chunk_vertex_buffers, "chunkvertices", "Chunk vertices");
+        compact_chunk_buffer(device, encoder, &mut self.chunk_index_buffers, "chunkindices", "Chunk indices");
+        compact_chunk_buffer(device, encoder, &mut self.water_vertex_buffers, "watervertices", "Water vertices");
+        compact_chunk_buffer(device, encoder, &mut self.water_index_buffers, "waterindices", "Water indices");
     }
 
     pub fn remove_chunk_mesh(&mut self, pos: ChunkPos) {
         self.chunk_vertex_buffers.remove(&pos);
         self.chunk_index_buffers.remove(&pos);
+        self.water_vertex_buffers.remove(&pos);
+        self.water_index_buffers.remove(&pos);
+        self.chunk_visibility.remove(&pos);
+    }
+
+    /// Create a view of the texture atlas, for the other renderers that draw its textures
+    pub fn create_texture_atlas_view(&self) -> wgpu::TextureView {
+        self.texture_atlas.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        })
+    }
+
+    /// Replace the texture of a region of the texture atlas, and the border around it
+    pub fn update_texture_atlas_region(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        rect: TextureRect,
+        image: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    ) {
+        let x = (rect.x * MAX_TEXTURE_SIZE as f32).round() as u32 - TEXTURE_PADDING;
+        let y = (rect.y * MAX_TEXTURE_SIZE as f32).round() as u32 - TEXTURE_PADDING;
+        let image = pad_texture(image, TEXTURE_PADDING);
+        update_image_region(device, encoder, &self.texture_atlas, rect.page, (x, y), &image);
     }
 }
 
 /*========== CHUNK RENDERING ==========*/
+/// The indexed draws of the meshes of some chunks, or of the edges of their quads with the wireframe indices.
+/// When the device supports multi-draw-indirect, their arguments are uploaded to an indirect buffer so that they are
+/// all issued with a single call.
+struct ChunkDraws {
+    /// The indices and the base vertex of every draw
+    draws: Vec<(Range<u32>, i32)>,
+    indirect_buffer: Option<wgpu::Buffer>,
+}
+
+impl ChunkDraws {
+    fn new(
+        device: &wgpu::Device,
+        index_buffers: &MultiBuffer<ChunkPos, u32>,
+        vertex_buffers: &MultiBuffer<ChunkPos, ChunkVertex>,
+        chunks: &[ChunkPos],
+        wireframe: bool,
+        multi_draw_indirect: bool,
+    ) -> Self {
+        let draws = chunks
+            .iter()
+            .map(|chunk_pos| {
+                let (index_pos, index_len) = index_buffers.get_pos_len(chunk_pos).unwrap();
+                let (vertex_pos, vertex_len) = vertex_buffers.get_pos_len(chunk_pos).unwrap();
+                let indices = if wireframe {
+                    // 12 indices outline the 4 vertices of every quad
+                    0..(vertex_len / 4 * 12) as u32
+                } else {
+                    (index_pos as u32)..((index_pos + index_len) as u32)
+                };
+                (indices, vertex_pos as i32)
+            })
+            .collect::<Vec<_>>();
+        let indirect_buffer = if multi_draw_indirect && !draws.is_empty() {
+            let args = draws
+                .iter()
+                .flat_map(|(indices, base_vertex)| draw_indexed_indirect_args(indices.clone(), *base_vertex))
+                .collect::<Vec<u32>>();
+            Some(buffer_from_slice(device, wgpu::BufferUsage::INDIRECT, to_u8_slice(&args)))
+        } else {
+            None
+        };
+        Self { draws, indirect_buffer }
+    }
+
+    /// Draw the meshes with the pipeline and the bind group of the render pass
+    fn draw<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        index_buffers: &'a MultiBuffer<ChunkPos, u32>,
+        vertex_buffers: &'a MultiBuffer<ChunkPos, ChunkVertex>,
+        wireframe_indices: Option<&'a wgpu::Buffer>,
+    ) {
+        rpass.set_vertex_buffer(0, vertex_buffers.get_buffer().slice(..));
+        rpass.set_index_buffer(wireframe_indices.unwrap_or_else(|| index_buffers.get_buffer()).slice(..));
+        match &self.indirect_buffer {
+            Some(indirect_buffer) => rpass.multi_draw_indexed_indirect(indirect_buffer, 0, self.draws.len() as u32),
+            None => {
+                for (indices, base_vertex) in &self.draws {
+                    rpass.draw_indexed(indices.clone(), *base_vertex, 0..1);
+                }
+            }
+        }
+    }
+
+    /// The number of draw calls that `draw` issues
+    fn draw_calls(&self) -> usize {
+        match self.indirect_buffer {
+            Some(_) => 1,
+            None => self.draws.len(),
+        }
+    }
+}
+
+/// The arguments of an indexed indirect draw of a single instance:
+/// the index count, the instance count, the first index, the base vertex and the first instance
+fn draw_indexed_indirect_args(indices: Range<u32>, base_vertex: i32) -> [u32; 5] {
+    [indices.end - indices.start, 1, indices.start, base_vertex as u32, 0]
+}
+
+/// Compact a buffer of chunk meshes while too much of it is lost in holes, and report how it is used
+fn compact_chunk_buffer<T: Copy + std::fmt::Debug + 'static>(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    buffer: &mut MultiBuffer<ChunkPos, T>,
+    debug_id: &str,
+    name: &str,
+) {
+    let usage = buffer.usage();
+    if usage.holes as f64 > COMPACTION_THRESHOLD * usage.capacity as f64 {
+        buffer.compact(device, encoder, COMPACTION_BYTES_PER_FRAME / std::mem::size_of::<T>());
+    }
+    send_debug_info(
+        "Render",
+        debug_id,
+        format!(
+            "{}: {:.0}% of {} used, {:.0}% of the free space in {} holes",
+            name,
+            usage.occupancy() * 100.0,
+            usage.capacity,
+            usage.fragmentation() * 100.0,
+            usage.hole_count,
+        ),
+    );
+}
+
 /// Chunk vertex
 #[derive(Debug, Clone, Copy)]
 pub struct ChunkVertex {
@@ -451,6 +972,8 @@ pub struct ChunkVertex {
     pub texture_size: [f32; 2],
     pub texture_max_uv: [f32; 2],
     pub texture_uv: [f32; 2],
+    /// The face in the 3 lowest bits, then the ambient occlusion in 2 bits, then the light level of the vertex
+    /// in 4 bits, which comes from the light chunks of the server, then the page of the atlas with the texture
     pub occl_and_face: u32,
 }
 
@@ -510,33 +1033,410 @@ const CHUNK_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> =
                 ty: wgpu::BindingType::SampledTexture {
                     component_type: wgpu::TextureComponentType::Uint,
                     multisampled: false,
-                    dimension: wgpu::TextureViewDimension::D2,
+                    dimension: wgpu::TextureViewDimension::D2Array,
+                },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                // sky, whose animation time is also used by the vertices of the water
+                binding: 3,
+                visibility: wgpu::ShaderStage::from_bits_truncate(
+                    wgpu::ShaderStage::VERTEX.bits() | wgpu::ShaderStage::FRAGMENT.bits()
+                ),
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                // shadows
+                binding: 4,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    component_type: wgpu::TextureComponentType::Float,
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2Array,
                 },
                 count: None
             },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: true },
+                count: None
+            },
         ],
     };
 
+/// The pipelines of the world, which are recreated when the number of samples of the window buffers changes
+struct WorldPipelines {
+    chunk: wgpu::RenderPipeline,
+    // Water, with the chunk bind group
+    water: wgpu::RenderPipeline,
+    skybox: wgpu::RenderPipeline,
+    // Sun and moon
+    celestial: wgpu::RenderPipeline,
+    stars: wgpu::RenderPipeline,
+    cloud: wgpu::RenderPipeline,
+    // Targeted block
+    target: wgpu::RenderPipeline,
+    crack: wgpu::RenderPipeline,
+    model: wgpu::RenderPipeline,
+    // Held item
+    hand: wgpu::RenderPipeline,
+    // Edges of the quads of the chunks, with the chunk bind group
+    wireframe: wgpu::RenderPipeline,
+    // Colored lines of the debug views
+    debug_lines: wgpu::RenderPipeline,
+}
+
+/// Create the pipelines of the world, for window buffers with some number of samples
+fn create_world_pipelines(
+    device: &wgpu::Device,
+    chunk_bind_group_layout: &wgpu::BindGroupLayout,
+    vpm_bind_group_layout: &wgpu::BindGroupLayout,
+    sample_count: u32,
+) -> WorldPipelines {
+    // Create chunk pipeline
+    let chunk_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/world.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/world.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_default_pipeline(
+            device,
+            chunk_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<ChunkVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &CHUNK_VERTEX_ATTRIBUTES,
+            },
+            true,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create water pipeline
+    let water_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/water.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/water.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        // The surface must also be seen from below the water
+        create_transparent_pipeline(
+            device,
+            chunk_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<ChunkVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &CHUNK_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create skybox pipeline
+    let skybox_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/skybox.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/skybox.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_default_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<SkyboxVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &SKYBOX_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create sun and moon pipeline
+    let celestial_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/celestial.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/celestial.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        // The clouds drawn after them must not be hidden by their quads
+        create_transparent_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<SkyboxVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &SKYBOX_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create star pipeline
+    let star_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/stars.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/stars.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_transparent_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<SkyboxVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &SKYBOX_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create cloud pipeline
+    let cloud_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/clouds.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/clouds.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_transparent_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<SkyboxVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &SKYBOX_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create target pipeline
+    let target_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/target.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/target.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_default_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::LineList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<SkyboxVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &SKYBOX_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create crack pipeline
+    let crack_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/crack.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/crack.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_transparent_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<SkyboxVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &SKYBOX_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create model pipeline
+    let model_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/model.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/model.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_default_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<RgbVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &RGB_VERTEX_ATTRIBUTES,
+            },
+            true,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create held item pipeline
+    let hand_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/hand.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/model.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_default_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<RgbVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &RGB_VERTEX_ATTRIBUTES,
+            },
+            true,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create wireframe pipeline
+    let wireframe_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/world.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/world_wireframe.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_default_pipeline(
+            device,
+            chunk_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::LineList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<ChunkVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &CHUNK_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+    // Create debug line pipeline
+    let debug_lines_pipeline = {
+        let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/debug_lines.vert");
+        let vertex_shader = wgpu::util::make_spirv(&vertex_shader_bytes);
+        let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/debug_lines.frag");
+        let fragment_shader = wgpu::util::make_spirv(&fragment_shader_bytes);
+
+        create_default_pipeline(
+            device,
+            vpm_bind_group_layout,
+            vertex_shader,
+            fragment_shader,
+            wgpu::PrimitiveTopology::LineList,
+            wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<DebugLineVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &DEBUG_LINE_VERTEX_ATTRIBUTES,
+            },
+            false,
+            crate::window::HDR_FORMAT,
+            sample_count,
+        )
+    };
+
+WorldPipelines {
+    chunk: chunk_pipeline,
+    water: water_pipeline,
+    skybox: skybox_pipeline,
+    celestial: celestial_pipeline,
+    stars: star_pipeline,
+    cloud: cloud_pipeline,
+    target: target_pipeline,
+    crack: crack_pipeline,
+    model: model_pipeline,
+    hand: hand_pipeline,
+    wireframe: wireframe_pipeline,
+    debug_lines: debug_lines_pipeline,
+}
+}
+
 /// Create chunk bind group
 fn create_chunk_bind_group(
     device: &wgpu::Device,
     layout: &wgpu::BindGroupLayout,
     texture_atlas_view: &wgpu::TextureView,
     uniform_view_proj: &wgpu::Buffer,
+    uniform_sky: &wgpu::Buffer,
+    textures: TextureSettings,
+    shadow_maps: &ShadowMaps,
 ) -> wgpu::BindGroup {
     // Create texture sampler
+    let filter = match textures.filter {
+        TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+        TextureFilter::Linear => wgpu::FilterMode::Linear,
+    };
+    // Only the powers of two up to 16 are valid
+    let anisotropy = textures.anisotropy.clamp(1, 16);
+    let anisotropy = if anisotropy.is_power_of_two() { anisotropy } else { anisotropy.next_power_of_two() / 2 };
     let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
         label: None,
         address_mode_u: wgpu::AddressMode::ClampToEdge,
         address_mode_v: wgpu::AddressMode::ClampToEdge,
         address_mode_w: wgpu::AddressMode::ClampToEdge,
-        mag_filter: wgpu::FilterMode::Nearest,
-        min_filter: wgpu::FilterMode::Nearest,
+        mag_filter: filter,
+        min_filter: filter,
         mipmap_filter: wgpu::FilterMode::Linear,
         lod_min_clamp: 0.0,
-        lod_max_clamp: 5.0,
+        lod_max_clamp: if textures.mipmaps { (MIPMAP_LEVELS - 1) as f32 } else { 0.0 },
         compare: Some(wgpu::CompareFunction::Always),
-        anisotropy_clamp: None
+        anisotropy_clamp: std::num::NonZeroU8::new(anisotropy).filter(|&anisotropy| anisotropy.get() > 1),
     });
 
     device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -557,6 +1457,26 @@ fn create_chunk_bind_group(
                 binding: 2,
                 resource: wgpu::BindingResource::TextureView(texture_atlas_view),
             },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Buffer(
+                    uniform_sky.slice(0..SKY_UNIFORM_SIZE)
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::Buffer(
+                    shadow_maps.uniform.slice(0..SHADOW_UNIFORM_SIZE)
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&shadow_maps.array_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::Sampler(&shadow_maps.sampler),
+            },
         ],
     })
 }
@@ -594,6 +1514,13 @@ const SKYBOX_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> =
                 ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
                 count: None
             },
+            wgpu::BindGroupLayoutEntry {
+                // sky
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
+                count: None
+            },
         ],
     };
 
@@ -603,6 +1530,7 @@ fn create_vpm_bind_group(
     layout: &wgpu::BindGroupLayout,
     uniform_view_proj: &wgpu::Buffer,
     uniform_model: &wgpu::Buffer,
+    uniform_sky: &wgpu::Buffer,
 ) -> wgpu::BindGroup {
     device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
@@ -617,13 +1545,82 @@ fn create_vpm_bind_group(
             wgpu::BindGroupEntry {
                 binding: 1,
                 resource: wgpu::BindingResource::Buffer(
-                    uniform_model.slice(0..64)
+                    uniform_model.slice(0..80)
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Buffer(
+                    uniform_sky.slice(0..SKY_UNIFORM_SIZE)
                 ),
             },
         ],
     })
 }
 
+/// The model uniform of a model: its model matrix, followed by its brightness
+fn model_uniform(model: &model::Model, brightness: f32) -> [f32; 20] {
+    let mut transform = Similarity3::identity();
+    transform.append_scaling_mut(model.scale);
+    let offset_translation = Translation3::from(-Vector3::from(model.rot_offset));
+    transform.append_translation_mut(&offset_translation);
+    transform.append_rotation_mut(&UnitQuaternion::from_axis_angle(
+        &Vector3::z_axis(),
+        model.roll,
+    ));
+    transform.append_rotation_mut(&UnitQuaternion::from_axis_angle(
+        &Vector3::y_axis(),
+        model.rot_y,
+    ));
+    transform.append_translation_mut(&Translation3::from(
+        Vector3::new(model.pos_x, model.pos_y, model.pos_z)
+            + Vector3::from(model.rot_offset),
+    ));
+    let transformation_matrix: Matrix4<f32> = nalgebra::convert(transform);
+    let mut model_uniform = [0.0f32; 20];
+    model_uniform[..16].copy_from_slice(transformation_matrix.as_slice());
+    model_uniform[16] = brightness;
+    model_uniform
+}
+
+/*========== SUN AND MOON RENDERING ==========*/
+/// The model uniform of the quad of the sun or of the moon: the model matrix that puts the quad
+/// in `direction` from the camera, facing it, followed by the color and the alpha of the disc
+fn celestial_model_uniform(camera: Vector3<f64>, direction: Vector3<f64>, size: f64, color: [f32; 3], alpha: f32) -> [f32; 20] {
+    // The sun moves in the x-y plane, so its direction is never parallel to the z axis
+    let right = direction.cross(&Vector3::z()).normalize() * size;
+    let up = right.cross(&direction).normalize() * size;
+    let center = camera + direction * CELESTIAL_DISTANCE;
+    [
+        right.x as f32, right.y as f32, right.z as f32, 0.0,
+        up.x as f32, up.y as f32, up.z as f32, 0.0,
+        direction.x as f32, direction.y as f32, direction.z as f32, 0.0,
+        center.x as f32, center.y as f32, center.z as f32, 1.0,
+        color[0], color[1], color[2], alpha,
+    ]
+}
+
+/// The model uniform of the stars: the rotation that turns them around the axis of the orbit of the sun,
+/// so that they move across the sky with it, centered on the camera, followed by their color and their alpha
+fn star_model_uniform(camera: Vector3<f64>, sun_direction: Vector3<f64>, alpha: f32) -> [f32; 20] {
+    // The sun turns around the z axis
+    let angle = sun_direction.y.atan2(sun_direction.x);
+    let (sin, cos) = (angle.sin() as f32, angle.cos() as f32);
+    [
+        cos, sin, 0.0, 0.0,
+        -sin, cos, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        camera.x as f32, camera.y as f32, camera.z as f32, 1.0,
+        STAR_COLOR[0], STAR_COLOR[1], STAR_COLOR[2], alpha,
+    ]
+}
+
+/// Smooth interpolation from 0 below `edge0` to 1 above `edge1`, like in GLSL
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
 /*========== TARGET RENDERING ==========*/
 // `SkyboxVertex` is shamelessly stolen to also draw the targeted block
 
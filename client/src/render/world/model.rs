@@ -13,6 +13,8 @@ pub struct Model {
     pub scale: f32,
     /// Model rotation (after scaling)
     pub rot_y: f32,
+    /// Model rotation around the z axis, before `rot_y`
+    pub roll: f32,
     /// Offset to apply before rotating the model
     pub rot_offset: [f32; 3],
 }
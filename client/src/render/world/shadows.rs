@@ -0,0 +1,264 @@
+//! Shadows of the sun. The depth of the chunks seen from the sun is drawn into cascades of growing size around the
+//! camera, and the world shader compares the depth of its fragments with the cascade that contains them.
+use super::CHUNK_VERTEX_ATTRIBUTES;
+use super::ChunkVertex;
+use crate::render::init::{load_glsl_shader, ShaderStage, RASTERIZER_NO_CULLING};
+use history_survival_common::world::{ChunkPos, CHUNK_SIZE};
+use nalgebra::{Matrix4, Point3, Vector3};
+
+/// Number of shadow cascades
+pub const SHADOW_CASCADES: usize = 3;
+/// Radius of the cascades around the camera, in blocks. Nothing is shadowed beyond the last cascade.
+pub const SHADOW_CASCADE_RADII: [f64; SHADOW_CASCADES] = [16.0, 48.0, 144.0];
+/// Side of the shadow map of every cascade, in texels
+const SHADOW_MAP_SIZE: u32 = 2048;
+/// How far towards the sun from the edge of a cascade the blocks that cast shadows into it can be, in blocks
+const SHADOW_CASTER_DISTANCE: f64 = 128.0;
+/// How much of the light the shadows remove
+const SHADOW_STRENGTH: f32 = 0.45;
+/// Size of the uniform of the shadows: the view-projection matrices of the cascades,
+/// followed by their radii and the strength of the shadows, which is 0 when they are disabled
+pub const SHADOW_UNIFORM_SIZE: u64 = (SHADOW_CASCADES * 64 + 16) as u64;
+
+/// The region around the camera that a shadow map covers
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowCascade {
+    /// The rotation from the world to the view from the sun
+    light_view: Matrix4<f64>,
+    /// The center of the cascade in the view from the sun
+    center: Vector3<f64>,
+    radius: f64,
+}
+
+impl ShadowCascade {
+    /// The cascade of some radius around the camera, for the sun in some direction
+    pub fn new(camera: Vector3<f64>, sun_direction: Vector3<f64>, radius: f64) -> Self {
+        // The sun never goes through the poles of the sky, which are along z
+        let light_view = Matrix4::look_at_rh(&Point3::origin(), &Point3::from(-sun_direction), &Vector3::z());
+        let mut center = light_view.transform_point(&Point3::from(camera)).coords;
+        // The cascade moves by whole texels, so that the edges of the shadows don't flicker when the camera moves
+        let texel = 2.0 * radius / SHADOW_MAP_SIZE as f64;
+        center.x = (center.x / texel).round() * texel;
+        center.y = (center.y / texel).round() * texel;
+        Self {
+            light_view,
+            center,
+            radius,
+        }
+    }
+
+    /// The view-projection matrix of the cascade, with the OpenGL depth range
+    pub fn get_view_projection(&self) -> Matrix4<f64> {
+        let (c, r) = (self.center, self.radius);
+        // The view looks towards -z
+        let projection =
+            Matrix4::new_orthographic(c.x - r, c.x + r, c.y - r, c.y + r, -c.z - r - SHADOW_CASTER_DISTANCE, -c.z + r);
+        projection * self.light_view
+    }
+
+    /// Whether the blocks of a chunk can cast shadows into the cascade
+    pub fn contains_chunk(&self, chunk_pos: ChunkPos) -> bool {
+        let half_size = CHUNK_SIZE as f64 / 2.0;
+        let chunk_center = Point3::new(
+            chunk_pos.px as f64 * CHUNK_SIZE as f64 + half_size,
+            chunk_pos.py as f64 * CHUNK_SIZE as f64 + half_size,
+            chunk_pos.pz as f64 * CHUNK_SIZE as f64 + half_size,
+        );
+        let d = self.light_view.transform_point(&chunk_center).coords - self.center;
+        let margin = self.radius + half_size * 3.0f64.sqrt();
+        d.x.abs() <= margin && d.y.abs() <= margin && d.z <= margin + SHADOW_CASTER_DISTANCE && d.z >= -margin
+    }
+}
+
+/// The uniform of the shadows for some cascades, or with no shadows if there are no cascades
+pub fn shadow_uniform(cascades: &[Matrix4<f32>]) -> [f32; SHADOW_UNIFORM_SIZE as usize / 4] {
+    let mut uniform = [0.0; SHADOW_UNIFORM_SIZE as usize / 4];
+    for (i, view_proj) in cascades.iter().enumerate() {
+        uniform[16 * i..16 * (i + 1)].copy_from_slice(view_proj.as_slice());
+    }
+    for (i, radius) in SHADOW_CASCADE_RADII.iter().enumerate() {
+        uniform[16 * SHADOW_CASCADES + i] = *radius as f32;
+    }
+    uniform[16 * SHADOW_CASCADES + 3] = if cascades.is_empty() { 0.0 } else { SHADOW_STRENGTH };
+    uniform
+}
+
+/// The shadow maps of the cascades, and what is needed to draw them
+pub struct ShadowMaps {
+    /// Whether the shadows are drawn
+    pub enabled: bool,
+    /// The shadow maps of all the cascades, in the layers of a texture, kept alive for the views
+    _texture: wgpu::Texture,
+    /// The shadow maps, as seen by the world shader
+    pub array_view: wgpu::TextureView,
+    /// The shadow map of every cascade, to draw it
+    pub cascade_views: Vec<wgpu::TextureView>,
+    /// The sampler that compares the depth of the fragments with the shadow maps
+    pub sampler: wgpu::Sampler,
+    /// The matrices of the cascades and the strength of the shadows, for the world shader
+    pub uniform: wgpu::Buffer,
+    /// The view-projection matrix of the cascade being drawn
+    pub uniform_cascade: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMaps {
+    /// Create the shadow maps. They are only 1 texel wide if the shadows are disabled.
+    pub fn new(device: &wgpu::Device, enabled: bool) -> Self {
+        let size = if enabled { SHADOW_MAP_SIZE } else { 1 };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth: SHADOW_CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: crate::window::DEPTH_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        });
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let cascade_views = (0..SHADOW_CASCADES as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        // The comparisons of the neighbouring texels are blended, which smooths the edges of the shadows
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 0.0,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            anisotropy_clamp: None,
+        });
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: SHADOW_UNIFORM_SIZE,
+            usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
+        });
+        let uniform_cascade = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: 64,
+            usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(uniform_cascade.slice(0..64)),
+            }],
+        });
+        let pipeline = create_shadow_pipeline(device, &bind_group_layout);
+        Self {
+            enabled,
+            _texture: texture,
+            array_view,
+            cascade_views,
+            sampler,
+            uniform,
+            uniform_cascade,
+            bind_group,
+            pipeline,
+        }
+    }
+}
+
+/// Create the pipeline that draws the depth of the chunks into a shadow map
+fn create_shadow_pipeline(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/shadow.vert");
+    let vertex_shader_module = device.create_shader_module(wgpu::util::make_spirv(&vertex_shader_bytes));
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vertex_shader_module,
+            entry_point: "main",
+        },
+        // Only the depth is needed
+        fragment_stage: None,
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint32,
+            vertex_buffers: &[wgpu::VertexBufferDescriptor {
+                stride: std::mem::size_of::<ChunkVertex>() as u64,
+                step_mode: wgpu::InputStepMode::Vertex,
+                // The position
+                attributes: &CHUNK_VERTEX_ATTRIBUTES[..1],
+            }],
+        },
+        // Both sides of the faces cast shadows, and the depth is pushed back to keep the faces from shadowing themselves
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            depth_bias: 2,
+            depth_bias_slope_scale: 2.0,
+            ..RASTERIZER_NO_CULLING
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[],
+        depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            ..crate::render::init::DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR
+        }),
+        sample_count: 1,
+        sample_mask: 0xFFFFFFFF,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The position of a point in the shadow map of a cascade, with the depth
+    fn project(cascade: &ShadowCascade, point: Vector3<f64>) -> Vector3<f64> {
+        let projected = cascade.get_view_projection() * point.push(1.0);
+        projected.xyz() / projected.w
+    }
+
+    #[test]
+    fn cascades_look_from_the_sun() {
+        let camera = Vector3::new(10.3, 70.0, -5.8);
+        let sun = Vector3::new(0.5, 0.8, 0.2).normalize();
+        let cascade = ShadowCascade::new(camera, sun, 16.0);
+        let at_camera = project(&cascade, camera);
+        assert!(at_camera.iter().all(|c| c.abs() < 1.0));
+        // A block between the camera and the sun hides the camera from the sun
+        let above = project(&cascade, camera + sun * 50.0);
+        assert!((above.xy() - at_camera.xy()).norm() < 1e-6);
+        assert!(above.z < at_camera.z && above.z > -1.0);
+        assert!(cascade.contains_chunk(ChunkPos::from([0, 2, -1])));
+        assert!(!cascade.contains_chunk(ChunkPos::from([0, -2, -1])));
+    }
+}
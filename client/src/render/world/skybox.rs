@@ -1,9 +1,20 @@
 //! Skybox rendering
 
+use nalgebra::Vector3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::ops::Range;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use super::{ SkyboxVertex, to_u8_slice };
 
-const FAR: f32 = 900.0;
+/// Half of the size of the skybox cube
+pub const FAR: f32 = 900.0;
+/// Distance from the camera to the stars, behind the sun and the moon but inside the skybox
+const STAR_DISTANCE: f32 = 850.0;
+const STAR_COUNT: usize = 1500;
+/// Half of the size of the quads of the stars, which makes some stars brighter than the others
+const STAR_SIZES: Range<f32> = 0.8..2.4;
+/// The stars are placed with a fixed seed, so that the constellations are the same in every game
+const STAR_SEED: u64 = 0x5354_4152;
 
 const EAST: [[f32; 3]; 4] = [
     [FAR, -FAR, -FAR],
@@ -86,3 +97,79 @@ pub fn create_skybox(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer) {
         }
     )
 }
+
+/// The corners of the quad of the sun, of the moon and of the clouds, as two triangles
+const CELESTIAL_QUAD: [[f32; 3]; 6] = [
+    [-1.0, -1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+];
+
+/// Create the vertex buffer of the quad that the sun, the moon and the clouds are drawn on.
+pub fn create_celestial_quad(device: &wgpu::Device) -> wgpu::Buffer {
+    let vertices = CELESTIAL_QUAD.iter().map(|&position| SkyboxVertex { position }).collect::<Vec<_>>();
+    device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("celestial_vertices"),
+        usage: wgpu::BufferUsage::VERTEX,
+        contents: to_u8_slice(&vertices),
+    })
+}
+
+/// The quads of the stars, spread evenly on a sphere around the camera, as two triangles each
+fn star_vertices() -> Vec<SkyboxVertex> {
+    let mut rng = StdRng::seed_from_u64(STAR_SEED);
+    let mut vertices = Vec::with_capacity(6 * STAR_COUNT);
+    for _ in 0..STAR_COUNT {
+        // A uniform height and angle give a uniform direction on the sphere
+        let y: f32 = rng.gen_range(-1.0..1.0);
+        let angle: f32 = rng.gen_range(0.0..2.0 * std::f32::consts::PI);
+        let radius = (1.0 - y * y).sqrt();
+        let direction = Vector3::new(radius * angle.cos(), y, radius * angle.sin());
+        let size = rng.gen_range(STAR_SIZES);
+        let axis = if direction.y.abs() < 0.9 { Vector3::y() } else { Vector3::x() };
+        let right = direction.cross(&axis).normalize() * size;
+        let up = right.cross(&direction).normalize() * size;
+        let center = direction * STAR_DISTANCE;
+        for corner in CELESTIAL_QUAD.iter() {
+            let position = center + right * corner[0] + up * corner[1];
+            vertices.push(SkyboxVertex { position: position.into() });
+        }
+    }
+    vertices
+}
+
+/// Create the vertex buffer of the stars, and return it with its number of vertices
+pub fn create_star_field(device: &wgpu::Device) -> (wgpu::Buffer, u32) {
+    let vertices = star_vertices();
+    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("star_vertices"),
+        usage: wgpu::BufferUsage::VERTEX,
+        contents: to_u8_slice(&vertices),
+    });
+    (buffer, vertices.len() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_surround_the_camera() {
+        let vertices = star_vertices();
+        assert_eq!(vertices.len(), 6 * STAR_COUNT);
+        for star in vertices.chunks(6) {
+            // The quads are small and at the distance of the stars
+            let center = (Vector3::from(star[0].position) + Vector3::from(star[3].position)) / 2.0;
+            assert!((center.norm() - STAR_DISTANCE).abs() < 0.01 * STAR_DISTANCE);
+            assert!((Vector3::from(star[0].position) - Vector3::from(star[3].position)).norm() < 4.0 * STAR_SIZES.end);
+        }
+        // Both halves of the sky have stars
+        let above = vertices.chunks(6).filter(|star| star[0].position[1] > 0.0).count();
+        assert!(above > STAR_COUNT / 3 && above < 2 * STAR_COUNT / 3);
+        // The sky is the same in every game
+        assert_eq!(star_vertices()[0].position, vertices[0].position);
+    }
+}
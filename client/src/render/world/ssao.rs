@@ -0,0 +1,271 @@
+//! Screen-space ambient occlusion. The occlusion of every pixel is computed from the depth buffer of the chunks,
+//! then blurred and multiplied with the color of the chunks.
+use crate::render::init::create_fullscreen_pipeline;
+use crate::render::{buffer_from_slice, to_u8_slice};
+use crate::settings::AmbientOcclusion;
+use crate::window::WindowBuffers;
+use nalgebra::Matrix4;
+
+/// Format of the occlusion of the pixels, before it is blurred
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+/// Radius of the hemisphere around the pixels where the occluders are looked for, in blocks
+const SSAO_RADIUS: f32 = 0.75;
+/// How dark the fully occluded pixels are made
+const SSAO_STRENGTH: f32 = 0.8;
+/// Size of the uniform: the projection matrix and its inverse, then the number of samples, the radius and the strength
+const SSAO_UNIFORM_SIZE: u64 = 2 * 64 + 16;
+
+/// Number of samples of the depth buffer for every pixel
+fn occlusion_samples(quality: AmbientOcclusion) -> u32 {
+    match quality {
+        AmbientOcclusion::Off => 0,
+        AmbientOcclusion::Low => 8,
+        AmbientOcclusion::Medium => 16,
+        AmbientOcclusion::High => 32,
+    }
+}
+
+/// The resources of the ambient occlusion pass
+pub struct Ssao {
+    samples: u32,
+    uniform: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    occlusion_layout: wgpu::BindGroupLayout,
+    occlusion_pipeline: wgpu::RenderPipeline,
+    composite_layout: wgpu::BindGroupLayout,
+    composite_pipeline: wgpu::RenderPipeline,
+    /// The occlusion of the pixels with the size of the window, and the bind group that reads it
+    occlusion: Option<((u32, u32), wgpu::TextureView, wgpu::BindGroup)>,
+}
+
+impl Ssao {
+    /// Create the ambient occlusion pass for window buffers with some number of samples, unless it is disabled
+    pub fn new(device: &wgpu::Device, quality: AmbientOcclusion, sample_count: u32) -> Option<Self> {
+        if quality == AmbientOcclusion::Off {
+            return None;
+        }
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: SSAO_UNIFORM_SIZE,
+            usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 0.0,
+            compare: None,
+            anisotropy_clamp: None,
+        });
+        let occlusion_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size: None },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    // depth buffer
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: sample_count > 1,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let occlusion_pipeline = create_fullscreen_pipeline(
+            device,
+            &occlusion_layout,
+            "assets/shaders/ssao.frag",
+            if sample_count > 1 { &["MULTISAMPLED"] } else { &[] },
+            wgpu::ColorStateDescriptor {
+                format: OCCLUSION_FORMAT,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+            1,
+        );
+        // The color of the world is multiplied by the blurred occlusion
+        let composite_pipeline = create_fullscreen_pipeline(
+            device,
+            &composite_layout,
+            "assets/shaders/ssao_composite.frag",
+            &[],
+            wgpu::ColorStateDescriptor {
+                format: crate::window::HDR_FORMAT,
+                color_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::SrcColor,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+            sample_count,
+        );
+        Some(Self {
+            samples: occlusion_samples(quality),
+            uniform,
+            sampler,
+            occlusion_layout,
+            occlusion_pipeline,
+            composite_layout,
+            composite_pipeline,
+            occlusion: None,
+        })
+    }
+
+    /// Darken the pixels of the window whose depth is occluded.
+    /// `projection` is the projection matrix of the camera, with the depth range of wgpu.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: WindowBuffers,
+        window_size: (u32, u32),
+        projection: Matrix4<f32>,
+    ) {
+        // The occlusion texture follows the size of the window
+        if self.occlusion.as_ref().map(|(size, _, _)| *size) != Some(window_size) {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: window_size.0,
+                    height: window_size.1,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: OCCLUSION_FORMAT,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.composite_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+            self.occlusion = Some((window_size, view, bind_group));
+        }
+        let (_, occlusion_view, composite_bind_group) = self.occlusion.as_ref().unwrap();
+
+        // Update the uniform
+        let inverse_projection = projection.try_inverse().unwrap_or_else(Matrix4::identity);
+        let mut uniform = [0.0f32; SSAO_UNIFORM_SIZE as usize / 4];
+        uniform[0..16].copy_from_slice(projection.as_slice());
+        uniform[16..32].copy_from_slice(inverse_projection.as_slice());
+        uniform[32..35].copy_from_slice(&[self.samples as f32, SSAO_RADIUS, SSAO_STRENGTH]);
+        let src_buffer = buffer_from_slice(device, wgpu::BufferUsage::COPY_SRC, to_u8_slice(&uniform));
+        encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform, 0, SSAO_UNIFORM_SIZE);
+
+        // The depth buffer is replaced when the window is resized, so its bind group is created every frame
+        let occlusion_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.occlusion_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(self.uniform.slice(..)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(buffers.depth_buffer),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        // Compute the occlusion
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: occlusion_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.occlusion_pipeline);
+            rpass.set_bind_group(0, &occlusion_bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+
+        // Blur it onto the color of the world
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: buffers.hdr_multisampled_texture_buffer,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.composite_pipeline);
+            rpass.set_bind_group(0, composite_bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+}
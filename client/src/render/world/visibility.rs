@@ -0,0 +1,142 @@
+//! Occlusion culling of the chunks with a visibility graph.
+//!
+//! Every chunk records which pairs of its faces are connected by the transparent blocks inside it. The chunks are then
+//! flooded from the chunk of the camera, only going from a face through which a chunk was entered to a face connected
+//! to it, so that the chunks behind the mountains and the caves that are closed on all sides are not drawn.
+//! (cf: https://tomcc.github.io/2014/08/31/visibility-1.html)
+use history_survival_common::world::{ChunkPos, CHUNK_SIZE};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Offsets of the chunks on the sides of a chunk, in the order of the faces: +x, -x, +y, -y, +z, -z
+const FACE_OFFSETS: [(i64, i64, i64); 6] = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+
+/// The pairs of faces of a chunk that are connected by its transparent blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkVisibility {
+    /// Bit `6 * a + b` is set when the faces `a` and `b` are connected
+    connections: u64,
+}
+
+impl ChunkVisibility {
+    /// A chunk through which every face sees every other face, like a chunk of air
+    pub const OPEN: ChunkVisibility = ChunkVisibility { connections: (1 << 36) - 1 };
+
+    /// Compute the visibility of a chunk from the opacity of its blocks, by flooding its transparent blocks
+    pub fn compute(is_opaque: impl Fn(u32, u32, u32) -> bool) -> Self {
+        const SIZE: usize = CHUNK_SIZE as usize;
+        let index = |x: usize, y: usize, z: usize| (x * SIZE + y) * SIZE + z;
+        let mut visited = vec![false; SIZE * SIZE * SIZE];
+        for x in 0..SIZE {
+            for y in 0..SIZE {
+                for z in 0..SIZE {
+                    visited[index(x, y, z)] = is_opaque(x as u32, y as u32, z as u32);
+                }
+            }
+        }
+        let mut connections = 0;
+        let mut stack = Vec::new();
+        for start in 0..visited.len() {
+            if visited[start] {
+                continue;
+            }
+            // Flood the transparent blocks connected to the start, and collect the faces of the chunk they touch
+            let mut faces = 0u8;
+            visited[start] = true;
+            stack.push((start / (SIZE * SIZE), start / SIZE % SIZE, start % SIZE));
+            while let Some((x, y, z)) = stack.pop() {
+                let coords = [x, y, z];
+                for (face, &(dx, dy, dz)) in FACE_OFFSETS.iter().enumerate() {
+                    let axis = face / 2;
+                    if (dx + dy + dz > 0 && coords[axis] == SIZE - 1) || (dx + dy + dz < 0 && coords[axis] == 0) {
+                        faces |= 1 << face;
+                        continue;
+                    }
+                    let (nx, ny, nz) = ((x as i64 + dx) as usize, (y as i64 + dy) as usize, (z as i64 + dz) as usize);
+                    if !visited[index(nx, ny, nz)] {
+                        visited[index(nx, ny, nz)] = true;
+                        stack.push((nx, ny, nz));
+                    }
+                }
+            }
+            for a in 0..6 {
+                for b in 0..6 {
+                    if faces & (1 << a) != 0 && faces & (1 << b) != 0 {
+                        connections |= 1 << (6 * a + b);
+                    }
+                }
+            }
+        }
+        Self { connections }
+    }
+
+    /// Whether some face of the chunk can be seen from another one
+    pub fn connects(self, a: usize, b: usize) -> bool {
+        self.connections & (1 << (6 * a + b)) != 0
+    }
+}
+
+/// The chunks that can be seen from the chunk of the camera, among the chunks for which `is_candidate` is true.
+/// The chunks that are missing from `visibility` are considered open, up to `radius` chunks away from the camera.
+pub fn visible_chunks(
+    camera_chunk: ChunkPos,
+    visibility: &HashMap<ChunkPos, ChunkVisibility>,
+    radius: i64,
+    is_candidate: impl Fn(ChunkPos) -> bool,
+) -> HashSet<ChunkPos> {
+    let mut visible = HashSet::new();
+    visible.insert(camera_chunk);
+    // The chunks to visit, with the face through which they were entered and the directions taken to reach them
+    let mut queue = VecDeque::new();
+    queue.push_back((camera_chunk, None, 0u8));
+    while let Some((chunk_pos, entry_face, directions)) = queue.pop_front() {
+        let chunk_visibility = visibility.get(&chunk_pos).copied().unwrap_or(ChunkVisibility::OPEN);
+        for (face, &(dx, dy, dz)) in FACE_OFFSETS.iter().enumerate() {
+            // Going back towards the camera can't reveal anything that was not already reached
+            if directions & (1 << (face ^ 1)) != 0 {
+                continue;
+            }
+            if let Some(entry_face) = entry_face {
+                if !chunk_visibility.connects(entry_face, face) {
+                    continue;
+                }
+            }
+            let next = chunk_pos.offset(dx, dy, dz);
+            let distance = (next.px - camera_chunk.px).abs().max((next.py - camera_chunk.py).abs()).max((next.pz - camera_chunk.pz).abs());
+            if distance > radius || visible.contains(&next) || !is_candidate(next) {
+                continue;
+            }
+            visible.insert(next);
+            queue.push_back((next, Some(face ^ 1), directions | (1 << face)));
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walls_separate_the_faces() {
+        // A wall across the middle of the chunk, perpendicular to x
+        let wall = ChunkVisibility::compute(|x, _, _| x == CHUNK_SIZE / 2);
+        assert!(!wall.connects(0, 1));
+        assert!(wall.connects(2, 3) && wall.connects(0, 2) && wall.connects(1, 4));
+        assert_eq!(ChunkVisibility::compute(|_, _, _| false), ChunkVisibility::OPEN);
+        assert!(!ChunkVisibility::compute(|_, _, _| true).connects(2, 2));
+    }
+
+    #[test]
+    fn closed_chunks_hide_the_chunks_behind_them() {
+        let camera = ChunkPos { px: 0, py: 0, pz: 0 };
+        let mut visibility = HashMap::new();
+        // A closed chunk on the +x side of the camera
+        visibility.insert(camera.offset(1, 0, 0), ChunkVisibility { connections: 0 });
+        let visible = visible_chunks(camera, &visibility, 3, |_| true);
+        assert!(visible.contains(&camera.offset(1, 0, 0)));
+        assert!(!visible.contains(&camera.offset(2, 0, 0)));
+        // The chunks behind it can still be seen around it
+        assert!(visible.contains(&camera.offset(2, 1, 0)));
+        assert!(visible.contains(&camera.offset(-3, 0, 0)));
+    }
+}
@@ -0,0 +1,82 @@
+//! The automatic render distance, that drops the farthest chunks while the frames take longer than a budget
+//! and brings them back once the frames are fast again.
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// The horizontal render distances that can be chosen in the options, in chunks
+pub const HORIZONTAL_RENDER_DISTANCES: RangeInclusive<u64> = 2..=32;
+/// How quickly the average frame time follows the duration of the frames, per second
+const AVERAGE_SPEED: f64 = 2.0;
+/// Minimum time between a change of the render distance and its next reduction, so that the frame time settles
+const REDUCE_COOLDOWN: Duration = Duration::from_secs(3);
+/// Minimum time between a change of the render distance and its next increase. It is longer than the cooldown
+/// of the reductions so that the render distance doesn't keep going up and down around the budget.
+const RAISE_COOLDOWN: Duration = Duration::from_secs(10);
+/// The render distance is raised back when the frames take less than this fraction of the budget
+const RAISE_FRACTION: f64 = 0.6;
+
+/// By how many chunks the render distance is reduced to keep the frame time under a budget
+#[derive(Debug, Default)]
+pub struct AutoRenderDistance {
+    average_frame_time: Option<f64>,
+    /// Time since the last change of the reduction, in seconds
+    since_change: f64,
+    reduction: u64,
+}
+
+impl AutoRenderDistance {
+    pub fn reduction(&self) -> u64 {
+        self.reduction
+    }
+
+    /// Record the duration of a frame, returning whether the reduction changed.
+    /// The reduction stays below `max_reduction`, so that some chunks are still rendered.
+    pub fn update(&mut self, seconds_delta: f64, budget: Duration, max_reduction: u64) -> bool {
+        let average = match self.average_frame_time {
+            Some(average) => average + (seconds_delta - average) * (AVERAGE_SPEED * seconds_delta).min(1.0),
+            None => seconds_delta,
+        };
+        self.average_frame_time = Some(average);
+        self.since_change += seconds_delta;
+        let budget = budget.as_secs_f64();
+        let reduction = if self.reduction > max_reduction {
+            max_reduction
+        } else if average > budget && self.reduction < max_reduction && self.since_change >= REDUCE_COOLDOWN.as_secs_f64() {
+            self.reduction + 1
+        } else if average < budget * RAISE_FRACTION && self.reduction > 0 && self.since_change >= RAISE_COOLDOWN.as_secs_f64() {
+            self.reduction - 1
+        } else {
+            return false;
+        };
+        self.reduction = reduction;
+        self.since_change = 0.0;
+        true
+    }
+
+    /// Stop reducing the render distance, returning whether it was reduced
+    pub fn reset(&mut self) -> bool {
+        let was_reduced = self.reduction > 0;
+        *self = Self::default();
+        was_reduced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_frames_reduce_the_render_distance_until_they_are_fast() {
+        let budget = Duration::from_millis(20);
+        let mut auto = AutoRenderDistance::default();
+        // 0.1 s frames are over the budget: one chunk is dropped every 3 seconds, up to the maximum
+        let changes = (0..100).filter(|_| auto.update(0.1, budget, 2)).count();
+        assert_eq!((changes, auto.reduction()), (2, 2));
+        // Frames that fit the budget without much room keep the reduction
+        assert!(!(0..200).any(|_| auto.update(0.015, budget, 2)));
+        // Fast frames bring the chunks back one by one
+        let changes = (0..2000).filter(|_| auto.update(0.01, budget, 2)).count();
+        assert_eq!((changes, auto.reduction()), (2, 0));
+        assert!(!auto.reset());
+    }
+}
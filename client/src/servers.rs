@@ -0,0 +1,73 @@
+//! The servers saved in the multiplayer screen.
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The servers saved in the multiplayer screen, in the order in which they are shown
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ServerList {
+    pub servers: Vec<SavedServer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedServer {
+    /// The host of the server, optionally followed by its port, like "example.com:36363"
+    pub address: String,
+}
+
+impl ServerList {
+    /// Read the saved servers, or start with no servers if the file doesn't exist or can't be read
+    pub fn load(path: &Path) -> Self {
+        if !path.is_file() {
+            return Self::default();
+        }
+        let read = std::fs::read_to_string(path)
+            .context(format!("Failed to read server list file {}", path.display()))
+            .and_then(|string| toml::de::from_str(&string).context(format!("Failed to parse server list file {}", path.display())));
+        match read {
+            Ok(servers) => servers,
+            Err(error) => {
+                warn!("{:?}", error);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        info!("Writing the server list to {}", path.display());
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder)?;
+        }
+        let string = toml::ser::to_string(self).context("Failed to serialize the server list")?;
+        std::fs::write(path, string).context(format!("Failed to write server list file {}", path.display()))
+    }
+
+    /// Save a server after the others, unless it is already saved. Return whether it was added.
+    pub fn add(&mut self, address: &str) -> bool {
+        let address = address.trim();
+        if address.is_empty() || self.servers.iter().any(|server| server.address == address) {
+            return false;
+        }
+        self.servers.push(SavedServer { address: address.to_owned() });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn servers_are_saved_once() {
+        let mut list = ServerList::default();
+        assert!(list.add(" 127.0.0.1:1234 "));
+        assert!(!list.add("127.0.0.1:1234"));
+        assert!(!list.add(""));
+        assert!(list.add("localhost"));
+        let string = toml::ser::to_string(&list).unwrap();
+        assert_eq!(toml::de::from_str::<ServerList>(&string).unwrap(), list);
+        assert_eq!(list.servers[0].address, "127.0.0.1:1234");
+    }
+}
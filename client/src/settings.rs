@@ -1,12 +1,22 @@
+use crate::keybindings::KeyBindings;
+use crate::lang::DEFAULT_LANGUAGE;
 use anyhow::{Context, Result};
-use log::info;
+use history_survival_common::player::RenderDistance;
+use log::{error, info};
 use serde::{Deserialize, Serialize};
 use std::{
     fs::OpenOptions,
     io::{Read, Write},
     path::Path,
+    time::Duration,
 };
 
+/// The file where the settings are read at startup and written when they change
+pub const SETTINGS_FILE: &str = "config/settings.toml";
+/// The scales of the UI are kept in this range, so that the menus stay usable
+const MIN_UI_SCALE: f64 = 0.5;
+const MAX_UI_SCALE: f64 = 4.0;
+
 pub fn load_settings(folder_path: &Path, file_path: &Path) -> Result<Settings> {
     info!(
         "Reading settings from folder path {} and file path {}...",
@@ -41,8 +51,6 @@ pub fn load_settings(folder_path: &Path, file_path: &Path) -> Result<Settings> {
         settings
     };
 
-    // TODO: write settings
-
     Ok(settings)
 }
 
@@ -63,21 +71,315 @@ fn write_settings(path: impl AsRef<Path>, settings: &Settings) -> Result<()> {
     Ok(())
 }
 
+/// Write the settings to the settings file, logging the errors since the game can go on without saving them
+pub fn save_settings(settings: &Settings) {
+    if let Err(error) = write_settings(SETTINGS_FILE, settings) {
+        error!("{:?}", error);
+    }
+}
+
 /// Settings of the game
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(default)]
 pub struct Settings {
     pub window_size: (u32, u32),
+    /// Whether the window covers the whole screen without borders. It is toggled with F11 by default.
+    pub fullscreen: bool,
     pub invert_mouse: bool,
+    /// How fast the camera turns when the mouse moves, 1 being the default speed
+    pub mouse_sensitivity: f64,
+    /// Vertical field of view of the camera, in degrees
+    pub fov: f64,
+    /// How much the field of view widens while sprinting, in degrees
+    pub sprint_fov_increase: f64,
+    /// Vertical field of view while the zoom key is held, in degrees
+    pub zoom_fov: f64,
+    /// Whether the view bobs while walking
+    pub view_bobbing: bool,
+    /// Whether the camera follows the movements and the turns of the player with some delay
+    pub camera_smoothing: bool,
     pub render_distance: (u64, u64, u64, u64, u64, u64),
+    /// Whether the render distance is lowered while the frames take longer than `frame_time_budget_ms`
+    pub auto_render_distance: bool,
+    /// Duration of a frame above which the automatic render distance drops the farthest chunks, in milliseconds
+    pub frame_time_budget_ms: u64,
+    /// Duration of a frame above which a hitch report is written, in milliseconds
+    pub hitch_threshold_ms: u64,
+    /// Maximum size of each of the GPU buffers of the chunk meshes, in megabytes.
+    /// The render distance is reduced when the chunk meshes don't fit.
+    pub chunk_buffer_budget_mb: u64,
+    /// Whether the coplanar faces of the chunks are merged into larger quads.
+    /// Disabling it only makes sense to compare the vertex counts.
+    pub greedy_meshing: bool,
+    /// Whether the chunks hidden behind other chunks are skipped. It is toggled with Ctrl+K by default.
+    pub occlusion_culling: bool,
+    /// Whether the light is `flat` on every face or `smooth` across the faces
+    pub lighting: Lighting,
+    pub clouds: CloudSettings,
+    pub textures: TextureSettings,
+    /// Whether the sun casts shadows. The chunks are drawn once more for every cascade of shadows.
+    pub shadows: bool,
+    /// How precisely the corners and the crevices are darkened from the depth of the screen
+    pub ambient_occlusion: AmbientOcclusion,
+    pub post_processing: PostProcessingSettings,
+    /// How many samples of every pixel smooth the edges of the polygons: `off`, `2x` or `4x`
+    pub antialiasing: Antialiasing,
+    /// How the frames are shown: `on` waits for the screen, `off` shows them right away and may tear,
+    /// `mailbox` shows the latest frame when the screen refreshes without waiting
+    pub vsync: Vsync,
+    /// Maximum number of frames per second, if any
+    pub max_fps: Option<u32>,
+    /// Maximum number of frames per second while the mouse is free in a menu or the window is in the background
+    pub menu_max_fps: Option<u32>,
+    /// Number of physical pixels per pixel of the UI, to make it larger on the screens with a high density of pixels.
+    /// If it isn't set, it follows the scale factor of the screen.
+    pub ui_scale: Option<f64>,
+    /// Code of the language of the texts, the name of a file of `data/lang`
+    pub language: String,
+    /// Whether the pipelines of the world are rebuilt when the shaders in `assets/shaders` change, to develop them
+    pub shader_hot_reload: bool,
+    pub audio: AudioSettings,
+    pub key_bindings: KeyBindings,
+}
+
+/// The volumes of the sounds, from 0 for silent to 1 for full volume
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Volume of every sound, multiplied with the volumes of the music and of the effects
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 0.7,
+            effects_volume: 1.0,
+        }
+    }
+}
+
+/// How the light of the blocks is spread on their faces
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Lighting {
+    /// Every face has the light of the block in front of it
+    Flat,
+    /// Every corner of a face has the average light of the blocks around it, and the light is interpolated in between
+    Smooth,
+}
+
+/// The layer of clouds above the world
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct CloudSettings {
+    /// Height of the clouds, in blocks
+    pub height: f32,
+    /// Fraction of the sky that the clouds cover, from 0 for no clouds to 1 for an overcast sky
+    pub density: f32,
+}
+
+impl Default for CloudSettings {
+    fn default() -> Self {
+        Self {
+            height: 192.0,
+            density: 0.4,
+        }
+    }
+}
+
+/// How the textures of the world are filtered
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct TextureSettings {
+    /// Whether the texels are drawn as sharp squares (`nearest`) or blended with their neighbours (`linear`)
+    pub filter: TextureFilter,
+    /// Whether the distant textures are drawn from smaller copies of the texture atlas, which stops them from shimmering
+    pub mipmaps: bool,
+    /// Maximum number of samples of the textures seen at grazing angles: 1, 2, 4, 8 or 16.
+    /// 1 disables the anisotropic filtering, which is also ignored by the GPUs that don't support it.
+    pub anisotropy: u8,
+}
+
+impl Default for TextureSettings {
+    fn default() -> Self {
+        Self {
+            filter: TextureFilter::Nearest,
+            mipmaps: true,
+            anisotropy: 4,
+        }
+    }
+}
+
+/// How a texture is sampled between its texels
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+/// The quality of the screen-space ambient occlusion, which adds to the occlusion at the corners of the faces
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AmbientOcclusion {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+/// The effects applied to the picture of the world before the interface is drawn over it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct PostProcessingSettings {
+    /// Multiplier of the colors before they are mapped to the colors of the screen
+    pub exposure: f32,
+    /// Whether the brightest parts of the picture bleed light into their surroundings
+    pub bloom: bool,
+    /// How much the corners of the picture are darkened, from 0 for not at all to 1 for black corners
+    pub vignette: f32,
+    /// Gamma correction of the picture after the tonemapping. Above 1, the dark colors are brightened,
+    /// which helps to see in the caves and at night. 1 leaves the picture as it is.
+    pub gamma: f32,
+}
+
+impl Default for PostProcessingSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            bloom: true,
+            vignette: 0.3,
+            gamma: 1.0,
+        }
+    }
+}
+
+/// The multisample anti-aliasing of the window buffers
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Antialiasing {
+    #[serde(rename = "off")]
+    Off,
+    #[serde(rename = "2x")]
+    X2,
+    #[serde(rename = "4x")]
+    X4,
+}
+
+impl Antialiasing {
+    /// Number of samples of every pixel of the window buffers
+    pub fn sample_count(self) -> u32 {
+        match self {
+            Antialiasing::Off => 1,
+            Antialiasing::X2 => 2,
+            Antialiasing::X4 => 4,
+        }
+    }
+}
+
+/// How the frames are presented to the screen
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Vsync {
+    On,
+    Off,
+    Mailbox,
+}
+
+impl Vsync {
+    pub fn present_mode(self) -> wgpu::PresentMode {
+        match self {
+            Vsync::On => wgpu::PresentMode::Fifo,
+            Vsync::Off => wgpu::PresentMode::Immediate,
+            Vsync::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+impl Settings {
+    /// The maximum number of frames per second, lower in the menus
+    pub fn frame_rate_cap(&self, in_menu: bool) -> Option<u32> {
+        match (self.max_fps, self.menu_max_fps.filter(|_| in_menu)) {
+            (Some(max_fps), Some(menu_max_fps)) => Some(max_fps.min(menu_max_fps)),
+            (max_fps, menu_max_fps) => max_fps.or(menu_max_fps),
+        }
+    }
+
+    /// The scale of the UI on a screen with some scale factor
+    pub fn ui_scale(&self, hidpi_factor: f64) -> f64 {
+        let scale = self.ui_scale.unwrap_or(hidpi_factor);
+        if scale.is_finite() {
+            scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+        } else {
+            1.0
+        }
+    }
+
+    pub fn hitch_threshold(&self) -> Duration {
+        Duration::from_millis(self.hitch_threshold_ms)
+    }
+
+    pub fn frame_time_budget(&self) -> Duration {
+        Duration::from_millis(self.frame_time_budget_ms)
+    }
+
+    /// The render distance to request from the server
+    pub fn get_render_distance(&self) -> RenderDistance {
+        let (x_max, x_min, y_max, y_min, z_max, z_min) = self.render_distance;
+        RenderDistance {
+            x_max,
+            x_min,
+            y_max,
+            y_min,
+            z_max,
+            z_min,
+        }
+    }
+
+    /// Set the render distance towards the sides, keeping the vertical render distance
+    pub fn set_horizontal_render_distance(&mut self, distance: u64) {
+        let (_, _, y_max, y_min, _, _) = self.render_distance;
+        self.render_distance = (distance, distance, y_max, y_min, distance, distance);
+    }
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             window_size: (1600, 900),
+            fullscreen: false,
             invert_mouse: false,
+            mouse_sensitivity: 1.0,
+            fov: 90.0,
+            sprint_fov_increase: 10.0,
+            zoom_fov: 30.0,
+            view_bobbing: true,
+            camera_smoothing: false,
             render_distance: (10, 10, 10, 10, 10, 10),
+            auto_render_distance: false,
+            frame_time_budget_ms: 33,
+            hitch_threshold_ms: 100,
+            chunk_buffer_budget_mb: 512,
+            greedy_meshing: true,
+            occlusion_culling: true,
+            lighting: Lighting::Smooth,
+            clouds: CloudSettings::default(),
+            textures: TextureSettings::default(),
+            shadows: false,
+            ambient_occlusion: AmbientOcclusion::Medium,
+            post_processing: PostProcessingSettings::default(),
+            antialiasing: Antialiasing::X4,
+            vsync: Vsync::Mailbox,
+            max_fps: None,
+            menu_max_fps: Some(60),
+            ui_scale: None,
+            language: DEFAULT_LANGUAGE.to_owned(),
+            shader_hot_reload: false,
+            audio: AudioSettings::default(),
+            key_bindings: KeyBindings::default(),
         }
     }
 }
@@ -1,36 +1,106 @@
 use anyhow::Result;
-use log::info;
+use log::{info, warn};
 
 use history_survival_common::{
-    block::Block,
-    network::{messages::ToClient, messages::ToServer, Client, ClientEvent},
-    player::RenderDistance,
+    block::{breaking::get_break_time, entity::BlockEntity, get_climbable_blocks, item_frame::ItemFrame, painting::PaintingLimits, Block, BlockId},
+    inventory::{Inventory, ItemStack, HOTBAR_SIZE, PLAYER_INVENTORY_SIZE},
+    item::map::MapId,
+    network::{
+        messages::{InteractionAction, InteractionTarget, ToClient, ToServer},
+        Client, ClientEvent,
+    },
+    player::{DeathCause, GameMode, PlayerStats, RenderDistance},
     registry::Registry,
-    world::BlockPos,
+    world::{BlockPos, CHUNK_SIZE},
 };
 
 use history_survival_common::physics::player::YawPitch;
 //use crate::model::model::Model;
 //use crate::world::meshing::ChunkMeshData;
-use crate::render::{Frustum, UiRenderer, WorldRenderer};
+use crate::render::{DebugRenderMode, Frustum, UiRenderer, WorldRenderer};
 use crate::window::WindowBuffers;
 use crate::{
+    box_models::BoxModels,
+    camera::{CameraMode, SmoothCamera, ViewBobbing},
+    consistency::ConsistencyChecker,
+    entities::InterpolatedEntities,
+    fov::{target_fov, FieldOfView},
     fps::FpsCounter,
+    hand::{held_item_model, HandSwing},
+    weather::WeatherParticles,
     input::InputState,
-    settings::Settings,
+    keybindings::{Action, InputContext},
+    item_icons::{get_item_mesh_id, get_item_texture, IconContext},
+    render_distance::{AutoRenderDistance, HORIZONTAL_RENDER_DISTANCES},
+    settings::{save_settings, Settings},
     ui::Ui,
     window::{State, StateTransition, WindowData, WindowFlags},
     world::World,
 };
 use nalgebra::Vector3;
-use std::time::Instant;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use history_survival_common::data::vox::VoxelModel;
-use history_survival_common::debug::{send_debug_info, send_perf_breakdown, DebugInfo};
+use history_survival_common::debug::{send_debug_info, send_perf_breakdown, send_time_graph, DebugInfo, TIME_GRAPH_SAMPLES};
+use history_survival_common::entity::{EntityKind, VehicleKind};
+use history_survival_common::advancement::{Advancement, AdvancementId};
+use history_survival_common::crafting::Recipe;
 use history_survival_common::item::{Item, ItemMesh};
+use history_survival_common::physics::raycast::raycast;
 use history_survival_common::physics::simulation::{ClientPhysicsSimulation, PhysicsState, ServerState};
-use history_survival_common::time::BreakdownCounter;
+use history_survival_common::physics::{aabb::AABB, entity::find_pointed_entity, vehicle::MOUNT_RANGE};
+use history_survival_common::time::{BreakdownCounter, WorldClock, WorldTime};
+use history_survival_common::watchdog::Watchdog;
 use winit::event::{ElementState, MouseButton};
-use crate::gui::Gui;
+use crate::chat::Chat;
+use crate::loading::SpawnAreaLoading;
+use crate::gui::{
+    banner_editor::{render_banner_editor, BannerEditor, BannerEditorResult},
+    chat::{render_chat, CHAT_LINES},
+    chest::{render_chest_screen, ChestScreen, ChestScreenResult},
+    death_screen::{render_death_screen, DeathScreenButton},
+    furnace::{render_furnace_screen, FurnaceScreen, FurnaceScreenResult},
+    inventory::{render_inventory_screen, InventoryScreen, InventoryScreenResult},
+    loading::render_loading_screen,
+    options::{render_options, OptionsMenu, SettingsChange},
+    painting_picker::{render_painting_picker, PaintingPicker, PaintingPickerResult},
+    pause_menu::{render_options_back_button, render_pause_menu, PauseMenuButton},
+    Gui,
+};
+
+/// Size of a voxel of the vehicle models, in blocks
+const VEHICLE_MODEL_SCALE: f32 = 1.0 / 16.0;
+/// Size of the models of the dropped items, in blocks
+const DROPPED_ITEM_MODEL_SIZE: f32 = 0.35;
+/// Size of the models of the framed items, in blocks
+const FRAMED_ITEM_MODEL_SIZE: f32 = 0.6;
+/// Distance between the framed items and their frame, in blocks
+const FRAMED_ITEM_OFFSET: f32 = 0.05;
+/// Time between two blocks broken by holding the left button in creative mode
+const CREATIVE_BREAK_INTERVAL: Duration = Duration::from_millis(150);
+/// How long the hitches are shown on the screen
+const WARNING_TOAST_DURATION: Duration = Duration::from_secs(4);
+/// Minimum time between two reductions of the render distance when the chunk meshes don't fit in the GPU memory,
+/// so that the far chunks have time to make room
+const MEMORY_PRESSURE_COOLDOWN: Duration = Duration::from_secs(2);
+/// How long an earned advancement is shown
+const ADVANCEMENT_TOAST_DURATION: Duration = Duration::from_secs(5);
+/// The keys that close the open chat and scroll its history
+const ESCAPE_SCANCODE: u32 = 1;
+const PAGE_UP_SCANCODE: u32 = 104;
+const PAGE_DOWN_SCANCODE: u32 = 109;
+
+// TODO: refactor
+const D: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
 
 /// State of a singleplayer world
 pub struct SinglePlayer {
@@ -39,29 +109,123 @@ pub struct SinglePlayer {
     ui_renderer: UiRenderer,
     gui: Gui,
     world: World,
-    #[allow(dead_code)] // TODO: remove this
     block_registry: Registry<Block>,
     item_registry: Registry<Item>,
+    recipe_registry: Registry<Recipe>,
+    advancement_registry: Registry<Advancement>,
     item_meshes: Vec<ItemMesh>,
     model_registry: Registry<VoxelModel>,
     client: Box<dyn Client>,
+    /// The thread of the integrated server of a local world, which stops once the world is saved after the player left
+    server_thread: Option<JoinHandle<()>>,
+    /// The render distance the server sends chunks for, lower than the one in the settings if the connection is too slow
     render_distance: RenderDistance,
+    /// The render distance requested from the server: the one in the settings, reduced if the chunk meshes don't fit
+    /// in the GPU memory or if the frames take too long
+    requested_render_distance: RenderDistance,
+    /// By how many chunks the render distance is reduced because the chunk meshes didn't fit in the GPU memory,
+    /// and when it was last reduced
+    memory_reduction: u64,
+    last_memory_reduction: Option<Instant>,
+    auto_render_distance: AutoRenderDistance,
+    /// The change of the settings made in the menu, applied to the settings during the next update
+    settings_change: Option<SettingsChange>,
+    options_menu: OptionsMenu,
+    chat: Chat,
+    /// The loading of the terrain around the player, `None` once the player can play
+    loading: Option<SpawnAreaLoading>,
+    /// What killed the player, `None` while it is alive. The death screen is shown until the player respawns.
+    death: Option<DeathCause>,
     // TODO: put this in the settigs
     physics_simulation: ClientPhysicsSimulation,
     debug_info: DebugInfo,
     start_time: Instant,
     client_timing: BreakdownCounter,
+    /// Reports the frames that take too long
+    watchdog: Watchdog,
+    /// A warning shown for a few seconds, like the slowest part of the last hitch, and when it happened
+    warning_toast: Option<(String, Instant)>,
+    /// The earned advancements, shown one after the other, and since when the first one is shown
+    advancement_toasts: VecDeque<AdvancementId>,
+    advancement_toast_since: Option<Instant>,
+    /// The position the compass points to
+    compass_target: Option<BlockPos>,
+    /// The world time, extrapolated from the last update sent by the server
+    world_clock: Option<WorldClock>,
+    /// The rendered entity positions
+    interpolated_entities: InterpolatedEntities,
+    /// The rain and snow around the camera
+    weather_particles: WeatherParticles,
+    /// The models of the mobs and of the other players
+    box_models: BoxModels,
+    /// Repairs the holes in the world
+    consistency_checker: ConsistencyChecker,
+    /// The inventory of the player, as sent by the server
+    inventory: Inventory,
+    /// The health and hunger of the player, as sent by the server
+    player_stats: PlayerStats,
+    /// When the player last lost health, to flash the hearts
+    damaged_at: Option<Instant>,
+    /// The game mode of the player, as sent by the server
+    game_mode: GameMode,
+    /// The hotbar slot that was last sent to the server
+    hotbar_slot: usize,
+    /// The swing of the held item
+    hand_swing: HandSwing,
+    /// The field of view of the camera, which follows the sprint and the zoom
+    fov: FieldOfView,
+    /// Whether the camera is at the eyes of the player, behind it or in front of it
+    camera_mode: CameraMode,
+    /// The camera that follows the eyes of the player
+    smooth_camera: SmoothCamera,
+    view_bobbing: ViewBobbing,
+    /// What the world renderer draws to debug the meshing and the physics
+    debug_render_mode: DebugRenderMode,
+    /// The blocks that the players are breaking, with the stage of their cracks
+    break_stages: Vec<(BlockPos, u8)>,
+    /// The block the player is breaking, and when it started
+    breaking: Option<(BlockPos, Instant)>,
+    /// The editor of the banner or the painting the player is decorating, if any
+    block_editor: Option<BlockEditor>,
+    /// How large the images of the paintings can be, as sent by the server
+    painting_limits: PaintingLimits,
+    /// The maps whose canvas was requested for the item frames
+    requested_maps: HashSet<MapId>,
+}
+
+/// An editor of a block entity or the inventory of the player, that uses the mouse instead of the camera
+enum BlockEditor {
+    Banner(BannerEditor),
+    Painting(PaintingPicker),
+    Chest(ChestScreen),
+    Furnace(FurnaceScreen),
+    Inventory(InventoryScreen),
+}
+
+impl BlockEditor {
+    /// Whether the edited block entity is still there
+    fn is_still_valid(&self, world: &World) -> bool {
+        match self {
+            BlockEditor::Banner(editor) => matches!(world.get_block_entity(editor.pos), Some(BlockEntity::Banner(_))),
+            BlockEditor::Painting(picker) => matches!(world.get_block_entity(picker.pos), Some(BlockEntity::Painting(_))),
+            BlockEditor::Chest(screen) => matches!(world.get_block_entity(screen.pos), Some(BlockEntity::Chest(_))),
+            BlockEditor::Furnace(screen) => matches!(world.get_block_entity(screen.pos), Some(BlockEntity::Furnace(_))),
+            BlockEditor::Inventory(_) => true,
+        }
+    }
 }
 
 impl SinglePlayer {
-    pub fn new_factory(client: Box<dyn Client>) -> crate::window::StateFactory {
-        Box::new(move |settings, device| Self::new(settings, device, client))
+    /// `server_thread` is the thread of the integrated server of a local world, `None` for a remote server
+    pub fn new_factory(client: Box<dyn Client>, server_thread: Option<JoinHandle<()>>) -> crate::window::StateFactory {
+        Box::new(move |settings, device| Self::new(settings, device, client, server_thread))
     }
 
     pub fn new(
         settings: &mut Settings,
         device: &mut wgpu::Device,
         mut client: Box<dyn Client>,
+        server_thread: Option<JoinHandle<()>>,
     ) -> Result<(Box<dyn State>, wgpu::CommandBuffer)> {
         info!("Launching singleplayer");
         // Wait for data and player_id from the server
@@ -84,28 +248,39 @@ impl SinglePlayer {
         info!("Received game data from the server");
 
         // Set render distance
-        let (x1, x2, y1, y2, z1, z2) = settings.render_distance;
-        let render_distance = RenderDistance {
-            x_max: x1,
-            x_min: x2,
-            y_max: y1,
-            y_min: y2,
-            z_max: z1,
-            z_min: z2,
-        };
+        let render_distance = settings.get_render_distance();
         client.send(ToServer::SetRenderDistance(render_distance));
         // Create the renderers
-        let ui_renderer = UiRenderer::new(device);
+        let mut ui_renderer = UiRenderer::new(device, settings.antialiasing.sample_count());
 
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        let world_renderer = WorldRenderer::new(
+        // The item frames draw the canvas of these items
+        let map_items = (0..data.items.get_number_of_ids())
+            .filter_map(|item_id| data.items.get_value_by_id(item_id))
+            .filter(|item| item.is_map())
+            .map(|item| item.name.clone())
+            .collect();
+
+        let mut world_renderer = WorldRenderer::new(
             device,
             &mut encoder,
-            data.texture_atlas,
+            &data.texture_atlas,
             &data.models,
+            settings.chunk_buffer_budget_mb * 1024 * 1024,
+            settings.clouds,
+            settings.textures,
+            settings.shadows,
+            settings.ambient_occlusion,
+            settings.post_processing,
+            settings.antialiasing.sample_count(),
         );
+        if settings.shader_hot_reload {
+            world_renderer.enable_shader_hot_reload();
+        }
+        // The hotbar draws the icons of the items from the texture atlas
+        ui_renderer.set_texture_atlas(device, &world_renderer.create_texture_atlas_view());
 
         Ok((
             Box::new(Self {
@@ -113,13 +288,35 @@ impl SinglePlayer {
                 ui: Ui::new(),
                 ui_renderer,
                 gui: Gui::new(),
-                world: World::new(data.meshes.clone(), world_renderer),
+                world: World::new(
+                    data.meshes.clone(),
+                    data.blocks.get_id_by_name(&"water".to_owned()).map(|id| id as BlockId),
+                    get_climbable_blocks(&data.blocks),
+                    data.dynamic_textures.clone(),
+                    map_items,
+                    settings.greedy_meshing,
+                    settings.lighting,
+                    world_renderer,
+                ),
+                box_models: BoxModels::new(&data.models, &data.player_model, &data.mobs),
                 block_registry: data.blocks,
                 model_registry: data.models,
                 item_registry: data.items,
+                recipe_registry: data.recipes,
+                advancement_registry: data.advancements,
                 item_meshes: data.item_meshes,
                 client,
+                server_thread,
                 render_distance,
+                requested_render_distance: render_distance,
+                memory_reduction: 0,
+                last_memory_reduction: None,
+                auto_render_distance: AutoRenderDistance::default(),
+                settings_change: None,
+                options_menu: OptionsMenu::default(),
+                chat: Chat::default(),
+                loading: Some(SpawnAreaLoading::default()),
+                death: None,
                 physics_simulation: ClientPhysicsSimulation::new(
                     ServerState {
                         physics_state: PhysicsState::default(),
@@ -131,65 +328,384 @@ impl SinglePlayer {
                 debug_info: DebugInfo::new_current(),
                 start_time: Instant::now(),
                 client_timing: BreakdownCounter::new(),
+                watchdog: Watchdog::new("client", settings.hitch_threshold()),
+                warning_toast: None,
+                advancement_toasts: VecDeque::new(),
+                advancement_toast_since: None,
+                compass_target: None,
+                world_clock: None,
+                interpolated_entities: InterpolatedEntities::new(),
+                weather_particles: WeatherParticles::new(),
+                consistency_checker: ConsistencyChecker::new(),
+                inventory: Inventory::new(PLAYER_INVENTORY_SIZE),
+                player_stats: Default::default(),
+                damaged_at: None,
+                game_mode: Default::default(),
+                hotbar_slot: 0,
+                hand_swing: HandSwing::default(),
+                fov: FieldOfView::new(settings.fov),
+                camera_mode: CameraMode::default(),
+                smooth_camera: SmoothCamera::default(),
+                view_bobbing: ViewBobbing::default(),
+                debug_render_mode: DebugRenderMode::default(),
+                break_stages: Vec::new(),
+                breaking: None,
+                block_editor: None,
+                painting_limits: Default::default(),
+                requested_maps: HashSet::new(),
             }),
             encoder.finish(),
         ))
     }
 
+    /// Leave the server and wait until the integrated server saved the world, before going back to the main menu
+    fn save_and_quit(&mut self) {
+        info!("Leaving the world");
+        self.client.disconnect();
+        if let Some(server_thread) = self.server_thread.take() {
+            if server_thread.join().is_err() {
+                warn!("The server stopped because of a panic, the world may not be saved");
+            }
+        }
+    }
+
+    /// Draw the loading screen instead of the world, while the meshes of the chunks around the player are built
+    fn render_loading_screen(
+        &mut self,
+        buffers: WindowBuffers,
+        device: &mut wgpu::Device,
+        data: &WindowData,
+        loading: &SpawnAreaLoading,
+    ) -> Result<(StateTransition, wgpu::CommandBuffer)> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.world.get_new_chunk_meshes(device, &mut encoder);
+        crate::render::clear_frame(&mut encoder, buffers);
+        let (window_width, window_height) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+        self.ui.rebuild(&mut self.debug_info, data)?;
+        self.gui.prepare();
+        render_loading_screen(&mut self.gui, window_width, window_height, loading);
+        self.gui.finish();
+        self.ui_renderer.render(buffers, device, &mut encoder, data, &self.ui.ui, &mut self.gui, false);
+        Ok((StateTransition::KeepCurrent, encoder.finish()))
+    }
+
+    /// Close the chat, forgetting the message that wasn't sent
+    fn close_chat(&mut self) {
+        self.ui.close_chat();
+        self.chat.cancel_input();
+        self.gui.clear_focus();
+    }
+
+    /// Whether the mouse is used by a menu or an editor instead of the camera
+    fn is_in_menu(&self) -> bool {
+        !self.ui.should_update_camera() || self.block_editor.is_some() || self.loading.is_some() || self.death.is_some()
+    }
+
+    /// Break the pointed block once the left button was held for long enough
+    fn update_breaking(&mut self, input_state: &InputState) {
+        let pp = self.physics_simulation.get_player();
+        let YawPitch { yaw, pitch } = pp.yaw_pitch;
+        let can_break = self.game_mode.can_edit_world() && !self.is_in_menu();
+        let pointed_block = if input_state.is_mouse_button_pressed(MouseButton::Left) && can_break {
+            let (y, p) = (yaw.to_radians(), pitch.to_radians());
+            let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+            pp.get_pointed_at(dir, 10.0, &self.world)
+        } else {
+            None
+        };
+        let (block, face) = match pointed_block {
+            Some(pointed_block) => pointed_block,
+            None => {
+                if self.breaking.take().is_some() {
+                    self.client.send(ToServer::StopBreaking);
+                }
+                send_debug_info("Player", "breaking", "breaking = None");
+                return;
+            }
+        };
+
+        let now = Instant::now();
+        // The hand keeps swinging while the block breaks
+        self.hand_swing.swing(now);
+        let start = match self.breaking {
+            Some((breaking_block, start)) if breaking_block == block => start,
+            _ => {
+                self.breaking = Some((block, now));
+                self.client.send(ToServer::StartBreaking(pp.aabb.pos, block));
+                now
+            }
+        };
+        let tool = self
+            .inventory
+            .get(self.hotbar_slot)
+            .and_then(|stack| self.item_registry.get_value_by_id(stack.item_id))
+            .and_then(Item::get_tool);
+        let break_time = if self.game_mode.breaks_instantly() {
+            Some(CREATIVE_BREAK_INTERVAL)
+        } else {
+            self.world
+                .get_block(block)
+                .and_then(|block_id| self.block_registry.get_value_by_id(block_id as u32))
+                .and_then(|block_data| get_break_time(block_data, tool))
+        };
+        if let Some(break_time) = break_time {
+            let elapsed = now - start;
+            send_debug_info(
+                "Player",
+                "breaking",
+                format!("breaking = {:.0}%", 100.0 * (elapsed.as_secs_f64() / break_time.as_secs_f64().max(1e-9)).min(1.0)),
+            );
+            if elapsed >= break_time {
+                self.interact(InteractionTarget::Block(block, face), InteractionAction::Attack);
+                // Start breaking again if the block is still there, for example if the server refused the break
+                self.breaking = None;
+            }
+        }
+    }
+
+    /// Interact with the world using the item of the selected hotbar slot
+    fn interact(&mut self, target: InteractionTarget, action: InteractionAction) {
+        let pp = self.physics_simulation.get_player();
+        let YawPitch { yaw, pitch } = pp.yaw_pitch;
+        self.hand_swing.swing(Instant::now());
+        self.client.send(ToServer::Interact {
+            player_pos: pp.aabb.pos,
+            yaw,
+            pitch,
+            target,
+            action,
+            hand: self.hotbar_slot,
+        });
+    }
+
+    /// Get the current world time, or 0 if the server didn't send it yet
+    fn get_world_time(&self) -> WorldTime {
+        self.world_clock
+            .map(|clock| clock.get_time(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// Close the block editor, telling the server if it was a chest or a furnace so that it stops sending its changes
+    fn close_block_editor(&mut self) {
+        match self.block_editor.take() {
+            Some(BlockEditor::Chest(_)) => self.client.send(ToServer::CloseChest),
+            Some(BlockEditor::Furnace(_)) => self.client.send(ToServer::CloseFurnace),
+            _ => {}
+        }
+    }
+
     fn handle_server_messages(&mut self) {
+        let mut received_chunks = Vec::new();
         loop {
             match self.client.receive_event() {
                 ClientEvent::NoEvent => break,
                 ClientEvent::ServerMessage(message) => match message {
                     ToClient::Chunk(chunk, light_chunk) => {
-                        self.world.add_chunk(chunk, light_chunk);
+                        received_chunks.push(chunk.pos);
+                        self.world.add_chunk(Arc::new(chunk.to_chunk()), Arc::new(light_chunk.to_chunk()));
+                    }
+                    ToClient::ChunkHashes(hashes) => {
+                        let out_of_sync = self.consistency_checker.verify_hashes(&self.world, &hashes);
+                        if !out_of_sync.is_empty() {
+                            self.client.send(ToServer::RequestChunks(out_of_sync));
+                        }
                     }
                     ToClient::UpdatePhysics(server_state) => {
                         self.physics_simulation.receive_server_update(server_state);
                     }
                     ToClient::GameData(_) => {}
                     ToClient::CurrentId(_) => {}
+                    ToClient::CompassTarget(pos) => {
+                        self.compass_target = Some(pos);
+                    }
+                    ToClient::TimeUpdate(time) => {
+                        self.world_clock = Some(WorldClock::new(time));
+                    }
+                    ToClient::WeatherUpdate(weather) => {
+                        self.weather_particles.set_weather(weather);
+                    }
+                    ToClient::Inventory(inventory) => {
+                        self.inventory = inventory;
+                    }
+                    ToClient::InventorySlotUpdate(slot, stack) => {
+                        self.inventory.set(slot, stack);
+                    }
+                    ToClient::RenderDistanceLimit(render_distance) => {
+                        self.render_distance = render_distance;
+                    }
+                    ToClient::ChatMessage(message) => {
+                        info!("[Chat] {}", message);
+                        self.chat.receive(message, Instant::now());
+                    }
+                    ToClient::PlayerStats(stats) => {
+                        if stats.health < self.player_stats.health {
+                            self.damaged_at = Some(Instant::now());
+                        }
+                        self.player_stats = stats;
+                    }
+                    ToClient::PaintingLimits(limits) => {
+                        self.painting_limits = limits;
+                    }
+                    ToClient::MapCanvas(map_id, canvas) => {
+                        self.world.set_map_canvas(map_id, canvas);
+                    }
+                    ToClient::MapUpdate(map_id, patch) => {
+                        self.world.update_map(map_id, &patch);
+                    }
+                    ToClient::Died(cause) => {
+                        info!("{}", cause.message());
+                        self.death = Some(cause);
+                        self.breaking = None;
+                        self.close_block_editor();
+                        self.close_chat();
+                        self.ui.resume();
+                    }
+                    ToClient::Respawn(_) => {
+                        info!("Respawned");
+                        self.death = None;
+                        self.breaking = None;
+                        self.close_block_editor();
+                    }
+                    ToClient::GameMode(game_mode) => {
+                        self.game_mode = game_mode;
+                        self.breaking = None;
+                        if !game_mode.can_edit_world() {
+                            self.close_block_editor();
+                        }
+                    }
+                    ToClient::OpenChest(pos, inventory) => {
+                        self.block_editor = Some(BlockEditor::Chest(ChestScreen::new(pos, inventory)));
+                    }
+                    ToClient::ChestSlotUpdate(slot, stack) => {
+                        if let Some(BlockEditor::Chest(screen)) = &mut self.block_editor {
+                            screen.set_slot(slot, stack);
+                        }
+                    }
+                    ToClient::CloseChest => {
+                        if let Some(BlockEditor::Chest(_)) = self.block_editor {
+                            self.block_editor = None;
+                        }
+                    }
+                    ToClient::OpenFurnace(pos, inventory, progress) => {
+                        self.block_editor = Some(BlockEditor::Furnace(FurnaceScreen::new(pos, inventory, progress)));
+                    }
+                    ToClient::FurnaceSlotUpdate(slot, stack) => {
+                        if let Some(BlockEditor::Furnace(screen)) = &mut self.block_editor {
+                            screen.set_slot(slot, stack);
+                        }
+                    }
+                    ToClient::FurnaceProgress(progress) => {
+                        if let Some(BlockEditor::Furnace(screen)) = &mut self.block_editor {
+                            screen.progress = progress;
+                        }
+                    }
+                    ToClient::CloseFurnace => {
+                        if let Some(BlockEditor::Furnace(_)) = self.block_editor {
+                            self.block_editor = None;
+                        }
+                    }
+                    ToClient::AdvancementEarned(advancement) => self.advancement_toasts.push_back(advancement),
+                    ToClient::BreakStages(break_stages) => self.break_stages = break_stages,
+                    ToClient::SpawnAreaProgress(generated, total) => {
+                        if let Some(loading) = &mut self.loading {
+                            loading.set_generation_progress(generated, total);
+                        }
+                    }
                 },
                 ClientEvent::Disconnected => unimplemented!("server disconnected"),
                 ClientEvent::Connected => {}
             }
         }
+        if !received_chunks.is_empty() {
+            self.client.send(ToServer::ChunksReceived(received_chunks));
+        }
+        // Request the maps of the item frames, once
+        for map_id in self.world.take_missing_maps() {
+            if self.requested_maps.insert(map_id) {
+                self.client.send(ToServer::RequestMap(map_id));
+            }
+        }
     }
 }
 
 impl State for SinglePlayer {
     fn update(
         &mut self,
-        _settings: &mut Settings,
+        settings: &mut Settings,
         input_state: &InputState,
         _data: &WindowData,
         flags: &mut WindowFlags,
-        _seconds_delta: f64,
+        seconds_delta: f64,
         _device: &mut wgpu::Device,
     ) -> Result<StateTransition> {
         self.client_timing.start_frame();
+        self.watchdog.start_frame();
         // Handle server messages
         self.handle_server_messages();
         self.client_timing.record_part("Network events");
 
         // Init input
-        let frame_input =
-            input_state.get_physics_input(YawPitch::default(), self.ui.should_update_camera());
+        let mut frame_input =
+            input_state.get_physics_input(YawPitch::default());
+        // The server doesn't let hungry players sprint, and adapts the flight to the game mode
+        frame_input.sprinting &= self.player_stats.can_sprint();
+        self.game_mode.adapt_input(&mut frame_input);
+        // The player doesn't move until the terrain around it is loaded, nor while it is dead
+        if self.loading.is_some() || self.death.is_some() {
+            frame_input = Default::default();
+        }
         // Send input to server
         self.client.send(ToServer::UpdateInput(frame_input));
+        let hotbar_slot = input_state.get_hotbar_slot();
+        if hotbar_slot != self.hotbar_slot {
+            self.hotbar_slot = hotbar_slot;
+            self.client.send(ToServer::SelectHotbarSlot(hotbar_slot));
+        }
         self.client_timing.record_part("Collect and send input");
 
         // Update physics
         self.physics_simulation
             .step_simulation(frame_input, Instant::now(), &self.world);
+        self.interpolated_entities
+            .update(&self.physics_simulation, seconds_delta);
+        send_debug_info(
+            "Physics",
+            "interpolation",
+            format!("interpolation alpha = {:.2}", self.physics_simulation.get_interpolation_alpha()),
+        );
         self.client_timing.record_part("Update physics");
 
         // Collect new input
-        let frame_input =
-            input_state.get_physics_input(self.physics_simulation.get_player().yaw_pitch, self.ui.should_update_camera());
+        let mut frame_input =
+            input_state.get_physics_input(self.physics_simulation.get_player().yaw_pitch);
+        frame_input.sprinting &= self.player_stats.can_sprint();
+        self.game_mode.adapt_input(&mut frame_input);
         // Send mew input to server
         self.client.send(ToServer::UpdateInput(frame_input));
 
+        // Widen the view while sprinting forward, and narrow it while zooming
+        let sprinting = frame_input.sprinting && frame_input.key_move_forward;
+        let target_fov = target_fov(settings, sprinting, input_state.is_action_active(Action::Zoom));
+        self.fov.update(target_fov, seconds_delta);
+
+        // Move the camera after the eyes of the player, and bob the view while walking
+        let player = self.physics_simulation.get_player();
+        self.smooth_camera.update(
+            self.physics_simulation.get_interpolated_camera_position(),
+            player.yaw_pitch,
+            seconds_delta,
+            settings.camera_smoothing,
+        );
+        if settings.view_bobbing {
+            let on_the_ground = player.vehicle.is_none() && player.aabb.clone().is_on_the_ground(&self.world);
+            self.view_bobbing.update(player.velocity, on_the_ground, seconds_delta);
+        } else {
+            self.view_bobbing = ViewBobbing::default();
+        }
+
+        self.update_breaking(input_state);
+        self.client_timing.record_part("Break blocks");
+
         let p = self.physics_simulation.get_camera_position();
         let player_chunk = BlockPos::from(p).containing_chunk_pos();
 
@@ -211,21 +727,101 @@ impl State for SinglePlayer {
             ),
         );
 
+        // Apply the settings changed in the menu. When the render distance changes, the memory reduction is given up,
+        // in case the new render distance fits.
+        let mut render_distance_changed = false;
+        if let Some(change) = self.settings_change.take() {
+            change.apply(settings);
+            save_settings(settings);
+            self.world.set_lighting(settings.lighting);
+            if change.changes_render_distance() {
+                self.memory_reduction = 0;
+                render_distance_changed = true;
+            }
+        }
+        // Drop the farthest chunks while the frames take longer than the budget
+        if settings.auto_render_distance {
+            let max_reduction = settings.render_distance.0.saturating_sub(*HORIZONTAL_RENDER_DISTANCES.start());
+            render_distance_changed |= self.auto_render_distance.update(seconds_delta, settings.frame_time_budget(), max_reduction);
+        } else {
+            render_distance_changed |= self.auto_render_distance.reset();
+        }
+        // Render fewer chunks when their meshes don't fit in the GPU memory budget: the farthest chunks are dropped
+        let can_reduce = self.last_memory_reduction.map(|last| last.elapsed() >= MEMORY_PRESSURE_COOLDOWN).unwrap_or(true);
+        if self.world.take_memory_pressure() && can_reduce {
+            self.last_memory_reduction = Some(Instant::now());
+            self.memory_reduction += 1;
+            render_distance_changed = true;
+            warn!("The chunk meshes don't fit in the GPU memory budget, reducing the render distance");
+            self.warning_toast = Some(("Not enough GPU memory, render distance reduced".to_owned(), Instant::now()));
+        }
+        if render_distance_changed {
+            let reduction = self.memory_reduction + self.auto_render_distance.reduction();
+            self.requested_render_distance = settings.get_render_distance().reduced(reduction);
+            // The server sends a lower limit again if the connection is too slow
+            self.render_distance = self.requested_render_distance;
+            self.client.send(ToServer::SetRenderDistance(self.requested_render_distance));
+            info!("Render distance set to {:?}", self.render_distance);
+        }
+
         // Remove chunks that are too far
         self.world.remove_far_chunks(player_chunk, &self.render_distance);
         self.client_timing.record_part("Drop far chunks");
 
-        // Send chunks to meshing
-        self.world.enqueue_chunks_for_meshing(player_chunk, &self.render_distance);
+        // Send chunks to meshing, the ones the player looks at first
+        let yaw_pitch = self.physics_simulation.get_player().yaw_pitch;
+        let (yaw, pitch) = (yaw_pitch.yaw.to_radians(), yaw_pitch.pitch.to_radians());
+        let look_direction = Vector3::new(-yaw.sin() * pitch.cos(), pitch.sin(), -yaw.cos() * pitch.cos());
+        self.world.enqueue_chunks_for_meshing(player_chunk, look_direction, &self.render_distance);
         self.client_timing.record_part("Send chunks to meshing");
 
+        // Wait until the chunks around the joining player are meshed
+        if let Some(loading) = &mut self.loading {
+            let world = &self.world;
+            let spawn_area = self.render_distance.spawn_area().iterate_around_player(player_chunk).collect::<Vec<_>>();
+            let received = spawn_area.iter().filter(|&&pos| world.has_chunk(pos)).count() as u32;
+            let meshed = spawn_area.iter().filter(|&&pos| world.is_chunk_meshed(pos)).count() as u32;
+            loading.set_local_progress(received, meshed, spawn_area.len() as u32);
+            if loading.is_done() {
+                info!("The terrain around the player is loaded after {:?}", self.start_time.elapsed());
+                self.loading = None;
+            }
+        }
+
+        // Repair the holes in the world
+        let missing_chunks = self.consistency_checker.update(&mut self.world, player_chunk, &self.render_distance, Instant::now());
+        if !missing_chunks.is_empty() {
+            self.client.send(ToServer::RequestChunks(missing_chunks));
+        }
+        self.client_timing.record_part("Check world consistency");
+
         send_debug_info("Chunks", "clientloaded", format!("Client loaded {} chunks", self.world.num_loaded_chunks()));
 
-        flags.grab_cursor = self.ui.should_capture_mouse();
+        // Update weather particles
+        self.weather_particles.update(&self.world, p, seconds_delta);
+        send_debug_info(
+            "Weather",
+            "particles",
+            format!(
+                "Weather particles = {}\nCamera covered = {}",
+                self.weather_particles.num_particles(),
+                self.weather_particles.is_camera_covered(&self.world, p),
+            ),
+        );
+        self.client_timing.record_part("Update weather particles");
+
+        // Close the editor if the edited block was broken
+        if let Some(editor) = &self.block_editor {
+            if !editor.is_still_valid(&self.world) {
+                self.close_block_editor();
+            }
+        }
+
+        flags.grab_cursor = self.ui.should_capture_mouse() && self.block_editor.is_none();
 
-        if self.ui.should_exit() {
-            //Ok(StateTransition::ReplaceCurrent(Box::new(crate::mainmenu::MainMenu::new)))
-            Ok(StateTransition::CloseWindow)
+        if self.ui.should_quit() {
+            self.save_and_quit();
+            Ok(StateTransition::ReplaceCurrent(crate::mainmenu::MainMenu::new_factory()))
         } else {
             Ok(StateTransition::KeepCurrent)
         }
@@ -233,7 +829,7 @@ impl State for SinglePlayer {
 
     fn render<'a>(
         &mut self,
-        _settings: &Settings,
+        settings: &Settings,
         buffers: WindowBuffers<'a>,
         device: &mut wgpu::Device,
         data: &WindowData,
@@ -242,11 +838,26 @@ impl State for SinglePlayer {
         // Count fps TODO: move this to update
         self.fps_counter.add_frame();
         send_debug_info("Player", "fps", format!("fps = {}", self.fps_counter.fps()));
+        let frame_times = self.fps_counter.recent_frame_times(TIME_GRAPH_SAMPLES);
+        send_time_graph("Client performance", "frametime", "Frame time", settings.hitch_threshold(), frame_times);
+        send_debug_info("Player", "inventory", format!("inventory = {} items", self.inventory.count_items()));
+        if let Some(loading) = self.loading {
+            return self.render_loading_screen(buffers, device, data, &loading);
+        }
+        let held_item = self
+            .inventory
+            .get(self.hotbar_slot)
+            .and_then(|stack| self.item_registry.get_value_by_id(stack.item_id).map(|item| format!("{} x{}", item.name, stack.count)))
+            .unwrap_or_else(|| "nothing".to_owned());
+        send_debug_info("Player", "hotbar", format!("hotbar slot = {}, holding {}", self.hotbar_slot + 1, held_item));
 
-        let frustum = Frustum::new(
-            self.physics_simulation.get_camera_position(),
+        let (eye, eye_yaw_pitch) = self.smooth_camera.get(
+            self.physics_simulation.get_interpolated_camera_position(),
             self.physics_simulation.get_player().yaw_pitch,
         );
+        let (camera_position, camera_yaw_pitch) = self.camera_mode.place(eye, eye_yaw_pitch, &self.world);
+        let (view_offset, roll) = self.view_bobbing.offset();
+        let frustum = Frustum::new(camera_position, camera_yaw_pitch, self.fov.degrees()).with_view_offset(view_offset, roll);
 
         // Try raytracing TODO: move this to update
         let pp = self.physics_simulation.get_player();
@@ -288,6 +899,7 @@ impl State for SinglePlayer {
             scale: 0.3,
             rot_offset: [0.0, 0.0, 0.0],
             rot_y: 0.0,
+            roll: 0.0,
         });
         let item_rotation = (Instant::now() - self.start_time).as_secs_f32(); // TODO: use f64
         models_to_draw.push(crate::render::Model {
@@ -301,17 +913,241 @@ impl State for SinglePlayer {
             scale: 1.0 / 32.0,
             rot_offset: [0.5, 0.5, 1.0 / 64.0],
             rot_y: item_rotation,
+            roll: 0.0,
         });
-        // Draw chunks
+        let icon_context = IconContext {
+            player_pos: self.physics_simulation.get_camera_position(),
+            yaw: self.physics_simulation.get_player().yaw_pitch.yaw,
+            compass_target: self.compass_target,
+            world_time: self.get_world_time(),
+        };
+        if let Some(compass_id) = self.item_registry.get_id_by_name(&"compass".to_owned()) {
+            models_to_draw.push(crate::render::Model {
+                mesh_id: get_item_mesh_id(&self.item_meshes[compass_id as usize], &icon_context),
+                pos_x: 32.0,
+                pos_y: 55.0,
+                pos_z: 30.0,
+                scale: 1.0 / 32.0,
+                rot_offset: [0.5, 0.5, 1.0 / 64.0],
+                rot_y: 0.0,
+                roll: 0.0,
+            });
+        }
+        if let Some(clock_id) = self.item_registry.get_id_by_name(&"clock".to_owned()) {
+            models_to_draw.push(crate::render::Model {
+                mesh_id: get_item_mesh_id(&self.item_meshes[clock_id as usize], &icon_context),
+                pos_x: 34.0,
+                pos_y: 55.0,
+                pos_z: 30.0,
+                scale: 1.0 / 32.0,
+                rot_offset: [0.5, 0.5, 1.0 / 64.0],
+                rot_y: 0.0,
+                roll: 0.0,
+            });
+        }
+        // Draw projectiles
+        if let Some(rock_id) = self.item_registry.get_id_by_name(&"rock".to_owned()) {
+            let rock_mesh_id = get_item_mesh_id(&self.item_meshes[rock_id as usize], &icon_context);
+            for (id, entity) in self.physics_simulation.get_entities().iter() {
+                if let EntityKind::Projectile = entity.kind {
+                    let pos = self.interpolated_entities.get_position(*id).unwrap_or(entity.aabb.pos);
+                    let size = entity.aabb.size_x as f32;
+                    models_to_draw.push(crate::render::Model {
+                        mesh_id: rock_mesh_id,
+                        pos_x: pos.x as f32,
+                        pos_y: pos.y as f32,
+                        pos_z: pos.z as f32,
+                        scale: size / 32.0,
+                        rot_offset: [size / 2.0, size / 2.0, size / 64.0],
+                        rot_y: entity.yaw.to_radians() as f32,
+                        roll: 0.0,
+                    });
+                }
+            }
+        }
+        // Draw dropped items, spinning above the ground
+        for (id, entity) in self.physics_simulation.get_entities().iter() {
+            if let EntityKind::Item(item_id) = entity.kind {
+                let item_mesh = &self.item_meshes[item_id as usize];
+                let (item_scale, mesh_center) = match item_mesh {
+                    ItemMesh::SimpleMesh { scale, mesh_center, .. } => (*scale, *mesh_center),
+                    ItemMesh::DynamicMesh { scale, mesh_center, .. } => (*scale, *mesh_center),
+                };
+                let scale = item_scale * DROPPED_ITEM_MODEL_SIZE;
+                let pos = self.interpolated_entities.get_position(*id).unwrap_or(entity.aabb.pos);
+                let half_x = mesh_center.0 * scale;
+                let half_z = mesh_center.2 * scale;
+                models_to_draw.push(crate::render::Model {
+                    mesh_id: get_item_mesh_id(item_mesh, &icon_context),
+                    pos_x: (pos.x + entity.aabb.size_x / 2.0) as f32 - half_x,
+                    pos_y: pos.y as f32,
+                    pos_z: (pos.z + entity.aabb.size_z / 2.0) as f32 - half_z,
+                    scale,
+                    rot_offset: [half_x, 0.0, half_z],
+                    rot_y: item_rotation + id.0 as f32,
+                    roll: 0.0,
+                });
+            }
+        }
+        // Draw the framed items in front of their frame, except the maps that are drawn on the frame
+        for (pos, block_entity) in self.world.loaded_block_entities() {
+            let (framed_item, facing) = match block_entity {
+                BlockEntity::ItemFrame(ItemFrame { item: Some(item), facing }) => (item, *facing),
+                _ => continue,
+            };
+            let item_id = match framed_item.get_item_id(&self.item_registry) {
+                Some(item_id) if !self.item_registry.get_value_by_id(item_id).unwrap().is_map() => item_id,
+                _ => continue,
+            };
+            let item_mesh = &self.item_meshes[item_id as usize];
+            let (item_scale, mesh_center) = match item_mesh {
+                ItemMesh::SimpleMesh { scale, mesh_center, .. } => (*scale, *mesh_center),
+                ItemMesh::DynamicMesh { scale, mesh_center, .. } => (*scale, *mesh_center),
+            };
+            let scale = item_scale * FRAMED_ITEM_MODEL_SIZE;
+            let half = [mesh_center.0 * scale, mesh_center.1 * scale, mesh_center.2 * scale];
+            let normal = D[facing];
+            let mut center = [0, 1, 2].map(|i| {
+                let block = [pos.px, pos.py, pos.pz][i] as f32 + 0.5;
+                block + normal[i] as f32 * (0.5 + FRAMED_ITEM_OFFSET)
+            });
+            // The items stand on the frames that face up or down
+            center[1] += normal[1] as f32 * half[1];
+            // The items turn around the normal of the frame, clockwise when seen from the front
+            let turn = framed_item.rotation as f32 * std::f32::consts::FRAC_PI_2;
+            models_to_draw.push(crate::render::Model {
+                mesh_id: get_item_mesh_id(item_mesh, &icon_context),
+                pos_x: center[0] - half[0],
+                pos_y: center[1] - half[1],
+                pos_z: center[2] - half[2],
+                scale,
+                rot_offset: half,
+                // The flat side of the items faces away from the frame
+                rot_y: if normal[0] != 0 {
+                    std::f32::consts::FRAC_PI_2
+                } else {
+                    -normal[1] as f32 * turn
+                },
+                roll: -(normal[0] + normal[2]) as f32 * turn,
+            });
+        }
+        // Draw vehicles
+        for (id, entity) in self.physics_simulation.get_entities().iter() {
+            if let EntityKind::Vehicle(kind) = entity.kind {
+                let model_name = match kind {
+                    VehicleKind::Boat => "vehicle:boat",
+                    VehicleKind::Minecart => "vehicle:minecart",
+                };
+                if let Some(mesh_id) = self.model_registry.get_id_by_name(&model_name.to_owned()) {
+                    let model = self.model_registry.get_value_by_id(mesh_id).unwrap();
+                    // The models are centered on the bottom of the aabb
+                    let pos = self.interpolated_entities.get_position(*id).unwrap_or(entity.aabb.pos);
+                    let half_x = model.size_x as f32 * VEHICLE_MODEL_SCALE / 2.0;
+                    let half_z = model.size_z as f32 * VEHICLE_MODEL_SCALE / 2.0;
+                    models_to_draw.push(crate::render::Model {
+                        mesh_id,
+                        pos_x: (pos.x + entity.aabb.size_x / 2.0) as f32 - half_x,
+                        pos_y: pos.y as f32,
+                        pos_z: (pos.z + entity.aabb.size_z / 2.0) as f32 - half_z,
+                        scale: VEHICLE_MODEL_SCALE,
+                        rot_offset: [half_x, 0.0, half_z],
+                        rot_y: entity.yaw.to_radians() as f32,
+                        roll: 0.0,
+                    });
+                }
+            }
+        }
+        // Draw mobs
+        let animation_time = (Instant::now() - self.start_time).as_secs_f32();
+        for (id, entity) in self.physics_simulation.get_entities().iter() {
+            if let EntityKind::Mob(mob_id) = entity.kind {
+                let pos = self.interpolated_entities.get_position(*id).unwrap_or(entity.aabb.pos);
+                let size = (entity.aabb.size_x, entity.aabb.size_y, entity.aabb.size_z);
+                models_to_draw.extend(self.box_models.mob_models(mob_id, pos, size, entity.yaw, entity.velocity, animation_time));
+            }
+        }
+        // Draw the other players
+        for (player, pos) in self.physics_simulation.get_other_players() {
+            let size = (player.aabb.size_x, player.aabb.size_y, player.aabb.size_z);
+            let yaw = player.yaw_pitch.yaw;
+            models_to_draw.extend(self.box_models.player_models(pos, size, yaw, player.velocity, animation_time));
+        }
+        // Draw the player itself when the camera is away from its eyes
+        if self.camera_mode.shows_player() {
+            let player = self.physics_simulation.get_player();
+            let hitbox = player.pose.hitbox();
+            let pos = self.physics_simulation.get_interpolated_camera_position()
+                - Vector3::new(hitbox.side / 2.0, hitbox.eye_height, hitbox.side / 2.0);
+            let size = (player.aabb.size_x, player.aabb.size_y, player.aabb.size_z);
+            let yaw = player.yaw_pitch.yaw;
+            models_to_draw.extend(self.box_models.player_models(pos, size, yaw, player.velocity, animation_time));
+        }
+        // Draw weather particles
+        if let (Some(rain_mesh_id), Some(snow_mesh_id)) = (
+            self.model_registry.get_id_by_name(&"particle:rain".to_owned()),
+            self.model_registry.get_id_by_name(&"particle:snow".to_owned()),
+        ) {
+            models_to_draw.extend(self.weather_particles.get_models(&self.world, rain_mesh_id, snow_mesh_id));
+        }
+        send_debug_info(
+            "Player",
+            "compass",
+            match icon_context.compass_angle() {
+                Some(angle) => format!("compass angle = {:.0}", angle),
+                None => "compass angle = None".to_owned(),
+            },
+        );
+        // Draw the held item, unless the camera shows the player
+        let held_model = self
+            .inventory
+            .get(self.hotbar_slot)
+            .filter(|_| !self.camera_mode.shows_player())
+            .map(|stack| {
+                let item_mesh = &self.item_meshes[stack.item_id as usize];
+                let (item_scale, mesh_center) = match item_mesh {
+                    ItemMesh::SimpleMesh { scale, mesh_center, .. } => (*scale, *mesh_center),
+                    ItemMesh::DynamicMesh { scale, mesh_center, .. } => (*scale, *mesh_center),
+                };
+                let mesh_id = get_item_mesh_id(item_mesh, &icon_context);
+                held_item_model(mesh_id, item_scale, mesh_center, self.hand_swing.progress(Instant::now()))
+            });
+        // The hitboxes of the debug view, where the entities and the players are drawn
+        let mut hitboxes = Vec::new();
+        if self.debug_render_mode == DebugRenderMode::Hitboxes {
+            for (id, entity) in self.physics_simulation.get_entities().iter() {
+                let pos = self.interpolated_entities.get_position(*id).unwrap_or(entity.aabb.pos);
+                hitboxes.push(AABB { pos, ..entity.aabb.clone() });
+            }
+            for (player, pos) in self.physics_simulation.get_other_players() {
+                hitboxes.push(AABB { pos, ..player.aabb.clone() });
+            }
+            // The hitbox of the player itself would surround the camera in the first-person view
+            if self.camera_mode.shows_player() {
+                let player = self.physics_simulation.get_player();
+                let hitbox = player.pose.hitbox();
+                let pos = self.physics_simulation.get_interpolated_camera_position()
+                    - Vector3::new(hitbox.side / 2.0, hitbox.eye_height, hitbox.side / 2.0);
+                hitboxes.push(AABB { pos, ..player.aabb.clone() });
+            }
+        }
+        // Draw chunks, with the fog hiding the border of the render distance
+        let rd = self.render_distance;
+        let fog_distance = rd.x_max.min(rd.x_min).min(rd.z_max).min(rd.z_min) as f32 * CHUNK_SIZE as f32;
         self.world.render_chunks(
             device,
             &mut encoder,
             buffers,
             data,
             &frustum,
-            input_state.enable_culling,
+            settings.occlusion_culling,
             pointed_block,
+            &self.break_stages,
             &models_to_draw,
+            self.get_world_time(),
+            fog_distance,
+            held_model.as_ref(),
+            self.debug_render_mode,
+            &hitboxes,
         );
         self.client_timing.record_part("Render chunks");
 
@@ -321,7 +1157,189 @@ impl State for SinglePlayer {
         self.ui.rebuild(&mut self.debug_info, data)?;
         self.gui.prepare();
         crate::gui::experiments::render_debug_info(&mut self.gui, &mut self.debug_info);
+        // The players who own a clock see the time of the world
+        let items = &self.item_registry;
+        let is_clock = |stack: ItemStack| items.get_value_by_id(stack.item_id).map(Item::is_clock).unwrap_or(false);
+        let owns_clock = self.inventory.slots().flatten().any(is_clock);
+        if self.world_clock.is_some() && owns_clock {
+            let world_time = self.get_world_time();
+            crate::gui::hud::render_clock(&mut self.gui, data.logical_window_size.width as i32, world_time);
+        }
+        if self.game_mode.has_hunger() {
+            let damage_flash = self.damaged_at.map(|since| crate::gui::hud::damage_flash(since.elapsed())).unwrap_or(0.0);
+            crate::gui::hud::render_status_bars(
+                &mut self.gui,
+                data.logical_window_size.width as i32,
+                data.logical_window_size.height as i32,
+                self.player_stats,
+                damage_flash,
+                self.start_time.elapsed().as_secs_f32(),
+            );
+        } else {
+            crate::gui::hud::render_game_mode(&mut self.gui, data.logical_window_size.width as i32, self.game_mode);
+        }
+        let inventory_icons = (0..self.inventory.len())
+            .map(|slot| {
+                let stack = self.inventory.get(slot)?;
+                let item_mesh = self.item_meshes.get(stack.item_id as usize)?;
+                Some((get_item_texture(item_mesh, &icon_context), stack.count))
+            })
+            .collect::<Vec<_>>();
+        crate::gui::hud::render_hotbar(
+            &mut self.gui,
+            data.logical_window_size.width as i32,
+            data.logical_window_size.height as i32,
+            &inventory_icons[..HOTBAR_SIZE],
+            self.hotbar_slot,
+        );
+        // Show the held map
+        let held_map = self
+            .inventory
+            .get(self.hotbar_slot)
+            .filter(|stack| self.item_registry.get_value_by_id(stack.item_id).map(Item::is_map).unwrap_or(false))
+            .and_then(|stack| self.world.get_map(stack.data))
+            .cloned();
+        if let Some(canvas) = held_map {
+            let player_pos = self.physics_simulation.get_player().aabb.pos;
+            let player_pixel = canvas.get_pixel_pos(player_pos.x.floor() as i64, player_pos.z.floor() as i64);
+            crate::gui::hud::render_held_map(
+                &mut self.gui,
+                data.logical_window_size.width as i32,
+                data.logical_window_size.height as i32,
+                &canvas,
+                player_pixel,
+            );
+        }
+        if let Some((text, since)) = &self.warning_toast {
+            if since.elapsed() < WARNING_TOAST_DURATION {
+                crate::gui::hud::render_toast(&mut self.gui, data.logical_window_size.width as i32, text.clone());
+            } else {
+                self.warning_toast = None;
+            }
+        }
+        if let Some(&advancement) = self.advancement_toasts.front() {
+            let since = *self.advancement_toast_since.get_or_insert_with(Instant::now);
+            if since.elapsed() < ADVANCEMENT_TOAST_DURATION {
+                if let Some(advancement) = self.advancement_registry.get_value_by_id(advancement) {
+                    let window_width = data.logical_window_size.width as i32;
+                    crate::gui::hud::render_advancement_toast(&mut self.gui, window_width, &advancement.ty);
+                }
+            } else {
+                self.advancement_toasts.pop_front();
+                self.advancement_toast_since = None;
+            }
+        }
+        let window_height = data.logical_window_size.height as i32;
+        if render_chat(&mut self.gui, window_height, &mut self.chat, self.ui.is_chat_open(), Instant::now()) {
+            if let Some(message) = self.chat.take_input() {
+                self.client.send(ToServer::ChatMessage(message));
+            }
+            self.close_chat();
+        }
+        if let Some(cause) = self.death {
+            let (window_width, window_height) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+            match render_death_screen(&mut self.gui, window_width, window_height, cause) {
+                Some(DeathScreenButton::Respawn) => self.client.send(ToServer::Respawn),
+                Some(DeathScreenButton::TitleScreen) => self.ui.quit(),
+                None => {}
+            }
+        }
+        // The options replace the buttons of the menu, next to which the recipes can be clicked
+        if self.ui.is_options_open() && self.block_editor.is_none() {
+            let (window_width, window_height) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+            let auto_reduction = self.auto_render_distance.reduction();
+            let window_size = (window_width, window_height);
+            if let Some(change) = render_options(&mut self.gui, window_size, settings, &mut self.options_menu, auto_reduction) {
+                self.settings_change = Some(change);
+            }
+            if render_options_back_button(&mut self.gui, window_width, window_height) {
+                self.ui.close_options();
+            }
+        } else if self.ui.is_menu_open() && self.block_editor.is_none() {
+            let (window_width, window_height) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+            match render_pause_menu(&mut self.gui, window_width, window_height) {
+                Some(PauseMenuButton::Resume) => self.ui.resume(),
+                Some(PauseMenuButton::Options) => self.ui.open_options(),
+                Some(PauseMenuButton::SaveAndQuit) => self.ui.quit(),
+                None => {}
+            }
+            let clicked_recipe = crate::gui::crafting::render_crafting(
+                &mut self.gui,
+                window_width,
+                &self.recipe_registry,
+                &self.item_registry,
+                &self.inventory,
+            );
+            if let Some(recipe_id) = clicked_recipe {
+                self.client.send(ToServer::Craft(recipe_id));
+            }
+        }
+        let window_width = data.logical_window_size.width as i32;
+        let close_editor = match self.block_editor.as_mut() {
+            Some(BlockEditor::Banner(editor)) => match render_banner_editor(&mut self.gui, window_width, editor) {
+                Some(BannerEditorResult::Apply) => {
+                    self.client.send(ToServer::SetBanner(editor.pos, editor.pattern.clone()));
+                    true
+                }
+                Some(BannerEditorResult::Cancel) => true,
+                None => false,
+            },
+            Some(BlockEditor::Painting(picker)) => {
+                match render_painting_picker(&mut self.gui, window_width, picker, self.painting_limits) {
+                    Some(PaintingPickerResult::Import(image)) => {
+                        self.client.send(ToServer::SetPainting(picker.pos, image));
+                        true
+                    }
+                    Some(PaintingPickerResult::Cancel) => true,
+                    None => false,
+                }
+            }
+            Some(BlockEditor::Chest(screen)) => {
+                match render_chest_screen(&mut self.gui, window_width, screen, &self.inventory, &self.item_registry) {
+                    Some(ChestScreenResult::Move(from, to)) => {
+                        self.client.send(ToServer::MoveChestItems(from, to));
+                        false
+                    }
+                    Some(ChestScreenResult::Close) => true,
+                    None => false,
+                }
+            }
+            Some(BlockEditor::Furnace(screen)) => {
+                match render_furnace_screen(&mut self.gui, window_width, screen, &self.inventory, &self.item_registry) {
+                    Some(FurnaceScreenResult::Move(from, to)) => {
+                        self.client.send(ToServer::MoveFurnaceItems(from, to));
+                        false
+                    }
+                    Some(FurnaceScreenResult::Close) => true,
+                    None => false,
+                }
+            }
+            Some(BlockEditor::Inventory(screen)) => {
+                let window_height = data.logical_window_size.height as i32;
+                let quick_move = input_state.get_modifiers_state().shift();
+                let (inventory, item_registry) = (&self.inventory, &self.item_registry);
+                let names = (0..inventory.len())
+                    .map(|slot| {
+                        let stack = inventory.get(slot)?;
+                        item_registry.get_value_by_id(stack.item_id).map(|item| item.name.as_str())
+                    })
+                    .collect::<Vec<_>>();
+                let icons = &inventory_icons;
+                match render_inventory_screen(&mut self.gui, window_width, window_height, screen, icons, &names, quick_move) {
+                    Some(InventoryScreenResult::Move(from, to)) => self.client.send(ToServer::MoveItems(from, to)),
+                    Some(InventoryScreenResult::MoveSome(from, to, count)) => self.client.send(ToServer::MoveSomeItems(from, to, count)),
+                    Some(InventoryScreenResult::QuickMove(slot)) => self.client.send(ToServer::QuickMoveItems(slot)),
+                    None => {}
+                }
+                false
+            }
+            None => false,
+        };
+        if close_editor {
+            self.close_block_editor();
+        }
         self.gui.finish();
+        let show_crosshair = !self.is_in_menu();
         self.ui_renderer.render(
             buffers,
             device,
@@ -329,10 +1347,17 @@ impl State for SinglePlayer {
             &data,
             &self.ui.ui,
             &mut self.gui,
-            self.ui.should_capture_mouse(),
+            show_crosshair,
         );
         self.client_timing.record_part("Render UI");
 
+        if let Some(report) = self.watchdog.end_frame(&self.client_timing) {
+            let slowest = match report.slowest_part() {
+                Some((part, duration)) => format!(", mostly in {} ({} ms)", part, duration.as_millis()),
+                None => String::new(),
+            };
+            self.warning_toast = Some((format!("Hitch: {} ms{}", report.duration.as_millis(), slowest), Instant::now()));
+        }
         send_perf_breakdown("Client performance", "mainloop", "Client main loop", self.client_timing.extract_part_averages());
 
         Ok((StateTransition::KeepCurrent, encoder.finish()))
@@ -340,7 +1365,8 @@ impl State for SinglePlayer {
 
     fn handle_mouse_motion(&mut self, _settings: &Settings, delta: (f64, f64)) {
         // if self.ui.should_update_camera() {
-        //     self.physics_simulation.get_player().yaw_pitch.update_cursor(delta.0, delta.1);
+        //     let (dx, dy) = (delta.0 * settings.mouse_sensitivity, delta.1 * settings.mouse_sensitivity);
+        //     self.physics_simulation.get_player().yaw_pitch.update_cursor(dx, dy);
         // }
     }
 
@@ -350,30 +1376,78 @@ impl State for SinglePlayer {
         self.gui.update_mouse_position(x, y);
     }
 
+    fn handle_mouse_wheel(&mut self, delta: winit::event::MouseScrollDelta) {
+        self.gui.update_mouse_wheel(delta);
+    }
+
     fn handle_mouse_state_changes(
         &mut self,
         changes: Vec<(winit::event::MouseButton, winit::event::ElementState)>,
     ) {
+        if self.options_menu.is_rebinding() {
+            for (button, state) in changes {
+                self.options_menu.handle_mouse_button(button, state);
+            }
+            return;
+        }
         for (button, state) in changes.iter() {
             let pp = self.physics_simulation.get_player();
             let y = self.physics_simulation.get_player().yaw_pitch.yaw;
             let p = self.physics_simulation.get_player().yaw_pitch.pitch;
             match *button {
-                MouseButton::Left => match *state {
-                    ElementState::Pressed => {
-                        self.client.send(ToServer::BreakBlock(pp.aabb.pos, y, p));
-                    }
-                    _ => {}
-                },
+                // The editor uses the mouse, and spectators can't interact with the world
+                MouseButton::Right if self.block_editor.is_some() || !self.game_mode.can_edit_world() => {}
                 MouseButton::Right => match *state {
                     ElementState::Pressed => {
-                        self.client.send(ToServer::PlaceBlock(pp.aabb.pos, y, p));
+                        // Right-clicking a mob or a vehicle uses it, unless there is a block in the way.
+                        // Players who already ride a vehicle can't mount another one.
+                        let camera_pos = pp.get_camera_position();
+                        let dir = Vector3::new(
+                            -y.to_radians().sin() * p.to_radians().cos(),
+                            p.to_radians().sin(),
+                            -y.to_radians().cos() * p.to_radians().cos(),
+                        );
+                        let entity_range = raycast(camera_pos, dir, MOUNT_RANGE, &self.world)
+                            .map(|hit| (hit.pos - camera_pos).norm())
+                            .unwrap_or(MOUNT_RANGE);
+                        let entities = self.physics_simulation.get_entities();
+                        let pointed_entity = find_pointed_entity(camera_pos, dir, entity_range, entities)
+                            .filter(|entity| pp.vehicle.is_none() || !matches!(entities[entity].kind, EntityKind::Vehicle(_)));
+                        match pointed_entity {
+                            Some(entity) => self.interact(InteractionTarget::Entity(entity), InteractionAction::Use),
+                            None => {
+                                // Right-clicking a banner or a painting opens its editor instead of placing a block
+                                let pointed_block = pp.get_pointed_at(dir, 10.0, &self.world);
+                                let editor = pointed_block.and_then(|(block, _face)| match self.world.get_block_entity(block) {
+                                    Some(BlockEntity::Banner(pattern)) => Some(BlockEditor::Banner(BannerEditor::new(block, pattern.clone()))),
+                                    Some(BlockEntity::Painting(_)) => Some(BlockEditor::Painting(PaintingPicker::new(block))),
+                                    // The server frames the held item, makes the player sleep in the bed or opens the chest or furnace
+                                    Some(BlockEntity::ItemFrame(_))
+                                    | Some(BlockEntity::Bed(_))
+                                    | Some(BlockEntity::Chest(_))
+                                    | Some(BlockEntity::Furnace(_))
+                                    | None => None,
+                                });
+                                match (editor, pointed_block) {
+                                    (Some(editor), _) => self.block_editor = Some(editor),
+                                    (None, Some((block, face))) => self.interact(InteractionTarget::Block(block, face), InteractionAction::Use),
+                                    (None, None) => {}
+                                }
+                            }
+                        }
                     }
                     _ => {}
                 },
                 MouseButton::Middle => match *state {
                     ElementState::Pressed => {
-                        self.client.send(ToServer::SelectBlock(pp.aabb.pos, y, p));
+                        let dir = Vector3::new(
+                            -y.to_radians().sin() * p.to_radians().cos(),
+                            p.to_radians().sin(),
+                            -y.to_radians().cos() * p.to_radians().cos(),
+                        );
+                        if let Some((block, face)) = pp.get_pointed_at(dir, 10.0, &self.world) {
+                            self.interact(InteractionTarget::Block(block, face), InteractionAction::Pick);
+                        }
                     }
                     _ => {}
                 },
@@ -388,6 +1462,7 @@ impl State for SinglePlayer {
                         self.gui.update_mouse_button(false);
                     }
                 },
+                MouseButton::Right => self.gui.update_right_mouse_button(*state == ElementState::Pressed),
                 _ => {}
             }
         }
@@ -395,6 +1470,71 @@ impl State for SinglePlayer {
     }
 
     fn handle_key_state_changes(&mut self, changes: Vec<(u32, winit::event::ElementState)>) {
+        if self.options_menu.is_rebinding() {
+            for (scancode, state) in changes {
+                self.options_menu.handle_key(scancode, state);
+            }
+            return;
+        }
+        if self.ui.is_chat_open() {
+            for (scancode, state) in changes {
+                match (scancode, state) {
+                    (ESCAPE_SCANCODE, ElementState::Pressed) => self.close_chat(),
+                    (PAGE_UP_SCANCODE, ElementState::Pressed) => self.chat.scroll(CHAT_LINES as isize - 1),
+                    (PAGE_DOWN_SCANCODE, ElementState::Pressed) => self.chat.scroll(1 - CHAT_LINES as isize),
+                    _ => {}
+                }
+            }
+            return;
+        }
         self.ui.handle_key_state_changes(changes);
     }
+
+    fn handle_received_characters(&mut self, characters: Vec<char>) {
+        // The characters are typed in the chat, and the key that opens it isn't typed since it is handled afterwards
+        if self.ui.is_chat_open() {
+            self.gui.update_typed_characters(characters);
+        }
+    }
+
+    fn handle_actions(&mut self, actions: Vec<Action>) {
+        // Nothing can be done until the terrain is loaded, nor while the player is dead
+        if self.loading.is_some() || self.death.is_some() {
+            return;
+        }
+        for action in actions {
+            match action {
+                Action::UseItem => self.interact(InteractionTarget::Air, InteractionAction::Use),
+                // Sneaking leaves the vehicle
+                Action::MoveDown if self.physics_simulation.get_player().vehicle.is_some() => {
+                    self.client.send(ToServer::DismountVehicle);
+                }
+                // Escape closes the block editor before it opens the menu
+                Action::ToggleMenu if self.block_editor.is_some() => self.close_block_editor(),
+                Action::ToggleMenu => self.ui.toggle_menu(),
+                Action::OpenChat => self.ui.open_chat(),
+                Action::ToggleInventory if matches!(self.block_editor, Some(BlockEditor::Inventory(_))) => self.close_block_editor(),
+                Action::ToggleInventory if !self.is_in_menu() => {
+                    self.block_editor = Some(BlockEditor::Inventory(InventoryScreen::default()));
+                }
+                Action::CycleCamera => self.camera_mode = self.camera_mode.next(),
+                Action::CycleDebugRenderMode => {
+                    self.debug_render_mode = self.debug_render_mode.next();
+                    info!("Debug render mode: {:?}", self.debug_render_mode);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn input_context(&self) -> InputContext {
+        if self.options_menu.is_rebinding() || self.ui.is_chat_open() {
+            // The keys are captured by the settings or typed in the chat
+            InputContext::Chat
+        } else if !self.is_in_menu() {
+            InputContext::Gameplay
+        } else {
+            InputContext::Ui
+        }
+    }
 }
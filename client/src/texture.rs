@@ -2,22 +2,107 @@ use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use image::{ImageBuffer, Rgba};
 use log::info;
 
-const MIPMAP_LEVELS: u32 = 5;
+/// Number of mipmap levels of the textures, including the full size image
+pub const MIPMAP_LEVELS: u32 = 5;
 
-/// Load an image into a texture
-pub fn load_image(
+/// Load images of the same size into the layers of an array texture
+pub fn load_images(
     device: &wgpu::Device,
     encoder: &mut wgpu::CommandEncoder,
-    image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    images: &[ImageBuffer<Rgba<u8>, Vec<u8>>],
 ) -> wgpu::Texture {
-    info!("Loading image...");
+    info!("Loading {} images...", images.len());
     // Only squared images are allowed
     // TODO: check for power of two
+    let image_size = images[0].width();
+    assert!(images.iter().all(|image| image.dimensions() == (image_size, image_size)));
+    // Create texture
+    info!("Creating texture");
+    let texture_descriptor = wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: image_size,
+            height: image_size,
+            depth: images.len() as u32,
+        },
+        mip_level_count: MIPMAP_LEVELS,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::SAMPLED,
+    };
+    let texture = device.create_texture(&texture_descriptor);
+    // Send texture to GPU
+    for (layer, image) in images.iter().enumerate() {
+        info!("Copying layer {}", layer);
+        update_image_region(device, encoder, &texture, layer as u32, (0, 0), image);
+    }
+    info!("Texture loading successful");
+    texture
+}
+
+/// Replace a square region of a layer of a texture created by `load_images`, including its mipmaps.
+/// `(x, y)` is the top left corner of the region, in pixels.
+pub fn update_image_region(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    layer: u32,
+    (x, y): (u32, u32),
+    image: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+) {
     assert_eq!(image.width(), image.height());
     let image_size = image.width();
-    // Generate mipmaps
+    let mipmaps = generate_mipmaps(image);
+    for (level, mipmap) in mipmaps.iter().enumerate() {
+        let current_size = image_size >> level;
+        // The rows of the buffer must be aligned
+        let bytes_per_row = 4 * current_size;
+        let padded_bytes_per_row =
+            bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let mut contents = vec![0; (padded_bytes_per_row * current_size) as usize];
+        for row in 0..current_size as usize {
+            let src = row * bytes_per_row as usize;
+            let dst = row * padded_bytes_per_row as usize;
+            contents[dst..dst + bytes_per_row as usize].copy_from_slice(&mipmap[src..src + bytes_per_row as usize]);
+        }
+        let src_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            usage: wgpu::BufferUsage::COPY_SRC,
+            contents: &contents,
+        });
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    rows_per_image: current_size,
+                    bytes_per_row: padded_bytes_per_row,
+                },
+                buffer: &src_buffer,
+            },
+            wgpu::TextureCopyView {
+                texture,
+                mip_level: level as u32,
+                origin: wgpu::Origin3d {
+                    x: x >> level,
+                    y: y >> level,
+                    z: layer,
+                },
+            },
+            wgpu::Extent3d {
+                width: current_size,
+                height: current_size,
+                depth: 1,
+            },
+        );
+    }
+}
+
+/// Compute the mipmap levels of a square image, starting with the image itself
+fn generate_mipmaps(image: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Vec<Vec<u8>> {
+    let image_size = image.width();
     let mut mipmaps = Vec::new();
-    mipmaps.push(Vec::from(&*image));
+    mipmaps.push(Vec::from(&**image));
     for level in 1..MIPMAP_LEVELS {
         // 5 mip maps only
         let current_size = (image_size >> level) as usize;
@@ -48,59 +133,5 @@ pub fn load_image(
         }
         mipmaps.push(new_layer);
     }
-    // Create texture
-    info!("Creating texture");
-    let texture_descriptor = wgpu::TextureDescriptor {
-        label: None,
-        size: wgpu::Extent3d {
-            width: image_size,
-            height: image_size,
-            depth: 1,
-        },
-        mip_level_count: MIPMAP_LEVELS,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8Unorm,
-        usage: wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::SAMPLED,
-    };
-    let texture = device.create_texture(&texture_descriptor);
-    // Send texture to GPU
-
-    for level in 0..MIPMAP_LEVELS {
-        info!("Copying mipmap level {mipmap_level}", mipmap_level = level);
-        let current_size = image_size >> level;
-        let src_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            usage: wgpu::BufferUsage::COPY_SRC,
-            contents: &mipmaps[level as usize]
-        });
-        let buffer_view = wgpu::BufferCopyView {
-            layout: wgpu::TextureDataLayout {
-                offset: 0,
-                rows_per_image: current_size,
-                bytes_per_row: 4 * current_size,
-            },
-            buffer: &src_buffer,
-        };
-        let texture_view = wgpu::TextureCopyView {
-            texture: &texture,
-            mip_level: level,
-            origin: wgpu::Origin3d {
-                x: 0,
-                y: 0,
-                z: 0,
-            },
-        };
-        encoder.copy_buffer_to_texture(
-            buffer_view,
-            texture_view,
-            wgpu::Extent3d {
-                width: current_size,
-                height: current_size,
-                depth: 1,
-            },
-        );
-    }
-    info!("Texture loading successful");
-    texture
+    mipmaps
 }
@@ -1,9 +1,11 @@
 use self::widgets::{Text, WithStyle};
+use crate::lang::tr;
 use crate::ui::widgets::Button;
 use crate::window::WindowData;
 use anyhow::Result;
 use quint::{wt, Size, Style, WidgetTree};
 use std::collections::BTreeMap;
+use history_survival_common::data::TextureRect;
 use history_survival_common::debug::DebugInfo;
 use wgpu_glyph::ab_glyph::PxScale;
 use winit::dpi::LogicalPosition;
@@ -16,14 +18,20 @@ pub mod widgets;
 #[derive(Debug, Clone, Copy)]
 pub enum Message {
     ExitMenu,
-    ExitGame,
+    OpenOptions,
+    SaveAndQuit,
 }
 
 pub struct Ui {
     pub ui: quint::Ui<PrimitiveBuffer, Message>,
     messages: Vec<Message>,
     show_menu: bool,
-    should_exit: bool,
+    /// Whether the chat is open, taking the keyboard to type a message
+    show_chat: bool,
+    /// Whether the options are shown in the menu instead of its buttons
+    show_options: bool,
+    /// Whether the player asked to save the world and go back to the main menu
+    should_quit: bool,
 }
 
 impl Ui {
@@ -32,7 +40,9 @@ impl Ui {
             ui: quint::Ui::new(),
             messages: Vec::new(),
             show_menu: false,
-            should_exit: false,
+            show_chat: false,
+            show_options: false,
+            should_quit: false,
         }
     }
 
@@ -44,7 +54,48 @@ impl Ui {
     }
 
     pub fn should_update_camera(&self) -> bool {
-        !self.show_menu
+        !self.show_menu && !self.show_chat
+    }
+
+    pub fn is_menu_open(&self) -> bool {
+        self.show_menu
+    }
+
+    pub fn is_chat_open(&self) -> bool {
+        self.show_chat
+    }
+
+    pub fn is_options_open(&self) -> bool {
+        self.show_menu && self.show_options
+    }
+
+    /// Show the options in the menu
+    pub fn open_options(&mut self) {
+        self.show_options = true;
+    }
+
+    /// Go back from the options to the buttons of the menu
+    pub fn close_options(&mut self) {
+        self.show_options = false;
+    }
+
+    /// Close the menu and go back to the game
+    pub fn resume(&mut self) {
+        self.show_menu = false;
+        self.show_options = false;
+    }
+
+    /// Save the world and go back to the main menu
+    pub fn quit(&mut self) {
+        self.should_quit = true;
+    }
+
+    pub fn open_chat(&mut self) {
+        self.show_chat = true;
+    }
+
+    pub fn close_chat(&mut self) {
+        self.show_chat = false;
     }
 
     /// Rebuild the Ui if it changed
@@ -133,12 +184,12 @@ impl Ui {
     }
 
     fn draw_menu(&self) -> WidgetTree<PrimitiveBuffer, Message> {
-        let menu_button = |text: &'static str, message| {
+        let menu_button = |text: String, message| {
             wt! {
                 Button {
                     text: vec![
                         TextPart {
-                            text: text.to_owned(),
+                            text,
                             font_size: PxScale::from(50.0),
                             color: [1.0, 1.0, 1.0, 1.0],
                             font: Some("arcade".to_owned()),
@@ -159,8 +210,9 @@ impl Ui {
                     .vertical(),
             }),
             vec![
-                menu_button("RESUME", Message::ExitMenu),
-                menu_button("EXIT", Message::ExitGame),
+                menu_button(tr("menu.resume"), Message::ExitMenu),
+                menu_button(tr("menu.options"), Message::OpenOptions),
+                menu_button(tr("menu.save_and_quit"), Message::SaveAndQuit),
             ],
         );
         buttons_container
@@ -182,20 +234,24 @@ impl Ui {
 
     pub fn handle_key_state_changes(&mut self, changes: Vec<(u32, winit::event::ElementState)>) {
         for (key, state) in changes.into_iter() {
-            // Escape key
-            // if key == 1 {
-            //     if let winit::event::ElementState::Pressed = state {
-            //         self.show_menu = !self.show_menu;
-            //     }
-            // }
+        }
+    }
+
+    /// Open the menu, or close it if it is open. The options go back to the buttons of the menu instead.
+    pub fn toggle_menu(&mut self) {
+        if self.is_options_open() {
+            self.show_options = false;
+        } else {
+            self.show_menu = !self.show_menu;
         }
     }
 
     fn update(&mut self) {
-        for message in self.messages.drain(..) {
+        for message in std::mem::take(&mut self.messages) {
             match message {
-                Message::ExitMenu => self.show_menu = false,
-                Message::ExitGame => self.should_exit = true,
+                Message::ExitMenu => self.resume(),
+                Message::OpenOptions => self.open_options(),
+                Message::SaveAndQuit => self.quit(),
             }
         }
     }
@@ -204,8 +260,8 @@ impl Ui {
         false
     }
 
-    pub fn should_exit(&self) -> bool {
-        self.should_exit
+    pub fn should_quit(&self) -> bool {
+        self.should_quit
     }
 }
 
@@ -226,11 +282,40 @@ pub fn quint_element_state(state: winit::event::ElementState) -> quint::ButtonSt
     }
 }
 
+/// A rectangle of the window outside of which the primitives are not drawn, in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipRect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl ClipRect {
+    /// The part of the rectangle inside another one
+    pub fn intersection(self, other: ClipRect) -> ClipRect {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let right = (self.x + self.w).min(other.x + other.w);
+        let bottom = (self.y + self.h).min(other.y + other.h);
+        ClipRect {
+            x,
+            y,
+            w: (right - x).max(0),
+            h: (bottom - y).max(0),
+        }
+    }
+
+    pub fn contains(self, x: i32, y: i32) -> bool {
+        self.x <= x && x < self.x + self.w && self.y <= y && y < self.y + self.h
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RectanglePrimitive {
     pub layout: quint::Layout,
     pub color: [f32; 4],
     pub z: f32,
+    pub clip: Option<ClipRect>,
 }
 
 #[derive(Debug, Clone)]
@@ -243,6 +328,7 @@ pub struct TextPrimitive {
     pub z: f32,
     pub center_horizontally: bool,
     pub center_vertically: bool,
+    pub clip: Option<ClipRect>,
 }
 
 #[derive(Debug, Clone)]
@@ -250,6 +336,16 @@ pub struct TrianglesPrimitive {
     pub vertices: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
     pub color: [f32; 4],
+    pub clip: Option<ClipRect>,
+}
+
+/// A rectangle showing a texture of the texture atlas
+#[derive(Debug, Clone)]
+pub struct TexturedPrimitive {
+    pub layout: quint::Layout,
+    pub texture: TextureRect,
+    pub z: f32,
+    pub clip: Option<ClipRect>,
 }
 
 #[derive(Debug, Clone)]
@@ -265,11 +361,20 @@ pub struct PrimitiveBuffer {
     pub rectangle: Vec<RectanglePrimitive>,
     pub text: Vec<TextPrimitive>,
     pub triangles: Vec<TrianglesPrimitive>,
+    pub textured: Vec<TexturedPrimitive>,
+    /// The clipping rectangle of the primitives drawn from now on
+    clip: Option<ClipRect>,
 }
 
 impl PrimitiveBuffer {
+    /// Clip the primitives drawn from now on to a rectangle, or stop clipping them
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
     pub fn draw_rectangle(&mut self, color: [f32; 4], layout: quint::Layout, z: f32) {
-        self.rectangle.push(RectanglePrimitive { color, layout, z });
+        let clip = self.clip;
+        self.rectangle.push(RectanglePrimitive { color, layout, z, clip });
     }
 
     pub fn draw_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: [f32; 4], z: f32) {
@@ -282,6 +387,22 @@ impl PrimitiveBuffer {
                 height: h as f32,
             },
             z,
+            clip: self.clip,
+        });
+    }
+
+    /// Draw a texture of the texture atlas, which is only drawn in the states that give the atlas to the ui renderer
+    pub fn draw_textured_rect(&mut self, x: i32, y: i32, w: i32, h: i32, texture: TextureRect, z: f32) {
+        self.textured.push(TexturedPrimitive {
+            layout: quint::Layout {
+                x: x as f32,
+                y: y as f32,
+                width: w as f32,
+                height: h as f32,
+            },
+            texture,
+            z,
+            clip: self.clip,
         });
     }
 
@@ -315,6 +436,7 @@ impl PrimitiveBuffer {
             z,
             center_horizontally: false,
             center_vertically: true,
+            clip: self.clip,
         });
     }
 
@@ -323,6 +445,7 @@ impl PrimitiveBuffer {
             vertices,
             indices,
             color,
+            clip: self.clip,
         });
     }
 }
@@ -0,0 +1,171 @@
+//! Client-side precipitation particles. Particles are not spawned nor rendered under cover,
+//! which is found from the sky light of the chunks the server sent.
+use crate::world::World;
+use history_survival_common::{physics::BlockContainer, weather::Weather, world::BlockPos};
+use nalgebra::Vector3;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Maximum number of particles at the same time
+const MAX_PARTICLES: usize = 400;
+/// Horizontal distance from the camera at which particles spawn
+const SPAWN_RADIUS: f64 = 16.0;
+/// Maximum height above the camera at which particles spawn
+const SPAWN_HEIGHT: f64 = 12.0;
+/// Particles that fall this far below the camera are removed
+const DESPAWN_DEPTH: f64 = 8.0;
+/// Number of blocks above and below the camera that are scanned to find the covers of a column
+const COVER_SCAN_HEIGHT: i64 = 48;
+/// The cover cache is recomputed when the camera moves vertically by more than this
+const COVER_CACHE_MAX_OFFSET: i64 = 8;
+/// The maximum light level, only reached by blocks that the sky lights directly
+const SKY_LIGHT: u8 = 15;
+
+struct Particle {
+    pos: Vector3<f64>,
+}
+
+/// The falling rain or snow around the camera
+pub struct WeatherParticles {
+    weather: Weather,
+    particles: Vec<Particle>,
+    /// For every (x, z) column, the height of the highest block that the sky doesn't reach directly
+    cover_cache: HashMap<(i64, i64), i64>,
+    /// The chunk version of the world when the cache was computed
+    cache_chunk_version: u64,
+    /// The height of the camera when the cache was computed
+    cache_base_y: i64,
+    /// Fraction of the particles to spawn that weren't spawned yet, to spawn at a constant rate
+    spawn_accumulator: f64,
+}
+
+impl WeatherParticles {
+    pub fn new() -> Self {
+        Self {
+            weather: Weather::Clear,
+            particles: Vec::new(),
+            cover_cache: HashMap::new(),
+            cache_chunk_version: 0,
+            cache_base_y: 0,
+            spawn_accumulator: 0.0,
+        }
+    }
+
+    pub fn set_weather(&mut self, weather: Weather) {
+        self.weather = weather;
+    }
+
+    /// Get the height of the highest block of the column that the sky doesn't reach directly, using the cache
+    fn get_cover_height(&mut self, world: &World, x: i64, z: i64) -> i64 {
+        let base_y = self.cache_base_y;
+        *self.cover_cache.entry((x, z)).or_insert_with(|| {
+            for y in (base_y - COVER_SCAN_HEIGHT..=base_y + COVER_SCAN_HEIGHT).rev() {
+                let pos = BlockPos { px: x, py: y, pz: z };
+                let is_lit_by_sky = world.get_light(pos).map(|light| light == SKY_LIGHT).unwrap_or(true);
+                if !is_lit_by_sky || world.is_block_full(pos) {
+                    return y;
+                }
+            }
+            i64::MIN
+        })
+    }
+
+    /// Check whether some position is under a roof
+    fn is_covered(&mut self, world: &World, pos: Vector3<f64>) -> bool {
+        let block = BlockPos::from(pos);
+        self.get_cover_height(world, block.px, block.pz) >= block.py
+    }
+
+    /// Check whether the camera is under a roof, for example to muffle the sound of the rain
+    pub fn is_camera_covered(&mut self, world: &World, camera_pos: Vector3<f64>) -> bool {
+        self.is_covered(world, camera_pos)
+    }
+
+    /// Move, spawn and remove the particles
+    pub fn update(&mut self, world: &World, camera_pos: Vector3<f64>, seconds_delta: f64) {
+        // Invalidate the cover cache if the world changed or the camera moved too much
+        let camera_y = camera_pos.y.floor() as i64;
+        if world.get_chunk_version() != self.cache_chunk_version
+            || (camera_y - self.cache_base_y).abs() > COVER_CACHE_MAX_OFFSET
+        {
+            self.cover_cache.clear();
+            self.cache_chunk_version = world.get_chunk_version();
+            self.cache_base_y = camera_y;
+        }
+
+        // Move the particles, and remove those that hit something or went too far
+        let fall_speed = match self.weather {
+            Weather::Snow => 2.5,
+            _ => 18.0,
+        };
+        let particles = std::mem::take(&mut self.particles);
+        self.particles = particles
+            .into_iter()
+            .filter_map(|mut particle| {
+                particle.pos.y -= fall_speed * seconds_delta;
+                let offset = particle.pos - camera_pos;
+                let is_close = offset.y > -DESPAWN_DEPTH && offset.x.abs() < SPAWN_RADIUS && offset.z.abs() < SPAWN_RADIUS;
+                if is_close && !world.is_block_full(BlockPos::from(particle.pos)) {
+                    Some(particle)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        // Spawn new particles in the columns that are not covered
+        if !self.weather.has_precipitation() {
+            self.spawn_accumulator = 0.0;
+            return;
+        }
+        let spawn_rate = match self.weather {
+            Weather::Snow => 150.0,
+            _ => 400.0,
+        };
+        self.spawn_accumulator += spawn_rate * seconds_delta;
+        let mut rng = rand::thread_rng();
+        while self.spawn_accumulator >= 1.0 {
+            self.spawn_accumulator -= 1.0;
+            if self.particles.len() >= MAX_PARTICLES {
+                continue;
+            }
+            let pos = camera_pos
+                + Vector3::new(
+                    rng.gen_range(-SPAWN_RADIUS..SPAWN_RADIUS),
+                    rng.gen_range(0.0..SPAWN_HEIGHT),
+                    rng.gen_range(-SPAWN_RADIUS..SPAWN_RADIUS),
+                );
+            if !self.is_covered(world, pos) {
+                self.particles.push(Particle { pos });
+            }
+        }
+    }
+
+    /// Get the models of the particles that are not under a roof
+    pub fn get_models(&mut self, world: &World, rain_mesh_id: u32, snow_mesh_id: u32) -> Vec<crate::render::Model> {
+        let (mesh_id, scale) = match self.weather {
+            Weather::Snow => (snow_mesh_id, 1.0 / 24.0),
+            _ => (rain_mesh_id, 1.0 / 32.0),
+        };
+        let positions = self.particles.iter().map(|particle| particle.pos).collect::<Vec<_>>();
+        positions
+            .into_iter()
+            .filter(|pos| !self.is_covered(world, *pos))
+            .map(|pos| crate::render::Model {
+                mesh_id,
+                pos_x: pos.x as f32,
+                pos_y: pos.y as f32,
+                pos_z: pos.z as f32,
+                scale,
+                rot_offset: [0.0, 0.0, 0.0],
+                rot_y: 0.0,
+                roll: 0.0,
+            })
+            .collect()
+    }
+
+    /// Number of particles, for the debug info
+    pub fn num_particles(&self) -> usize {
+        self.particles.len()
+    }
+}
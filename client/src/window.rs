@@ -1,13 +1,13 @@
-use crate::{input::InputState, settings::Settings};
+use crate::{fps::FrameLimiter, input::InputState, keybindings::{Action, InputContext}, settings::{save_settings, Settings}};
 use anyhow::Result;
 use log::{info, warn};
 use std::time::Instant;
 use wgpu::Device;
 use futures::executor::block_on;
 use winit::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
-use winit::event::{ElementState, MouseButton};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta};
 use winit::event_loop::ControlFlow;
-use winit::window::Window;
+use winit::window::{Fullscreen, Window};
 
 /// A closure that creates a new instance of `State`.
 pub type StateFactory =
@@ -18,7 +18,6 @@ pub enum StateTransition {
     /// Don't transition, keep the current state.
     KeepCurrent,
     /// Transition to another state using its `StateFactory`.
-    #[allow(dead_code)] // TODO: remove when it will be used again
     ReplaceCurrent(StateFactory),
     /// Don't transition, close the current window.
     CloseWindow,
@@ -27,12 +26,14 @@ pub enum StateTransition {
 /// Read-only data that is provided to the states.
 #[derive(Debug, Clone)]
 pub struct WindowData {
-    /// Logical size of the window. See [the winit documentation](winit::dpi).
+    /// Logical size of the window, in pixels of the UI. See [the winit documentation](winit::dpi).
     pub logical_window_size: LogicalSize<f64>,
     /// Physical size of the window.
     pub physical_window_size: PhysicalSize<u32>,
     /// HiDpi factor of the window.
     pub hidpi_factor: f64,
+    /// Number of physical pixels per pixel of the UI: the HiDpi factor, unless the settings override it
+    pub ui_scale: f64,
     /// `true` if the window is currently focused
     pub focused: bool,
 }
@@ -73,14 +74,24 @@ pub trait State {
     fn handle_mouse_motion(&mut self, settings: &Settings, delta: (f64, f64));
     /// Cursor moved
     fn handle_cursor_movement(&mut self, logical_position: LogicalPosition<f64>);
+    /// Mouse wheel scrolled
+    fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta);
     /// Mouse clicked
     fn handle_mouse_state_changes(&mut self, changes: Vec<(MouseButton, ElementState)>);
     /// Key pressed
     fn handle_key_state_changes(&mut self, changes: Vec<(u32, ElementState)>);
+    /// Characters typed, for the text fields
+    fn handle_received_characters(&mut self, characters: Vec<char>);
+    /// Actions triggered by key presses
+    fn handle_actions(&mut self, actions: Vec<Action>);
+    /// The context in which the keys are resolved
+    fn input_context(&self) -> InputContext;
 }
 
 /// Color format of the window's color buffer
 pub const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+/// Color format of the buffers where the world is drawn before the post-processing, which keeps the colors above 1
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 /// Format of the window's depth buffer
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -92,8 +103,10 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
     let event_loop = winit::event_loop::EventLoop::new();
     let window = Window::new(&event_loop).expect("Failed to create window");
     window.set_title(&window_title);
+    if settings.fullscreen {
+        window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+    }
     // Create the Surface, i.e. the render target of the program
-    let hidpi_factor = window.scale_factor();
     let physical_window_size = window.inner_size();
     info!("Creating the swap chain");
     let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
@@ -105,8 +118,9 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
     }))
     .expect("Failed to create adapter");
     // TODO: device should be immutable
+    // The chunks are drawn with a single indirect call per pass where multi-draw-indirect is supported
     let (mut device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-        features: wgpu::Features::empty(),
+        features: adapter.features() & wgpu::Features::MULTI_DRAW_INDIRECT,
         limits: wgpu::Limits::default(),
         shader_validation: true
     }, None))
@@ -117,10 +131,11 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
         format: COLOR_FORMAT,
         width: physical_window_size.width,
         height: physical_window_size.height,
-        present_mode: wgpu::PresentMode::Mailbox,
+        present_mode: settings.vsync.present_mode(),
     };
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
     info!("Creating the multisampled texture buffer");
+    let mut sample_count = settings.antialiasing.sample_count();
     let texture_view_descriptor = wgpu::TextureViewDescriptor::default();
     let mut msaa_texture_descriptor = wgpu::TextureDescriptor {
         label: None,
@@ -130,7 +145,7 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
             depth: 1,
         },
         mip_level_count: 1,
-        sample_count: SAMPLE_COUNT,
+        sample_count,
         dimension: wgpu::TextureDimension::D2,
         format: sc_desc.format,
         usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
@@ -146,27 +161,46 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
             depth: 1,
         },
         mip_level_count: 1,
-        sample_count: SAMPLE_COUNT,
+        sample_count,
         dimension: wgpu::TextureDimension::D2,
         format: DEPTH_FORMAT,
-        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+        // The ambient occlusion reads the depth of the world
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
     };
     let mut depth_texture = device.create_texture(&depth_texture_descriptor);
     let mut depth_texture_view = depth_texture.create_view(&texture_view_descriptor);
+    info!("Creating the HDR buffers of the world");
+    let mut hdr_msaa_texture_descriptor = wgpu::TextureDescriptor {
+        format: HDR_FORMAT,
+        ..msaa_texture_descriptor.clone()
+    };
+    let mut hdr_msaa_texture_view = device.create_texture(&hdr_msaa_texture_descriptor).create_view(&texture_view_descriptor);
+    // The multisampled world is resolved into this buffer, which the post-processing reads
+    let mut hdr_texture_descriptor = wgpu::TextureDescriptor {
+        sample_count: 1,
+        format: HDR_FORMAT,
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        ..msaa_texture_descriptor.clone()
+    };
+    let mut hdr_texture_view = device.create_texture(&hdr_texture_descriptor).create_view(&texture_view_descriptor);
 
     let mut window_data = {
         let physical_window_size = window.inner_size();
         let hidpi_factor = window.scale_factor();
-        let logical_window_size = physical_window_size.to_logical(hidpi_factor);
+        let ui_scale = settings.ui_scale(hidpi_factor);
+        let logical_window_size = physical_window_size.to_logical(ui_scale);
         WindowData {
             logical_window_size,
             physical_window_size,
             hidpi_factor,
+            ui_scale,
             focused: false,
         }
     };
 
-    let mut input_state = InputState::new();
+    let mut input_state = InputState::new(settings.key_bindings.clone());
+    // Keep the bindings without their conflicts, so that they only differ once the player rebinds a key
+    settings.key_bindings = input_state.key_bindings().clone();
 
     let mut window_flags = WindowFlags {
         grab_cursor: false,
@@ -180,14 +214,17 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
     queue.submit(vec![cmd]);
 
     let mut previous_time = std::time::Instant::now();
+    let mut frame_limiter = FrameLimiter::default();
 
     let mut window_resized = false;
     let mut mouse_state_changes = Vec::new();
     let mut key_state_changes = Vec::new();
+    let mut received_characters = Vec::new();
 
     // Main loop
     event_loop.run(move |event, _, control_flow| {
         use winit::event::Event::*;
+        input_state.set_context(state.input_context());
         match event {
             /* NORMAL EVENT HANDLING */
             WindowEvent { event, .. } => {
@@ -197,7 +234,7 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
                     Moved(_) => (),
                     CloseRequested | Destroyed => *control_flow = ControlFlow::Exit,
                     DroppedFile(_) | HoveredFile(_) | HoveredFileCancelled => (),
-                    ReceivedCharacter(_) => (),
+                    ReceivedCharacter(c) => received_characters.push(c),
                     Focused(focused) => {
                         window_data.focused = focused;
                         input_state.clear();
@@ -207,8 +244,12 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
                             key_state_changes.push((input.scancode, input.state));
                         }
                     }
-                    CursorMoved { position, .. } => state.handle_cursor_movement(position.to_logical(hidpi_factor)),
-                    CursorEntered { .. } | CursorLeft { .. } | MouseWheel { .. } => (),
+                    CursorMoved { position, .. } => state.handle_cursor_movement(position.to_logical(window_data.ui_scale)),
+                    CursorEntered { .. } | CursorLeft { .. } => (),
+                    MouseWheel { delta, .. } => {
+                        input_state.process_mouse_wheel(delta);
+                        state.handle_mouse_wheel(delta);
+                    }
                     MouseInput {
                         button,
                         state: element_state,
@@ -235,35 +276,87 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
             }
             /* MAIN LOOP TICK */
             MainEventsCleared => {
+                // Cap the frame rate, especially in the menus where there is not much to see
+                let in_menu = !window_flags.grab_cursor || !window_data.focused;
+                std::thread::sleep(frame_limiter.wait_time(Instant::now(), settings.frame_rate_cap(in_menu)));
+
+                // A minimized window has no area: nothing can be drawn and the buffers can't be created,
+                // so they are recreated once the window is restored
+                let minimized = {
+                    let size = window.inner_size();
+                    size.width == 0 || size.height == 0
+                };
+                let resize_buffers = window_resized && !minimized;
                 // If the window was resized, update the SwapChain and the window data
-                if window_resized {
+                if resize_buffers {
                     info!("The window was resized, adjusting buffers...");
                     // Update window data
                     window_data.physical_window_size = window.inner_size();
                     window_data.hidpi_factor = window.scale_factor();
-                    window_data.logical_window_size = window_data.physical_window_size.to_logical(window_data.hidpi_factor);
                     // Update SwapChain
                     sc_desc.width = window_data.physical_window_size.width;
                     sc_desc.height = window_data.physical_window_size.height;
                     swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                }
+                // The UI is scaled again when the window moves to another screen or when the settings change
+                window_data.ui_scale = settings.ui_scale(window_data.hidpi_factor);
+                window_data.logical_window_size = window_data.physical_window_size.to_logical(window_data.ui_scale);
+                // The swap chain is also recreated when the vsync setting changes
+                if settings.vsync.present_mode() != sc_desc.present_mode {
+                    info!("Changing the present mode to {:?}", settings.vsync.present_mode());
+                    sc_desc.present_mode = settings.vsync.present_mode();
+                    swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                }
+                // If the window was resized or the anti-aliasing changed, recreate the buffers
+                if resize_buffers || settings.antialiasing.sample_count() != sample_count {
+                    sample_count = settings.antialiasing.sample_count();
                     // TODO: remove copy/paste
                     // Update depth buffer
                     depth_texture_descriptor.size.width = sc_desc.width;
                     depth_texture_descriptor.size.height = sc_desc.height;
+                    depth_texture_descriptor.sample_count = sample_count;
                     depth_texture = device.create_texture(&depth_texture_descriptor);
                     depth_texture_view = depth_texture.create_view(&texture_view_descriptor);
                     // Udate MSAA frame buffer
                     msaa_texture_descriptor.size.width = sc_desc.width;
                     msaa_texture_descriptor.size.height = sc_desc.height;
+                    msaa_texture_descriptor.sample_count = sample_count;
                     msaa_texture = device.create_texture(&msaa_texture_descriptor);
                     msaa_texture_view = msaa_texture.create_view(&texture_view_descriptor);
+                    // Update HDR buffers
+                    hdr_msaa_texture_descriptor.size = msaa_texture_descriptor.size;
+                    hdr_msaa_texture_descriptor.sample_count = sample_count;
+                    hdr_msaa_texture_view = device.create_texture(&hdr_msaa_texture_descriptor).create_view(&texture_view_descriptor);
+                    hdr_texture_descriptor.size = msaa_texture_descriptor.size;
+                    hdr_texture_view = device.create_texture(&hdr_texture_descriptor).create_view(&texture_view_descriptor);
+                }
+                if !minimized {
+                    window_resized = false;
                 }
-                window_resized = false;
 
                 // Update state
                 let (v1, v2) = (Vec::new(), Vec::new()); // TODO: clean up
                 state.handle_mouse_state_changes(std::mem::replace(&mut mouse_state_changes, v1));
                 state.handle_key_state_changes(std::mem::replace(&mut key_state_changes, v2));
+                state.handle_received_characters(std::mem::take(&mut received_characters));
+                let mut actions = input_state.take_triggered_actions();
+                if actions.contains(&Action::ToggleFullscreen) {
+                    settings.fullscreen = !settings.fullscreen;
+                    save_settings(&settings);
+                    actions.retain(|action| *action != Action::ToggleFullscreen);
+                }
+                if actions.contains(&Action::ToggleCulling) {
+                    settings.occlusion_culling = !settings.occlusion_culling;
+                    info!("Toggling the occlusion culling {}", if settings.occlusion_culling { "on" } else { "off" });
+                    save_settings(&settings);
+                    actions.retain(|action| *action != Action::ToggleCulling);
+                }
+                // The fullscreen is changed with F11 or in the settings
+                if settings.fullscreen != window.fullscreen().is_some() {
+                    info!("Toggling the fullscreen {}", if settings.fullscreen { "on" } else { "off" });
+                    window.set_fullscreen(if settings.fullscreen { Some(Fullscreen::Borderless(window.current_monitor())) } else { None });
+                }
+                state.handle_actions(actions);
                 let seconds_delta = {
                     let current_time = Instant::now();
                     let delta = current_time - previous_time;
@@ -280,6 +373,12 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
                         &mut device,
                     )
                     .expect("Failed to `update` the current window state"); // TODO: remove this
+                if settings.key_bindings != *input_state.key_bindings() {
+                    input_state.set_key_bindings(settings.key_bindings.clone());
+                }
+                if settings.language != crate::lang::current_language() {
+                    crate::lang::set_language(&settings.language);
+                }
 
                 // Update window flags
                 window.set_title(&window_flags.window_title);
@@ -319,15 +418,33 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> ! {
                     }
                 }
 
+                if minimized {
+                    return;
+                }
+
                 // Render frame
-                let swap_chain_output = swap_chain.get_current_frame().expect("Failed to unwrap swap chain output.");
+                let swap_chain_output = match swap_chain.get_current_frame() {
+                    Ok(output) => output,
+                    Err(err) => {
+                        // The swap chain is outdated or lost when the window changes in ways that don't always
+                        // send a resize event, for example when switching to the fullscreen
+                        warn!("Failed to get the next frame of the swap chain ({:?}), recreating it", err);
+                        swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                        return;
+                    }
+                };
+                // Without multisampling, the frame and the world are drawn into the resolved buffers directly
+                let multisampled = sample_count > 1;
                 let (state_transition, commands) = state
                     .render(
                         &settings,
                         WindowBuffers {
                             texture_buffer: &swap_chain_output.output.view,
-                            multisampled_texture_buffer: &msaa_texture_view,
+                            multisampled_texture_buffer: if multisampled { &msaa_texture_view } else { &swap_chain_output.output.view },
                             depth_buffer: &depth_texture_view,
+                            hdr_multisampled_texture_buffer: if multisampled { &hdr_msaa_texture_view } else { &hdr_texture_view },
+                            hdr_texture_buffer: &hdr_texture_view,
+                            sample_count,
                         },
                         &mut device,
                         &window_data,
@@ -364,11 +481,17 @@ pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
     a: 1.0,
 };
 pub const CLEAR_DEPTH: f32 = 1.0;
-pub const SAMPLE_COUNT: u32 = 4;
 
 #[derive(Debug, Clone, Copy)]
 pub struct WindowBuffers<'a> {
     pub texture_buffer: &'a wgpu::TextureView,
     pub multisampled_texture_buffer: &'a wgpu::TextureView,
     pub depth_buffer: &'a wgpu::TextureView,
+    /// The multisampled buffer where the world is drawn, in `HDR_FORMAT`
+    pub hdr_multisampled_texture_buffer: &'a wgpu::TextureView,
+    /// The resolved world, read by the post-processing that draws it into the multisampled frame buffer
+    pub hdr_texture_buffer: &'a wgpu::TextureView,
+    /// Number of samples of the multisampled buffers and of the depth buffer.
+    /// With 1 sample, the multisampled buffers are the resolved buffers themselves.
+    pub sample_count: u32,
 }
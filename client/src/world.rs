@@ -1,13 +1,22 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use history_survival_common::{
-    block::BlockMesh,
-    physics::BlockContainer,
+    biome::Biome,
+    block::{entity::BlockEntity, BlockId, BlockMesh},
+    data::TextureRect,
+    item::map::{MapCanvas, MapId, MapPatch},
+    physics::{aabb::AABB, BlockContainer},
     player::{CloseChunks, RenderDistance},
+    time::WorldTime,
     world::{BlockPos, ChunkPos, Chunk, LightChunk},
 };
+use crate::biome_colors::BiomeColors;
+use crate::block_entity_textures::BlockEntityTextures;
 use crate::render::WorldRenderer;
-use crate::render::world::{ChunkMeshData, MeshingWorker, start_meshing_worker};
+use crate::settings::Lighting;
+use crate::render::world::{sample_light, ChunkMeshData, LightSource, MeshingFocus, MeshingWorker, start_meshing_worker};
+use nalgebra::Vector3;
 
 /// Client-side world.
 /// It is currently responsible for:
@@ -16,22 +25,57 @@ use crate::render::world::{ChunkMeshData, MeshingWorker, start_meshing_worker};
 pub struct World {
     /// The chunks
     chunks: HashMap<ChunkPos, ClientChunk>,
-    /// The meshing worker
+    /// The meshing worker, and where the camera is so that it meshes the chunks the player sees first
     meshing_worker: MeshingWorker,
+    meshing_focus: Arc<Mutex<Option<MeshingFocus>>>,
+    /// The lighting of the chunks that the meshing worker meshes next
+    meshing_lighting: Arc<Mutex<Lighting>>,
     /// The chunks the player can see
     close_chunks: CloseChunks,
     /// The renderer
     renderer: WorldRenderer,
+    /// Incremented every time a chunk is added or removed, so that caches of the world can be invalidated
+    chunk_version: u64,
+    /// For every block id, whether the block is climbable
+    climbable_blocks: Vec<bool>,
+    /// The textures of the banners, the paintings and the framed maps
+    block_entity_textures: BlockEntityTextures,
+    /// The canvases of the maps, as sent by the server
+    maps: HashMap<MapId, MapCanvas>,
+    /// Whether some chunk meshes didn't fit in the memory budget of the renderer since the last check
+    memory_pressure: bool,
+    /// The water block, which changes the view when the camera is in it
+    water_block: Option<BlockId>,
 }
 
 impl World {
     /// Create a new empty world using the provided chunks
-    pub fn new(block_meshes: Vec<BlockMesh>, renderer: WorldRenderer) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        block_meshes: Vec<BlockMesh>,
+        water_block: Option<BlockId>,
+        climbable_blocks: Vec<bool>,
+        dynamic_textures: Vec<TextureRect>,
+        map_items: Vec<String>,
+        greedy_meshing: bool,
+        lighting: Lighting,
+        renderer: WorldRenderer,
+    ) -> Self {
+        let (meshing_worker, meshing_focus, meshing_lighting) =
+            start_meshing_worker(block_meshes, water_block, greedy_meshing, lighting);
         Self {
             chunks: HashMap::new(),
-            meshing_worker: start_meshing_worker(block_meshes),
+            meshing_worker,
+            meshing_focus,
+            meshing_lighting,
             close_chunks: CloseChunks::new(&RenderDistance::default()),
             renderer,
+            chunk_version: 0,
+            climbable_blocks,
+            block_entity_textures: BlockEntityTextures::new(dynamic_textures, map_items),
+            maps: HashMap::new(),
+            memory_pressure: false,
+            water_block,
         }
     }
 
@@ -39,11 +83,15 @@ impl World {
     pub fn add_chunk(&mut self, chunk: Arc<Chunk>, light_chunk: Arc<LightChunk>) {
         // TODO: make sure this only happens once
         let chunk_pos = chunk.pos;
+        self.chunk_version += 1;
+        self.block_entity_textures.update_chunk(&chunk, &self.maps);
         self.chunks.insert(chunk_pos, ClientChunk {
             chunk,
             light_chunk,
             is_in_meshing_queue: false,
+            meshing_queued_at: Instant::now(),
             needs_remesh: true,
+            has_mesh: false,
         });
         // Queue adjacent chunks for meshing
         for i in -1..=1 {
@@ -58,7 +106,42 @@ impl World {
         }
     }
 
-    /// Fetch the new chunk meshes from the meshing worker
+    /// Receive the whole canvas of a map from the server
+    pub fn set_map_canvas(&mut self, map_id: MapId, canvas: MapCanvas) {
+        self.maps.insert(map_id, canvas);
+        self.map_changed(map_id);
+    }
+
+    /// Receive an update of a region of a map from the server
+    pub fn update_map(&mut self, map_id: MapId, patch: &MapPatch) {
+        if let Some(canvas) = self.maps.get_mut(&map_id) {
+            if canvas.apply_patch(patch) {
+                self.map_changed(map_id);
+            }
+        }
+    }
+
+    /// Update the item frames that display a map
+    fn map_changed(&mut self, map_id: MapId) {
+        for chunk_pos in self.block_entity_textures.update_map(map_id, &self.maps) {
+            if let Some(client_chunk) = self.chunks.get_mut(&chunk_pos) {
+                client_chunk.needs_remesh = true;
+            }
+        }
+    }
+
+    /// Return the canvas of a map, if it was received
+    pub fn get_map(&self, map_id: MapId) -> Option<&MapCanvas> {
+        self.maps.get(&map_id)
+    }
+
+    /// The maps displayed by item frames whose canvas must be requested from the server
+    pub fn take_missing_maps(&mut self) -> Vec<MapId> {
+        let missing = self.block_entity_textures.take_missing_maps();
+        missing.into_iter().filter(|map_id| !self.maps.contains_key(map_id)).collect()
+    }
+
+    /// Fetch the new chunk meshes from the meshing worker, then fill the holes that the removed meshes left
     pub fn get_new_chunk_meshes(
         &mut self,
         device: &wgpu::Device,
@@ -67,43 +150,90 @@ impl World {
         while let Some(mesh) = self.meshing_worker.get_result() {
             if let Some(client_chunk) = self.chunks.get_mut(&mesh.0) {
                 client_chunk.is_in_meshing_queue = false;
-                self.renderer.update_chunk_mesh(device, encoder, mesh);
+                if self.renderer.update_chunk_mesh(device, encoder, mesh).is_ok() {
+                    client_chunk.has_mesh = true;
+                } else {
+                    // The chunk is meshed again once the far chunks make room
+                    client_chunk.has_mesh = false;
+                    client_chunk.needs_remesh = true;
+                    self.memory_pressure = true;
+                }
             }
         }
+        self.renderer.compact_chunk_buffers(device, encoder);
+    }
+
+    /// Change how the light is spread on the faces of the blocks, meshing every chunk again if it changed
+    pub fn set_lighting(&mut self, lighting: Lighting) {
+        let mut meshing_lighting = self.meshing_lighting.lock().unwrap();
+        if *meshing_lighting != lighting {
+            *meshing_lighting = lighting;
+            for client_chunk in self.chunks.values_mut() {
+                client_chunk.needs_remesh = true;
+            }
+        }
+    }
+
+    /// Whether some chunk meshes didn't fit in the memory budget of the renderer since the last call
+    pub fn take_memory_pressure(&mut self) -> bool {
+        std::mem::take(&mut self.memory_pressure)
     }
 
     /// Remove chunks that are too far for the player
     pub fn remove_far_chunks(&mut self, player_chunk: ChunkPos, render_distance: &RenderDistance) {
-        let Self { ref mut chunks, ref mut renderer, .. } = self;
+        let Self { ref mut chunks, ref mut renderer, ref mut chunk_version, ref mut block_entity_textures, .. } = self;
         chunks.retain(|chunk_pos, _| {
             if render_distance.is_chunk_visible(player_chunk, *chunk_pos) {
                 true
             } else {
                 renderer.remove_chunk_mesh(*chunk_pos);
+                block_entity_textures.remove_chunk(*chunk_pos);
+                *chunk_version += 1;
                 false
             }
         })
     }
 
-    /// Start the meshing of a few chunks
-    pub fn enqueue_chunks_for_meshing(&mut self, player_chunk: ChunkPos, render_distance: &RenderDistance) {
+    /// Start the meshing of a few chunks, the closest ones and the ones in front of the camera first.
+    /// The chunks that are already in the meshing queue are reordered as the camera moves.
+    pub fn enqueue_chunks_for_meshing(
+        &mut self,
+        player_chunk: ChunkPos,
+        look_direction: Vector3<f64>,
+        render_distance: &RenderDistance,
+    ) {
+        let focus = MeshingFocus {
+            camera_chunk: player_chunk,
+            look_direction,
+        };
+        *self.meshing_focus.lock().unwrap() = Some(focus);
         self.close_chunks.update(render_distance);
-        for pos in self.close_chunks.get_close_chunks() {
-            let pos = pos.offset_by_pos(player_chunk);
-            if let Some(client_chunk) = self.chunks.get(&pos) {
-                if client_chunk.needs_remesh && !client_chunk.is_in_meshing_queue {
-                    let res = self.meshing_worker.enqueue(self.create_chunk_mesh_data(pos));
-                    match res {
-                        // If the meshing queue is not full, update chunk status
-                        Ok(()) => {
-                            let client_chunk = self.chunks.get_mut(&pos).expect("Logic error");
-                            client_chunk.needs_remesh = false;
-                            client_chunk.is_in_meshing_queue = true;
-                        },
-                        // If the meshing queue is full, stop
-                        Err(_) => break,
-                    }
-                }
+        let mut chunks_to_mesh = self
+            .close_chunks
+            .get_close_chunks()
+            .iter()
+            .map(|pos| pos.offset_by_pos(player_chunk))
+            .filter(|pos| {
+                self.chunks
+                    .get(pos)
+                    .map(|client_chunk| client_chunk.needs_remesh && !client_chunk.is_in_meshing_queue)
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        // The close chunks are sorted by distance, and the sort is stable
+        chunks_to_mesh.sort_by_key(|pos| focus.priority(*pos));
+        for pos in chunks_to_mesh {
+            let res = self.meshing_worker.enqueue(self.create_chunk_mesh_data(pos));
+            match res {
+                // If the meshing queue is not full, update chunk status
+                Ok(()) => {
+                    let client_chunk = self.chunks.get_mut(&pos).expect("Logic error");
+                    client_chunk.needs_remesh = false;
+                    client_chunk.is_in_meshing_queue = true;
+                    client_chunk.meshing_queued_at = Instant::now();
+                },
+                // If the meshing queue is full, stop
+                Err(_) => break,
             }
         }
     }
@@ -130,10 +260,13 @@ impl World {
             light_chunk: client_chunk.light_chunk.clone(),
             all_chunks,
             all_light_chunks,
+            texture_overrides: self.block_entity_textures.get_texture_overrides(pos),
         }
     }
 
-    /// Render the chunks
+    /// Render the chunks and the models. `held_model` is placed relative to the camera.
+    /// `hitboxes` are drawn when `debug_mode` shows them.
+    #[allow(clippy::too_many_arguments)]
     pub fn render_chunks(
         &mut self,
         device: &wgpu::Device,
@@ -143,17 +276,147 @@ impl World {
         frustum: &crate::render::Frustum,
         enable_culling: bool,
         pointed_block: Option<(BlockPos, usize)>,
+        break_stages: &[(BlockPos, u8)],
         models: &[crate::render::world::Model],
+        world_time: WorldTime,
+        fog_distance: f32,
+        held_model: Option<&crate::render::world::Model>,
+        debug_mode: crate::render::DebugRenderMode,
+        hitboxes: &[AABB],
     ) {
         // TODO: remove some of the parameters and calculate them here instead
+        self.block_entity_textures.upload(&mut self.renderer, device, encoder);
         self.get_new_chunk_meshes(device, encoder);
-        self.renderer.render(device, encoder, buffers, data, frustum, enable_culling, pointed_block, models);
+        // Light the models like the blocks around them, whose light comes from the sky
+        let sky_light_factor = world_time.sky_light_factor() as f32;
+        let model_brightness = models
+            .iter()
+            .map(|model| {
+                let pos = [
+                    (model.pos_x + model.rot_offset[0]) as f64,
+                    (model.pos_y + model.rot_offset[1]) as f64,
+                    (model.pos_z + model.rot_offset[2]) as f64,
+                ];
+                sample_light(self, pos).brightness() * sky_light_factor
+            })
+            .collect::<Vec<_>>();
+        let camera = [frustum.position.x, frustum.position.y, frustum.position.z];
+        let held_model = held_model.map(|model| (model, sample_light(self, camera).brightness() * sky_light_factor));
+        let biome_colors = BiomeColors::around(self, BlockPos::from(frustum.position));
+        let underwater = self.water_block.is_some() && self.get_block(BlockPos::from(frustum.position)) == self.water_block;
+        self.renderer.render(
+            device,
+            encoder,
+            buffers,
+            data,
+            frustum,
+            enable_culling,
+            pointed_block,
+            break_stages,
+            models,
+            &model_brightness,
+            world_time,
+            biome_colors,
+            fog_distance,
+            held_model,
+            underwater,
+            debug_mode,
+            hitboxes,
+        );
     }
 
     /// Number of loaded chunks
     pub fn num_loaded_chunks(&self) -> usize {
         self.chunks.len()
     }
+
+    /// Whether the chunk at some position was received
+    pub fn has_chunk(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    /// Whether the chunk at some position was received and meshed
+    pub fn is_chunk_meshed(&self, pos: ChunkPos) -> bool {
+        self.chunks.get(&pos).map(|client_chunk| client_chunk.has_mesh).unwrap_or(false)
+    }
+
+    /// Mark for meshing the chunks that were never meshed, and the chunks that stayed in the meshing queue
+    /// for longer than `timeout`. Return the number of chunks that were marked.
+    pub fn requeue_unmeshed_chunks(&mut self, now: Instant, timeout: Duration) -> usize {
+        let mut requeued = 0;
+        for client_chunk in self.chunks.values_mut() {
+            let is_stuck = client_chunk.is_in_meshing_queue && now - client_chunk.meshing_queued_at >= timeout;
+            let is_forgotten = !client_chunk.is_in_meshing_queue && !client_chunk.needs_remesh && !client_chunk.has_mesh;
+            if is_stuck || is_forgotten {
+                client_chunk.is_in_meshing_queue = false;
+                client_chunk.needs_remesh = true;
+                requeued += 1;
+            }
+        }
+        requeued
+    }
+
+    /// The content hash of the chunk at some position, or `None` if the chunk is not loaded
+    pub fn get_chunk_hash(&self, pos: ChunkPos) -> Option<u64> {
+        self.chunks.get(&pos).map(|client_chunk| client_chunk.chunk.content_hash())
+    }
+
+    /// Get the current chunk version. It changes every time a chunk is added or removed.
+    pub fn get_chunk_version(&self) -> u64 {
+        self.chunk_version
+    }
+
+    /// Return the block at position `pos`, or `None` if the chunk is not loaded
+    pub fn get_block(&self, pos: BlockPos) -> Option<BlockId> {
+        self.chunks
+            .get(&pos.containing_chunk_pos())
+            .map(|chunk| chunk.chunk.get_block_at(pos.pos_in_containing_chunk()))
+    }
+
+    /// Return the biome of the column of position `pos`, or `None` if the chunk is not loaded
+    pub fn get_biome(&self, pos: BlockPos) -> Option<Biome> {
+        let (x, _, z) = pos.pos_in_containing_chunk();
+        self.chunks
+            .get(&pos.containing_chunk_pos())
+            .map(|chunk| chunk.chunk.get_biome_at((x, z)))
+    }
+
+    /// Return the block entity at position `pos`, if any
+    pub fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        self.chunks
+            .get(&pos.containing_chunk_pos())
+            .and_then(|chunk| chunk.chunk.get_block_entity(pos.pos_in_containing_chunk()))
+    }
+
+    /// The block entities of the loaded chunks, with their position
+    pub fn loaded_block_entities(&self) -> impl Iterator<Item = (BlockPos, &BlockEntity)> + '_ {
+        self.chunks.values().flat_map(|client_chunk| {
+            let chunk_pos = client_chunk.chunk.pos;
+            client_chunk
+                .chunk
+                .block_entities
+                .iter()
+                .map(move |(pos, block_entity)| (chunk_pos.block_pos(*pos), block_entity))
+        })
+    }
+
+    /// Return the light level at position `pos`, or `None` if the chunk is not loaded
+    pub fn get_light(&self, pos: BlockPos) -> Option<u8> {
+        self.chunks
+            .get(&pos.containing_chunk_pos())
+            .map(|chunk| chunk.light_chunk.get_light_at(pos.pos_in_containing_chunk()))
+    }
+}
+
+impl LightSource for World {
+    fn is_opaque(&self, x: i64, y: i64, z: i64) -> bool {
+        self.is_block_full(BlockPos { px: x, py: y, pz: z })
+    }
+
+    fn get_light(&self, x: i64, y: i64, z: i64) -> u8 {
+        // Unloaded chunks are considered lit by the sky
+        self.get_light(BlockPos { px: x, py: y, pz: z }).unwrap_or(15)
+    }
 }
 
 impl BlockContainer for World {
@@ -164,6 +427,16 @@ impl BlockContainer for World {
             Some(chunk) => chunk.chunk.get_block_at(pos.pos_in_containing_chunk()) != 0,
         }
     }
+
+    fn is_block_climbable(&self, pos: BlockPos) -> bool {
+        match self.chunks.get(&pos.containing_chunk_pos()) {
+            None => false,
+            Some(chunk) => {
+                let block = chunk.chunk.get_block_at(pos.pos_in_containing_chunk());
+                self.climbable_blocks.get(block as usize).cloned().unwrap_or(false)
+            }
+        }
+    }
 }
 
 /// The data for each chunk stored by the client
@@ -174,6 +447,10 @@ struct ClientChunk {
     pub light_chunk: Arc<LightChunk>,
     /// True if the chunk is in the meshing queue
     pub is_in_meshing_queue: bool,
+    /// When the chunk was last sent to the meshing queue
+    pub meshing_queued_at: Instant,
     /// True if the chunk needs to be meshed, for example before it never was meshed or because it changed.
     pub needs_remesh: bool,
+    /// True if a mesh of the chunk was received
+    pub has_mesh: bool,
 }
\ No newline at end of file
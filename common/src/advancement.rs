@@ -0,0 +1,128 @@
+//! Advancements: goals that the players reach while they play, following the advancements of the data.
+//!
+//! The server triggers advancement events when the players do something, and tells the players about the
+//! advancements that they earn.
+use crate::registry::Registry;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+pub type AdvancementId = u32;
+
+/// An advancement as it is written in the data files
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Advancement")]
+pub struct AdvancementType {
+    /// The title shown to the player who earns the advancement
+    pub title: String,
+    pub description: String,
+    pub trigger: AdvancementTrigger,
+}
+
+/// What earns an advancement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum AdvancementTrigger {
+    /// Break any block
+    BreakBlock,
+    /// Stay alive and connected from the nightfall to the morning
+    SurviveNight,
+    /// Go down to some height or below
+    ReachDepth(i64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Advancement {
+    pub name: String,
+    pub ty: AdvancementType,
+}
+
+/// Something that a player did, which may earn advancements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvancementEvent {
+    BlockBroken,
+    NightSurvived,
+    /// The player is at some height
+    ReachedHeight(i64),
+}
+
+impl AdvancementTrigger {
+    /// Whether an event earns the advancements with this trigger
+    pub fn is_triggered_by(self, event: AdvancementEvent) -> bool {
+        match (self, event) {
+            (AdvancementTrigger::BreakBlock, AdvancementEvent::BlockBroken) => true,
+            (AdvancementTrigger::SurviveNight, AdvancementEvent::NightSurvived) => true,
+            (AdvancementTrigger::ReachDepth(depth), AdvancementEvent::ReachedHeight(height)) => height <= depth,
+            _ => false,
+        }
+    }
+}
+
+/// The advancements that a player earned
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerAdvancements {
+    earned: HashSet<AdvancementId>,
+}
+
+impl PlayerAdvancements {
+    pub fn has(&self, id: AdvancementId) -> bool {
+        self.earned.contains(&id)
+    }
+
+    /// Mark an advancement as earned, for example when it is loaded. Return whether it was not earned yet.
+    pub fn earn(&mut self, id: AdvancementId) -> bool {
+        self.earned.insert(id)
+    }
+
+    /// The earned advancements, in no particular order
+    pub fn earned(&self) -> impl Iterator<Item = AdvancementId> + '_ {
+        self.earned.iter().copied()
+    }
+
+    /// Earn the advancements that an event triggers. Return the advancements that were not earned yet.
+    pub fn trigger(&mut self, advancements: &Registry<Advancement>, event: AdvancementEvent) -> Vec<AdvancementId> {
+        (0..advancements.get_number_of_ids())
+            .filter(|&id| advancements.get_value_by_id(id).unwrap().ty.trigger.is_triggered_by(event))
+            .filter(|&id| self.earn(id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advancements() -> Registry<Advancement> {
+        let mut registry = Registry::default();
+        let triggers = [
+            ("first_block", AdvancementTrigger::BreakBlock),
+            ("night", AdvancementTrigger::SurviveNight),
+            ("deep", AdvancementTrigger::ReachDepth(-20)),
+        ];
+        for (name, trigger) in triggers.iter() {
+            let ty = AdvancementType {
+                title: name.to_string(),
+                description: String::new(),
+                trigger: *trigger,
+            };
+            registry.register(name.to_string(), Advancement { name: name.to_string(), ty }).unwrap();
+        }
+        registry
+    }
+
+    #[test]
+    fn advancements_are_earned_once() {
+        let advancements = advancements();
+        let mut player = PlayerAdvancements::default();
+        assert_eq!(player.trigger(&advancements, AdvancementEvent::BlockBroken), vec![0]);
+        assert_eq!(player.trigger(&advancements, AdvancementEvent::BlockBroken), vec![]);
+        assert!(player.has(0));
+        assert!(!player.has(1));
+    }
+
+    #[test]
+    fn depth_is_reached_below_the_height() {
+        let advancements = advancements();
+        let mut player = PlayerAdvancements::default();
+        assert_eq!(player.trigger(&advancements, AdvancementEvent::ReachedHeight(-19)), vec![]);
+        assert_eq!(player.trigger(&advancements, AdvancementEvent::ReachedHeight(-25)), vec![2]);
+    }
+}
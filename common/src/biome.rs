@@ -0,0 +1,44 @@
+//! Biomes: the climate of the columns of the world, chosen by the world generator.
+//!
+//! The biomes are sent with the chunks, and the client uses them to color the water, the sky and the fog.
+
+/// The id of a biome, as stored in the chunks
+pub type BiomeId = u8;
+
+/// A biome
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Biome {
+    #[default]
+    Plains = 0,
+    Ocean = 1,
+    Swamp = 2,
+    Snowy = 3,
+}
+
+impl Biome {
+    /// All the biomes, ordered by id
+    pub const ALL: [Biome; 4] = [Biome::Plains, Biome::Ocean, Biome::Swamp, Biome::Snowy];
+
+    pub fn id(self) -> BiomeId {
+        self as BiomeId
+    }
+
+    /// The biome with some id. Unknown ids are plains, so that newer servers can add biomes.
+    pub fn from_id(id: BiomeId) -> Self {
+        Self::ALL.get(id as usize).copied().unwrap_or(Biome::Plains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_round_trip() {
+        for biome in Biome::ALL.iter() {
+            assert_eq!(Biome::from_id(biome.id()), *biome);
+        }
+        assert_eq!(Biome::from_id(200), Biome::Plains);
+    }
+}
@@ -0,0 +1,179 @@
+//! Banners: decorative blocks whose texture is made of layered patterns.
+//!
+//! The server only stores and syncs the pattern of every banner. The texture is composed by the clients.
+use image::{ImageBuffer, Rgba};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of layers on top of the base color of a banner
+pub const MAX_BANNER_LAYERS: usize = 6;
+
+/// A color of a banner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BannerColor {
+    White,
+    Black,
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Orange,
+    Purple,
+}
+
+impl BannerColor {
+    pub const ALL: [BannerColor; 8] = [
+        BannerColor::White,
+        BannerColor::Black,
+        BannerColor::Red,
+        BannerColor::Green,
+        BannerColor::Blue,
+        BannerColor::Yellow,
+        BannerColor::Orange,
+        BannerColor::Purple,
+    ];
+
+    /// The RGB components of the color
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            BannerColor::White => [0xF0, 0xF0, 0xF0],
+            BannerColor::Black => [0x20, 0x20, 0x24],
+            BannerColor::Red => [0xB0, 0x2E, 0x26],
+            BannerColor::Green => [0x5E, 0x7C, 0x16],
+            BannerColor::Blue => [0x3C, 0x44, 0xAA],
+            BannerColor::Yellow => [0xFE, 0xD8, 0x3D],
+            BannerColor::Orange => [0xF9, 0x80, 0x1D],
+            BannerColor::Purple => [0x89, 0x32, 0xB8],
+        }
+    }
+}
+
+/// A shape that is drawn on a banner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BannerPatternKind {
+    StripeTop,
+    StripeBottom,
+    StripeLeft,
+    StripeRight,
+    StripeCenter,
+    StripeMiddle,
+    Cross,
+    Saltire,
+    HalfDiagonal,
+    Border,
+    Circle,
+    Checkered,
+}
+
+impl BannerPatternKind {
+    pub const ALL: [BannerPatternKind; 12] = [
+        BannerPatternKind::StripeTop,
+        BannerPatternKind::StripeBottom,
+        BannerPatternKind::StripeLeft,
+        BannerPatternKind::StripeRight,
+        BannerPatternKind::StripeCenter,
+        BannerPatternKind::StripeMiddle,
+        BannerPatternKind::Cross,
+        BannerPatternKind::Saltire,
+        BannerPatternKind::HalfDiagonal,
+        BannerPatternKind::Border,
+        BannerPatternKind::Circle,
+        BannerPatternKind::Checkered,
+    ];
+
+    /// Whether the shape covers the point `(u, v)` of the banner, with `(0, 0)` the top left corner
+    /// and `(1, 1)` the bottom right corner
+    pub fn covers(self, u: f64, v: f64) -> bool {
+        const THIRD: f64 = 1.0 / 3.0;
+        match self {
+            BannerPatternKind::StripeTop => v < THIRD,
+            BannerPatternKind::StripeBottom => v >= 2.0 * THIRD,
+            BannerPatternKind::StripeLeft => u < THIRD,
+            BannerPatternKind::StripeRight => u >= 2.0 * THIRD,
+            BannerPatternKind::StripeCenter => (THIRD..2.0 * THIRD).contains(&u),
+            BannerPatternKind::StripeMiddle => (THIRD..2.0 * THIRD).contains(&v),
+            BannerPatternKind::Cross => (u - 0.5).abs() < 0.125 || (v - 0.5).abs() < 0.125,
+            BannerPatternKind::Saltire => (u - v).abs() < 0.125 || (u + v - 1.0).abs() < 0.125,
+            BannerPatternKind::HalfDiagonal => u > v,
+            BannerPatternKind::Border => !(0.125..0.875).contains(&u) || !(0.125..0.875).contains(&v),
+            BannerPatternKind::Circle => (u - 0.5).powi(2) + (v - 0.5).powi(2) < 0.25f64.powi(2),
+            BannerPatternKind::Checkered => ((u * 4.0) as u32 + (v * 4.0) as u32) & 1 == 0,
+        }
+    }
+}
+
+/// The look of a banner: a base color, and layers of colored shapes drawn on top of each other
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BannerPattern {
+    pub base: BannerColor,
+    pub layers: Vec<(BannerPatternKind, BannerColor)>,
+}
+
+impl Default for BannerPattern {
+    fn default() -> Self {
+        Self {
+            base: BannerColor::White,
+            layers: Vec::new(),
+        }
+    }
+}
+
+impl BannerPattern {
+    /// Whether the pattern can be put on a banner
+    pub fn is_valid(&self) -> bool {
+        self.layers.len() <= MAX_BANNER_LAYERS
+    }
+
+    /// The color of the point `(u, v)` of the banner, with `(0, 0)` the top left corner
+    pub fn color_at(&self, u: f64, v: f64) -> BannerColor {
+        self.layers
+            .iter()
+            .rev()
+            .find(|(kind, _)| kind.covers(u, v))
+            .map(|&(_, color)| color)
+            .unwrap_or(self.base)
+    }
+
+    /// Draw the banner into a square texture of side `size`
+    pub fn compose(&self, size: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(size, size, |x, y| {
+            // Sample the center of the pixels
+            let u = (x as f64 + 0.5) / size as f64;
+            let v = (y as f64 + 0.5) / size as f64;
+            let [r, g, b] = self.color_at(u, v).rgb();
+            Rgba([r, g, b, 255])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_drawn_in_order() {
+        let pattern = BannerPattern {
+            base: BannerColor::White,
+            layers: vec![
+                (BannerPatternKind::StripeTop, BannerColor::Red),
+                (BannerPatternKind::StripeLeft, BannerColor::Blue),
+            ],
+        };
+        let texture = pattern.compose(6);
+        let pixel = |x, y| texture.get_pixel(x, y).0;
+        let rgba = |color: BannerColor| {
+            let [r, g, b] = color.rgb();
+            [r, g, b, 255]
+        };
+        // The left stripe is drawn on top of the top stripe
+        assert_eq!(pixel(0, 0), rgba(BannerColor::Blue));
+        assert_eq!(pixel(5, 0), rgba(BannerColor::Red));
+        assert_eq!(pixel(5, 5), rgba(BannerColor::White));
+        assert_eq!(pixel(0, 5), rgba(BannerColor::Blue));
+
+        let too_many_layers = BannerPattern {
+            base: BannerColor::Black,
+            layers: vec![(BannerPatternKind::Border, BannerColor::Red); MAX_BANNER_LAYERS + 1],
+        };
+        assert!(!too_many_layers.is_valid());
+    }
+}
@@ -0,0 +1,12 @@
+//! Beds: blocks that the players sleep in at night.
+//!
+//! Sleeping in a bed makes it the spawn point of the player, and the night is skipped once all the players are asleep.
+use crate::player::PlayerId;
+use serde::{Deserialize, Serialize};
+
+/// The state of a bed block
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bed {
+    /// The player who sleeps in the bed, so that two players can't sleep in the same bed
+    pub sleeper: Option<PlayerId>,
+}
@@ -0,0 +1,107 @@
+//! How long blocks take to break, depending on the tool that breaks them.
+use super::{Block, BlockType};
+use crate::item::ToolProperties;
+use std::time::Duration;
+
+/// How many times slower a block breaks when the tool can't harvest it
+const UNHARVESTABLE_PENALTY: f64 = 3.0;
+/// Number of stages of the cracks of the blocks that are being broken
+pub const BREAK_STAGES: u8 = 10;
+
+/// Whether breaking the block with the tool (`None` for bare hands) yields its drops
+pub fn can_harvest(block: &Block, tool: Option<ToolProperties>) -> bool {
+    match block.block_type {
+        BlockType::Air => false,
+        BlockType::NormalCube {
+            effective_tool,
+            required_tier,
+            ..
+        } => {
+            required_tier == 0
+                || tool
+                    .map(|tool| Some(tool.class) == effective_tool && tool.tier >= required_tier)
+                    .unwrap_or(false)
+        }
+    }
+}
+
+/// Time to break the block with the tool (`None` for bare hands), or `None` if the block can't be broken
+pub fn get_break_time(block: &Block, tool: Option<ToolProperties>) -> Option<Duration> {
+    match block.block_type {
+        BlockType::Air => None,
+        BlockType::NormalCube {
+            hardness,
+            effective_tool,
+            ..
+        } => {
+            let mut seconds = hardness.max(0.0);
+            if let Some(tool) = tool {
+                if Some(tool.class) == effective_tool {
+                    seconds /= tool.speed.max(1.0);
+                }
+            }
+            if !can_harvest(block, tool) {
+                seconds *= UNHARVESTABLE_PENALTY;
+            }
+            Some(Duration::from_secs_f64(seconds))
+        }
+    }
+}
+
+/// The stage of the cracks of a block that was broken for some time, or `None` if the block breaks instantly
+pub fn get_break_stage(elapsed: Duration, break_time: Duration) -> Option<u8> {
+    if break_time.is_zero() {
+        return None;
+    }
+    let progress = elapsed.as_secs_f64() / break_time.as_secs_f64();
+    Some(((progress * BREAK_STAGES as f64) as u8).min(BREAK_STAGES - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::ToolClass;
+
+    #[test]
+    fn tools_break_faster() {
+        let stone = Block {
+            name: "stone".to_owned(),
+            block_type: BlockType::NormalCube {
+                face_textures: Vec::new(),
+                climbable: false,
+                replaceable: false,
+                drops: Default::default(),
+                hardness: 1.5,
+                effective_tool: Some(ToolClass::Pickaxe),
+                required_tier: 1,
+                block_entity: None,
+                tilled_into: None,
+                crop: None,
+                model: None,
+            },
+        };
+        let pickaxe = ToolProperties {
+            class: ToolClass::Pickaxe,
+            tier: 2,
+            speed: 3.0,
+        };
+        let shovel = ToolProperties {
+            class: ToolClass::Shovel,
+            ..pickaxe
+        };
+        assert!(!can_harvest(&stone, None));
+        assert!(!can_harvest(&stone, Some(shovel)));
+        assert!(can_harvest(&stone, Some(pickaxe)));
+        assert_eq!(get_break_time(&stone, None), Some(Duration::from_secs_f64(4.5)));
+        assert_eq!(get_break_time(&stone, Some(pickaxe)), Some(Duration::from_secs_f64(0.5)));
+    }
+
+    #[test]
+    fn cracks_grow_until_the_block_breaks() {
+        let second = Duration::from_secs(1);
+        assert_eq!(get_break_stage(Duration::ZERO, second), Some(0));
+        assert_eq!(get_break_stage(second / 4, second), Some(2));
+        assert_eq!(get_break_stage(second * 2, second), Some(BREAK_STAGES - 1));
+        assert_eq!(get_break_stage(second, Duration::ZERO), None);
+    }
+}
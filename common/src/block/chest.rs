@@ -0,0 +1,140 @@
+//! Chests: blocks that store items, shared by all the players who open them.
+//!
+//! The server owns the contents of the chests. The players who have a chest open send the slots they move items
+//! between, and the server sends the changed slots to all the players who have the same chest open.
+use crate::{
+    inventory::{Inventory, ItemStack, MAX_STACK_SIZE},
+    item::Item,
+    registry::Registry,
+};
+use serde::{Deserialize, Serialize};
+
+/// Number of slots of a chest
+pub const CHEST_SIZE: usize = 27;
+
+/// A stack stored in a chest or in a furnace.
+/// The item is stored by name, like in the player saves, because the item ids depend on the loading order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChestStack {
+    pub item: String,
+    pub count: u32,
+    #[serde(default)]
+    pub data: u32,
+}
+
+/// The state of a chest block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chest {
+    pub slots: Vec<Option<ChestStack>>,
+}
+
+impl Default for Chest {
+    fn default() -> Self {
+        Self {
+            slots: vec![None; CHEST_SIZE],
+        }
+    }
+}
+
+impl Chest {
+    /// Whether the chest has the right number of slots and valid stacks
+    pub fn is_valid(&self) -> bool {
+        are_valid_slots(&self.slots, CHEST_SIZE)
+    }
+
+    /// The contents of the chest as an inventory. The items that don't exist anymore leave their slot empty.
+    pub fn to_inventory(&self, items: &Registry<Item>) -> Inventory {
+        slots_to_inventory(&self.slots, items)
+    }
+
+    /// Store the contents of an inventory returned by `to_inventory`.
+    /// The items that don't exist anymore are kept, unless their slot was filled in the meantime.
+    pub fn set_inventory(&mut self, inventory: &Inventory, items: &Registry<Item>) {
+        store_inventory(&mut self.slots, inventory, items);
+    }
+
+    /// The stacks stored in the chest, to drop them when it is broken
+    pub fn stacks(&self, items: &Registry<Item>) -> Vec<ItemStack> {
+        self.to_inventory(items).slots().flatten().collect()
+    }
+}
+
+/// Whether there are `size` stored slots with valid stacks
+pub(super) fn are_valid_slots(slots: &[Option<ChestStack>], size: usize) -> bool {
+    slots.len() == size && slots.iter().flatten().all(|stack| stack.count > 0 && stack.count <= MAX_STACK_SIZE)
+}
+
+/// The stored slots as an inventory. The items that don't exist anymore leave their slot empty.
+pub(super) fn slots_to_inventory(slots: &[Option<ChestStack>], items: &Registry<Item>) -> Inventory {
+    let mut inventory = Inventory::new(slots.len());
+    for (slot, stack) in slots.iter().enumerate() {
+        if let Some(stack) = stack {
+            if let Some(item_id) = items.get_id_by_name(&stack.item) {
+                inventory.set(slot, Some(ItemStack::with_data(item_id, stack.count, stack.data)));
+            }
+        }
+    }
+    inventory
+}
+
+/// Store the contents of an inventory returned by `slots_to_inventory`.
+/// The items that don't exist anymore are kept, unless their slot was filled in the meantime.
+pub(super) fn store_inventory(slots: &mut [Option<ChestStack>], inventory: &Inventory, items: &Registry<Item>) {
+    for (slot, stack) in inventory.slots().enumerate().take(slots.len()) {
+        let stored = stack.and_then(|stack| {
+            items.get_value_by_id(stack.item_id).map(|item| ChestStack {
+                item: item.name.clone(),
+                count: stack.count,
+                data: stack.data,
+            })
+        });
+        let is_unknown = |stack: &ChestStack| items.get_id_by_name(&stack.item).is_none();
+        if stored.is_some() || !slots[slot].as_ref().map(is_unknown).unwrap_or(false) {
+            slots[slot] = stored;
+        }
+    }
+}
+
+/// A slot of the inventory of a player or of the chest or furnace they opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChestSlot {
+    Player(usize),
+    Chest(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::ItemType;
+
+    fn register_item(items: &mut Registry<Item>, name: &str) -> u32 {
+        let item = Item {
+            name: name.to_owned(),
+            ty: ItemType::MapItem { texture: String::new() },
+        };
+        items.register(name.to_owned(), item).unwrap()
+    }
+
+    #[test]
+    fn unknown_items_are_kept() {
+        let mut items = Registry::default();
+        let wood = register_item(&mut items, "wood");
+        let mut chest = Chest::default();
+        chest.slots[0] = Some(ChestStack {
+            item: "removed".to_owned(),
+            count: 3,
+            data: 0,
+        });
+        let mut inventory = chest.to_inventory(&items);
+        assert_eq!(inventory.get(0), None);
+        inventory.set(1, Some(ItemStack::new(wood, 5)));
+        chest.set_inventory(&inventory, &items);
+        assert_eq!(chest.slots[0].as_ref().map(|stack| stack.item.as_str()), Some("removed"));
+        assert_eq!(chest.stacks(&items), vec![ItemStack::new(wood, 5)]);
+        assert!(chest.is_valid());
+        // Filling the slot of the unknown item replaces it
+        inventory.set(0, Some(ItemStack::new(wood, 1)));
+        chest.set_inventory(&inventory, &items);
+        assert_eq!(chest.slots[0].as_ref().map(|stack| stack.item.as_str()), Some("wood"));
+    }
+}
@@ -0,0 +1,80 @@
+//! What blocks yield when they are broken.
+use crate::inventory::ItemStack;
+use serde::Deserialize;
+
+/// The drops of a block as written in the data files, with the items referenced by name
+#[derive(Debug, Clone, Default, Deserialize)]
+pub enum BlockDrops {
+    /// The item that places the block
+    #[default]
+    Itself,
+    /// No item at all
+    Nothing,
+    /// Always the same items: (item, count)
+    Item(String, u32),
+    /// A single entry chosen at random, with a probability proportional to its weight
+    Table(Vec<DropEntry>),
+}
+
+/// An entry of a weighted drop table
+#[derive(Debug, Clone, Deserialize)]
+pub struct DropEntry {
+    pub weight: u32,
+    /// The dropped items: (item, count), or `None` if the entry drops nothing
+    pub item: Option<(String, u32)>,
+}
+
+/// The drops of a block, with the items resolved to their ids
+#[derive(Debug, Clone, Default)]
+pub struct DropTable {
+    /// The entries and their weights
+    entries: Vec<(u32, Option<ItemStack>)>,
+    total_weight: u32,
+}
+
+impl DropTable {
+    pub fn new(entries: Vec<(u32, Option<ItemStack>)>) -> Self {
+        let total_weight = entries.iter().map(|(weight, _)| weight).sum();
+        Self { entries, total_weight }
+    }
+
+    /// A table that always drops the same stack
+    pub fn single(stack: ItemStack) -> Self {
+        Self::new(vec![(1, Some(stack))])
+    }
+
+    /// Choose the dropped items. `random` must be uniformly distributed in `[0, 1)`.
+    pub fn roll(&self, random: f64) -> Option<ItemStack> {
+        if self.total_weight == 0 {
+            return None;
+        }
+        let mut target = (random * self.total_weight as f64) as u32;
+        for (weight, stack) in self.entries.iter() {
+            if target < *weight {
+                return *stack;
+            }
+            target -= weight;
+        }
+        // Only reachable through rounding errors
+        self.entries.last().and_then(|(_, stack)| *stack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_rolls() {
+        let leaves = ItemStack::new(1, 1);
+        let sapling = ItemStack::new(2, 1);
+        let table = DropTable::new(vec![(2, Some(leaves)), (1, Some(sapling)), (1, None)]);
+        assert_eq!(table.roll(0.0), Some(leaves));
+        assert_eq!(table.roll(0.49), Some(leaves));
+        assert_eq!(table.roll(0.5), Some(sapling));
+        assert_eq!(table.roll(0.74), Some(sapling));
+        assert_eq!(table.roll(0.75), None);
+        assert_eq!(table.roll(0.999), None);
+        assert_eq!(DropTable::default().roll(0.5), None);
+    }
+}
@@ -0,0 +1,72 @@
+//! Block entities: the data attached to single blocks, in addition to their id.
+//!
+//! They are stored in the chunks, so that they are saved and sent to the clients with the blocks.
+use super::banner::BannerPattern;
+use super::bed::Bed;
+use super::chest::Chest;
+use super::furnace::Furnace;
+use super::item_frame::{ItemFrame, FRAMED_ITEM_ROTATIONS};
+use super::painting::{Painting, PaintingLimits, MAX_PAINTING_COLORS, MAX_PAINTING_SIZE};
+use serde::{Deserialize, Serialize};
+
+/// The data attached to a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockEntity {
+    Banner(BannerPattern),
+    Painting(Painting),
+    ItemFrame(ItemFrame),
+    Bed(Bed),
+    Chest(Chest),
+    Furnace(Furnace),
+}
+
+/// The kind of block entity that a block has, as written in the data files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BlockEntityKind {
+    Banner,
+    Painting,
+    ItemFrame,
+    Bed,
+    Chest,
+    Furnace,
+}
+
+impl BlockEntityKind {
+    /// The block entity of a block that was just placed, with its front face toward `facing`
+    pub fn create(self, facing: usize) -> BlockEntity {
+        match self {
+            BlockEntityKind::Banner => BlockEntity::Banner(BannerPattern::default()),
+            BlockEntityKind::Painting => BlockEntity::Painting(Painting {
+                facing,
+                image: None,
+                author: None,
+            }),
+            BlockEntityKind::ItemFrame => BlockEntity::ItemFrame(ItemFrame { facing, item: None }),
+            BlockEntityKind::Bed => BlockEntity::Bed(Bed::default()),
+            BlockEntityKind::Chest => BlockEntity::Chest(Chest::default()),
+            BlockEntityKind::Furnace => BlockEntity::Furnace(Furnace::default()),
+        }
+    }
+
+    /// Whether a block entity can be attached to a block of this kind
+    pub fn accepts(self, block_entity: &BlockEntity) -> bool {
+        match (self, block_entity) {
+            (BlockEntityKind::Banner, BlockEntity::Banner(pattern)) => pattern.is_valid(),
+            (BlockEntityKind::Painting, BlockEntity::Painting(painting)) => {
+                // The limits of the server are checked when the image is imported
+                let limits = PaintingLimits {
+                    max_size: MAX_PAINTING_SIZE,
+                    max_colors: MAX_PAINTING_COLORS,
+                };
+                painting.facing < 6 && painting.image.as_ref().map(|image| image.is_valid(limits)).unwrap_or(true)
+            }
+            (BlockEntityKind::ItemFrame, BlockEntity::ItemFrame(frame)) => {
+                frame.facing < 6 && frame.item.as_ref().map(|item| item.rotation < FRAMED_ITEM_ROTATIONS).unwrap_or(true)
+            }
+            (BlockEntityKind::Bed, BlockEntity::Bed(_)) => true,
+            (BlockEntityKind::Chest, BlockEntity::Chest(chest)) => chest.is_valid(),
+            (BlockEntityKind::Furnace, BlockEntity::Furnace(furnace)) => furnace.is_valid(),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Farming: hoes till the soil into farmland, and the crops planted on farmland grow in stages.
+//!
+//! Every stage of a crop is a block, that turns into the block of the next stage when it grows.
+use super::{Block, BlockId, BlockType};
+use crate::registry::Registry;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Minimum light level above a crop for it to grow
+pub const MIN_GROWTH_LIGHT: u8 = 9;
+/// Maximum horizontal distance from the soil of a crop to the water that keeps it wet
+pub const WATER_RANGE: i64 = 4;
+
+/// The crop properties of a block, as written in the data files
+#[derive(Debug, Clone, Deserialize)]
+pub struct CropData {
+    /// The block the crop must be planted on
+    pub soil: String,
+    /// The block the crop grows into, or `None` for the last stage
+    #[serde(default)]
+    pub next_stage: Option<String>,
+}
+
+/// The farming behavior of a block, with the blocks resolved to their ids
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Farming {
+    /// The block this block turns into when a hoe tills it
+    pub tilled: Option<BlockId>,
+    /// The block the crop must be planted on, if this block is a crop
+    pub soil: Option<BlockId>,
+    /// The block the crop grows into
+    pub next_stage: Option<BlockId>,
+}
+
+impl Farming {
+    /// Resolve the farming behavior of every block, by block id
+    pub fn resolve_all(blocks: &Registry<Block>) -> Result<Vec<Farming>> {
+        (0..blocks.get_number_of_ids())
+            .map(|block_id| {
+                let block = blocks.get_value_by_id(block_id).unwrap();
+                let get_block_id = |name: &String| {
+                    blocks
+                        .get_id_by_name(name)
+                        .map(|id| id as BlockId)
+                        .with_context(|| format!("unknown block {} in the farming properties of block {}", name, block.name))
+                };
+                match &block.block_type {
+                    BlockType::Air => Ok(Farming::default()),
+                    BlockType::NormalCube { tilled_into, crop, .. } => Ok(Farming {
+                        tilled: tilled_into.as_ref().map(get_block_id).transpose()?,
+                        soil: crop.as_ref().map(|crop| get_block_id(&crop.soil)).transpose()?,
+                        next_stage: crop
+                            .as_ref()
+                            .and_then(|crop| crop.next_stage.as_ref())
+                            .map(get_block_id)
+                            .transpose()?,
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether a crop grows on a random tick, from the light above it and whether there is water close to its soil
+pub fn can_grow(light_above: u8, has_water: bool) -> bool {
+    light_above >= MIN_GROWTH_LIGHT && has_water
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register_block(blocks: &mut Registry<Block>, name: &str, tilled_into: Option<&str>, crop: Option<CropData>) {
+        let block = Block {
+            name: name.to_owned(),
+            block_type: BlockType::NormalCube {
+                face_textures: Vec::new(),
+                climbable: false,
+                replaceable: false,
+                drops: Default::default(),
+                hardness: 1.0,
+                effective_tool: None,
+                required_tier: 0,
+                block_entity: None,
+                tilled_into: tilled_into.map(str::to_owned),
+                crop,
+                model: None,
+            },
+        };
+        blocks.register(name.to_owned(), block).unwrap();
+    }
+
+    #[test]
+    fn resolve_farming_blocks() {
+        let mut blocks = Registry::default();
+        register_block(&mut blocks, "dirt", Some("farmland"), None);
+        register_block(&mut blocks, "farmland", None, None);
+        let crop = |next_stage: Option<&str>| CropData {
+            soil: "farmland".to_owned(),
+            next_stage: next_stage.map(str::to_owned),
+        };
+        register_block(&mut blocks, "carrots_0", None, Some(crop(Some("carrots_1"))));
+        register_block(&mut blocks, "carrots_1", None, Some(crop(None)));
+        let farming = Farming::resolve_all(&blocks).unwrap();
+        assert_eq!(farming[0].tilled, Some(1));
+        assert_eq!(farming[2], Farming { tilled: None, soil: Some(1), next_stage: Some(3) });
+        assert_eq!(farming[3].next_stage, None);
+
+        register_block(&mut blocks, "weeds", None, Some(crop(Some("unknown"))));
+        assert!(Farming::resolve_all(&blocks).is_err());
+    }
+
+    #[test]
+    fn crops_need_light_and_water() {
+        assert!(can_grow(15, true));
+        assert!(!can_grow(15, false));
+        assert!(!can_grow(MIN_GROWTH_LIGHT - 1, true));
+    }
+}
@@ -0,0 +1,224 @@
+//! Furnaces: blocks that smelt the items of their input slot while they burn the items of their fuel slot.
+//!
+//! The server runs the smelting. The players who have a furnace open move items like in a chest, and receive
+//! the changed slots and the progress of the smelting.
+use super::chest::{are_valid_slots, slots_to_inventory, store_inventory, ChestStack};
+use crate::{
+    inventory::{Inventory, ItemStack, MAX_STACK_SIZE},
+    item::Item,
+    registry::Registry,
+    smelting::{Smelting, SmeltingRecipe},
+};
+use serde::{Deserialize, Serialize};
+
+/// Number of slots of a furnace
+pub const FURNACE_SIZE: usize = 3;
+/// The slot of the burned items
+pub const FUEL_SLOT: usize = 0;
+/// The slot of the smelted items
+pub const INPUT_SLOT: usize = 1;
+/// The slot of the produced items
+pub const OUTPUT_SLOT: usize = 2;
+
+/// How far a furnace is in burning its fuel and smelting its input, in ticks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FurnaceProgress {
+    /// The remaining ticks of the fuel that burns
+    pub burn_left: u32,
+    /// The burn time of the fuel that burns
+    pub burn_time: u32,
+    /// The ticks that the input item has been smelting
+    pub smelted: u32,
+    /// The ticks to smelt the input item, 0 if it can't be smelted
+    pub smelt_time: u32,
+}
+
+impl FurnaceProgress {
+    /// Whether the fuel is burning
+    pub fn is_burning(&self) -> bool {
+        self.burn_left > 0
+    }
+}
+
+/// The state of a furnace block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Furnace {
+    pub slots: Vec<Option<ChestStack>>,
+    #[serde(default)]
+    pub progress: FurnaceProgress,
+}
+
+impl Default for Furnace {
+    fn default() -> Self {
+        Self {
+            slots: vec![None; FURNACE_SIZE],
+            progress: FurnaceProgress::default(),
+        }
+    }
+}
+
+impl Furnace {
+    /// Whether the furnace has the right number of slots and valid stacks
+    pub fn is_valid(&self) -> bool {
+        are_valid_slots(&self.slots, FURNACE_SIZE)
+    }
+
+    /// The contents of the furnace as an inventory. The items that don't exist anymore leave their slot empty.
+    pub fn to_inventory(&self, items: &Registry<Item>) -> Inventory {
+        slots_to_inventory(&self.slots, items)
+    }
+
+    /// Store the contents of an inventory returned by `to_inventory`.
+    pub fn set_inventory(&mut self, inventory: &Inventory, items: &Registry<Item>) {
+        store_inventory(&mut self.slots, inventory, items);
+    }
+
+    /// The stacks stored in the furnace, to drop them when it is broken
+    pub fn stacks(&self, items: &Registry<Item>) -> Vec<ItemStack> {
+        self.to_inventory(items).slots().flatten().collect()
+    }
+
+    /// Whether the furnace may need to smelt, without looking up the items: it burns, or it has fuel and input
+    pub fn may_be_active(&self) -> bool {
+        self.progress.is_burning() || (self.slots[FUEL_SLOT].is_some() && self.slots[INPUT_SLOT].is_some())
+    }
+}
+
+/// The recipe that smelts the input of a furnace, if the output slot has room for its result
+fn get_smeltable_recipe<'a>(inventory: &Inventory, smelting: &'a Smelting) -> Option<&'a SmeltingRecipe> {
+    let recipe = smelting.get_recipe(inventory.get(INPUT_SLOT)?.item_id)?;
+    let has_room = match inventory.get(OUTPUT_SLOT) {
+        None => true,
+        Some(output) => output.can_merge_with(&recipe.result) && output.count + recipe.result.count <= MAX_STACK_SIZE,
+    };
+    Some(recipe).filter(|_| has_room)
+}
+
+/// Run a furnace for some ticks: burn the fuel while there is something to smelt, and smelt the input.
+/// Return the slots that changed.
+pub fn smelt(inventory: &mut Inventory, progress: &mut FurnaceProgress, smelting: &Smelting, ticks: u32) -> Vec<usize> {
+    let mut changed_slots = Vec::new();
+    for _ in 0..ticks {
+        let recipe = get_smeltable_recipe(inventory, smelting);
+        // A new fuel item is only burned if there is something to smelt
+        if !progress.is_burning() && recipe.is_some() {
+            let burn_time = inventory.get(FUEL_SLOT).and_then(|fuel| smelting.get_burn_time(fuel.item_id));
+            if let Some(burn_time) = burn_time {
+                inventory.remove(FUEL_SLOT, 1);
+                changed_slots.push(FUEL_SLOT);
+                progress.burn_left = burn_time;
+                progress.burn_time = burn_time;
+            }
+        }
+        progress.smelt_time = recipe.map(|recipe| recipe.time).unwrap_or(0);
+        if !progress.is_burning() {
+            progress.smelted = 0;
+            continue;
+        }
+        progress.burn_left -= 1;
+        match recipe {
+            Some(recipe) => {
+                progress.smelted += 1;
+                if progress.smelted >= recipe.time {
+                    progress.smelted = 0;
+                    inventory.remove(INPUT_SLOT, 1);
+                    let output = match inventory.get(OUTPUT_SLOT) {
+                        Some(output) => ItemStack { count: output.count + recipe.result.count, ..output },
+                        None => recipe.result,
+                    };
+                    inventory.set(OUTPUT_SLOT, Some(output));
+                    changed_slots.extend([INPUT_SLOT, OUTPUT_SLOT].iter());
+                }
+            }
+            None => progress.smelted = 0,
+        }
+    }
+    changed_slots.sort_unstable();
+    changed_slots.dedup();
+    changed_slots
+}
+
+/// Whether the players may move items from the contents `before` to the contents `after` of a furnace:
+/// only fuel goes in the fuel slot, and nothing goes in the output slot.
+pub fn is_valid_move(before: &Inventory, after: &Inventory, smelting: &Smelting) -> bool {
+    let fuel_is_valid = match after.get(FUEL_SLOT) {
+        Some(fuel) => smelting.get_burn_time(fuel.item_id).is_some(),
+        None => true,
+    };
+    let output_is_valid = match (before.get(OUTPUT_SLOT), after.get(OUTPUT_SLOT)) {
+        (_, None) => true,
+        (Some(before), Some(after)) => before.can_merge_with(&after) && after.count <= before.count,
+        (None, Some(_)) => false,
+    };
+    fuel_is_valid && output_is_valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const ORE: u32 = 1;
+    const INGOT: u32 = 2;
+    const COAL: u32 = 3;
+
+    fn smelting() -> Smelting {
+        let recipe = SmeltingRecipe {
+            name: "ingot".to_owned(),
+            input: ORE,
+            result: ItemStack::new(INGOT, 1),
+            time: 10,
+        };
+        Smelting::new(vec![recipe], vec![(COAL, 15)].into_iter().collect::<HashMap<_, _>>())
+    }
+
+    fn furnace(fuel: u32, input: u32) -> Inventory {
+        let mut inventory = Inventory::new(FURNACE_SIZE);
+        inventory.set(FUEL_SLOT, Some(ItemStack::new(COAL, fuel)));
+        inventory.set(INPUT_SLOT, Some(ItemStack::new(ORE, input)));
+        inventory
+    }
+
+    #[test]
+    fn smelting_burns_fuel() {
+        let smelting = smelting();
+        let mut inventory = furnace(2, 3);
+        let mut progress = FurnaceProgress::default();
+        assert_eq!(smelt(&mut inventory, &mut progress, &smelting, 9), vec![FUEL_SLOT]);
+        assert_eq!(inventory.get(FUEL_SLOT), Some(ItemStack::new(COAL, 1)));
+        assert_eq!(progress, FurnaceProgress { burn_left: 6, burn_time: 15, smelted: 9, smelt_time: 10 });
+        assert_eq!(smelt(&mut inventory, &mut progress, &smelting, 1), vec![INPUT_SLOT, OUTPUT_SLOT]);
+        assert_eq!(inventory.get(OUTPUT_SLOT), Some(ItemStack::new(INGOT, 1)));
+        // The second fuel item lasts until the end of the third smelting
+        smelt(&mut inventory, &mut progress, &smelting, 20);
+        assert_eq!(inventory.get(INPUT_SLOT), None);
+        assert_eq!(inventory.get(FUEL_SLOT), None);
+        assert_eq!(inventory.get(OUTPUT_SLOT), Some(ItemStack::new(INGOT, 3)));
+        assert!(!progress.is_burning());
+    }
+
+    #[test]
+    fn fuel_is_kept_without_input() {
+        let smelting = smelting();
+        let mut inventory = furnace(1, 1);
+        inventory.set(INPUT_SLOT, Some(ItemStack::new(INGOT, 1)));
+        let mut progress = FurnaceProgress::default();
+        assert_eq!(smelt(&mut inventory, &mut progress, &smelting, 5), Vec::<usize>::new());
+        assert_eq!(inventory.get(FUEL_SLOT), Some(ItemStack::new(COAL, 1)));
+        assert_eq!(progress, FurnaceProgress::default());
+    }
+
+    #[test]
+    fn only_fuel_goes_in_the_fuel_slot() {
+        let smelting = smelting();
+        let before = furnace(1, 1);
+        let mut after = before.clone();
+        after.set(FUEL_SLOT, Some(ItemStack::new(ORE, 1)));
+        assert!(!is_valid_move(&before, &after, &smelting));
+        let mut after = before.clone();
+        after.set(OUTPUT_SLOT, Some(ItemStack::new(INGOT, 1)));
+        assert!(!is_valid_move(&before, &after, &smelting));
+        after.set(OUTPUT_SLOT, None);
+        assert!(is_valid_move(&before, &after, &smelting));
+    }
+}
@@ -0,0 +1,58 @@
+//! Item frames: blocks that display an item on their front face.
+//! Right-clicking a frame turns the displayed item, so that the maps can be hung in any orientation.
+use crate::{
+    inventory::ItemStack,
+    item::{Item, ItemId},
+    registry::Registry,
+};
+use serde::{Deserialize, Serialize};
+
+/// Number of orientations of the framed items, which turn by a quarter turn at every interaction
+pub const FRAMED_ITEM_ROTATIONS: u8 = 4;
+
+/// An item displayed in an item frame.
+/// The item is stored by name, like in the player saves, because the item ids depend on the loading order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FramedItem {
+    pub item: String,
+    /// The data of the stack the item was taken from, for example the canvas of a map
+    pub data: u32,
+    /// Number of quarter turns of the item around the normal of the frame, clockwise
+    #[serde(default)]
+    pub rotation: u8,
+}
+
+impl FramedItem {
+    /// The item of a stack, to put in a frame
+    pub fn from_stack(stack: ItemStack, items: &Registry<Item>) -> Option<Self> {
+        items.get_value_by_id(stack.item_id).map(|item| Self {
+            item: item.name.clone(),
+            data: stack.data,
+            rotation: 0,
+        })
+    }
+
+    /// Turn the item by a quarter turn
+    pub fn rotate(&mut self) {
+        self.rotation = (self.rotation + 1) % FRAMED_ITEM_ROTATIONS;
+    }
+
+    /// The id of the item, `None` if the item doesn't exist anymore
+    pub fn get_item_id(&self, items: &Registry<Item>) -> Option<ItemId> {
+        items.get_id_by_name(&self.item)
+    }
+
+    /// A stack of the single item, to take it out of the frame
+    pub fn to_stack(&self, items: &Registry<Item>) -> Option<ItemStack> {
+        self.get_item_id(items).map(|item_id| ItemStack::with_data(item_id, 1, self.data))
+    }
+}
+
+/// The state of an item frame block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemFrame {
+    /// The face that displays the item, away from the wall the frame was placed against
+    pub facing: usize,
+    /// The displayed item, if any
+    pub item: Option<FramedItem>,
+}
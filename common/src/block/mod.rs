@@ -1,6 +1,20 @@
 use crate::data::TextureRect;
+use crate::item::ToolClass;
+use crate::registry::Registry;
 use serde::Deserialize;
 
+pub mod banner;
+pub mod bed;
+pub mod breaking;
+pub mod chest;
+pub mod drops;
+pub mod entity;
+pub mod farming;
+pub mod furnace;
+pub mod item_frame;
+pub mod model;
+pub mod painting;
+
 pub type BlockId = u16;
 
 /// The type of a block. It contains the behavior and the mesh of the block.
@@ -9,7 +23,43 @@ pub type BlockId = u16;
 #[serde(rename = "Block")]
 pub enum BlockType {
     Air, // TODO: skip when deserializing
-    NormalCube { face_textures: Vec<String> },
+    NormalCube {
+        face_textures: Vec<String>,
+        /// Whether players can climb the block, like a ladder
+        #[serde(default)]
+        climbable: bool,
+        /// Whether a placed block replaces the block, like water
+        #[serde(default)]
+        replaceable: bool,
+        /// What the block yields when it is broken
+        #[serde(default)]
+        drops: drops::BlockDrops,
+        /// Time to break the block with bare hands, in seconds
+        #[serde(default = "default_hardness")]
+        hardness: f64,
+        /// The tool class that breaks the block faster
+        #[serde(default)]
+        effective_tool: Option<ToolClass>,
+        /// The minimum tier of the effective tool to get the drops of the block
+        #[serde(default)]
+        required_tier: u32,
+        /// The kind of data attached to every block of this type
+        #[serde(default)]
+        block_entity: Option<entity::BlockEntityKind>,
+        /// The block this block turns into when a hoe tills it, like dirt into farmland
+        #[serde(default)]
+        tilled_into: Option<String>,
+        /// The crop properties of the block, if it is a stage of a crop
+        #[serde(default)]
+        crop: Option<farming::CropData>,
+        /// The name of the block model of the block, if it is not a full cube
+        #[serde(default)]
+        model: Option<String>,
+    },
+}
+
+fn default_hardness() -> f64 {
+    1.0
 }
 
 /// A general block in-memory representation.
@@ -19,6 +69,39 @@ pub struct Block {
     pub block_type: BlockType,
 }
 
+impl Block {
+    /// Whether players can climb the block
+    pub fn is_climbable(&self) -> bool {
+        match self.block_type {
+            BlockType::Air => false,
+            BlockType::NormalCube { climbable, .. } => climbable,
+        }
+    }
+
+    /// Whether a placed block can take the place of the block
+    pub fn is_replaceable(&self) -> bool {
+        match self.block_type {
+            BlockType::Air => true,
+            BlockType::NormalCube { replaceable, .. } => replaceable,
+        }
+    }
+
+    /// The kind of data attached to the block, if any
+    pub fn get_block_entity_kind(&self) -> Option<entity::BlockEntityKind> {
+        match self.block_type {
+            BlockType::Air => None,
+            BlockType::NormalCube { block_entity, .. } => block_entity,
+        }
+    }
+}
+
+/// For every block id, whether the block is climbable
+pub fn get_climbable_blocks(blocks: &Registry<Block>) -> Vec<bool> {
+    (0..blocks.get_number_of_ids())
+        .map(|id| blocks.get_value_by_id(id).map(Block::is_climbable).unwrap_or(false))
+        .collect()
+}
+
 /// The mesh of a block.
 #[derive(Debug, Clone)]
 pub enum BlockMesh {
@@ -26,6 +109,8 @@ pub enum BlockMesh {
     Empty,
     /// A usual full cube
     FullCube { textures: [TextureRect; 6] },
+    /// The faces of a block model, which don't hide the faces of the blocks around them
+    Model { quads: Vec<model::ModelQuad> },
 }
 
 impl BlockMesh {
@@ -33,6 +118,7 @@ impl BlockMesh {
         match self {
             Self::Empty => false,
             Self::FullCube { .. } => true,
+            Self::Model { .. } => false,
         }
     }
 }
@@ -0,0 +1,196 @@
+//! Block models made of boxes, for the blocks that are not full cubes, like slabs, plants and fences.
+//!
+//! The models are loaded from `data/block_models`, and the blocks refer to them by name. The faces of the boxes
+//! use the face textures of the block, so that the same model can be used by blocks of different materials.
+use crate::data::TextureRect;
+use serde::Deserialize;
+
+/// Number of model units along each side of a block
+pub const MODEL_UNITS_PER_BLOCK: f32 = 16.0;
+
+/// A block model, as described in a data file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "BlockModel")]
+pub struct BlockModel {
+    pub elements: Vec<ModelElement>,
+}
+
+/// A box of a block model
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelElement {
+    /// The corner of the box with the lowest coordinates, in 16ths of a block
+    pub from: [f32; 3],
+    /// The corner of the box with the highest coordinates, in 16ths of a block
+    pub to: [f32; 3],
+    /// Rotation of the box around the vertical axis through the center of the block, in degrees
+    #[serde(default)]
+    pub rotation: f32,
+    /// The faces of the box that are drawn, in the order of the faces of the blocks: +x, -x, +y, -y, +z, -z
+    pub faces: [Option<ModelFace>; 6],
+}
+
+/// A face of a box of a block model
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ModelFace {
+    /// The index of the face texture of the block that the face uses, by default the texture of the same face
+    #[serde(default)]
+    pub texture: Option<usize>,
+    /// The region of the texture that the face shows, as (u0, v0, u1, v1) in 16ths of the texture.
+    /// By default it is the region that the face covers when it is projected on the side of the block.
+    #[serde(default)]
+    pub uv: Option<[f32; 4]>,
+    /// Whether the face is hidden when the block on its side is opaque, for the faces on the sides of the block
+    #[serde(default)]
+    pub cull: bool,
+}
+
+/// A face of a block model, ready to be meshed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelQuad {
+    /// The corners of the quad, in blocks from the corner of the block. They turn counter-clockwise when the quad
+    /// is seen from the front, starting from the top left corner of the texture.
+    pub corners: [[f32; 3]; 4],
+    /// The texture coordinates of the corners, in fractions of the texture
+    pub uvs: [[f32; 2]; 4],
+    pub texture: TextureRect,
+    /// The face of the blocks whose normal is the closest to the one of the quad, used for the shading
+    pub face: usize,
+    /// The side of the block that hides the quad if the block there is opaque
+    pub cull_face: Option<usize>,
+}
+
+impl BlockModel {
+    /// The quads of the model, for a block whose face textures are `textures`
+    pub fn quads(&self, textures: &[TextureRect]) -> Vec<ModelQuad> {
+        let mut quads = Vec::new();
+        for element in self.elements.iter() {
+            let from = element.from.map(|x| x / MODEL_UNITS_PER_BLOCK);
+            let to = element.to.map(|x| x / MODEL_UNITS_PER_BLOCK);
+            for (face, model_face) in element.faces.iter().enumerate() {
+                let model_face = match model_face {
+                    Some(model_face) => model_face,
+                    None => continue,
+                };
+                let corners = box_face_corners(from, to, face);
+                let [u0, v0, u1, v1] = match model_face.uv {
+                    Some(uv) => uv.map(|x| x / MODEL_UNITS_PER_BLOCK),
+                    None => default_uv(from, to, face),
+                };
+                let rotation = element.rotation.to_radians();
+                quads.push(ModelQuad {
+                    corners: corners.map(|corner| rotate_y(corner, rotation)),
+                    uvs: [[u0, v0], [u0, v1], [u1, v1], [u1, v0]],
+                    texture: textures.get(model_face.texture.unwrap_or(face)).copied().unwrap_or_default(),
+                    face: if element.rotation == 0.0 { face } else { rotated_face(face, rotation) },
+                    cull_face: Some(face).filter(|_| model_face.cull && element.rotation == 0.0),
+                });
+            }
+        }
+        quads
+    }
+}
+
+/// The corners of a face of a box, counter-clockwise from the top left corner of the texture
+fn box_face_corners([x0, y0, z0]: [f32; 3], [x1, y1, z1]: [f32; 3], face: usize) -> [[f32; 3]; 4] {
+    match face {
+        0 => [[x1, y1, z1], [x1, y0, z1], [x1, y0, z0], [x1, y1, z0]],
+        1 => [[x0, y1, z0], [x0, y0, z0], [x0, y0, z1], [x0, y1, z1]],
+        2 => [[x0, y1, z0], [x0, y1, z1], [x1, y1, z1], [x1, y1, z0]],
+        3 => [[x0, y0, z1], [x0, y0, z0], [x1, y0, z0], [x1, y0, z1]],
+        4 => [[x0, y1, z1], [x0, y0, z1], [x1, y0, z1], [x1, y1, z1]],
+        _ => [[x1, y1, z0], [x1, y0, z0], [x0, y0, z0], [x0, y1, z0]],
+    }
+}
+
+/// The region of the texture that a face of a box covers when it is projected on the side of the block,
+/// like the faces of the full cubes
+fn default_uv([x0, y0, z0]: [f32; 3], [x1, y1, z1]: [f32; 3], face: usize) -> [f32; 4] {
+    match face {
+        0 => [1.0 - z1, 1.0 - y1, 1.0 - z0, 1.0 - y0],
+        1 => [z0, 1.0 - y1, z1, 1.0 - y0],
+        2 => [x0, z0, x1, z1],
+        3 => [x0, 1.0 - z1, x1, 1.0 - z0],
+        4 => [x0, 1.0 - y1, x1, 1.0 - y0],
+        _ => [1.0 - x1, 1.0 - y1, 1.0 - x0, 1.0 - y0],
+    }
+}
+
+/// Rotate a position around the vertical axis through the center of the block
+fn rotate_y([x, y, z]: [f32; 3], angle: f32) -> [f32; 3] {
+    let (sin, cos) = angle.sin_cos();
+    let (dx, dz) = (x - 0.5, z - 0.5);
+    [0.5 + cos * dx + sin * dz, y, 0.5 - sin * dx + cos * dz]
+}
+
+/// The face whose normal is the closest to the normal of a face that was rotated around the vertical axis
+fn rotated_face(face: usize, angle: f32) -> usize {
+    let normal = match face {
+        0 => [1.0, 0.0, 0.0],
+        1 => [-1.0, 0.0, 0.0],
+        4 => [0.0, 0.0, 1.0],
+        5 => [0.0, 0.0, -1.0],
+        // The top and the bottom don't change
+        _ => return face,
+    };
+    let [x, _, z] = rotate_y([normal[0] + 0.5, 0.0, normal[2] + 0.5], angle).map(|c| c - 0.5);
+    if x.abs() >= z.abs() {
+        if x > 0.0 { 0 } else { 1 }
+    } else if z > 0.0 {
+        4
+    } else {
+        5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quads_face_outwards() {
+        let full = BlockModel {
+            elements: vec![ModelElement {
+                from: [0.0; 3],
+                to: [16.0; 3],
+                rotation: 0.0,
+                faces: [Some(ModelFace { texture: None, uv: None, cull: true }); 6],
+            }],
+        };
+        let normals = [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]];
+        for quad in full.quads(&[TextureRect::default(); 6]) {
+            // The cross product of the edges of a counter-clockwise quad points towards its front
+            let [a, b, c, _] = quad.corners;
+            let (u, v) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+            let cross = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+            assert_eq!(cross, normals[quad.face]);
+            assert_eq!(quad.cull_face, Some(quad.face));
+            // The full faces show the whole texture
+            let us = quad.uvs.map(|uv| uv[0]);
+            assert!(us.contains(&0.0) && us.contains(&1.0));
+        }
+    }
+
+    #[test]
+    fn rotated_quads_are_not_culled() {
+        let model: BlockModel = ron::de::from_str(
+            "BlockModel(elements: [(from: (0, 0, 8), to: (16, 16, 8), rotation: 90, faces: (None, None, None, None, Some((cull: true)), None))])",
+        )
+        .unwrap();
+        let quads = model.quads(&[TextureRect::default(); 6]);
+        assert_eq!(quads.len(), 1);
+        assert_eq!(quads[0].face, 0);
+        assert_eq!(quads[0].cull_face, None);
+        assert!(quads[0].corners.iter().all(|corner| (corner[0] - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn block_model_files_are_valid() {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/../data/block_models");
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            let model: Result<BlockModel, _> = ron::de::from_str(&std::fs::read_to_string(&path).unwrap());
+            let model = model.unwrap_or_else(|e| panic!("{} is invalid: {}", path.display(), e));
+            assert!(!model.quads(&[TextureRect::default(); 6]).is_empty(), "{} has no faces", path.display());
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Paintings: blocks that display an image imported by a player on their front face.
+//!
+//! The image is downscaled and palettized by the client that imports it, and stored in the block entity.
+//! Like the banners, the texture is composed by the clients.
+use crate::player::PlayerId;
+use image::{imageops::FilterType, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// Maximum side of the image of a painting, whatever the limits of the server
+pub const MAX_PAINTING_SIZE: u32 = 32;
+/// Maximum number of colors of the image of a painting, whatever the limits of the server
+pub const MAX_PAINTING_COLORS: usize = 256;
+/// The color of the part of the front face that the image doesn't cover
+const CANVAS_COLOR: [u8; 3] = [0x6B, 0x4A, 0x2B];
+
+/// How large the images of the paintings can be, as configured by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaintingLimits {
+    /// Maximum side of the image
+    pub max_size: u32,
+    /// Maximum number of colors of the image
+    pub max_colors: usize,
+}
+
+impl Default for PaintingLimits {
+    fn default() -> Self {
+        Self {
+            max_size: MAX_PAINTING_SIZE,
+            max_colors: 16,
+        }
+    }
+}
+
+/// A palettized image
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PaintingImage {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 3]>,
+    /// The index in the palette of every pixel, row by row
+    pub pixels: Vec<u8>,
+}
+
+impl PaintingImage {
+    /// Downscale an image to fit the limits, and reduce its colors to a palette.
+    /// Transparent pixels are drawn on a white background.
+    pub fn import(image: &RgbaImage, limits: PaintingLimits) -> Self {
+        let max_size = limits.max_size.clamp(1, MAX_PAINTING_SIZE);
+        let max_colors = limits.max_colors.clamp(1, MAX_PAINTING_COLORS);
+        // Keep the aspect ratio
+        let scale = (max_size as f64 / image.width().max(image.height()) as f64).min(1.0);
+        let width = ((image.width() as f64 * scale).round() as u32).clamp(1, max_size);
+        let height = ((image.height() as f64 * scale).round() as u32).clamp(1, max_size);
+        let image = image::imageops::resize(image, width, height, FilterType::Triangle);
+        let colors = image
+            .pixels()
+            .map(|pixel| {
+                let alpha = pixel[3] as u32;
+                let blend = |c: u8| ((c as u32 * alpha + 255 * (255 - alpha)) / 255) as u8;
+                [blend(pixel[0]), blend(pixel[1]), blend(pixel[2])]
+            })
+            .collect::<Vec<_>>();
+
+        let palette = median_cut(&colors, max_colors);
+        let pixels = colors
+            .iter()
+            .map(|color| {
+                let distance = |other: &[u8; 3]| (0..3).map(|c| (color[c] as i32 - other[c] as i32).pow(2)).sum::<i32>();
+                (0..palette.len()).min_by_key(|&i| distance(&palette[i])).unwrap() as u8
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            palette,
+            pixels,
+        }
+    }
+
+    /// Whether the image is well-formed and fits the limits
+    pub fn is_valid(&self, limits: PaintingLimits) -> bool {
+        (1..=limits.max_size.min(MAX_PAINTING_SIZE)).contains(&self.width)
+            && (1..=limits.max_size.min(MAX_PAINTING_SIZE)).contains(&self.height)
+            && self.pixels.len() == (self.width * self.height) as usize
+            && !self.palette.is_empty()
+            && self.palette.len() <= limits.max_colors.min(MAX_PAINTING_COLORS)
+            && self.pixels.iter().all(|&index| (index as usize) < self.palette.len())
+    }
+
+    /// Draw the image in the middle of a square texture of side `size`, keeping its aspect ratio
+    pub fn compose(&self, size: u32) -> RgbaImage {
+        let side = self.width.max(self.height) as f64;
+        let offset_x = (side - self.width as f64) / 2.0;
+        let offset_y = (side - self.height as f64) / 2.0;
+        RgbaImage::from_fn(size, size, |x, y| {
+            // Sample the center of the pixels
+            let u = (x as f64 + 0.5) / size as f64 * side - offset_x;
+            let v = (y as f64 + 0.5) / size as f64 * side - offset_y;
+            let [r, g, b] = if u >= 0.0 && v >= 0.0 && u < self.width as f64 && v < self.height as f64 {
+                let index = self.pixels[(v as u32 * self.width + u as u32) as usize];
+                self.palette[index as usize]
+            } else {
+                CANVAS_COLOR
+            };
+            Rgba([r, g, b, 255])
+        })
+    }
+}
+
+/// Reduce colors to a palette of at most `max_colors` colors with the median cut algorithm:
+/// the colors are split in boxes, and the box with the widest range of some channel is cut at its median until there are enough boxes
+fn median_cut(colors: &[[u8; 3]], max_colors: usize) -> Vec<[u8; 3]> {
+    // The widest channel of a box, and its range
+    fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+        (0..3)
+            .map(|c| {
+                let min = colors.iter().map(|color| color[c]).min().unwrap_or(0);
+                let max = colors.iter().map(|color| color[c]).max().unwrap_or(0);
+                (c, max - min)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap()
+    }
+
+    let mut boxes = vec![colors.to_vec()];
+    while boxes.len() < max_colors {
+        let (index, (channel, range)) = boxes
+            .iter()
+            .map(|colors| widest_channel(colors))
+            .enumerate()
+            .max_by_key(|&(_, (_, range))| range)
+            .unwrap();
+        // All the boxes have a single color
+        if range == 0 {
+            break;
+        }
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_by_key(|color| color[channel]);
+        let upper = lower.split_off(lower.len() / 2);
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+    // The palette contains the average color of every box
+    boxes
+        .iter()
+        .filter(|colors| !colors.is_empty())
+        .map(|colors| {
+            let mut sum = [0usize; 3];
+            for color in colors.iter() {
+                for c in 0..3 {
+                    sum[c] += color[c] as usize;
+                }
+            }
+            [0, 1, 2].map(|c| (sum[c] / colors.len()) as u8)
+        })
+        .collect()
+}
+
+/// The state of a painting block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Painting {
+    /// The face that displays the image, away from the wall the painting was placed against
+    pub facing: usize,
+    /// The displayed image, `None` for a blank canvas
+    pub image: Option<PaintingImage>,
+    /// The player who imported the image
+    pub author: Option<PlayerId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imported_images_fit_the_limits() {
+        // Left half red, right half blue
+        let image = RgbaImage::from_fn(64, 32, |x, _| if x < 32 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) });
+        let limits = PaintingLimits {
+            max_size: 16,
+            max_colors: 4,
+        };
+        let painting = PaintingImage::import(&image, limits);
+        assert_eq!((painting.width, painting.height), (16, 8));
+        assert!(painting.is_valid(limits));
+        let color_at = |x: u32, y: u32| painting.palette[painting.pixels[(y * painting.width + x) as usize] as usize];
+        assert!(color_at(0, 0)[0] > 200 && color_at(0, 0)[2] < 50);
+        assert!(color_at(15, 7)[2] > 200 && color_at(15, 7)[0] < 50);
+
+        // The image is letterboxed in the texture
+        let texture = painting.compose(16);
+        let [r, g, b] = CANVAS_COLOR;
+        assert_eq!(texture.get_pixel(0, 0).0, [r, g, b, 255]);
+        assert_eq!(texture.get_pixel(0, 8).0[..3], color_at(0, 4));
+
+        let stricter = PaintingLimits {
+            max_size: 8,
+            max_colors: 4,
+        };
+        assert!(!painting.is_valid(stricter));
+        let mut broken = painting;
+        broken.pixels[0] = 200;
+        assert!(!broken.is_valid(limits));
+    }
+}
@@ -0,0 +1,106 @@
+//! Crafting: turning some items of an inventory into other items, following the recipes of the data.
+use crate::inventory::{Inventory, ItemStack};
+use crate::item::ItemId;
+use serde::Deserialize;
+
+pub type RecipeId = u32;
+
+/// A recipe as it is written in the data files, with the items referenced by name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Recipe")]
+pub struct RecipeType {
+    /// The consumed items and their counts
+    pub ingredients: Vec<(String, u32)>,
+    /// The produced item and its count
+    pub result: (String, u32),
+}
+
+/// A general recipe in-memory representation
+#[derive(Debug, Clone)]
+pub struct Recipe {
+    pub name: String,
+    pub ingredients: Vec<(ItemId, u32)>,
+    pub result: ItemStack,
+}
+
+/// Why a recipe couldn't be crafted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CraftingError {
+    /// Some ingredients are not in the inventory
+    MissingIngredients,
+    /// There is no room for the result in the inventory
+    InventoryFull,
+}
+
+impl std::fmt::Display for CraftingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CraftingError::MissingIngredients => write!(f, "missing ingredients"),
+            CraftingError::InventoryFull => write!(f, "inventory full"),
+        }
+    }
+}
+
+/// Whether the inventory contains the ingredients of the recipe
+pub fn can_craft(inventory: &Inventory, recipe: &Recipe) -> bool {
+    recipe
+        .ingredients
+        .iter()
+        .all(|&(item_id, count)| inventory.count_item(item_id) >= count)
+}
+
+/// Craft a recipe once. The inventory is only modified if the recipe could be crafted.
+/// Return the slots that changed.
+pub fn craft(inventory: &mut Inventory, recipe: &Recipe) -> Result<Vec<usize>, CraftingError> {
+    if !can_craft(inventory, recipe) {
+        return Err(CraftingError::MissingIngredients);
+    }
+    let mut crafted = inventory.clone();
+    let mut changed_slots = Vec::new();
+    for &(item_id, count) in recipe.ingredients.iter() {
+        changed_slots.extend(crafted.remove_item(item_id, count));
+    }
+    let (result_slots, remaining) = crafted.insert(recipe.result);
+    if remaining.is_some() {
+        return Err(CraftingError::InventoryFull);
+    }
+    changed_slots.extend(result_slots);
+    changed_slots.sort_unstable();
+    changed_slots.dedup();
+    *inventory = crafted;
+    Ok(changed_slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone_recipe() -> Recipe {
+        Recipe {
+            name: "stone".to_owned(),
+            ingredients: vec![(1, 4)],
+            result: ItemStack::new(2, 1),
+        }
+    }
+
+    #[test]
+    fn craft_consumes_the_ingredients() {
+        let mut inventory = Inventory::new(3);
+        inventory.set(0, Some(ItemStack::new(1, 3)));
+        inventory.set(1, Some(ItemStack::new(1, 3)));
+        assert_eq!(craft(&mut inventory, &stone_recipe()), Ok(vec![0, 1]));
+        assert_eq!(inventory.count_item(1), 2);
+        assert_eq!(inventory.get(1), Some(ItemStack::new(2, 1)));
+        assert_eq!(craft(&mut inventory, &stone_recipe()), Err(CraftingError::MissingIngredients));
+        assert_eq!(inventory.count_item(1), 2);
+    }
+
+    #[test]
+    fn craft_needs_room_for_the_result() {
+        let mut inventory = Inventory::new(2);
+        inventory.set(0, Some(ItemStack::new(1, 8)));
+        inventory.set(1, Some(ItemStack::new(3, 1)));
+        assert_eq!(craft(&mut inventory, &stone_recipe()), Err(CraftingError::InventoryFull));
+        assert_eq!(inventory.count_item(1), 8);
+    }
+}
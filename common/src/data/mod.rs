@@ -1,32 +1,69 @@
 pub mod vox;
 
 use crate::{
-    block::{Block, BlockMesh, BlockType},
+    block::{drops::{BlockDrops, DropTable}, farming::Farming, model::BlockModel, Block, BlockMesh, BlockType},
     registry::Registry,
 };
 
+use crate::advancement::{Advancement, AdvancementType};
+use crate::crafting::{Recipe, RecipeType};
+use crate::smelting::{FuelType, Smelting, SmeltingRecipe, SmeltingRecipeType};
 use crate::data::vox::{load_voxel_model, VoxelModel};
-use crate::item::{Item, ItemMesh, ItemType};
+use crate::inventory::ItemStack;
+use crate::dimension::{Dimension, DimensionType};
+use crate::entity::model::{box_model_part_name, BoxModel};
+use crate::item::{Item, ItemId, ItemMesh, ItemType};
+use crate::mob::{Mob, MobType};
 use anyhow::{Context, Result};
-use image::{ImageBuffer, Rgba};
-use log::info;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use log::{info, warn};
 use std::fs;
 use std::io::Read;
 use std::path::PathBuf;
-use texture_packer::{TexturePacker, TexturePackerConfig};
+use texture_packer::TexturePackerConfig;
 
 #[derive(Debug, Clone)]
 pub struct Data {
     pub blocks: Registry<Block>,
     pub meshes: Vec<BlockMesh>,
-    pub texture_atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    /// The pages of the texture atlas, which all have a side of `MAX_TEXTURE_SIZE`
+    pub texture_atlas: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>,
     pub models: Registry<VoxelModel>,
     pub items: Registry<Item>,
     pub item_meshes: Vec<ItemMesh>,
+    /// For every block id, the item that places the block
+    pub block_items: Vec<Option<ItemId>>,
+    /// For every block id, what the block yields when it is broken
+    pub block_drops: Vec<DropTable>,
+    /// For every block id, how it is tilled and how it grows
+    pub block_farming: Vec<Farming>,
+    /// For every block id, the average color of its top face, that the maps show
+    pub block_colors: Vec<[u8; 3]>,
+    pub recipes: Registry<Recipe>,
+    /// The smelting recipes and the fuels of the furnaces
+    pub smelting: Smelting,
+    pub mobs: Registry<Mob>,
+    /// The model of the players. The voxel models of its parts are in `models`, like the ones of the mobs.
+    pub player_model: BoxModel,
+    pub dimensions: Registry<Dimension>,
+    pub advancements: Registry<Advancement>,
+    /// Empty regions of the texture atlas, for the textures that the clients generate while playing
+    pub dynamic_textures: Vec<TextureRect>,
 }
 
+/// Maximum number of textures in an override pack
+const MAX_OVERRIDE_TEXTURES: usize = 64;
+/// Maximum size of a texture file of an override pack, in bytes
+const MAX_OVERRIDE_TEXTURE_FILE_SIZE: u64 = 64 * 1024;
+
+/// Load the data from a data directory.
+///
+/// `override_directory` is an optional override pack, for example the one bundled with a world save.
+/// Its textures are loaded on top of the textures of the data directory: they replace the textures with
+/// the same name, and the others are added. The overrides end up in the texture atlas, so they reach
+/// the clients with the rest of the data.
 // TODO: decent error handling
-pub fn load_data(data_directory: PathBuf) -> Result<Data> {
+pub fn load_data(data_directory: PathBuf, override_directory: Option<PathBuf>) -> Result<Data> {
     info!("Loading data from directory {}", data_directory.display());
 
     // Load textures
@@ -59,7 +96,55 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
         }
     }
 
-    let (texture_atlas, texture_rects) = load_textures(textures)?;
+    // Load the texture overrides
+    if let Some(override_textures_directory) = override_directory.map(|directory| directory.join("textures")) {
+        if override_textures_directory.is_dir() {
+            info!(
+                "Loading texture overrides from directory {}",
+                override_textures_directory.display()
+            );
+            // The entries are sorted so that the same textures are kept when there are too many of them
+            let mut dir_entries = fs::read_dir(&override_textures_directory)
+                .context("couldn't read texture overrides directory")?
+                .collect::<Result<Vec<_>, _>>()
+                .context("failed to read directory entry")?;
+            dir_entries.sort_by_key(|dir_entry| dir_entry.path());
+            let mut num_overrides = 0;
+            for dir_entry in dir_entries {
+                let metadata = dir_entry.metadata().context("failed to get file metadata")?;
+                if !metadata.is_file() {
+                    continue;
+                }
+                let file_path = dir_entry.path();
+                if num_overrides >= MAX_OVERRIDE_TEXTURES {
+                    warn!("Too many texture overrides, skipping {}...", file_path.display());
+                    continue;
+                }
+                if metadata.len() > MAX_OVERRIDE_TEXTURE_FILE_SIZE {
+                    warn!("Texture override {} is too large, skipping...", file_path.display());
+                    continue;
+                }
+                let name = match file_path.file_stem().and_then(|stem| stem.to_str()) {
+                    Some(name) => name.to_owned(),
+                    None => {
+                        warn!("Texture override {} doesn't have a valid name, skipping...", file_path.display());
+                        continue;
+                    }
+                };
+                match texture_registry.get_id_by_name(&name) {
+                    Some(id) => textures[id as usize] = file_path,
+                    None => {
+                        texture_registry.register(name, ())?;
+                        textures.push(file_path);
+                    }
+                }
+                num_overrides += 1;
+            }
+            info!("Loaded {} texture overrides", num_overrides);
+        }
+    }
+
+    let (texture_atlas, texture_rects, dynamic_textures) = load_textures(textures, NUM_DYNAMIC_TEXTURES)?;
 
     //Load model
     let mut models = Registry::default();
@@ -99,39 +184,17 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
     ).unwrap();
     models.register("knight".to_owned(), model_knight)?;
 
-    // Load items
-    let items_directory = data_directory.join("items");
-    let item_datas: Vec<(String, ItemType)> = load_files_from_folder(items_directory);
-    let mut items = Registry::default();
-    let mut item_meshes = Vec::new();
+    // Generate particle models
+    models.register("particle:rain".to_owned(), self::vox::generate_uniform_model((1, 8, 1), 0x00E0A070))?;
+    models.register("particle:snow".to_owned(), self::vox::generate_uniform_model((2, 2, 2), 0x00FFFFFF))?;
 
-    // Generate item models
-    for (name, ty) in item_datas.into_iter() {
-        match &ty {
-            ItemType::NormalItem { texture } => {
-                let texture_rect =
-                    texture_rects[texture_registry.get_id_by_name(texture).unwrap() as usize];
-                let model = self::vox::item::generate_item_model(texture_rect, &texture_atlas);
-                let mesh_center = (
-                    model.size_x as f32 / 2.0,
-                    model.size_y as f32 / 2.0,
-                    model.size_z as f32 / 2.0,
-                );
-                let scale = 1.0 / usize::max(model.size_x, model.size_y) as f32;
-                let mesh_id = models
-                    .register(format!("item:{}", name), model)
-                    .expect("Failed to register item model");
-                items
-                    .register(name.clone(), Item { name, ty })
-                    .expect("Failed to register item");
-                item_meshes.push(ItemMesh::SimpleMesh {
-                    mesh_id,
-                    scale,
-                    mesh_center,
-                });
-            }
-        }
-    }
+    // Generate vehicle models
+    // TODO: load them from .vox files
+    models.register("vehicle:boat".to_owned(), self::vox::generate_uniform_model((14, 8, 22), 0x002B5A8B))?;
+    models.register("vehicle:minecart".to_owned(), self::vox::generate_uniform_model((14, 12, 18), 0x00707070))?;
+
+    // Load block models
+    let block_models: Vec<(String, BlockModel)> = load_files_from_folder(data_directory.join("block_models"));
 
     // Load blocks
     let blocks_directory = data_directory.join("blocks");
@@ -163,6 +226,8 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
             // TODO: make sure there are exactly 6 face textures
             BlockType::NormalCube {
                 face_textures: names,
+                model: None,
+                ..
             } => BlockMesh::FullCube {
                 textures: [
                     texture_rects[texture_registry.get_id_by_name(&names[0]).unwrap() as usize],
@@ -173,10 +238,243 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
                     texture_rects[texture_registry.get_id_by_name(&names[5]).unwrap() as usize],
                 ],
             },
+            BlockType::NormalCube {
+                face_textures: names,
+                model: Some(model_name),
+                ..
+            } => {
+                let model = block_models
+                    .iter()
+                    .find(|(name, _)| name == &model_name)
+                    .map(|(_, model)| model)
+                    .with_context(|| format!("The block model {} doesn't exist", model_name))?;
+                let textures = names
+                    .iter()
+                    .map(|name| texture_rects[texture_registry.get_id_by_name(name).unwrap() as usize])
+                    .collect::<Vec<_>>();
+                BlockMesh::Model { quads: model.quads(&textures) }
+            }
         };
         meshes.push(mesh);
     }
 
+    // The maps show the top faces of the blocks
+    let block_colors = meshes
+        .iter()
+        .map(|mesh| match mesh {
+            BlockMesh::Empty => [0, 0, 0],
+            BlockMesh::FullCube { textures } => average_color(textures[2], &texture_atlas[textures[2].page as usize]),
+            BlockMesh::Model { quads } => quads
+                .first()
+                .map(|quad| average_color(quad.texture, &texture_atlas[quad.texture.page as usize]))
+                .unwrap_or([0, 0, 0]),
+        })
+        .collect();
+
+    // Load items
+    let items_directory = data_directory.join("items");
+    let mut item_datas: Vec<(String, ItemType)> = load_files_from_folder(items_directory);
+    // Every block without an item of the same name gets a block item that places it
+    for block_id in 0..blocks.get_number_of_ids() {
+        let block = blocks.get_value_by_id(block_id).unwrap();
+        if let BlockType::NormalCube { face_textures, .. } = &block.block_type {
+            if !item_datas.iter().any(|(name, _)| name == &block.name) {
+                item_datas.push((
+                    block.name.clone(),
+                    ItemType::NormalItem {
+                        texture: face_textures[0].clone(),
+                        places_block: Some(block.name.clone()),
+                        tool: None,
+                        food: None,
+                        throwable: false,
+                    },
+                ));
+            }
+        }
+    }
+    let mut items = Registry::default();
+    let mut item_meshes = Vec::new();
+
+    // Generate item models
+    let generate_item_model = |texture: &String| {
+        let texture_rect =
+            texture_rects[texture_registry.get_id_by_name(texture).unwrap() as usize];
+        let model = self::vox::item::generate_item_model(texture_rect, &texture_atlas[texture_rect.page as usize]);
+        let mesh_center = (
+            model.size_x as f32 / 2.0,
+            model.size_y as f32 / 2.0,
+            model.size_z as f32 / 2.0,
+        );
+        let scale = 1.0 / usize::max(model.size_x, model.size_y) as f32;
+        (model, mesh_center, scale, texture_rect)
+    };
+    for (name, ty) in item_datas.into_iter() {
+        match &ty {
+            ItemType::NormalItem { texture, .. } | ItemType::MapItem { texture } => {
+                let (model, mesh_center, scale, texture) = generate_item_model(texture);
+                let mesh_id = models
+                    .register(format!("item:{}", name), model)
+                    .expect("Failed to register item model");
+                item_meshes.push(ItemMesh::SimpleMesh {
+                    mesh_id,
+                    scale,
+                    mesh_center,
+                    texture,
+                });
+            }
+            ItemType::DynamicItem { textures, icon } => {
+                let mut mesh_ids = Vec::with_capacity(textures.len());
+                let mut texture_rects = Vec::with_capacity(textures.len());
+                let mut mesh_center = (0.0, 0.0, 0.0);
+                let mut scale = 1.0;
+                for (i, texture) in textures.iter().enumerate() {
+                    let (model, center, s, texture_rect) = generate_item_model(texture);
+                    mesh_center = center;
+                    scale = s;
+                    texture_rects.push(texture_rect);
+                    mesh_ids.push(
+                        models
+                            .register(format!("item:{}:{}", name, i), model)
+                            .expect("Failed to register item model"),
+                    );
+                }
+                item_meshes.push(ItemMesh::DynamicMesh {
+                    mesh_ids,
+                    scale,
+                    mesh_center,
+                    textures: texture_rects,
+                    icon: *icon,
+                });
+            }
+        }
+        items
+            .register(name.clone(), Item { name, ty })
+            .expect("Failed to register item");
+    }
+
+    // Map the blocks to the items that place them
+    let mut block_items = vec![None; blocks.get_number_of_ids() as usize];
+    for item_id in 0..items.get_number_of_ids() {
+        let placed_block = items.get_value_by_id(item_id).unwrap().get_placed_block();
+        if let Some(block_id) = placed_block.and_then(|name| blocks.get_id_by_name(name)) {
+            block_items[block_id as usize].get_or_insert(item_id);
+        }
+    }
+
+    // Resolve the drops of the blocks
+    let mut block_drops = Vec::with_capacity(blocks.get_number_of_ids() as usize);
+    for block_id in 0..blocks.get_number_of_ids() {
+        let block = blocks.get_value_by_id(block_id).unwrap();
+        let get_stack = |item: &String, count: u32| {
+            items
+                .get_id_by_name(item)
+                .map(|item_id| ItemStack::new(item_id, count))
+                .with_context(|| format!("unknown item {} in the drops of block {}", item, block.name))
+        };
+        let table = match &block.block_type {
+            BlockType::Air => DropTable::default(),
+            BlockType::NormalCube { drops, .. } => match drops {
+                BlockDrops::Itself => match block_items[block_id as usize] {
+                    Some(item_id) => DropTable::single(ItemStack::new(item_id, 1)),
+                    None => DropTable::default(),
+                },
+                BlockDrops::Nothing => DropTable::default(),
+                BlockDrops::Item(item, count) => DropTable::single(get_stack(item, *count)?),
+                BlockDrops::Table(entries) => DropTable::new(
+                    entries
+                        .iter()
+                        .map(|entry| {
+                            let stack = match &entry.item {
+                                Some((item, count)) => Some(get_stack(item, *count)?),
+                                None => None,
+                            };
+                            Ok((entry.weight, stack))
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                ),
+            },
+        };
+        block_drops.push(table);
+    }
+
+    // Resolve the farming properties of the blocks
+    let block_farming = Farming::resolve_all(&blocks)?;
+
+    // Load recipes
+    let recipes_directory = data_directory.join("recipes");
+    let recipe_datas: Vec<(String, RecipeType)> = load_files_from_folder(recipes_directory);
+    let mut recipes = Registry::default();
+    let get_item_id = |recipe: &String, item: &String| {
+        items
+            .get_id_by_name(item)
+            .with_context(|| format!("unknown item {} in recipe {}", item, recipe))
+    };
+    for (name, ty) in recipe_datas.into_iter() {
+        let ingredients = ty
+            .ingredients
+            .iter()
+            .map(|(item, count)| Ok((get_item_id(&name, item)?, *count)))
+            .collect::<Result<Vec<_>>>()?;
+        let result = ItemStack::new(get_item_id(&name, &ty.result.0)?, ty.result.1);
+        recipes.register(name.clone(), Recipe { name, ingredients, result })?;
+    }
+
+    // Load smelting recipes and fuels
+    let smelting_directory = data_directory.join("smelting");
+    let smelting_datas: Vec<(String, SmeltingRecipeType)> = load_files_from_folder(smelting_directory);
+    let smelting_recipes = smelting_datas
+        .into_iter()
+        .map(|(name, ty)| {
+            let input = get_item_id(&name, &ty.input)?;
+            let result = ItemStack::new(get_item_id(&name, &ty.result.0)?, ty.result.1);
+            Ok(SmeltingRecipe { name, input, result, time: ty.time })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let fuels_directory = data_directory.join("fuels");
+    let fuel_datas: Vec<(String, FuelType)> = load_files_from_folder(fuels_directory);
+    let fuels = fuel_datas
+        .into_iter()
+        .map(|(name, ty)| {
+            let item_id = items.get_id_by_name(&name).with_context(|| format!("unknown fuel item {}", name))?;
+            Ok((item_id, ty.burn_time))
+        })
+        .collect::<Result<_>>()?;
+    let smelting = Smelting::new(smelting_recipes, fuels);
+
+    // Load mobs
+    let mobs_directory = data_directory.join("mobs");
+    let mob_datas: Vec<(String, MobType)> = load_files_from_folder(mobs_directory);
+    let mut mobs = Registry::default();
+    for (name, ty) in mob_datas.into_iter() {
+        register_box_model(&mut models, &format!("mob:{}", name), &ty.model)?;
+        mobs.register(name.clone(), Mob { name, ty })?;
+    }
+
+    // Load the model of the players
+    let player_model_path = data_directory.join("model/player.ron");
+    let player_model: BoxModel = ron::de::from_str(
+        &fs::read_to_string(&player_model_path)
+            .context(format!("Failed to read player model {}", player_model_path.display()))?,
+    )
+    .context(format!("Failed to parse player model {}", player_model_path.display()))?;
+    register_box_model(&mut models, "player", &player_model)?;
+
+    // Load dimensions
+    let dimensions_directory = data_directory.join("dimensions");
+    let dimension_datas: Vec<(String, DimensionType)> = load_files_from_folder(dimensions_directory);
+    let mut dimensions = Registry::default();
+    for (name, ty) in dimension_datas.into_iter() {
+        dimensions.register(name.clone(), Dimension { name, ty })?;
+    }
+
+    // Load advancements
+    let advancements_directory = data_directory.join("advancements");
+    let advancement_datas: Vec<(String, AdvancementType)> = load_files_from_folder(advancements_directory);
+    let mut advancements = Registry::default();
+    for (name, ty) in advancement_datas.into_iter() {
+        advancements.register(name.clone(), Advancement { name, ty })?;
+    }
+
     info!("Data successfully loaded");
     Ok(Data {
         blocks,
@@ -185,75 +483,189 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
         models,
         items,
         item_meshes,
+        block_items,
+        block_drops,
+        block_farming,
+        block_colors,
+        recipes,
+        smelting,
+        mobs,
+        player_model,
+        dimensions,
+        advancements,
+        dynamic_textures,
     })
 }
 
+/// Register the parts of a box model as voxel models, named by `box_model_part_name`
+fn register_box_model(models: &mut Registry<VoxelModel>, model_name: &str, model: &BoxModel) -> Result<()> {
+    for (index, part) in model.parts.iter().enumerate() {
+        let (size_x, size_y, size_z) = part.size;
+        let voxel_model =
+            self::vox::generate_uniform_model((size_x as usize, size_y as usize, size_z as usize), part.color);
+        models.register(box_model_part_name(model_name, index), voxel_model)?;
+    }
+    Ok(())
+}
+
+/// The region of a texture in the texture atlas. The coordinates are fractions of the size of the pages.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct TextureRect {
     pub x: f32,
     pub y: f32,
     pub width: f32,
     pub height: f32,
+    /// The page of the atlas that contains the texture
+    pub page: u32,
 }
 
+/// Side of the pages of the texture atlas, in pixels
 pub const MAX_TEXTURE_SIZE: u32 = 2048;
+/// Number of empty regions reserved in the texture atlas for the textures generated by the clients
+pub const NUM_DYNAMIC_TEXTURES: usize = 64;
+/// Side of the regions reserved for the textures generated by the clients, in pixels
+pub const DYNAMIC_TEXTURE_SIZE: u32 = 32;
+/// Width of the border around every texture of the atlas, in pixels. The border repeats the opposite sides of the
+/// texture, so that the filtering and the mipmaps blend the textures with themselves and not with their neighbours.
+pub const TEXTURE_PADDING: u32 = 8;
+/// The textures and their border are extended to a multiple of this size, so that the textures of different
+/// resolutions don't share the texels of the mipmaps
+const TEXTURE_ALIGNMENT: u32 = 16;
 
-const TEXTURE_PACKER_CONFIG: TexturePackerConfig = TexturePackerConfig {
-    max_width: MAX_TEXTURE_SIZE,
-    max_height: MAX_TEXTURE_SIZE,
-    allow_rotation: false,
-    border_padding: 0,
-    texture_padding: 0,
-    trim: false,
-    texture_outlines: false,
-};
-
-/// Load given textures to a unique texture atlas
+/// Load given textures to a texture atlas, and reserve `num_dynamic_textures` empty regions in it
 fn load_textures(
     textures: Vec<PathBuf>,
-) -> Result<(ImageBuffer<Rgba<u8>, Vec<u8>>, Vec<TextureRect>)> {
+    num_dynamic_textures: usize,
+) -> Result<(Vec<RgbaImage>, Vec<TextureRect>, Vec<TextureRect>)> {
+    let images = textures
+        .iter()
+        .map(|path| {
+            image::open(path)
+                .map(|image| image.to_rgba8())
+                .context(format!("Failed to read texture {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (pages, rects, dynamic_rects) = pack_textures(&images, num_dynamic_textures, MAX_TEXTURE_SIZE)?;
+    for (i, page) in pages.iter().enumerate() {
+        page.save(format!("atlas_{}.png", i)).context("Failed to save texture atlas")?;
+    }
+    info!("Packed {} textures into {} atlas pages", images.len(), pages.len());
+    Ok((pages, rects, dynamic_rects))
+}
+
+/// Pack textures of any size into as many square pages of side `page_size` as needed,
+/// and reserve `num_dynamic_textures` empty regions in them
+fn pack_textures(
+    textures: &[RgbaImage],
+    num_dynamic_textures: usize,
+    page_size: u32,
+) -> Result<(Vec<RgbaImage>, Vec<TextureRect>, Vec<TextureRect>)> {
     use image::GenericImage;
-    use texture_packer::{exporter::ImageExporter, importer::ImageImporter};
-
-    let mut packer = TexturePacker::new_skyline(TEXTURE_PACKER_CONFIG);
-    for (i, path) in textures.iter().enumerate() {
-        packer.pack_own(
-            format!("{}", i),
-            ImageImporter::import_from_file(path).expect("Failed to read texture to pack"),
-        ).expect("Failed to pack textures");
+    use texture_packer::{exporter::ImageExporter, MultiTexturePacker};
+
+    let config = TexturePackerConfig {
+        max_width: page_size,
+        max_height: page_size,
+        allow_rotation: false,
+        border_padding: 0,
+        texture_padding: 0,
+        trim: false,
+        texture_outlines: false,
+    };
+    let mut packer = MultiTexturePacker::new_skyline(config);
+    let dynamic_texture = ImageBuffer::new(DYNAMIC_TEXTURE_SIZE, DYNAMIC_TEXTURE_SIZE);
+    let keyed_textures = textures
+        .iter()
+        .enumerate()
+        .map(|(i, texture)| (format!("{}", i), texture))
+        .chain((0..num_dynamic_textures).map(|i| (format!("dynamic:{}", i), &dynamic_texture)));
+    for (key, texture) in keyed_textures {
+        let (width, height) = texture.dimensions();
+        let padded = wrap_texture(texture, TEXTURE_PADDING, aligned_size(width), aligned_size(height));
+        packer
+            .pack_own(key.clone(), image::DynamicImage::ImageRgba8(padded))
+            .map_err(|_| anyhow::anyhow!("Texture {} of size {}x{} doesn't fit in a page of the atlas", key, width, height))?;
     }
 
-    let mut texture_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
-        ImageBuffer::new(MAX_TEXTURE_SIZE, MAX_TEXTURE_SIZE);
-    texture_buffer.copy_from(
-        &ImageExporter::export(&packer).expect("Failed to export texture from packer"),
-        0,
-        0,
-    ).expect("Failed to copy texture atlas to buffer");
-    texture_buffer
-        .save("atlas.png")
-        .expect("Failed to save texture atlas");
+    let mut pages = Vec::new();
+    for page in packer.get_pages() {
+        let mut page_buffer: RgbaImage = ImageBuffer::new(page_size, page_size);
+        page_buffer
+            .copy_from(&ImageExporter::export(page).expect("Failed to export texture from packer"), 0, 0)
+            .context("Failed to copy texture atlas to buffer")?;
+        pages.push(page_buffer);
+    }
+    let get_rect = |key: String, (width, height): (u32, u32)| {
+        let (page, frame) = packer
+            .get_pages()
+            .iter()
+            .enumerate()
+            .find_map(|(page, packer)| packer.get_frame(&key).map(|frame| (page, frame.frame)))
+            .expect("Texture packer frame key doesn't exist");
+        // The rect only covers the texture, without its border
+        TextureRect {
+            x: (frame.x + TEXTURE_PADDING) as f32 / page_size as f32,
+            y: (frame.y + TEXTURE_PADDING) as f32 / page_size as f32,
+            width: width as f32 / page_size as f32,
+            height: height as f32 / page_size as f32,
+            page: page as u32,
+        }
+    };
     Ok((
-        texture_buffer,
-        (0..textures.len())
-            .map(|i| {
-                let frame = packer
-                    .get_frame(&format!("{}", i))
-                    .expect("Texture packer frame key doesn't exist")
-                    .frame;
-                TextureRect {
-                    x: frame.x as f32 / MAX_TEXTURE_SIZE as f32,
-                    y: frame.y as f32 / MAX_TEXTURE_SIZE as f32,
-                    width: frame.w as f32 / MAX_TEXTURE_SIZE as f32,
-                    height: frame.h as f32 / MAX_TEXTURE_SIZE as f32,
-                }
-            })
+        pages,
+        textures.iter().enumerate().map(|(i, texture)| get_rect(format!("{}", i), texture.dimensions())).collect(),
+        (0..num_dynamic_textures)
+            .map(|i| get_rect(format!("dynamic:{}", i), dynamic_texture.dimensions()))
             .collect(),
     ))
 }
 
+/// The size of a side of a texture with its border, extended to a multiple of the alignment
+fn aligned_size(size: u32) -> u32 {
+    let padded_size = size + 2 * TEXTURE_PADDING;
+    padded_size.div_ceil(TEXTURE_ALIGNMENT) * TEXTURE_ALIGNMENT
+}
+
+/// Surround a texture with a border of `padding` pixels that repeats its opposite sides, as if it was tiled
+pub fn pad_texture(texture: &RgbaImage, padding: u32) -> RgbaImage {
+    let (width, height) = texture.dimensions();
+    wrap_texture(texture, padding, width + 2 * padding, height + 2 * padding)
+}
+
+/// Tile a texture into an image of some size, starting `padding` pixels from the top left corner
+fn wrap_texture(
+    texture: &RgbaImage,
+    padding: u32,
+    width: u32,
+    height: u32,
+) -> RgbaImage {
+    let (texture_width, texture_height) = texture.dimensions();
+    let wrap = |x: u32, size: u32| (x + size - padding % size) % size;
+    ImageBuffer::from_fn(width, height, |x, y| *texture.get_pixel(wrap(x, texture_width), wrap(y, texture_height)))
+}
+
+/// The average color of a texture of the atlas, ignoring the transparent pixels. `atlas` is the page of the texture.
+fn average_color(texture: TextureRect, atlas: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> [u8; 3] {
+    let x = (texture.x * MAX_TEXTURE_SIZE as f32).round() as u32;
+    let y = (texture.y * MAX_TEXTURE_SIZE as f32).round() as u32;
+    let width = (texture.width * MAX_TEXTURE_SIZE as f32).round() as u32;
+    let height = (texture.height * MAX_TEXTURE_SIZE as f32).round() as u32;
+    let mut sum = [0u64; 3];
+    let mut total_alpha = 0u64;
+    for u in x..(x + width) {
+        for v in y..(y + height) {
+            let rgba = atlas.get_pixel(u, v);
+            for c in 0..3 {
+                sum[c] += rgba[c] as u64 * rgba[3] as u64;
+            }
+            total_alpha += rgba[3] as u64;
+        }
+    }
+    [0, 1, 2].map(|c| (sum[c] / total_alpha.max(1)) as u8)
+}
+
 /// Load all <name>.ron files from a given folder and parse them into type `T`.
-fn load_files_from_folder<T: serde::de::DeserializeOwned>(directory: PathBuf) -> Vec<(String, T)> {
+pub fn load_files_from_folder<T: serde::de::DeserializeOwned>(directory: PathBuf) -> Vec<(String, T)> {
     let mut result = Vec::new();
     info!(
         "Loading objects of type {} from directory {}",
@@ -313,3 +725,42 @@ fn load_files_from_folder<T: serde::de::DeserializeOwned>(directory: PathBuf) ->
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_repeats_the_texture() {
+        let texture = ImageBuffer::from_fn(4, 2, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+        let padded = pad_texture(&texture, 3);
+        assert_eq!(padded.dimensions(), (10, 8));
+        for (x, y, pixel) in padded.enumerate_pixels() {
+            assert_eq!(pixel, texture.get_pixel((x + 1) % 4, (y + 1) % 2));
+        }
+    }
+
+    #[test]
+    fn textures_of_any_size_are_packed_into_pages() {
+        let textures = [16, 24, 40, 96]
+            .iter()
+            .map(|&size| ImageBuffer::from_fn(size, size, |x, y| Rgba([x as u8, y as u8, size as u8, 255])))
+            .collect::<Vec<_>>();
+        let (pages, rects, dynamic_rects) = pack_textures(&textures, 2, 128).unwrap();
+        assert!(pages.len() > 1);
+        assert_eq!(dynamic_rects.len(), 2);
+        for (texture, rect) in textures.iter().zip(rects.iter()) {
+            let page = &pages[rect.page as usize];
+            let (x, y) = ((rect.x * 128.0).round() as u32, (rect.y * 128.0).round() as u32);
+            // The mipmaps of the textures stay apart
+            assert_eq!((x - TEXTURE_PADDING) % TEXTURE_ALIGNMENT, 0);
+            assert_eq!((y - TEXTURE_PADDING) % TEXTURE_ALIGNMENT, 0);
+            assert_eq!((rect.width * 128.0).round() as u32, texture.width());
+            for (u, v, pixel) in texture.enumerate_pixels() {
+                assert_eq!(page.get_pixel(x + u, y + v), pixel);
+            }
+        }
+        // A texture that is larger than a page can't be packed
+        assert!(pack_textures(&[ImageBuffer::new(128, 128)], 0, 128).is_err());
+    }
+}
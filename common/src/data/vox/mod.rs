@@ -48,6 +48,17 @@ pub struct VoxelModel {
     pub full: Vec<bool>,
 }
 
+/// Generate a box-shaped model of a single color. The color is encoded like the voxels of a `VoxelModel`.
+pub fn generate_uniform_model((size_x, size_y, size_z): (usize, usize, usize), color: u32) -> VoxelModel {
+    VoxelModel {
+        size_x,
+        size_y,
+        size_z,
+        voxels: vec![color; size_x * size_y * size_z],
+        full: vec![true; size_x * size_y * size_z],
+    }
+}
+
 pub fn load_voxel_model(path: &str) -> Option<VoxelModel> {
     let file = File::open(path);
     match file {
@@ -1,6 +1,6 @@
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use lazy_static::lazy_static;
-use std::{collections::BTreeMap, sync::Arc, sync::RwLock};
+use std::{collections::BTreeMap, sync::Arc, sync::RwLock, time::Duration};
 lazy_static! {
     static ref DEBUG_INFO: Arc<RwLock<Option<Sender<DebugInfoUnit>>>> = Arc::new(RwLock::new(None));
 }
@@ -16,9 +16,13 @@ struct DebugInfoUnit {
 pub enum DebugInfoPart {
     Message(String),
     WorkerPerf(WorkerPerf),
-    PerfBreakdown(String, Vec<(String, f64)>)
+    PerfBreakdown(String, Vec<(String, f64)>),
+    TimeGraph(TimeGraph),
 }
 
+/// Number of durations shown by the time graphs
+pub const TIME_GRAPH_SAMPLES: usize = 100;
+
 /// Helper struct allowing multiple threads to easily show debug info.
 /// There can only be one active `DebugInfo` at any time.
 pub struct DebugInfo {
@@ -107,4 +111,32 @@ pub fn send_perf_breakdown(section: impl ToString, id: impl ToString, name: impl
             })
             .unwrap()
     });
-}
\ No newline at end of file
+}
+
+/// The durations of the last frames or ticks of a loop, to show its hitches
+#[derive(Debug, Clone)]
+pub struct TimeGraph {
+    pub name: String,
+    /// How long a frame can take before it is a hitch, in milliseconds
+    pub hitch_threshold_millis: f32,
+    /// The durations of the last frames in milliseconds, oldest first
+    pub millis: Vec<f32>,
+}
+
+/// Send a debug info time graph of the last `TIME_GRAPH_SAMPLES` durations
+pub fn send_time_graph(section: impl ToString, id: impl ToString, name: impl ToString, hitch_threshold: Duration, durations: Vec<Duration>) {
+    if let Some(sender) = DEBUG_INFO.read().unwrap().as_ref() {
+        let skipped = durations.len().saturating_sub(TIME_GRAPH_SAMPLES);
+        sender
+            .send(DebugInfoUnit {
+                section: section.to_string(),
+                id: id.to_string(),
+                part: DebugInfoPart::TimeGraph(TimeGraph {
+                    name: name.to_string(),
+                    hitch_threshold_millis: hitch_threshold.as_secs_f32() * 1000.0,
+                    millis: durations[skipped..].iter().map(|d| d.as_secs_f32() * 1000.0).collect(),
+                }),
+            })
+            .unwrap()
+    }
+}
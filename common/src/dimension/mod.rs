@@ -0,0 +1,37 @@
+use serde::Deserialize;
+
+pub type DimensionId = u32;
+
+/// The rules of a dimension. Systems that behave differently depending on the dimension
+/// should read them from here instead of checking the name of the dimension.
+/// This is the data provided by the creator of the dimension.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Dimension")]
+pub struct DimensionType {
+    /// Whether players can sleep in a bed in this dimension
+    pub can_sleep: bool,
+    /// Whether water disappears when it is placed in this dimension
+    pub water_evaporates: bool,
+    /// Minimum light level of every block in this dimension
+    pub ambient_light_floor: u8,
+    /// What happens to the items of the blocks that are broken in this dimension
+    #[serde(default)]
+    pub drop_mode: DropMode,
+}
+
+/// Where the items of broken blocks go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum DropMode {
+    /// The items are spawned as item entities that the players can pick up
+    #[default]
+    ItemEntity,
+    /// The items are put directly in the inventory of the player who broke the block
+    Inventory,
+}
+
+/// A general dimension in-memory representation
+#[derive(Debug, Clone)]
+pub struct Dimension {
+    pub name: String,
+    pub ty: DimensionType,
+}
@@ -0,0 +1,48 @@
+use crate::item::ItemId;
+use crate::mob::MobId;
+
+pub mod model;
+
+/// Some unique entity id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(pub u32);
+
+/// The kind of an entity, i.e. what it is and how it should be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    /// A mob, with its id in the mob registry
+    Mob(MobId),
+    /// A thrown projectile, for example a rock
+    Projectile,
+    /// A vehicle that a player can ride
+    Vehicle(VehicleKind),
+    /// A dropped item that players can pick up, with its id in the item registry
+    Item(ItemId),
+}
+
+/// The kind of a vehicle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VehicleKind {
+    /// Steers freely, but slowly
+    Boat,
+    /// Fast, but only moves along the axis it is facing
+    Minecart,
+}
+
+impl VehicleKind {
+    /// Get the size of the aabb of the vehicle. It is the same along the x and z axes, so that it doesn't depend on the direction of the vehicle.
+    pub fn size(self) -> (f64, f64, f64) {
+        match self {
+            VehicleKind::Boat => (1.4, 0.5, 1.4),
+            VehicleKind::Minecart => (1.2, 0.75, 1.2),
+        }
+    }
+
+    /// Get the height of the feet of the rider above the bottom of the vehicle
+    pub fn seat_height(self) -> f64 {
+        match self {
+            VehicleKind::Boat => 0.2,
+            VehicleKind::Minecart => 0.3,
+        }
+    }
+}
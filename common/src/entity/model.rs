@@ -0,0 +1,47 @@
+//! The models of the mobs and of the players, made of cuboids of a single color.
+use serde::Deserialize;
+
+/// Number of voxels of the parts of the box models along a block
+pub const BOX_MODEL_VOXELS_PER_BLOCK: f32 = 16.0;
+
+/// A model made of cuboids. The model faces +x, and its origin is the bottom center of the hitbox.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename = "BoxModel")]
+pub struct BoxModel {
+    pub parts: Vec<ModelPart>,
+}
+
+/// A cuboid of a box model
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelPart {
+    /// Position of the corner of the part with the lowest coordinates, in voxels
+    pub offset: (i32, i32, i32),
+    /// Size of the part, in voxels
+    pub size: (u32, u32, u32),
+    /// Color of the part, encoded like the voxels of a `VoxelModel`
+    pub color: u32,
+    /// Maximum angle of the part around the sideways axis through its top while the entity walks, in degrees.
+    /// The parts whose swing has opposite signs move in opposite directions, like the legs.
+    #[serde(default)]
+    pub swing: f32,
+}
+
+/// Name in the model registry of the voxel model of a part of a box model
+pub fn box_model_part_name(model_name: &str, part_index: usize) -> String {
+    format!("{}:part{}", model_name, part_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_model_is_valid() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../data/model/player.ron");
+        let model: BoxModel = ron::de::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert!(!model.parts.is_empty());
+        // The legs swing in opposite directions
+        assert_eq!(model.parts[0].swing, -model.parts[1].swing);
+        assert_eq!(box_model_part_name("player", 2), "player:part2");
+    }
+}
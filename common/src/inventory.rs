@@ -0,0 +1,353 @@
+//! Inventories: fixed numbers of slots that contain stacks of items.
+use crate::item::ItemId;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Maximum number of items in a single stack
+pub const MAX_STACK_SIZE: u32 = 64;
+/// Number of slots of the inventory of a player
+pub const PLAYER_INVENTORY_SIZE: usize = 36;
+/// Number of slots of the hotbar, which are the first slots of the inventory of a player
+pub const HOTBAR_SIZE: usize = 9;
+
+/// Some items of the same kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item_id: ItemId,
+    /// Number of items in the stack, between 1 and `MAX_STACK_SIZE`
+    pub count: u32,
+    /// Extra data whose meaning depends on the item. Stacks of the same item only merge if they have the same data.
+    pub data: u32,
+}
+
+impl ItemStack {
+    pub fn new(item_id: ItemId, count: u32) -> Self {
+        Self::with_data(item_id, count, 0)
+    }
+
+    pub fn with_data(item_id: ItemId, count: u32, data: u32) -> Self {
+        Self { item_id, count, data }
+    }
+
+    /// Whether the items of both stacks can be in the same stack
+    pub fn can_merge_with(&self, other: &ItemStack) -> bool {
+        self.item_id == other.item_id && self.data == other.data
+    }
+}
+
+/// The slots of an inventory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+    /// Create an empty inventory with `size` slots
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size],
+        }
+    }
+
+    /// Number of slots
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Get the stack in some slot. Return `None` if the slot is empty or doesn't exist.
+    pub fn get(&self, slot: usize) -> Option<ItemStack> {
+        self.slots.get(slot).cloned().flatten()
+    }
+
+    /// Replace the stack in some slot. Does nothing if the slot doesn't exist.
+    pub fn set(&mut self, slot: usize, stack: Option<ItemStack>) {
+        if let Some(s) = self.slots.get_mut(slot) {
+            *s = stack.filter(|stack| stack.count > 0);
+        }
+    }
+
+    /// Iterate over the slots
+    pub fn slots(&self) -> impl Iterator<Item = Option<ItemStack>> + '_ {
+        self.slots.iter().cloned()
+    }
+
+    /// Total number of items of the inventory
+    pub fn count_items(&self) -> u32 {
+        self.slots.iter().flatten().map(|stack| stack.count).sum()
+    }
+
+    /// Number of items of some kind in the inventory
+    pub fn count_item(&self, item_id: ItemId) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.item_id == item_id)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    /// Remove up to `count` items of some kind from the inventory, starting with the last slots.
+    /// Return the slots that changed.
+    pub fn remove_item(&mut self, item_id: ItemId, mut count: u32) -> Vec<usize> {
+        let mut changed_slots = Vec::new();
+        for i in (0..self.slots.len()).rev() {
+            if count == 0 {
+                break;
+            }
+            if self.slots[i].map(|stack| stack.item_id == item_id).unwrap_or(false) {
+                if let Some(removed) = self.remove(i, count) {
+                    count -= removed.count;
+                    changed_slots.push(i);
+                }
+            }
+        }
+        changed_slots
+    }
+
+    /// Add items to the inventory, filling the existing stacks of the same item first.
+    /// Return the slots that changed, and the items that didn't fit.
+    pub fn insert(&mut self, stack: ItemStack) -> (Vec<usize>, Option<ItemStack>) {
+        self.insert_in_slots(stack, 0..self.slots.len())
+    }
+
+    /// Add items to some slots of the inventory, like `insert`
+    fn insert_in_slots(&mut self, mut stack: ItemStack, slots: Range<usize>) -> (Vec<usize>, Option<ItemStack>) {
+        let mut changed_slots = Vec::new();
+        // Fill the existing stacks
+        for i in slots.clone() {
+            if stack.count == 0 {
+                break;
+            }
+            if let Some(existing) = &mut self.slots[i] {
+                if existing.can_merge_with(&stack) && existing.count < MAX_STACK_SIZE {
+                    let moved = stack.count.min(MAX_STACK_SIZE - existing.count);
+                    existing.count += moved;
+                    stack.count -= moved;
+                    changed_slots.push(i);
+                }
+            }
+        }
+        // Then the empty slots
+        for i in slots {
+            if stack.count == 0 {
+                break;
+            }
+            if self.slots[i].is_none() {
+                let moved = stack.count.min(MAX_STACK_SIZE);
+                self.slots[i] = Some(ItemStack { count: moved, ..stack });
+                stack.count -= moved;
+                changed_slots.push(i);
+            }
+        }
+        let remaining = if stack.count > 0 { Some(stack) } else { None };
+        (changed_slots, remaining)
+    }
+
+    /// Remove up to `count` items from some slot, and return them
+    pub fn remove(&mut self, slot: usize, count: u32) -> Option<ItemStack> {
+        let existing = self.slots.get_mut(slot)?.as_mut()?;
+        let removed = count.min(existing.count);
+        existing.count -= removed;
+        let removed_stack = ItemStack { count: removed, ..*existing };
+        if existing.count == 0 {
+            self.slots[slot] = None;
+        }
+        if removed > 0 {
+            Some(removed_stack)
+        } else {
+            None
+        }
+    }
+
+    /// Swap the contents of two slots. Return whether both slots exist.
+    pub fn swap(&mut self, a: usize, b: usize) -> bool {
+        if a >= self.slots.len() || b >= self.slots.len() {
+            return false;
+        }
+        self.slots.swap(a, b);
+        true
+    }
+
+    /// Move the stack of slot `from` to slot `to`. If both slots contain the same item, as many items as possible
+    /// are merged into `to`. Otherwise the slots are swapped. Return whether both slots exist.
+    pub fn move_items(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.slots.len() || to >= self.slots.len() {
+            return false;
+        }
+        if from == to {
+            return true;
+        }
+        match (self.slots[from], self.slots[to]) {
+            (Some(source), Some(mut target)) if source.can_merge_with(&target) => {
+                let moved = source.count.min(MAX_STACK_SIZE - target.count.min(MAX_STACK_SIZE));
+                target.count += moved;
+                self.slots[to] = Some(target);
+                self.slots[from] = if source.count > moved {
+                    Some(ItemStack { count: source.count - moved, ..source })
+                } else {
+                    None
+                };
+            }
+            _ => self.slots.swap(from, to),
+        }
+        true
+    }
+
+    /// Move up to `count` items of slot `from` to slot `to`, if it is empty or holds the same item.
+    /// Return whether both slots exist.
+    pub fn move_some_items(&mut self, from: usize, to: usize, count: u32) -> bool {
+        if from >= self.slots.len() || to >= self.slots.len() {
+            return false;
+        }
+        if let (Some(source), false) = (self.slots[from], from == to) {
+            let (target_count, room) = match self.slots[to] {
+                None => (0, MAX_STACK_SIZE),
+                Some(target) if source.can_merge_with(&target) => (target.count, MAX_STACK_SIZE - target.count.min(MAX_STACK_SIZE)),
+                Some(_) => (0, 0),
+            };
+            let moved = count.min(source.count).min(room);
+            if moved > 0 {
+                self.slots[to] = Some(ItemStack { count: target_count + moved, ..source });
+                self.set(from, Some(ItemStack { count: source.count - moved, ..source }));
+            }
+        }
+        true
+    }
+
+    /// Move the stack of a slot of the hotbar of a player inventory to the other slots, or the stack of another slot
+    /// to the hotbar, filling the existing stacks of the same item first. Return the slots that changed.
+    pub fn quick_move(&mut self, slot: usize) -> Vec<usize> {
+        let stack = match self.get(slot) {
+            Some(stack) => stack,
+            None => return Vec::new(),
+        };
+        let target_slots = if slot < HOTBAR_SIZE { HOTBAR_SIZE..self.slots.len() } else { 0..HOTBAR_SIZE.min(self.slots.len()) };
+        self.slots[slot] = None;
+        let (mut changed_slots, remaining) = self.insert_in_slots(stack, target_slots);
+        self.slots[slot] = remaining;
+        if !changed_slots.is_empty() {
+            changed_slots.push(slot);
+        }
+        changed_slots
+    }
+}
+
+/// Move the stack of slot `from` of an inventory to slot `to` of another inventory, like `Inventory::move_items`.
+/// Return whether both slots exist.
+pub fn move_items_between(source: &mut Inventory, from: usize, target: &mut Inventory, to: usize) -> bool {
+    if from >= source.slots.len() || to >= target.slots.len() {
+        return false;
+    }
+    match (source.slots[from], target.slots[to]) {
+        (Some(moved_stack), Some(mut target_stack)) if moved_stack.can_merge_with(&target_stack) => {
+            let moved = moved_stack.count.min(MAX_STACK_SIZE - target_stack.count.min(MAX_STACK_SIZE));
+            target_stack.count += moved;
+            target.slots[to] = Some(target_stack);
+            source.slots[from] = if moved_stack.count > moved {
+                Some(ItemStack { count: moved_stack.count - moved, ..moved_stack })
+            } else {
+                None
+            };
+        }
+        _ => std::mem::swap(&mut source.slots[from], &mut target.slots[to]),
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_fills_existing_stacks_first() {
+        let mut inventory = Inventory::new(3);
+        inventory.set(2, Some(ItemStack::new(1, 60)));
+        let (changed, remaining) = inventory.insert(ItemStack::new(1, 10));
+        assert_eq!(changed, vec![2, 0]);
+        assert_eq!(remaining, None);
+        assert_eq!(inventory.get(2), Some(ItemStack::new(1, 64)));
+        assert_eq!(inventory.get(0), Some(ItemStack::new(1, 6)));
+
+        let (_, remaining) = inventory.insert(ItemStack::new(2, 200));
+        assert_eq!(remaining, Some(ItemStack::new(2, 136)));
+        assert_eq!(inventory.count_items(), 64 + 6 + 64);
+    }
+
+    #[test]
+    fn stacks_with_different_data_dont_merge() {
+        let mut inventory = Inventory::new(2);
+        inventory.set(0, Some(ItemStack::with_data(1, 10, 3)));
+        let (changed, _) = inventory.insert(ItemStack::new(1, 10));
+        assert_eq!(changed, vec![1]);
+        assert!(inventory.move_items(1, 0));
+        assert_eq!(inventory.get(0), Some(ItemStack::new(1, 10)));
+        assert_eq!(inventory.get(1), Some(ItemStack::with_data(1, 10, 3)));
+    }
+
+    #[test]
+    fn move_merges_or_swaps() {
+        let mut inventory = Inventory::new(3);
+        inventory.set(0, Some(ItemStack::new(1, 40)));
+        inventory.set(1, Some(ItemStack::new(1, 40)));
+        inventory.set(2, Some(ItemStack::new(2, 1)));
+        assert!(inventory.move_items(0, 1));
+        assert_eq!(inventory.get(0), Some(ItemStack::new(1, 16)));
+        assert_eq!(inventory.get(1), Some(ItemStack::new(1, 64)));
+        assert!(inventory.move_items(2, 0));
+        assert_eq!(inventory.get(0), Some(ItemStack::new(2, 1)));
+        assert_eq!(inventory.get(2), Some(ItemStack::new(1, 16)));
+        assert!(!inventory.move_items(0, 3));
+    }
+
+    #[test]
+    fn split_and_quick_move() {
+        let mut inventory = Inventory::new(PLAYER_INVENTORY_SIZE);
+        inventory.set(0, Some(ItemStack::new(1, 40)));
+        inventory.set(HOTBAR_SIZE + 1, Some(ItemStack::new(1, 60)));
+        inventory.set(HOTBAR_SIZE + 2, Some(ItemStack::new(2, 1)));
+        // Only the empty slots and the stacks of the same item receive the items
+        assert!(inventory.move_some_items(0, 1, 20));
+        assert!(inventory.move_some_items(0, HOTBAR_SIZE + 2, 5));
+        assert_eq!(inventory.get(0), Some(ItemStack::new(1, 20)));
+        assert_eq!(inventory.get(1), Some(ItemStack::new(1, 20)));
+        assert_eq!(inventory.get(HOTBAR_SIZE + 2), Some(ItemStack::new(2, 1)));
+        // The stack of the hotbar fills the stack of the same item, then the first empty slot
+        assert_eq!(inventory.quick_move(0), vec![HOTBAR_SIZE + 1, HOTBAR_SIZE, 0]);
+        assert_eq!(inventory.get(HOTBAR_SIZE + 1), Some(ItemStack::new(1, 64)));
+        assert_eq!(inventory.get(HOTBAR_SIZE), Some(ItemStack::new(1, 16)));
+        assert_eq!(inventory.get(0), None);
+        // And the other stacks go to the hotbar
+        assert_eq!(inventory.quick_move(HOTBAR_SIZE + 2), vec![0, HOTBAR_SIZE + 2]);
+        assert_eq!(inventory.get(0), Some(ItemStack::new(2, 1)));
+        assert!(inventory.quick_move(HOTBAR_SIZE + 2).is_empty());
+    }
+
+    #[test]
+    fn move_between_inventories() {
+        let mut player = Inventory::new(2);
+        let mut chest = Inventory::new(2);
+        player.set(0, Some(ItemStack::new(1, 40)));
+        chest.set(0, Some(ItemStack::new(1, 40)));
+        chest.set(1, Some(ItemStack::new(2, 1)));
+        assert!(move_items_between(&mut player, 0, &mut chest, 0));
+        assert_eq!(player.get(0), Some(ItemStack::new(1, 16)));
+        assert_eq!(chest.get(0), Some(ItemStack::new(1, 64)));
+        assert!(move_items_between(&mut chest, 1, &mut player, 1));
+        assert_eq!(chest.get(1), None);
+        assert_eq!(player.get(1), Some(ItemStack::new(2, 1)));
+        assert!(!move_items_between(&mut player, 0, &mut chest, 2));
+    }
+
+    #[test]
+    fn remove_empties_the_slot() {
+        let mut inventory = Inventory::new(1);
+        inventory.set(0, Some(ItemStack::new(1, 3)));
+        assert_eq!(inventory.remove(0, 5), Some(ItemStack::new(1, 3)));
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(inventory.remove(0, 1), None);
+    }
+}
@@ -0,0 +1,173 @@
+//! Maps: items that record the terrain explored by the players who hold them, seen from above.
+//!
+//! The canvases of the maps are stored by the server, and the data of a map stack is the id of its canvas.
+//! The clients that hold a copy of a map receive its whole canvas once, and then only the regions that changed.
+use crate::world::BlockPos;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// The id of the canvas of a map, which is the data of the map stacks. Blank maps have the id 0, they get a canvas once they are held.
+pub type MapId = u32;
+
+/// Side of the area covered by a map, in blocks. Every pixel of the canvas is a column of blocks.
+pub const MAP_SIZE: u32 = 64;
+/// The color of the parts of the map that were not explored yet
+const PARCHMENT_COLOR: [u8; 3] = [0xD9, 0xC9, 0x9A];
+
+/// A rectangle of pixels of a canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapRect {
+    pub x: u32,
+    pub z: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MapRect {
+    /// The rectangle that only contains the pixel (x, z)
+    pub fn pixel(x: u32, z: u32) -> Self {
+        Self {
+            x,
+            z,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    /// The smallest rectangle that contains both rectangles
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let z = self.z.min(other.z);
+        Self {
+            x,
+            z,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.z + self.height).max(other.z + other.height) - z,
+        }
+    }
+}
+
+/// The pixels of a rectangle of a canvas that changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapPatch {
+    pub rect: MapRect,
+    /// The pixels of the rectangle, row by row
+    pub pixels: Vec<Option<[u8; 3]>>,
+}
+
+/// The terrain recorded by a map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapCanvas {
+    /// The x of the west edge of the area covered by the map
+    pub min_x: i64,
+    /// The z of the north edge of the area covered by the map
+    pub min_z: i64,
+    /// The color of every column of the area, row by row starting from the north, `None` if it was not explored yet
+    pub pixels: Vec<Option<[u8; 3]>>,
+}
+
+impl MapCanvas {
+    /// A blank map of the area that contains `pos`.
+    /// The areas of the maps are aligned on a grid, so that the maps of neighbouring areas line up.
+    pub fn new(pos: BlockPos) -> Self {
+        let size = MAP_SIZE as i64;
+        Self {
+            min_x: pos.px.div_euclid(size) * size,
+            min_z: pos.pz.div_euclid(size) * size,
+            pixels: vec![None; (MAP_SIZE * MAP_SIZE) as usize],
+        }
+    }
+
+    /// Whether the canvas has the right number of pixels, for the canvases read from the disk
+    pub fn is_valid(&self) -> bool {
+        self.pixels.len() == (MAP_SIZE * MAP_SIZE) as usize
+    }
+
+    /// The pixel of the column at (x, z), if the map covers it
+    pub fn get_pixel_pos(&self, x: i64, z: i64) -> Option<(u32, u32)> {
+        let range = 0..MAP_SIZE as i64;
+        let (u, v) = (x - self.min_x, z - self.min_z);
+        if range.contains(&u) && range.contains(&v) {
+            Some((u as u32, v as u32))
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, x: u32, z: u32) -> Option<[u8; 3]> {
+        self.pixels[(z * MAP_SIZE + x) as usize]
+    }
+
+    /// Set the color of a pixel, and return whether it changed
+    pub fn set(&mut self, x: u32, z: u32, color: [u8; 3]) -> bool {
+        let pixel = &mut self.pixels[(z * MAP_SIZE + x) as usize];
+        let changed = *pixel != Some(color);
+        *pixel = Some(color);
+        changed
+    }
+
+    /// Copy the pixels of a rectangle of the canvas
+    pub fn get_patch(&self, rect: MapRect) -> MapPatch {
+        let mut pixels = Vec::with_capacity((rect.width * rect.height) as usize);
+        for z in rect.z..rect.z + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                pixels.push(self.get(x, z));
+            }
+        }
+        MapPatch { rect, pixels }
+    }
+
+    /// Copy the pixels of a patch to the canvas. Return `false` and leave the canvas unchanged if the patch doesn't fit.
+    pub fn apply_patch(&mut self, patch: &MapPatch) -> bool {
+        let MapRect { x, z, width, height } = patch.rect;
+        if x + width > MAP_SIZE || z + height > MAP_SIZE || patch.pixels.len() != (width * height) as usize {
+            return false;
+        }
+        for (i, pixel) in patch.pixels.iter().enumerate() {
+            let (dx, dz) = (i as u32 % width, i as u32 / width);
+            self.pixels[((z + dz) * MAP_SIZE + x + dx) as usize] = *pixel;
+        }
+        true
+    }
+
+    /// Draw the map in a square texture of side `size`, north at the top
+    pub fn compose(&self, size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| {
+            let [r, g, b] = self
+                .get(x * MAP_SIZE / size, y * MAP_SIZE / size)
+                .unwrap_or(PARCHMENT_COLOR);
+            Rgba([r, g, b, 255])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_copy_the_changed_pixels() {
+        // The areas are aligned on the grid, even at negative coordinates
+        let mut canvas = MapCanvas::new(BlockPos { px: -1, py: 70, pz: 100 });
+        assert_eq!((canvas.min_x, canvas.min_z), (-(MAP_SIZE as i64), MAP_SIZE as i64));
+        assert_eq!(canvas.get_pixel_pos(-1, 100), Some((MAP_SIZE - 1, 100 - MAP_SIZE)));
+        assert_eq!(canvas.get_pixel_pos(0, 100), None);
+
+        let mut copy = canvas.clone();
+        assert!(canvas.set(3, 4, [1, 2, 3]));
+        assert!(!canvas.set(3, 4, [1, 2, 3]));
+        assert!(canvas.set(10, 2, [4, 5, 6]));
+        let rect = MapRect::pixel(3, 4).union(MapRect::pixel(10, 2));
+        assert_eq!(rect, MapRect { x: 3, z: 2, width: 8, height: 3 });
+
+        let patch = canvas.get_patch(rect);
+        assert!(copy.apply_patch(&patch));
+        assert_eq!(copy, canvas);
+
+        let outside = MapPatch {
+            rect: MapRect::pixel(MAP_SIZE, 0),
+            pixels: vec![None],
+        };
+        assert!(!copy.apply_patch(&outside));
+    }
+}
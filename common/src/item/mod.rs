@@ -1,5 +1,8 @@
+use crate::data::TextureRect;
 use serde::Deserialize;
 
+pub mod map;
+
 pub type ItemId = u32;
 
 /// The type of an item. It contains the behavior and the texture of the item.
@@ -7,7 +10,54 @@ pub type ItemId = u32;
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename = "Item")]
 pub enum ItemType {
-    NormalItem { texture: String },
+    NormalItem {
+        texture: String,
+        /// The name of the block that is placed when right-clicking with the item
+        #[serde(default)]
+        places_block: Option<String>,
+        /// The tool properties of the item, if it is a tool
+        #[serde(default)]
+        tool: Option<ToolProperties>,
+        /// The hunger points that eating the item restores, if it is food
+        #[serde(default)]
+        food: Option<u32>,
+        /// Whether using the item throws it as a projectile
+        #[serde(default)]
+        throwable: bool,
+    },
+    /// An item whose icon is chosen among `textures` by the client, depending on the state of the game
+    DynamicItem { textures: Vec<String>, icon: DynamicIcon },
+    /// A map, that records the terrain explored by the player who holds it
+    MapItem { texture: String },
+}
+
+/// The kind of blocks a tool is effective against
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub enum ToolClass {
+    Pickaxe,
+    Axe,
+    Shovel,
+    /// Tills the soil instead of breaking blocks faster
+    Hoe,
+}
+
+/// The properties of a tool item
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ToolProperties {
+    pub class: ToolClass,
+    /// The tier of the tool, compared to the tier that the blocks require to be harvested. Bare hands have tier 0.
+    pub tier: u32,
+    /// How many times faster the tool breaks the blocks it is effective against
+    pub speed: f64,
+}
+
+/// How the client chooses the current icon of a `DynamicItem`
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub enum DynamicIcon {
+    /// The textures are the needle pointing in evenly spaced directions, clockwise, starting straight ahead
+    Compass,
+    /// The textures are the dial at evenly spaced times of the day, starting at sunrise
+    Clock,
 }
 
 /// The mesh of an item
@@ -21,6 +71,20 @@ pub enum ItemMesh {
         scale: f32,
         /// Center of the mesh, relative to the cube at position (0, 0, 0), before scaling
         mesh_center: (f32, f32, f32),
+        /// The texture of the item in the texture atlas, its icon in the inventory
+        texture: TextureRect,
+    },
+    /// One mesh for every icon of a dynamic item
+    DynamicMesh {
+        /// Ids of the meshes, in the order of the textures of the item
+        mesh_ids: Vec<u32>,
+        /// Scale of the meshes
+        scale: f32,
+        /// Center of the meshes, relative to the cube at position (0, 0, 0), before scaling
+        mesh_center: (f32, f32, f32),
+        /// The textures of the item in the texture atlas, in the same order as the meshes
+        textures: Vec<TextureRect>,
+        icon: DynamicIcon,
     },
 }
 
@@ -30,3 +94,47 @@ pub struct Item {
     pub name: String,
     pub ty: ItemType,
 }
+
+impl Item {
+    /// The name of the block that is placed when right-clicking with the item, if any
+    pub fn get_placed_block(&self) -> Option<&String> {
+        match &self.ty {
+            ItemType::NormalItem { places_block, .. } => places_block.as_ref(),
+            ItemType::DynamicItem { .. } | ItemType::MapItem { .. } => None,
+        }
+    }
+
+    /// The tool properties of the item, if it is a tool
+    pub fn get_tool(&self) -> Option<ToolProperties> {
+        match &self.ty {
+            ItemType::NormalItem { tool, .. } => *tool,
+            ItemType::DynamicItem { .. } | ItemType::MapItem { .. } => None,
+        }
+    }
+
+    /// The hunger points that eating the item restores, if it is food
+    pub fn get_food(&self) -> Option<u32> {
+        match &self.ty {
+            ItemType::NormalItem { food, .. } => *food,
+            ItemType::DynamicItem { .. } | ItemType::MapItem { .. } => None,
+        }
+    }
+
+    /// Whether using the item throws it as a projectile
+    pub fn is_throwable(&self) -> bool {
+        match &self.ty {
+            ItemType::NormalItem { throwable, .. } => *throwable,
+            ItemType::DynamicItem { .. } | ItemType::MapItem { .. } => false,
+        }
+    }
+
+    /// Whether the item is a map, whose stacks have the id of their canvas as data
+    pub fn is_map(&self) -> bool {
+        matches!(self.ty, ItemType::MapItem { .. })
+    }
+
+    /// Whether the item is a clock, which shows the time of the world
+    pub fn is_clock(&self) -> bool {
+        matches!(self.ty, ItemType::DynamicItem { icon: DynamicIcon::Clock, .. })
+    }
+}
@@ -1,13 +1,23 @@
+pub mod advancement;
+pub mod biome;
 pub mod block;
 pub mod collections;
+pub mod crafting;
 pub mod data;
 pub mod debug;
+pub mod dimension;
+pub mod entity;
+pub mod inventory;
 pub mod item;
+pub mod mob;
 pub mod network;
 pub mod physics;
 pub mod player;
 pub mod registry;
+pub mod smelting;
 pub mod time;
+pub mod watchdog;
+pub mod weather;
 pub mod worker;
 pub mod world;
 pub mod worldgen;
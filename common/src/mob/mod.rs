@@ -0,0 +1,80 @@
+use crate::entity::model::BoxModel;
+use serde::Deserialize;
+
+pub type MobId = u32;
+
+/// The category of a mob. It decides how the mob reacts to players.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Hash)]
+pub enum MobCategory {
+    /// Wanders around and ignores players
+    Passive,
+    /// Chases the players that come too close
+    Hostile,
+    /// Wanders around and ignores players, only there for the atmosphere
+    Ambient,
+}
+
+/// The conditions that must hold for a mob to spawn at some position.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnConditions {
+    /// Minimum light level at the spawn position, inclusive
+    pub min_light: u8,
+    /// Maximum light level at the spawn position, inclusive
+    pub max_light: u8,
+    /// Names of the blocks the mob can spawn on. Until there are proper biomes, this is what ties mobs to a biome.
+    pub blocks: Vec<String>,
+}
+
+/// How a mob can be tamed by feeding it
+#[derive(Debug, Clone, Deserialize)]
+pub struct TamingConditions {
+    /// Name of the item that the mob is fed to tame it
+    pub food: String,
+    /// Probability that a single feeding tames the mob
+    pub chance: f64,
+}
+
+/// The type of a mob. It contains the behavior of the mob.
+/// This is the data provided by the creator of the mob.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Mob")]
+pub struct MobType {
+    pub category: MobCategory,
+    /// Size of the hitbox along the x, y and z axes
+    pub size: (f64, f64, f64),
+    /// Walking speed in blocks per second
+    pub speed: f64,
+    /// Distance at which a hostile mob notices players
+    pub detection_range: f64,
+    pub max_health: f64,
+    pub spawn: SpawnConditions,
+    /// Whether and how the mob can become a pet. Mobs without it can't be tamed.
+    #[serde(default)]
+    pub taming: Option<TamingConditions>,
+    /// The model that the clients draw. Mobs without it are invisible.
+    #[serde(default)]
+    pub model: BoxModel,
+}
+
+/// A general mob in-memory representation
+#[derive(Debug, Clone)]
+pub struct Mob {
+    pub name: String,
+    pub ty: MobType,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mob_files_are_valid() {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/../data/mobs");
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            let ty: Result<MobType, _> = ron::de::from_str(&std::fs::read_to_string(&path).unwrap());
+            let ty = ty.unwrap_or_else(|e| panic!("{} is invalid: {}", path.display(), e));
+            assert!(!ty.model.parts.is_empty(), "{} has no model", path.display());
+        }
+    }
+}
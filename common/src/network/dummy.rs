@@ -3,57 +3,106 @@ use crate::{
     network::{ClientEvent, ServerEvent},
     player::PlayerId,
 };
+use std::collections::VecDeque;
 use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 
 pub struct DummyClient {
     first_queried: bool,
-    pub(self) to_server: Sender<ToServer>,
+    /// The channel to the server, `None` once the client disconnected
+    pub(self) to_server: Option<Sender<ToServer>>,
     pub(self) to_client: Receiver<ToClient>,
 }
 
+/// The server side of a dummy client
+struct DummyConnection {
+    to_client: Sender<ToClient>,
+    to_server: Receiver<ToServer>,
+}
+
 pub struct DummyServer {
-    first_queried: bool,
-    pub(self) to_client: Sender<ToClient>,
-    pub(self) to_server: Receiver<ToServer>,
+    /// The clients whose connection was not reported yet
+    pending_connections: VecDeque<PlayerId>,
+    /// The connections indexed by player id, `None` once the client was dropped
+    connections: Vec<Option<DummyConnection>>,
+    /// The connection that is polled first, so that a busy client doesn't starve the others
+    next_connection: usize,
 }
 
 pub fn new() -> (DummyClient, DummyServer) {
-    let server_to_client = channel();
-    let client_to_server = channel();
-    (
-        DummyClient {
+    let (mut clients, server) = new_many(1);
+    (clients.pop().unwrap(), server)
+}
+
+/// Create `count` dummy clients connected to the same server, with the player ids `0..count`
+pub fn new_many(count: usize) -> (Vec<DummyClient>, DummyServer) {
+    assert!(count <= u16::MAX as usize + 1, "too many dummy clients");
+    let mut clients = Vec::with_capacity(count);
+    let mut connections = Vec::with_capacity(count);
+    for _ in 0..count {
+        let server_to_client = channel();
+        let client_to_server = channel();
+        clients.push(DummyClient {
             first_queried: true,
-            to_server: client_to_server.0,
+            to_server: Some(client_to_server.0),
             to_client: server_to_client.1,
-        },
-        DummyServer {
-            first_queried: true,
+        });
+        connections.push(Some(DummyConnection {
             to_client: server_to_client.0,
             to_server: client_to_server.1,
-        },
-    )
+        }));
+    }
+    let server = DummyServer {
+        pending_connections: (0..count).map(|i| PlayerId(i as u16)).collect(),
+        connections,
+        next_connection: 0,
+    };
+    (clients, server)
 }
 
 impl super::Server for DummyServer {
     fn receive_event(&mut self) -> ServerEvent {
-        if self.first_queried {
-            self.first_queried = false;
-            return ServerEvent::ClientConnected(PlayerId(0));
+        if let Some(id) = self.pending_connections.pop_front() {
+            return ServerEvent::ClientConnected(id);
+        }
+        let count = self.connections.len();
+        for offset in 0..count {
+            let i = (self.next_connection + offset) % count;
+            let connection = match &self.connections[i] {
+                Some(connection) => connection,
+                None => continue,
+            };
+            match connection.to_server.try_recv() {
+                Ok(m) => {
+                    self.next_connection = (i + 1) % count;
+                    return ServerEvent::ClientMessage(PlayerId(i as u16), m);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    self.connections[i] = None;
+                    return ServerEvent::ClientDisconnected(PlayerId(i as u16));
+                }
+            }
         }
-        match self.to_server.try_recv() {
-            Ok(m) => ServerEvent::ClientMessage(PlayerId(0), m),
-            Err(TryRecvError::Empty) => ServerEvent::NoEvent,
-            Err(TryRecvError::Disconnected) => panic!("Got to somehow terminate the server :)"),
+        ServerEvent::NoEvent
+    }
+
+    fn send(&mut self, client: PlayerId, message: ToClient) {
+        if let Some(Some(connection)) = self.connections.get(client.0 as usize) {
+            // The client may have been dropped since its last message
+            let _ = connection.to_client.send(message);
         }
     }
 
-    fn send(&mut self, _: PlayerId, message: ToClient) {
-        self.to_client.send(message).unwrap();
+    fn is_closed(&self) -> bool {
+        self.pending_connections.is_empty() && self.connections.iter().all(Option::is_none)
     }
 }
 
 impl super::Client for DummyClient {
     fn receive_event(&mut self) -> ClientEvent {
+        if self.to_server.is_none() {
+            return ClientEvent::NoEvent;
+        }
         if self.first_queried {
             self.first_queried = false;
             return ClientEvent::Connected;
@@ -66,6 +115,48 @@ impl super::Client for DummyClient {
     }
 
     fn send(&mut self, message: ToServer) {
-        self.to_server.send(message).unwrap();
+        if let Some(to_server) = &self.to_server {
+            to_server.send(message).unwrap();
+        }
+    }
+
+    fn disconnect(&mut self) {
+        // The server notices that the channel was closed
+        self.to_server = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{Client, Server};
+
+    #[test]
+    fn test_many_clients() {
+        let (mut clients, mut server) = new_many(2);
+        assert!(matches!(server.receive_event(), ServerEvent::ClientConnected(PlayerId(0))));
+        assert!(matches!(server.receive_event(), ServerEvent::ClientConnected(PlayerId(1))));
+        // A busy client doesn't starve the others
+        clients[0].send(ToServer::DismountVehicle);
+        clients[0].send(ToServer::DismountVehicle);
+        clients[1].send(ToServer::DismountVehicle);
+        assert!(matches!(server.receive_event(), ServerEvent::ClientMessage(PlayerId(0), _)));
+        assert!(matches!(server.receive_event(), ServerEvent::ClientMessage(PlayerId(1), _)));
+        assert!(matches!(server.receive_event(), ServerEvent::ClientMessage(PlayerId(0), _)));
+        assert!(matches!(server.receive_event(), ServerEvent::NoEvent));
+        // The messages go to the right client, and the dropped clients disconnect
+        server.send(PlayerId(1), ToClient::ChatMessage("hello".to_owned()));
+        assert!(matches!(clients[1].receive_event(), ClientEvent::Connected));
+        assert!(matches!(clients[1].receive_event(), ClientEvent::ServerMessage(ToClient::ChatMessage(_))));
+        drop(clients.remove(0));
+        assert!(matches!(server.receive_event(), ServerEvent::ClientDisconnected(PlayerId(0))));
+        server.send(PlayerId(0), ToClient::ChatMessage("gone".to_owned()));
+        assert!(matches!(server.receive_event(), ServerEvent::NoEvent));
+        // The server is closed once the last client disconnected
+        assert!(!server.is_closed());
+        clients[0].disconnect();
+        clients[0].send(ToServer::DismountVehicle);
+        assert!(matches!(server.receive_event(), ServerEvent::ClientDisconnected(PlayerId(1))));
+        assert!(server.is_closed());
     }
 }
@@ -1,9 +1,22 @@
 use crate::{
+    advancement::AdvancementId,
+    block::{
+        banner::BannerPattern,
+        chest::ChestSlot,
+        furnace::FurnaceProgress,
+        painting::{PaintingImage, PaintingLimits},
+    },
+    crafting::RecipeId,
     data::Data,
+    entity::EntityId,
+    inventory::{Inventory, ItemStack},
+    item::map::{MapCanvas, MapId, MapPatch},
     physics::simulation::ServerState,
     player::PlayerId,
-    player::{PlayerInput, RenderDistance},
-    world::{Chunk, LightChunk},
+    player::{DeathCause, GameMode, PlayerInput, PlayerStats, RenderDistance},
+    time::WorldTime,
+    weather::Weather,
+    world::{BlockPos, ChunkPos, CompressedChunk, CompressedLightChunk},
 };
 use nalgebra::Vector3;
 use std::sync::Arc;
@@ -15,12 +28,112 @@ pub enum ToServer {
     SetRenderDistance(RenderDistance),
     /// Update the player's input
     UpdateInput(PlayerInput),
-    /// Break a block (player pos, yaw, pitch)
-    BreakBlock(Vector3<f64>, f64, f64),
-    /// Select a block
-    SelectBlock(Vector3<f64>, f64, f64),
-    /// Place a block
-    PlaceBlock(Vector3<f64>, f64, f64),
+    /// Start breaking a block (player pos, block). The block is broken by attacking it once it was broken for long enough.
+    StartBreaking(Vector3<f64>, BlockPos),
+    /// Stop breaking the block, because the button was released or the player looks away
+    StopBreaking,
+    /// Interact with the world: use, attack or pick a block or an entity, or use the held item by itself
+    Interact {
+        /// The position and the view of the player, checked like its movement
+        player_pos: Vector3<f64>,
+        yaw: f64,
+        pitch: f64,
+        target: InteractionTarget,
+        action: InteractionAction,
+        /// The hotbar slot of the item that the player interacts with, which becomes the selected slot
+        hand: usize,
+    },
+    /// Stop riding the current vehicle
+    DismountVehicle,
+    /// Move the items of an inventory slot to another slot, merging them if they are the same item (from, to)
+    MoveItems(usize, usize),
+    /// Move up to some items of an inventory slot to another slot, if it is empty or holds the same item (from, to, count)
+    MoveSomeItems(usize, usize, u32),
+    /// Move the stack of an inventory slot between the hotbar and the rest of the inventory
+    QuickMoveItems(usize),
+    /// Swap the contents of two inventory slots
+    SwapSlots(usize, usize),
+    /// Craft a recipe once
+    Craft(RecipeId),
+    /// Change the pattern of the banner at some position
+    SetBanner(BlockPos, BannerPattern),
+    /// Display an imported image on the painting at some position
+    SetPainting(BlockPos, PaintingImage),
+    /// Select the slot of the hotbar that holds the item used with right-click
+    SelectHotbarSlot(usize),
+    /// Acknowledge the chunks that were received
+    ChunksReceived(Vec<ChunkPos>),
+    /// Send some chunks again, because they were never received or they are out of sync
+    RequestChunks(Vec<ChunkPos>),
+    /// Send the canvas of a map that the player sees in an item frame
+    RequestMap(MapId),
+    /// Send a chat message to all the players
+    ChatMessage(String),
+    /// Move the stack of a slot to another slot of the player inventory or of the open chest, like `MoveItems`
+    MoveChestItems(ChestSlot, ChestSlot),
+    /// Close the open chest
+    CloseChest,
+    /// Move the stack of a slot to another slot of the player inventory or of the open furnace, like `MoveChestItems`
+    MoveFurnaceItems(ChestSlot, ChestSlot),
+    /// Close the open furnace
+    CloseFurnace,
+    /// Come back to life after dying, at the bed or at the world spawn
+    Respawn,
+}
+
+/// What a player interacts with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionTarget {
+    /// The pointed block and its pointed face
+    Block(BlockPos, usize),
+    Entity(EntityId),
+    /// Nothing: the held item is used by itself, for example to eat or to throw it
+    Air,
+}
+
+/// How a player interacts with its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionAction {
+    /// Right-click: place a block, open a block, ride a vehicle, eat...
+    Use,
+    /// Left-click: break a block
+    Attack,
+    /// Middle-click: select the block to place in creative mode
+    Pick,
+}
+
+impl ToServer {
+    /// Whether the message changes the world, which spectators can't do
+    pub fn changes_world(&self) -> bool {
+        matches!(
+            self,
+            ToServer::StartBreaking(..)
+                | ToServer::Interact {
+                    action: InteractionAction::Use | InteractionAction::Attack,
+                    ..
+                }
+                | ToServer::SetBanner(..)
+                | ToServer::SetPainting(..)
+                | ToServer::MoveChestItems(..)
+                | ToServer::MoveFurnaceItems(..)
+        )
+    }
+
+    /// Whether a dead player can send the message. Dead players can only respawn, chat without commands,
+    /// close the block they were using, and keep receiving the chunks and the maps.
+    pub fn is_allowed_while_dead(&self) -> bool {
+        match self {
+            ToServer::ChatMessage(message) => !message.trim_start().starts_with('/'),
+            ToServer::Respawn
+            | ToServer::CloseChest
+            | ToServer::CloseFurnace
+            | ToServer::SetRenderDistance(..)
+            | ToServer::ChunksReceived(..)
+            | ToServer::RequestChunks(..)
+            | ToServer::RequestMap(..) => true,
+            _ => false,
+        }
+    }
 }
 
 /// A message sent to the client by the server
@@ -28,11 +141,62 @@ pub enum ToServer {
 pub enum ToClient {
     /// Send the game data
     GameData(Data),
-    /// Send the chunk at some position
-    Chunk(Arc<Chunk>, Arc<LightChunk>),
+    /// Send the compressed chunk at some position
+    Chunk(Arc<CompressedChunk>, Arc<CompressedLightChunk>),
+    /// The content hashes of a few chunks that the player received, to check that its copies are in sync
+    ChunkHashes(Vec<(ChunkPos, u64)>),
     /// Update the whole of the physics simulation
     // TODO: only send part of the physics simulation
     UpdatePhysics(ServerState),
     /// Set the id of a player
     CurrentId(PlayerId),
+    /// Set the position the player's compass points to
+    CompassTarget(BlockPos),
+    /// Update the world time
+    TimeUpdate(WorldTime),
+    /// Update the weather
+    WeatherUpdate(Weather),
+    /// Send the whole inventory of the player
+    Inventory(Inventory),
+    /// Update a single slot of the inventory of the player
+    InventorySlotUpdate(usize, Option<ItemStack>),
+    /// Set the render distance the server sends chunks for, which is lower than the requested one if the client is too slow
+    RenderDistanceLimit(RenderDistance),
+    /// Show a message to the player
+    ChatMessage(String),
+    /// Update the health and hunger of the player
+    PlayerStats(PlayerStats),
+    /// Set how large the images of the paintings can be
+    PaintingLimits(PaintingLimits),
+    /// Set the game mode of the player
+    GameMode(GameMode),
+    /// Send the whole canvas of a map
+    MapCanvas(MapId, MapCanvas),
+    /// Update a region of the canvas of a map that the client already has
+    MapUpdate(MapId, MapPatch),
+    /// The player died, and stays where it died until it asks to respawn
+    Died(DeathCause),
+    /// The player respawned at its bed or at the world spawn
+    Respawn(BlockPos),
+    /// Show the contents of the chest that the player opened
+    OpenChest(BlockPos, Inventory),
+    /// Update a single slot of the open chest
+    ChestSlotUpdate(usize, Option<ItemStack>),
+    /// Close the open chest, because it was removed or the player is too far
+    CloseChest,
+    /// Show the contents and the progress of the furnace that the player opened
+    OpenFurnace(BlockPos, Inventory, FurnaceProgress),
+    /// Update a single slot of the open furnace
+    FurnaceSlotUpdate(usize, Option<ItemStack>),
+    /// Update the progress of the open furnace
+    FurnaceProgress(FurnaceProgress),
+    /// Close the open furnace, because it was removed or the player is too far
+    CloseFurnace,
+    /// The player earned an advancement
+    AdvancementEarned(AdvancementId),
+    /// The blocks that the players are breaking, with the stage of their cracks, below `BREAK_STAGES`
+    BreakStages(Vec<(BlockPos, u8)>),
+    /// How many chunks of the spawn area of the joining player are generated, out of how many (generated, total).
+    /// It is sent when it changes, until the whole area is generated.
+    SpawnAreaProgress(u32, u32),
 }
@@ -34,6 +34,9 @@ pub trait Server {
     fn receive_event(&mut self) -> ServerEvent;
     /// Send a message to a client. The message will be dropped if it can't be sent.
     fn send(&mut self, client: PlayerId, message: messages::ToClient);
+    /// Whether every client left and no other client can connect, like the integrated server of a singleplayer world
+    /// once its player quit. The server should then save the world and stop.
+    fn is_closed(&self) -> bool;
 }
 
 /// An abstraction over a network client.
@@ -42,6 +45,8 @@ pub trait Client {
     fn receive_event(&mut self) -> ClientEvent;
     /// Send a message to the server. The message will be dropped if it can't be sent.
     fn send(&mut self, message: messages::ToServer);
+    /// Leave the server, which forgets the player. No more events are received and the messages are dropped.
+    fn disconnect(&mut self);
 }
 
 /// Dummy client and server implementations for testing
@@ -60,6 +60,35 @@ impl AABB {
         }
     }
 
+    /// Return the fraction of `delta` at which the segment from `origin` to `origin + delta` enters the box,
+    /// or `None` if it doesn't intersect the box
+    pub fn intersect_segment(&self, origin: Vector3<f64>, delta: Vector3<f64>) -> Option<f64> {
+        let min = [self.pos.x, self.pos.y, self.pos.z];
+        let max = [
+            self.pos.x + self.size_x,
+            self.pos.y + self.size_y,
+            self.pos.z + self.size_z,
+        ];
+        let mut t_min: f64 = 0.0;
+        let mut t_max: f64 = 1.0;
+        for axis in 0..3 {
+            if delta[axis].abs() < 1e-12 {
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return None;
+                }
+            } else {
+                let t1 = (min[axis] - origin[axis]) / delta[axis];
+                let t2 = (max[axis] - origin[axis]) / delta[axis];
+                t_min = t_min.max(t1.min(t2));
+                t_max = t_max.min(t1.max(t2));
+                if t_min > t_max {
+                    return None;
+                }
+            }
+        }
+        Some(t_min)
+    }
+
     /// Return true if the box intersect some block
     pub fn intersect_world<BC: BlockContainer>(&self, world: &BC) -> bool {
         let min_x = self.pos.x.floor() as i64;
@@ -81,6 +110,28 @@ impl AABB {
         return false;
     }
 
+    /// Return true if the AABB box touches a climbable block. Climbable blocks are full, so the box only needs to be next to them.
+    pub fn touches_climbable<BC: BlockContainer>(&self, world: &BC) -> bool {
+        const REACH: f64 = 0.1;
+        let min_x = (self.pos.x - REACH).floor() as i64;
+        let max_x = (self.pos.x + self.size_x + REACH).ceil() as i64;
+        let min_y = self.pos.y.floor() as i64;
+        let max_y = (self.pos.y + self.size_y).ceil() as i64;
+        let min_z = (self.pos.z - REACH).floor() as i64;
+        let max_z = (self.pos.z + self.size_z + REACH).ceil() as i64;
+
+        for i in min_x..max_x {
+            for j in min_y..max_y {
+                for k in min_z..max_z {
+                    if world.is_block_climbable((i, j, k).into()) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
     /// Try to move the box in the world and stop the movement if it goes trough a block
     /// Return the actual deplacement
     pub fn move_check_collision<BC: BlockContainer>(&mut self, world: &BC, delta: Vector3<f64>) -> Vector3<f64> {
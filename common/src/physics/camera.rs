@@ -3,7 +3,12 @@
 //! A `Camera` defines how a player's entity reacts to that player's inputs.
 
 use crate::{
-    debug::send_debug_info, physics::player::PhysicsPlayer, player::PlayerInput,
+    debug::send_debug_info,
+    entity::VehicleKind,
+    physics::entity::PhysicsEntity,
+    physics::player::{PhysicsPlayer, PlayerPose},
+    physics::vehicle::get_seat_position,
+    player::PlayerInput,
 };
 use super::BlockContainer;
 use nalgebra::Vector3;
@@ -29,6 +34,14 @@ pub fn default_camera<BC: BlockContainer>(
             Vector3::zeros()
         }
     }
+    // Sneak when going down without flying
+    let pose = if !input.flying && input.key_move_down {
+        PlayerPose::Crouching
+    } else {
+        PlayerPose::Standing
+    };
+    player.set_pose(pose, world);
+
     // Compute the expected movement of the player, i.e. assuming there are no collisions.
     if input.flying || player.aabb.intersect_world(world) {
         const ACCELERATION: f64 = 50.0;
@@ -64,12 +77,21 @@ pub fn default_camera<BC: BlockContainer>(
         if input.key_move_down {
             expected_movement.y -= (seconds_delta * MAX_SPEED) as f64;
         }
-        player.aabb.move_check_collision(world, expected_movement);
+        expected_movement += player.knockback_velocity * seconds_delta;
+        // Spectators go through the blocks
+        if input.no_clip {
+            player.aabb.pos += expected_movement;
+        } else {
+            player.aabb.move_check_collision(world, expected_movement);
+        }
     } else {
         const JUMP_SPEED: f64 = 8.0;
         const GRAVITY_ACCELERATION: f64 = 25.0;
         const MAX_DOWN_SPEED: f64 = 30.0;
         const HORIZONTAL_SPEED: f64 = 7.0;
+        const CROUCHING_HORIZONTAL_SPEED: f64 = 2.0;
+        const SPRINTING_HORIZONTAL_SPEED: f64 = 10.0;
+        const CLIMBING_SPEED: f64 = 3.0;
         player.velocity.x = 0.0;
         player.velocity.z = 0.0;
         let mut horizontal_velocity = Vector3::zeros();
@@ -85,8 +107,24 @@ pub fn default_camera<BC: BlockContainer>(
         if input.key_move_right {
             horizontal_velocity += movement_direction(input.yaw_pitch.yaw, 270.0);
         }
-        let horizontal_velocity = normalize_or_zero(horizontal_velocity) * HORIZONTAL_SPEED;
-        if player.aabb.is_on_the_ground(world) {
+        let horizontal_speed = if player.pose == PlayerPose::Crouching {
+            CROUCHING_HORIZONTAL_SPEED
+        } else if input.sprinting {
+            SPRINTING_HORIZONTAL_SPEED
+        } else {
+            HORIZONTAL_SPEED
+        };
+        let horizontal_velocity = normalize_or_zero(horizontal_velocity) * horizontal_speed;
+        if player.aabb.touches_climbable(world) {
+            // Climbing cancels gravity, and the vertical keys move up and down
+            player.velocity.y = if input.key_move_up {
+                CLIMBING_SPEED
+            } else if input.key_move_down {
+                -CLIMBING_SPEED
+            } else {
+                0.0
+            };
+        } else if player.aabb.is_on_the_ground(world) {
             player.velocity.y = if input.key_move_up { JUMP_SPEED } else { 0.0 };
         } else {
             player.velocity.y -= GRAVITY_ACCELERATION * seconds_delta;
@@ -94,9 +132,15 @@ pub fn default_camera<BC: BlockContainer>(
                 player.velocity.y = -MAX_DOWN_SPEED;
             }
         };
-        let expected_movement = (player.velocity + horizontal_velocity) * seconds_delta;
+        let expected_movement = (player.velocity + horizontal_velocity + player.knockback_velocity) * seconds_delta;
         player.aabb.move_check_collision(world, expected_movement);
     }
+    // The knockback fades out quickly
+    const KNOCKBACK_DECAY_RATE: f64 = 6.0;
+    player.knockback_velocity *= (-KNOCKBACK_DECAY_RATE * seconds_delta).exp();
+    if player.knockback_velocity.norm() < 1e-3 {
+        player.knockback_velocity = Vector3::zeros();
+    }
     // TODO: add a noclip camera mode
     send_debug_info(
         "Physics",
@@ -113,6 +157,29 @@ pub fn default_camera<BC: BlockContainer>(
         format!("velocity: {:.2} {:.2} {:.2}", vx, vy, vz),
     );
 
+    rotate_camera(player, input);
+}
+
+/// The camera of a player riding a vehicle. The player sits on the seat of the vehicle, and turns with it.
+pub fn vehicle_camera<BC: BlockContainer>(
+    player: &mut PhysicsPlayer,
+    input: PlayerInput,
+    vehicle: &PhysicsEntity,
+    kind: VehicleKind,
+    vehicle_yaw_delta: f64,
+    world: &BC,
+) {
+    player.set_pose(PlayerPose::Standing, world);
+    let half_side = player.aabb.size_x / 2.0;
+    player.aabb.pos = get_seat_position(vehicle, kind) - Vector3::new(half_side, 0.0, half_side);
+    player.velocity = Vector3::zeros();
+    player.knockback_velocity = Vector3::zeros();
+    player.yaw_pitch.yaw += vehicle_yaw_delta;
+    rotate_camera(player, input);
+}
+
+/// Rotate the camera with the rotation keys
+fn rotate_camera(player: &mut PhysicsPlayer, input: PlayerInput) {
     // TODO: don't hardcode this
     let camera_speed: f64 = 0.2;
     if input.key_rotate_left {
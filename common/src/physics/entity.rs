@@ -0,0 +1,117 @@
+use crate::entity::{EntityId, EntityKind};
+use crate::physics::aabb::AABB;
+use super::raycast::raycast;
+use super::BlockContainer;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+const GRAVITY_ACCELERATION: f64 = 25.0;
+const MAX_DOWN_SPEED: f64 = 30.0;
+/// Initial vertical speed of a jumping entity, enough to climb a single block
+pub const ENTITY_JUMP_SPEED: f64 = 8.0;
+
+/// The physics representation of a non-player entity
+#[derive(Debug, Clone)]
+pub struct PhysicsEntity {
+    pub kind: EntityKind,
+    /// The aabb of the entity
+    pub aabb: AABB,
+    /// The current velocity of the entity. The horizontal part is controlled by the entity's AI.
+    pub velocity: Vector3<f64>,
+    /// The direction the entity is facing, in degrees
+    pub yaw: f64,
+}
+
+impl PhysicsEntity {
+    pub fn new(kind: EntityKind, pos: Vector3<f64>, size: (f64, f64, f64)) -> Self {
+        Self {
+            kind,
+            aabb: AABB::new(pos, size),
+            velocity: Vector3::zeros(),
+            yaw: 0.0,
+        }
+    }
+
+    /// Get the position of the center of the bottom of the entity
+    pub fn get_feet_position(&self) -> Vector3<f64> {
+        self.aabb.pos + Vector3::new(self.aabb.size_x / 2.0, 0.0, self.aabb.size_z / 2.0)
+    }
+
+    /// Move the entity according to its velocity, applying gravity and collisions
+    pub fn step<BC: BlockContainer>(&mut self, seconds_delta: f64, world: &BC) {
+        if let EntityKind::Projectile = self.kind {
+            self.step_projectile(seconds_delta, world);
+            return;
+        }
+        if self.aabb.is_on_the_ground(world) {
+            self.velocity.y = self.velocity.y.max(0.0);
+        } else {
+            self.velocity.y -= GRAVITY_ACCELERATION * seconds_delta;
+            if self.velocity.y < -MAX_DOWN_SPEED {
+                self.velocity.y = -MAX_DOWN_SPEED;
+            }
+        }
+        let actual_movement = self.aabb.move_check_collision(world, self.velocity * seconds_delta);
+        // Stop falling or jumping if we hit something vertically
+        if (actual_movement.y - self.velocity.y * seconds_delta).abs() > 1e-9 {
+            self.velocity.y = 0.0;
+        }
+        // Vehicles stop when they hit a wall
+        if let EntityKind::Vehicle(_) = self.kind {
+            if (actual_movement.x - self.velocity.x * seconds_delta).abs() > 1e-9 {
+                self.velocity.x = 0.0;
+            }
+            if (actual_movement.z - self.velocity.z * seconds_delta).abs() > 1e-9 {
+                self.velocity.z = 0.0;
+            }
+        }
+    }
+
+    /// Projectiles follow a ballistic trajectory and stop in the first block they hit
+    fn step_projectile<BC: BlockContainer>(&mut self, seconds_delta: f64, world: &BC) {
+        if self.is_stuck() {
+            return;
+        }
+        let delta = self.velocity * seconds_delta;
+        let center = self.get_center();
+        match raycast(center, delta, delta.norm(), world) {
+            Some(hit) => {
+                self.aabb.pos += hit.pos - center;
+                self.velocity = Vector3::zeros();
+            }
+            None => {
+                self.aabb.pos += delta;
+                self.velocity.y -= GRAVITY_ACCELERATION * seconds_delta;
+                if self.velocity.y < -MAX_DOWN_SPEED {
+                    self.velocity.y = -MAX_DOWN_SPEED;
+                }
+                self.yaw = (-self.velocity.x).atan2(-self.velocity.z).to_degrees();
+            }
+        }
+    }
+
+    /// Get the position of the center of the entity
+    pub fn get_center(&self) -> Vector3<f64> {
+        self.aabb.pos + Vector3::new(self.aabb.size_x, self.aabb.size_y, self.aabb.size_z) / 2.0
+    }
+
+    /// Whether the entity is a projectile that has hit a block
+    pub fn is_stuck(&self) -> bool {
+        self.kind == EntityKind::Projectile && self.velocity == Vector3::zeros()
+    }
+}
+
+/// Find the closest mob or vehicle that the segment from `origin` to `origin + dir * max_dist` hits
+pub fn find_pointed_entity(
+    origin: Vector3<f64>,
+    dir: Vector3<f64>,
+    max_dist: f64,
+    entities: &HashMap<EntityId, PhysicsEntity>,
+) -> Option<EntityId> {
+    entities
+        .iter()
+        .filter(|(_, entity)| matches!(entity.kind, EntityKind::Mob(_) | EntityKind::Vehicle(_)))
+        .filter_map(|(id, entity)| entity.aabb.intersect_segment(origin, dir * max_dist).map(|t| (*id, t)))
+        .min_by(|(_, t1), (_, t2)| t1.partial_cmp(t2).unwrap())
+        .map(|(id, _)| id)
+}
@@ -2,11 +2,15 @@ use crate::world::BlockPos;
 
 pub mod aabb;
 pub mod camera;
+pub mod entity;
 pub mod player;
+pub mod raycast;
 pub mod simulation;
+pub mod vehicle;
 
 /// A "block container", i.e. either the client's World or the server's World.
 /// This trait allows the physics simulation to work transparently with both World structs.
 pub trait BlockContainer {
     fn is_block_full(&self, pos: BlockPos) -> bool;
+    fn is_block_climbable(&self, pos: BlockPos) -> bool;
 }
@@ -1,11 +1,53 @@
+use crate::entity::EntityId;
 use crate::physics::aabb::AABB;
 use crate::world::BlockPos;
+use super::raycast::raycast;
 use super::BlockContainer;
 use nalgebra::Vector3;
 
-const PLAYER_SIDE: f64 = 0.8;
-const PLAYER_HEIGHT: f64 = 1.8;
-const CAMERA_OFFSET: [f64; 3] = [0.4, 1.6, 0.4];
+/// The dimensions of the player in some pose
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerHitbox {
+    /// Size of the hitbox along the x and z axes
+    pub side: f64,
+    /// Size of the hitbox along the y axis
+    pub height: f64,
+    /// Height of the camera above the feet
+    pub eye_height: f64,
+}
+
+/// The pose of a player. It decides the size of the hitbox and the height of the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PlayerPose {
+    #[default]
+    Standing,
+    Crouching,
+    // TODO: use it once water can be entered
+    Swimming,
+}
+
+impl PlayerPose {
+    /// Get the dimensions of the player in this pose
+    pub fn hitbox(self) -> PlayerHitbox {
+        match self {
+            PlayerPose::Standing => PlayerHitbox {
+                side: 0.8,
+                height: 1.8,
+                eye_height: 1.6,
+            },
+            PlayerPose::Crouching => PlayerHitbox {
+                side: 0.8,
+                height: 1.5,
+                eye_height: 1.3,
+            },
+            PlayerPose::Swimming => PlayerHitbox {
+                side: 0.8,
+                height: 0.8,
+                eye_height: 0.6,
+            },
+        }
+    }
+}
 
 /// A helper struct to keep track of the yaw and pitch of a player
 #[derive(Debug, Clone, Copy)]
@@ -31,12 +73,39 @@ pub struct PhysicsPlayer {
     /// The current velocity of the player
     pub velocity: Vector3<f64>,
     pub yaw_pitch: YawPitch,
+    pub pose: PlayerPose,
+    /// Velocity given by impulses, for example knockback. It is added to the movement of the player and fades out.
+    pub knockback_velocity: Vector3<f64>,
+    /// The vehicle the player is riding, if any
+    pub vehicle: Option<EntityId>,
 }
 
 impl PhysicsPlayer {
-    /// Get the position of the camera
+    /// Get the position of the camera, derived from the eye height of the current pose
     pub fn get_camera_position(&self) -> Vector3<f64> {
-        self.aabb.pos + Vector3::from(CAMERA_OFFSET)
+        let hitbox = self.pose.hitbox();
+        self.aabb.pos + Vector3::new(hitbox.side / 2.0, hitbox.eye_height, hitbox.side / 2.0)
+    }
+
+    /// Try to change the pose of the player, resizing the hitbox around the feet.
+    /// The pose is not changed if the new hitbox doesn't fit. Return whether the pose was changed.
+    pub fn set_pose<BC: BlockContainer>(&mut self, pose: PlayerPose, world: &BC) -> bool {
+        if pose == self.pose {
+            return true;
+        }
+        let old = self.pose.hitbox();
+        let new = pose.hitbox();
+        let offset = (old.side - new.side) / 2.0;
+        let new_aabb = AABB::new(
+            self.aabb.pos + Vector3::new(offset, 0.0, offset),
+            (new.side, new.height, new.side),
+        );
+        if new_aabb.intersect_world(world) && !self.aabb.intersect_world(world) {
+            return false;
+        }
+        self.aabb = new_aabb;
+        self.pose = pose;
+        true
     }
 
     /// Ray trace to find the pointed block. Return the position of the block and the face (x/-x/y/-y/z/-z)
@@ -44,74 +113,26 @@ impl PhysicsPlayer {
     pub fn get_pointed_at<BC: BlockContainer>(
         &self,
         dir: Vector3<f64>,
-        mut max_dist: f64,
+        max_dist: f64,
         world: &BC,
     ) -> Option<(BlockPos, usize)> {
-        let dir = dir.normalize();
-        let mut pos = self.get_camera_position();
-        // Check current block first
-        let was_inside = world.is_block_full(BlockPos::from(pos));
-        let dirs = [
-            Vector3::new(-1.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(0.0, -1.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(0.0, 0.0, -1.0),
-            Vector3::new(0.0, 0.0, 1.0),
-        ];
-        loop {
-            let targets = [
-                pos.x.floor(),
-                pos.x.ceil(),
-                pos.y.floor(),
-                pos.y.ceil(),
-                pos.z.floor(),
-                pos.z.ceil(),
-            ];
-
-            let mut curr_min = 1e9;
-            let mut face = 0;
-
-            for i in 0..6 {
-                let effective_movement = dir.dot(&dirs[i]);
-                if effective_movement > 1e-6 {
-                    let dir_offset = (targets[i].abs() - pos.dot(&dirs[i]).abs()).abs();
-                    let dist = dir_offset / effective_movement;
-                    if curr_min > dist {
-                        curr_min = dist;
-                        face = i;
-                    }
-                }
-            }
-
-            if was_inside {
-                return Some((BlockPos::from(pos), face ^ 1));
-            }
-
-            if curr_min > max_dist {
-                return None;
-            } else {
-                curr_min += 1e-5;
-                max_dist -= curr_min;
-                pos += curr_min * dir;
-                let block_pos = BlockPos::from(pos);
-                if world.is_block_full(block_pos) {
-                    return Some((block_pos, face));
-                }
-            }
-        }
+        raycast(self.get_camera_position(), dir, max_dist, world).map(|hit| (hit.block, hit.face))
     }
 }
 
 impl Default for PhysicsPlayer {
     fn default() -> Self {
+        let standing = PlayerPose::Standing.hitbox();
         Self {
             aabb: AABB::new(
                 Vector3::new(1.46, 52.6, 1.85),
-                (PLAYER_SIDE, PLAYER_HEIGHT, PLAYER_SIDE),
+                (standing.side, standing.height, standing.side),
             ),
             velocity: Vector3::zeros(),
             yaw_pitch: Default::default(),
+            pose: PlayerPose::Standing,
+            knockback_velocity: Vector3::zeros(),
+            vehicle: None,
         }
     }
 }
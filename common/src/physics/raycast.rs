@@ -0,0 +1,88 @@
+use super::BlockContainer;
+use crate::world::BlockPos;
+use nalgebra::Vector3;
+
+/// A block hit by a ray
+#[derive(Debug, Clone, Copy)]
+pub struct RaycastHit {
+    /// The block that was hit
+    pub block: BlockPos,
+    /// The face that was hit (x/-x/y/-y/z/-z)
+    pub face: usize,
+    /// The position where the ray entered the block
+    pub pos: Vector3<f64>,
+}
+
+/// Ray trace from `origin` in direction `dir` to find the first full block at most `max_dist` away
+// TODO: use block registry
+pub fn raycast<BC: BlockContainer>(
+    origin: Vector3<f64>,
+    dir: Vector3<f64>,
+    mut max_dist: f64,
+    world: &BC,
+) -> Option<RaycastHit> {
+    if dir.norm() < 1e-9 {
+        return None;
+    }
+    let dir = dir.normalize();
+    let mut pos = origin;
+    // Check current block first
+    let was_inside = world.is_block_full(BlockPos::from(pos));
+    let dirs = [
+        Vector3::new(-1.0, 0.0, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(0.0, -1.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(0.0, 0.0, -1.0),
+        Vector3::new(0.0, 0.0, 1.0),
+    ];
+    loop {
+        let targets = [
+            pos.x.floor(),
+            pos.x.ceil(),
+            pos.y.floor(),
+            pos.y.ceil(),
+            pos.z.floor(),
+            pos.z.ceil(),
+        ];
+
+        let mut curr_min = 1e9;
+        let mut face = 0;
+
+        for i in 0..6 {
+            let effective_movement = dir.dot(&dirs[i]);
+            if effective_movement > 1e-6 {
+                let dir_offset = (targets[i].abs() - pos.dot(&dirs[i]).abs()).abs();
+                let dist = dir_offset / effective_movement;
+                if curr_min > dist {
+                    curr_min = dist;
+                    face = i;
+                }
+            }
+        }
+
+        if was_inside {
+            return Some(RaycastHit {
+                block: BlockPos::from(pos),
+                face: face ^ 1,
+                pos,
+            });
+        }
+
+        if curr_min > max_dist {
+            return None;
+        } else {
+            curr_min += 1e-5;
+            max_dist -= curr_min;
+            pos += curr_min * dir;
+            let block_pos = BlockPos::from(pos);
+            if world.is_block_full(block_pos) {
+                return Some(RaycastHit {
+                    block: block_pos,
+                    face,
+                    pos,
+                });
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{
-    physics::camera::default_camera,
+    entity::{EntityId, EntityKind},
+    physics::camera::{default_camera, vehicle_camera},
+    physics::entity::PhysicsEntity,
     physics::player::PhysicsPlayer,
+    physics::vehicle::drive_vehicle,
     physics::BlockContainer,
     player::{PlayerId, PlayerInput},
 };
@@ -20,20 +23,52 @@ pub struct Input {
 #[derive(Debug, Clone, Default)]
 pub struct PhysicsState {
     pub players: HashMap<PlayerId, PhysicsPlayer>,
+    pub entities: HashMap<EntityId, PhysicsEntity>,
 }
 
 impl PhysicsState {
     /// Step the full physics simulation.
-    /// For now, it just moves all connected players.
+    /// It moves all connected players according to their inputs, and all the entities according to their velocities.
+    /// The players that ride a vehicle drive it instead, and then follow it.
     pub fn step_simulation<BC: BlockContainer>(&mut self, input: &Input, dt: Duration, world: &BC) {
         let seconds_delta = dt.as_secs_f64();
+        let mut vehicle_inputs = HashMap::new();
         for (&id, input) in input.player_inputs.iter() {
             let player = self.players.entry(id).or_insert(Default::default());
-            default_camera(player, *input, seconds_delta, world);
+            // Dismount the vehicle if it doesn't exist anymore
+            let entities = &self.entities;
+            let vehicle = player.vehicle.and_then(|vehicle_id| entities.get(&vehicle_id));
+            match vehicle.map(|vehicle| vehicle.kind) {
+                Some(EntityKind::Vehicle(_)) => {
+                    vehicle_inputs.insert(player.vehicle.unwrap(), *input);
+                }
+                _ => {
+                    player.vehicle = None;
+                    default_camera(player, *input, seconds_delta, world);
+                }
+            }
         }
         // Remove players that don't exist anymore
         self.players
             .retain(|id, _| input.player_inputs.contains_key(id));
+        let mut vehicle_yaw_deltas = HashMap::new();
+        for (id, entity) in self.entities.iter_mut() {
+            if let EntityKind::Vehicle(kind) = entity.kind {
+                let yaw_before = entity.yaw;
+                drive_vehicle(entity, kind, vehicle_inputs.get(id).copied(), seconds_delta, world);
+                vehicle_yaw_deltas.insert(*id, entity.yaw - yaw_before);
+            }
+            entity.step(seconds_delta, world);
+        }
+        // Move the riders with their vehicles
+        for (id, player) in self.players.iter_mut() {
+            if let Some(vehicle_id) = player.vehicle {
+                let vehicle = &self.entities[&vehicle_id];
+                if let EntityKind::Vehicle(kind) = vehicle.kind {
+                    vehicle_camera(player, input.player_inputs[id], vehicle, kind, vehicle_yaw_deltas[&vehicle_id], world);
+                }
+            }
+        }
     }
 }
 
@@ -45,6 +80,35 @@ pub struct ServerState {
     pub input: Input,
 }
 
+/// The id of something that moves in the physics simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PhysicsBodyId {
+    Player(PlayerId),
+    Entity(EntityId),
+}
+
+impl From<PlayerId> for PhysicsBodyId {
+    fn from(id: PlayerId) -> Self {
+        PhysicsBodyId::Player(id)
+    }
+}
+
+impl From<EntityId> for PhysicsBodyId {
+    fn from(id: EntityId) -> Self {
+        PhysicsBodyId::Entity(id)
+    }
+}
+
+/// Duration of a single physics step, on both the client and the server.
+/// Stepping by a fixed duration makes the simulation independent of the frame rate.
+pub const PHYSICS_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Maximum number of physics steps in a single update. If the simulation falls further behind, the extra time is skipped.
+const MAX_STEPS_PER_UPDATE: u32 = 30;
+/// Time for the camera to catch up with half of a misprediction corrected by a server update
+const CORRECTION_HALF_LIFE: Duration = Duration::from_millis(50);
+/// Mispredictions further than this distance, like teleportations, move the camera instantly
+const MAX_BLENDED_CORRECTION: f64 = 2.0;
+
 /// The client's physics simulation
 pub struct ClientPhysicsSimulation {
     /// Previous client inputs
@@ -53,6 +117,16 @@ pub struct ClientPhysicsSimulation {
     last_server_state: ServerState,
     /// Current simulation state
     current_state: PhysicsState,
+    /// Simulation state one step before the current state, used for interpolation
+    previous_state: PhysicsState,
+    /// Time of the current simulation state
+    current_time: Instant,
+    /// How far the last update time is between the previous and the current states, in [0; 1]
+    interpolation_alpha: f64,
+    /// Offset of the displayed camera from the predicted one, left by the corrections of the server and shrinking over time
+    camera_correction: Vector3<f64>,
+    /// Time of the last update
+    last_update_time: Instant,
     /// Dirty flag: whether the physics need to be computed again starting from the last server state.
     needs_recomputing: bool,
     /// Id of the current player
@@ -62,10 +136,17 @@ pub struct ClientPhysicsSimulation {
 impl ClientPhysicsSimulation {
     /// Create a new simulation from some `ServerState` and the client's id
     pub fn new(server_state: ServerState, player_id: PlayerId) -> Self {
+        let mut physics_state = server_state.physics_state.clone();
+        physics_state.players.entry(player_id).or_default();
         Self {
             client_inputs: Vec::new(),
-            last_server_state: server_state.clone(),
-            current_state: server_state.physics_state,
+            current_time: server_state.server_time,
+            last_update_time: server_state.server_time,
+            last_server_state: server_state,
+            previous_state: physics_state.clone(),
+            current_state: physics_state,
+            interpolation_alpha: 0.0,
+            camera_correction: Vector3::zeros(),
             needs_recomputing: false,
             player_id,
         }
@@ -92,60 +173,130 @@ impl ClientPhysicsSimulation {
             .get_camera_position()
     }
 
+    /// Get the camera position of the client, interpolated between the last two physics steps
+    /// and blended with the position predicted before the last server update
+    pub fn get_interpolated_camera_position(&self) -> Vector3<f64> {
+        self.get_predicted_camera_position() + self.camera_correction
+    }
+
+    /// Get the camera position of the client, interpolated between the last two physics steps
+    fn get_predicted_camera_position(&self) -> Vector3<f64> {
+        let current = self.get_camera_position();
+        match self.previous_state.players.get(&self.player_id) {
+            Some(previous) => lerp(previous.get_camera_position(), current, self.interpolation_alpha),
+            None => current,
+        }
+    }
+
     /// Get the client player
     pub fn get_player(&self) -> &PhysicsPlayer {
         self.current_state.players.get(&self.player_id).unwrap()
     }
 
+    /// Get the players other than the client, with the position of their aabb interpolated between the last two physics steps
+    pub fn get_other_players(&self) -> impl Iterator<Item = (&PhysicsPlayer, Vector3<f64>)> + '_ {
+        self.current_state
+            .players
+            .iter()
+            .filter(move |(id, _)| **id != self.player_id)
+            .map(move |(id, player)| {
+                let position = match self.previous_state.players.get(id) {
+                    Some(previous) => lerp(previous.aabb.pos, player.aabb.pos, self.interpolation_alpha),
+                    None => player.aabb.pos,
+                };
+                (player, position)
+            })
+    }
+
+    /// Get the entities of the current simulation state
+    pub fn get_entities(&self) -> &HashMap<EntityId, PhysicsEntity> {
+        &self.current_state.entities
+    }
+
+    /// Get the position of the aabb of an entity, interpolated between the last two physics steps
+    pub fn get_interpolated_entity_position(&self, entity_id: EntityId) -> Option<Vector3<f64>> {
+        let current = self.current_state.entities.get(&entity_id)?.aabb.pos;
+        Some(match self.previous_state.entities.get(&entity_id) {
+            Some(previous) => lerp(previous.aabb.pos, current, self.interpolation_alpha),
+            None => current,
+        })
+    }
+
+    /// Get how far the last update time is between the previous and the current physics steps, in [0; 1]
+    pub fn get_interpolation_alpha(&self) -> f64 {
+        self.interpolation_alpha
+    }
+
     /// Step the simulation according to the current input and time
     pub fn step_simulation<BC: BlockContainer>(&mut self, input: PlayerInput, time: Instant, world: &BC) {
+        // Store input for future processing
+        self.client_inputs.push((time, input));
+
+        // The camera catches up with the corrected predictions
+        let elapsed = time.saturating_duration_since(self.last_update_time);
+        self.last_update_time = time;
+        self.camera_correction *= 0.5f64.powf(elapsed.as_secs_f64() / CORRECTION_HALF_LIFE.as_secs_f64());
+        let displayed_camera = self.get_interpolated_camera_position();
+
         // Recompute simulation if necessary
+        let recomputed = self.needs_recomputing;
         if self.needs_recomputing {
             self.needs_recomputing = false;
             self.current_state = self.last_server_state.physics_state.clone();
+            self.current_state.players.entry(self.player_id).or_default();
+            self.previous_state = self.current_state.clone();
+            self.current_time = self.last_server_state.server_time;
+        }
 
-            let mut previous_time = self.last_server_state.server_time;
-            for &(time, player_input) in self.client_inputs.iter() {
-                // First, we have to apply the current client input to the server's input
+        // Step local simulation
+        let mut steps = 0;
+        while self.current_time + PHYSICS_TIMESTEP <= time {
+            if steps == MAX_STEPS_PER_UPDATE {
+                self.current_time = time;
+                break;
+            }
+            // The input of a step is the last input that was given before the step
+            let current_time = self.current_time;
+            if let Some(&(_, player_input)) = self.client_inputs.iter().rev().find(|(t, _)| *t <= current_time) {
                 self.last_server_state
                     .input
                     .player_inputs
                     .insert(self.player_id, player_input);
-                // Only then can we step the simulation
-                self.current_state.step_simulation(
-                    &self.last_server_state.input,
-                    time - previous_time,
-                    world,
-                );
-                previous_time = time;
             }
+            self.previous_state = self.current_state.clone();
+            self.current_state.step_simulation(
+                &self.last_server_state.input,
+                PHYSICS_TIMESTEP,
+                world,
+            );
+            self.current_time += PHYSICS_TIMESTEP;
+            steps += 1;
         }
+        self.interpolation_alpha = ((time - self.current_time).as_secs_f64() / PHYSICS_TIMESTEP.as_secs_f64()).min(1.0);
 
-        let previous_instant = match self.client_inputs.last() {
-            Some((time, _)) => *time,
-            None => self.last_server_state.server_time,
-        };
-
-        // Store input for future processing
-        self.client_inputs.push((time, input));
-        self.last_server_state
-            .input
-            .player_inputs
-            .insert(self.player_id, input);
-
-        // Step local simulation
-        self.current_state.step_simulation(
-            &self.last_server_state.input,
-            time - previous_instant,
-            world,
-        );
+        // Blend the small mispredictions instead of moving the camera suddenly
+        if recomputed {
+            let correction = displayed_camera - self.get_predicted_camera_position();
+            self.camera_correction = if correction.norm() <= MAX_BLENDED_CORRECTION {
+                correction
+            } else {
+                Vector3::zeros()
+            };
+        }
     }
 }
 
+/// Linear interpolation between `a` and `b`
+fn lerp(a: Vector3<f64>, b: Vector3<f64>, alpha: f64) -> Vector3<f64> {
+    a + (b - a) * alpha
+}
+
 /// The server's physics simulation
 pub struct ServerPhysicsSimulation {
     /// The current state of the simulation
     server_state: ServerState,
+    /// The id of the next spawned entity
+    next_entity_id: u32,
 }
 
 impl ServerPhysicsSimulation {
@@ -157,6 +308,7 @@ impl ServerPhysicsSimulation {
                 server_time: Instant::now(),
                 input: Default::default(),
             },
+            next_entity_id: 0,
         }
     }
 
@@ -168,23 +320,143 @@ impl ServerPhysicsSimulation {
             .insert(player_id, input);
     }
 
+    /// Get a mutable reference to a player of the simulation
+    pub fn get_player_mut(&mut self, player_id: PlayerId) -> Option<&mut PhysicsPlayer> {
+        self.server_state.physics_state.players.get_mut(&player_id)
+    }
+
+    /// Move a player to some position and stop it, adding it to the simulation if it wasn't simulated yet
+    pub fn teleport_player(&mut self, player_id: PlayerId, pos: Vector3<f64>) {
+        let player = self.server_state.physics_state.players.entry(player_id).or_default();
+        player.aabb.pos = pos;
+        player.velocity = Vector3::zeros();
+        player.knockback_velocity = Vector3::zeros();
+        player.vehicle = None;
+    }
+
     /// Remove a player from the simulation
     pub fn remove(&mut self, player_id: PlayerId) {
         self.server_state.input.player_inputs.remove(&player_id);
     }
 
-    /// Step the simulation according to the current input and time
-    pub fn step_simulation<BC: BlockContainer>(&mut self, time: Instant, world: &BC) {
-        self.server_state.physics_state.step_simulation(
-            &self.server_state.input,
-            time - self.server_state.server_time,
-            world,
-        );
-        self.server_state.server_time = time;
+    /// Step the simulation by fixed steps until it reaches `time`. Return the number of steps.
+    pub fn step_simulation<BC: BlockContainer>(&mut self, time: Instant, world: &BC) -> u32 {
+        let mut steps = 0;
+        while self.server_state.server_time + PHYSICS_TIMESTEP <= time {
+            if steps == MAX_STEPS_PER_UPDATE {
+                // Too far behind, skip the remaining time
+                self.server_state.server_time = time;
+                break;
+            }
+            self.server_state.physics_state.step_simulation(
+                &self.server_state.input,
+                PHYSICS_TIMESTEP,
+                world,
+            );
+            self.server_state.server_time += PHYSICS_TIMESTEP;
+            steps += 1;
+        }
+        steps
     }
 
     /// Get a reference to the current state of the simulation
     pub fn get_state(&self) -> &ServerState {
         &self.server_state
     }
+
+    /// Add a new entity to the simulation and return its id
+    pub fn spawn_entity(&mut self, entity: PhysicsEntity) -> EntityId {
+        let entity_id = EntityId(self.next_entity_id);
+        self.next_entity_id += 1;
+        self.server_state.physics_state.entities.insert(entity_id, entity);
+        entity_id
+    }
+
+    /// Remove an entity from the simulation
+    pub fn remove_entity(&mut self, entity_id: EntityId) {
+        self.server_state.physics_state.entities.remove(&entity_id);
+    }
+
+    /// Push a player or an entity. The impulse is the change of velocity, as every body has the same mass.
+    /// It is integrated in the next physics steps, and is therefore part of the next state sent to the clients.
+    pub fn apply_impulse(&mut self, id: impl Into<PhysicsBodyId>, impulse: Vector3<f64>) {
+        let physics_state = &mut self.server_state.physics_state;
+        match id.into() {
+            PhysicsBodyId::Player(player_id) => {
+                if let Some(player) = physics_state.players.get_mut(&player_id) {
+                    player.knockback_velocity += impulse;
+                }
+            }
+            PhysicsBodyId::Entity(entity_id) => {
+                if let Some(entity) = physics_state.entities.get_mut(&entity_id) {
+                    entity.velocity += impulse;
+                }
+            }
+        }
+    }
+
+    /// Get a mutable reference to an entity of the simulation
+    pub fn get_entity_mut(&mut self, entity_id: EntityId) -> Option<&mut PhysicsEntity> {
+        self.server_state.physics_state.entities.get_mut(&entity_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BlockPos;
+
+    /// A world without any block
+    struct EmptyWorld;
+
+    impl BlockContainer for EmptyWorld {
+        fn is_block_full(&self, _pos: BlockPos) -> bool {
+            false
+        }
+
+        fn is_block_climbable(&self, _pos: BlockPos) -> bool {
+            false
+        }
+    }
+
+    fn server_state(player_id: PlayerId, pos: Vector3<f64>, server_time: Instant) -> ServerState {
+        let mut physics_state = PhysicsState::default();
+        physics_state.players.entry(player_id).or_default().aabb.pos = pos;
+        let mut input = Input::default();
+        input.player_inputs.insert(player_id, PlayerInput::default());
+        ServerState {
+            physics_state,
+            server_time,
+            input,
+        }
+    }
+
+    #[test]
+    fn mispredictions_are_blended() {
+        let player_id = PlayerId(0);
+        let start = Instant::now();
+        let pos = Vector3::new(0.0, 50.0, 0.0);
+        let mut simulation = ClientPhysicsSimulation::new(server_state(player_id, pos, start), player_id);
+        // The flying player doesn't move without input
+        let input = PlayerInput::default();
+        simulation.step_simulation(input, start + Duration::from_millis(100), &EmptyWorld);
+        let displayed = simulation.get_interpolated_camera_position();
+
+        // A small correction of the server moves the camera progressively
+        let corrected_pos = pos + Vector3::new(0.5, 0.0, 0.0);
+        simulation.receive_server_update(server_state(player_id, corrected_pos, start + Duration::from_millis(100)));
+        simulation.step_simulation(input, start + Duration::from_millis(110), &EmptyWorld);
+        assert!((simulation.get_interpolated_camera_position() - displayed).norm() < 1e-6);
+        simulation.step_simulation(input, start + Duration::from_millis(160), &EmptyWorld);
+        let halfway = (simulation.get_interpolated_camera_position() - displayed).x;
+        assert!(0.2 < halfway && halfway < 0.3);
+        simulation.step_simulation(input, start + Duration::from_millis(1000), &EmptyWorld);
+        assert!((simulation.get_interpolated_camera_position() - simulation.get_camera_position()).norm() < 1e-3);
+
+        // Teleportations move the camera instantly
+        let teleported_pos = pos + Vector3::new(20.0, 0.0, 0.0);
+        simulation.receive_server_update(server_state(player_id, teleported_pos, start + Duration::from_millis(1000)));
+        simulation.step_simulation(input, start + Duration::from_millis(1010), &EmptyWorld);
+        assert_eq!(simulation.get_interpolated_camera_position(), simulation.get_camera_position());
+    }
 }
@@ -0,0 +1,92 @@
+//! Vehicles are entities that players can ride. While a player is riding, its input drives the vehicle.
+use crate::{
+    entity::VehicleKind,
+    physics::entity::PhysicsEntity,
+    player::PlayerInput,
+};
+use super::BlockContainer;
+use nalgebra::Vector3;
+
+/// Maximum distance between the camera of a player and a vehicle it mounts
+pub const MOUNT_RANGE: f64 = 5.0;
+
+/// How a vehicle reacts to the input of its rider
+struct VehicleHandling {
+    /// Acceleration when the rider moves forward or backward, in blocks per second squared
+    acceleration: f64,
+    /// Maximum forward speed, in blocks per second
+    max_speed: f64,
+    /// Maximum backward speed, in blocks per second
+    max_backward_speed: f64,
+    /// How fast the vehicle slows down by itself, in 1/s
+    drag: f64,
+    /// How fast the vehicle turns when the rider moves left or right, in degrees per second
+    turn_speed: f64,
+}
+
+impl VehicleHandling {
+    fn of(kind: VehicleKind) -> Self {
+        match kind {
+            VehicleKind::Boat => Self {
+                acceleration: 8.0,
+                max_speed: 8.0,
+                max_backward_speed: 3.0,
+                drag: 1.0,
+                turn_speed: 120.0,
+            },
+            VehicleKind::Minecart => Self {
+                acceleration: 6.0,
+                max_speed: 12.0,
+                max_backward_speed: 12.0,
+                drag: 0.2,
+                turn_speed: 0.0,
+            },
+        }
+    }
+}
+
+/// Update the velocity and the direction of a vehicle according to the input of its rider, if it has one.
+/// The vehicle is then moved by the usual entity step, which applies gravity and collisions.
+pub fn drive_vehicle<BC: BlockContainer>(
+    entity: &mut PhysicsEntity,
+    kind: VehicleKind,
+    input: Option<PlayerInput>,
+    seconds_delta: f64,
+    world: &BC,
+) {
+    let handling = VehicleHandling::of(kind);
+    let input = input.unwrap_or_default();
+    let forward = input.key_move_forward as i32 - input.key_move_backward as i32;
+    let turn = input.key_move_left as i32 - input.key_move_right as i32;
+
+    match kind {
+        VehicleKind::Boat => entity.yaw += turn as f64 * handling.turn_speed * seconds_delta,
+        // TODO: follow rails once they exist
+        VehicleKind::Minecart => entity.yaw = (entity.yaw / 90.0).round() * 90.0,
+    }
+    if entity.yaw < -180.0 {
+        entity.yaw += 360.0;
+    }
+    if entity.yaw > 180.0 {
+        entity.yaw -= 360.0;
+    }
+
+    // Vehicles only move in the direction they are facing, and can only accelerate on the ground
+    let direction = Vector3::new(-entity.yaw.to_radians().sin(), 0.0, -entity.yaw.to_radians().cos());
+    let mut speed = entity.velocity.dot(&direction);
+    if entity.aabb.is_on_the_ground(world) {
+        speed += forward as f64 * handling.acceleration * seconds_delta;
+    }
+    speed *= (-handling.drag * seconds_delta).exp();
+    if speed.abs() < 1e-3 {
+        speed = 0.0;
+    }
+    let speed = speed.clamp(-handling.max_backward_speed, handling.max_speed);
+    entity.velocity.x = direction.x * speed;
+    entity.velocity.z = direction.z * speed;
+}
+
+/// Get the position of the feet of the rider of a vehicle
+pub fn get_seat_position(entity: &PhysicsEntity, kind: VehicleKind) -> Vector3<f64> {
+    entity.get_feet_position() + Vector3::new(0.0, kind.seat_height(), 0.0)
+}
@@ -1,5 +1,5 @@
 use crate::world::ChunkPos;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use crate::physics::player::YawPitch;
 
 /// The input of a player
@@ -15,6 +15,9 @@ pub struct PlayerInput {
     pub key_rotate_right: bool,
     pub yaw_pitch: YawPitch,
     pub flying: bool,
+    pub sprinting: bool,
+    /// Whether the player goes through the blocks
+    pub no_clip: bool,
 }
 
 impl Default for PlayerInput {
@@ -30,14 +33,136 @@ impl Default for PlayerInput {
             key_rotate_right: false,
             yaw_pitch: Default::default(),
             flying: true,
+            sprinting: false,
+            no_clip: false,
+        }
+    }
+}
+
+/// How a player plays the game
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Blocks take time to break and must be gathered, and the player gets hungry
+    #[default]
+    Survival,
+    /// The player flies, breaks blocks instantly and places as many as it wants
+    Creative,
+    /// The player flies through the blocks, and can't change the world
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Survival, GameMode::Creative, GameMode::Spectator];
+
+    /// The name of the game mode in the commands
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+            GameMode::Spectator => "spectator",
+        }
+    }
+
+    /// Find a game mode by its name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().cloned().find(|mode| mode.name() == name)
+    }
+
+    /// Whether the player is allowed to fly
+    pub fn can_fly(self) -> bool {
+        self != GameMode::Survival
+    }
+
+    /// Whether the player can break and place blocks
+    pub fn can_edit_world(self) -> bool {
+        self != GameMode::Spectator
+    }
+
+    /// Whether the player breaks blocks instantly, without getting their drops
+    pub fn breaks_instantly(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    /// Whether placing a block doesn't use it up
+    pub fn has_infinite_blocks(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    /// Whether the player gets hungry and loses health
+    pub fn has_hunger(self) -> bool {
+        self == GameMode::Survival
+    }
+
+    /// Adapt an input to the game mode: only spectators go through the blocks, and they always fly
+    pub fn adapt_input(self, input: &mut PlayerInput) {
+        input.no_clip = self == GameMode::Spectator;
+        if self == GameMode::Spectator {
+            input.flying = true;
+        } else if !self.can_fly() {
+            input.flying = false;
+        }
+    }
+}
+
+/// Maximum health of a player
+pub const MAX_HEALTH: u32 = 20;
+/// Maximum hunger of a player
+pub const MAX_HUNGER: u32 = 20;
+
+/// The status values of a player, that the server sends to the client for the HUD
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub health: u32,
+    pub hunger: u32,
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        Self {
+            health: MAX_HEALTH,
+            hunger: MAX_HUNGER,
+        }
+    }
+}
+
+impl PlayerStats {
+    /// Whether the player has the energy to sprint
+    pub fn can_sprint(&self) -> bool {
+        self.hunger > 0
+    }
+}
+
+/// What killed a player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Starvation,
+    /// Hit by a projectile thrown by another player
+    Projectile,
+}
+
+impl DeathCause {
+    /// The message shown to the player who died
+    pub fn message(self) -> &'static str {
+        match self {
+            DeathCause::Starvation => "You starved to death",
+            DeathCause::Projectile => "You were hit by a projectile",
         }
     }
 }
 
 /// Some unique player id.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PlayerId(pub(crate) u16);
 
+impl std::fmt::Display for PlayerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How far around a joining player the chunks are loaded before the player can play, in chunks
+pub const SPAWN_AREA_RADIUS: u64 = 2;
+
 /// The render distance of a player
 #[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
 pub struct RenderDistance {
@@ -64,6 +189,33 @@ impl RenderDistance {
             && player_chunk.py - chunk_pos.py <= self.y_min as i64
             && player_chunk.pz - chunk_pos.pz <= self.z_min as i64
     }
+
+    /// The area around the player that is loaded before a joining player can play: the render distance, limited to
+    /// `SPAWN_AREA_RADIUS` chunks in every direction
+    pub fn spawn_area(self) -> Self {
+        let limit = |distance: u64| distance.min(SPAWN_AREA_RADIUS);
+        Self {
+            x_max: limit(self.x_max),
+            x_min: limit(self.x_min),
+            y_max: limit(self.y_max),
+            y_min: limit(self.y_min),
+            z_max: limit(self.z_max),
+            z_min: limit(self.z_min),
+        }
+    }
+
+    /// Reduce the render distance by some number of chunks in every direction, without going below one chunk
+    pub fn reduced(self, chunks: u64) -> Self {
+        let reduce = |distance: u64| distance.saturating_sub(chunks).max(distance.min(1));
+        Self {
+            x_max: reduce(self.x_max),
+            x_min: reduce(self.x_min),
+            y_max: reduce(self.y_max),
+            y_min: reduce(self.y_min),
+            z_max: reduce(self.z_max),
+            z_min: reduce(self.z_min),
+        }
+    }
 }
 
 pub struct RenderDistanceIterator {
@@ -0,0 +1,62 @@
+//! Smelting: the furnaces turn an item into another item while they burn fuel, following the smelting recipes
+//! and the fuels of the data.
+use crate::inventory::ItemStack;
+use crate::item::ItemId;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// A smelting recipe as it is written in the data files, with the items referenced by name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "SmeltingRecipe")]
+pub struct SmeltingRecipeType {
+    /// The smelted item, one at a time
+    pub input: String,
+    /// The produced item and its count
+    pub result: (String, u32),
+    /// The number of ticks to smelt one input item
+    pub time: u32,
+}
+
+/// A fuel as it is written in the data files. The name of the file is the name of the item.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Fuel")]
+pub struct FuelType {
+    /// The number of ticks that one item of the fuel burns
+    pub burn_time: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmeltingRecipe {
+    pub name: String,
+    pub input: ItemId,
+    pub result: ItemStack,
+    pub time: u32,
+}
+
+/// The smelting recipes and the fuels
+#[derive(Debug, Clone, Default)]
+pub struct Smelting {
+    /// The recipes, by input item
+    recipes: HashMap<ItemId, SmeltingRecipe>,
+    /// The burn times of the fuels, by item
+    fuels: HashMap<ItemId, u32>,
+}
+
+impl Smelting {
+    pub fn new(recipes: Vec<SmeltingRecipe>, fuels: HashMap<ItemId, u32>) -> Self {
+        Self {
+            recipes: recipes.into_iter().map(|recipe| (recipe.input, recipe)).collect(),
+            fuels,
+        }
+    }
+
+    /// The recipe that smelts some item
+    pub fn get_recipe(&self, input: ItemId) -> Option<&SmeltingRecipe> {
+        self.recipes.get(&input)
+    }
+
+    /// The number of ticks that one item burns, `None` if the item is not a fuel
+    pub fn get_burn_time(&self, item: ItemId) -> Option<u32> {
+        self.fuels.get(&item).copied().filter(|&burn_time| burn_time > 0)
+    }
+}
@@ -112,9 +112,172 @@ impl BreakdownCounter {
         }
     }
 
+    /// The parts recorded since the start of the current frame, with their durations.
+    /// Must be called before `extract_part_averages`, which forgets the part names.
+    pub fn current_frame_parts(&self) -> Vec<(String, Duration)> {
+        match self.times.back() {
+            Some((_, durations)) if !self.part_names.is_empty() => {
+                self.part_names.iter().cloned().zip(durations.iter().cloned()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The total durations of the last `count` frames, oldest first
+    pub fn recent_frame_times(&self, count: usize) -> Vec<Duration> {
+        let skipped = self.times.len().saturating_sub(count);
+        self.times.iter().skip(skipped).map(|(_, durations)| durations.iter().sum()).collect()
+    }
+
     /// Extract part averages
     pub fn extract_part_averages(&mut self) -> Vec<(String, f64)> {
         let total_micros = self.total_micros.iter().sum::<u128>() as f64;
         self.part_names.drain(..).zip(self.total_micros.iter()).map(|(s, m)| (s, *m as f64 / total_micros)).collect()
     }
-}
\ No newline at end of file
+}
+
+/// Number of world ticks in a full day-night cycle
+pub const TICKS_PER_DAY: u64 = 24000;
+/// Number of world ticks per real-time second
+pub const TICKS_PER_SECOND: f64 = 20.0;
+/// Intensity of the sky light at night, relative to the day
+const NIGHT_SKY_LIGHT: f64 = 0.2;
+/// How much the path of the sun is tilted towards the south
+const SUN_TILT: f64 = 0.4;
+
+/// The time of the world, i.e. the number of ticks since the first sunrise
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WorldTime(pub u64);
+
+/// The phase of the day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayPhase {
+    Sunrise,
+    Day,
+    Sunset,
+    Night,
+}
+
+impl WorldTime {
+    /// Number of full days since the first sunrise
+    pub fn day(self) -> u64 {
+        self.0 / TICKS_PER_DAY
+    }
+
+    /// Position in the current day, from 0 (sunrise) to 1 (next sunrise). Sunset is at 0.5.
+    pub fn day_fraction(self) -> f64 {
+        (self.0 % TICKS_PER_DAY) as f64 / TICKS_PER_DAY as f64
+    }
+
+    /// The phase of the day, from the position in the current day
+    pub fn phase(self) -> DayPhase {
+        match self.day_fraction() {
+            f if f < 0.05 => DayPhase::Sunrise,
+            f if f < 0.45 => DayPhase::Day,
+            f if f < 0.55 => DayPhase::Sunset,
+            f if f < 0.95 => DayPhase::Night,
+            _ => DayPhase::Sunrise,
+        }
+    }
+
+    /// The direction of the sun, which rises in the east (+x) and sets in the west. It is below the horizon at night.
+    pub fn sun_direction(self) -> [f64; 3] {
+        let angle = self.day_fraction() * 2.0 * std::f64::consts::PI;
+        let (x, y, z) = (angle.cos(), angle.sin(), SUN_TILT);
+        let norm = (x * x + y * y + z * z).sqrt();
+        [x / norm, y / norm, z / norm]
+    }
+
+    /// Intensity of the sky light, from `NIGHT_SKY_LIGHT` at night to 1 during the day.
+    /// It changes smoothly while the sun is close to the horizon.
+    pub fn sky_light_factor(self) -> f64 {
+        let t = ((self.sun_direction()[1] + 0.1) / 0.3).clamp(0.0, 1.0);
+        let smooth = t * t * (3.0 - 2.0 * t);
+        NIGHT_SKY_LIGHT + (1.0 - NIGHT_SKY_LIGHT) * smooth
+    }
+
+    /// Whether the players can sleep, from the sunset to the next sunrise
+    pub fn is_night(self) -> bool {
+        matches!(self.phase(), DayPhase::Sunset | DayPhase::Night)
+    }
+
+    /// The morning of the next day, once the sun is up
+    pub fn next_morning(self) -> WorldTime {
+        WorldTime((self.day() + 1) * TICKS_PER_DAY + TICKS_PER_DAY / 20)
+    }
+
+    /// Hours and minutes on a 24h clock, sunrise being at 6:00
+    pub fn hours_minutes(self) -> (u32, u32) {
+        let minutes = ((self.day_fraction() * 24.0 * 60.0) as u32 + 6 * 60) % (24 * 60);
+        (minutes / 60, minutes % 60)
+    }
+}
+
+/// Helper struct to advance the world time with the real time
+#[derive(Debug, Clone, Copy)]
+pub struct WorldClock {
+    base_time: WorldTime,
+    base_instant: Instant,
+}
+
+impl WorldClock {
+    /// Create a new clock showing `time` at the current instant
+    pub fn new(time: WorldTime) -> Self {
+        Self {
+            base_time: time,
+            base_instant: Instant::now(),
+        }
+    }
+
+    /// Get the world time at some instant
+    pub fn get_time(&self, now: Instant) -> WorldTime {
+        let elapsed_ticks = (now.saturating_duration_since(self.base_instant).as_secs_f64() * TICKS_PER_SECOND) as u64;
+        WorldTime(self.base_time.0 + elapsed_ticks)
+    }
+
+    /// Set the world time at some instant
+    pub fn set_time(&mut self, time: WorldTime, now: Instant) {
+        self.base_time = time;
+        self.base_instant = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_sky_is_dark_at_night() {
+        let noon = WorldTime(TICKS_PER_DAY / 4);
+        let midnight = WorldTime(TICKS_PER_DAY * 3 / 4);
+        assert!(noon.sun_direction()[1] > 0.9);
+        assert!(midnight.sun_direction()[1] < -0.9);
+        assert_eq!(noon.sky_light_factor(), 1.0);
+        assert_eq!(midnight.sky_light_factor(), NIGHT_SKY_LIGHT);
+        // The sky gets brighter during the sunrise
+        let dawn = WorldTime(TICKS_PER_DAY - TICKS_PER_DAY / 100);
+        let sunrise = WorldTime(TICKS_PER_DAY + TICKS_PER_DAY / 100);
+        assert!(NIGHT_SKY_LIGHT < dawn.sky_light_factor() && dawn.sky_light_factor() < sunrise.sky_light_factor());
+        assert!(sunrise.sky_light_factor() < 1.0);
+        // Sleeping skips to the morning of the next day
+        assert!(midnight.is_night() && !noon.is_night());
+        let morning = midnight.next_morning();
+        assert_eq!(morning.day(), 1);
+        assert!(!morning.is_night() && morning.sky_light_factor() > midnight.sky_light_factor());
+    }
+
+    #[test]
+    fn the_frame_times_add_up_the_parts() {
+        let mut counter = BreakdownCounter::new();
+        for _ in 0..3 {
+            counter.start_frame();
+            counter.record_part("first");
+            counter.record_part("second");
+        }
+        let parts = counter.current_frame_parts();
+        let frame_times = counter.recent_frame_times(2);
+        assert_eq!(frame_times.len(), 2);
+        assert_eq!(frame_times[1], parts[0].1 + parts[1].1);
+        assert_eq!(counter.recent_frame_times(10).len(), 3);
+    }
+}
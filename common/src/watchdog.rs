@@ -0,0 +1,209 @@
+//! Watchdog of the main thread of the client and of the server.
+//!
+//! The frames or ticks that take longer than a threshold are hitches: a report with the time taken by each part
+//! of the frame, as recorded by the `BreakdownCounter`, is written to the disk so that rare stutters can be diagnosed
+//! from the reports sent by the users. A background thread also warns when the main thread is stuck in a frame.
+use crate::time::BreakdownCounter;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The directory the hitch reports are written to
+const HITCHES_DIRECTORY: &str = "hitches";
+/// Minimum time between two hitch reports, so that a slow machine doesn't fill the disk
+const REPORT_COOLDOWN: Duration = Duration::from_secs(10);
+/// How long a frame must last for the background thread to warn that the main thread is stuck
+const STALL_DURATION: Duration = Duration::from_secs(5);
+/// Time between two checks of the background thread
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A frame that took longer than the threshold
+#[derive(Debug, Clone)]
+pub struct HitchReport {
+    /// The thread that hitched, `"client"` or `"server"`
+    pub name: &'static str,
+    pub duration: Duration,
+    pub threshold: Duration,
+    /// The parts of the frame with their duration, the slowest first
+    pub parts: Vec<(String, Duration)>,
+}
+
+impl HitchReport {
+    /// The slowest part of the frame, if any part was recorded
+    pub fn slowest_part(&self) -> Option<&(String, Duration)> {
+        self.parts.first()
+    }
+
+    /// The text written to the report file
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Hitch of the {} main thread: {:.1} ms (threshold {:.1} ms)\n\n",
+            self.name,
+            millis(self.duration),
+            millis(self.threshold),
+        );
+        let recorded: Duration = self.parts.iter().map(|(_, d)| *d).sum();
+        for (name, duration) in &self.parts {
+            text += &format!("{:>9.2} ms  {:>5.1}%  {}\n", millis(*duration), percent(*duration, self.duration), name);
+        }
+        let unrecorded = self.duration.checked_sub(recorded).unwrap_or_default();
+        text += &format!("{:>9.2} ms  {:>5.1}%  (not recorded)\n", millis(unrecorded), percent(unrecorded, self.duration));
+        text
+    }
+
+    /// Write the report in the hitches directory, returning the path of the file
+    fn write(&self) -> Result<PathBuf> {
+        std::fs::create_dir_all(HITCHES_DIRECTORY).context(format!("Failed to create directory {}", HITCHES_DIRECTORY))?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let path = PathBuf::from(HITCHES_DIRECTORY).join(format!("{}-{}.txt", self.name, timestamp));
+        std::fs::write(&path, self.to_text()).context(format!("Failed to write hitch report {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn percent(part: Duration, total: Duration) -> f64 {
+    if total.as_nanos() == 0 {
+        0.0
+    } else {
+        part.as_secs_f64() / total.as_secs_f64() * 100.0
+    }
+}
+
+/// The frame the main thread is in, shared with the background thread: (frame index, start of the frame)
+type Heartbeat = Mutex<(u64, Instant)>;
+
+/// Watchdog of a main thread, called at the start and at the end of every frame
+pub struct Watchdog {
+    name: &'static str,
+    threshold: Duration,
+    heartbeat: Arc<Heartbeat>,
+    frame_start: Instant,
+    last_report: Option<Instant>,
+}
+
+impl Watchdog {
+    /// Create a new watchdog reporting the frames longer than `threshold`, and start its background thread
+    pub fn new(name: &'static str, threshold: Duration) -> Self {
+        let now = Instant::now();
+        let heartbeat = Arc::new(Mutex::new((0, now)));
+        let weak_heartbeat = Arc::downgrade(&heartbeat);
+        std::thread::Builder::new()
+            .name(format!("{} watchdog", name))
+            .spawn(move || watch_stalls(name, weak_heartbeat))
+            .expect("Failed to spawn the watchdog thread");
+        Self {
+            name,
+            threshold,
+            heartbeat,
+            frame_start: now,
+            last_report: None,
+        }
+    }
+
+    /// Start a new frame
+    pub fn start_frame(&mut self) {
+        self.frame_start = Instant::now();
+        let mut heartbeat = self.heartbeat.lock().unwrap();
+        *heartbeat = (heartbeat.0 + 1, self.frame_start);
+    }
+
+    /// End the frame, and write a hitch report if it took too long.
+    /// Must be called before the part averages of `timing` are extracted.
+    pub fn end_frame(&mut self, timing: &BreakdownCounter) -> Option<HitchReport> {
+        let now = Instant::now();
+        let report = self.check_frame(now - self.frame_start, timing.current_frame_parts(), now)?;
+        match report.write() {
+            Ok(path) => warn!(
+                "The {} frame took {:.1} ms, hitch report written to {}",
+                self.name,
+                millis(report.duration),
+                path.display()
+            ),
+            Err(e) => warn!("The {} frame took {:.1} ms, but the hitch report couldn't be written: {:?}", self.name, millis(report.duration), e),
+        }
+        Some(report)
+    }
+
+    /// Build the report of a frame that lasted `duration`, if it is a hitch and no hitch was reported recently
+    fn check_frame(&mut self, duration: Duration, mut parts: Vec<(String, Duration)>, now: Instant) -> Option<HitchReport> {
+        if duration <= self.threshold {
+            return None;
+        }
+        if let Some(last_report) = self.last_report {
+            if now - last_report < REPORT_COOLDOWN {
+                return None;
+            }
+        }
+        self.last_report = Some(now);
+        parts.sort_by_key(|(_, duration)| std::cmp::Reverse(*duration));
+        Some(HitchReport {
+            name: self.name,
+            duration,
+            threshold: self.threshold,
+            parts,
+        })
+    }
+}
+
+/// Warn once per frame when the main thread has been stuck in it for too long, until the watchdog is dropped
+fn watch_stalls(name: &'static str, heartbeat: Weak<Heartbeat>) {
+    let mut warned_frame = None;
+    loop {
+        std::thread::sleep(STALL_CHECK_INTERVAL);
+        let (frame, start) = match heartbeat.upgrade() {
+            Some(heartbeat) => *heartbeat.lock().unwrap(),
+            None => break,
+        };
+        let elapsed = start.elapsed();
+        if elapsed >= STALL_DURATION && warned_frame != Some(frame) {
+            warned_frame = Some(frame);
+            warn!("The {} main thread has been stuck in the same frame for {:.1} s", name, elapsed.as_secs_f64());
+        }
+    }
+    info!("Stopped the {} watchdog", name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_hitch_detection() {
+        let mut watchdog = Watchdog::new("test", ms(50));
+        let start = Instant::now();
+        let parts = vec![("Network events".to_owned(), ms(10)), ("Update physics".to_owned(), ms(70))];
+        assert!(watchdog.check_frame(ms(40), parts.clone(), start).is_none());
+        // The slowest part comes first, and the time that no part recorded is reported
+        let report = watchdog.check_frame(ms(100), parts.clone(), start).unwrap();
+        assert_eq!(report.slowest_part().unwrap().0, "Update physics");
+        let text = report.to_text();
+        assert!(text.find("Update physics").unwrap() < text.find("Network events").unwrap());
+        assert!(text.contains("20.00 ms"));
+        // Hitches are not reported again until the cooldown is over
+        assert!(watchdog.check_frame(ms(100), parts.clone(), start + ms(1000)).is_none());
+        assert!(watchdog.check_frame(ms(100), parts, start + REPORT_COOLDOWN).is_some());
+    }
+
+    #[test]
+    fn test_current_frame_parts() {
+        let mut timing = BreakdownCounter::new();
+        timing.start_frame();
+        timing.record_part("First");
+        timing.record_part("Second");
+        let parts = timing.current_frame_parts();
+        assert_eq!(parts.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["First", "Second"]);
+        timing.extract_part_averages();
+        timing.start_frame();
+        assert!(timing.current_frame_parts().is_empty());
+    }
+}
@@ -0,0 +1,15 @@
+/// The weather of the world, decided by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Snow,
+}
+
+impl Weather {
+    /// Whether something falls from the sky
+    pub fn has_precipitation(self) -> bool {
+        self != Weather::Clear
+    }
+}
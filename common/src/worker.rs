@@ -9,10 +9,16 @@ use crate::{debug::send_worker_perf, time::AverageTimeCounter};
 /// A type that takes inputs of type `Input` produces outputs of type `Output`.
 pub trait WorkerState<Input, Output> {
     fn compute(&mut self, input: Input) -> Output;
+
+    /// The priority of an input waiting to be computed. The inputs with the lowest value are computed first,
+    /// and the inputs with the same priority in order. The priority is checked again every time an input is picked.
+    fn priority(&self, _input: &Input) -> u64 {
+        0
+    }
 }
 
 /// A generic worker allowing to offload expensive computations to other threads.
-/// The worker will try to process the inputs in order.
+/// The worker will try to process the inputs in order of priority, then in order.
 /// `Input`: the input type
 /// `Output`: the output type
 /// `State`: the worker state
@@ -31,7 +37,25 @@ impl<Input: Send + 'static, Output: Send + 'static, State: WorkerState<Input, Ou
         std::thread::spawn(move || { // TODO: debug timing
             let mut state = state;
             let mut timing = AverageTimeCounter::new();
-            while let Ok(input) = in_receiver.recv() {
+            // The inputs received from the channel, at most as many as the channel can hold
+            let mut pending = Vec::new();
+            loop {
+                // Wait for an input if there is none, then take the inputs that are already waiting
+                if pending.is_empty() {
+                    match in_receiver.recv() {
+                        Ok(input) => pending.push(input),
+                        Err(_) => break,
+                    }
+                }
+                while pending.len() < channel_size {
+                    match in_receiver.try_recv() {
+                        Ok(input) => pending.push(input),
+                        Err(_) => break,
+                    }
+                }
+                let next = (0..pending.len()).min_by_key(|i| state.priority(&pending[*i])).expect("logic error!");
+                let input = pending.remove(next);
+
                 // Compute
                 let t1 = Instant::now();
                 let output = state.compute(input);
@@ -39,7 +63,7 @@ impl<Input: Send + 'static, Output: Send + 'static, State: WorkerState<Input, Ou
                 timing.add_time(t2 - t1);
 
                 // Send debug info
-                send_worker_perf("Workers", &name, &name, timing.average_time_micros() as f32, timing.average_iter_per_sec(), 0);
+                send_worker_perf("Workers", &name, &name, timing.average_time_micros() as f32, timing.average_iter_per_sec(), pending.len());
 
                 // Send result
                 match out_sender.send(output) {
@@ -1,8 +1,13 @@
 use crate::{
-    block::{Block, BlockId},
+    biome::{Biome, BiomeId},
+    block::{entity::BlockEntity, Block, BlockId},
     registry::Registry,
 };
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hasher;
+use twox_hash::XxHash64;
 
 /// The position of a block in the world.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -30,6 +35,15 @@ impl BlockPos {
             self.pz.rem_euclid(CHUNK_SIZE as i64) as u32,
         )
     }
+
+    /// Offset the current block position by some amount of blocks
+    pub fn offset(self, dx: i64, dy: i64, dz: i64) -> Self {
+        Self {
+            px: self.px + dx,
+            py: self.py + dy,
+            pz: self.pz + dz,
+        }
+    }
 }
 
 impl From<(i64, i64, i64)> for BlockPos {
@@ -63,10 +77,19 @@ pub trait WorldGenerator {
     /// Generate the chunk at position `pos`. The result must always be the same,
     /// independently of the previous calls to this function!
     fn generate_chunk(&mut self, pos: ChunkPos, block_registry: &Registry<Block>) -> Chunk;
+
+    /// Choose the position where the players spawn: the block above a safe surface block near the origin
+    fn find_spawn_point(&mut self) -> BlockPos;
+
+    /// Choose the biomes of the columns of the chunks at some horizontal position, in the order of `Chunk::biomes`.
+    /// Like the chunks, the result must always be the same.
+    fn generate_biomes(&mut self, pos: ChunkPosXZ) -> Vec<BiomeId>;
 }
 
 /// Number of blocks along an axis of the chunk
 pub const CHUNK_SIZE: u32 = 32;
+/// Number of columns of a chunk, which each have a biome
+const CHUNK_COLUMNS: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
 
 /// Position of a chunk in the world
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -91,6 +114,15 @@ impl ChunkPos {
         self.offset(other.px, other.py, other.pz)
     }
 
+    /// The position in the world of a block of the chunk
+    pub fn block_pos(self, (x, y, z): (u32, u32, u32)) -> BlockPos {
+        BlockPos {
+            px: self.px * CHUNK_SIZE as i64 + x as i64,
+            py: self.py * CHUNK_SIZE as i64 + y as i64,
+            pz: self.pz * CHUNK_SIZE as i64 + z as i64,
+        }
+    }
+
     /// Squared euclidian distance to other chunk
     #[inline(always)]
     pub fn squared_euclidian_distance(self, other: ChunkPos) -> u64 {
@@ -163,6 +195,10 @@ impl From<ChunkPos> for ChunkPosXZ {
 pub struct CompressedChunk {
     pub pos: ChunkPos,
     pub data: Vec<(u16, BlockId)>,
+    /// The block entities of the chunk, with their position in the chunk
+    pub block_entities: Vec<((u32, u32, u32), BlockEntity)>,
+    /// The RLE-compressed biomes of the columns
+    pub biomes: Vec<(u16, BiomeId)>,
 }
 
 impl CompressedChunk {
@@ -182,9 +218,19 @@ impl CompressedChunk {
 
         compressed_data.push((current_block_count, current_block));
 
+        let mut biomes: Vec<(u16, BiomeId)> = Vec::new();
+        for &biome in chunk.biomes.iter() {
+            match biomes.last_mut() {
+                Some((count, last_biome)) if *last_biome == biome => *count += 1,
+                _ => biomes.push((1, biome)),
+            }
+        }
+
         Self {
             pos: chunk.pos,
             data: compressed_data,
+            block_entities: chunk.block_entities.iter().map(|(pos, entity)| (*pos, entity.clone())).collect(),
+            biomes,
         }
     }
 
@@ -200,18 +246,53 @@ impl CompressedChunk {
             i += len;
         }
 
+        // The missing columns are plains
+        let mut biomes = vec![Biome::Plains.id(); CHUNK_COLUMNS];
+        let mut i = 0;
+        for &(len, biome) in self.biomes.iter() {
+            let end = (i + len as usize).min(CHUNK_COLUMNS);
+            for el in &mut biomes[i..end] {
+                *el = biome;
+            }
+            i = end;
+        }
+
         Chunk {
             pos: self.pos,
             data,
+            block_entities: self.block_entities.iter().cloned().collect(),
+            biomes,
+            pending_ticks: Vec::new(),
         }
     }
 }
 
+/// The priority of a scheduled block tick. Among the ticks that are due at the same tick, the lowest priority runs first.
+pub type TickPriority = i8;
+
+/// A block tick that was scheduled in a chunk while the chunk was unloaded or saved.
+/// The tick is for the block that is at its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTick {
+    /// The position of the block in the chunk
+    pub pos: (u32, u32, u32),
+    /// The number of ticks that remained before the tick, when the chunk was unloaded or saved
+    pub delay: u64,
+    pub priority: TickPriority,
+}
+
 /// A chunk
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub pos: ChunkPos,
     pub data: Vec<BlockId>,
+    /// The block entities, by position in the chunk
+    pub block_entities: HashMap<(u32, u32, u32), BlockEntity>,
+    /// The biomes of the columns of the chunk, by `x * CHUNK_SIZE + z`
+    pub biomes: Vec<BiomeId>,
+    /// The scheduled block ticks, in the order they run. Only the server uses them, to keep the ticks of the chunks
+    /// that are not loaded: time doesn't pass for the ticks of an unloaded chunk.
+    pub pending_ticks: Vec<PendingTick>,
 }
 
 impl Chunk {
@@ -222,7 +303,13 @@ impl Chunk {
                 (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize,
             )
         };
-        Self { pos, data }
+        Self {
+            pos,
+            data,
+            block_entities: HashMap::new(),
+            biomes: vec![Biome::Plains.id(); CHUNK_COLUMNS],
+            pending_ticks: Vec::new(),
+        }
     }
 
     /// Get block at some position
@@ -237,6 +324,24 @@ impl Chunk {
         self.data[(px * CHUNK_SIZE * CHUNK_SIZE + py * CHUNK_SIZE + pz) as usize] = block;
     }
 
+    /// Get the biome of the column at some position
+    pub fn get_biome_at(&self, (px, pz): (u32, u32)) -> Biome {
+        Biome::from_id(self.biomes[(px * CHUNK_SIZE + pz) as usize])
+    }
+
+    /// Get the block entity at some position
+    pub fn get_block_entity(&self, pos: (u32, u32, u32)) -> Option<&BlockEntity> {
+        self.block_entities.get(&pos)
+    }
+
+    /// Set or remove the block entity at some position
+    pub fn set_block_entity(&mut self, pos: (u32, u32, u32), block_entity: Option<BlockEntity>) {
+        match block_entity {
+            Some(block_entity) => self.block_entities.insert(pos, block_entity),
+            None => self.block_entities.remove(&pos),
+        };
+    }
+
     #[inline(always)]
     pub unsafe fn get_block_at_unsafe(&self, (px, py, pz): (u32, u32, u32)) -> BlockId {
         *self
@@ -266,6 +371,20 @@ impl Chunk {
             self.data[i] = block;
         }
     }
+
+    /// A fast hash of the blocks and the biomes of the chunk, which is the same on every machine.
+    /// The server and the clients compare it to find the chunks that are out of sync.
+    /// The block entities and the pending ticks are not part of it.
+    pub fn content_hash(&self) -> u64 {
+        let mut bytes = Vec::with_capacity(self.data.len() * std::mem::size_of::<BlockId>());
+        for block in self.data.iter() {
+            bytes.extend_from_slice(&block.to_le_bytes());
+        }
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(&bytes);
+        hasher.write(&self.biomes);
+        hasher.finish()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -343,3 +462,27 @@ impl CompressedLightChunk {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_hash_only_depends_on_blocks_and_biomes() {
+        let pos = ChunkPos { px: 1, py: -2, pz: 3 };
+        let mut chunk = Chunk::new(pos);
+        chunk.set_block_at((1, 2, 3), 5);
+        let hash = chunk.content_hash();
+        // The same contents at another position have the same hash
+        let mut copy = Chunk::new(pos.offset(1, 0, 0));
+        copy.set_block_at((1, 2, 3), 5);
+        copy.pending_ticks.push(PendingTick { pos: (1, 2, 3), delay: 10, priority: 0 });
+        assert_eq!(copy.content_hash(), hash);
+        // Any change of a block or a biome changes the hash
+        copy.set_block_at((1, 2, 3), 6);
+        assert_ne!(copy.content_hash(), hash);
+        copy.set_block_at((1, 2, 3), 5);
+        copy.biomes[0] += 1;
+        assert_ne!(copy.content_hash(), hash);
+    }
+}
@@ -3,21 +3,25 @@ use std::collections::{HashMap, HashSet};
 use crate::world::BlockPos;
 use crate::worldgen::perlin::rand_pos_int;
 use crate::{
+    biome::{Biome, BiomeId},
     block::Block,
     registry::Registry,
-    world::{Chunk, ChunkPos, CHUNK_SIZE, WorldGenerator},
+    world::{Chunk, ChunkPos, ChunkPosXZ, CHUNK_SIZE, WorldGenerator},
 };
 
 use crate::debug::send_debug_info;
 use crate::worldgen::decorator::Decorator;
 use crate::worldgen::decorator::DecoratorPass;
-use crate::worldgen::topology::{generate_chunk_topology, HeightMap};
+use crate::worldgen::topology::{generate_chunk_biomes, generate_chunk_topology, HeightMap};
 
 pub mod perlin;
 #[macro_use]
 pub mod decorator;
 pub mod topology;
 
+/// Maximum horizontal distance from the origin at which the spawn point is searched, in blocks
+const SPAWN_SEARCH_RADIUS: i64 = 256;
+
 pub struct DefaultWorldGenerator {
     pregenerated_chunks: HashMap<ChunkPos, Chunk>,
     pregenerated_chunks_decorator_count: HashMap<ChunkPos, u32>,
@@ -299,6 +303,29 @@ impl WorldGenerator for DefaultWorldGenerator {
 
         chunk_res
     }
+
+    fn find_spawn_point(&mut self) -> BlockPos {
+        // Search squares of increasing size around the origin, and take the highest grass block of the first square that has one.
+        // The grass is above the water and the beaches.
+        for radius in 0..=SPAWN_SEARCH_RADIUS {
+            let square = (-radius..=radius)
+                .flat_map(|x| vec![(x, -radius), (x, radius)])
+                .chain((1 - radius..radius).flat_map(|z| vec![(-radius, z), (radius, z)]));
+            let highest = square
+                .map(|(x, z)| (x, self.height_map.get_ground_level(x, z), z))
+                .filter(|&(_, height, _)| height >= 1)
+                .max_by_key(|&(_, height, _)| height);
+            if let Some((x, height, z)) = highest {
+                return BlockPos::from((x, height as i64 + 1, z));
+            }
+        }
+        // Only water around the origin: spawn on the surface
+        BlockPos::from((0, self.height_map.get_ground_level(0, 0).max(0) as i64 + 1, 0))
+    }
+
+    fn generate_biomes(&mut self, pos: ChunkPosXZ) -> Vec<BiomeId> {
+        generate_chunk_biomes(pos, &mut self.height_map)
+    }
 }
 
 pub struct DebugWorldGenerator;
@@ -318,4 +345,12 @@ impl WorldGenerator for DebugWorldGenerator {
         }
         c
     }
+
+    fn find_spawn_point(&mut self) -> BlockPos {
+        BlockPos::from((0, 1, 0))
+    }
+
+    fn generate_biomes(&mut self, _pos: ChunkPosXZ) -> Vec<BiomeId> {
+        vec![Biome::Plains.id(); (CHUNK_SIZE * CHUNK_SIZE) as usize]
+    }
 }
@@ -1,3 +1,4 @@
+use crate::biome::{Biome, BiomeId};
 use crate::block::Block;
 use crate::registry::Registry;
 use crate::world::{Chunk, CHUNK_SIZE, ChunkPosXZ};
@@ -29,6 +30,13 @@ impl  HeightMap {
         return self.height_map.get(&pos).unwrap();
     }
 
+    /// The height of the highest block of the terrain at (x, z), without the decorations
+    pub fn get_ground_level(&mut self, x: i64, z: i64) -> i32 {
+        let size = CHUNK_SIZE as i64;
+        let pos = ChunkPosXZ { px: x.div_euclid(size), pz: z.div_euclid(size) };
+        self.get_chunk_height_map(pos)[(x.rem_euclid(size) * size + z.rem_euclid(size)) as usize]
+    }
+
 }
 
 pub fn generate_ground_level(px: f32, pz: f32) -> Vec<f32> {
@@ -91,6 +99,39 @@ pub fn generate_ground_level(px: f32, pz: f32) -> Vec<f32> {
     return res;
 }
 
+/// Columns whose ground is deeper than this are oceans
+const OCEAN_DEPTH: i32 = -6;
+/// Columns colder than this are snowy
+const SNOWY_TEMPERATURE: f32 = 0.4;
+/// Columns more humid than this and close to the water level are swamps
+const SWAMP_HUMIDITY: f32 = 0.6;
+/// Maximum ground level of the swamps
+const SWAMP_MAX_HEIGHT: i32 = 8;
+
+/// Choose the biomes of the columns of a chunk from their height, temperature and humidity
+pub fn generate_chunk_biomes(pos: ChunkPosXZ, height_map: &mut HeightMap) -> Vec<BiomeId> {
+    let c = CHUNK_SIZE as f32;
+    let (px, pz) = (pos.px as f32 * c, pos.pz as f32 * c);
+    let temperature = perlin::perlin2d(px, pz, CHUNK_SIZE as usize, 1.0 / 512.0, 1.0 / 512.0, 3, 0.5, 10);
+    let humidity = perlin::perlin2d(px, pz, CHUNK_SIZE as usize, 1.0 / 256.0, 1.0 / 256.0, 3, 0.5, 20);
+    let h = height_map.get_chunk_height_map(pos);
+
+    (0..(CHUNK_SIZE * CHUNK_SIZE) as usize)
+        .map(|i| {
+            let biome = if h[i] < OCEAN_DEPTH {
+                Biome::Ocean
+            } else if temperature[i] < SNOWY_TEMPERATURE {
+                Biome::Snowy
+            } else if humidity[i] > SWAMP_HUMIDITY && h[i] <= SWAMP_MAX_HEIGHT {
+                Biome::Swamp
+            } else {
+                Biome::Plains
+            };
+            biome.id()
+        })
+        .collect()
+}
+
 /// Generate the topology of the chunk
 pub fn generate_chunk_topology(chunk: &mut Chunk, block_registry: &Registry<Block>,height_map :  &mut HeightMap) {
     let stone_block = block_registry.get_id_by_name(&"stone".to_owned()).unwrap() as u16;
@@ -0,0 +1,263 @@
+//! Reading the Anvil region files, the `r.<x>.<z>.mca` files of the worlds of the most common voxel game.
+//!
+//! A region file stores 32x32 columns of 16x16 blocks. Every column is compressed NBT, split into sections of 16
+//! blocks of height whose blocks are indices into a palette of block names, packed into longs.
+use crate::nbt::{read_nbt, Tag};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use log::warn;
+
+/// The size of the sectors that the region files are divided into
+const SECTOR_SIZE: usize = 4096;
+/// Number of columns in a region file
+const COLUMNS_PER_REGION: usize = 32 * 32;
+/// The size of the sections, in blocks
+pub const SECTION_SIZE: i64 = 16;
+/// Number of blocks in a section
+const SECTION_BLOCKS: usize = 16 * 16 * 16;
+/// The first data version whose packed indices don't span two longs
+const NON_SPANNING_DATA_VERSION: i64 = 2529;
+
+/// A column of a region file
+#[derive(Debug, Clone)]
+pub struct Column {
+    /// The position of the column, in columns
+    pub x: i64,
+    pub z: i64,
+    pub sections: Vec<Section>,
+}
+
+/// A section of a column
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// The height of the section, in sections
+    pub y: i64,
+    /// The names of the blocks of the section
+    pub palette: Vec<String>,
+    /// The index in the palette of every block, by `(y * 16 + z) * 16 + x`
+    pub blocks: Vec<u16>,
+}
+
+/// Read the columns of a region file. The columns that can't be read are skipped with a warning.
+pub fn read_region(bytes: &[u8]) -> Result<Vec<Column>> {
+    ensure!(bytes.len() >= 2 * SECTOR_SIZE, "The region file is too short for its header");
+    let mut columns = Vec::new();
+    for index in 0..COLUMNS_PER_REGION {
+        let location = &bytes[4 * index..4 * index + 4];
+        let offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize * SECTOR_SIZE;
+        if offset == 0 {
+            // The column was never generated
+            continue;
+        }
+        match read_column_data(bytes, offset).and_then(|data| read_nbt(&data)).and_then(|tag| read_column(&tag)) {
+            Ok(column) => columns.push(column),
+            Err(e) => warn!("Skipping the column {} of the region: {:?}", index, e),
+        }
+    }
+    Ok(columns)
+}
+
+/// Decompress the NBT data of a column at some offset of the region file
+fn read_column_data(bytes: &[u8], offset: usize) -> Result<Vec<u8>> {
+    ensure!(offset + 5 <= bytes.len(), "The column is outside of the file");
+    let length = u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]) as usize;
+    ensure!(length >= 1 && offset + 4 + length <= bytes.len(), "The column has an invalid length {}", length);
+    let compression = bytes[offset + 4];
+    let data = &bytes[offset + 5..offset + 4 + length];
+    match compression {
+        1 => miniz_oxide::inflate::decompress_to_vec(skip_gzip_header(data)?).map_err(|e| anyhow!("Invalid gzip data: {:?}", e)),
+        2 => miniz_oxide::inflate::decompress_to_vec_zlib(data).map_err(|e| anyhow!("Invalid zlib data: {:?}", e)),
+        3 => Ok(data.to_vec()),
+        _ if compression & 128 != 0 => bail!("The column is stored in a separate file, which is not supported"),
+        _ => bail!("Unsupported compression {}", compression),
+    }
+}
+
+/// The deflate data of a gzip member, without its header
+fn skip_gzip_header(data: &[u8]) -> Result<&[u8]> {
+    ensure!(data.len() >= 10 && data[0] == 0x1f && data[1] == 0x8b && data[2] == 8, "Invalid gzip header");
+    let flags = data[3];
+    let mut position = 10;
+    if flags & 4 != 0 {
+        // Extra field
+        ensure!(data.len() >= position + 2, "Invalid gzip header");
+        position += 2 + u16::from_le_bytes([data[position], data[position + 1]]) as usize;
+    }
+    // File name and comment, terminated by a zero
+    for &flag in [8, 16].iter() {
+        if flags & flag != 0 {
+            let end = data.get(position..).and_then(|rest| rest.iter().position(|&byte| byte == 0));
+            position += end.context("Invalid gzip header")? + 1;
+        }
+    }
+    if flags & 2 != 0 {
+        // Header checksum
+        position += 2;
+    }
+    data.get(position..).context("Invalid gzip header")
+}
+
+/// Read the sections of a column, in the format of the versions since 1.18 or in the older format with a `Level` tag
+fn read_column(tag: &Tag) -> Result<Column> {
+    let data_version = tag.get("DataVersion").and_then(Tag::as_i64).unwrap_or(0);
+    let spanning = data_version < NON_SPANNING_DATA_VERSION;
+    let (level, sections_name, palette_name, states_name) = match tag.get("Level") {
+        Some(level) => (level, "Sections", "Palette", "BlockStates"),
+        None => (tag, "sections", "palette", "data"),
+    };
+    let position = |name| level.get(name).and_then(Tag::as_i64).context(format!("The column has no {}", name));
+    let (x, z) = (position("xPos")?, position("zPos")?);
+    let mut sections = Vec::new();
+    for section in level.get(sections_name).and_then(Tag::as_list).unwrap_or(&[]) {
+        let y = section.get("Y").and_then(Tag::as_i64).context("The section has no height")?;
+        // The block states are in their own compound since 1.18
+        let states = section.get("block_states").unwrap_or(section);
+        let palette = match states.get(palette_name).and_then(Tag::as_list) {
+            Some(palette) => palette,
+            // Sections without blocks, for example the ones that only store light
+            None => continue,
+        };
+        let palette = palette
+            .iter()
+            .map(|block| block.get("Name").and_then(Tag::as_str).map(str::to_owned))
+            .collect::<Option<Vec<_>>>()
+            .context("A block of the palette has no name")?;
+        ensure!(!palette.is_empty(), "The section at height {} has an empty palette", y);
+        let blocks = match states.get(states_name).and_then(Tag::as_long_array) {
+            Some(data) => unpack_indices(data, palette.len(), spanning)?,
+            // A palette with a single block has no data
+            None => vec![0; SECTION_BLOCKS],
+        };
+        sections.push(Section { y, palette, blocks });
+    }
+    Ok(Column { x, z, sections })
+}
+
+/// Unpack the palette indices of a section. `spanning` is whether an index can be split between two longs.
+fn unpack_indices(data: &[i64], palette_len: usize, spanning: bool) -> Result<Vec<u16>> {
+    let bits = (usize::BITS - palette_len.saturating_sub(1).leading_zeros()).max(4) as usize;
+    let mask = (1u64 << bits) - 1;
+    let mut blocks = Vec::with_capacity(SECTION_BLOCKS);
+    for i in 0..SECTION_BLOCKS {
+        let index = if spanning {
+            let start = i * bits;
+            let (long, offset) = (start / 64, start % 64);
+            let mut value = *data.get(long).context("The block data is too short")? as u64 >> offset;
+            if offset + bits > 64 {
+                value |= (*data.get(long + 1).context("The block data is too short")? as u64) << (64 - offset);
+            }
+            value & mask
+        } else {
+            let per_long = 64 / bits;
+            let long = *data.get(i / per_long).context("The block data is too short")? as u64;
+            (long >> ((i % per_long) * bits)) & mask
+        };
+        ensure!((index as usize) < palette_len, "The block index {} is outside of the palette", index);
+        blocks.push(index as u16);
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack indices like the game does
+    fn pack(indices: &[u64], bits: usize, spanning: bool) -> Vec<i64> {
+        let mut data = vec![0u64; if spanning { indices.len() * bits / 64 } else { indices.len().div_ceil(64 / bits) }];
+        for (i, &index) in indices.iter().enumerate() {
+            if spanning {
+                let start = i * bits;
+                data[start / 64] |= index << (start % 64);
+                if start % 64 + bits > 64 {
+                    data[start / 64 + 1] |= index >> (64 - start % 64);
+                }
+            } else {
+                let per_long = 64 / bits;
+                data[i / per_long] |= index << ((i % per_long) * bits);
+            }
+        }
+        data.into_iter().map(|long| long as i64).collect()
+    }
+
+    #[test]
+    fn indices_are_unpacked() {
+        // 20 blocks in the palette need 5 bits, which don't divide 64
+        let indices = (0..SECTION_BLOCKS as u64).map(|i| (i * 7) % 20).collect::<Vec<_>>();
+        for &spanning in [false, true].iter() {
+            let unpacked = unpack_indices(&pack(&indices, 5, spanning), 20, spanning).unwrap();
+            assert!(unpacked.iter().zip(indices.iter()).all(|(&a, &b)| a as u64 == b));
+        }
+        // Small palettes still use 4 bits
+        let indices = vec![1; SECTION_BLOCKS];
+        assert_eq!(unpack_indices(&pack(&indices, 4, false), 2, false).unwrap(), vec![1; SECTION_BLOCKS]);
+        assert!(unpack_indices(&pack(&indices, 4, false), 1, false).is_err());
+        assert!(unpack_indices(&[], 2, false).is_err());
+    }
+
+    /// A named tag of NBT data
+    fn named(id: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![id];
+        bytes.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn regions_are_read() {
+        let name = named(8, "Name", &[&[0, 15][..], b"minecraft:stone"].concat());
+        let palette = [&[10, 0, 0, 0, 1][..], &name, &[0]].concat();
+        let block_states = [named(9, "palette", &palette), vec![0]].concat();
+        let section = [named(1, "Y", &[255]), named(10, "block_states", &block_states), vec![0]].concat();
+        let root = [
+            named(3, "DataVersion", &3000i32.to_be_bytes()),
+            named(3, "xPos", &1i32.to_be_bytes()),
+            named(3, "zPos", &(-1i32).to_be_bytes()),
+            named(9, "sections", &[&[10, 0, 0, 0, 1][..], &section].concat()),
+            vec![0],
+        ]
+        .concat();
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&named(10, "", &root), 6);
+
+        // The column is in the third sector, after the header
+        let mut region = vec![0; 2 * SECTOR_SIZE];
+        region[5 * 4..5 * 4 + 4].copy_from_slice(&[0, 0, 2, 1]);
+        region.extend_from_slice(&(compressed.len() as u32 + 1).to_be_bytes());
+        region.push(2);
+        region.extend_from_slice(&compressed);
+        region.resize(3 * SECTOR_SIZE, 0);
+        // A column that points outside of the file is skipped
+        region[7 * 4..7 * 4 + 4].copy_from_slice(&[0, 0, 9, 1]);
+
+        let columns = read_region(&region).unwrap();
+        assert_eq!(columns.len(), 1);
+        let column = &columns[0];
+        assert_eq!((column.x, column.z), (1, -1));
+        assert_eq!(column.sections.len(), 1);
+        assert_eq!(column.sections[0].y, -1);
+        assert_eq!(column.sections[0].palette, vec!["minecraft:stone".to_owned()]);
+        assert!(column.sections[0].blocks.iter().all(|&block| block == 0));
+    }
+
+    #[test]
+    fn empty_palettes_are_rejected() {
+        let block_states = [named(9, "palette", &[10, 0, 0, 0, 0]), vec![0]].concat();
+        let section = [named(1, "Y", &[0]), named(10, "block_states", &block_states), vec![0]].concat();
+        let root = [
+            named(3, "xPos", &0i32.to_be_bytes()),
+            named(3, "zPos", &0i32.to_be_bytes()),
+            named(9, "sections", &[&[10, 0, 0, 0, 1][..], &section].concat()),
+            vec![0],
+        ]
+        .concat();
+        let tag = read_nbt(&named(10, "", &root)).unwrap();
+        assert!(read_column(&tag).is_err());
+    }
+
+    #[test]
+    fn gzip_headers_are_skipped() {
+        let header = [0x1f, 0x8b, 8, 8, 0, 0, 0, 0, 0, 0, b'a', 0, 42];
+        assert_eq!(skip_gzip_header(&header).unwrap(), &[42]);
+        assert!(skip_gzip_header(&header[..5]).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Converting the columns of the region files into chunks, following a table of block names.
+use crate::anvil::{Column, SECTION_SIZE};
+use anyhow::{Context, Result};
+use history_survival_common::{
+    block::BlockId,
+    world::{BlockPos, Chunk, ChunkPos},
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The mapping file that is used if no other file is given on the command line
+pub const DEFAULT_MAPPING_FILE: &str = "importer/mappings/minecraft.ron";
+
+/// The blocks of the game that the external blocks become
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BlockMapping {
+    /// The names of the blocks of the game, by name of the external block.
+    /// The external blocks that are not listed become air.
+    pub blocks: HashMap<String, String>,
+}
+
+/// Load a block mapping file
+pub fn load_mapping(path: &str) -> Result<BlockMapping> {
+    let string = std::fs::read_to_string(Path::new(path)).context(format!("Failed to read mapping file {}", path))?;
+    ron::de::from_str(&string).context(format!("Failed to parse mapping file {}", path))
+}
+
+/// Converts columns into chunks. The block ids of the chunks are only valid with the block names of the converter.
+pub struct Converter {
+    mapping: BlockMapping,
+    /// The names of the blocks of the game, by id in the converted chunks
+    block_names: Vec<String>,
+    /// The id in the converted chunks of every external block
+    block_ids: HashMap<String, BlockId>,
+    /// Number of converted blocks that were not in the mapping, by external name
+    unmapped: HashMap<String, usize>,
+}
+
+impl Converter {
+    pub fn new(mapping: BlockMapping) -> Self {
+        Self {
+            mapping,
+            block_names: vec!["air".to_owned()],
+            block_ids: HashMap::new(),
+            unmapped: HashMap::new(),
+        }
+    }
+
+    /// The names of the blocks by id in the converted chunks
+    pub fn block_names(&self) -> &[String] {
+        &self.block_names
+    }
+
+    /// The external blocks that became air because they were not in the mapping, with their number, most frequent first
+    pub fn unmapped_blocks(&self) -> Vec<(&str, usize)> {
+        let mut unmapped = self.unmapped.iter().map(|(name, count)| (name.as_str(), *count)).collect::<Vec<_>>();
+        unmapped.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        unmapped
+    }
+
+    /// The id of the block of the game that an external block becomes
+    fn block_id(&mut self, external_name: &str) -> BlockId {
+        if let Some(&id) = self.block_ids.get(external_name) {
+            return id;
+        }
+        let id = match self.mapping.blocks.get(external_name) {
+            Some(name) => match self.block_names.iter().position(|known| known == name) {
+                Some(id) => id as BlockId,
+                None => {
+                    self.block_names.push(name.clone());
+                    (self.block_names.len() - 1) as BlockId
+                }
+            },
+            None => 0,
+        };
+        self.block_ids.insert(external_name.to_owned(), id);
+        id
+    }
+
+    /// Convert some columns into the chunks that contain their sections.
+    /// Every chunk must be converted at once, so all the columns of the chunks must be given together.
+    pub fn convert(&mut self, columns: &[Column]) -> Vec<Chunk> {
+        let mut chunks: HashMap<ChunkPos, Chunk> = HashMap::new();
+        for column in columns {
+            for section in column.sections.iter() {
+                let ids = section.palette.iter().map(|name| self.block_id(name)).collect::<Vec<_>>();
+                let origin = BlockPos::from((column.x * SECTION_SIZE, section.y * SECTION_SIZE, column.z * SECTION_SIZE));
+                for (i, &index) in section.blocks.iter().enumerate() {
+                    let i = i as i64;
+                    let pos = origin.offset(i % SECTION_SIZE, i / (SECTION_SIZE * SECTION_SIZE), (i / SECTION_SIZE) % SECTION_SIZE);
+                    let chunk_pos = pos.containing_chunk_pos();
+                    let chunk = chunks.entry(chunk_pos).or_insert_with(|| Chunk::new(chunk_pos));
+                    let id = ids[index as usize];
+                    chunk.set_block_at(pos.pos_in_containing_chunk(), id);
+                    if id == 0 && !self.mapping.blocks.contains_key(&section.palette[index as usize]) {
+                        *self.unmapped.entry(section.palette[index as usize].clone()).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        chunks.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anvil::Section;
+
+    #[test]
+    fn default_mapping_is_valid() {
+        let mapping = load_mapping(concat!(env!("CARGO_MANIFEST_DIR"), "/mappings/minecraft.ron")).unwrap();
+        assert_eq!(mapping.blocks.get("minecraft:grass_block").map(String::as_str), Some("dirt_grass"));
+    }
+
+    #[test]
+    fn blocks_are_mapped() {
+        let mut mapping = BlockMapping::default();
+        mapping.blocks.insert("external:rock".to_owned(), "stone".to_owned());
+        mapping.blocks.insert("external:air".to_owned(), "air".to_owned());
+        let mut converter = Converter::new(mapping);
+        let mut blocks = vec![0; 4096];
+        // The block at x = 1, y = 2, z = 3 of the section
+        blocks[(2 * 16 + 3) * 16 + 1] = 1;
+        blocks[5] = 2;
+        let column = Column {
+            x: -1,
+            z: 2,
+            sections: vec![Section {
+                y: 3,
+                palette: vec!["external:air".to_owned(), "external:rock".to_owned(), "external:flower".to_owned()],
+                blocks,
+            }],
+        };
+        let chunks = converter.convert(&[column]);
+        assert_eq!(chunks.len(), 1);
+        let chunk = &chunks[0];
+        let pos = BlockPos::from((-16 + 1, 48 + 2, 32 + 3));
+        assert_eq!(chunk.pos, pos.containing_chunk_pos());
+        let stone = chunk.get_block_at(pos.pos_in_containing_chunk());
+        assert_eq!(converter.block_names()[stone as usize], "stone");
+        assert_eq!(chunk.data.iter().filter(|&&block| block != 0).count(), 1);
+        assert_eq!(converter.unmapped_blocks(), vec![("external:flower", 1)]);
+    }
+}
@@ -0,0 +1,75 @@
+//! An importer of the worlds of other voxel games, to bring existing builds into the world of the server.
+//!
+//! Usage: `history_survival_importer <region directory> [block mapping file]`.
+//! The Anvil region files of the directory are converted with the block mapping into the chunks of `saves/world`,
+//! replacing the chunks that were already saved there. The rest of the world is still generated as usual.
+use anyhow::{Context, Result};
+use history_survival_server::save_chunks;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+mod anvil;
+mod convert;
+mod nbt;
+
+use anvil::{read_region, Column};
+use convert::{load_mapping, Converter, DEFAULT_MAPPING_FILE};
+
+/// Number of columns of a region file along each side of a chunk
+const COLUMNS_PER_CHUNK: i64 = history_survival_common::world::CHUNK_SIZE as i64 / anvil::SECTION_SIZE;
+/// Number of unmapped blocks listed at the end of the import
+const LISTED_UNMAPPED_BLOCKS: usize = 20;
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let region_directory = args
+        .next()
+        .context("Usage: history_survival_importer <region directory> [block mapping file]")?;
+    let mapping_file = args.next().unwrap_or_else(|| DEFAULT_MAPPING_FILE.to_owned());
+    let mut converter = Converter::new(load_mapping(&mapping_file)?);
+
+    let mut region_paths = std::fs::read_dir(&region_directory)
+        .context(format!("Failed to read the region directory {}", region_directory))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|extension| extension == "mca").unwrap_or(false))
+        .collect::<Vec<PathBuf>>();
+    region_paths.sort();
+    info!("Importing {} region files from {}", region_paths.len(), region_directory);
+
+    let mut total_chunks = 0;
+    for path in region_paths {
+        let columns = match std::fs::read(&path).map_err(anyhow::Error::from).and_then(|bytes| read_region(&bytes)) {
+            Ok(columns) => columns,
+            Err(e) => {
+                warn!("Skipping the region file {}: {:?}", path.display(), e);
+                continue;
+            }
+        };
+        // The chunks are wider than the columns, so the columns are converted by groups that fill whole chunks
+        let mut groups: HashMap<(i64, i64), Vec<Column>> = HashMap::new();
+        for column in columns {
+            let key = (column.x.div_euclid(COLUMNS_PER_CHUNK), column.z.div_euclid(COLUMNS_PER_CHUNK));
+            groups.entry(key).or_default().push(column);
+        }
+        let mut region_chunks = 0;
+        for (_, group) in groups {
+            let chunks = converter.convert(&group);
+            region_chunks += save_chunks(chunks.iter(), converter.block_names())?;
+        }
+        info!("Imported {} chunks from {}", region_chunks, path.display());
+        total_chunks += region_chunks;
+    }
+    info!("Imported {} chunks", total_chunks);
+
+    let unmapped = converter.unmapped_blocks();
+    if !unmapped.is_empty() {
+        warn!("{} kinds of blocks are not in {} and became air:", unmapped.len(), mapping_file);
+        for (name, count) in unmapped.into_iter().take(LISTED_UNMAPPED_BLOCKS) {
+            warn!("{}: {} blocks", name, count);
+        }
+    }
+    Ok(())
+}
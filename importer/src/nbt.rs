@@ -0,0 +1,195 @@
+//! A reader of the NBT format, the binary tree of named tags that the region files store the chunks in.
+use anyhow::{bail, ensure, Context, Result};
+use std::collections::HashMap;
+
+/// The maximum nesting of lists and compounds, so that a corrupted file can't overflow the stack
+const MAX_DEPTH: usize = 512;
+
+/// A tag and its payload. The names of the tags are the keys of their compound.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// The tag with some name, if this is a compound that has it
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(tags) => tags.get(name),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer tag of any size
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(value) => Some(value as i64),
+            Tag::Short(value) => Some(value as i64),
+            Tag::Int(value) => Some(value as i64),
+            Tag::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(tags) => Some(tags),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Tag::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Read the root compound of some uncompressed NBT data
+pub fn read_nbt(bytes: &[u8]) -> Result<Tag> {
+    let mut reader = Reader { bytes, position: 0 };
+    let id = reader.read_u8()?;
+    ensure!(id == 10, "The root tag is not a compound but has id {}", id);
+    reader.read_string().context("Failed to read the name of the root tag")?;
+    reader.read_payload(id, 0)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        ensure!(self.bytes.len() - self.position >= length, "Unexpected end of the data at byte {}", self.position);
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    /// The length of an array or a list, which can't be negative
+    fn read_length(&mut self) -> Result<usize> {
+        let length = i32::from_be_bytes(self.read_array()?);
+        ensure!(length >= 0, "Negative length {} at byte {}", length, self.position);
+        Ok(length as usize)
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let length = u16::from_be_bytes(self.read_array()?) as usize;
+        // The strings are in a modified UTF-8 that only differs for the characters that the block names don't use
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+
+    fn read_payload(&mut self, id: u8, depth: usize) -> Result<Tag> {
+        ensure!(depth < MAX_DEPTH, "The tags are nested too deeply");
+        Ok(match id {
+            1 => Tag::Byte(self.read_u8()? as i8),
+            2 => Tag::Short(i16::from_be_bytes(self.read_array()?)),
+            3 => Tag::Int(i32::from_be_bytes(self.read_array()?)),
+            4 => Tag::Long(i64::from_be_bytes(self.read_array()?)),
+            5 => Tag::Float(f32::from_be_bytes(self.read_array()?)),
+            6 => Tag::Double(f64::from_be_bytes(self.read_array()?)),
+            7 => {
+                let length = self.read_length()?;
+                Tag::ByteArray(self.take(length)?.iter().map(|&byte| byte as i8).collect())
+            }
+            8 => Tag::String(self.read_string()?),
+            9 => {
+                let element_id = self.read_u8()?;
+                let length = self.read_length()?;
+                // Empty lists may have the id of the end tag
+                if length > 0 {
+                    ensure!(element_id != 0, "List of end tags at byte {}", self.position);
+                }
+                let mut tags = Vec::new();
+                for _ in 0..length {
+                    tags.push(self.read_payload(element_id, depth + 1)?);
+                }
+                Tag::List(tags)
+            }
+            10 => {
+                let mut tags = HashMap::new();
+                loop {
+                    let id = self.read_u8()?;
+                    if id == 0 {
+                        break;
+                    }
+                    let name = self.read_string()?;
+                    tags.insert(name, self.read_payload(id, depth + 1)?);
+                }
+                Tag::Compound(tags)
+            }
+            11 => {
+                let length = self.read_length()?;
+                let mut values = Vec::new();
+                for _ in 0..length {
+                    values.push(i32::from_be_bytes(self.read_array()?));
+                }
+                Tag::IntArray(values)
+            }
+            12 => {
+                let length = self.read_length()?;
+                let mut values = Vec::new();
+                for _ in 0..length {
+                    values.push(i64::from_be_bytes(self.read_array()?));
+                }
+                Tag::LongArray(values)
+            }
+            _ => bail!("Unknown tag id {} at byte {}", id, self.position),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compounds_are_read() {
+        let mut bytes = vec![10, 0, 0];
+        // Int "a" = 5
+        bytes.extend_from_slice(&[3, 0, 1, b'a', 0, 0, 0, 5]);
+        // List "b" of 2 strings
+        bytes.extend_from_slice(&[9, 0, 1, b'b', 8, 0, 0, 0, 2, 0, 1, b'x', 0, 2, b'y', b'z']);
+        // Long array "c" = [-1]
+        bytes.extend_from_slice(&[12, 0, 1, b'c', 0, 0, 0, 1, 255, 255, 255, 255, 255, 255, 255, 255]);
+        bytes.push(0);
+        let root = read_nbt(&bytes).unwrap();
+        assert_eq!(root.get("a").and_then(Tag::as_i64), Some(5));
+        let list = root.get("b").and_then(Tag::as_list).unwrap();
+        assert_eq!(list.iter().filter_map(Tag::as_str).collect::<Vec<_>>(), vec!["x", "yz"]);
+        assert_eq!(root.get("c").and_then(Tag::as_long_array), Some(&[-1][..]));
+
+        assert!(read_nbt(&bytes[..bytes.len() - 3]).is_err());
+        assert!(read_nbt(&[3, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+//! Server-side triggers of the advancements that depend on the passing time and on the positions of the players.
+use history_survival_common::{advancement::AdvancementEvent, player::PlayerId, time::WorldTime};
+use nalgebra::Vector3;
+use std::collections::HashSet;
+
+/// Watches the nights and the positions of the players
+pub struct AdvancementTriggers {
+    /// Whether it was night during the last update
+    was_night: bool,
+    /// The players who have been connected and alive since the nightfall
+    night_survivors: HashSet<PlayerId>,
+}
+
+impl AdvancementTriggers {
+    pub fn new() -> Self {
+        Self {
+            was_night: false,
+            night_survivors: HashSet::new(),
+        }
+    }
+
+    /// A player died or disconnected, so it doesn't survive the current night
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.night_survivors.remove(&player);
+    }
+
+    /// Find the events of the players at some positions. The players who joined during the night don't survive it.
+    pub fn update(&mut self, world_time: WorldTime, players: impl Iterator<Item = (PlayerId, Vector3<f64>)>) -> Vec<(PlayerId, AdvancementEvent)> {
+        let is_night = world_time.is_night();
+        let mut events = Vec::new();
+        let mut connected_players = HashSet::new();
+        for (player, pos) in players {
+            connected_players.insert(player);
+            events.push((player, AdvancementEvent::ReachedHeight(pos.y.floor() as i64)));
+        }
+        if is_night && !self.was_night {
+            self.night_survivors = connected_players;
+        } else if !is_night && self.was_night {
+            events.extend(self.night_survivors.drain().map(|player| (player, AdvancementEvent::NightSurvived)));
+        }
+        self.was_night = is_night;
+        events
+    }
+}
+
@@ -0,0 +1,274 @@
+//! Tracking of the chunks sent to every client, to detect the clients that can't keep up.
+//!
+//! The clients acknowledge the chunks they receive. The server measures the delivery latency and the number of
+//! chunks that were sent but not acknowledged yet. When a client falls behind for too long, its effective render
+//! distance is reduced, and it is restored once the connection recovers.
+//!
+//! The server also periodically sends the content hashes of a few acknowledged chunks, so that the client can
+//! request the chunks that got out of sync again.
+use history_survival_common::world::ChunkPos;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the buckets of the latency histogram, in milliseconds. The last bucket has no upper bound.
+const LATENCY_BUCKETS_MS: [u64; 5] = [50, 100, 250, 500, 1000];
+/// Maximum number of chunks that were sent but not acknowledged.
+/// No more chunks are sent to a client past this limit, so that the server doesn't buffer too much data for it.
+pub const MAX_PENDING_CHUNKS: usize = 256;
+/// Time between two evaluations of the connection of a client
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(5);
+/// Number of consecutive slow evaluations after which the render distance is reduced
+const SLOW_EVALUATIONS_BEFORE_REDUCING: u32 = 2;
+/// Number of consecutive healthy evaluations after which the render distance is restored by one step
+const HEALTHY_EVALUATIONS_BEFORE_RESTORING: u32 = 3;
+/// The connection is slow if 90% of the deliveries are not faster than this
+const SLOW_LATENCY: Duration = Duration::from_millis(1000);
+/// The connection is healthy if 90% of the deliveries are faster than this
+const HEALTHY_LATENCY: Duration = Duration::from_millis(250);
+/// Maximum reduction of the render distance, in chunks
+const MAX_REDUCTION: u64 = 8;
+/// Time between two spot-checks of the chunks of a client
+const HASH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Number of chunks whose hash is sent in a spot-check
+pub const CHUNKS_PER_HASH_CHECK: usize = 8;
+
+/// A histogram of delivery latencies
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    counts: [u32; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+impl LatencyHistogram {
+    /// Record a latency
+    pub fn record(&mut self, latency: Duration) {
+        let ms = latency.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| ms < bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+    }
+
+    /// Number of recorded latencies
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// Get the upper bound of the bucket that contains the given fraction of the latencies, or `None` if nothing was
+    /// recorded. `Duration::MAX` is returned for the last bucket.
+    pub fn percentile(&self, fraction: f64) -> Option<Duration> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        let target = (fraction * total as f64).ceil() as u32;
+        let mut cumulative = 0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= target {
+                return Some(match LATENCY_BUCKETS_MS.get(i) {
+                    Some(&bound) => Duration::from_millis(bound),
+                    None => Duration::MAX,
+                });
+            }
+        }
+        Some(Duration::MAX)
+    }
+
+    /// Describe the histogram for the debug info
+    pub fn describe(&self) -> String {
+        let mut description = String::new();
+        let mut lower = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            match LATENCY_BUCKETS_MS.get(i) {
+                Some(&bound) => description += &format!("{}-{}ms: {}  ", lower, bound, count),
+                None => description += &format!("{}ms+: {}", lower, count),
+            }
+            lower = LATENCY_BUCKETS_MS.get(i).cloned().unwrap_or(lower);
+        }
+        description
+    }
+}
+
+/// A change of the render distance of a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryChange {
+    /// The client is too slow, and its render distance was reduced
+    Reduced,
+    /// The client caught up, and its render distance was increased back
+    Restored,
+}
+
+/// The chunk deliveries of a single client
+pub struct ChunkDelivery {
+    /// The chunks that were sent but not acknowledged, with the time they were sent
+    pending: HashMap<ChunkPos, Instant>,
+    /// The latencies since the last evaluation
+    window: LatencyHistogram,
+    /// All the latencies, for the debug info
+    histogram: LatencyHistogram,
+    /// By how many chunks the render distance of the client is currently reduced
+    reduction: u64,
+    slow_evaluations: u32,
+    healthy_evaluations: u32,
+    last_evaluation: Instant,
+    last_hash_check: Instant,
+}
+
+impl ChunkDelivery {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            pending: HashMap::new(),
+            window: LatencyHistogram::default(),
+            histogram: LatencyHistogram::default(),
+            reduction: 0,
+            slow_evaluations: 0,
+            healthy_evaluations: 0,
+            last_evaluation: now,
+            last_hash_check: now,
+        }
+    }
+
+    /// Number of chunks that can still be sent before reaching the limit of pending chunks
+    pub fn remaining_capacity(&self) -> usize {
+        MAX_PENDING_CHUNKS.saturating_sub(self.pending.len())
+    }
+
+    /// Number of chunks that were sent but not acknowledged
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// By how many chunks the render distance of the client is reduced
+    pub fn get_reduction(&self) -> u64 {
+        self.reduction
+    }
+
+    pub fn get_histogram(&self) -> &LatencyHistogram {
+        &self.histogram
+    }
+
+    /// Whether a chunk was sent but not acknowledged
+    pub fn is_pending(&self, pos: ChunkPos) -> bool {
+        self.pending.contains_key(&pos)
+    }
+
+    /// Whether it is time to spot-check the hashes of some chunks of the client
+    pub fn is_hash_check_due(&mut self, now: Instant) -> bool {
+        if now - self.last_hash_check < HASH_CHECK_INTERVAL {
+            return false;
+        }
+        self.last_hash_check = now;
+        true
+    }
+
+    /// Remember that a chunk was sent
+    pub fn chunk_sent(&mut self, pos: ChunkPos, now: Instant) {
+        self.pending.insert(pos, now);
+    }
+
+    /// Record the chunks that the client acknowledged
+    pub fn chunks_received(&mut self, positions: &[ChunkPos], now: Instant) {
+        for pos in positions {
+            if let Some(sent) = self.pending.remove(pos) {
+                let latency = now - sent;
+                self.window.record(latency);
+                self.histogram.record(latency);
+            }
+        }
+    }
+
+    /// Stop waiting for the acknowledgement of some chunks, because the client didn't receive them
+    pub fn forget_chunks(&mut self, positions: &[ChunkPos]) {
+        for pos in positions {
+            self.pending.remove(pos);
+        }
+    }
+
+    /// Periodically check whether the client keeps up, and change its render distance if necessary
+    pub fn evaluate(&mut self, now: Instant) -> Option<DeliveryChange> {
+        if now - self.last_evaluation < EVALUATION_INTERVAL {
+            return None;
+        }
+        self.last_evaluation = now;
+
+        // Chunks that are pending for longer than the slow latency count as slow deliveries
+        let overdue = self.pending.values().filter(|&&sent| now - sent >= SLOW_LATENCY).count();
+        for _ in 0..overdue {
+            self.window.record(SLOW_LATENCY);
+        }
+        let p90 = self.window.percentile(0.9);
+        self.window = LatencyHistogram::default();
+        let is_slow = self.pending.len() >= MAX_PENDING_CHUNKS / 2 || p90.map(|p90| p90 > SLOW_LATENCY).unwrap_or(false);
+        let is_healthy = self.pending.len() < MAX_PENDING_CHUNKS / 8 && p90.map(|p90| p90 <= HEALTHY_LATENCY).unwrap_or(true);
+
+        if is_slow {
+            self.healthy_evaluations = 0;
+            self.slow_evaluations += 1;
+            if self.slow_evaluations >= SLOW_EVALUATIONS_BEFORE_REDUCING && self.reduction < MAX_REDUCTION {
+                self.slow_evaluations = 0;
+                self.reduction += 1;
+                return Some(DeliveryChange::Reduced);
+            }
+        } else if is_healthy {
+            self.slow_evaluations = 0;
+            self.healthy_evaluations += 1;
+            if self.healthy_evaluations >= HEALTHY_EVALUATIONS_BEFORE_RESTORING && self.reduction > 0 {
+                self.healthy_evaluations = 0;
+                self.reduction -= 1;
+                return Some(DeliveryChange::Restored);
+            }
+        } else {
+            self.slow_evaluations = 0;
+            self.healthy_evaluations = 0;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_percentiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile(0.5), None);
+        for _ in 0..9 {
+            histogram.record(Duration::from_millis(10));
+        }
+        histogram.record(Duration::from_millis(5000));
+        assert_eq!(histogram.percentile(0.5), Some(Duration::from_millis(50)));
+        assert_eq!(histogram.percentile(0.9), Some(Duration::from_millis(50)));
+        assert_eq!(histogram.percentile(1.0), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn slow_clients_are_reduced_then_restored() {
+        let start = Instant::now();
+        let mut delivery = ChunkDelivery::new(start);
+        let pos = |i: i64| ChunkPos::from([i, 0, 0]);
+        // A client that doesn't acknowledge anything
+        for i in 0..MAX_PENDING_CHUNKS as i64 {
+            delivery.chunk_sent(pos(i), start);
+        }
+        assert_eq!(delivery.remaining_capacity(), 0);
+        let mut time = start;
+        let mut evaluate = |delivery: &mut ChunkDelivery| {
+            time += EVALUATION_INTERVAL;
+            delivery.evaluate(time)
+        };
+        assert_eq!(evaluate(&mut delivery), None);
+        assert_eq!(evaluate(&mut delivery), Some(DeliveryChange::Reduced));
+        assert_eq!(delivery.get_reduction(), 1);
+
+        // The client catches up
+        let positions = (0..MAX_PENDING_CHUNKS as i64).map(pos).collect::<Vec<_>>();
+        delivery.chunks_received(&positions, start + Duration::from_millis(20));
+        assert_eq!(delivery.num_pending(), 0);
+        assert_eq!(evaluate(&mut delivery), None);
+        assert_eq!(evaluate(&mut delivery), None);
+        assert_eq!(evaluate(&mut delivery), Some(DeliveryChange::Restored));
+        assert_eq!(delivery.get_reduction(), 0);
+    }
+}
@@ -0,0 +1,151 @@
+//! Compression of the chunks sent to the players, off the tick thread.
+//!
+//! Chunks are compressed by a pool of workers, and the compressed chunks are kept in an LRU cache keyed by chunk
+//! version, so that a chunk sent to multiple players is only compressed once.
+use history_survival_common::{
+    debug::send_debug_info,
+    world::{Chunk, ChunkPos, CompressedChunk, CompressedLightChunk, LightChunk},
+    worker::{Worker, WorkerState},
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Number of compression workers
+const NUM_ENCODING_WORKERS: usize = 2;
+/// Size of the queue of each compression worker
+const ENCODING_QUEUE_SIZE: usize = 20;
+/// Maximum number of compressed chunks kept in the cache
+const CACHE_CAPACITY: usize = 4096;
+
+/// A compressed chunk, ready to be sent
+pub type EncodedChunk = (Arc<CompressedChunk>, Arc<CompressedLightChunk>);
+
+/// A chunk to compress, with its version
+pub struct EncodingInput {
+    chunk: Arc<Chunk>,
+    light_chunk: Arc<LightChunk>,
+    version: u64,
+}
+
+pub struct EncodingState;
+
+impl WorkerState<EncodingInput, (u64, EncodedChunk)> for EncodingState {
+    fn compute(&mut self, input: EncodingInput) -> (u64, EncodedChunk) {
+        (
+            input.version,
+            (
+                Arc::new(CompressedChunk::from_chunk(&input.chunk)),
+                Arc::new(CompressedLightChunk::from_chunk(&input.light_chunk)),
+            ),
+        )
+    }
+}
+
+pub type EncodingWorker = Worker<EncodingInput, (u64, EncodedChunk), EncodingState>;
+
+struct CacheEntry {
+    version: u64,
+    encoded: EncodedChunk,
+    last_use: u64,
+}
+
+/// The compression workers and the cache of compressed chunks
+pub struct ChunkEncoder {
+    workers: Vec<EncodingWorker>,
+    /// The latest compressed version of the chunks
+    cache: HashMap<ChunkPos, CacheEntry>,
+    /// The chunk versions that are being compressed
+    in_progress: HashSet<(ChunkPos, u64)>,
+    /// Incremented at every cache access, to find the least recently used entries
+    use_counter: u64,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+
+impl ChunkEncoder {
+    pub fn new() -> Self {
+        Self {
+            workers: (0..NUM_ENCODING_WORKERS)
+                .map(|i| Worker::new(EncodingState, ENCODING_QUEUE_SIZE, format!("Chunk encoding {}", i)))
+                .collect(),
+            cache: HashMap::new(),
+            in_progress: HashSet::new(),
+            use_counter: 0,
+            cache_hits: 0,
+            cache_misses: 0,
+        }
+    }
+
+    /// Get some version of a chunk compressed. If it is not in the cache, start compressing it and return `None`:
+    /// the chunk will be available in a later tick.
+    pub fn get_encoded(&mut self, chunk: &Arc<Chunk>, light_chunk: &Arc<LightChunk>, version: u64) -> Option<EncodedChunk> {
+        self.use_counter += 1;
+        if let Some(entry) = self.cache.get_mut(&chunk.pos) {
+            if entry.version == version {
+                entry.last_use = self.use_counter;
+                self.cache_hits += 1;
+                return Some(entry.encoded.clone());
+            }
+        }
+        self.cache_misses += 1;
+        if !self.in_progress.contains(&(chunk.pos, version)) {
+            let mut input = EncodingInput {
+                chunk: chunk.clone(),
+                light_chunk: light_chunk.clone(),
+                version,
+            };
+            for worker in self.workers.iter() {
+                match worker.enqueue(input) {
+                    Ok(()) => {
+                        self.in_progress.insert((chunk.pos, version));
+                        break;
+                    }
+                    // Try the next worker
+                    Err(returned_input) => input = returned_input,
+                }
+            }
+        }
+        None
+    }
+
+    /// Fetch the compressed chunks from the workers
+    pub fn get_new_encoded_chunks(&mut self) {
+        for worker in self.workers.iter() {
+            while let Some((version, encoded)) = worker.get_result() {
+                let pos = encoded.0.pos;
+                self.in_progress.remove(&(pos, version));
+                // Keep the most recent version
+                if self.cache.get(&pos).map(|entry| entry.version < version).unwrap_or(true) {
+                    self.cache.insert(
+                        pos,
+                        CacheEntry {
+                            version,
+                            encoded,
+                            last_use: self.use_counter,
+                        },
+                    );
+                }
+            }
+        }
+
+        // Evict the least recently used tenth of the cache
+        if self.cache.len() > CACHE_CAPACITY {
+            let mut last_uses = self.cache.values().map(|entry| entry.last_use).collect::<Vec<_>>();
+            let num_evicted = self.cache.len() - CACHE_CAPACITY + CACHE_CAPACITY / 10;
+            let (_, &mut threshold, _) = last_uses.select_nth_unstable(num_evicted - 1);
+            self.cache.retain(|_, entry| entry.last_use > threshold);
+        }
+
+        send_debug_info(
+            "Chunks",
+            "encoding",
+            format!(
+                "Compressed chunk cache: {} chunks, {} being compressed, {} hits, {} misses",
+                self.cache.len(),
+                self.in_progress.len(),
+                self.cache_hits,
+                self.cache_misses
+            ),
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! The commands of the server, typed in the console by its administrator or in the chat by the players.
+//!
+//! Both are parsed and run by the same dispatcher. Every command has a permission, and the players who are not
+//! operators can only run the commands that everyone can run.
+use crate::validation::MovementValidator;
+use crate::world::World;
+use crate::{spawn_position, standing_position, PlayerData};
+use history_survival_common::{
+    block::{entity::BlockEntity, painting::Painting},
+    network::{messages::ToClient, Server},
+    physics::{player::PlayerPose, simulation::ServerPhysicsSimulation},
+    player::{GameMode, PlayerId},
+    time::{WorldClock, WorldTime, TICKS_PER_DAY},
+    world::BlockPos,
+};
+use log::{info, warn};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A command of the console or of the chat
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// List the commands
+    Help,
+    /// List the paintings of the loaded chunks that display an image
+    ListPaintings,
+    /// Remove the image of the painting at some position
+    RemovePainting(BlockPos),
+    /// Remove the images of all the loaded paintings imported by some player
+    RemovePaintingsBy(String),
+    /// Change the game mode of a connected player
+    SetGameMode(String, GameMode),
+    /// Show the world time
+    ShowTime,
+    /// Set the world time to some number of ticks
+    SetTime(WorldTime),
+    /// Set the time of the current day, keeping the number of days
+    SetTimeOfDay(u64),
+    /// Go back to the world spawn
+    Spawn,
+    /// Remember the position of the player as its home
+    SetHome,
+    /// Go back to the home of the player
+    Home,
+    /// Go to a connected player or to a position
+    Teleport(TeleportTarget),
+}
+
+/// Where a player is teleported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TeleportTarget {
+    Player(String),
+    Position(BlockPos),
+}
+
+/// Who runs a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandSender {
+    /// The administrator, who can run every command
+    Console,
+    Player(PlayerId),
+}
+
+/// Who can run a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Everyone,
+    /// The administrator and the players listed in the configuration of the server
+    Operators,
+    /// The players, because the command acts on the player who runs it
+    Players,
+}
+
+/// The commands with their arguments and their permission, for the help
+const USAGES: [(&str, Permission); 10] = [
+    ("help", Permission::Everyone),
+    ("spawn", Permission::Players),
+    ("home [set]", Permission::Players),
+    ("tp <player id|x y z>", Permission::Operators),
+    ("paintings", Permission::Operators),
+    ("removepainting <x> <y> <z>", Permission::Operators),
+    ("removepaintings <player id>", Permission::Operators),
+    ("gamemode <player id> <survival|creative|spectator>", Permission::Operators),
+    ("time", Permission::Operators),
+    ("time set <ticks|sunrise|day|noon|sunset|night|midnight>", Permission::Operators),
+];
+
+/// The times of day that can be set by name, in ticks since the sunrise
+const NAMED_TIMES: [(&str, u64); 6] = [
+    ("sunrise", 0),
+    ("day", TICKS_PER_DAY / 20),
+    ("noon", TICKS_PER_DAY / 4),
+    ("sunset", TICKS_PER_DAY / 2),
+    ("night", TICKS_PER_DAY * 11 / 20),
+    ("midnight", TICKS_PER_DAY * 3 / 4),
+];
+
+impl Command {
+    pub fn permission(&self) -> Permission {
+        match self {
+            Command::Help => Permission::Everyone,
+            Command::Spawn | Command::SetHome | Command::Home => Permission::Players,
+            Command::ListPaintings
+            | Command::RemovePainting(_)
+            | Command::RemovePaintingsBy(_)
+            | Command::SetGameMode(..)
+            | Command::ShowTime
+            | Command::SetTime(_)
+            | Command::SetTimeOfDay(_)
+            | Command::Teleport(_) => Permission::Operators,
+        }
+    }
+}
+
+impl CommandSender {
+    /// Whether the sender can run the commands with some permission
+    pub fn has_permission(self, permission: Permission, operators: &[String]) -> bool {
+        let is_operator = match self {
+            CommandSender::Console => true,
+            CommandSender::Player(id) => operators.contains(&id.to_string()),
+        };
+        match permission {
+            Permission::Everyone => true,
+            Permission::Operators => is_operator,
+            Permission::Players => matches!(self, CommandSender::Player(_)),
+        }
+    }
+}
+
+/// The help of a sender: the commands that it can run
+pub fn help(sender: CommandSender, operators: &[String]) -> String {
+    let usages = USAGES
+        .iter()
+        .filter(|(_, permission)| sender.has_permission(*permission, operators))
+        .map(|(usage, _)| *usage)
+        .collect::<Vec<_>>();
+    format!("Commands: {}", usages.join(", "))
+}
+
+/// Parse a command. The commands can start with a slash, like in the chat.
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let mut words = line.strip_prefix('/').unwrap_or(line).split_whitespace();
+    let name = words.next().unwrap_or("");
+    let arguments = words.collect::<Vec<_>>();
+    let parse_pos = |x: &str, y: &str, z: &str| -> Result<BlockPos, String> {
+        let parse = |coordinate: &str| {
+            coordinate
+                .parse::<i64>()
+                .map_err(|_| format!("invalid coordinate {}", coordinate))
+        };
+        Ok(BlockPos {
+            px: parse(x)?,
+            py: parse(y)?,
+            pz: parse(z)?,
+        })
+    };
+    match (name, &arguments[..]) {
+        ("help", []) => Ok(Command::Help),
+        ("spawn", []) => Ok(Command::Spawn),
+        ("home", []) => Ok(Command::Home),
+        ("home", ["set"]) => Ok(Command::SetHome),
+        ("tp", [player]) => Ok(Command::Teleport(TeleportTarget::Player(player.to_string()))),
+        ("tp", [x, y, z]) => Ok(Command::Teleport(TeleportTarget::Position(parse_pos(x, y, z)?))),
+        ("paintings", []) => Ok(Command::ListPaintings),
+        ("removepainting", [x, y, z]) => Ok(Command::RemovePainting(parse_pos(x, y, z)?)),
+        ("removepaintings", [player]) => Ok(Command::RemovePaintingsBy(player.to_string())),
+        ("gamemode", [player, mode]) => match GameMode::from_name(mode) {
+            Some(mode) => Ok(Command::SetGameMode(player.to_string(), mode)),
+            None => Err(format!("unknown game mode {}", mode)),
+        },
+        ("time", []) => Ok(Command::ShowTime),
+        ("time", ["set", time]) => match NAMED_TIMES.iter().find(|(name, _)| name == time) {
+            Some(&(_, time_of_day)) => Ok(Command::SetTimeOfDay(time_of_day)),
+            None => time
+                .parse::<u64>()
+                .map(|ticks| Command::SetTime(WorldTime(ticks)))
+                .map_err(|_| format!("invalid time {}", time)),
+        },
+        ("help", _) | ("spawn", _) | ("home", _) | ("tp", _) | ("paintings", _) | ("removepainting", _) | ("removepaintings", _)
+        | ("gamemode", _) | ("time", _) => Err(format!("wrong arguments for {}, type help for the list of commands", name)),
+        _ => Err(format!("unknown command {:?}, type help for the list of commands", line)),
+    }
+}
+
+/// What the commands can act on
+pub struct CommandContext<'a> {
+    pub world: &'a mut World,
+    pub world_clock: &'a mut WorldClock,
+    pub world_spawn: BlockPos,
+    pub players: &'a mut HashMap<PlayerId, PlayerData>,
+    pub physics_simulation: &'a mut ServerPhysicsSimulation,
+    pub movement_validator: &'a mut MovementValidator,
+    pub server: &'a mut dyn Server,
+    /// The ids of the players who can run the commands of the operators
+    pub operators: &'a [String],
+}
+
+/// Parse and run a line of the console or of the chat, and report the result to the sender:
+/// in the logs for the console, and in the chat for the players.
+pub fn execute_command(sender: CommandSender, line: &str, context: &mut CommandContext) {
+    let result = parse_command(line).and_then(|command| run_permitted_command(sender, command, context));
+    match sender {
+        CommandSender::Console => match result {
+            Ok(lines) => lines.iter().for_each(|line| info!("{}", line)),
+            Err(e) => warn!("Console: {}", e),
+        },
+        CommandSender::Player(id) => {
+            info!("Player {} ran the command {:?}", id, line);
+            let lines = result.unwrap_or_else(|e| vec![format!("Can't run the command: {}", e)]);
+            for line in lines {
+                context.server.send(id, ToClient::ChatMessage(line));
+            }
+        }
+    }
+}
+
+/// Run a command if the sender has the permission
+fn run_permitted_command(sender: CommandSender, command: Command, context: &mut CommandContext) -> Result<Vec<String>, String> {
+    if !sender.has_permission(command.permission(), context.operators) {
+        return Err(match command.permission() {
+            Permission::Players => "only the players can run this command".to_owned(),
+            _ => "you don't have the permission to run this command".to_owned(),
+        });
+    }
+    run_command(sender, command, context)
+}
+
+/// Run a command that the sender has the permission for
+fn run_command(sender: CommandSender, command: Command, context: &mut CommandContext) -> Result<Vec<String>, String> {
+    match command {
+        Command::Help => Ok(vec![help(sender, context.operators)]),
+        Command::ListPaintings => {
+            let paintings = context
+                .world
+                .loaded_block_entities()
+                .filter_map(|(pos, block_entity)| match block_entity {
+                    BlockEntity::Painting(Painting {
+                        image: Some(_), author, ..
+                    }) => Some((pos, *author)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let mut lines = vec![format!("{} loaded paintings display an image", paintings.len())];
+            for (pos, author) in paintings {
+                let author = author.map(|author| author.to_string()).unwrap_or_else(|| "unknown".to_owned());
+                lines.push(format!("Painting at {} {} {} by player {}", pos.px, pos.py, pos.pz, author));
+            }
+            Ok(lines)
+        }
+        Command::RemovePainting(pos) => {
+            let removed = remove_painting_images(context.world, |painting_pos, _| painting_pos == pos);
+            if removed == 0 {
+                Err(format!("There is no loaded painting with an image at {} {} {}", pos.px, pos.py, pos.pz))
+            } else {
+                Ok(vec![format!("Removed the image of the painting at {} {} {}", pos.px, pos.py, pos.pz)])
+            }
+        }
+        Command::RemovePaintingsBy(player) => {
+            let removed = remove_painting_images(context.world, |_, painting| {
+                painting.author.map(|author| author.to_string()) == Some(player.clone())
+            });
+            Ok(vec![format!("Removed the images of {} loaded paintings by player {}", removed, player)])
+        }
+        Command::SetGameMode(player, game_mode) => match context.players.iter_mut().find(|(id, _)| id.to_string() == player) {
+            Some((&id, player_data)) => {
+                player_data.game_mode = game_mode;
+                player_data.breaking = None;
+                context.server.send(id, ToClient::GameMode(game_mode));
+                context.server.send(id, ToClient::ChatMessage(format!("Your game mode is now {}", game_mode.name())));
+                Ok(vec![format!("Player {} is now in {} mode", id, game_mode.name())])
+            }
+            None => Err(format!("There is no connected player {}", player)),
+        },
+        Command::ShowTime => {
+            let time = context.world_clock.get_time(Instant::now());
+            let (hours, minutes) = time.hours_minutes();
+            Ok(vec![format!("The world time is {} ticks: day {}, {:02}:{:02}", time.0, time.day() + 1, hours, minutes)])
+        }
+        Command::SetTime(time) => Ok(vec![set_time(time, context)]),
+        Command::SetTimeOfDay(time_of_day) => {
+            let day = context.world_clock.get_time(Instant::now()).day();
+            Ok(vec![set_time(WorldTime(day * TICKS_PER_DAY + time_of_day), context)])
+        }
+        Command::Spawn | Command::SetHome | Command::Home | Command::Teleport(_) => {
+            let id = match sender {
+                CommandSender::Player(id) => id,
+                CommandSender::Console => return Err("only the players can run this command".to_owned()),
+            };
+            run_player_command(id, command, context)
+        }
+    }
+}
+
+/// Run a command that acts on the player who runs it
+fn run_player_command(id: PlayerId, command: Command, context: &mut CommandContext) -> Result<Vec<String>, String> {
+    let player_pos = context
+        .physics_simulation
+        .get_state()
+        .physics_state
+        .players
+        .get(&id)
+        .map(|player| player.aabb.pos)
+        .ok_or_else(|| "you are not in the world".to_owned())?;
+    let destination = match command {
+        Command::Spawn => spawn_position(context.world, context.world_spawn),
+        Command::SetHome => {
+            let side = PlayerPose::Standing.hitbox().side;
+            let home = BlockPos::from(player_pos + Vector3::new(side / 2.0, 0.0, side / 2.0));
+            context.players.get_mut(&id).unwrap().home = Some(home);
+            return Ok(vec![format!("Your home is now at {} {} {}", home.px, home.py, home.pz)]);
+        }
+        Command::Home => match context.players[&id].home {
+            Some(home) => standing_position(home),
+            None => return Err("you don't have a home, set it with /home set".to_owned()),
+        },
+        Command::Teleport(TeleportTarget::Position(pos)) => standing_position(pos),
+        Command::Teleport(TeleportTarget::Player(player)) => context
+            .physics_simulation
+            .get_state()
+            .physics_state
+            .players
+            .iter()
+            .find(|(other, _)| other.to_string() == player)
+            .map(|(_, other)| other.aabb.pos)
+            .ok_or_else(|| format!("There is no connected player {}", player))?,
+        _ => unreachable!("not a player command"),
+    };
+    context.players.get_mut(&id).unwrap().breaking = None;
+    context.physics_simulation.teleport_player(id, destination);
+    // The player was moved by the server, which is not a movement violation
+    context.movement_validator.reset_position(id);
+    Ok(vec![format!(
+        "Teleported to {:.0} {:.0} {:.0}",
+        destination.x, destination.y, destination.z
+    )])
+}
+
+/// Change the world time, and send it to the players right away so that their sky changes immediately
+fn set_time(time: WorldTime, context: &mut CommandContext) -> String {
+    context.world_clock.set_time(time, Instant::now());
+    for &id in context.players.keys() {
+        context.server.send(id, ToClient::TimeUpdate(time));
+    }
+    let (hours, minutes) = time.hours_minutes();
+    format!("Set the world time to {} ticks: day {}, {:02}:{:02}", time.0, time.day() + 1, hours, minutes)
+}
+
+/// Turn the loaded paintings that match the filter back into blank canvases, and return how many were changed
+fn remove_painting_images(world: &mut World, filter: impl Fn(BlockPos, &Painting) -> bool) -> usize {
+    let matching = world
+        .loaded_block_entities()
+        .filter(|(pos, block_entity)| match block_entity {
+            BlockEntity::Painting(painting) => painting.image.is_some() && filter(*pos, painting),
+            _ => false,
+        })
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    for &pos in matching.iter() {
+        let chunk = world.get_chunk(pos.containing_chunk_pos()).unwrap();
+        let mut new_chunk = (*chunk).clone();
+        if let Some(BlockEntity::Painting(painting)) = new_chunk.get_block_entity(pos.pos_in_containing_chunk()) {
+            let blank = Painting {
+                facing: painting.facing,
+                image: None,
+                author: None,
+            };
+            new_chunk.set_block_entity(pos.pos_in_containing_chunk(), Some(BlockEntity::Painting(blank)));
+        }
+        world.set_chunk(Arc::new(new_chunk));
+    }
+    matching.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(parse_command("paintings"), Ok(Command::ListPaintings));
+        assert_eq!(
+            parse_command("removepainting 1 -2  3"),
+            Ok(Command::RemovePainting(BlockPos { px: 1, py: -2, pz: 3 }))
+        );
+        assert_eq!(parse_command("removepaintings 4"), Ok(Command::RemovePaintingsBy("4".to_owned())));
+        assert!(parse_command("removepainting 1 2").is_err());
+        assert!(parse_command("removepainting 1 2 x").is_err());
+        assert_eq!(
+            parse_command("/gamemode 2 creative"),
+            Ok(Command::SetGameMode("2".to_owned(), GameMode::Creative))
+        );
+        assert!(parse_command("gamemode 2 hardcore").is_err());
+        assert_eq!(parse_command("time"), Ok(Command::ShowTime));
+        assert_eq!(parse_command("/time set 1234"), Ok(Command::SetTime(WorldTime(1234))));
+        assert_eq!(parse_command("time set noon"), Ok(Command::SetTimeOfDay(TICKS_PER_DAY / 4)));
+        assert!(parse_command("time set later").is_err());
+        assert!(parse_command("teleport").is_err());
+    }
+
+    #[test]
+    fn player_commands_are_parsed() {
+        assert_eq!(parse_command("/spawn"), Ok(Command::Spawn));
+        assert_eq!(parse_command("/home"), Ok(Command::Home));
+        assert_eq!(parse_command("/home set"), Ok(Command::SetHome));
+        assert!(parse_command("/home sweet").is_err());
+        assert_eq!(parse_command("/tp 3"), Ok(Command::Teleport(TeleportTarget::Player("3".to_owned()))));
+        assert_eq!(
+            parse_command("/tp 1 64 -5"),
+            Ok(Command::Teleport(TeleportTarget::Position(BlockPos { px: 1, py: 64, pz: -5 })))
+        );
+        assert!(parse_command("/tp 1 64").is_err());
+    }
+
+    #[test]
+    fn permissions_are_checked() {
+        let operators = vec!["7".to_owned()];
+        assert!(CommandSender::Console.has_permission(Permission::Operators, &operators));
+        assert!(!CommandSender::Console.has_permission(Permission::Players, &operators));
+        assert_eq!(Command::Teleport(TeleportTarget::Player("1".to_owned())).permission(), Permission::Operators);
+        assert_eq!(Command::Home.permission(), Permission::Players);
+        assert!(!help(CommandSender::Console, &operators).contains("spawn"));
+        assert!(help(CommandSender::Console, &operators).contains("gamemode"));
+    }
+}
@@ -0,0 +1,63 @@
+//! The configuration of the server, written by its administrator.
+use crate::rate_limits::RateLimits;
+use history_survival_common::{block::painting::PaintingLimits, player::GameMode};
+use log::{info, warn};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// The file the configuration is read from
+const CONFIG_FILE: &str = "config/server.ron";
+
+/// The configuration of the server
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// How large the images of the paintings can be
+    pub painting_limits: PaintingLimits,
+    /// The game mode of the players who join for the first time
+    pub default_game_mode: GameMode,
+    /// Duration of a tick of the main loop above which a hitch report is written, in milliseconds
+    pub hitch_threshold_ms: u64,
+    /// How often the players can chat and interact with the world
+    pub rate_limits: RateLimits,
+    /// The ids of the players who can run the commands of the administrator in the chat
+    pub operators: Vec<String>,
+}
+
+impl ServerConfig {
+    pub fn hitch_threshold(&self) -> Duration {
+        Duration::from_millis(self.hitch_threshold_ms)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            painting_limits: Default::default(),
+            default_game_mode: Default::default(),
+            hitch_threshold_ms: 250,
+            rate_limits: Default::default(),
+            operators: Vec::new(),
+        }
+    }
+}
+
+/// Load the configuration of the server, or use the default configuration if there is none or it is invalid
+pub fn load_config() -> ServerConfig {
+    let path = Path::new(CONFIG_FILE);
+    if !path.is_file() {
+        info!("No server configuration at {}, using the default configuration", CONFIG_FILE);
+        return ServerConfig::default();
+    }
+    let parsed = std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|string| ron::de::from_str(&string).map_err(anyhow::Error::from));
+    match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Failed to load the server configuration {}, using the default configuration: {:?}", CONFIG_FILE, e);
+            ServerConfig::default()
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! The server console: commands typed in the terminal of the server by its administrator, for moderation.
+use std::io::BufRead;
+use std::sync::mpsc::{channel, Receiver};
+
+/// Reads the commands from the standard input, in a separate thread
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) if line.trim().is_empty() => {}
+                    // Stop reading once the server stopped listening
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Self { lines }
+    }
+
+    /// The lines that were typed since the last call, which are run by the command dispatcher
+    pub fn receive_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        while let Ok(line) = self.lines.try_recv() {
+            lines.push(line);
+        }
+        lines
+    }
+}
@@ -0,0 +1,120 @@
+//! Server-side management of the dropped items: spawning, pickup and despawning.
+use history_survival_common::{
+    debug::send_debug_info,
+    entity::{EntityId, EntityKind},
+    inventory::ItemStack,
+    physics::entity::PhysicsEntity,
+    physics::simulation::ServerPhysicsSimulation,
+    player::PlayerId,
+};
+use nalgebra::Vector3;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Side of the cubic hitbox of a dropped item
+const DROPPED_ITEM_SIZE: f64 = 0.25;
+/// Maximum horizontal speed given to a dropped item when it spawns, in blocks per second
+const DROP_HORIZONTAL_SPEED: f64 = 1.5;
+/// Vertical speed given to a dropped item when it spawns, in blocks per second
+const DROP_VERTICAL_SPEED: f64 = 4.0;
+/// Maximum distance between the centers of a player and of an item for the player to pick it up
+const PICKUP_RANGE: f64 = 1.5;
+/// Time before a dropped item can be picked up, so that it can be seen flying out of the block
+const PICKUP_DELAY: Duration = Duration::from_millis(500);
+/// Time after which a dropped item that nobody picked up disappears
+const MAX_LIFETIME: Duration = Duration::from_secs(300);
+
+/// The data that the server stores for every dropped item, in addition to its physics.
+struct DroppedItem {
+    stack: ItemStack,
+    spawn_time: Instant,
+}
+
+/// All the dropped items of the world
+pub struct DroppedItems {
+    items: HashMap<EntityId, DroppedItem>,
+}
+
+impl DroppedItems {
+    pub fn new() -> Self {
+        Self { items: HashMap::new() }
+    }
+
+    /// Drop a stack centered on `pos`, with a small random velocity
+    pub fn drop_stack(&mut self, pos: Vector3<f64>, stack: ItemStack, physics_simulation: &mut ServerPhysicsSimulation, now: Instant) {
+        let half_size = Vector3::new(DROPPED_ITEM_SIZE, DROPPED_ITEM_SIZE, DROPPED_ITEM_SIZE) / 2.0;
+        let mut entity = PhysicsEntity::new(
+            EntityKind::Item(stack.item_id),
+            pos - half_size,
+            (DROPPED_ITEM_SIZE, DROPPED_ITEM_SIZE, DROPPED_ITEM_SIZE),
+        );
+        let mut rng = rand::thread_rng();
+        entity.velocity = Vector3::new(
+            rng.gen_range(-DROP_HORIZONTAL_SPEED..=DROP_HORIZONTAL_SPEED),
+            DROP_VERTICAL_SPEED,
+            rng.gen_range(-DROP_HORIZONTAL_SPEED..=DROP_HORIZONTAL_SPEED),
+        );
+        let id = physics_simulation.spawn_entity(entity);
+        self.items.insert(id, DroppedItem { stack, spawn_time: now });
+    }
+
+    /// Let the players pick up the items close to them, and remove the old items.
+    /// `give` tries to insert a stack in the inventory of a player, and returns what didn't fit.
+    /// To be called right after the physics step.
+    pub fn update(
+        &mut self,
+        physics_simulation: &mut ServerPhysicsSimulation,
+        now: Instant,
+        mut give: impl FnMut(PlayerId, ItemStack) -> Option<ItemStack>,
+    ) {
+        let mut removed = Vec::new();
+        {
+            let physics_state = &physics_simulation.get_state().physics_state;
+            for (&id, item) in self.items.iter_mut() {
+                let entity = match physics_state.entities.get(&id) {
+                    Some(entity) => entity,
+                    None => {
+                        removed.push(id);
+                        continue;
+                    }
+                };
+                if now - item.spawn_time > MAX_LIFETIME {
+                    removed.push(id);
+                    continue;
+                }
+                if now - item.spawn_time < PICKUP_DELAY {
+                    continue;
+                }
+                let center = entity.get_center();
+                let nearest_player = physics_state
+                    .players
+                    .iter()
+                    .map(|(player_id, player)| {
+                        let aabb = &player.aabb;
+                        let player_center = aabb.pos + Vector3::new(aabb.size_x, aabb.size_y, aabb.size_z) / 2.0;
+                        (*player_id, (player_center - center).norm())
+                    })
+                    .filter(|(_, distance)| *distance <= PICKUP_RANGE)
+                    .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap());
+                if let Some((player_id, _)) = nearest_player {
+                    match give(player_id, item.stack) {
+                        None => removed.push(id),
+                        Some(remaining) => item.stack = remaining,
+                    }
+                }
+            }
+        }
+
+        for id in removed {
+            self.items.remove(&id);
+            physics_simulation.remove_entity(id);
+        }
+
+        send_debug_info(
+            "Mobs",
+            "dropped items",
+            format!("Dropped items = {}", self.items.len()),
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Server-side farming: tilling the soil with a hoe, planting the crops, growing them with random ticks,
+//! and uprooting them when their soil is removed.
+use crate::world::World;
+use history_survival_common::{
+    block::{
+        farming::{can_grow, Farming, WATER_RANGE},
+        BlockId,
+    },
+    world::{BlockPos, CHUNK_SIZE},
+};
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Number of random blocks of every loaded chunk that are ticked per second
+const RANDOM_TICKS_PER_CHUNK_PER_SECOND: f64 = 240.0;
+/// Maximum time that the random ticks catch up with in a single update, if the server was late, in seconds
+const MAX_CATCH_UP_SECONDS: f64 = 1.0;
+
+/// Ticks random blocks of the loaded chunks, which makes the crops grow
+pub struct RandomTicks {
+    last_update: Instant,
+    /// Fraction of the random ticks that weren't done yet, to tick at a constant rate
+    tick_accumulator: f64,
+}
+
+impl RandomTicks {
+    pub fn new() -> Self {
+        Self {
+            last_update: Instant::now(),
+            tick_accumulator: 0.0,
+        }
+    }
+
+    /// Tick random blocks of every loaded chunk, and grow the ticked crops that have light and water.
+    /// Return the number of crops that grew.
+    pub fn update(&mut self, world: &mut World, farming: &[Farming], water_block: Option<BlockId>, now: Instant) -> usize {
+        let seconds = (now - self.last_update).as_secs_f64().min(MAX_CATCH_UP_SECONDS);
+        self.last_update = now;
+        self.tick_accumulator += seconds * RANDOM_TICKS_PER_CHUNK_PER_SECOND;
+        let ticks = self.tick_accumulator.floor();
+        self.tick_accumulator -= ticks;
+
+        let mut rng = rand::thread_rng();
+        let mut ticked_crops = Vec::new();
+        for chunk in world.loaded_chunks() {
+            for _ in 0..ticks as usize {
+                let pos = (rng.gen_range(0..CHUNK_SIZE), rng.gen_range(0..CHUNK_SIZE), rng.gen_range(0..CHUNK_SIZE));
+                let block = chunk.get_block_at(pos);
+                if let Some(next_stage) = farming.get(block as usize).and_then(|farming| farming.next_stage) {
+                    ticked_crops.push((chunk.pos.block_pos(pos), block, next_stage));
+                }
+            }
+        }
+        let growing_crops = ticked_crops
+            .into_iter()
+            .filter(|&(pos, block, _)| is_growing(world, farming, water_block, pos, block))
+            .collect::<Vec<_>>();
+        for &(pos, _, next_stage) in growing_crops.iter() {
+            set_block(world, pos, next_stage);
+        }
+        growing_crops.len()
+    }
+}
+
+/// Whether the crop at some position stands on its soil, with enough light above it and water close to the soil
+fn is_growing(world: &World, farming: &[Farming], water_block: Option<BlockId>, pos: BlockPos, crop: BlockId) -> bool {
+    let soil_pos = pos.offset(0, -1, 0);
+    let soil = farming.get(crop as usize).and_then(|farming| farming.soil);
+    if soil.is_none() || soil != Some(world.get_block(soil_pos)) {
+        return false;
+    }
+    let has_water = water_block.is_some()
+        && (-WATER_RANGE..=WATER_RANGE)
+            .flat_map(|dx| (-WATER_RANGE..=WATER_RANGE).map(move |dz| (dx, dz)))
+            .any(|(dx, dz)| Some(world.get_block(soil_pos.offset(dx, 0, dz))) == water_block);
+    can_grow(world.get_light(pos.offset(0, 1, 0)), has_water)
+}
+
+/// Replace the block at some position, if its chunk is loaded
+fn set_block(world: &mut World, pos: BlockPos, block: BlockId) {
+    if let Some(chunk) = world.get_chunk(pos.containing_chunk_pos()) {
+        let mut new_chunk = (*chunk).clone();
+        new_chunk.set_block_at(pos.pos_in_containing_chunk(), block);
+        new_chunk.set_block_entity(pos.pos_in_containing_chunk(), None);
+        world.set_chunk(Arc::new(new_chunk));
+    }
+}
+
+/// Till the block at some position with a hoe, if it can be tilled and nothing covers it. Return whether it was tilled.
+pub fn till(world: &mut World, farming: &[Farming], pos: BlockPos) -> bool {
+    let tilled = match farming.get(world.get_block(pos) as usize).and_then(|farming| farming.tilled) {
+        Some(tilled) => tilled,
+        None => return false,
+    };
+    if world.get_block(pos.offset(0, 1, 0)) != 0 {
+        return false;
+    }
+    set_block(world, pos, tilled);
+    true
+}
+
+/// Run the scheduled tick of a block: a crop that lost its soil is uprooted. Return whether the block was uprooted.
+pub fn uproot_if_unplanted(world: &mut World, farming: &[Farming], pos: BlockPos, block: BlockId) -> bool {
+    if can_plant(world, farming, block, pos) {
+        return false;
+    }
+    set_block(world, pos, 0);
+    true
+}
+
+/// Whether a block can be placed at some position: crops can only be planted on their soil
+pub fn can_plant(world: &World, farming: &[Farming], block: BlockId, pos: BlockPos) -> bool {
+    match farming.get(block as usize).and_then(|farming| farming.soil) {
+        Some(soil) => world.get_block(pos.offset(0, -1, 0)) == soil,
+        None => true,
+    }
+}
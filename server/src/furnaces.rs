@@ -0,0 +1,114 @@
+//! Server-side smelting in the furnaces of the loaded chunks.
+use crate::world::World;
+use history_survival_common::{
+    block::{
+        entity::BlockEntity,
+        furnace::{smelt, Furnace, FurnaceProgress},
+    },
+    data::Data,
+    inventory::Inventory,
+    time::TICKS_PER_SECOND,
+    world::BlockPos,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Maximum number of ticks that the furnaces catch up with in a single update, if the server was late
+const MAX_CATCH_UP_TICKS: u32 = 20;
+
+/// What changed in a furnace during an update
+pub struct FurnaceUpdate {
+    pub pos: BlockPos,
+    /// The contents of the furnace
+    pub inventory: Inventory,
+    /// The slots that changed
+    pub changed_slots: Vec<usize>,
+    pub progress: FurnaceProgress,
+}
+
+/// Runs the furnaces of the loaded chunks
+pub struct Furnaces {
+    last_update: Instant,
+    /// Fraction of a tick that didn't pass yet
+    tick_accumulator: f64,
+    /// The progress of the active furnaces. It changes every tick, so it is only written to the block entity
+    /// when the slots of the furnace change or when the furnace stops, instead of modifying the chunk every tick.
+    progress: HashMap<BlockPos, FurnaceProgress>,
+}
+
+impl Furnaces {
+    pub fn new() -> Self {
+        Self {
+            last_update: Instant::now(),
+            tick_accumulator: 0.0,
+            progress: HashMap::new(),
+        }
+    }
+
+    /// The current progress of a furnace
+    pub fn get_progress(&self, pos: BlockPos, furnace: &Furnace) -> FurnaceProgress {
+        self.progress.get(&pos).copied().unwrap_or(furnace.progress)
+    }
+
+    /// Run the furnaces for the ticks that passed since the last update. Return the furnaces that ran.
+    pub fn update(&mut self, world: &mut World, game_data: &Data, now: Instant) -> Vec<FurnaceUpdate> {
+        self.tick_accumulator += (now - self.last_update).as_secs_f64() * TICKS_PER_SECOND;
+        self.last_update = now;
+        let ticks = self.tick_accumulator.floor();
+        self.tick_accumulator -= ticks;
+        let ticks = (ticks as u32).min(MAX_CATCH_UP_TICKS);
+        if ticks == 0 {
+            return Vec::new();
+        }
+
+        let furnaces = world
+            .loaded_block_entities()
+            .filter_map(|(pos, block_entity)| match block_entity {
+                BlockEntity::Furnace(furnace) if furnace.may_be_active() || self.progress.contains_key(&pos) => {
+                    Some((pos, furnace.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Forget the furnaces that were removed or unloaded
+        self.progress.retain(|pos, _| furnaces.iter().any(|(furnace_pos, _)| furnace_pos == pos));
+
+        let mut updates = Vec::with_capacity(furnaces.len());
+        for (pos, furnace) in furnaces {
+            let mut progress = self.get_progress(pos, &furnace);
+            let mut inventory = furnace.to_inventory(&game_data.items);
+            let changed_slots = smelt(&mut inventory, &mut progress, &game_data.smelting, ticks);
+            let stopped = !progress.is_burning();
+            if !changed_slots.is_empty() || stopped {
+                let mut new_furnace = furnace.clone();
+                new_furnace.set_inventory(&inventory, &game_data.items);
+                new_furnace.progress = progress;
+                if new_furnace != furnace {
+                    set_furnace(world, pos, new_furnace);
+                }
+            }
+            if stopped {
+                self.progress.remove(&pos);
+            } else {
+                self.progress.insert(pos, progress);
+            }
+            updates.push(FurnaceUpdate {
+                pos,
+                inventory,
+                changed_slots,
+                progress,
+            });
+        }
+        updates
+    }
+}
+
+/// Replace the furnace block entity at some position
+pub fn set_furnace(world: &mut World, pos: BlockPos, furnace: Furnace) {
+    if let Some(chunk) = world.get_chunk(pos.containing_chunk_pos()) {
+        let mut new_chunk = (*chunk).clone();
+        new_chunk.set_block_entity(pos.pos_in_containing_chunk(), Some(BlockEntity::Furnace(furnace)));
+        world.set_chunk(Arc::new(new_chunk));
+    }
+}
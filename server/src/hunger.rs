@@ -0,0 +1,115 @@
+//! Hunger: the players get exhausted as they move and act, which makes them hungry.
+//! Well-fed players regenerate health, and starving players slowly lose it.
+use history_survival_common::player::{PlayerStats, MAX_HEALTH};
+use nalgebra::Vector3;
+
+/// Exhaustion that makes the player lose one hunger point
+const EXHAUSTION_PER_HUNGER_POINT: f64 = 4.0;
+/// Exhaustion per block walked
+const WALKING_EXHAUSTION: f64 = 0.01;
+/// Exhaustion per block sprinted
+const SPRINTING_EXHAUSTION: f64 = 0.1;
+/// Exhaustion of breaking a block
+pub const BREAKING_EXHAUSTION: f64 = 0.025;
+/// Exhaustion of placing a block
+pub const PLACING_EXHAUSTION: f64 = 0.01;
+/// Exhaustion of throwing a projectile
+pub const THROWING_EXHAUSTION: f64 = 0.1;
+/// Exhaustion of regenerating one health point
+const REGENERATION_EXHAUSTION: f64 = 1.5;
+/// Minimum hunger for the player to regenerate health
+const REGENERATION_MIN_HUNGER: u32 = 18;
+/// Seconds between two health points regenerated or lost to starvation
+const HEALTH_CHANGE_INTERVAL: f64 = 4.0;
+/// Movement in a single tick above which the player is considered teleported, and the movement doesn't exhaust it
+const MAX_TICK_MOVEMENT: f64 = 10.0;
+
+/// The hunger state of a player, in addition to its stats
+pub struct Hunger {
+    /// The accumulated exhaustion, turned into hunger points
+    exhaustion: f64,
+    /// Seconds since the last health point that was regenerated or lost to starvation
+    health_timer: f64,
+    /// The position of the player at the last update
+    last_pos: Option<Vector3<f64>>,
+}
+
+impl Hunger {
+    pub fn new() -> Self {
+        Self {
+            exhaustion: 0.0,
+            health_timer: 0.0,
+            last_pos: None,
+        }
+    }
+
+    /// Exhaust the player because of an action
+    pub fn exhaust(&mut self, exhaustion: f64) {
+        self.exhaustion += exhaustion;
+    }
+
+    /// Exhaust the player because of its movement, and regenerate or starve.
+    /// `pos` is `None` if the player's movement doesn't exhaust it, for example when it's flying or in a vehicle.
+    pub fn update(&mut self, stats: &mut PlayerStats, pos: Option<Vector3<f64>>, sprinting: bool, seconds_delta: f64) {
+        if let (Some(pos), Some(last_pos)) = (pos, self.last_pos) {
+            // Only the horizontal movement counts, so that falling is free
+            let movement = Vector3::new(pos.x - last_pos.x, 0.0, pos.z - last_pos.z).norm();
+            if movement < MAX_TICK_MOVEMENT {
+                let rate = if sprinting { SPRINTING_EXHAUSTION } else { WALKING_EXHAUSTION };
+                self.exhaustion += movement * rate;
+            }
+        }
+        self.last_pos = pos;
+
+        self.health_timer += seconds_delta;
+        if self.health_timer >= HEALTH_CHANGE_INTERVAL {
+            self.health_timer -= HEALTH_CHANGE_INTERVAL;
+            if stats.hunger >= REGENERATION_MIN_HUNGER && stats.health < MAX_HEALTH {
+                stats.health += 1;
+                self.exhaustion += REGENERATION_EXHAUSTION;
+            } else if stats.hunger == 0 {
+                stats.health = stats.health.saturating_sub(1);
+            }
+        }
+
+        while self.exhaustion >= EXHAUSTION_PER_HUNGER_POINT {
+            self.exhaustion -= EXHAUSTION_PER_HUNGER_POINT;
+            stats.hunger = stats.hunger.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunger_depletes_and_changes_health() {
+        let mut hunger = Hunger::new();
+        let mut stats = PlayerStats::default();
+        // Sprinting 45 blocks costs a hunger point, walking 40 blocks doesn't
+        hunger.update(&mut stats, Some(Vector3::zeros()), true, 0.0);
+        for i in 1..=9 {
+            hunger.update(&mut stats, Some(Vector3::new(5.0 * i as f64, 0.0, 0.0)), true, 0.0);
+        }
+        assert_eq!(stats.hunger, 19);
+        for i in 10..=17 {
+            hunger.update(&mut stats, Some(Vector3::new(5.0 * i as f64, 0.0, 0.0)), false, 0.0);
+        }
+        assert_eq!(stats.hunger, 19);
+
+        // Well-fed players regenerate health
+        stats.health = 10;
+        hunger.update(&mut stats, None, false, HEALTH_CHANGE_INTERVAL);
+        assert_eq!(stats.health, 11);
+        // Players that are neither well-fed nor starving keep their health
+        stats.hunger = 5;
+        hunger.update(&mut stats, None, false, HEALTH_CHANGE_INTERVAL);
+        assert_eq!(stats.health, 11);
+        // Starving players lose health
+        stats.hunger = 0;
+        hunger.update(&mut stats, None, false, HEALTH_CHANGE_INTERVAL);
+        assert_eq!(stats.health, 10);
+        assert!(!stats.can_sprint());
+    }
+}
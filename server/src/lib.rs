@@ -1,35 +1,97 @@
+use crate::advancements::AdvancementTriggers;
+use crate::chunk_delivery::{ChunkDelivery, DeliveryChange, CHUNKS_PER_HASH_CHECK};
+use crate::config::load_config;
+use crate::commands::{execute_command, CommandContext, CommandSender};
+use crate::console::Console;
+use crate::dropped_items::DroppedItems;
+use crate::farming::{can_plant, till, uproot_if_unplanted, RandomTicks};
+use crate::furnaces::{set_furnace, Furnaces};
+use crate::hunger::{Hunger, BREAKING_EXHAUSTION, PLACING_EXHAUSTION, THROWING_EXHAUSTION};
+use crate::maps::Maps;
+use crate::mobs::Mobs;
+use crate::persistence::{load_player, save_player, SavedPlayer};
+use crate::projectiles::Projectiles;
+use crate::rate_limits::PlayerRateLimits;
+use crate::validation::MovementValidator;
+use crate::vehicles::Vehicles;
+use crate::weather::WeatherCycle;
 use crate::world::World;
-use anyhow::Result;
-use log::info;
+use crate::world_save::{load_or_choose_spawn_point, OVERRIDES_DIRECTORY};
+use anyhow::{Context, Result};
+use log::{info, warn};
 use nalgebra::Vector3;
-use std::collections::HashMap;
+use rand::seq::IteratorRandom;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Instant;
-use history_survival_common::block::BlockId;
+use std::time::{Duration, Instant};
+use history_survival_common::block::breaking::{can_harvest, get_break_stage, get_break_time};
+use history_survival_common::block::{
+    bed::Bed,
+    chest::ChestSlot,
+    furnace::is_valid_move,
+    entity::BlockEntity,
+    item_frame::{FramedItem, ItemFrame},
+    painting::Painting,
+    Block,
+    BlockId,
+};
 use history_survival_common::physics::aabb::AABB;
 use history_survival_common::physics::player::PhysicsPlayer;
-use history_survival_common::physics::player::YawPitch;
+use history_survival_common::physics::player::{PlayerPose, YawPitch};
 use history_survival_common::{
+    advancement::{AdvancementEvent, PlayerAdvancements},
+    crafting::craft,
     data::load_data,
-    debug::{send_debug_info, send_perf_breakdown},
+    dimension::DropMode,
+    debug::{send_debug_info, send_perf_breakdown, send_time_graph, TIME_GRAPH_SAMPLES},
+    data::Data,
+    inventory::{move_items_between, Inventory, ItemStack, HOTBAR_SIZE, PLAYER_INVENTORY_SIZE},
+    item::{map::MapId, Item, ToolClass, ToolProperties},
+    registry::Registry,
     network::{
-        messages::{ToClient, ToServer},
+        messages::{InteractionAction, InteractionTarget, ToClient, ToServer},
         Server, ServerEvent,
     },
     physics::simulation::ServerPhysicsSimulation,
-    player::{CloseChunks, RenderDistance},
+    player::{CloseChunks, DeathCause, GameMode, PlayerId, PlayerInput, PlayerStats, RenderDistance, MAX_HUNGER},
     world::{
         ChunkPos,
         BlockPos,
     },
     worldgen::DefaultWorldGenerator,
 };
-use history_survival_common::time::BreakdownCounter;
+use history_survival_common::time::{BreakdownCounter, WorldClock, WorldTime, TICKS_PER_DAY};
+use history_survival_common::watchdog::Watchdog;
 
+mod advancements;
+mod chunk_delivery;
+mod chunk_encoding;
+mod commands;
+mod config;
+mod console;
+mod dropped_items;
+mod farming;
+mod furnaces;
+mod hunger;
 mod light;
+mod maps;
+mod mobs;
+mod persistence;
+mod projectiles;
+mod rate_limits;
+mod scheduled_ticks;
+mod validation;
+mod vehicles;
+mod weather;
 mod world;
+mod world_save;
 mod worldgen;
 
+pub use world_save::save_chunks;
+
+/// Maximum distance between the camera of a player and the blocks it interacts with
+const BLOCK_REACH: f64 = 10.0;
+
 // TODO: refactor
 const D: [[i64; 3]; 6] = [
     [1, 0, 0],
@@ -46,6 +108,93 @@ pub struct PlayerData {
     render_distance: RenderDistance,
     close_chunks: CloseChunks,
     block_to_place: BlockId,
+    /// The position the player's compass points to
+    compass_target: BlockPos,
+    /// How the player plays the game
+    game_mode: GameMode,
+    inventory: Inventory,
+    /// The selected slot of the hotbar
+    hotbar_slot: usize,
+    /// The chunks sent to the player that it didn't acknowledge yet
+    chunk_delivery: ChunkDelivery,
+    /// The block the player started breaking, and when
+    breaking: Option<(BlockPos, Instant)>,
+    /// The last validated input of the player
+    input: PlayerInput,
+    /// The health and hunger of the player
+    stats: PlayerStats,
+    hunger: Hunger,
+    /// What killed the player, `None` while it is alive. Dead players don't move until they respawn.
+    death: Option<DeathCause>,
+    /// The maps whose canvas was sent to the player, and that are kept up to date because the player has a copy
+    known_maps: HashSet<MapId>,
+    /// The bed the player respawns at, `None` for the world spawn
+    spawn_point: Option<BlockPos>,
+    /// Where the player goes with `/home`
+    home: Option<BlockPos>,
+    sleeping: Option<Sleep>,
+    /// The chest the player has open, whose changes are sent to the player
+    open_chest: Option<BlockPos>,
+    /// The furnace the player has open, whose changes and progress are sent to the player
+    open_furnace: Option<BlockPos>,
+    advancements: PlayerAdvancements,
+    /// How often the player can chat and interact with the world
+    rate_limits: PlayerRateLimits,
+    /// The progress of the generation of the spawn area that was last sent to the joining player, `None` once the
+    /// whole area is generated. It starts at (0, 0) so that the first progress is sent.
+    spawn_area_progress: Option<(u32, u32)>,
+}
+
+/// A player sleeping in a bed
+#[derive(Debug, Clone, Copy)]
+struct Sleep {
+    bed: BlockPos,
+    /// Where the player lay down, moving away from it wakes the player up
+    pos: Vector3<f64>,
+    since: Instant,
+}
+
+impl PlayerData {
+    /// The render distance the chunks are sent for, reduced if the player can't keep up
+    fn effective_render_distance(&self) -> RenderDistance {
+        self.render_distance.reduced(self.chunk_delivery.get_reduction())
+    }
+
+    /// The block placed by the item in the selected hotbar slot, if any
+    fn get_held_block(&self, game_data: &Data) -> Option<BlockId> {
+        let stack = self.inventory.get(self.hotbar_slot)?;
+        let block_name = game_data.items.get_value_by_id(stack.item_id)?.get_placed_block()?;
+        game_data.blocks.get_id_by_name(block_name).map(|id| id as BlockId)
+    }
+
+    /// The item in the selected hotbar slot, if any
+    fn get_held_item<'a>(&self, game_data: &'a Data) -> Option<&'a Item> {
+        let stack = self.inventory.get(self.hotbar_slot)?;
+        game_data.items.get_value_by_id(stack.item_id)
+    }
+
+    /// The id of the map in the selected hotbar slot, `Some(0)` for a blank map and `None` if the player doesn't hold a map
+    fn get_held_map(&self, game_data: &Data) -> Option<MapId> {
+        let stack = self.inventory.get(self.hotbar_slot)?;
+        let item = game_data.items.get_value_by_id(stack.item_id)?;
+        if item.is_map() {
+            Some(stack.data)
+        } else {
+            None
+        }
+    }
+
+    /// The tool properties of the item in the selected hotbar slot, `None` for bare hands
+    fn get_held_tool(&self, game_data: &Data) -> Option<ToolProperties> {
+        let stack = self.inventory.get(self.hotbar_slot)?;
+        game_data.items.get_value_by_id(stack.item_id)?.get_tool()
+    }
+
+    /// The hunger points restored by the item in the selected hotbar slot, if it is food
+    fn get_held_food(&self, game_data: &Data) -> Option<u32> {
+        let stack = self.inventory.get(self.hotbar_slot)?;
+        game_data.items.get_value_by_id(stack.item_id)?.get_food()
+    }
 }
 
 impl Default for PlayerData {
@@ -57,10 +206,423 @@ impl Default for PlayerData {
             render_distance,
             close_chunks,
             block_to_place: 1,
+            // Set to the world spawn when the player joins
+            compass_target: BlockPos::from((0, 0, 0)),
+            game_mode: Default::default(),
+            inventory: Inventory::new(PLAYER_INVENTORY_SIZE),
+            hotbar_slot: 0,
+            chunk_delivery: ChunkDelivery::new(Instant::now()),
+            breaking: None,
+            input: Default::default(),
+            stats: Default::default(),
+            hunger: Hunger::new(),
+            death: None,
+            known_maps: HashSet::new(),
+            spawn_point: None,
+            home: None,
+            sleeping: None,
+            open_chest: None,
+            open_furnace: None,
+            advancements: PlayerAdvancements::default(),
+            rate_limits: PlayerRateLimits::new(&Default::default(), Instant::now()),
+            spawn_area_progress: Some((0, 0)),
+        }
+    }
+}
+
+/// Time between two world time updates sent to the players
+const TIME_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+/// Time between two saves of the player data
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// Fraction of the break time that is enough for the server to accept a break, to allow for network jitter
+const BREAK_TIME_TOLERANCE: f64 = 0.8;
+/// Maximum distance between the camera of a player and the center of a banner or a painting for the player to edit it
+const BLOCK_EDIT_RANGE: f64 = 10.0;
+/// Time between two updates of the maps held by the players
+const MAP_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+/// How long all the players have to sleep for the night to be skipped
+const SLEEP_DURATION: Duration = Duration::from_secs(2);
+/// Distance from where a sleeping player lay down after which it wakes up, in blocks
+const MAX_SLEEP_DISTANCE: f64 = 1.0;
+/// Maximum number of characters of a chat message, the rest is cut
+const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+
+/// The position of the players that spawn at the world spawn: on the highest block of the column if it is loaded,
+/// in case something was built on the spawn point or dug below it
+fn spawn_position(world: &World, world_spawn: BlockPos) -> Vector3<f64> {
+    let y = match world.get_highest_block(world_spawn.px, world_spawn.pz) {
+        Some((highest, _)) => highest + 1,
+        None => world_spawn.py,
+    };
+    standing_position(BlockPos::from((world_spawn.px, y, world_spawn.pz)))
+}
+
+/// The position of a player standing in the middle of the block at `pos`
+fn standing_position(pos: BlockPos) -> Vector3<f64> {
+    let side = PlayerPose::Standing.hitbox().side;
+    Vector3::new(pos.px as f64 + 0.5 - side / 2.0, pos.py as f64, pos.pz as f64 + 0.5 - side / 2.0)
+}
+
+/// Bring a dead player back to life at its bed or at the world spawn, with full health and hunger.
+/// The bed is trusted if its chunk is not loaded, and forgotten if it was removed.
+fn respawn_player(
+    id: PlayerId,
+    player_data: &mut PlayerData,
+    world: &World,
+    world_spawn: BlockPos,
+    physics_simulation: &mut ServerPhysicsSimulation,
+    movement_validator: &mut MovementValidator,
+    server: &mut dyn Server,
+) {
+    player_data.stats = Default::default();
+    player_data.hunger = Hunger::new();
+    player_data.breaking = None;
+    player_data.open_chest = None;
+    player_data.open_furnace = None;
+    let bed = player_data.spawn_point.filter(|&bed| {
+        world.get_chunk(bed.containing_chunk_pos()).is_none() || matches!(world.get_block_entity(bed), Some(BlockEntity::Bed(_)))
+    });
+    if player_data.spawn_point.is_some() && bed.is_none() {
+        server.send(id, ToClient::ChatMessage("Your bed is missing, you respawned at the world spawn".to_owned()));
+    }
+    player_data.spawn_point = bed;
+    let (spawn_point, pos) = match bed {
+        Some(bed) => (bed, standing_position(bed.offset(0, 1, 0))),
+        None => (world_spawn, spawn_position(world, world_spawn)),
+    };
+    physics_simulation.teleport_player(id, pos);
+    // The player was moved by the server, which is not a movement violation
+    movement_validator.reset_position(id);
+    server.send(id, ToClient::PlayerStats(player_data.stats));
+    server.send(id, ToClient::Respawn(spawn_point));
+    info!("Player {} respawned", id);
+}
+
+/// Kill a player, who stops where it died until it respawns
+fn kill_player(
+    id: PlayerId,
+    player_data: &mut PlayerData,
+    cause: DeathCause,
+    physics_simulation: &mut ServerPhysicsSimulation,
+    advancement_triggers: &mut AdvancementTriggers,
+    server: &mut dyn Server,
+) {
+    advancement_triggers.remove_player(id);
+    player_data.death = Some(cause);
+    player_data.breaking = None;
+    player_data.input = PlayerInput::default();
+    physics_simulation.set_player_input(id, player_data.input);
+    server.send(id, ToClient::Died(cause));
+    info!("Player {} died: {:?}", id, cause);
+}
+
+/// The inventory of the players that join for the first time
+fn starting_inventory(items: &Registry<Item>) -> Inventory {
+    let mut inventory = Inventory::new(PLAYER_INVENTORY_SIZE);
+    for (name, count) in [("compass", 1), ("clock", 1), ("rock", 16), ("ladder", 16)].iter() {
+        if let Some(item) = items.get_id_by_name(&name.to_string()) {
+            inventory.insert(ItemStack::new(item, *count));
+        }
+    }
+    inventory
+}
+
+/// Send the new contents of some inventory slots to a player
+fn send_inventory_slots(server: &mut dyn Server, id: PlayerId, inventory: &Inventory, slots: &[usize]) {
+    for &slot in slots {
+        server.send(id, ToClient::InventorySlotUpdate(slot, inventory.get(slot)));
+    }
+}
+
+/// Earn the advancements that an event triggers, and tell the player about them
+fn trigger_advancements(server: &mut dyn Server, game_data: &Data, id: PlayerId, player_data: &mut PlayerData, event: AdvancementEvent) {
+    for advancement in player_data.advancements.trigger(&game_data.advancements, event) {
+        server.send(id, ToClient::AdvancementEarned(advancement));
+    }
+}
+
+/// Save the data of a player, logging failures
+fn save_player_data(id: PlayerId, player_data: &PlayerData, game_data: &Data) {
+    let saved_player = SavedPlayer {
+        inventory: player_data.inventory.clone(),
+        stats: player_data.stats,
+        game_mode: player_data.game_mode,
+        spawn_point: player_data.spawn_point,
+        home: player_data.home,
+        advancements: player_data.advancements.clone(),
+    };
+    if let Err(e) = save_player(id, &saved_player, &game_data.items, &game_data.advancements) {
+        warn!("Failed to save player {}: {:?}", id, e);
+    }
+}
+
+/// Replace the block entity at `pos` by the result of `edit`, which is given the current block entity.
+/// Nothing changes if the block is out of reach of the player, if `edit` returns `None`, or if the block doesn't accept the new block entity.
+fn edit_block_entity(
+    world: &mut World,
+    game_data: &Data,
+    physics_simulation: &ServerPhysicsSimulation,
+    id: PlayerId,
+    pos: BlockPos,
+    edit: impl FnOnce(Option<&BlockEntity>) -> Option<BlockEntity>,
+) {
+    if !is_in_edit_range(physics_simulation, id, pos) {
+        return;
+    }
+    let chunk = match world.get_chunk(pos.containing_chunk_pos()) {
+        Some(chunk) => chunk,
+        None => return,
+    };
+    let block_pos = pos.pos_in_containing_chunk();
+    let block_entity = match edit(chunk.get_block_entity(block_pos)) {
+        Some(block_entity) => block_entity,
+        None => return,
+    };
+    let accepts = game_data
+        .blocks
+        .get_value_by_id(chunk.get_block_at(block_pos) as u32)
+        .and_then(|block_data| block_data.get_block_entity_kind())
+        .map(|kind| kind.accepts(&block_entity))
+        .unwrap_or(false);
+    if accepts {
+        let mut new_chunk = (*chunk).clone();
+        new_chunk.set_block_entity(block_pos, Some(block_entity));
+        world.set_chunk(Arc::new(new_chunk));
+    }
+}
+
+/// Whether a block is close enough to a player for the player to edit it
+fn is_in_edit_range(physics_simulation: &ServerPhysicsSimulation, id: PlayerId, pos: BlockPos) -> bool {
+    let player_center = match physics_simulation.get_state().physics_state.players.get(&id) {
+        Some(player) => player.get_camera_position(),
+        None => return false,
+    };
+    let block_center = Vector3::new(pos.px as f64, pos.py as f64, pos.pz as f64).add_scalar(0.5);
+    (block_center - player_center).norm() <= BLOCK_EDIT_RANGE
+}
+
+/// Move items between the inventory of a player and the chest they have open,
+/// and send the changed chest slots to all the players who have the chest open
+fn move_chest_items(
+    world: &mut World,
+    game_data: &Data,
+    server: &mut dyn Server,
+    players: &mut HashMap<PlayerId, PlayerData>,
+    id: PlayerId,
+    from: ChestSlot,
+    to: ChestSlot,
+) {
+    let player_data = players.get_mut(&id).unwrap();
+    let pos = match player_data.open_chest {
+        Some(pos) => pos,
+        None => return,
+    };
+    let mut chest = match world.get_block_entity(pos) {
+        Some(BlockEntity::Chest(chest)) => chest.clone(),
+        _ => return,
+    };
+    let mut chest_inventory = chest.to_inventory(&game_data.items);
+    let inventory = &mut player_data.inventory;
+    let moved = match (from, to) {
+        (ChestSlot::Player(a), ChestSlot::Player(b)) => inventory.move_items(a, b),
+        (ChestSlot::Chest(a), ChestSlot::Chest(b)) => chest_inventory.move_items(a, b),
+        (ChestSlot::Player(a), ChestSlot::Chest(b)) => move_items_between(inventory, a, &mut chest_inventory, b),
+        (ChestSlot::Chest(a), ChestSlot::Player(b)) => move_items_between(&mut chest_inventory, a, inventory, b),
+    };
+    if !moved {
+        return;
+    }
+    let (player_slots, chest_slots) = split_slots(from, to);
+    send_inventory_slots(server, id, inventory, &player_slots);
+    if chest_slots.is_empty() {
+        return;
+    }
+    chest.set_inventory(&chest_inventory, &game_data.items);
+    let chunk = world.get_chunk(pos.containing_chunk_pos()).unwrap();
+    let mut new_chunk = (*chunk).clone();
+    new_chunk.set_block_entity(pos.pos_in_containing_chunk(), Some(BlockEntity::Chest(chest)));
+    world.set_chunk(Arc::new(new_chunk));
+    // The messages are handled one at a time, so the players who share the chest see the changes in the same order
+    for (&viewer, viewer_data) in players.iter() {
+        if viewer_data.open_chest == Some(pos) {
+            for &slot in &chest_slots {
+                server.send(viewer, ToClient::ChestSlotUpdate(slot, chest_inventory.get(slot)));
+            }
+        }
+    }
+}
+
+/// The slots of a move of items that belong to the player, and those that belong to the open chest or furnace
+fn split_slots(from: ChestSlot, to: ChestSlot) -> (Vec<usize>, Vec<usize>) {
+    let mut player_slots = Vec::new();
+    let mut container_slots = Vec::new();
+    for slot in [from, to].iter() {
+        match *slot {
+            ChestSlot::Player(slot) => player_slots.push(slot),
+            ChestSlot::Chest(slot) => container_slots.push(slot),
+        }
+    }
+    (player_slots, container_slots)
+}
+
+/// Move items between the inventory of a player and the furnace they have open, like `move_chest_items`.
+/// Only fuel goes in the fuel slot, and nothing goes in the output slot.
+#[allow(clippy::too_many_arguments)]
+fn move_furnace_items(
+    world: &mut World,
+    game_data: &Data,
+    furnaces: &Furnaces,
+    server: &mut dyn Server,
+    players: &mut HashMap<PlayerId, PlayerData>,
+    id: PlayerId,
+    from: ChestSlot,
+    to: ChestSlot,
+) {
+    let player_data = players.get_mut(&id).unwrap();
+    let pos = match player_data.open_furnace {
+        Some(pos) => pos,
+        None => return,
+    };
+    let mut furnace = match world.get_block_entity(pos) {
+        Some(BlockEntity::Furnace(furnace)) => furnace.clone(),
+        _ => return,
+    };
+    let furnace_inventory = furnace.to_inventory(&game_data.items);
+    let mut new_furnace_inventory = furnace_inventory.clone();
+    let mut new_inventory = player_data.inventory.clone();
+    let moved = match (from, to) {
+        (ChestSlot::Player(a), ChestSlot::Player(b)) => new_inventory.move_items(a, b),
+        (ChestSlot::Chest(a), ChestSlot::Chest(b)) => new_furnace_inventory.move_items(a, b),
+        (ChestSlot::Player(a), ChestSlot::Chest(b)) => move_items_between(&mut new_inventory, a, &mut new_furnace_inventory, b),
+        (ChestSlot::Chest(a), ChestSlot::Player(b)) => move_items_between(&mut new_furnace_inventory, a, &mut new_inventory, b),
+    };
+    if !moved || !is_valid_move(&furnace_inventory, &new_furnace_inventory, &game_data.smelting) {
+        return;
+    }
+    player_data.inventory = new_inventory;
+    let (player_slots, furnace_slots) = split_slots(from, to);
+    send_inventory_slots(server, id, &player_data.inventory, &player_slots);
+    if furnace_slots.is_empty() {
+        return;
+    }
+    furnace.progress = furnaces.get_progress(pos, &furnace);
+    furnace.set_inventory(&new_furnace_inventory, &game_data.items);
+    set_furnace(world, pos, furnace);
+    for (&viewer, viewer_data) in players.iter() {
+        if viewer_data.open_furnace == Some(pos) {
+            for &slot in &furnace_slots {
+                server.send(viewer, ToClient::FurnaceSlotUpdate(slot, new_furnace_inventory.get(slot)));
+            }
         }
     }
 }
 
+/// Put the held item of a player in an empty item frame, or give the item of a full frame back to the player
+fn use_item_frame(
+    world: &mut World,
+    game_data: &Data,
+    server: &mut dyn Server,
+    id: PlayerId,
+    player_data: &mut PlayerData,
+    pos: BlockPos,
+    frame: ItemFrame,
+) {
+    let item = match &frame.item {
+        Some(framed_item) => {
+            // The item is lost if it doesn't exist anymore
+            if let Some(stack) = framed_item.to_stack(&game_data.items) {
+                let (changed_slots, remaining) = player_data.inventory.insert(stack);
+                send_inventory_slots(server, id, &player_data.inventory, &changed_slots);
+                if remaining.is_some() {
+                    return;
+                }
+            }
+            None
+        }
+        None => {
+            let slot = player_data.hotbar_slot;
+            let stack = match player_data.inventory.get(slot) {
+                Some(stack) => stack,
+                None => return,
+            };
+            // Framing an item uses it up, except in creative mode
+            if !player_data.game_mode.has_infinite_blocks() {
+                player_data.inventory.remove(slot, 1);
+                send_inventory_slots(server, id, &player_data.inventory, &[slot]);
+            }
+            FramedItem::from_stack(stack, &game_data.items)
+        }
+    };
+    set_item_frame(world, pos, ItemFrame { item, ..frame });
+}
+
+fn set_item_frame(world: &mut World, pos: BlockPos, frame: ItemFrame) {
+    let chunk = world.get_chunk(pos.containing_chunk_pos()).unwrap();
+    let mut new_chunk = (*chunk).clone();
+    new_chunk.set_block_entity(pos.pos_in_containing_chunk(), Some(BlockEntity::ItemFrame(frame)));
+    world.set_chunk(Arc::new(new_chunk));
+}
+
+/// Set the player who sleeps in a bed, if the bed is still there
+fn set_bed_sleeper(world: &mut World, pos: BlockPos, sleeper: Option<PlayerId>) {
+    if let Some(BlockEntity::Bed(_)) = world.get_block_entity(pos) {
+        let chunk = world.get_chunk(pos.containing_chunk_pos()).unwrap();
+        let mut new_chunk = (*chunk).clone();
+        new_chunk.set_block_entity(pos.pos_in_containing_chunk(), Some(BlockEntity::Bed(Bed { sleeper })));
+        world.set_chunk(Arc::new(new_chunk));
+    }
+}
+
+/// Wake a player up, freeing its bed
+fn wake_up(world: &mut World, player_data: &mut PlayerData) {
+    if let Some(sleep) = player_data.sleeping.take() {
+        set_bed_sleeper(world, sleep.bed, None);
+    }
+}
+
+/// The current pose of a player in the simulation
+fn player_pose(physics_simulation: &ServerPhysicsSimulation, id: PlayerId) -> PlayerPose {
+    physics_simulation
+        .get_state()
+        .physics_state
+        .players
+        .get(&id)
+        .map(|player| player.pose)
+        .unwrap_or_default()
+}
+
+/// A player at the position and with the view that it reported, to find its camera
+fn view_player(player_pos: Vector3<f64>, yaw: f64, pitch: f64, pose: PlayerPose) -> PhysicsPlayer {
+    PhysicsPlayer {
+        aabb: AABB {
+            pos: player_pos,
+            size_x: 0.0,
+            size_y: 0.0,
+            size_z: 0.0,
+        },
+        velocity: Vector3::zeros(),
+        yaw_pitch: YawPitch { yaw, pitch },
+        pose,
+        knockback_velocity: Vector3::zeros(),
+        vehicle: None,
+    }
+}
+
+/// The block and the face that a player sees with its view, if it is in reach
+fn get_pointed_block(physics_player: &PhysicsPlayer, world: &World) -> Option<(BlockPos, usize)> {
+    let YawPitch { yaw, pitch } = physics_player.yaw_pitch;
+    let (y, p) = (yaw.to_radians(), pitch.to_radians());
+    let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+    physics_player.get_pointed_at(dir, BLOCK_REACH, world)
+}
+
+/// Whether a block is close enough to the camera of a player to interact with it
+fn is_in_reach(camera_pos: Vector3<f64>, block: BlockPos) -> bool {
+    let block_center = Vector3::new(block.px as f64, block.py as f64, block.pz as f64).add_scalar(0.5);
+    // The reach is measured to the surface of the block
+    (block_center - camera_pos).norm() <= BLOCK_REACH + 3f64.sqrt() / 2.0
+}
+
 /// Start a new server instance.
 pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
     info!("Starting server");
@@ -68,19 +630,52 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
     let mut server_timing = BreakdownCounter::new();
 
     // Load data
-    let game_data = load_data("data".into())?;
+    let game_data = load_data("data".into(), Some(OVERRIDES_DIRECTORY.into()))?;
+    let config = load_config();
 
+    // TODO: support more than one dimension
+    let dimension = game_data
+        .dimensions
+        .get_id_by_name(&"overworld".to_owned())
+        .and_then(|id| game_data.dimensions.get_value_by_id(id))
+        .context("couldn't find the overworld dimension")?;
+    let mut world_generator = DefaultWorldGenerator::new(&game_data.blocks.clone());
+    let world_spawn = load_or_choose_spawn_point(&mut world_generator);
     let mut world = World::new(
         game_data.blocks.clone(),
-        Box::new(DefaultWorldGenerator::new(&game_data.blocks.clone())),
+        Box::new(world_generator),
+        dimension.ty.clone(),
     );
     let mut players = HashMap::new();
     let mut physics_simulation = ServerPhysicsSimulation::new();
+    let mut mobs = Mobs::new(game_data.mobs.clone(), &game_data.blocks);
+    let mut projectiles = Projectiles::new();
+    let mut dropped_items = DroppedItems::new();
+    let mut maps = Maps::new();
+    let mut last_map_update = Instant::now();
+    let mut vehicles = Vehicles::new();
+    let mut movement_validator = MovementValidator::new();
+    // Start in the morning
+    let mut world_clock = WorldClock::new(WorldTime(TICKS_PER_DAY / 20));
+    let mut last_time_update = Instant::now();
+    // The cracks of the blocks that the players are breaking, as last sent to the players
+    let mut last_break_stages = Vec::new();
+    let mut last_autosave = Instant::now();
+    let mut last_stats_update = Instant::now();
+    let mut weather = WeatherCycle::new();
+    let mut random_ticks = RandomTicks::new();
+    let mut furnaces = Furnaces::new();
+    let mut advancement_triggers = AdvancementTriggers::new();
     let mut close_chunks_merged = Vec::new();
+    let lodestone_block = game_data.blocks.get_id_by_name(&"lodestone".to_owned()).map(|id| id as BlockId);
+    let water_block = game_data.blocks.get_id_by_name(&"water".to_owned()).map(|id| id as BlockId);
+    let console = Console::new();
+    let mut watchdog = Watchdog::new("server", config.hitch_threshold());
 
     info!("Server initialized successfully! Starting server loop");
     loop {
         server_timing.start_frame();
+        watchdog.start_frame();
 
         // Handle messages
         loop {
@@ -89,110 +684,503 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                 ServerEvent::ClientConnected(id) => {
                     info!("Client connected to the server!");
                     physics_simulation.set_player_input(id, Default::default());
-                    players.insert(id, PlayerData::default());
+                    physics_simulation.teleport_player(id, spawn_position(&world, world_spawn));
+                    let new_player = || SavedPlayer {
+                        inventory: starting_inventory(&game_data.items),
+                        stats: Default::default(),
+                        game_mode: config.default_game_mode,
+                        spawn_point: None,
+                        home: None,
+                        advancements: Default::default(),
+                    };
+                    let saved_player = match load_player(id, &game_data.items, &game_data.advancements, config.default_game_mode) {
+                        Ok(Some(saved_player)) => saved_player,
+                        Ok(None) => new_player(),
+                        Err(e) => {
+                            warn!("Failed to load player {}: {:?}", id, e);
+                            new_player()
+                        }
+                    };
+                    let SavedPlayer {
+                        inventory,
+                        stats,
+                        game_mode,
+                        spawn_point,
+                        home,
+                        advancements,
+                    } = saved_player;
+                    players.insert(id, PlayerData {
+                        inventory: inventory.clone(),
+                        stats,
+                        game_mode,
+                        spawn_point,
+                        home,
+                        advancements,
+                        compass_target: world_spawn,
+                        rate_limits: PlayerRateLimits::new(&config.rate_limits, Instant::now()),
+                        ..Default::default()
+                    });
+                    movement_validator.add_player(id);
                     server.send(id, ToClient::GameData(game_data.clone()));
                     server.send(id, ToClient::CurrentId(id));
+                    server.send(id, ToClient::CompassTarget(world_spawn));
+                    server.send(id, ToClient::TimeUpdate(world_clock.get_time(Instant::now())));
+                    server.send(id, ToClient::WeatherUpdate(weather.current()));
+                    server.send(id, ToClient::BreakStages(last_break_stages.clone()));
+                    server.send(id, ToClient::Inventory(inventory));
+                    server.send(id, ToClient::PlayerStats(stats));
+                    server.send(id, ToClient::GameMode(game_mode));
+                    server.send(id, ToClient::PaintingLimits(config.painting_limits));
                 }
                 ServerEvent::ClientDisconnected(id) => {
                     physics_simulation.remove(id);
-                    players.remove(&id);
+                    advancement_triggers.remove_player(id);
+                    if let Some(mut player_data) = players.remove(&id) {
+                        wake_up(&mut world, &mut player_data);
+                        save_player_data(id, &player_data, &game_data);
+                    }
+                    movement_validator.remove_player(id);
+                }
+                // Spectators only watch the world
+                ServerEvent::ClientMessage(id, message) if message.changes_world() && !players[&id].game_mode.can_edit_world() => {}
+                // Dead players wait on the death screen until they respawn
+                ServerEvent::ClientMessage(id, message) if players[&id].death.is_some() && !message.is_allowed_while_dead() => {}
+                // The players who chat or interact too often are slowed down
+                ServerEvent::ClientMessage(id, message) if !players.get_mut(&id).unwrap().rate_limits.allow(&message, Instant::now()) => {
+                    if let Some(feedback) = players.get_mut(&id).unwrap().rate_limits.take_feedback() {
+                        server.send(id, ToClient::ChatMessage(feedback.to_owned()));
+                    }
                 }
                 ServerEvent::ClientMessage(id, message) => match message {
                     ToServer::UpdateInput(input) => {
                         assert!(players.contains_key(&id));
+                        let player_data = players.get_mut(&id).unwrap();
+                        let mut input = movement_validator.validate_input(id, input, player_data.game_mode);
+                        // Hungry players are too weak to sprint
+                        input.sprinting &= player_data.stats.can_sprint();
+                        player_data.input = input;
                         physics_simulation.set_player_input(id, input);
                     }
                     ToServer::SetRenderDistance(render_distance) => {
                         assert!(players.contains_key(&id));
-                        players.entry(id).and_modify(move |player_data| {
-                            player_data.render_distance = render_distance
-                        });
+                        let player_data = players.get_mut(&id).unwrap();
+                        player_data.render_distance = render_distance;
+                        if player_data.chunk_delivery.get_reduction() > 0 {
+                            server.send(id, ToClient::RenderDistanceLimit(player_data.effective_render_distance()));
+                        }
                     }
-                    ToServer::BreakBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
-                        let physics_player = PhysicsPlayer {
-                            aabb: AABB {
-                                pos: player_pos,
-                                size_x: 0.0,
-                                size_y: 0.0,
-                                size_z: 0.0,
-                            },
-                            velocity: Vector3::zeros(),
-                            yaw_pitch: YawPitch {yaw: yaw, pitch: pitch},
-                        };
-                        let y = yaw.to_radians();
-                        let p = pitch.to_radians();
-                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-                        // TODO: don't hardcode max dist
-                        if let Some((block, _face)) =
-                            physics_player.get_pointed_at(dir, 10.0, &world)
-                        {
-                            let chunk_pos = block.containing_chunk_pos();
-                            if let Some(chunk) = world.get_chunk(chunk_pos) {
-                                let mut new_chunk = (*chunk).clone();
-                                new_chunk.set_block_at(block.pos_in_containing_chunk(), 0);
-                                world.set_chunk(Arc::new(new_chunk));
+                    ToServer::StartBreaking(player_pos, block) => {
+                        if !movement_validator.check_reported_position(id, player_pos, &physics_simulation) {
+                            continue;
+                        }
+                        let camera_pos = view_player(player_pos, 0.0, 0.0, player_pose(&physics_simulation, id)).get_camera_position();
+                        let player_data = players.get_mut(&id).unwrap();
+                        player_data.breaking = Some(block).filter(|&block| is_in_reach(camera_pos, block)).map(|block| (block, Instant::now()));
+                    }
+                    ToServer::StopBreaking => {
+                        players.get_mut(&id).unwrap().breaking = None;
+                    }
+                    ToServer::Interact { player_pos, yaw, pitch, target, action, hand } => {
+                        if hand >= HOTBAR_SIZE || !movement_validator.check_reported_position(id, player_pos, &physics_simulation) {
+                            continue;
+                        }
+                        players.get_mut(&id).unwrap().hotbar_slot = hand;
+                        let physics_player = view_player(player_pos, yaw, pitch, player_pose(&physics_simulation, id));
+                        let camera_pos = physics_player.get_camera_position();
+                        // Only the block that the player sees in reach can be interacted with, not the blocks behind walls
+                        if let InteractionTarget::Block(block, face) = target {
+                            if get_pointed_block(&physics_player, &world) != Some((block, face)) {
+                                continue;
+                            }
+                        }
+                        match (action, target) {
+                            // Attacking a block breaks it
+                            (InteractionAction::Attack, InteractionTarget::Block(block, _)) => {
+                                let chunk_pos = block.containing_chunk_pos();
+                                if let Some(chunk) = world.get_chunk(chunk_pos) {
+                                    let broken_block = chunk.get_block_at(block.pos_in_containing_chunk());
+                                    // The player must have been breaking this block for long enough with the held tool
+                                    let player_data = players.get_mut(&id).unwrap();
+                                    let tool = player_data.get_held_tool(&game_data);
+                                    let block_data = game_data.blocks.get_value_by_id(broken_block as u32).unwrap();
+                                    let break_time = match get_break_time(block_data, tool) {
+                                        Some(break_time) => break_time,
+                                        None => continue,
+                                    };
+                                    let game_mode = player_data.game_mode;
+                                    match player_data.breaking.take() {
+                                        _ if game_mode.breaks_instantly() => {}
+                                        Some((breaking_block, start)) if breaking_block == block => {
+                                            if Instant::now() - start < break_time.mul_f64(BREAK_TIME_TOLERANCE) {
+                                                continue;
+                                            }
+                                        }
+                                        _ => continue,
+                                    }
+                                    // The item of a broken item frame and the contents of a broken chest or furnace are dropped
+                                    let contained_items = match chunk.get_block_entity(block.pos_in_containing_chunk()) {
+                                        Some(BlockEntity::ItemFrame(frame)) => frame.item.iter().filter_map(|item| item.to_stack(&game_data.items)).collect(),
+                                        Some(BlockEntity::Chest(chest)) => chest.stacks(&game_data.items),
+                                        Some(BlockEntity::Furnace(furnace)) => furnace.stacks(&game_data.items),
+                                        _ => Vec::new(),
+                                    };
+                                    let mut new_chunk = (*chunk).clone();
+                                    new_chunk.set_block_at(block.pos_in_containing_chunk(), 0);
+                                    new_chunk.set_block_entity(block.pos_in_containing_chunk(), None);
+                                    world.set_chunk(Arc::new(new_chunk));
+                                    // The block above reacts to the broken block during the next tick
+                                    world.schedule_tick(block.offset(0, 1, 0), 1, 0);
+                                    let player_data = players.get_mut(&id).unwrap();
+                                    player_data.hunger.exhaust(BREAKING_EXHAUSTION);
+                                    trigger_advancements(&mut *server, &game_data, id, player_data, AdvancementEvent::BlockBroken);
+                                    // The broken block yields its drops if the tool can harvest it,
+                                    // in the inventory of the player or as dropped items
+                                    let drops = if can_harvest(block_data, tool) && !game_mode.breaks_instantly() {
+                                        game_data.block_drops.get(broken_block as usize).and_then(|drops| drops.roll(rand::random()))
+                                    } else {
+                                        None
+                                    };
+                                    let drops = match (drops, world.get_dimension().drop_mode) {
+                                        (Some(stack), DropMode::Inventory) => {
+                                            let player_data = players.get_mut(&id).unwrap();
+                                            let (changed_slots, remaining) = player_data.inventory.insert(stack);
+                                            send_inventory_slots(&mut *server, id, &player_data.inventory, &changed_slots);
+                                            remaining
+                                        }
+                                        (drops, _) => drops,
+                                    };
+                                    // The items that don't fit in the inventory are dropped too
+                                    let block_center = Vector3::new(block.px as f64, block.py as f64, block.pz as f64).add_scalar(0.5);
+                                    for stack in drops.into_iter().chain(contained_items) {
+                                        dropped_items.drop_stack(block_center, stack, &mut physics_simulation, Instant::now());
+                                    }
+                                }
+                            }
+                            // Picking a block selects it as the block to place in creative mode
+                            (InteractionAction::Pick, InteractionTarget::Block(block, _)) => {
+                                // TODO: careful with more complicated blocks
+                                players.get_mut(&id).unwrap().block_to_place = world.get_block(block);
+                            }
+                            // Using a block opens it or places a block against it
+                            (InteractionAction::Use, InteractionTarget::Block(mut block, face)) => {
+                                // Right-clicking a lodestone binds the compass to it instead of placing a block
+                                if Some(world.get_block(block)) == lodestone_block {
+                                    players.get_mut(&id).unwrap().compass_target = block;
+                                    server.send(id, ToClient::CompassTarget(block));
+                                    continue;
+                                }
+                                // Right-clicking an item frame frames the held item or turns the framed item.
+                                // Crouching players take the framed item back instead.
+                                if let Some(BlockEntity::ItemFrame(mut frame)) = world.get_block_entity(block).cloned() {
+                                    match &mut frame.item {
+                                        Some(framed_item) if physics_player.pose != PlayerPose::Crouching => {
+                                            framed_item.rotate();
+                                            set_item_frame(&mut world, block, frame);
+                                        }
+                                        _ => {
+                                            let player_data = players.get_mut(&id).unwrap();
+                                            use_item_frame(&mut world, &game_data, &mut *server, id, player_data, block, frame);
+                                        }
+                                    }
+                                    continue;
+                                }
+                                // Right-clicking a chest opens it
+                                if let Some(BlockEntity::Chest(chest)) = world.get_block_entity(block) {
+                                    players.get_mut(&id).unwrap().open_chest = Some(block);
+                                    server.send(id, ToClient::OpenChest(block, chest.to_inventory(&game_data.items)));
+                                    continue;
+                                }
+                                // Right-clicking a furnace opens it
+                                if let Some(BlockEntity::Furnace(furnace)) = world.get_block_entity(block) {
+                                    players.get_mut(&id).unwrap().open_furnace = Some(block);
+                                    let progress = furnaces.get_progress(block, furnace);
+                                    server.send(id, ToClient::OpenFurnace(block, furnace.to_inventory(&game_data.items), progress));
+                                    continue;
+                                }
+                                // Right-clicking a bed at night sleeps in it, which also makes it the spawn point of the player
+                                if let Some(BlockEntity::Bed(bed)) = world.get_block_entity(block).cloned() {
+                                    // The sleeper saved in the bed is outdated if the server stopped while they were sleeping
+                                    let occupied = bed
+                                        .sleeper
+                                        .filter(|&sleeper| sleeper != id)
+                                        .and_then(|sleeper| players.get(&sleeper)?.sleeping)
+                                        .map(|sleep| sleep.bed == block)
+                                        .unwrap_or(false);
+                                    let message = if !world.get_dimension().can_sleep {
+                                        "You can't sleep here"
+                                    } else if !world_clock.get_time(Instant::now()).is_night() {
+                                        "You can only sleep at night"
+                                    } else if occupied {
+                                        "This bed is occupied"
+                                    } else {
+                                        let player_data = players.get_mut(&id).unwrap();
+                                        wake_up(&mut world, player_data);
+                                        player_data.spawn_point = Some(block);
+                                        player_data.sleeping = Some(Sleep {
+                                            bed: block,
+                                            pos: player_pos,
+                                            since: Instant::now(),
+                                        });
+                                        set_bed_sleeper(&mut world, block, Some(id));
+                                        "Respawn point set, sleeping..."
+                                    };
+                                    server.send(id, ToClient::ChatMessage(message.to_owned()));
+                                    continue;
+                                }
+                                // Right-clicking with a hoe tills the soil instead of placing a block
+                                let player_data = players.get_mut(&id).unwrap();
+                                if player_data.get_held_tool(&game_data).map(|tool| tool.class) == Some(ToolClass::Hoe) {
+                                    if till(&mut world, &game_data.block_farming, block) {
+                                        player_data.hunger.exhaust(PLACING_EXHAUSTION);
+                                    }
+                                    continue;
+                                }
+                                // Place the block of the held item, or in creative mode the last selected block if the held item is not a block
+                                let player_data = players.get(&id).unwrap();
+                                let held_block = player_data.get_held_block(&game_data);
+                                let block_to_place = match held_block {
+                                    Some(block) => block,
+                                    None if player_data.game_mode.has_infinite_blocks() => player_data.block_to_place,
+                                    None => continue,
+                                };
+                                // Placed water evaporates in some dimensions
+                                if Some(block_to_place) == water_block && world.get_dimension().water_evaporates {
+                                    continue;
+                                }
+                                block.px += D[face][0];
+                                block.py += D[face][1];
+                                block.pz += D[face][2];
+                                // The placed block only replaces air and the replaceable blocks, not the chests or the furnaces
+                                let replaced_block = game_data.blocks.get_value_by_id(world.get_block(block) as u32);
+                                if !replaced_block.map(Block::is_replaceable).unwrap_or(false) {
+                                    continue;
+                                }
+                                // Crops are only planted on their soil
+                                if !can_plant(&world, &game_data.block_farming, block_to_place, block) {
+                                    continue;
+                                }
+                                let chunk_pos = block.containing_chunk_pos();
+                                if let Some(chunk) = world.get_chunk(chunk_pos) {
+                                    let mut new_chunk = (*chunk).clone();
+                                    new_chunk.set_block_at(block.pos_in_containing_chunk(), block_to_place);
+                                    let block_entity = game_data
+                                        .blocks
+                                        .get_value_by_id(block_to_place as u32)
+                                        .and_then(|block_data| block_data.get_block_entity_kind())
+                                        .map(|kind| kind.create(face));
+                                    new_chunk.set_block_entity(block.pos_in_containing_chunk(), block_entity);
+                                    world.set_chunk(Arc::new(new_chunk));
+                                    let player_data = players.get_mut(&id).unwrap();
+                                    player_data.hunger.exhaust(PLACING_EXHAUSTION);
+                                    // Placing a held block uses it up, except in creative mode
+                                    if held_block.is_some() && !player_data.game_mode.has_infinite_blocks() {
+                                        let slot = player_data.hotbar_slot;
+                                        player_data.inventory.remove(slot, 1);
+                                        send_inventory_slots(&mut *server, id, &player_data.inventory, &[slot]);
+                                    }
+                                }
+                            }
+                            // Using a vehicle rides it, and using a mob can tame it or make a pet sit
+                            (InteractionAction::Use, InteractionTarget::Entity(entity)) => {
+                                if vehicles.mount(id, entity, &mut physics_simulation) {
+                                    movement_validator.reset_position(id);
+                                } else {
+                                    // Feeding a mob uses up the held food
+                                    let player_data = players.get_mut(&id).unwrap();
+                                    let held_item = player_data.get_held_item(&game_data).map(|item| item.name.as_str());
+                                    if mobs.interact(id, entity, camera_pos, held_item, &physics_simulation) {
+                                        let slot = player_data.hotbar_slot;
+                                        player_data.inventory.remove(slot, 1);
+                                        send_inventory_slots(&mut *server, id, &player_data.inventory, &[slot]);
+                                    }
+                                }
                             }
+                            // Using the held item by itself
+                            (InteractionAction::Use, InteractionTarget::Air) => {
+                                // Hungry players eat the held food
+                                let player_data = players.get_mut(&id).unwrap();
+                                if let Some(food) = player_data.get_held_food(&game_data) {
+                                    if player_data.game_mode.has_hunger() && player_data.stats.hunger < MAX_HUNGER {
+                                        player_data.stats.hunger = (player_data.stats.hunger + food).min(MAX_HUNGER);
+                                        let slot = player_data.hotbar_slot;
+                                        player_data.inventory.remove(slot, 1);
+                                        send_inventory_slots(&mut *server, id, &player_data.inventory, &[slot]);
+                                        server.send(id, ToClient::PlayerStats(player_data.stats));
+                                    }
+                                    continue;
+                                }
+                                // Throwable items are thrown one at a time
+                                if player_data.get_held_item(&game_data).map(Item::is_throwable) != Some(true) {
+                                    continue;
+                                }
+                                projectiles.throw(id, camera_pos, yaw, pitch, &mut physics_simulation);
+                                player_data.hunger.exhaust(THROWING_EXHAUSTION);
+                                if !player_data.game_mode.has_infinite_blocks() {
+                                    let slot = player_data.hotbar_slot;
+                                    player_data.inventory.remove(slot, 1);
+                                    send_inventory_slots(&mut *server, id, &player_data.inventory, &[slot]);
+                                }
+                            }
+                            // The entities can't be attacked or picked yet
+                            (InteractionAction::Attack, _) | (InteractionAction::Pick, _) => {}
                         }
                     }
-                    ToServer::SelectBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
-                        let physics_player = PhysicsPlayer {
-                            aabb: AABB {
-                                pos: player_pos,
-                                size_x: 0.0,
-                                size_y: 0.0,
-                                size_z: 0.0,
-                            },
-                            velocity: Vector3::zeros(),
-                            yaw_pitch: YawPitch {yaw: yaw, pitch: pitch},
-                        };
-                        let y = yaw.to_radians();
-                        let p = pitch.to_radians();
-                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-                        // TODO: don't hardcode max dist
-                        if let Some((block, _face)) =
-                            physics_player.get_pointed_at(dir, 10.0, &world)
-                        {
-                            // TODO: careful with more complicated blocks
-                            players.get_mut(&id).unwrap().block_to_place = world.get_block(block);
+                    ToServer::DismountVehicle => {
+                        if vehicles.dismount(id, &world, &mut physics_simulation) {
+                            movement_validator.reset_position(id);
+                        }
+                    }
+                    ToServer::MoveItems(from, to) => {
+                        let inventory = &mut players.get_mut(&id).unwrap().inventory;
+                        if inventory.move_items(from, to) {
+                            send_inventory_slots(&mut *server, id, inventory, &[from, to]);
+                        }
+                    }
+                    ToServer::MoveSomeItems(from, to, count) => {
+                        let inventory = &mut players.get_mut(&id).unwrap().inventory;
+                        if inventory.move_some_items(from, to, count) {
+                            send_inventory_slots(&mut *server, id, inventory, &[from, to]);
+                        }
+                    }
+                    ToServer::QuickMoveItems(slot) => {
+                        let inventory = &mut players.get_mut(&id).unwrap().inventory;
+                        let changed_slots = inventory.quick_move(slot);
+                        send_inventory_slots(&mut *server, id, inventory, &changed_slots);
+                    }
+                    ToServer::SwapSlots(a, b) => {
+                        let inventory = &mut players.get_mut(&id).unwrap().inventory;
+                        if inventory.swap(a, b) {
+                            send_inventory_slots(&mut *server, id, inventory, &[a, b]);
                         }
                     }
-                    ToServer::PlaceBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
-                        let physics_player = PhysicsPlayer {
-                            aabb: AABB {
-                                pos: player_pos,
-                                size_x: 0.0,
-                                size_y: 0.0,
-                                size_z: 0.0,
-                            },
-                            velocity: Vector3::zeros(),
-                            yaw_pitch: YawPitch {yaw: yaw, pitch: pitch},
+                    ToServer::Craft(recipe_id) => {
+                        let recipe = match game_data.recipes.get_value_by_id(recipe_id) {
+                            Some(recipe) => recipe,
+                            None => continue,
                         };
-                        let y = yaw.to_radians();
-                        let p = pitch.to_radians();
-                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-                        // TODO: don't hardcode max dist
-                        if let Some((mut block, face)) =
-                        physics_player.get_pointed_at(dir, 10.0, &world)
-                        {
-                            block.px += D[face][0];
-                            block.py += D[face][1];
-                            block.pz += D[face][2];
-                            let chunk_pos = block.containing_chunk_pos();
-                            if let Some(chunk) = world.get_chunk(chunk_pos) {
-                                let mut new_chunk = (*chunk).clone();
-                                new_chunk.set_block_at(block.pos_in_containing_chunk(), players.get(&id).unwrap().block_to_place);
-                                world.set_chunk(Arc::new(new_chunk));
+                        let inventory = &mut players.get_mut(&id).unwrap().inventory;
+                        match craft(inventory, recipe) {
+                            Ok(changed_slots) => send_inventory_slots(&mut *server, id, inventory, &changed_slots),
+                            Err(e) => server.send(id, ToClient::ChatMessage(format!("Can't craft {}: {}", recipe.name, e))),
+                        }
+                    }
+                    ToServer::SetBanner(pos, pattern) => {
+                        edit_block_entity(&mut world, &game_data, &physics_simulation, id, pos, |_| {
+                            Some(BlockEntity::Banner(pattern))
+                        });
+                    }
+                    ToServer::SetPainting(pos, image) => {
+                        if !image.is_valid(config.painting_limits) {
+                            warn!("Player {} sent a painting image that doesn't fit the limits", id);
+                            continue;
+                        }
+                        // Only the image of a painting can change, not the direction it faces
+                        edit_block_entity(&mut world, &game_data, &physics_simulation, id, pos, |current| match current {
+                            Some(BlockEntity::Painting(painting)) => Some(BlockEntity::Painting(Painting {
+                                facing: painting.facing,
+                                image: Some(image),
+                                author: Some(id),
+                            })),
+                            _ => None,
+                        });
+                    }
+                    ToServer::SelectHotbarSlot(slot) => {
+                        if slot < HOTBAR_SIZE {
+                            players.get_mut(&id).unwrap().hotbar_slot = slot;
+                        }
+                    }
+                    ToServer::ChunksReceived(positions) => {
+                        players.get_mut(&id).unwrap().chunk_delivery.chunks_received(&positions, Instant::now());
+                    }
+                    ToServer::RequestChunks(positions) => {
+                        // The chunks will be sent again with the next chunk updates
+                        let player_data = players.get_mut(&id).unwrap();
+                        player_data.chunk_delivery.forget_chunks(&positions);
+                        for pos in positions.iter() {
+                            player_data.loaded_chunks.remove(pos);
+                        }
+                    }
+                    ToServer::RequestMap(map_id) => {
+                        if let Some(canvas) = maps.get(map_id) {
+                            server.send(id, ToClient::MapCanvas(map_id, canvas.clone()));
+                        }
+                    }
+                    ToServer::MoveChestItems(from, to) => {
+                        move_chest_items(&mut world, &game_data, &mut *server, &mut players, id, from, to);
+                    }
+                    ToServer::CloseChest => {
+                        players.get_mut(&id).unwrap().open_chest = None;
+                    }
+                    ToServer::MoveFurnaceItems(from, to) => {
+                        move_furnace_items(&mut world, &game_data, &furnaces, &mut *server, &mut players, id, from, to);
+                    }
+                    ToServer::CloseFurnace => {
+                        players.get_mut(&id).unwrap().open_furnace = None;
+                    }
+                    ToServer::Respawn => {
+                        let player_data = players.get_mut(&id).unwrap();
+                        if player_data.death.take().is_some() {
+                            respawn_player(
+                                id,
+                                player_data,
+                                &world,
+                                world_spawn,
+                                &mut physics_simulation,
+                                &mut movement_validator,
+                                &mut *server,
+                            );
+                        }
+                    }
+                    ToServer::ChatMessage(message) => {
+                        let message: String = message.trim().chars().take(MAX_CHAT_MESSAGE_LENGTH).collect();
+                        if message.starts_with('/') {
+                            let mut context = CommandContext {
+                                world: &mut world,
+                                world_clock: &mut world_clock,
+                                world_spawn,
+                                players: &mut players,
+                                physics_simulation: &mut physics_simulation,
+                                movement_validator: &mut movement_validator,
+                                server: &mut *server,
+                                operators: &config.operators,
+                            };
+                            execute_command(CommandSender::Player(id), &message, &mut context);
+                        } else if !message.is_empty() {
+                            let message = format!("<Player {}> {}", id, message);
+                            info!("[Chat] {}", message);
+                            for &player in players.keys() {
+                                server.send(player, ToClient::ChatMessage(message.clone()));
                             }
                         }
                     }
                 },
             }
         }
+        // The integrated server of a singleplayer world stops once its player left
+        if server.is_closed() {
+            info!("Every player left, saving the world before stopping the server");
+            maps.save();
+            world.save_and_wait();
+            return Ok(());
+        }
         server_timing.record_part("Network events");
 
+        // Run the console commands
+        for line in console.receive_lines() {
+            let mut context = CommandContext {
+                world: &mut world,
+                world_clock: &mut world_clock,
+                world_spawn,
+                players: &mut players,
+                physics_simulation: &mut physics_simulation,
+                movement_validator: &mut movement_validator,
+                server: &mut *server,
+                operators: &config.operators,
+            };
+            execute_command(CommandSender::Console, &line, &mut context);
+        }
+        server_timing.record_part("Console commands");
+
         // Receive generated chunks
         world.get_new_generated_chunks();
         server_timing.record_part("Receive generated chunks");
@@ -201,10 +1189,227 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
         world.get_new_light_chunks();
         server_timing.record_part("Receive lighted chunks");
 
+        // Receive compressed chunks
+        world.get_new_encoded_chunks();
+        server_timing.record_part("Receive compressed chunks");
+
         // Tick game
-        physics_simulation.step_simulation(Instant::now(), &world);
+        mobs.update_spawning(&world, &mut physics_simulation);
+        server_timing.record_part("Spawn and despawn mobs");
+
+        vehicles.update(&world, world_spawn, &mut physics_simulation);
+        server_timing.record_part("Spawn vehicles");
+
+        let now = Instant::now();
+        mobs.update_ai(&world, &mut physics_simulation, now);
+        server_timing.record_part("Update mob AI");
+
+        physics_simulation.step_simulation(now, &world);
+        // The players hit by a projectile lose some health
+        for player in projectiles.update(&mut mobs, &mut physics_simulation, now) {
+            let player_data = match players.get_mut(&player) {
+                Some(player_data) if player_data.game_mode.has_hunger() && player_data.death.is_none() => player_data,
+                _ => continue,
+            };
+            player_data.stats.health = player_data.stats.health.saturating_sub(projectiles::PLAYER_DAMAGE);
+            server.send(player, ToClient::PlayerStats(player_data.stats));
+            if player_data.stats.health == 0 {
+                let cause = DeathCause::Projectile;
+                kill_player(player, player_data, cause, &mut physics_simulation, &mut advancement_triggers, &mut *server);
+            }
+        }
+        dropped_items.update(&mut physics_simulation, now, |player, stack| {
+            // Spectators don't pick up the items
+            let player_data = match players.get_mut(&player) {
+                Some(player_data) if player_data.game_mode.can_edit_world() => player_data,
+                _ => return Some(stack),
+            };
+            let (changed_slots, remaining) = player_data.inventory.insert(stack);
+            send_inventory_slots(&mut *server, player, &player_data.inventory, &changed_slots);
+            remaining
+        });
+        movement_validator.check_displacements(&mut physics_simulation, now);
         server_timing.record_part("Update physics");
 
+        // Grow the crops
+        random_ticks.update(&mut world, &game_data.block_farming, water_block, now);
+        server_timing.record_part("Random ticks");
+
+        // Run the scheduled block ticks
+        for tick in world.take_due_ticks(now) {
+            if uproot_if_unplanted(&mut world, &game_data.block_farming, tick.pos, tick.block) {
+                let drops = game_data.block_drops.get(tick.block as usize).and_then(|drops| drops.roll(rand::random()));
+                let block_center = Vector3::new(tick.pos.px as f64, tick.pos.py as f64, tick.pos.pz as f64).add_scalar(0.5);
+                if let Some(stack) = drops {
+                    dropped_items.drop_stack(block_center, stack, &mut physics_simulation, now);
+                }
+            }
+        }
+        server_timing.record_part("Scheduled ticks");
+
+        // Update the hunger and health of the players
+        let seconds_delta = (now - last_stats_update).as_secs_f64();
+        last_stats_update = now;
+        let gets_hungry = |player_data: &PlayerData| player_data.game_mode.has_hunger() && player_data.death.is_none();
+        for (&player, player_data) in players.iter_mut().filter(|(_, player_data)| gets_hungry(player_data)) {
+            // Only walking exhausts the players
+            let pos = physics_simulation
+                .get_state()
+                .physics_state
+                .players
+                .get(&player)
+                .filter(|physics_player| !player_data.input.flying && physics_player.vehicle.is_none())
+                .map(|physics_player| physics_player.aabb.pos);
+            let old_stats = player_data.stats;
+            player_data
+                .hunger
+                .update(&mut player_data.stats, pos, player_data.input.sprinting, seconds_delta);
+            if player_data.stats != old_stats {
+                server.send(player, ToClient::PlayerStats(player_data.stats));
+            }
+            if player_data.stats.health == 0 {
+                let cause = DeathCause::Starvation;
+                kill_player(player, player_data, cause, &mut physics_simulation, &mut advancement_triggers, &mut *server);
+            }
+        }
+        server_timing.record_part("Update hunger");
+
+        // Wake up the players who moved or whose bed was removed, and skip the night once all the players sleep.
+        // Spectators and dead players can't sleep, so they don't keep the others awake.
+        let world_time = world_clock.get_time(now);
+        for (&player, player_data) in players.iter_mut() {
+            let sleep = match player_data.sleeping {
+                Some(sleep) => sleep,
+                None => continue,
+            };
+            let in_bed = matches!(world.get_block_entity(sleep.bed), Some(BlockEntity::Bed(_)))
+                && physics_simulation
+                    .get_state()
+                    .physics_state
+                    .players
+                    .get(&player)
+                    .map(|physics_player| (physics_player.aabb.pos - sleep.pos).norm() <= MAX_SLEEP_DISTANCE)
+                    .unwrap_or(false);
+            if !in_bed || !world_time.is_night() {
+                wake_up(&mut world, player_data);
+            }
+        }
+        let mut sleepers = players
+            .values()
+            .filter(|player_data| player_data.game_mode.can_edit_world() && player_data.death.is_none())
+            .peekable();
+        let everyone_asleep = sleepers.peek().is_some()
+            && sleepers.all(|player_data| player_data.sleeping.map(|sleep| now - sleep.since >= SLEEP_DURATION).unwrap_or(false));
+        if everyone_asleep {
+            let morning = world_time.next_morning();
+            world_clock.set_time(morning, now);
+            for (&player, player_data) in players.iter_mut() {
+                wake_up(&mut world, player_data);
+                server.send(player, ToClient::TimeUpdate(morning));
+            }
+            info!("All the players slept, the night was skipped");
+        }
+        server_timing.record_part("Update sleep");
+
+        // Trigger the advancements of the passing nights and of the positions of the players. Spectators don't earn advancements.
+        let positions = physics_simulation
+            .get_state()
+            .physics_state
+            .players
+            .iter()
+            .filter(|(player, _)| players.get(player).map(|player_data| player_data.game_mode.can_edit_world()).unwrap_or(false))
+            .map(|(&player, physics_player)| (player, physics_player.aabb.pos));
+        for (player, event) in advancement_triggers.update(world_clock.get_time(now), positions) {
+            if let Some(player_data) = players.get_mut(&player) {
+                trigger_advancements(&mut *server, &game_data, player, player_data, event);
+            }
+        }
+        server_timing.record_part("Trigger advancements");
+
+        // Close the chests that were removed or that are too far from the players who have them open
+        for (&player, player_data) in players.iter_mut() {
+            if let Some(pos) = player_data.open_chest {
+                let is_chest = matches!(world.get_block_entity(pos), Some(BlockEntity::Chest(_)));
+                if !is_chest || !player_data.game_mode.can_edit_world() || !is_in_edit_range(&physics_simulation, player, pos) {
+                    player_data.open_chest = None;
+                    server.send(player, ToClient::CloseChest);
+                }
+            }
+        }
+        server_timing.record_part("Update open chests");
+
+        // Run the furnaces, and send their changes and progress to the players who have them open
+        for update in furnaces.update(&mut world, &game_data, now) {
+            for (&player, player_data) in players.iter() {
+                if player_data.open_furnace == Some(update.pos) {
+                    for &slot in &update.changed_slots {
+                        server.send(player, ToClient::FurnaceSlotUpdate(slot, update.inventory.get(slot)));
+                    }
+                    server.send(player, ToClient::FurnaceProgress(update.progress));
+                }
+            }
+        }
+        // Close the furnaces that were removed or that are too far from the players who have them open
+        for (&player, player_data) in players.iter_mut() {
+            if let Some(pos) = player_data.open_furnace {
+                let is_furnace = matches!(world.get_block_entity(pos), Some(BlockEntity::Furnace(_)));
+                if !is_furnace || !player_data.game_mode.can_edit_world() || !is_in_edit_range(&physics_simulation, player, pos) {
+                    player_data.open_furnace = None;
+                    server.send(player, ToClient::CloseFurnace);
+                }
+            }
+        }
+        server_timing.record_part("Update furnaces");
+
+        // Draw the terrain around the players holding a map, and send the changes to the players who have a copy
+        if now - last_map_update >= MAP_UPDATE_INTERVAL {
+            last_map_update = now;
+            for (&player, player_data) in players.iter_mut() {
+                let map_id = match player_data.get_held_map(&game_data) {
+                    Some(map_id) => map_id,
+                    None => continue,
+                };
+                let player_pos = match physics_simulation.get_state().physics_state.players.get(&player) {
+                    Some(physics_player) => BlockPos::from(physics_player.aabb.pos),
+                    None => continue,
+                };
+                // Blank maps get the canvas of the area around the player, all the maps of the stack become copies of it
+                let map_id = if map_id == 0 {
+                    let map_id = maps.create(player_pos);
+                    let slot = player_data.hotbar_slot;
+                    let stack = player_data.inventory.get(slot).unwrap();
+                    player_data.inventory.set(slot, Some(ItemStack::with_data(stack.item_id, stack.count, map_id)));
+                    send_inventory_slots(&mut *server, player, &player_data.inventory, &[slot]);
+                    map_id
+                } else {
+                    map_id
+                };
+                maps.explore(map_id, player_pos, &world, &game_data.block_colors);
+            }
+            let changes = maps.take_changes();
+            for (&player, player_data) in players.iter_mut() {
+                let copies = player_data
+                    .inventory
+                    .slots()
+                    .flatten()
+                    .filter(|stack| stack.data != 0)
+                    .filter(|stack| game_data.items.get_value_by_id(stack.item_id).map(Item::is_map).unwrap_or(false))
+                    .map(|stack| stack.data)
+                    .collect::<HashSet<_>>();
+                for &map_id in copies.iter() {
+                    if !player_data.known_maps.contains(&map_id) {
+                        if let Some(canvas) = maps.get(map_id) {
+                            server.send(player, ToClient::MapCanvas(map_id, canvas.clone()));
+                        }
+                    } else if let Some(patch) = changes.get(&map_id) {
+                        server.send(player, ToClient::MapUpdate(map_id, patch.clone()));
+                    }
+                }
+                player_data.known_maps = copies;
+            }
+        }
+        server_timing.record_part("Update maps");
+
         // Send physics updates to players
         for (&player, _) in players.iter() {
             server.send(
@@ -214,6 +1419,56 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
         }
         server_timing.record_part("Send physics updates to players");
 
+        // Send the cracks of the blocks that the players are breaking, when they change
+        let break_stages = players
+            .values()
+            .filter(|player_data| !player_data.game_mode.breaks_instantly())
+            .filter_map(|player_data| {
+                let (block, start) = player_data.breaking?;
+                let block_data = game_data.blocks.get_value_by_id(world.get_block(block) as u32)?;
+                let break_time = get_break_time(block_data, player_data.get_held_tool(&game_data))?;
+                Some((block, get_break_stage(now - start, break_time)?))
+            })
+            .collect::<Vec<_>>();
+        if break_stages != last_break_stages {
+            for &player in players.keys() {
+                server.send(player, ToClient::BreakStages(break_stages.clone()));
+            }
+            last_break_stages = break_stages;
+        }
+        server_timing.record_part("Send break stages to players");
+
+        // Send world time to players
+        if now - last_time_update >= TIME_UPDATE_INTERVAL {
+            last_time_update = now;
+            let world_time = world_clock.get_time(now);
+            for &player in players.keys() {
+                server.send(player, ToClient::TimeUpdate(world_time));
+            }
+        }
+        server_timing.record_part("Send world time to players");
+
+        // Save the player data
+        if now - last_autosave >= AUTOSAVE_INTERVAL {
+            last_autosave = now;
+            for (&player, player_data) in players.iter() {
+                save_player_data(player, player_data, &game_data);
+            }
+            maps.save();
+            world.start_save();
+        }
+        world.update_save();
+        server_timing.record_part("Save player data and world");
+
+        // Change the weather
+        if let Some(new_weather) = weather.update(now) {
+            info!("The weather changed to {:?}", new_weather);
+            for &player in players.keys() {
+                server.send(player, ToClient::WeatherUpdate(new_weather));
+            }
+        }
+        server_timing.record_part("Update weather");
+
         // Send chunks to players
         let mut player_positions = Vec::new();
         for (player, data) in players.iter_mut() {
@@ -226,14 +1481,69 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                 .get_camera_position()
             );
             let player_chunk = player_pos.containing_chunk_pos();
-            player_positions.push((player_chunk, data.render_distance));
+            // Adapt the render distance to the speed of the client
+            if let Some(change) = data.chunk_delivery.evaluate(now) {
+                let render_distance = data.effective_render_distance();
+                let note = match change {
+                    DeliveryChange::Reduced => format!(
+                        "Your connection can't keep up with the chunk updates, your render distance was reduced to {} chunks.",
+                        render_distance.x_max
+                    ),
+                    DeliveryChange::Restored => format!(
+                        "Your connection recovered, your render distance was increased to {} chunks.",
+                        render_distance.x_max
+                    ),
+                };
+                info!("Player {}: {:?} render distance to {:?}", player, change, render_distance);
+                server.send(*player, ToClient::RenderDistanceLimit(render_distance));
+                server.send(*player, ToClient::ChatMessage(note));
+            }
+            let render_distance = data.effective_render_distance();
+            player_positions.push((player_chunk, render_distance));
+            // Tell the joining player how much of the terrain around it is ready
+            if let Some(sent_progress) = data.spawn_area_progress {
+                let spawn_area = render_distance.spawn_area().iterate_around_player(player_chunk).collect::<Vec<_>>();
+                let generated = spawn_area.iter().filter(|&&pos| world.is_chunk_loaded(pos)).count() as u32;
+                let total = spawn_area.len() as u32;
+                if (generated, total) != sent_progress {
+                    server.send(*player, ToClient::SpawnAreaProgress(generated, total));
+                }
+                data.spawn_area_progress = Some((generated, total)).filter(|_| generated < total);
+            }
             // Send new chunks
             let updates = world.send_chunks_to_player(player_chunk, data);
             for (chunk, light_chunk) in updates {
+                data.chunk_delivery.chunk_sent(chunk.pos, now);
                 server.send(*player, ToClient::Chunk(chunk, light_chunk));
             }
+            // Spot-check a few of the chunks that the player has, which will be requested again if they are out of sync
+            if data.chunk_delivery.is_hash_check_due(now) {
+                let chunk_delivery = &data.chunk_delivery;
+                let checked_chunks = data
+                    .loaded_chunks
+                    .iter()
+                    .filter(|(pos, _)| !chunk_delivery.is_pending(**pos))
+                    .choose_multiple(&mut rand::thread_rng(), CHUNKS_PER_HASH_CHECK);
+                let hashes = checked_chunks
+                    .into_iter()
+                    .filter_map(|(&pos, &version)| world.get_chunk_hash(pos, version).map(|hash| (pos, hash)))
+                    .collect::<Vec<_>>();
+                if !hashes.is_empty() {
+                    server.send(*player, ToClient::ChunkHashes(hashes));
+                }
+            }
+            send_debug_info(
+                "Chunks",
+                format!("delivery{}", player),
+                format!(
+                    "Player {}: {} pending chunks, render distance reduced by {}\nLatency: {}",
+                    player,
+                    data.chunk_delivery.num_pending(),
+                    data.chunk_delivery.get_reduction(),
+                    data.chunk_delivery.get_histogram().describe(),
+                ),
+            );
             // Drop chunks that are too far away
-            let render_distance = data.render_distance;
             data.loaded_chunks
                 .retain(|chunk_pos, _| render_distance.is_chunk_visible(player_chunk, *chunk_pos));
         }
@@ -241,7 +1551,7 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
 
         // Compute close chunks
         for (_, data) in players.iter_mut() {
-            data.close_chunks.update(&data.render_distance);
+            data.close_chunks.update(&data.effective_render_distance());
         }
         let all_close_chunks = players
             .iter()
@@ -269,12 +1579,16 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
 
         send_debug_info("Chunks", "server",
                         format!(
-                            "Server loaded chunks = {}\nServer loaded chunk columns = {}\n",
+                            "Server loaded chunks = {}\nServer loaded chunk columns = {}\nScheduled block ticks = {}\n",
                             world.num_loaded_chunks(),
                             world.num_loaded_chunk_columns(),
+                            world.num_scheduled_ticks(),
                         ));
 
         // Nothing else to do for now :-)
+        watchdog.end_frame(&server_timing);
+        let tick_times = server_timing.recent_frame_times(TIME_GRAPH_SAMPLES);
+        send_time_graph("Server", "ticktime", "Server tick time", config.hitch_threshold(), tick_times);
         send_perf_breakdown("Server", "mainloop", "Server main loop", server_timing.extract_part_averages());
     }
 }
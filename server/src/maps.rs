@@ -0,0 +1,169 @@
+//! The canvases of the maps. The terrain around the players who hold a map is drawn on its canvas,
+//! and the regions that changed are sent to the players who have a copy of the map.
+use crate::world::World;
+use anyhow::{Context, Result};
+use history_survival_common::{
+    item::map::{MapCanvas, MapId, MapPatch, MapRect},
+    world::BlockPos,
+};
+use log::warn;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::path::PathBuf;
+
+/// The directory where the canvases are saved
+const MAPS_DIRECTORY: &str = "saves/world/maps";
+/// Horizontal distance from the player up to which the terrain is drawn, in blocks
+const EXPLORATION_RADIUS: i64 = 24;
+/// Brightness of the columns that are higher than the column to their north, to show the relief
+const HIGHER_SHADE: f64 = 1.15;
+/// Brightness of the columns that are lower than the column to their north
+const LOWER_SHADE: f64 = 0.8;
+
+fn map_file_path(id: MapId) -> PathBuf {
+    PathBuf::from(MAPS_DIRECTORY).join(format!("{}.ron", id))
+}
+
+/// Load a saved canvas. Return `None` if the map was never saved.
+fn load_canvas(id: MapId) -> Result<Option<MapCanvas>> {
+    let path = map_file_path(id);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let string = std::fs::read_to_string(&path).context(format!("Failed to read map file {}", path.display()))?;
+    let canvas: MapCanvas = ron::de::from_str(&string).context(format!("Failed to parse map file {}", path.display()))?;
+    if !canvas.is_valid() {
+        anyhow::bail!("Map file {} has the wrong number of pixels", path.display());
+    }
+    Ok(Some(canvas))
+}
+
+fn save_canvas(id: MapId, canvas: &MapCanvas) -> Result<()> {
+    let path = map_file_path(id);
+    let string = ron::ser::to_string(canvas).context("Failed to serialize map file")?;
+    std::fs::create_dir_all(MAPS_DIRECTORY).context(format!("Failed to create directory {}", MAPS_DIRECTORY))?;
+    std::fs::write(&path, string).context(format!("Failed to write map file {}", path.display()))?;
+    Ok(())
+}
+
+/// The highest id of the saved maps
+fn highest_saved_id() -> MapId {
+    std::fs::read_dir(MAPS_DIRECTORY)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.path().file_stem()?.to_str()?.parse::<MapId>().ok())
+                .max()
+                .unwrap_or(0)
+        })
+        .unwrap_or(0)
+}
+
+/// Multiply the brightness of a color
+fn shade([r, g, b]: [u8; 3], factor: f64) -> [u8; 3] {
+    [r, g, b].map(|c| (c as f64 * factor).min(255.0) as u8)
+}
+
+/// The canvases of the maps that were used since the server started
+pub struct Maps {
+    canvases: HashMap<MapId, MapCanvas>,
+    /// The regions of the canvases that changed since the last updates were sent
+    changes: HashMap<MapId, MapRect>,
+    /// The canvases that changed since they were saved
+    unsaved: HashSet<MapId>,
+    next_id: MapId,
+}
+
+impl Maps {
+    pub fn new() -> Self {
+        Self {
+            canvases: HashMap::new(),
+            changes: HashMap::new(),
+            unsaved: HashSet::new(),
+            next_id: highest_saved_id() + 1,
+        }
+    }
+
+    /// Create the blank canvas of a new map of the area around `pos`, and return its id
+    pub fn create(&mut self, pos: BlockPos) -> MapId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.canvases.insert(id, MapCanvas::new(pos));
+        self.unsaved.insert(id);
+        id
+    }
+
+    /// The canvas of a map, loaded from the disk if needed
+    pub fn get(&mut self, id: MapId) -> Option<&MapCanvas> {
+        if let Entry::Vacant(entry) = self.canvases.entry(id) {
+            match load_canvas(id) {
+                Ok(Some(canvas)) => {
+                    entry.insert(canvas);
+                }
+                Ok(None) => return None,
+                Err(e) => {
+                    warn!("Failed to load map {}: {:?}", id, e);
+                    return None;
+                }
+            }
+        }
+        self.canvases.get(&id)
+    }
+
+    /// Draw the terrain around a player who holds a map on the canvas of the map
+    pub fn explore(&mut self, id: MapId, player_pos: BlockPos, world: &World, block_colors: &[[u8; 3]]) {
+        if self.get(id).is_none() {
+            return;
+        }
+        let canvas = self.canvases.get_mut(&id).unwrap();
+        let mut changed: Option<MapRect> = None;
+        for x in player_pos.px - EXPLORATION_RADIUS..=player_pos.px + EXPLORATION_RADIUS {
+            for z in player_pos.pz - EXPLORATION_RADIUS..=player_pos.pz + EXPLORATION_RADIUS {
+                let (dx, dz) = (x - player_pos.px, z - player_pos.pz);
+                if dx * dx + dz * dz > EXPLORATION_RADIUS * EXPLORATION_RADIUS {
+                    continue;
+                }
+                let (u, v) = match canvas.get_pixel_pos(x, z) {
+                    Some(pixel) => pixel,
+                    None => continue,
+                };
+                let (y, block) = match world.get_highest_block(x, z) {
+                    Some(highest_block) => highest_block,
+                    None => continue,
+                };
+                let color = block_colors.get(block as usize).cloned().unwrap_or_default();
+                let color = match world.get_highest_block(x, z - 1) {
+                    Some((north_y, _)) if north_y < y => shade(color, HIGHER_SHADE),
+                    Some((north_y, _)) if north_y > y => shade(color, LOWER_SHADE),
+                    _ => color,
+                };
+                if canvas.set(u, v, color) {
+                    let pixel = MapRect::pixel(u, v);
+                    changed = Some(changed.map(|rect| rect.union(pixel)).unwrap_or(pixel));
+                }
+            }
+        }
+        if let Some(rect) = changed {
+            let change = self.changes.entry(id).or_insert(rect);
+            *change = change.union(rect);
+            self.unsaved.insert(id);
+        }
+    }
+
+    /// Take the regions of the canvases that changed since the last call
+    pub fn take_changes(&mut self) -> HashMap<MapId, MapPatch> {
+        let canvases = &self.canvases;
+        self.changes
+            .drain()
+            .map(|(id, rect)| (id, canvases[&id].get_patch(rect)))
+            .collect()
+    }
+
+    /// Save the canvases that changed, logging failures
+    pub fn save(&mut self) {
+        for id in self.unsaved.drain() {
+            if let Err(e) = save_canvas(id, &self.canvases[&id]) {
+                warn!("Failed to save map {}: {:?}", id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,523 @@
+//! Server-side mob management: spawning, despawning and AI.
+use crate::world::World;
+use history_survival_common::{
+    block::{Block, BlockId},
+    debug::send_debug_info,
+    entity::{EntityId, EntityKind},
+    mob::{Mob, MobCategory, MobId},
+    physics::entity::{PhysicsEntity, ENTITY_JUMP_SPEED},
+    physics::simulation::ServerPhysicsSimulation,
+    player::PlayerId,
+    registry::Registry,
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+mod pathfinding;
+mod spawning;
+
+use spawning::{mob_cap, SpawnMetrics, CATEGORIES, SPAWN_ATTEMPTS_PER_TICK};
+
+/// Mobs spawn at a horizontal distance between these two values from a player
+const SPAWN_MIN_DISTANCE: f64 = 24.0;
+const SPAWN_MAX_DISTANCE: f64 = 64.0;
+/// Number of blocks above and below the player's height that are scanned to find the ground to spawn on
+const SPAWN_VERTICAL_RANGE: i64 = 24;
+/// Mobs that are further than this from every player are despawned
+const DESPAWN_DISTANCE: f64 = 96.0;
+/// Maximum distance of a wander target
+const WANDER_DISTANCE: i64 = 8;
+/// Time between two decisions of an idle mob
+const DECISION_INTERVAL: Duration = Duration::from_secs(3);
+/// Time between two path recomputations of a chasing mob
+const CHASE_REPATH_INTERVAL: Duration = Duration::from_millis(500);
+/// Distance at which a mob considers a waypoint reached
+const WAYPOINT_REACHED_DISTANCE: f64 = 0.25;
+/// Time during which a mob that was hit doesn't control its movement
+const KNOCKBACK_DURATION: Duration = Duration::from_millis(400);
+/// Maximum distance at which a player can interact with a mob
+const INTERACTION_RANGE: f64 = 5.0;
+/// Pets start following their owner when they are further than this
+const FOLLOW_START_DISTANCE: f64 = 3.0;
+/// Pets that are further than this from their owner are teleported next to them
+const PET_TELEPORT_DISTANCE: f64 = 16.0;
+/// Maximum horizontal distance from the owner at which a pet is teleported
+const PET_TELEPORT_RANGE: i64 = 2;
+
+/// What a mob is currently trying to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    /// Stand still
+    Idle,
+    /// Walk to a random position
+    Wander,
+    /// Walk to a player
+    Chase(PlayerId),
+    /// Stay close to the owner of the pet
+    Follow(PlayerId),
+    /// Don't move until the owner says otherwise
+    Sit,
+}
+
+/// What makes a mob a pet
+struct PetData {
+    owner: PlayerId,
+    sitting: bool,
+}
+
+/// The data that the server stores for every mob, in addition to its physics.
+struct ServerMob {
+    mob_id: MobId,
+    goal: Goal,
+    /// Remaining waypoints, the next one being last
+    path: Vec<BlockPos>,
+    /// When the mob should pick a new goal or recompute its path
+    next_decision: Instant,
+    health: f64,
+    /// Until when the mob is being knocked back
+    knocked_back_until: Instant,
+    /// Set if the mob was tamed by a player
+    pet: Option<PetData>,
+}
+
+/// All the mobs of the world
+pub struct Mobs {
+    mobs: HashMap<EntityId, ServerMob>,
+    mob_registry: Registry<Mob>,
+    /// The blocks every mob can spawn on
+    spawn_blocks: Vec<HashSet<BlockId>>,
+    /// The mobs of every category
+    mobs_by_category: HashMap<MobCategory, Vec<MobId>>,
+}
+
+impl Mobs {
+    pub fn new(mob_registry: Registry<Mob>, block_registry: &Registry<Block>) -> Self {
+        let spawn_blocks = (0..mob_registry.get_number_of_ids())
+            .map(|id| {
+                let mob = mob_registry.get_value_by_id(id).unwrap();
+                mob.ty
+                    .spawn
+                    .blocks
+                    .iter()
+                    .filter_map(|name| match block_registry.get_id_by_name(name) {
+                        Some(id) => Some(id as BlockId),
+                        None => {
+                            log::warn!("Unknown spawn block {} for mob {}, ignoring...", name, mob.name);
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut mobs_by_category: HashMap<MobCategory, Vec<MobId>> = HashMap::new();
+        for id in 0..mob_registry.get_number_of_ids() {
+            let category = mob_registry.get_value_by_id(id).unwrap().ty.category;
+            mobs_by_category.entry(category).or_default().push(id);
+        }
+        Self {
+            mobs: HashMap::new(),
+            mob_registry,
+            spawn_blocks,
+            mobs_by_category,
+        }
+    }
+
+    /// Get the number of mobs of every category
+    fn count_by_category(&self) -> HashMap<MobCategory, usize> {
+        let mut counts = HashMap::new();
+        for mob in self.mobs.values() {
+            let category = self.mob_registry.get_value_by_id(mob.mob_id).unwrap().ty.category;
+            *counts.entry(category).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Try to spawn mobs around the players within the caps of every category,
+    /// and despawn the mobs that are too far from all players
+    pub fn update_spawning(&mut self, world: &World, physics_simulation: &mut ServerPhysicsSimulation) {
+        let player_positions = physics_simulation
+            .get_state()
+            .physics_state
+            .players
+            .values()
+            .map(|player| player.aabb.pos)
+            .collect::<Vec<_>>();
+
+        // Despawn
+        let mut despawned = Vec::new();
+        for (&id, entity) in physics_simulation.get_state().physics_state.entities.iter() {
+            if !self.mobs.contains_key(&id) {
+                continue;
+            }
+            // Pets stay in the world even when their owner is away
+            if self.mobs[&id].pet.is_some() {
+                continue;
+            }
+            let pos = entity.get_feet_position();
+            let is_close_to_a_player = player_positions
+                .iter()
+                .any(|player_pos| (player_pos - pos).norm() < DESPAWN_DISTANCE);
+            let is_chunk_loaded = world.get_chunk(BlockPos::from(pos).containing_chunk_pos()).is_some();
+            if !is_close_to_a_player || !is_chunk_loaded {
+                despawned.push(id);
+            }
+        }
+        let mut metrics = SpawnMetrics {
+            despawned: despawned.len(),
+            ..Default::default()
+        };
+        for id in despawned {
+            self.mobs.remove(&id);
+            physics_simulation.remove_entity(id);
+        }
+
+        // Spawn, within the caps and the attempt budget
+        let loaded_chunks = world.num_loaded_chunks();
+        let mut counts = self.count_by_category();
+        let mut rng = rand::thread_rng();
+        for _ in 0..SPAWN_ATTEMPTS_PER_TICK {
+            if player_positions.is_empty() {
+                break;
+            }
+            let open_categories = CATEGORIES
+                .iter()
+                .filter(|category| self.mobs_by_category.contains_key(category))
+                .filter(|category| counts.get(category).cloned().unwrap_or(0) < mob_cap(**category, loaded_chunks))
+                .collect::<Vec<_>>();
+            if open_categories.is_empty() {
+                break;
+            }
+            metrics.attempts += 1;
+            let category = *open_categories[rng.gen_range(0..open_categories.len())];
+            let category_mobs = &self.mobs_by_category[&category];
+            let mob_id = category_mobs[rng.gen_range(0..category_mobs.len())];
+            let player_pos = player_positions[rng.gen_range(0..player_positions.len())];
+            let angle = rng.gen_range(0.0..std::f64::consts::PI * 2.0);
+            let distance = rng.gen_range(SPAWN_MIN_DISTANCE..SPAWN_MAX_DISTANCE);
+            let column = BlockPos::from(player_pos + Vector3::new(angle.cos(), 0.0, angle.sin()) * distance);
+            if let Some(pos) = self.find_spawn_position(world, mob_id, column) {
+                self.spawn(mob_id, pos, physics_simulation);
+                *counts.entry(category).or_insert(0) += 1;
+                metrics.spawned += 1;
+            }
+        }
+
+        let caps = CATEGORIES
+            .iter()
+            .map(|category| {
+                format!(
+                    "{:?} mobs = {} / {}",
+                    category,
+                    counts.get(category).cloned().unwrap_or(0),
+                    mob_cap(*category, loaded_chunks),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        send_debug_info(
+            "Mobs",
+            "spawning",
+            format!(
+                "{}\nSpawn attempts = {}\nSpawned = {}\nDespawned = {}",
+                caps, metrics.attempts, metrics.spawned, metrics.despawned,
+            ),
+        );
+    }
+
+    /// Find a position in the column of `column` where the mob could spawn
+    fn find_spawn_position(&self, world: &World, mob_id: MobId, column: BlockPos) -> Option<BlockPos> {
+        let mob = &self.mob_registry.get_value_by_id(mob_id).unwrap().ty;
+        let height = mob.size.1.ceil() as i64;
+        for dy in (-SPAWN_VERTICAL_RANGE..=SPAWN_VERTICAL_RANGE).rev() {
+            let pos = column.offset(0, dy, 0);
+            if !pathfinding::can_stand_at(world, pos, height) {
+                continue;
+            }
+            let light = world.get_light(pos);
+            let ground = world.get_block(pos.offset(0, -1, 0));
+            if light >= mob.spawn.min_light
+                && light <= mob.spawn.max_light
+                && self.spawn_blocks[mob_id as usize].contains(&ground)
+            {
+                return Some(pos);
+            }
+            // Only try the highest ground in the column
+            return None;
+        }
+        None
+    }
+
+    /// Spawn a mob with its feet in the block at `pos`
+    fn spawn(&mut self, mob_id: MobId, pos: BlockPos, physics_simulation: &mut ServerPhysicsSimulation) {
+        let mob = &self.mob_registry.get_value_by_id(mob_id).unwrap().ty;
+        let (size_x, _, size_z) = mob.size;
+        let entity_pos = Vector3::new(
+            pos.px as f64 + 0.5 - size_x / 2.0,
+            pos.py as f64,
+            pos.pz as f64 + 0.5 - size_z / 2.0,
+        );
+        let id = physics_simulation.spawn_entity(PhysicsEntity::new(EntityKind::Mob(mob_id), entity_pos, mob.size));
+        self.mobs.insert(
+            id,
+            ServerMob {
+                mob_id,
+                goal: Goal::Idle,
+                path: Vec::new(),
+                next_decision: Instant::now(),
+                health: mob.max_health,
+                knocked_back_until: Instant::now(),
+                pet: None,
+            },
+        );
+    }
+
+    /// Check whether some entity is a mob
+    pub fn is_mob(&self, id: EntityId) -> bool {
+        self.mobs.contains_key(&id)
+    }
+
+    /// Interact with a mob, if it is within `INTERACTION_RANGE` of the camera of the player at `origin`.
+    /// Feeding a tamable mob the item named `held_item`, if it is its taming food, can tame it,
+    /// and interacting with one's own pet makes it sit or stand up.
+    /// Return whether the mob ate one of the held items.
+    pub fn interact(
+        &mut self,
+        player_id: PlayerId,
+        id: EntityId,
+        origin: Vector3<f64>,
+        held_item: Option<&str>,
+        physics_simulation: &ServerPhysicsSimulation,
+    ) -> bool {
+        let is_close = match physics_simulation.get_state().physics_state.entities.get(&id) {
+            Some(entity) => (entity.get_center() - origin).norm() <= INTERACTION_RANGE + entity.aabb.size_x,
+            None => false,
+        };
+        let mob = match self.mobs.get_mut(&id) {
+            Some(mob) if is_close => mob,
+            _ => return false,
+        };
+        let mob_type = &self.mob_registry.get_value_by_id(mob.mob_id).unwrap();
+        match &mut mob.pet {
+            Some(pet) => {
+                if pet.owner == player_id {
+                    pet.sitting = !pet.sitting;
+                    mob.path.clear();
+                    mob.next_decision = Instant::now();
+                }
+                false
+            }
+            None => match &mob_type.ty.taming {
+                Some(taming) if held_item == Some(taming.food.as_str()) => {
+                    if rand::thread_rng().gen_bool(taming.chance.clamp(0.0, 1.0)) {
+                        log::info!("A {} was tamed by player {:?}", mob_type.name, player_id);
+                        mob.pet = Some(PetData {
+                            owner: player_id,
+                            sitting: false,
+                        });
+                        mob.path.clear();
+                        mob.next_decision = Instant::now();
+                    }
+                    true
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// Damage a mob and push it with velocity `knockback`. The mob is removed if it dies.
+    pub fn damage(
+        &mut self,
+        id: EntityId,
+        amount: f64,
+        knockback: Vector3<f64>,
+        physics_simulation: &mut ServerPhysicsSimulation,
+        now: Instant,
+    ) {
+        if let Some(mob) = self.mobs.get_mut(&id) {
+            mob.health -= amount;
+            if mob.health <= 0.0 {
+                self.mobs.remove(&id);
+                physics_simulation.remove_entity(id);
+            } else {
+                physics_simulation.apply_impulse(id, knockback);
+                mob.knocked_back_until = now + KNOCKBACK_DURATION;
+            }
+        }
+    }
+
+    /// Update the goals and paths of the mobs, and set their velocities accordingly
+    pub fn update_ai(&mut self, world: &World, physics_simulation: &mut ServerPhysicsSimulation, now: Instant) {
+        let players = physics_simulation
+            .get_state()
+            .physics_state
+            .players
+            .iter()
+            .map(|(&id, player)| (id, player.aabb.pos + Vector3::new(player.aabb.size_x / 2.0, 0.0, player.aabb.size_z / 2.0)))
+            .collect::<Vec<_>>();
+        let mut rng = rand::thread_rng();
+        let mut chasing = 0;
+        let mut pets = 0;
+
+        for (&id, mob) in self.mobs.iter_mut() {
+            let mob_type = &self.mob_registry.get_value_by_id(mob.mob_id).unwrap().ty;
+            let entity = match physics_simulation.get_entity_mut(id) {
+                Some(entity) => entity,
+                None => continue,
+            };
+            let feet_pos = entity.get_feet_position();
+            let feet_block = BlockPos::from(feet_pos);
+            let height = mob_type.size.1.ceil() as i64;
+
+            // Pets follow their owner, unless they were told to sit
+            if let Some(pet) = &mob.pet {
+                pets += 1;
+                let goal = if pet.sitting { Goal::Sit } else { Goal::Follow(pet.owner) };
+                if mob.goal != goal {
+                    mob.goal = goal;
+                    mob.path.clear();
+                    mob.next_decision = now;
+                }
+            }
+            // Hostile mobs chase the closest player in range
+            else if mob_type.category == MobCategory::Hostile {
+                let target = players
+                    .iter()
+                    .map(|(player_id, player_pos)| (*player_id, (player_pos - feet_pos).norm()))
+                    .filter(|(_, distance)| *distance < mob_type.detection_range)
+                    .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap());
+                match target {
+                    Some((player_id, _)) => {
+                        if mob.goal != Goal::Chase(player_id) {
+                            mob.goal = Goal::Chase(player_id);
+                            mob.next_decision = now;
+                        }
+                    }
+                    None => {
+                        if let Goal::Chase(_) = mob.goal {
+                            mob.goal = Goal::Idle;
+                            mob.path.clear();
+                        }
+                    }
+                }
+            }
+
+            // Pick a new goal or recompute the path
+            if now >= mob.next_decision {
+                match mob.goal {
+                    Goal::Chase(player_id) => {
+                        if let Some((_, player_pos)) = players.iter().find(|(id, _)| *id == player_id) {
+                            mob.path = pathfinding::find_path(world, feet_block, BlockPos::from(*player_pos), height)
+                                .unwrap_or_default();
+                        }
+                        mob.next_decision = now + CHASE_REPATH_INTERVAL;
+                    }
+                    Goal::Follow(owner) => {
+                        if let Some((_, owner_pos)) = players.iter().find(|(id, _)| *id == owner) {
+                            let distance = (owner_pos - feet_pos).norm();
+                            let owner_block = BlockPos::from(*owner_pos);
+                            if distance > PET_TELEPORT_DISTANCE {
+                                if let Some(pos) = find_teleport_position(world, owner_block, height) {
+                                    let (size_x, _, size_z) = mob_type.size;
+                                    entity.aabb.pos = Vector3::new(
+                                        pos.px as f64 + 0.5 - size_x / 2.0,
+                                        pos.py as f64,
+                                        pos.pz as f64 + 0.5 - size_z / 2.0,
+                                    );
+                                    entity.velocity = Vector3::zeros();
+                                }
+                                mob.path.clear();
+                            } else if distance > FOLLOW_START_DISTANCE {
+                                mob.path = pathfinding::find_path(world, feet_block, owner_block, height)
+                                    .unwrap_or_default();
+                            } else {
+                                mob.path.clear();
+                            }
+                        }
+                        mob.next_decision = now + CHASE_REPATH_INTERVAL;
+                    }
+                    Goal::Sit => {
+                        mob.path.clear();
+                        mob.next_decision = now + DECISION_INTERVAL;
+                    }
+                    Goal::Idle | Goal::Wander => {
+                        if rng.gen_bool(0.5) {
+                            let target = feet_block.offset(
+                                rng.gen_range(-WANDER_DISTANCE..=WANDER_DISTANCE),
+                                0,
+                                rng.gen_range(-WANDER_DISTANCE..=WANDER_DISTANCE),
+                            );
+                            mob.path = pathfinding::find_path(world, feet_block, target, height).unwrap_or_default();
+                            mob.goal = Goal::Wander;
+                        } else {
+                            mob.path.clear();
+                            mob.goal = Goal::Idle;
+                        }
+                        mob.next_decision = now + DECISION_INTERVAL;
+                    }
+                }
+            }
+            if let Goal::Chase(_) = mob.goal {
+                chasing += 1;
+            }
+
+            // Knocked back mobs don't control their movement
+            if now < mob.knocked_back_until {
+                continue;
+            }
+
+            // Follow the path
+            while let Some(&waypoint) = mob.path.last() {
+                let waypoint_center = Vector3::new(waypoint.px as f64 + 0.5, feet_pos.y, waypoint.pz as f64 + 0.5);
+                if (waypoint_center - feet_pos).norm() < WAYPOINT_REACHED_DISTANCE && feet_block.py >= waypoint.py {
+                    mob.path.pop();
+                } else {
+                    break;
+                }
+            }
+            match mob.path.last() {
+                Some(&waypoint) => {
+                    let waypoint_center = Vector3::new(waypoint.px as f64 + 0.5, feet_pos.y, waypoint.pz as f64 + 0.5);
+                    let direction = waypoint_center - feet_pos;
+                    let direction = if direction.norm() > 1e-9 { direction.normalize() } else { direction };
+                    entity.velocity.x = direction.x * mob_type.speed;
+                    entity.velocity.z = direction.z * mob_type.speed;
+                    entity.yaw = (-direction.x).atan2(-direction.z).to_degrees();
+                    if waypoint.py > feet_block.py && entity.aabb.is_on_the_ground(world) {
+                        entity.velocity.y = ENTITY_JUMP_SPEED;
+                    }
+                }
+                None => {
+                    entity.velocity.x = 0.0;
+                    entity.velocity.z = 0.0;
+                    if mob.goal == Goal::Wander {
+                        mob.goal = Goal::Idle;
+                    }
+                }
+            }
+        }
+
+        send_debug_info(
+            "Mobs",
+            "server",
+            format!("Mobs = {}\nChasing mobs = {}\nPets = {}", self.mobs.len(), chasing, pets),
+        );
+    }
+}
+
+/// Find a position close to `center` where a mob of height `height` can stand
+fn find_teleport_position(world: &World, center: BlockPos, height: i64) -> Option<BlockPos> {
+    for dy in [0, 1, -1, 2, -2].iter() {
+        for dx in -PET_TELEPORT_RANGE..=PET_TELEPORT_RANGE {
+            for dz in -PET_TELEPORT_RANGE..=PET_TELEPORT_RANGE {
+                let pos = center.offset(dx, *dy, dz);
+                if (dx != 0 || dz != 0) && pathfinding::can_stand_at(world, pos, height) {
+                    return Some(pos);
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,203 @@
+//! A* pathfinding over the block grid
+use history_survival_common::{physics::BlockContainer, world::BlockPos};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Maximum number of nodes explored by a single search, to keep the server tick bounded
+const MAX_EXPLORED_NODES: usize = 1000;
+/// Maximum number of blocks a mob is willing to fall
+const MAX_DROP: i64 = 3;
+/// Horizontal neighbors of a block
+const NEIGHBORS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Check whether an entity that is `height` blocks tall can stand with its feet in the block at `pos`
+pub fn can_stand_at<BC: BlockContainer>(world: &BC, pos: BlockPos, height: i64) -> bool {
+    world.is_block_full(pos.offset(0, -1, 0)) && is_column_free(world, pos, height)
+}
+
+/// Check whether the `height` blocks starting at `pos` and going up are all empty
+fn is_column_free<BC: BlockContainer>(world: &BC, pos: BlockPos, height: i64) -> bool {
+    (0..height).all(|dy| !world.is_block_full(pos.offset(0, dy, 0)))
+}
+
+/// Estimated cost to go from `a` to `b`. It never overestimates the real cost.
+fn heuristic(a: BlockPos, b: BlockPos) -> u32 {
+    ((a.px - b.px).abs() + (a.py - b.py).abs() + (a.pz - b.pz).abs()) as u32
+}
+
+/// The positions that can be reached in one step from `pos`, with the cost of the step
+fn walkable_neighbors<BC: BlockContainer>(world: &BC, pos: BlockPos, height: i64) -> Vec<(BlockPos, u32)> {
+    let mut neighbors = Vec::with_capacity(4);
+    for &(dx, dz) in NEIGHBORS.iter() {
+        let next = pos.offset(dx, 0, dz);
+        if can_stand_at(world, next, height) {
+            neighbors.push((next, 1));
+        } else if world.is_block_full(next) {
+            // Jump one block up, which requires some room above the current position
+            let up = next.offset(0, 1, 0);
+            if can_stand_at(world, up, height) && !world.is_block_full(pos.offset(0, height, 0)) {
+                neighbors.push((up, 2));
+            }
+        } else if is_column_free(world, next, height) {
+            // Fall down a few blocks
+            for drop in 1..=MAX_DROP {
+                let down = next.offset(0, -drop, 0);
+                if world.is_block_full(down) {
+                    break;
+                }
+                if can_stand_at(world, down, height) {
+                    neighbors.push((down, 1 + drop as u32));
+                    break;
+                }
+            }
+        }
+    }
+    neighbors
+}
+
+#[derive(PartialEq, Eq)]
+struct Node {
+    /// Cost so far plus estimated remaining cost
+    estimated_cost: u32,
+    pos: BlockPos,
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed to make the `BinaryHeap` a min-heap
+        other.estimated_cost.cmp(&self.estimated_cost)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Find a path for an entity that is `height` blocks tall from `start` to `goal`.
+/// The returned path doesn't include `start` and is stored in reverse order, so that the next waypoint can be popped.
+/// If the goal can't be reached, return a path to the explored position that is closest to the goal,
+/// or `None` if no such position is closer than `start`.
+pub fn find_path<BC: BlockContainer>(world: &BC, start: BlockPos, goal: BlockPos, height: i64) -> Option<Vec<BlockPos>> {
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<BlockPos, BlockPos> = HashMap::new();
+    let mut cost_so_far: HashMap<BlockPos, u32> = HashMap::new();
+    let mut closest = (heuristic(start, goal), start);
+
+    open.push(Node { estimated_cost: heuristic(start, goal), pos: start });
+    cost_so_far.insert(start, 0);
+
+    let mut explored = 0;
+    while let Some(Node { pos, .. }) = open.pop() {
+        if pos == goal {
+            closest = (0, pos);
+            break;
+        }
+        explored += 1;
+        if explored > MAX_EXPLORED_NODES {
+            break;
+        }
+        let current_cost = cost_so_far[&pos];
+        for (next, step_cost) in walkable_neighbors(world, pos, height) {
+            let next_cost = current_cost + step_cost;
+            if cost_so_far.get(&next).map(|&c| next_cost < c).unwrap_or(true) {
+                cost_so_far.insert(next, next_cost);
+                came_from.insert(next, pos);
+                let remaining = heuristic(next, goal);
+                if remaining < closest.0 {
+                    closest = (remaining, next);
+                }
+                open.push(Node { estimated_cost: next_cost + remaining, pos: next });
+            }
+        }
+    }
+
+    let (_, end) = closest;
+    if end == start {
+        return None;
+    }
+    let mut path = vec![end];
+    let mut current = end;
+    while let Some(&previous) = came_from.get(&current) {
+        if previous == start {
+            break;
+        }
+        path.push(previous);
+        current = previous;
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    struct TestWorld {
+        blocks: HashSet<BlockPos>,
+    }
+
+    impl TestWorld {
+        /// A flat 16x16 floor at y = 0
+        fn flat() -> Self {
+            let mut blocks = HashSet::new();
+            for x in 0..16 {
+                for z in 0..16 {
+                    blocks.insert(BlockPos::from((x, 0, z)));
+                }
+            }
+            Self { blocks }
+        }
+    }
+
+    impl BlockContainer for TestWorld {
+        fn is_block_full(&self, pos: BlockPos) -> bool {
+            self.blocks.contains(&pos)
+        }
+
+        fn is_block_climbable(&self, _pos: BlockPos) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_straight_path() {
+        let world = TestWorld::flat();
+        let path = find_path(&world, (0, 1, 0).into(), (5, 1, 0).into(), 2).unwrap();
+        assert_eq!(path.len(), 5);
+        assert_eq!(path[0], BlockPos::from((5, 1, 0)));
+    }
+
+    #[test]
+    fn test_path_around_wall() {
+        let mut world = TestWorld::flat();
+        // A three block high wall at x = 3 blocking z = 0..5
+        for z in 0..5 {
+            for y in 1..4 {
+                world.blocks.insert((3, y, z).into());
+            }
+        }
+        let path = find_path(&world, (1, 1, 0).into(), (5, 1, 0).into(), 2).unwrap();
+        assert_eq!(path[0], BlockPos::from((5, 1, 0)));
+        assert!(path.iter().any(|pos| pos.pz >= 5));
+        assert!(path.iter().all(|pos| pos.py == 1));
+    }
+
+    #[test]
+    fn test_jump_onto_step() {
+        let mut world = TestWorld::flat();
+        world.blocks.insert((2, 1, 0).into());
+        let path = find_path(&world, (0, 1, 0).into(), (2, 2, 0).into(), 2).unwrap();
+        assert_eq!(path, vec![BlockPos::from((2, 2, 0)), BlockPos::from((1, 1, 0))]);
+    }
+
+    #[test]
+    fn test_unreachable_goal_gets_closer() {
+        let world = TestWorld::flat();
+        // The goal floats high above the floor
+        let path = find_path(&world, (0, 1, 0).into(), (4, 10, 0).into(), 2).unwrap();
+        assert_eq!(path[0], BlockPos::from((4, 1, 0)));
+        assert!(find_path(&world, (4, 1, 0).into(), (4, 10, 0).into(), 2).is_none());
+    }
+}
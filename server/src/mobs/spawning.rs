@@ -0,0 +1,56 @@
+//! Mob density control: how many mobs of every category can exist, and how many spawn attempts are made.
+use history_survival_common::mob::MobCategory;
+
+/// The categories of mobs, in the order in which they are listed in the debug info
+pub const CATEGORIES: [MobCategory; 3] = [MobCategory::Hostile, MobCategory::Passive, MobCategory::Ambient];
+/// Maximum number of spawn attempts in a single tick, for all players and categories together
+pub const SPAWN_ATTEMPTS_PER_TICK: usize = 4;
+
+/// The density limits of a category of mobs
+struct CategoryCap {
+    /// Number of mobs allowed per loaded chunk
+    per_chunk: f64,
+    /// Number of mobs that is never exceeded, however many chunks are loaded
+    max: usize,
+}
+
+fn category_cap(category: MobCategory) -> CategoryCap {
+    match category {
+        MobCategory::Hostile => CategoryCap { per_chunk: 0.1, max: 30 },
+        MobCategory::Passive => CategoryCap { per_chunk: 0.05, max: 20 },
+        MobCategory::Ambient => CategoryCap { per_chunk: 0.02, max: 10 },
+    }
+}
+
+/// Get the maximum number of mobs of a category, given the number of loaded chunks
+pub fn mob_cap(category: MobCategory, loaded_chunks: usize) -> usize {
+    let cap = category_cap(category);
+    ((cap.per_chunk * loaded_chunks as f64).ceil() as usize).min(cap.max)
+}
+
+/// What happened during the last spawning tick, for the debug info
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpawnMetrics {
+    pub attempts: usize,
+    pub spawned: usize,
+    pub despawned: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cap_scales_with_loaded_chunks() {
+        assert_eq!(mob_cap(MobCategory::Hostile, 0), 0);
+        assert_eq!(mob_cap(MobCategory::Hostile, 50), 5);
+        assert!(mob_cap(MobCategory::Hostile, 100) > mob_cap(MobCategory::Hostile, 50));
+    }
+
+    #[test]
+    fn cap_is_bounded() {
+        for &category in CATEGORIES.iter() {
+            assert_eq!(mob_cap(category, 1_000_000), category_cap(category).max);
+        }
+    }
+}
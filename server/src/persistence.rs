@@ -0,0 +1,136 @@
+//! Saving and loading the data of the players, so that they find it again when they reconnect.
+use anyhow::{Context, Result};
+use history_survival_common::{
+    advancement::{Advancement, PlayerAdvancements},
+    inventory::{Inventory, ItemStack, PLAYER_INVENTORY_SIZE},
+    item::Item,
+    player::{GameMode, PlayerId, PlayerStats},
+    registry::Registry,
+    world::BlockPos,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The directory where the player data is saved
+const PLAYERS_DIRECTORY: &str = "saves/players";
+
+/// The data of a player that is kept between connections
+#[derive(Debug, Clone)]
+pub struct SavedPlayer {
+    pub inventory: Inventory,
+    pub stats: PlayerStats,
+    pub game_mode: GameMode,
+    /// The bed the player respawns at, `None` for the world spawn
+    pub spawn_point: Option<BlockPos>,
+    /// Where the player goes with `/home`
+    pub home: Option<BlockPos>,
+    pub advancements: PlayerAdvancements,
+}
+
+/// The data of a player as it is written to the disk. Items and advancements are saved by name, because their ids depend on the loading order.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct PlayerFile {
+    inventory: Vec<Option<SavedStack>>,
+    /// `None` in the files saved before the players had stats
+    stats: Option<PlayerStats>,
+    game_mode: Option<GameMode>,
+    spawn_point: Option<(i64, i64, i64)>,
+    home: Option<(i64, i64, i64)>,
+    advancements: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedStack {
+    item: String,
+    count: u32,
+    #[serde(default)]
+    data: u32,
+}
+
+// TODO: use the name of the player once there are accounts
+fn player_file_path(id: PlayerId) -> PathBuf {
+    PathBuf::from(PLAYERS_DIRECTORY).join(format!("{}.ron", id))
+}
+
+/// Load the saved data of a player. Return `None` if the player was never saved.
+/// The players saved before they had a game mode get `default_game_mode`.
+pub fn load_player(
+    id: PlayerId,
+    items: &Registry<Item>,
+    advancements: &Registry<Advancement>,
+    default_game_mode: GameMode,
+) -> Result<Option<SavedPlayer>> {
+    let path = player_file_path(id);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let string = std::fs::read_to_string(&path).context(format!("Failed to read player file {}", path.display()))?;
+    let file: PlayerFile = ron::de::from_str(&string).context(format!("Failed to parse player file {}", path.display()))?;
+
+    let mut inventory = Inventory::new(PLAYER_INVENTORY_SIZE);
+    for (slot, saved_stack) in file.inventory.into_iter().enumerate() {
+        if let Some(SavedStack { item, count, data }) = saved_stack {
+            match items.get_id_by_name(&item) {
+                Some(item_id) => inventory.set(slot, Some(ItemStack::with_data(item_id, count, data))),
+                None => warn!("Unknown item {} in player file {}, skipping...", item, path.display()),
+            }
+        }
+    }
+    let mut player_advancements = PlayerAdvancements::default();
+    for name in file.advancements.iter() {
+        match advancements.get_id_by_name(name) {
+            Some(advancement_id) => {
+                player_advancements.earn(advancement_id);
+            }
+            None => warn!("Unknown advancement {} in player file {}, skipping...", name, path.display()),
+        }
+    }
+    Ok(Some(SavedPlayer {
+        inventory,
+        stats: file.stats.unwrap_or_default(),
+        game_mode: file.game_mode.unwrap_or(default_game_mode),
+        spawn_point: file.spawn_point.map(BlockPos::from),
+        home: file.home.map(BlockPos::from),
+        advancements: player_advancements,
+    }))
+}
+
+/// Save the data of a player
+pub fn save_player(id: PlayerId, player: &SavedPlayer, items: &Registry<Item>, advancements: &Registry<Advancement>) -> Result<()> {
+    let path = player_file_path(id);
+    let mut advancement_names: Vec<String> = player
+        .advancements
+        .earned()
+        .filter_map(|advancement_id| advancements.get_value_by_id(advancement_id))
+        .map(|advancement| advancement.name.clone())
+        .collect();
+    advancement_names.sort();
+    let file = PlayerFile {
+        inventory: player
+            .inventory
+            .slots()
+            .map(|stack| {
+                stack.and_then(|stack| {
+                    items
+                        .get_value_by_id(stack.item_id)
+                        .map(|item| SavedStack {
+                            item: item.name.clone(),
+                            count: stack.count,
+                            data: stack.data,
+                        })
+                })
+            })
+            .collect(),
+        stats: Some(player.stats),
+        game_mode: Some(player.game_mode),
+        spawn_point: player.spawn_point.map(|pos| (pos.px, pos.py, pos.pz)),
+        home: player.home.map(|pos| (pos.px, pos.py, pos.pz)),
+        advancements: advancement_names,
+    };
+    let string = ron::ser::to_string_pretty(&file, Default::default()).context("Failed to serialize player file")?;
+    std::fs::create_dir_all(PLAYERS_DIRECTORY).context(format!("Failed to create directory {}", PLAYERS_DIRECTORY))?;
+    std::fs::write(&path, string).context(format!("Failed to write player file {}", path.display()))?;
+    Ok(())
+}
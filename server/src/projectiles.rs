@@ -0,0 +1,191 @@
+//! Server-side projectile management: spawning, hit detection and removal.
+use crate::mobs::Mobs;
+use history_survival_common::{
+    debug::send_debug_info,
+    entity::{EntityId, EntityKind},
+    physics::entity::PhysicsEntity,
+    player::PlayerId,
+    physics::simulation::ServerPhysicsSimulation,
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Side of the cubic hitbox of a projectile
+const PROJECTILE_SIZE: f64 = 0.25;
+/// Initial speed of a thrown projectile, in blocks per second
+const THROW_SPEED: f64 = 25.0;
+/// Damage dealt by a projectile hit
+const PROJECTILE_DAMAGE: f64 = 4.0;
+/// Health points that a player loses when a projectile hits it
+pub const PLAYER_DAMAGE: u32 = 4;
+/// Speed given to the entity that was hit, horizontally and vertically
+const KNOCKBACK_HORIZONTAL_SPEED: f64 = 6.0;
+const KNOCKBACK_VERTICAL_SPEED: f64 = 4.0;
+/// Time after which a projectile that hit a block disappears
+const STUCK_LIFETIME: Duration = Duration::from_secs(2);
+/// Time after which a projectile disappears, even if it didn't hit anything
+const MAX_LIFETIME: Duration = Duration::from_secs(10);
+
+/// The data that the server stores for every projectile, in addition to its physics.
+struct ServerProjectile {
+    /// The player who threw the projectile, who can't be hit by it
+    thrower: PlayerId,
+    spawn_time: Instant,
+    /// When the projectile hit a block
+    stuck_since: Option<Instant>,
+    /// The center of the projectile before the last physics step
+    last_center: Vector3<f64>,
+}
+
+/// All the projectiles of the world
+pub struct Projectiles {
+    projectiles: HashMap<EntityId, ServerProjectile>,
+}
+
+impl Projectiles {
+    pub fn new() -> Self {
+        Self {
+            projectiles: HashMap::new(),
+        }
+    }
+
+    /// Throw a projectile from the camera of a player at `pos`, in the direction given by `yaw` and `pitch`
+    pub fn throw(
+        &mut self,
+        thrower: PlayerId,
+        pos: Vector3<f64>,
+        yaw: f64,
+        pitch: f64,
+        physics_simulation: &mut ServerPhysicsSimulation,
+    ) {
+        let y = yaw.to_radians();
+        let p = pitch.to_radians();
+        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+        let half_size = Vector3::new(PROJECTILE_SIZE, PROJECTILE_SIZE, PROJECTILE_SIZE) / 2.0;
+        let mut entity = PhysicsEntity::new(
+            EntityKind::Projectile,
+            pos - half_size,
+            (PROJECTILE_SIZE, PROJECTILE_SIZE, PROJECTILE_SIZE),
+        );
+        entity.velocity = dir * THROW_SPEED;
+        entity.yaw = yaw;
+        let id = physics_simulation.spawn_entity(entity);
+        self.projectiles.insert(
+            id,
+            ServerProjectile {
+                thrower,
+                spawn_time: Instant::now(),
+                stuck_since: None,
+                last_center: pos,
+            },
+        );
+    }
+
+    /// Check which projectiles hit a mob or a player during the last physics step, and remove the old projectiles.
+    /// The mobs are damaged and the bodies that were hit are knocked back. Return the players who were hit,
+    /// whose health is handled by the caller. To be called right after the physics step.
+    pub fn update(
+        &mut self,
+        mobs: &mut Mobs,
+        physics_simulation: &mut ServerPhysicsSimulation,
+        now: Instant,
+    ) -> Vec<PlayerId> {
+        let mut removed = Vec::new();
+        let mut hits = Vec::new();
+        {
+            let physics_state = &physics_simulation.get_state().physics_state;
+            for (&id, projectile) in self.projectiles.iter_mut() {
+                let entity = match physics_state.entities.get(&id) {
+                    Some(entity) => entity,
+                    None => {
+                        removed.push(id);
+                        continue;
+                    }
+                };
+                let center = entity.get_center();
+                let delta = center - projectile.last_center;
+
+                // Find the first mob or player hit by the projectile
+                let mob_hits = physics_state
+                    .entities
+                    .iter()
+                    .filter(|(other_id, _)| mobs.is_mob(**other_id))
+                    .filter_map(|(other_id, other)| {
+                        other
+                            .aabb
+                            .intersect_segment(projectile.last_center, delta)
+                            .map(|t| (Target::Mob(*other_id), t))
+                    });
+                let player_hits = physics_state
+                    .players
+                    .iter()
+                    .filter(|(player_id, _)| **player_id != projectile.thrower)
+                    .filter_map(|(player_id, player)| {
+                        player
+                            .aabb
+                            .intersect_segment(projectile.last_center, delta)
+                            .map(|t| (Target::Player(*player_id), t))
+                    });
+                let hit = mob_hits
+                    .chain(player_hits)
+                    .min_by(|(_, t1), (_, t2)| t1.partial_cmp(t2).unwrap());
+                projectile.last_center = center;
+
+                if let Some((target, _)) = hit {
+                    let horizontal = Vector3::new(delta.x, 0.0, delta.z);
+                    let horizontal = if horizontal.norm() > 1e-9 {
+                        horizontal.normalize()
+                    } else {
+                        horizontal
+                    };
+                    let knockback = horizontal * KNOCKBACK_HORIZONTAL_SPEED
+                        + Vector3::new(0.0, KNOCKBACK_VERTICAL_SPEED, 0.0);
+                    hits.push((target, knockback));
+                    removed.push(id);
+                    continue;
+                }
+
+                if entity.is_stuck() && projectile.stuck_since.is_none() {
+                    projectile.stuck_since = Some(now);
+                }
+                let stuck_for_too_long = projectile
+                    .stuck_since
+                    .map(|stuck_since| now - stuck_since > STUCK_LIFETIME)
+                    .unwrap_or(false);
+                if stuck_for_too_long || now - projectile.spawn_time > MAX_LIFETIME {
+                    removed.push(id);
+                }
+            }
+        }
+
+        let mut hit_players = Vec::new();
+        for (target, knockback) in hits {
+            match target {
+                Target::Mob(mob) => mobs.damage(mob, PROJECTILE_DAMAGE, knockback, physics_simulation, now),
+                Target::Player(player) => {
+                    physics_simulation.apply_impulse(player, knockback);
+                    hit_players.push(player);
+                }
+            }
+        }
+        for id in removed {
+            self.projectiles.remove(&id);
+            physics_simulation.remove_entity(id);
+        }
+
+        send_debug_info(
+            "Mobs",
+            "projectiles",
+            format!("Projectiles = {}", self.projectiles.len()),
+        );
+        hit_players
+    }
+}
+
+/// What a projectile can hit
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Mob(EntityId),
+    Player(PlayerId),
+}
@@ -0,0 +1,152 @@
+//! Limits on how often the players can chat and interact with the world, so that a macro or a modified client
+//! can't flood the server. The players who go over a limit are told to slow down.
+use history_survival_common::network::messages::ToServer;
+use serde::Deserialize;
+use std::time::Instant;
+
+/// How often the players can do something: they can do it `burst` times in a row, and then `per_second` times per second
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+/// The limits of the different kinds of messages
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RateLimits {
+    /// Chat messages, and the commands that the players will send through the chat
+    pub chat: RateLimit,
+    /// Breaking, placing, using and picking
+    pub interactions: RateLimit,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            chat: RateLimit { burst: 5, per_second: 0.5 },
+            // Enough for breaking blocks as fast as in creative mode
+            interactions: RateLimit { burst: 40, per_second: 20.0 },
+        }
+    }
+}
+
+/// A token bucket: every action takes a token, and the tokens come back at the rate of the limit
+#[derive(Debug, Clone)]
+struct RateLimiter {
+    limit: RateLimit,
+    tokens: f64,
+    last_update: Instant,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last_update: now,
+        }
+    }
+
+    /// Take a token if there is one, return whether the action is allowed
+    fn try_take(&mut self, now: Instant) -> bool {
+        let seconds = now.saturating_duration_since(self.last_update).as_secs_f64();
+        self.tokens = (self.tokens + seconds * self.limit.per_second).min(self.limit.burst as f64);
+        self.last_update = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The rate limits of a player
+#[derive(Debug, Clone)]
+pub struct PlayerRateLimits {
+    chat: RateLimiter,
+    interactions: RateLimiter,
+    /// The feedback about the last refused message, if the player wasn't told yet
+    feedback: Option<&'static str>,
+    /// Whether the last message of each kind was refused, so that the player is only told once
+    chat_refused: bool,
+    interactions_refused: bool,
+}
+
+impl PlayerRateLimits {
+    pub fn new(limits: &RateLimits, now: Instant) -> Self {
+        Self {
+            chat: RateLimiter::new(limits.chat, now),
+            interactions: RateLimiter::new(limits.interactions, now),
+            feedback: None,
+            chat_refused: false,
+            interactions_refused: false,
+        }
+    }
+
+    /// Whether the player may send a message now. The messages that are not limited are always allowed.
+    pub fn allow(&mut self, message: &ToServer, now: Instant) -> bool {
+        let (limiter, refused, feedback) = match message {
+            ToServer::ChatMessage(_) => (&mut self.chat, &mut self.chat_refused, "You are chatting too fast, slow down"),
+            ToServer::StartBreaking(..) | ToServer::Interact { .. } => (
+                &mut self.interactions,
+                &mut self.interactions_refused,
+                "You are interacting too fast, slow down",
+            ),
+            _ => return true,
+        };
+        let allowed = limiter.try_take(now);
+        if !allowed && !*refused {
+            self.feedback = Some(feedback);
+        }
+        *refused = !allowed;
+        allowed
+    }
+
+    /// The feedback to send to the player about the last refused message, once
+    pub fn take_feedback(&mut self) -> Option<&'static str> {
+        self.feedback.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn bursts_are_limited() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(RateLimit { burst: 3, per_second: 2.0 }, now);
+        assert!((0..3).all(|_| limiter.try_take(now)));
+        assert!(!limiter.try_take(now));
+        // A token comes back every half second
+        assert!(!limiter.try_take(now + Duration::from_millis(400)));
+        assert!(limiter.try_take(now + Duration::from_millis(500)));
+        assert!(!limiter.try_take(now + Duration::from_millis(500)));
+        // The tokens don't pile up above the burst
+        let later = now + Duration::from_secs(60);
+        assert!((0..3).all(|_| limiter.try_take(later)));
+        assert!(!limiter.try_take(later));
+    }
+
+    #[test]
+    fn players_are_told_once_to_slow_down() {
+        let now = Instant::now();
+        let limits = RateLimits {
+            chat: RateLimit { burst: 1, per_second: 1.0 },
+            ..Default::default()
+        };
+        let mut player = PlayerRateLimits::new(&limits, now);
+        let message = ToServer::ChatMessage("hello".to_owned());
+        assert!(player.allow(&message, now));
+        assert_eq!(player.take_feedback(), None);
+        assert!(!player.allow(&message, now));
+        assert!(!player.allow(&message, now));
+        assert!(player.take_feedback().is_some());
+        assert_eq!(player.take_feedback(), None);
+        // Other messages are not limited
+        assert!(player.allow(&ToServer::CloseChest, now));
+    }
+}
@@ -0,0 +1,222 @@
+//! Scheduled block ticks: a block asks to be ticked again after a precise number of ticks.
+//!
+//! Unlike the random ticks, the scheduled ticks run in a deterministic order: by due tick, then by priority,
+//! then in the order they were scheduled. They are stored by chunk, so that the ticks of a chunk are kept with it
+//! when it is unloaded and saved.
+use history_survival_common::{
+    block::BlockId,
+    time::TICKS_PER_SECOND,
+    world::{BlockPos, ChunkPos, PendingTick, TickPriority},
+};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+
+/// Maximum number of scheduled ticks that run during a single tick. The remaining due ticks run during the next ticks.
+pub const MAX_SCHEDULED_TICKS_PER_TICK: usize = 1024;
+/// Maximum number of ticks that the scheduler catches up with in a single update, if the server was late
+const MAX_CATCH_UP_TICKS: u64 = 20;
+
+/// The order of a scheduled tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct TickKey {
+    due: u64,
+    priority: TickPriority,
+    /// The number of ticks that were scheduled before this one
+    sequence: u64,
+}
+
+/// A scheduled tick that is due
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DueTick {
+    pub pos: BlockPos,
+    /// The block that scheduled the tick
+    pub block: BlockId,
+}
+
+/// The scheduled ticks of the loaded chunks
+pub struct ScheduledTicks {
+    /// The number of ticks since the server started
+    current_tick: u64,
+    last_update: Instant,
+    /// Fraction of a tick that didn't pass yet
+    tick_accumulator: f64,
+    /// The scheduled ticks in the order they run
+    queue: BTreeMap<TickKey, DueTick>,
+    /// The scheduled ticks of every chunk, by position in the chunk
+    chunks: HashMap<ChunkPos, HashMap<(u32, u32, u32), TickKey>>,
+    next_sequence: u64,
+}
+
+impl ScheduledTicks {
+    pub fn new() -> Self {
+        Self {
+            current_tick: 0,
+            last_update: Instant::now(),
+            tick_accumulator: 0.0,
+            queue: BTreeMap::new(),
+            chunks: HashMap::new(),
+            next_sequence: 0,
+        }
+    }
+
+    /// Schedule a tick for the block at some position in `delay` ticks.
+    /// A position only has one scheduled tick: return false if it already has one.
+    pub fn schedule(&mut self, pos: BlockPos, block: BlockId, delay: u64, priority: TickPriority) -> bool {
+        let chunk_ticks = self.chunks.entry(pos.containing_chunk_pos()).or_default();
+        if chunk_ticks.contains_key(&pos.pos_in_containing_chunk()) {
+            return false;
+        }
+        let key = TickKey {
+            // A tick never runs during the tick that scheduled it
+            due: self.current_tick + delay.max(1),
+            priority,
+            sequence: self.next_sequence,
+        };
+        self.next_sequence += 1;
+        chunk_ticks.insert(pos.pos_in_containing_chunk(), key);
+        self.queue.insert(key, DueTick { pos, block });
+        true
+    }
+
+    /// Let the time pass, and take the ticks that are due, in the order they must run.
+    /// Take at most `MAX_SCHEDULED_TICKS_PER_TICK` ticks.
+    pub fn take_due(&mut self, now: Instant) -> Vec<DueTick> {
+        self.tick_accumulator += (now - self.last_update).as_secs_f64() * TICKS_PER_SECOND;
+        self.last_update = now;
+        let elapsed_ticks = self.tick_accumulator.floor();
+        self.tick_accumulator -= elapsed_ticks;
+        self.current_tick += (elapsed_ticks as u64).min(MAX_CATCH_UP_TICKS);
+
+        let mut due_ticks = Vec::new();
+        while due_ticks.len() < MAX_SCHEDULED_TICKS_PER_TICK {
+            let key = match self.queue.keys().next() {
+                Some(key) if key.due <= self.current_tick => *key,
+                _ => break,
+            };
+            let tick = self.queue.remove(&key).unwrap();
+            self.remove_from_chunk(tick.pos);
+            due_ticks.push(tick);
+        }
+        due_ticks
+    }
+
+    fn remove_from_chunk(&mut self, pos: BlockPos) {
+        let chunk_pos = pos.containing_chunk_pos();
+        if let Some(chunk_ticks) = self.chunks.get_mut(&chunk_pos) {
+            chunk_ticks.remove(&pos.pos_in_containing_chunk());
+            if chunk_ticks.is_empty() {
+                self.chunks.remove(&chunk_pos);
+            }
+        }
+    }
+
+    /// The ticks scheduled in a chunk, in the order they run, with their remaining delay
+    pub fn chunk_ticks(&self, pos: ChunkPos) -> Vec<PendingTick> {
+        let mut keys = match self.chunks.get(&pos) {
+            Some(chunk_ticks) => chunk_ticks.iter().map(|(&pos, &key)| (key, pos)).collect::<Vec<_>>(),
+            None => return Vec::new(),
+        };
+        keys.sort_unstable();
+        keys.into_iter()
+            .map(|(key, pos)| PendingTick {
+                pos,
+                delay: key.due.saturating_sub(self.current_tick),
+                priority: key.priority,
+            })
+            .collect()
+    }
+
+    /// Remove the ticks of a chunk that is unloaded, and return them, in the order they run, with their remaining delay
+    pub fn unload_chunk(&mut self, pos: ChunkPos) -> Vec<PendingTick> {
+        let pending_ticks = self.chunk_ticks(pos);
+        for key in self.chunks.remove(&pos).into_iter().flat_map(|chunk_ticks| chunk_ticks.into_values()) {
+            self.queue.remove(&key);
+        }
+        pending_ticks
+    }
+
+    /// Schedule the ticks of a chunk that is loaded again. `get_block` returns the block at some position in the chunk.
+    pub fn load_chunk(&mut self, pos: ChunkPos, pending_ticks: &[PendingTick], get_block: impl Fn((u32, u32, u32)) -> BlockId) {
+        for tick in pending_ticks {
+            self.schedule(pos.block_pos(tick.pos), get_block(tick.pos), tick.delay, tick.priority);
+        }
+    }
+
+    /// Number of scheduled ticks in the loaded chunks
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn block_pos(px: i64, py: i64, pz: i64) -> BlockPos {
+        BlockPos { px, py, pz }
+    }
+
+    /// Let exactly `ticks` ticks pass
+    fn advance(ticks: &mut ScheduledTicks, num_ticks: u64) -> Vec<DueTick> {
+        let now = ticks.last_update + Duration::from_secs_f64(num_ticks as f64 / TICKS_PER_SECOND);
+        // Avoid the rounding errors of the accumulator
+        ticks.tick_accumulator = 0.5;
+        ticks.take_due(now)
+    }
+
+    #[test]
+    fn ticks_run_by_due_tick_then_priority_then_order() {
+        let mut ticks = ScheduledTicks::new();
+        assert!(ticks.schedule(block_pos(0, 0, 0), 1, 2, 0));
+        assert!(ticks.schedule(block_pos(1, 0, 0), 2, 1, 0));
+        assert!(ticks.schedule(block_pos(2, 0, 0), 3, 2, -1));
+        assert!(ticks.schedule(block_pos(3, 0, 0), 4, 2, 0));
+        // A position only has one tick
+        assert!(!ticks.schedule(block_pos(3, 0, 0), 4, 1, 0));
+
+        assert_eq!(advance(&mut ticks, 0), Vec::new());
+        let order = |due: Vec<DueTick>| due.into_iter().map(|tick| tick.block).collect::<Vec<_>>();
+        assert_eq!(order(advance(&mut ticks, 1)), vec![2]);
+        assert_eq!(order(advance(&mut ticks, 1)), vec![3, 1, 4]);
+        assert_eq!(ticks.len(), 0);
+    }
+
+    #[test]
+    fn due_ticks_are_capped() {
+        let mut ticks = ScheduledTicks::new();
+        for x in 0..MAX_SCHEDULED_TICKS_PER_TICK as i64 + 10 {
+            ticks.schedule(block_pos(x, 0, 0), 1, 1, 0);
+        }
+        assert_eq!(advance(&mut ticks, 1).len(), MAX_SCHEDULED_TICKS_PER_TICK);
+        let remaining = advance(&mut ticks, 0);
+        assert_eq!(remaining.len(), 10);
+        assert_eq!(remaining[0].pos, block_pos(MAX_SCHEDULED_TICKS_PER_TICK as i64, 0, 0));
+    }
+
+    #[test]
+    fn unloaded_chunks_keep_their_ticks() {
+        let mut ticks = ScheduledTicks::new();
+        ticks.schedule(block_pos(5, 3, 1), 7, 10, 1);
+        ticks.schedule(block_pos(2, 0, 0), 8, 4, 0);
+        ticks.schedule(block_pos(40, 0, 0), 9, 4, 0);
+        advance(&mut ticks, 3);
+
+        let chunk_pos = ChunkPos::from([0, 0, 0]);
+        let pending_ticks = ticks.unload_chunk(chunk_pos);
+        assert_eq!(
+            pending_ticks,
+            vec![
+                PendingTick { pos: (2, 0, 0), delay: 1, priority: 0 },
+                PendingTick { pos: (5, 3, 1), delay: 7, priority: 1 },
+            ]
+        );
+        assert_eq!(ticks.len(), 1);
+
+        // Time doesn't pass in the unloaded chunk
+        advance(&mut ticks, 5);
+        ticks.load_chunk(chunk_pos, &pending_ticks, |_| 7);
+        assert_eq!(ticks.chunk_ticks(chunk_pos), pending_ticks);
+        assert_eq!(advance(&mut ticks, 1), vec![DueTick { pos: block_pos(2, 0, 0), block: 7 }]);
+    }
+}
@@ -0,0 +1,203 @@
+//! Server-side sanity checks of what the clients send, so that a modified client can't move in impossible ways.
+use history_survival_common::{
+    player::{GameMode, PlayerId, PlayerInput},
+    physics::simulation::ServerPhysicsSimulation,
+};
+use log::warn;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Maximum speed a player can legitimately reach, in blocks per second, with some margin
+const MAX_PLAYER_SPEED: f64 = 50.0;
+/// Displacement that is always allowed during a single tick, to account for collision resolution
+const DISPLACEMENT_TOLERANCE: f64 = 1.0;
+/// Maximum distance between the position a client reports and the position simulated by the server.
+/// The client prediction can be ahead of the server by a few ticks, so this is not too strict.
+const MAX_REPORTED_POSITION_ERROR: f64 = 8.0;
+/// Number of violations in a single window after which the player is reported in the logs
+const VIOLATIONS_BEFORE_WARNING: u32 = 5;
+/// Duration of the window in which violations are counted
+const VIOLATION_WINDOW: Duration = Duration::from_secs(10);
+
+/// The validation state of a single player
+struct PlayerValidation {
+    /// The last position that passed validation
+    last_valid_pos: Option<Vector3<f64>>,
+    /// Number of violations in the current window
+    violations: u32,
+    /// Start of the current window
+    window_start: Instant,
+}
+
+/// Validates the inputs and the movement of every player
+pub struct MovementValidator {
+    players: HashMap<PlayerId, PlayerValidation>,
+    last_check: Instant,
+}
+
+impl MovementValidator {
+    pub fn new() -> Self {
+        Self {
+            players: HashMap::new(),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Start validating a new player
+    pub fn add_player(&mut self, id: PlayerId) {
+        self.players.insert(
+            id,
+            PlayerValidation {
+                last_valid_pos: None,
+                violations: 0,
+                window_start: Instant::now(),
+            },
+        );
+    }
+
+    /// Stop validating a player
+    pub fn remove_player(&mut self, id: PlayerId) {
+        self.players.remove(&id);
+    }
+
+    /// Forget the last valid position of a player, because the server moved it
+    pub fn reset_position(&mut self, id: PlayerId) {
+        if let Some(validation) = self.players.get_mut(&id) {
+            validation.last_valid_pos = None;
+        }
+    }
+
+    /// Remove the impossible parts of an input: what the game mode doesn't allow, and angles out of range
+    pub fn validate_input(&mut self, id: PlayerId, input: PlayerInput, game_mode: GameMode) -> PlayerInput {
+        let (input, violations) = sanitize_input(input, game_mode);
+        for reason in violations {
+            self.record_violation(id, reason);
+        }
+        input
+    }
+
+    /// Check that the position a client reports with an action is close to the simulated position of the player
+    pub fn check_reported_position(
+        &mut self,
+        id: PlayerId,
+        reported_pos: Vector3<f64>,
+        physics_simulation: &ServerPhysicsSimulation,
+    ) -> bool {
+        let actual_pos = match physics_simulation.get_state().physics_state.players.get(&id) {
+            Some(player) => player.aabb.pos,
+            None => return false,
+        };
+        if (reported_pos - actual_pos).norm() > MAX_REPORTED_POSITION_ERROR {
+            self.record_violation(id, "reported position is too far from the simulated position");
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Snap back the players that moved further than possible since the last check.
+    /// To be called right after the physics step.
+    pub fn check_displacements(&mut self, physics_simulation: &mut ServerPhysicsSimulation, now: Instant) {
+        let seconds_delta = (now - self.last_check).as_secs_f64();
+        self.last_check = now;
+        let max_displacement = MAX_PLAYER_SPEED * seconds_delta + DISPLACEMENT_TOLERANCE;
+
+        let mut offenders = Vec::new();
+        for (&id, validation) in self.players.iter_mut() {
+            let pos = match physics_simulation.get_state().physics_state.players.get(&id) {
+                Some(player) => player.aabb.pos,
+                None => continue,
+            };
+            match validation.last_valid_pos {
+                Some(last_valid_pos) if (pos - last_valid_pos).norm() > max_displacement => {
+                    offenders.push((id, last_valid_pos));
+                }
+                _ => validation.last_valid_pos = Some(pos),
+            }
+        }
+
+        for (id, last_valid_pos) in offenders {
+            if let Some(player) = physics_simulation.get_player_mut(id) {
+                player.aabb.pos = last_valid_pos;
+                player.velocity = Vector3::zeros();
+            }
+            self.record_violation(id, "moved too fast");
+        }
+    }
+
+    /// Count a violation, and report the player if it happens too often
+    fn record_violation(&mut self, id: PlayerId, reason: &str) {
+        if let Some(validation) = self.players.get_mut(&id) {
+            let now = Instant::now();
+            if now - validation.window_start > VIOLATION_WINDOW {
+                validation.window_start = now;
+                validation.violations = 0;
+            }
+            validation.violations += 1;
+            if validation.violations == VIOLATIONS_BEFORE_WARNING {
+                warn!(
+                    "Player {:?} had {} movement violations in the last {} seconds (latest: {})",
+                    id,
+                    validation.violations,
+                    VIOLATION_WINDOW.as_secs(),
+                    reason,
+                );
+            }
+        }
+    }
+}
+
+/// Fix an input, returning the fixed input and the reasons why it was invalid
+fn sanitize_input(mut input: PlayerInput, game_mode: GameMode) -> (PlayerInput, Vec<&'static str>) {
+    let mut violations = Vec::new();
+    if input.flying && !game_mode.can_fly() {
+        violations.push("flying is not allowed");
+    }
+    if input.no_clip && game_mode != GameMode::Spectator {
+        violations.push("going through blocks is not allowed");
+    }
+    game_mode.adapt_input(&mut input);
+    let yaw_pitch = &mut input.yaw_pitch;
+    if !yaw_pitch.yaw.is_finite() || !yaw_pitch.pitch.is_finite() {
+        *yaw_pitch = Default::default();
+        violations.push("invalid yaw or pitch");
+    } else if yaw_pitch.pitch.abs() > 90.0 {
+        yaw_pitch.pitch = yaw_pitch.pitch.clamp(-90.0, 90.0);
+        violations.push("pitch out of range");
+    }
+    (input, violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flying_is_removed_when_not_allowed() {
+        let input = PlayerInput { flying: true, ..Default::default() };
+        let (sanitized, violations) = sanitize_input(input, GameMode::Survival);
+        assert!(!sanitized.flying);
+        assert_eq!(violations.len(), 1);
+        let (sanitized, violations) = sanitize_input(input, GameMode::Creative);
+        assert!(sanitized.flying);
+        assert!(violations.is_empty());
+        // Spectators always fly through the blocks
+        let (sanitized, violations) = sanitize_input(PlayerInput::default(), GameMode::Spectator);
+        assert!(sanitized.flying && sanitized.no_clip);
+        assert!(violations.is_empty());
+        let no_clip = PlayerInput { no_clip: true, ..Default::default() };
+        let (sanitized, violations) = sanitize_input(no_clip, GameMode::Creative);
+        assert!(!sanitized.no_clip);
+        assert_eq!(violations.len(), 1);
+    }
+
+    #[test]
+    fn invalid_angles_are_fixed() {
+        let mut input = PlayerInput::default();
+        input.yaw_pitch.pitch = 180.0;
+        assert_eq!(sanitize_input(input, GameMode::Creative).0.yaw_pitch.pitch, 90.0);
+        input.yaw_pitch.yaw = f64::NAN;
+        assert!(sanitize_input(input, GameMode::Creative).0.yaw_pitch.yaw.is_finite());
+    }
+}
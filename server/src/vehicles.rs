@@ -0,0 +1,123 @@
+//! Server-side vehicle management: spawning, mounting and dismounting.
+use crate::world::World;
+use history_survival_common::{
+    debug::send_debug_info,
+    entity::{EntityId, EntityKind, VehicleKind},
+    physics::aabb::AABB,
+    physics::entity::PhysicsEntity,
+    physics::simulation::ServerPhysicsSimulation,
+    physics::vehicle::MOUNT_RANGE,
+    physics::BlockContainer,
+    player::PlayerId,
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+
+/// How far down from the world spawn the ground is searched for the first vehicles
+const GROUND_SEARCH_DEPTH: i64 = 64;
+
+/// The vehicles of the world
+pub struct Vehicles {
+    /// Whether the vehicles near the world spawn were spawned
+    spawned_initial_vehicles: bool,
+}
+
+impl Vehicles {
+    pub fn new() -> Self {
+        Self {
+            spawned_initial_vehicles: false,
+        }
+    }
+
+    /// Spawn a boat and a minecart next to the world spawn, once the ground there is generated
+    // TODO: craft and place vehicles once there is an inventory
+    pub fn update(&mut self, world: &World, world_spawn: BlockPos, physics_simulation: &mut ServerPhysicsSimulation) {
+        if !self.spawned_initial_vehicles {
+            let boat_ground = find_ground(world, world_spawn.offset(3, 0, 0));
+            let minecart_ground = find_ground(world, world_spawn.offset(-3, 0, 0));
+            if let (Some(boat_ground), Some(minecart_ground)) = (boat_ground, minecart_ground) {
+                spawn_vehicle(VehicleKind::Boat, boat_ground, physics_simulation);
+                spawn_vehicle(VehicleKind::Minecart, minecart_ground, physics_simulation);
+                self.spawned_initial_vehicles = true;
+            }
+        }
+
+        let physics_state = &physics_simulation.get_state().physics_state;
+        let num_vehicles = physics_state
+            .entities
+            .values()
+            .filter(|entity| matches!(entity.kind, EntityKind::Vehicle(_)))
+            .count();
+        let num_riders = physics_state.players.values().filter(|player| player.vehicle.is_some()).count();
+        send_debug_info(
+            "Mobs",
+            "vehicles",
+            format!("Vehicles = {}, riders = {}", num_vehicles, num_riders),
+        );
+    }
+
+    /// Make a player ride a vehicle if it is close enough and no one else rides it. Return whether the player mounted the vehicle.
+    pub fn mount(&mut self, player_id: PlayerId, vehicle_id: EntityId, physics_simulation: &mut ServerPhysicsSimulation) -> bool {
+        let physics_state = &physics_simulation.get_state().physics_state;
+        let (player, vehicle) = match (physics_state.players.get(&player_id), physics_state.entities.get(&vehicle_id)) {
+            (Some(player), Some(vehicle)) => (player, vehicle),
+            _ => return false,
+        };
+        let is_vehicle = matches!(vehicle.kind, EntityKind::Vehicle(_));
+        let is_close = (vehicle.get_center() - player.get_camera_position()).norm() <= MOUNT_RANGE + vehicle.aabb.size_x;
+        let is_taken = physics_state.players.values().any(|player| player.vehicle == Some(vehicle_id));
+        if !is_vehicle || !is_close || is_taken || player.vehicle.is_some() {
+            return false;
+        }
+        // The rider is moved to the seat during the next physics step
+        physics_simulation.get_player_mut(player_id).unwrap().vehicle = Some(vehicle_id);
+        true
+    }
+
+    /// Make a player stop riding its vehicle, and put it on top of the vehicle if there is room.
+    /// Return whether the player was riding a vehicle.
+    pub fn dismount(&mut self, player_id: PlayerId, world: &World, physics_simulation: &mut ServerPhysicsSimulation) -> bool {
+        let vehicle_top = {
+            let physics_state = &physics_simulation.get_state().physics_state;
+            match physics_state.players.get(&player_id).and_then(|player| player.vehicle) {
+                Some(vehicle_id) => physics_state
+                    .entities
+                    .get(&vehicle_id)
+                    .map(|vehicle| vehicle.get_feet_position() + Vector3::new(0.0, vehicle.aabb.size_y, 0.0)),
+                None => return false,
+            }
+        };
+        let player = physics_simulation.get_player_mut(player_id).unwrap();
+        player.vehicle = None;
+        if let Some(vehicle_top) = vehicle_top {
+            let half_side = player.aabb.size_x / 2.0;
+            let new_aabb = AABB::new(
+                vehicle_top - Vector3::new(half_side, 0.0, half_side),
+                (player.aabb.size_x, player.aabb.size_y, player.aabb.size_z),
+            );
+            if !new_aabb.intersect_world(world) {
+                player.aabb = new_aabb;
+            }
+        }
+        true
+    }
+}
+
+/// Spawn a vehicle with the center of its bottom at `feet`
+pub fn spawn_vehicle(kind: VehicleKind, feet: Vector3<f64>, physics_simulation: &mut ServerPhysicsSimulation) -> EntityId {
+    let size = kind.size();
+    let pos = feet - Vector3::new(size.0 / 2.0, 0.0, size.2 / 2.0);
+    physics_simulation.spawn_entity(PhysicsEntity::new(EntityKind::Vehicle(kind), pos, size))
+}
+
+/// Find the top of the first full block below `pos`, if the chunks down to it are loaded
+fn find_ground(world: &World, pos: BlockPos) -> Option<Vector3<f64>> {
+    for dy in 0..GROUND_SEARCH_DEPTH {
+        let below = pos.offset(0, -dy, 0);
+        world.get_chunk(below.containing_chunk_pos())?;
+        if world.is_block_full(below) {
+            return Some(Vector3::new(below.px as f64 + 0.5, below.py as f64 + 1.0, below.pz as f64 + 0.5));
+        }
+    }
+    None
+}
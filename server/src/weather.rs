@@ -0,0 +1,49 @@
+//! Server-side weather changes.
+use history_survival_common::weather::Weather;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Minimum and maximum time between two weather changes, in seconds
+const MIN_WEATHER_DURATION: u64 = 5 * 60;
+const MAX_WEATHER_DURATION: u64 = 15 * 60;
+
+/// Decides when the weather changes, and to what
+pub struct WeatherCycle {
+    current: Weather,
+    next_change: Instant,
+}
+
+impl WeatherCycle {
+    pub fn new() -> Self {
+        Self {
+            current: Weather::Clear,
+            next_change: Instant::now() + random_duration(),
+        }
+    }
+
+    /// Get the current weather
+    pub fn current(&self) -> Weather {
+        self.current
+    }
+
+    /// Change the weather if it's time to. Return the new weather if it changed.
+    pub fn update(&mut self, now: Instant) -> Option<Weather> {
+        if now < self.next_change {
+            return None;
+        }
+        self.next_change = now + random_duration();
+        let mut rng = rand::thread_rng();
+        // TODO: choose between rain and snow depending on the biome
+        let new_weather = match self.current {
+            Weather::Clear if rng.gen_bool(0.75) => Weather::Rain,
+            Weather::Clear => Weather::Snow,
+            Weather::Rain | Weather::Snow => Weather::Clear,
+        };
+        self.current = new_weather;
+        Some(new_weather)
+    }
+}
+
+fn random_duration() -> Duration {
+    Duration::from_secs(rand::thread_rng().gen_range(MIN_WEATHER_DURATION..=MAX_WEATHER_DURATION))
+}
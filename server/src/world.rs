@@ -1,26 +1,38 @@
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
+    time::{Duration, Instant},
 };
 use history_survival_common::{
-    block::{Block, BlockId},
+    block::{entity::BlockEntity, get_climbable_blocks, Block, BlockId},
+    debug::send_debug_info,
+    dimension::DimensionType,
     player::RenderDistance,
     physics::BlockContainer,
     registry::Registry,
     world::{
-        Chunk, ChunkPos, ChunkPosXZ,
+        Chunk, ChunkPos, ChunkPosXZ, CHUNK_SIZE,
         BlockPos,
         LightChunk,
+        PendingTick,
+        TickPriority,
         WorldGenerator,
     },
 };
 use crate::{
     light::HighestOpaqueBlock,
     light::worker::{ChunkLightingData, ChunkLightingWorker, start_lighting_worker},
+    chunk_encoding::{ChunkEncoder, EncodedChunk},
+    scheduled_ticks::{DueTick, ScheduledTicks},
+    world_save::WorldSaver,
     worldgen::{WorldGenerationWorker, start_worldgen_worker},
 };
+use log::{info, warn};
 use lazy_static::lazy_static;
 
+/// Time between two checks of whether the save finished, while the server waits for it before stopping
+const SAVE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 lazy_static! {
     static ref EMPTY_HOB: Arc<HighestOpaqueBlock> = {
         Arc::new(HighestOpaqueBlock::new())
@@ -32,6 +44,8 @@ lazy_static! {
 /// * storing chunk data
 /// * generating the chunks
 /// * updating the lighting
+/// * scheduling the block ticks
+/// * saving the modified chunks
 pub struct World {
     /// The chunks
     chunks: HashMap<ChunkPos, ServerChunk>,
@@ -45,23 +59,44 @@ pub struct World {
     worldgen_worker: WorldGenerationWorker,
     /// The light worker
     light_worker: ChunkLightingWorker,
+    /// The rules of the dimension of this world
+    dimension: DimensionType,
+    /// For every block id, whether the block is climbable
+    climbable_blocks: Vec<bool>,
+    /// The modified chunks that must be saved
+    saver: WorldSaver,
+    /// The compressed chunks that are sent to the players
+    encoder: ChunkEncoder,
+    /// The scheduled block ticks of the loaded chunks
+    scheduled_ticks: ScheduledTicks,
 }
 
 impl World {
     pub fn new(
         block_registry: Registry<Block>,
-        world_generator: Box<dyn WorldGenerator + Send>
+        world_generator: Box<dyn WorldGenerator + Send>,
+        dimension: DimensionType,
     ) -> Self {
         Self {
             chunks: HashMap::default(),
             chunk_columns: HashMap::default(),
             next_chunk_version: 0,
             worldgen_queue: HashSet::default(),
+            climbable_blocks: get_climbable_blocks(&block_registry),
+            saver: WorldSaver::new(&block_registry),
+            encoder: ChunkEncoder::new(),
+            scheduled_ticks: ScheduledTicks::new(),
             worldgen_worker: start_worldgen_worker(block_registry, world_generator),
             light_worker: start_lighting_worker(),
+            dimension,
         }
     }
 
+    /// Return the rules of the dimension of this world
+    pub fn get_dimension(&self) -> &DimensionType {
+        &self.dimension
+    }
+
     /// Return some chunk if is loaded
     pub fn get_chunk(&self, pos: ChunkPos) -> Option<Arc<Chunk>> {
         self.chunks.get(&pos).map(|server_chunk| server_chunk.chunk.clone())
@@ -75,6 +110,34 @@ impl World {
         }
     }
 
+    /// Return the block entity at position `pos` in the world, if its chunk is loaded
+    pub fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        self.chunks
+            .get(&pos.containing_chunk_pos())
+            .and_then(|server_chunk| server_chunk.chunk.get_block_entity(pos.pos_in_containing_chunk()))
+    }
+
+    /// Return the highest block of the column at (x, z) in the loaded chunks, with its height
+    pub fn get_highest_block(&self, x: i64, z: i64) -> Option<(i64, BlockId)> {
+        let size = CHUNK_SIZE as i64;
+        let column = self.chunk_columns.get(&ChunkPosXZ::from((x.div_euclid(size), z.div_euclid(size))))?;
+        let y = column.highest_opaque_block.y[(x.rem_euclid(size) * size + z.rem_euclid(size)) as usize];
+        if y == i64::MIN {
+            return None;
+        }
+        Some((y, self.get_block(BlockPos { px: x, py: y, pz: z })))
+    }
+
+    /// Return the light level at position `pos` in the world, never below the ambient light floor of the dimension.
+    /// The ambient light floor is returned if the chunk does not exists/is not loaded
+    pub fn get_light(&self, pos: BlockPos) -> u8 {
+        let light = match self.chunks.get(&pos.containing_chunk_pos()) {
+            None => 0,
+            Some(server_chunk) => server_chunk.light_chunk.get_light_at(pos.pos_in_containing_chunk()),
+        };
+        light.max(self.dimension.ambient_light_floor)
+    }
+
     /// Update the highest opaque block in the column, and mark relevant chunks for a light update.
     /// To be called after every chunk loading or modification.
     fn update_chunk_column(&mut self, pos: ChunkPos) {
@@ -111,9 +174,20 @@ impl World {
         }
     }
 
-    /// Set the chunk at some position
+    /// Set the chunk at some position, and remember to save it
     pub fn set_chunk(&mut self, chunk: Arc<Chunk>) {
+        self.saver.mark_dirty(chunk.pos);
+        self.insert_chunk(chunk);
+    }
+
+    /// Load a chunk that doesn't need to be saved
+    fn insert_chunk(&mut self, mut chunk: Arc<Chunk>) {
         let pos = chunk.pos;
+        // The ticks of a chunk that is loaded again are scheduled again
+        if !chunk.pending_ticks.is_empty() {
+            let pending_ticks = std::mem::take(&mut Arc::make_mut(&mut chunk).pending_ticks);
+            self.scheduled_ticks.load_chunk(pos, &pending_ticks, |pos| chunk.get_block_at(pos));
+        }
         let server_chunk = self.chunks.entry(pos).or_insert_with(|| {
             ServerChunk { 
                 chunk: chunk.clone(),
@@ -147,7 +221,7 @@ impl World {
         // TODO: if there are multiple chunks in the same column this may save time
         while let Some(chunk) = self.worldgen_worker.get_result() {
             self.worldgen_queue.remove(&chunk.pos);
-            self.set_chunk(Arc::new(chunk));
+            self.insert_chunk(Arc::new(chunk));
         }
     }
 
@@ -163,6 +237,11 @@ impl World {
         }
     }
 
+    /// Fetch the new compressed chunks from the encoding workers
+    pub fn get_new_encoded_chunks(&mut self) {
+        self.encoder.get_new_encoded_chunks();
+    }
+
     /// Start the lighting of a few chunks
     pub fn enqueue_chunks_for_lighting(&mut self, player_close_chunks: &[ChunkPos]) {
         for pos in player_close_chunks {
@@ -218,14 +297,9 @@ impl World {
     pub fn enqueue_chunks_for_worldgen(&mut self, player_close_chunks: &[ChunkPos]) {
         for pos in player_close_chunks {
             if !self.chunks.contains_key(pos) && !self.worldgen_queue.contains(pos) {
-                let res = self.worldgen_worker.enqueue(*pos);
-                match res {
-                    // If the worldgen queue is not full, update chunk status
-                    Ok(()) => {
-                        self.worldgen_queue.insert(*pos);
-                    },
-                    // If the worldgen queue is full, stop
-                    Err(_) => break,
+                // If the worldgen queue is full, stop
+                if !self.request_chunk(*pos) {
+                    break;
                 }
             }
         }
@@ -244,10 +318,31 @@ impl World {
         }
     }
 
-    /// Unload chunk
-    // TODO: persist to disk
+    /// Load a chunk that is not saved yet from memory, or start loading or generating it.
+    /// Return false if the worldgen queue is full.
+    fn request_chunk(&mut self, pos: ChunkPos) -> bool {
+        if let Some(chunk) = self.saver.take_unsaved_chunk(pos) {
+            self.insert_chunk(chunk);
+            return true;
+        }
+        match self.worldgen_worker.enqueue(pos) {
+            Ok(()) => {
+                self.worldgen_queue.insert(pos);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Unload chunk, keeping it until it is saved if it was modified
     fn unload_chunk(&mut self, pos: ChunkPos) {
-        self.chunks.remove(&pos);
+        if let Some(server_chunk) = self.chunks.remove(&pos) {
+            let pending_ticks = self.scheduled_ticks.unload_chunk(pos);
+            if !pending_ticks.is_empty() {
+                self.saver.mark_dirty(pos);
+            }
+            self.saver.chunk_unloaded(with_pending_ticks(server_chunk.chunk, pending_ticks));
+        }
         let column_pos = ChunkPosXZ::from(pos);
         let col = self.chunk_columns.get_mut(&column_pos).expect("No chunk column");
         col.loaded_chunks.remove(&pos);
@@ -257,36 +352,132 @@ impl World {
         }
     }
 
+    /// Whether the chunk at some position is generated or loaded
+    pub fn is_chunk_loaded(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
     /// Get chunks to send to a player this frame, and update the `PlayerData` accordingly. Start generating some chunks if necessary
-    pub fn send_chunks_to_player(&mut self, player_chunk: ChunkPos, data: &mut super::PlayerData) -> Vec<(Arc<Chunk>, Arc<LightChunk>)>{
+    pub fn send_chunks_to_player(&mut self, player_chunk: ChunkPos, data: &mut super::PlayerData) -> Vec<EncodedChunk> {
         const MAX_CHUNKS: usize = 20;
+        // Don't send more chunks than the player can handle
+        let max_chunks = MAX_CHUNKS.min(data.chunk_delivery.remaining_capacity());
         let mut updates = Vec::new();
+        if max_chunks == 0 {
+            return updates;
+        }
         for pos in data.close_chunks.get_close_chunks() {
             let pos = pos.offset_by_pos(player_chunk);
             if let Some(server_chunk) = self.chunks.get(&pos) {
-                // Send the chunk to the player
-                let loaded = data.loaded_chunks.insert(pos, server_chunk.version);
-                if let Some(old_client_version) = loaded {
-                    if old_client_version < server_chunk.version {
-                        updates.push((server_chunk.chunk.clone(), server_chunk.light_chunk.clone()));
+                // Send the chunk to the player, once it is compressed
+                let is_outdated = data
+                    .loaded_chunks
+                    .get(&pos)
+                    .map(|&client_version| client_version < server_chunk.version)
+                    .unwrap_or(true);
+                if is_outdated {
+                    let encoded = self.encoder.get_encoded(&server_chunk.chunk, &server_chunk.light_chunk, server_chunk.version);
+                    if let Some(encoded) = encoded {
+                        data.loaded_chunks.insert(pos, server_chunk.version);
+                        updates.push(encoded);
                     }
-                } else {
-                    updates.push((server_chunk.chunk.clone(), server_chunk.light_chunk.clone()));
                 }
-                if updates.len() == MAX_CHUNKS {
+                if updates.len() == max_chunks {
                     break
                 }
-            } else {
-                // Generate the chunk
-                let res = self.worldgen_worker.enqueue(pos);
-                if res.is_ok() {
-                    self.worldgen_queue.insert(pos);
-                }
+            } else if !self.worldgen_queue.contains(&pos) {
+                // Load or generate the chunk
+                self.request_chunk(pos);
             }
         }
         updates
     }
 
+    /// Start saving a snapshot of the modified chunks in the background, unless the previous save is not finished.
+    /// Return whether a save was started.
+    pub fn start_save(&mut self) -> bool {
+        let chunks = &self.chunks;
+        let scheduled_ticks = &self.scheduled_ticks;
+        self.saver.start_save(|pos| {
+            chunks
+                .get(&pos)
+                .map(|server_chunk| with_pending_ticks(server_chunk.chunk.clone(), scheduled_ticks.chunk_ticks(pos)))
+        })
+    }
+
+    /// Schedule a tick for the block at some position in `delay` ticks, if its chunk is loaded.
+    /// Return false if the chunk is not loaded or if the position already has a scheduled tick.
+    pub fn schedule_tick(&mut self, pos: BlockPos, delay: u64, priority: TickPriority) -> bool {
+        let block = match self.chunks.get(&pos.containing_chunk_pos()) {
+            Some(server_chunk) => server_chunk.chunk.get_block_at(pos.pos_in_containing_chunk()),
+            None => return false,
+        };
+        // The scheduled ticks are saved with the chunk
+        self.saver.mark_dirty(pos.containing_chunk_pos());
+        self.scheduled_ticks.schedule(pos, block, delay, priority)
+    }
+
+    /// Take the scheduled ticks that are due, in the order they must run.
+    /// The ticks of the blocks that were replaced since they scheduled them are dropped.
+    pub fn take_due_ticks(&mut self, now: Instant) -> Vec<DueTick> {
+        let due_ticks = self.scheduled_ticks.take_due(now);
+        for tick in due_ticks.iter() {
+            self.saver.mark_dirty(tick.pos.containing_chunk_pos());
+        }
+        due_ticks.into_iter().filter(|tick| self.get_block(tick.pos) == tick.block).collect()
+    }
+
+    /// Save the modified chunks and wait until they are written, before the server stops
+    pub fn save_and_wait(&mut self) {
+        // The save that is running doesn't have the chunks modified since it started
+        self.wait_for_save();
+        if self.start_save() {
+            self.wait_for_save();
+        }
+    }
+
+    fn wait_for_save(&mut self) {
+        while self.saver.is_saving() {
+            self.update_save();
+            std::thread::sleep(SAVE_POLL_INTERVAL);
+        }
+    }
+
+    /// Check whether the save finished
+    pub fn update_save(&mut self) {
+        match self.saver.get_save_result() {
+            Some(Ok(num_chunks)) => info!("Saved {} chunks", num_chunks),
+            Some(Err(e)) => warn!("Failed to save the world: {:?}", e),
+            None => {}
+        }
+        send_debug_info("Chunks", "save", self.saver.describe());
+    }
+
+    /// The content hash of a loaded chunk, if its current version is `version`
+    pub fn get_chunk_hash(&self, pos: ChunkPos, version: u64) -> Option<u64> {
+        self.chunks
+            .get(&pos)
+            .filter(|server_chunk| server_chunk.version == version)
+            .map(|server_chunk| server_chunk.chunk.content_hash())
+    }
+
+    /// The loaded chunks
+    pub fn loaded_chunks(&self) -> impl Iterator<Item = &Arc<Chunk>> + '_ {
+        self.chunks.values().map(|server_chunk| &server_chunk.chunk)
+    }
+
+    /// The block entities of the loaded chunks, with their position
+    pub fn loaded_block_entities(&self) -> impl Iterator<Item = (BlockPos, &BlockEntity)> + '_ {
+        self.chunks.values().flat_map(|server_chunk| {
+            let chunk_pos = server_chunk.chunk.pos;
+            server_chunk
+                .chunk
+                .block_entities
+                .iter()
+                .map(move |(pos, block_entity)| (chunk_pos.block_pos(*pos), block_entity))
+        })
+    }
+
     /// Number of loaded chunks
     pub fn num_loaded_chunks(&self) -> usize {
         self.chunks.len()
@@ -296,6 +487,19 @@ impl World {
     pub fn num_loaded_chunk_columns(&self) -> usize {
         self.chunk_columns.len()
     }
+
+    /// Number of scheduled block ticks in the loaded chunks
+    pub fn num_scheduled_ticks(&self) -> usize {
+        self.scheduled_ticks.len()
+    }
+}
+
+/// A copy of a chunk that keeps its scheduled ticks, to save it
+fn with_pending_ticks(mut chunk: Arc<Chunk>, pending_ticks: Vec<PendingTick>) -> Arc<Chunk> {
+    if !pending_ticks.is_empty() || !chunk.pending_ticks.is_empty() {
+        Arc::make_mut(&mut chunk).pending_ticks = pending_ticks;
+    }
+    chunk
 }
 
 impl BlockContainer for World {
@@ -306,6 +510,16 @@ impl BlockContainer for World {
             Some(chunk) => chunk.chunk.get_block_at(pos.pos_in_containing_chunk()) != 0,
         }
     }
+
+    fn is_block_climbable(&self, pos: BlockPos) -> bool {
+        match self.chunks.get(&pos.containing_chunk_pos()) {
+            None => false,
+            Some(chunk) => {
+                let block = chunk.chunk.get_block_at(pos.pos_in_containing_chunk());
+                self.climbable_blocks.get(block as usize).cloned().unwrap_or(false)
+            }
+        }
+    }
 }
 
 /// The data for each chunk stored by the server
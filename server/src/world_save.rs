@@ -0,0 +1,341 @@
+//! Saving the modified chunks of the world while the game keeps running.
+//!
+//! The chunks are never mutated once they are shared: modifying a chunk replaces its `Arc` with a modified copy.
+//! A snapshot of the world is therefore only a list of `Arc`s collected during a single tick, and the save worker
+//! can write it while the tick loop keeps modifying the world, without locks and without pausing the game.
+use anyhow::{Context, Result};
+use history_survival_common::{
+    block::{entity::BlockEntity, Block, BlockId},
+    registry::Registry,
+    world::{BlockPos, Chunk, ChunkPos, CompressedChunk, PendingTick, WorldGenerator, CHUNK_SIZE},
+    worker::{Worker, WorkerState},
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The directory where the chunks are saved
+const CHUNKS_DIRECTORY: &str = "saves/world/chunks";
+/// The directory of the override pack bundled with the world, loaded on top of the data
+pub const OVERRIDES_DIRECTORY: &str = "saves/world/overrides";
+/// The file where the spawn point of the world is saved
+const SPAWN_FILE: &str = "saves/world/spawn.ron";
+
+/// A chunk as it is written to the disk.
+/// Blocks are saved by name, because the block ids depend on the loading order.
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkFile {
+    /// The names of the blocks used in the chunk
+    palette: Vec<String>,
+    /// RLE-compressed blocks: (count, index in the palette)
+    data: Vec<(u16, u16)>,
+    /// The block entities, with their position in the chunk
+    #[serde(default)]
+    block_entities: Vec<((u32, u32, u32), BlockEntity)>,
+    /// The scheduled block ticks, in the order they run
+    #[serde(default)]
+    pending_ticks: Vec<PendingTick>,
+}
+
+impl ChunkFile {
+    fn from_chunk(chunk: &Chunk, block_names: &[String]) -> Self {
+        let compressed = CompressedChunk::from_chunk(chunk);
+        let mut palette_ids: Vec<BlockId> = Vec::new();
+        let mut data = Vec::with_capacity(compressed.data.len());
+        for (count, block) in compressed.data {
+            let index = match palette_ids.iter().position(|&id| id == block) {
+                Some(index) => index,
+                None => {
+                    palette_ids.push(block);
+                    palette_ids.len() - 1
+                }
+            };
+            data.push((count, index as u16));
+        }
+        let palette = palette_ids
+            .into_iter()
+            .map(|id| block_names.get(id as usize).cloned().unwrap_or_else(|| "air".to_owned()))
+            .collect();
+        Self {
+            palette,
+            data,
+            block_entities: compressed.block_entities,
+            pending_ticks: chunk.pending_ticks.clone(),
+        }
+    }
+
+    /// Recover the chunk. Unknown blocks are replaced by air, and the block entities that don't belong to their block are dropped.
+    fn to_chunk(&self, pos: ChunkPos, block_registry: &Registry<Block>) -> Chunk {
+        let palette_ids = self
+            .palette
+            .iter()
+            .map(|name| block_registry.get_id_by_name(name).unwrap_or(0) as BlockId)
+            .collect::<Vec<_>>();
+        let data = self
+            .data
+            .iter()
+            .map(|&(count, index)| (count, palette_ids.get(index as usize).cloned().unwrap_or(0)))
+            .collect();
+        let mut chunk = CompressedChunk {
+            pos,
+            data,
+            block_entities: Vec::new(),
+            biomes: Vec::new(),
+        }
+        .to_chunk();
+        for (block_pos, block_entity) in self.block_entities.iter() {
+            let (x, y, z) = *block_pos;
+            if x >= CHUNK_SIZE || y >= CHUNK_SIZE || z >= CHUNK_SIZE {
+                continue;
+            }
+            let accepts = block_registry
+                .get_value_by_id(chunk.get_block_at(*block_pos) as u32)
+                .and_then(Block::get_block_entity_kind)
+                .map(|kind| kind.accepts(block_entity))
+                .unwrap_or(false);
+            if accepts {
+                chunk.set_block_entity(*block_pos, Some(block_entity.clone()));
+            }
+        }
+        chunk.pending_ticks = self
+            .pending_ticks
+            .iter()
+            .filter(|tick| tick.pos.0 < CHUNK_SIZE && tick.pos.1 < CHUNK_SIZE && tick.pos.2 < CHUNK_SIZE)
+            .copied()
+            .collect();
+        chunk
+    }
+}
+
+fn chunk_file_path(pos: ChunkPos) -> PathBuf {
+    PathBuf::from(CHUNKS_DIRECTORY).join(format!("{}_{}_{}.ron", pos.px, pos.py, pos.pz))
+}
+
+/// Load a saved chunk. Return `None` if the chunk was never saved.
+pub fn load_chunk(pos: ChunkPos, block_registry: &Registry<Block>) -> Result<Option<Chunk>> {
+    let path = chunk_file_path(pos);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let string = std::fs::read_to_string(&path).context(format!("Failed to read chunk file {}", path.display()))?;
+    let file: ChunkFile = ron::de::from_str(&string).context(format!("Failed to parse chunk file {}", path.display()))?;
+    Ok(Some(file.to_chunk(pos, block_registry)))
+}
+
+/// Save a chunk. It is written to a temporary file first, so that a chunk that is loaded during the save
+/// is never read half-written.
+fn save_chunk(chunk: &Chunk, block_names: &[String]) -> Result<()> {
+    let path = chunk_file_path(chunk.pos);
+    let temporary_path = path.with_extension("ron.tmp");
+    let string = ron::ser::to_string(&ChunkFile::from_chunk(chunk, block_names)).context("Failed to serialize chunk file")?;
+    std::fs::write(&temporary_path, string).context(format!("Failed to write chunk file {}", temporary_path.display()))?;
+    std::fs::rename(&temporary_path, &path).context(format!("Failed to move chunk file to {}", path.display()))?;
+    Ok(())
+}
+
+/// Save some chunks while the server is not running, for example the chunks imported from another game.
+/// `block_names` are the names of the blocks by id in the chunks. The chunks that were already saved are replaced.
+pub fn save_chunks<'a>(chunks: impl Iterator<Item = &'a Chunk>, block_names: &[String]) -> Result<usize> {
+    std::fs::create_dir_all(CHUNKS_DIRECTORY).context(format!("Failed to create directory {}", CHUNKS_DIRECTORY))?;
+    let mut count = 0;
+    for chunk in chunks {
+        save_chunk(chunk, block_names)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn load_spawn_point() -> Result<Option<BlockPos>> {
+    let path = PathBuf::from(SPAWN_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let string = std::fs::read_to_string(&path).context(format!("Failed to read spawn file {}", path.display()))?;
+    let (px, py, pz) = ron::de::from_str(&string).context(format!("Failed to parse spawn file {}", path.display()))?;
+    Ok(Some(BlockPos { px, py, pz }))
+}
+
+fn save_spawn_point(spawn: BlockPos) -> Result<()> {
+    let string = ron::ser::to_string(&(spawn.px, spawn.py, spawn.pz)).context("Failed to serialize spawn file")?;
+    std::fs::create_dir_all("saves/world").context("Failed to create directory saves/world")?;
+    std::fs::write(SPAWN_FILE, string).context(format!("Failed to write spawn file {}", SPAWN_FILE))?;
+    Ok(())
+}
+
+/// The spawn point of the world. It is chosen by the generator the first time the world is loaded, and then saved.
+pub fn load_or_choose_spawn_point(world_generator: &mut dyn WorldGenerator) -> BlockPos {
+    match load_spawn_point() {
+        Ok(Some(spawn)) => return spawn,
+        Ok(None) => {}
+        Err(e) => warn!("Failed to load the spawn point, choosing it again: {:?}", e),
+    }
+    let spawn = world_generator.find_spawn_point();
+    info!("The spawn point of the world is {} {} {}", spawn.px, spawn.py, spawn.pz);
+    if let Err(e) = save_spawn_point(spawn) {
+        warn!("Failed to save the spawn point: {:?}", e);
+    }
+    spawn
+}
+
+/// The state of the world at some tick, as far as saving is concerned
+pub struct WorldSnapshot {
+    pub chunks: Vec<Arc<Chunk>>,
+}
+
+pub struct SaveState {
+    /// The names of the blocks, by id
+    block_names: Vec<String>,
+}
+
+impl WorkerState<WorldSnapshot, Result<usize>> for SaveState {
+    fn compute(&mut self, snapshot: WorldSnapshot) -> Result<usize> {
+        save_chunks(snapshot.chunks.iter().map(|chunk| &**chunk), &self.block_names)
+    }
+}
+
+pub type SaveWorker = Worker<WorldSnapshot, Result<usize>, SaveState>;
+
+/// Keeps track of the chunks that must be saved, and of the chunks that are being saved
+pub struct WorldSaver {
+    worker: SaveWorker,
+    /// The loaded chunks that were modified since the last snapshot
+    dirty_chunks: HashSet<ChunkPos>,
+    /// The chunks that were modified and unloaded since the last snapshot
+    unloaded_chunks: HashMap<ChunkPos, Arc<Chunk>>,
+    /// The chunks of the snapshot that is being written
+    saving_chunks: HashMap<ChunkPos, Arc<Chunk>>,
+}
+
+impl WorldSaver {
+    pub fn new(block_registry: &Registry<Block>) -> Self {
+        let block_names = (0..block_registry.get_number_of_ids())
+            .map(|id| block_registry.get_value_by_id(id).unwrap().name.clone())
+            .collect();
+        Self {
+            worker: Worker::new(SaveState { block_names }, 1, "World save".into()),
+            dirty_chunks: HashSet::new(),
+            unloaded_chunks: HashMap::new(),
+            saving_chunks: HashMap::new(),
+        }
+    }
+
+    /// Remember that a loaded chunk was modified
+    pub fn mark_dirty(&mut self, pos: ChunkPos) {
+        self.dirty_chunks.insert(pos);
+    }
+
+    /// Keep a chunk that is unloaded until it is saved, if it was modified
+    pub fn chunk_unloaded(&mut self, chunk: Arc<Chunk>) {
+        if self.dirty_chunks.remove(&chunk.pos) {
+            self.unloaded_chunks.insert(chunk.pos, chunk);
+        }
+    }
+
+    /// Get a chunk that is not saved on the disk yet, so that it is loaded from memory and not from the outdated file.
+    /// The returned chunk must be loaded into the world again.
+    pub fn take_unsaved_chunk(&mut self, pos: ChunkPos) -> Option<Arc<Chunk>> {
+        if let Some(chunk) = self.unloaded_chunks.remove(&pos) {
+            self.dirty_chunks.insert(pos);
+            Some(chunk)
+        } else {
+            self.saving_chunks.get(&pos).cloned()
+        }
+    }
+
+    /// Whether a snapshot is being written
+    pub fn is_saving(&self) -> bool {
+        !self.saving_chunks.is_empty()
+    }
+
+    /// Start writing a snapshot of the modified chunks. `get_chunk` must return the current version of the loaded chunks.
+    /// Return whether a save was started.
+    pub fn start_save(&mut self, get_chunk: impl Fn(ChunkPos) -> Option<Arc<Chunk>>) -> bool {
+        if self.is_saving() || (self.dirty_chunks.is_empty() && self.unloaded_chunks.is_empty()) {
+            return false;
+        }
+        // The loaded version of a chunk is always more recent than its unloaded version
+        self.saving_chunks = self.unloaded_chunks.drain().collect();
+        for pos in self.dirty_chunks.drain() {
+            if let Some(chunk) = get_chunk(pos) {
+                self.saving_chunks.insert(pos, chunk);
+            }
+        }
+        let chunks = self.saving_chunks.values().cloned().collect();
+        match self.worker.enqueue(WorldSnapshot { chunks }) {
+            Ok(()) => true,
+            Err(snapshot) => {
+                // Try again during the next save
+                self.saving_chunks.clear();
+                self.unloaded_chunks.extend(snapshot.chunks.into_iter().map(|chunk| (chunk.pos, chunk)));
+                false
+            }
+        }
+    }
+
+    /// Check whether the snapshot was written. Return the result of the save if it finished.
+    pub fn get_save_result(&mut self) -> Option<Result<usize>> {
+        let result = self.worker.get_result()?;
+        let saved_chunks = std::mem::take(&mut self.saving_chunks);
+        if result.is_err() {
+            // Keep the chunks that were not written for the next save
+            for (pos, chunk) in saved_chunks {
+                if !self.dirty_chunks.contains(&pos) {
+                    self.unloaded_chunks.insert(pos, chunk);
+                }
+            }
+        }
+        Some(result)
+    }
+
+    /// Describe the state of the saver for the debug info
+    pub fn describe(&self) -> String {
+        format!(
+            "Modified chunks = {}, unloaded unsaved chunks = {}, chunks being saved = {}",
+            self.dirty_chunks.len(),
+            self.unloaded_chunks.len(),
+            self.saving_chunks.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_file_round_trip() {
+        let mut block_registry = Registry::default();
+        for name in ["air", "stone", "dirt"].iter() {
+            let block = Block {
+                name: name.to_string(),
+                block_type: history_survival_common::block::BlockType::Air,
+            };
+            block_registry.register(name.to_string(), block).unwrap();
+        }
+        let block_names = vec!["air".to_owned(), "stone".to_owned(), "dirt".to_owned()];
+        let pos = ChunkPos::from([1, -2, 3]);
+        let mut chunk = Chunk::new(pos);
+        chunk.set_block_at((0, 0, 0), 1);
+        chunk.set_block_at((5, 7, 2), 2);
+        chunk.set_block_at((5, 7, 3), 1);
+        // Stone can't hold a banner
+        let banner = BlockEntity::Banner(Default::default());
+        chunk.set_block_entity((0, 0, 0), Some(banner));
+        chunk.pending_ticks = vec![
+            PendingTick { pos: (5, 7, 2), delay: 3, priority: 0 },
+            PendingTick { pos: (40, 0, 0), delay: 5, priority: 0 },
+        ];
+
+        let file = ChunkFile::from_chunk(&chunk, &block_names);
+        assert_eq!(file.palette, vec!["stone".to_owned(), "air".to_owned(), "dirt".to_owned()]);
+        let loaded = file.to_chunk(pos, &block_registry);
+        assert_eq!(loaded.pos, pos);
+        assert_eq!(loaded.data, chunk.data);
+        assert_eq!(file.block_entities.len(), 1);
+        assert!(loaded.block_entities.is_empty());
+        // Ticks outside of the chunk are dropped
+        assert_eq!(loaded.pending_ticks, chunk.pending_ticks[..1].to_vec());
+    }
+}
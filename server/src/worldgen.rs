@@ -4,6 +4,8 @@ use history_survival_common::{
     world::{Chunk, ChunkPos, WorldGenerator},
 };
 use history_survival_common::worker::{WorkerState, Worker};
+use log::warn;
+use crate::world_save::load_chunk;
 
 static WORLDGEN_QUEUE_SIZE: usize = 20;
 
@@ -30,7 +32,18 @@ impl WorldGenerationState {
 
 impl WorkerState<ChunkPos, Chunk> for WorldGenerationState {
     fn compute(&mut self, pos: ChunkPos) -> Chunk {
-        self.world_generator.generate_chunk(pos, &self.block_registry)
+        // Load the chunk if it was saved, and generate it otherwise
+        let mut chunk = match load_chunk(pos, &self.block_registry) {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => self.world_generator.generate_chunk(pos, &self.block_registry),
+            Err(e) => {
+                warn!("Failed to load chunk {:?}, generating it again: {:?}", pos, e);
+                self.world_generator.generate_chunk(pos, &self.block_registry)
+            }
+        };
+        // The biomes are not saved, because they only depend on the world generator
+        chunk.biomes = self.world_generator.generate_biomes(pos.into());
+        chunk
     }
 }
 